@@ -0,0 +1,72 @@
+use std::{
+  path::PathBuf,
+  time::Instant,
+};
+
+use hashexpr::{
+  span::Span,
+  Expr,
+};
+use yatima::{
+  core::bench::{
+    allocations,
+    Counting,
+  },
+  parse::package::{
+    parse_package,
+    PackageEnv,
+  },
+  term::Link,
+};
+
+// Parsing packages of more and more definitions, each referring to the one
+// before it. Each declaration is parsed in the package's scope as it is, not
+// a copy of it, so the bytes allocated for a definition stay about the same
+// however many definitions come before it. Run with `cargo bench`.
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+// The source of a package of `n` definitions
+fn package(n: usize) -> String {
+  let mut source = String::from("package Chain where\n");
+  source.push_str("def d0 : #Natural = 0\n");
+  for i in 1..n {
+    source.push_str(&format!("def d{} : #Natural = d{}\n", i, i - 1));
+  }
+  source
+}
+
+fn main() {
+  let mut per_def = Vec::new();
+  for &n in &[250, 500, 1000, 2000] {
+    let source = package(n);
+    let env = PackageEnv::new(PathBuf::from("Chain.ya"));
+    let link = Link::make(&Expr::serialize_text(&source));
+    let (allocs, bytes) = allocations();
+    let start = Instant::now();
+    let res = parse_package(env, link)(Span::new(&source));
+    let time = start.elapsed();
+    let (allocs_after, bytes_after) = allocations();
+    let (_, (_, pack, ..)) = res.expect("the package does not parse");
+    assert_eq!(pack.decls.len(), n);
+    let bytes = bytes_after - bytes;
+    println!(
+      "{} definitions: {:?}, {} allocations, {} bytes allocated",
+      n,
+      time,
+      allocs_after - allocs,
+      bytes
+    );
+    per_def.push(bytes / n as u64);
+  }
+  // A scope copied for each definition would make the last eight times the
+  // first
+  let (first, last) = (per_def[0], per_def[per_def.len() - 1]);
+  assert!(
+    last <= 2 * first,
+    "{} bytes allocated per definition of 2000, {} per definition of 250",
+    last,
+    first
+  );
+}
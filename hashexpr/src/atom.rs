@@ -8,7 +8,10 @@ use num_bigint::{
   BigUint,
   Sign,
 };
-use std::fmt;
+use std::{
+  borrow::Cow,
+  fmt,
+};
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum Atom {
@@ -32,14 +35,17 @@ impl Atom {
     }
   }
 
-  pub fn data_bytes(&self) -> Vec<u8> {
+  pub fn data_bytes(&self) -> Vec<u8> { self.data().into_owned() }
+
+  // The data of the atom, borrowed where it is stored as bytes already
+  pub fn data(&self) -> Cow<[u8]> {
     match self {
-      Self::Link(x) => x.as_bytes().to_vec(),
-      Self::Bits(x) => x.to_owned(),
-      Self::Text(x) => x.to_owned().into_bytes(),
-      Self::Char(x) => (*x as u32).to_be_bytes().to_vec(),
-      Self::Nat(x) => x.to_bytes_be(),
-      Self::Int(x) => x.to_signed_bytes_be(),
+      Self::Link(x) => Cow::Owned(x.as_bytes().to_vec()),
+      Self::Bits(x) => Cow::Borrowed(x),
+      Self::Text(x) => Cow::Borrowed(x.as_bytes()),
+      Self::Char(x) => Cow::Owned((*x as u32).to_be_bytes().to_vec()),
+      Self::Nat(x) => Cow::Owned(x.to_bytes_be()),
+      Self::Int(x) => Cow::Owned(x.to_signed_bytes_be()),
     }
   }
 }
@@ -100,6 +100,23 @@ pub fn pack_u64(x: u64) -> (u8, Vec<u8>) {
   )
 }
 
+// The serialization of an atom with the given type code and data
+fn serialize_atom(type_code: &[u8], data: &[u8]) -> Vec<u8> {
+  let type_len = type_code.len() as u8;
+  let data_len = (data.len() as u64) * 8;
+  let (data_len_len, data_len_bytes) = pack_u64(data_len);
+
+  let size_byte: u8 = type_len - 1 << 3 | data_len_len - 1;
+
+  let len = 1 + type_code.len() + data_len_bytes.len() + data.len();
+  let mut ret = Vec::with_capacity(len);
+  ret.push(size_byte);
+  ret.extend_from_slice(type_code);
+  ret.extend(data_len_bytes);
+  ret.extend_from_slice(data);
+  ret
+}
+
 pub fn bytelen_from_bitlen(bits: u64) -> u64 {
   if bits % 8 == 0 { bits / 8 } else { bits / 8 + 1 }
 }
@@ -125,22 +142,7 @@ impl Expr {
 
   pub fn serialize(&self) -> Vec<u8> {
     match self {
-      Self::Atom(_, atom) => {
-        let type_code = atom.type_code();
-        let data = atom.data_bytes();
-        let type_len = type_code.len() as u8;
-        let data_len = (data.len() as u64) * 8;
-        let (data_len_len, data_len_bytes) = pack_u64(data_len);
-
-        let size_byte: u8 = type_len - 1 << 3 | data_len_len - 1;
-
-        let mut ret = vec![];
-        ret.extend(vec![size_byte]);
-        ret.extend(type_code);
-        ret.extend(data_len_bytes);
-        ret.extend(data);
-        ret
-      }
+      Self::Atom(_, atom) => serialize_atom(&atom.type_code(), &atom.data()),
       Self::Cons(_, xs) => {
         let (xs_count_len, xs_count) = pack_u64(xs.len() as u64);
         let size_byte: u8 = 0b1000_0000 | (xs_count_len - 1);
@@ -156,6 +158,11 @@ impl Expr {
     }
   }
 
+  // The serialization of a text atom, without copying the text into one
+  pub fn serialize_text(x: &str) -> Vec<u8> {
+    serialize_atom(&atom::Atom::Text(String::new()).type_code(), x.as_bytes())
+  }
+
   pub fn link(&self) -> Link { Link::make(&self.serialize()) }
 
   pub fn hash(&self) -> Expr { link!(self.position(), self.link()) }
@@ -504,6 +511,13 @@ pub mod tests {
     }
   }
 
+  #[test]
+  fn serializes_text() {
+    let x = "λ x => x";
+    assert_eq!(Expr::serialize_text(x), text!(String::from(x)).serialize());
+    assert_eq!(Expr::serialize_text(""), text!("").serialize());
+  }
+
   #[test]
   fn expr_test_cases() {
     let a = parse("(())").unwrap().1;
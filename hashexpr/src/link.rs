@@ -75,6 +75,23 @@ impl fmt::Display for Link {
   }
 }
 
+// With serde, a link is its text, as it is displayed and parsed
+impl serde::Serialize for Link {
+  fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&self.to_string())
+  }
+}
+
+impl<'de> serde::Deserialize<'de> for Link {
+  fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    let s = String::deserialize(d)?;
+    match Link::parse(&s) {
+      Ok((i, link)) if i.fragment().is_empty() => Ok(link),
+      _ => Err(serde::de::Error::custom(format!("invalid link {}", s))),
+    }
+  }
+}
+
 #[cfg(test)]
 pub mod tests {
   use super::*;
@@ -138,4 +155,19 @@ pub mod tests {
       _ => false,
     }
   }
+  #[quickcheck]
+  fn link_serde_text(x: Link) -> bool {
+    use serde::{
+      de::{
+        value::{
+          Error,
+          StringDeserializer,
+        },
+        IntoDeserializer,
+      },
+      Deserialize,
+    };
+    let text: StringDeserializer<Error> = format!("{}", x).into_deserializer();
+    <Link as Deserialize>::deserialize(text) == Ok(x)
+  }
 }
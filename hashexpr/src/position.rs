@@ -27,6 +27,39 @@ impl Pos {
   }
 }
 
+// With serde, a position is the tuple of its offsets, lines and columns, in
+// the order of its fields
+impl serde::Serialize for Pos {
+  fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(
+      &(
+        self.from_offset,
+        self.from_line,
+        self.from_column,
+        self.upto_offset,
+        self.upto_line,
+        self.upto_column,
+      ),
+      s,
+    )
+  }
+}
+
+impl<'de> serde::Deserialize<'de> for Pos {
+  fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    let xs: (u64, u64, u64, u64, u64, u64) =
+      serde::Deserialize::deserialize(d)?;
+    Ok(Pos {
+      from_offset: xs.0,
+      from_line: xs.1,
+      from_column: xs.2,
+      upto_offset: xs.3,
+      upto_line: xs.4,
+      upto_column: xs.5,
+    })
+  }
+}
+
 impl fmt::Display for Pos {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(
@@ -0,0 +1,92 @@
+/*
+ * The C interface of Yatima, for embedding it in programs in other languages,
+ * as defined in `src/ffi.rs` and built into the library's `cdylib`.
+ *
+ * Scopes and terms are opaque handles, made by these functions and freed by
+ * their `_free` functions. The strings and bytes returned are owned by the
+ * caller, and are freed by `yatima_string_free` and `yatima_bytes_free`. A
+ * function which fails returns NULL, and points its `error` at a string
+ * saying why, unless `error` is NULL. Strings passed in are UTF-8 ending in
+ * NUL.
+ */
+
+#ifndef YATIMA_H
+#define YATIMA_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The definitions and the names in scope */
+typedef struct YatimaScope YatimaScope;
+
+/* An elaborated term */
+typedef struct YatimaTerm YatimaTerm;
+
+/* The scope of the prelude */
+YatimaScope *yatima_scope_new(void);
+
+void yatima_scope_free(YatimaScope *scope);
+
+/* Parse the source of the package `name`, storing it in the hashspace, into
+ * a new scope, of `scope` and the package's definitions, pointing `link`, if
+ * it is not NULL, at the package's link */
+YatimaScope *yatima_scope_parse(
+  const YatimaScope *scope,
+  const char *name,
+  const char *source,
+  char **link,
+  char **error);
+
+/* Open the package at `link` in the hashspace into a new scope, of `scope`
+ * and the package's definitions */
+YatimaScope *yatima_scope_open(
+  const YatimaScope *scope,
+  const char *link,
+  char **error);
+
+/* Parse and elaborate an expression in `scope` */
+YatimaTerm *yatima_term_parse(
+  const YatimaScope *scope,
+  const char *expr,
+  char **error);
+
+/* The normal form of a term, reduced lazily with at most `fuel` reductions,
+ * or with no limit if `fuel` is 0 */
+YatimaTerm *yatima_term_normalize(
+  const YatimaScope *scope,
+  const YatimaTerm *term,
+  uint64_t fuel,
+  char **error);
+
+/* The type of a term */
+YatimaTerm *yatima_term_check(
+  const YatimaScope *scope,
+  const YatimaTerm *term,
+  char **error);
+
+/* A term, laid out to `width` columns, or on one line if `width` is 0 */
+char *yatima_term_print(const YatimaTerm *term, size_t width, char **error);
+
+void yatima_term_free(YatimaTerm *term);
+
+/* Put the serialized expression of `len` bytes at `data` in the hashspace,
+ * returning its link */
+char *yatima_hashspace_put(const uint8_t *data, size_t len, char **error);
+
+/* The serialized expression at `link` in the hashspace, pointing `len` at its
+ * number of bytes */
+uint8_t *yatima_hashspace_get(const char *link, size_t *len, char **error);
+
+void yatima_string_free(char *s);
+
+void yatima_bytes_free(uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -122,7 +122,7 @@ pub mod tests {
       for _ in 0..n {
         xs.push(arbitrary_anon_term(g, ctx))
       }
-      Ctor(arbitrary_name(g), xs)
+      Ctor(arbitrary_name(g).to_string(), xs)
     })
   }
 
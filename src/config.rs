@@ -0,0 +1,559 @@
+#[cfg(feature = "native")]
+use directories_next::ProjectDirs;
+
+use crate::{
+  global::Global,
+  hashspace::wire::Encoding,
+  warning::{
+    Level,
+    Lint,
+  },
+};
+
+use std::{
+  fmt,
+  fs,
+  iter::Peekable,
+  path::{
+    Path,
+    PathBuf,
+  },
+  str::{
+    Chars,
+    FromStr,
+  },
+};
+
+// Configuration, read from the user's `config.toml`, in the platform's
+// configuration directory, and then from the `yatima.toml` of the project,
+// found in the current directory or the nearest one above it, whose settings
+// override the user's. Command line flags override both. The files are
+// written in the part of TOML needed for this: tables, and keys whose values
+// are strings, integers, booleans or arrays of them. Relative paths in a
+// file are relative to the directory it is in.
+//
+//   [hashspace]
+//   directory = "~/.yatima"     # where the hashspace is kept
+//   remote = "http://host:8000" # the remote to publish to and get from
+//   token = "secret"            # the token the remote allows puts with
+//   encoding = "cbor"           # or "hashexpr", what is sent to a remote
+//
+//   [packages]
+//   search = ["lib"]            # where to look for opened packages
+//
+//   [repl]
+//   edit_mode = "emacs"         # or "vi"
+//   history = "history.txt"
+//   load = ["Prelude.ya"]       # packages loaded on starting
+//
+//   [output]
+//   color = "auto"              # or "always" or "never"
+//   verbosity = "normal"        # or "quiet", "verbose" or "debug"
+//
+//   [warnings]
+//   shadowed-name = "deny"      # or "allow" or "warn", for each lint
+
+// The name of a project's configuration file
+pub const PROJECT_FILE: &str = "yatima.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+  // Only when printing to a terminal, and NO_COLOR is not set
+  Auto,
+  Always,
+  Never,
+}
+
+impl Color {
+  // Whether to color what is printed to a terminal, if `terminal`
+  pub fn enabled(self, terminal: bool) -> bool {
+    match self {
+      Self::Auto => terminal && std::env::var_os("NO_COLOR").is_none(),
+      Self::Always => true,
+      Self::Never => false,
+    }
+  }
+}
+
+impl FromStr for Color {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(Self::Auto),
+      "always" => Ok(Self::Always),
+      "never" => Ok(Self::Never),
+      _ => Err(format!("Unknown color setting {}", s)),
+    }
+  }
+}
+
+// How much is reported of what is done, besides its results
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+  Quiet,
+  Normal,
+  Verbose,
+  // Everything reported at `Verbose`, and the detail of it
+  Debug,
+}
+
+impl FromStr for Verbosity {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "quiet" => Ok(Self::Quiet),
+      "normal" => Ok(Self::Normal),
+      "verbose" => Ok(Self::Verbose),
+      "debug" => Ok(Self::Debug),
+      _ => Err(format!("Unknown verbosity {}", s)),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditMode {
+  Emacs,
+  Vi,
+}
+
+impl FromStr for EditMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "emacs" => Ok(Self::Emacs),
+      "vi" => Ok(Self::Vi),
+      _ => Err(format!("Unknown edit mode {}", s)),
+    }
+  }
+}
+
+// The settings of a configuration file, each unset unless it is given
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+  pub hashspace: Option<PathBuf>,
+  pub remote: Option<String>,
+  pub token: Option<String>,
+  pub encoding: Option<Encoding>,
+  pub search: Vec<PathBuf>,
+  pub edit_mode: Option<EditMode>,
+  pub history: Option<PathBuf>,
+  pub load: Vec<PathBuf>,
+  pub color: Option<Color>,
+  pub verbosity: Option<Verbosity>,
+  // The levels of lints, later ones overriding earlier ones
+  pub warnings: Vec<(Lint, Level)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+  Io(PathBuf, String),
+  // A line of a file which cannot be read as TOML, and why
+  Syntax(PathBuf, usize, String),
+  // A setting whose value is not one it can have
+  Invalid(PathBuf, String, String),
+}
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+      Self::Syntax(path, line, e) => {
+        write!(f, "{}:{}: {}", path.display(), line, e)
+      }
+      Self::Invalid(path, key, e) => {
+        write!(f, "{}: invalid setting {}: {}", path.display(), key, e)
+      }
+    }
+  }
+}
+
+impl std::error::Error for ConfigError {}
+
+// A value in a configuration file
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Str(String),
+  Int(i64),
+  Bool(bool),
+  Arr(Vec<Value>),
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+  while let Some(c) = chars.peek() {
+    match c {
+      ' ' | '\t' | '\r' | '\n' => {
+        chars.next();
+      }
+      // A comment, to the end of its line
+      '#' => {
+        for c in chars.by_ref() {
+          if c == '\n' {
+            break;
+          }
+        }
+      }
+      _ => break,
+    }
+  }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+  match chars.next() {
+    Some('"') => {
+      let mut s = String::new();
+      loop {
+        match chars.next() {
+          Some('"') => return Ok(Value::Str(s)),
+          Some('\\') => match chars.next() {
+            Some('n') => s.push('\n'),
+            Some('t') => s.push('\t'),
+            Some('"') => s.push('"'),
+            Some('\\') => s.push('\\'),
+            c => return Err(format!("Unknown escape {:?}", c)),
+          },
+          Some('\n') | None => return Err(String::from("Unclosed string")),
+          Some(c) => s.push(c),
+        }
+      }
+    }
+    Some('\'') => {
+      let mut s = String::new();
+      loop {
+        match chars.next() {
+          Some('\'') => return Ok(Value::Str(s)),
+          Some('\n') | None => return Err(String::from("Unclosed string")),
+          Some(c) => s.push(c),
+        }
+      }
+    }
+    Some('[') => {
+      let mut xs = Vec::new();
+      loop {
+        skip_space(chars);
+        if chars.peek() == Some(&']') {
+          chars.next();
+          return Ok(Value::Arr(xs));
+        }
+        xs.push(parse_value(chars)?);
+        skip_space(chars);
+        match chars.next() {
+          Some(',') => (),
+          Some(']') => return Ok(Value::Arr(xs)),
+          _ => return Err(String::from("Expected , or ] in array")),
+        }
+      }
+    }
+    Some(c) => {
+      let mut s = String::from(c);
+      while let Some(c) = chars.peek() {
+        if c.is_alphanumeric() || *c == '-' || *c == '_' || *c == '+' {
+          s.push(*c);
+          chars.next();
+        }
+        else {
+          break;
+        }
+      }
+      match s.as_str() {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        s => s
+          .replace('_', "")
+          .parse()
+          .map(Value::Int)
+          .map_err(|_| format!("Invalid value {}", s)),
+      }
+    }
+    None => Err(String::from("Expected a value")),
+  }
+}
+
+fn is_key_char(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+// The settings in the source of a configuration file, each by its key
+// qualified by its table, in order, or the line an error is on and the error
+pub fn parse(source: &str) -> Result<Vec<(String, Value)>, (usize, String)> {
+  let mut settings = Vec::new();
+  let mut table = String::new();
+  let mut chars = source.chars().peekable();
+  loop {
+    skip_space(&mut chars);
+    let read = source.chars().count() - chars.clone().count();
+    let line = source.chars().take(read).filter(|c| *c == '\n').count() + 1;
+    let err = |e: String| (line, e);
+    match chars.peek() {
+      None => return Ok(settings),
+      Some('[') => {
+        chars.next();
+        table = chars.by_ref().take_while(|c| *c != ']').collect();
+        table = String::from(table.trim());
+        if table.is_empty() || !table.chars().all(is_key_char) {
+          return Err(err(format!("Invalid table name [{}]", table)));
+        }
+      }
+      Some(_) => {
+        let mut key = String::new();
+        while let Some(c) = chars.peek() {
+          if is_key_char(*c) {
+            key.push(*c);
+            chars.next();
+          }
+          else {
+            break;
+          }
+        }
+        if key.is_empty() {
+          return Err(err(String::from("Expected a key")));
+        }
+        while let Some(' ') | Some('\t') = chars.peek() {
+          chars.next();
+        }
+        if chars.next() != Some('=') {
+          return Err(err(format!("Expected = after {}", key)));
+        }
+        while let Some(' ') | Some('\t') = chars.peek() {
+          chars.next();
+        }
+        let value = parse_value(&mut chars).map_err(err)?;
+        let key =
+          if table.is_empty() { key } else { format!("{}.{}", table, key) };
+        settings.push((key, value));
+      }
+    }
+  }
+}
+
+// A path in a configuration file in `dir`, with a leading `~` being the
+// home directory
+fn path(dir: &Path, s: &str) -> PathBuf {
+  match (s.strip_prefix("~/"), std::env::var_os("HOME")) {
+    (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+    _ => dir.join(s),
+  }
+}
+
+impl Config {
+  // The settings of the configuration file at `file`, with source `source`
+  pub fn from_source(file: &Path, source: &str) -> Result<Self, ConfigError> {
+    let dir = file.parent().unwrap_or_else(|| Path::new("."));
+    let settings = parse(source)
+      .map_err(|(line, e)| ConfigError::Syntax(file.to_path_buf(), line, e))?;
+    let mut config = Config::default();
+    for (key, value) in settings {
+      let invalid = |e: &str| {
+        ConfigError::Invalid(file.to_path_buf(), key.clone(), String::from(e))
+      };
+      let unparsed = |e: String| invalid(&e);
+      let string = || match &value {
+        Value::Str(s) => Ok(s.clone()),
+        _ => Err(invalid("expected a string")),
+      };
+      let paths = || -> Result<Vec<PathBuf>, ConfigError> {
+        match &value {
+          Value::Arr(xs) => xs
+            .iter()
+            .map(|x| match x {
+              Value::Str(s) => Ok(path(dir, s)),
+              _ => Err(invalid("expected an array of strings")),
+            })
+            .collect(),
+          _ => Err(invalid("expected an array of strings")),
+        }
+      };
+      match key.as_str() {
+        "hashspace.directory" => {
+          config.hashspace = Some(path(dir, &string()?))
+        }
+        "hashspace.remote" => config.remote = Some(string()?),
+        "hashspace.token" => config.token = Some(string()?),
+        "hashspace.encoding" => {
+          config.encoding = Some(string()?.parse().map_err(unparsed)?)
+        }
+        "packages.search" => config.search = paths()?,
+        "repl.edit_mode" => {
+          config.edit_mode = Some(string()?.parse().map_err(unparsed)?)
+        }
+        "repl.history" => config.history = Some(path(dir, &string()?)),
+        "repl.load" => config.load = paths()?,
+        "output.color" => {
+          config.color = Some(string()?.parse().map_err(unparsed)?)
+        }
+        "output.verbosity" => {
+          config.verbosity = Some(string()?.parse().map_err(unparsed)?)
+        }
+        key if key.starts_with("warnings.") => {
+          let lint = key["warnings.".len()..].parse().map_err(unparsed)?;
+          let level = string()?.parse().map_err(unparsed)?;
+          config.warnings.push((lint, level));
+        }
+        _ => return Err(invalid("unknown setting")),
+      }
+    }
+    Ok(config)
+  }
+
+  pub fn from_file(file: &Path) -> Result<Self, ConfigError> {
+    let source = fs::read_to_string(file)
+      .map_err(|e| ConfigError::Io(file.to_path_buf(), e.to_string()))?;
+    Self::from_source(file, &source)
+  }
+
+  // These settings, overridden by those which `other` sets
+  pub fn merge(self, other: Config) -> Config {
+    fn list(xs: Vec<PathBuf>, ys: Vec<PathBuf>) -> Vec<PathBuf> {
+      if ys.is_empty() {
+        xs
+      }
+      else {
+        ys
+      }
+    }
+    let mut warnings = self.warnings;
+    warnings.extend(other.warnings);
+    Config {
+      hashspace: other.hashspace.or(self.hashspace),
+      remote: other.remote.or(self.remote),
+      token: other.token.or(self.token),
+      encoding: other.encoding.or(self.encoding),
+      search: list(self.search, other.search),
+      edit_mode: other.edit_mode.or(self.edit_mode),
+      history: other.history.or(self.history),
+      load: list(self.load, other.load),
+      color: other.color.or(self.color),
+      verbosity: other.verbosity.or(self.verbosity),
+      warnings,
+    }
+  }
+
+  // The settings of the user's configuration file, if there is one, and of
+  // the project's, `project`, or else the nearest one to the current
+  // directory
+  #[cfg(feature = "native")]
+  pub fn load(project: Option<PathBuf>) -> Result<Self, ConfigError> {
+    let mut config = Config::default();
+    if let Some(file) = user_file().filter(|file| file.exists()) {
+      config = config.merge(Self::from_file(&file)?);
+    }
+    let project = project.or_else(|| {
+      std::env::current_dir().ok().and_then(|dir| project_file(&dir))
+    });
+    if let Some(file) = project {
+      config = config.merge(Self::from_file(&file)?);
+    }
+    Ok(config)
+  }
+}
+
+// The user's configuration file
+#[cfg(feature = "native")]
+pub fn user_file() -> Option<PathBuf> {
+  let dirs = ProjectDirs::from("io", "yatima", "yatima")?;
+  Some(dirs.config_dir().join("config.toml"))
+}
+
+// The project configuration file in `dir` or the nearest directory above it
+pub fn project_file(dir: &Path) -> Option<PathBuf> {
+  dir
+    .ancestors()
+    .map(|dir| dir.join(PROJECT_FILE))
+    .find(|file| file.is_file())
+}
+
+static CONFIG: Global<Config> = Global::new();
+
+// The configured verbosity, by default `Normal`
+pub fn verbosity() -> Verbosity { get().verbosity.unwrap_or(Verbosity::Normal) }
+
+// Set the configuration of the process, once, before it is first read
+pub fn set(config: Config) { let _ = CONFIG.set(config); }
+
+// The configuration of the process, with nothing set if none was
+pub fn get() -> &'static Config { CONFIG.get_or_init(Config::default) }
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn parses() {
+    let source = "# A comment\n\
+                  top = 1_000\n\
+                  [repl]\n\
+                  edit_mode = \"vi\" # the mode\n\
+                  load = [\n  'A.ya', # a package\n  \"B.ya\",\n]\n\
+                  on = true\n";
+    assert_eq!(
+      parse(source),
+      Ok(vec![
+        (String::from("top"), Value::Int(1000)),
+        (String::from("repl.edit_mode"), Value::Str(String::from("vi"))),
+        (
+          String::from("repl.load"),
+          Value::Arr(vec![
+            Value::Str(String::from("A.ya")),
+            Value::Str(String::from("B.ya")),
+          ])
+        ),
+        (String::from("repl.on"), Value::Bool(true)),
+      ])
+    );
+    let unclosed = Err((1, String::from("Unclosed string")));
+    assert_eq!(parse("a = \"b\nc = 1"), unclosed);
+    assert_eq!(parse("\n\n= 1"), Err((3, String::from("Expected a key"))));
+  }
+
+  #[test]
+  fn configures() {
+    let file = Path::new("/project/yatima.toml");
+    let user = Config::from_source(
+      Path::new("/home/config.toml"),
+      "[hashspace]\nremote = \"http://a:8000\"\ntoken = \"t\"\n\
+       encoding = \"cbor\"\n\
+       [output]\ncolor = \"never\"\n\
+       [warnings]\nconfusable = \"deny\"\nunused-import = \"allow\"\n\
+       [packages]\nsearch = [\"lib\"]\n",
+    )
+    .unwrap();
+    let project = Config::from_source(
+      file,
+      "[hashspace]\ndirectory = \".hashspace\"\n\
+       [repl]\nedit_mode = \"emacs\"\nload = [\"Main.ya\"]\n\
+       [output]\ncolor = \"always\"\nverbosity = \"quiet\"\n\
+       [warnings]\nunused-import = \"warn\"\n",
+    )
+    .unwrap();
+    let config = user.merge(project);
+    assert_eq!(config.hashspace, Some(PathBuf::from("/project/.hashspace")));
+    assert_eq!(config.remote, Some(String::from("http://a:8000")));
+    assert_eq!(config.token, Some(String::from("t")));
+    assert_eq!(config.encoding, Some(Encoding::Cbor));
+    assert_eq!(config.search, vec![PathBuf::from("/home/lib")]);
+    assert_eq!(config.edit_mode, Some(EditMode::Emacs));
+    assert_eq!(config.load, vec![PathBuf::from("/project/Main.ya")]);
+    assert_eq!(config.color, Some(Color::Always));
+    assert_eq!(config.verbosity, Some(Verbosity::Quiet));
+    assert_eq!(config.warnings, vec![
+      (Lint::Confusable, Level::Deny),
+      (Lint::UnusedImport, Level::Allow),
+      (Lint::UnusedImport, Level::Warn),
+    ]);
+    let invalid = |source: &str| Config::from_source(file, source).unwrap_err();
+    assert_eq!(
+      invalid("[output]\ncolor = \"sometimes\""),
+      ConfigError::Invalid(
+        file.to_path_buf(),
+        String::from("output.color"),
+        String::from("Unknown color setting sometimes")
+      )
+    );
+    assert!(matches!(invalid("colour = 1"), ConfigError::Invalid(..)));
+    let e = invalid("[warnings]\nunused = \"deny\"");
+    let key = String::from("warnings.unused");
+    assert!(matches!(e, ConfigError::Invalid(_, k, _) if k == key));
+  }
+}
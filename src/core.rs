@@ -1,6 +1,29 @@
+pub mod auto;
+#[cfg(feature = "native")]
+pub mod bench;
+pub mod check;
+pub mod codatatype;
+pub mod compile;
 pub mod dag;
+pub mod datatype;
+pub mod derivation;
 pub mod dll;
+pub mod elab;
+pub mod equality;
+pub mod erase;
 pub mod eval;
+pub mod gas;
 pub mod literal;
+pub mod matching;
+pub mod nbe;
+pub mod parallel;
 pub mod primop;
+#[cfg(feature = "native")]
+pub mod profile;
+pub mod record;
+pub mod run;
+pub mod smallstep;
+pub mod stepper;
+pub mod termination;
+pub mod unify;
 pub mod uses;
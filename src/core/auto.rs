@@ -0,0 +1,263 @@
+use crate::{
+  core::{
+    check::{
+      check,
+      synth,
+      CheckError,
+      Ctx,
+    },
+    nbe::{
+      eval,
+      Value,
+    },
+    unify,
+  },
+  name::Name,
+  term::{
+    Defs,
+    Term,
+  },
+};
+
+use im::Vector;
+
+// Type-directed search for a term to fill a hole. A goal which is a function
+// type is filled with a λ, whose body is searched for with the bound variable
+// in scope, and a goal which is a Σ with a pair of terms searched for in turn.
+// Any other goal is filled by applying a head, which is either a variable in
+// scope or a reference to a definition, to as many arguments as make its
+// type match the goal. The arguments start out as placeholders, most of
+// which are solved by the match itself, and the others are searched for in
+// turn with a smaller bound. Heads are tried innermost variable first, then
+// references in order of name, and the first term found is the one kept.
+//
+// The search is bounded by the nesting depth of the arguments it searches
+// for, so it always terminates, but it is not complete: it does not case on
+// the variables in scope, nor does it fill a Σ whose second component
+// constrains its first.
+
+// The name of the hole which elaboration fills by search
+pub const AUTO: &str = "auto";
+
+// The default bound on the nesting depth of searched arguments
+pub const DEPTH: u32 = 3;
+
+// Search for a term of type `goal` in the context `ctx`
+pub fn search(ctx: &Ctx, goal: &Value, depth: u32) -> Option<Term> {
+  match ctx.force(goal.clone()) {
+    Value::All(uses, nam, dom, img) => {
+      let nam = if nam.is_empty() { Name::from("x") } else { nam };
+      let var = Value::Var(nam.clone(), ctx.depth());
+      let ctx2 = ctx.bind(&nam, (*dom).clone()).with_uses(uses);
+      let bod = search(&ctx2, &img.apply(&ctx.defs, var), depth)?;
+      Some(Term::Lam(None, nam, Box::new(bod)))
+    }
+    Value::Sig(_, dom, img) => {
+      let fst = search(ctx, &dom, depth)?;
+      let snd = search(ctx, &img.apply(&ctx.defs, ctx.eval(&fst)), depth)?;
+      Some(Term::Par(None, Box::new((fst, snd))))
+    }
+    // A goal which is yet unknown would be matched by every head
+    goal if unify::flex(&goal).is_some() => None,
+    goal => heads(ctx)
+      .into_iter()
+      .find_map(|(head, typ)| apply(ctx, &head, &typ, &goal, depth)),
+  }
+}
+
+// The heads a goal may be filled with, and their types
+fn heads(ctx: &Ctx) -> Vec<(Term, Value)> {
+  let mut heads = Vec::new();
+  // The definition being checked is bound to a reference to itself, which is
+  // left out so that the search does not fill a goal by recursion
+  let mut this = Vec::new();
+  for (idx, val) in ctx.env.iter().enumerate() {
+    match val {
+      Value::Ref(_, def_link, _) => this.push(*def_link),
+      _ => {
+        let var = Term::Var(None, ctx.names[idx].clone(), idx as u64);
+        heads.push((var, ctx.types[idx].clone()));
+      }
+    }
+  }
+  let mut defs: Vec<_> = ctx
+    .defs
+    .iter()
+    .filter(|(def_link, _)| !this.contains(def_link))
+    .collect();
+  defs.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+  for (def_link, def) in defs {
+    let (_, _, a) = def.clone().embed();
+    let ast_link = a.encode().link();
+    let head = Term::Ref(None, def.name.clone(), *def_link, ast_link);
+    heads.push((head, eval(&ctx.defs, Vector::new(), &def.typ_)));
+  }
+  heads
+}
+
+// The number of arguments a head of type `typ` can be applied to
+fn arity(ctx: &Ctx, typ: &Value) -> usize {
+  match ctx.force(typ.clone()) {
+    Value::All(uses, nam, dom, img) => {
+      let var = Value::Var(nam.clone(), ctx.depth());
+      let ctx2 = ctx.bind(&nam, (*dom).clone()).with_uses(uses);
+      1 + arity(&ctx2, &img.apply(&ctx.defs, var))
+    }
+    _ => 0,
+  }
+}
+
+// Fill a goal by applying `head` to arguments, leaving the metavariables as
+// they were if it cannot be
+fn apply(
+  ctx: &Ctx,
+  head: &Term,
+  typ: &Value,
+  goal: &Value,
+  depth: u32,
+) -> Option<Term> {
+  for args in 0..=arity(ctx, typ) {
+    let saved = ctx.metas.borrow().clone();
+    let fresh = saved.metas.len() as u64;
+    let term = (0..args).fold(head.clone(), |acc, _| {
+      Term::App(None, Box::new((acc, Term::Hol(None, None))))
+    });
+    let term = ctx.insert_metas(&term);
+    if check(ctx, &term, goal).is_ok() && arguments(ctx, fresh, depth) {
+      return Some(ctx.zonk(&term));
+    }
+    *ctx.metas.borrow_mut() = saved;
+  }
+  None
+}
+
+// Search for the arguments left unsolved, those of the metavariables from
+// `fresh` on, in the order they were created
+fn arguments(ctx: &Ctx, fresh: u64, depth: u32) -> bool {
+  let last = ctx.metas.borrow().metas.len() as u64;
+  for idx in fresh..last {
+    let typ = match ctx.metas.borrow().get(idx) {
+      Some(meta) if meta.solution.is_none() => meta.typ.clone(),
+      _ => continue,
+    };
+    let arg = match typ {
+      Some((typ, _)) if depth > 0 => search(ctx, &typ, depth - 1),
+      _ => None,
+    };
+    match arg {
+      Some(arg) if ctx.conv(&ctx.meta_app(idx), &ctx.eval(&arg)) => (),
+      _ => return false,
+    }
+  }
+  true
+}
+
+// Search for a term to fill the hole `?name` of a closed term
+pub fn fill(
+  defs: &Defs,
+  term: &Term,
+  name: &str,
+) -> Result<Option<Term>, CheckError> {
+  let ctx = Ctx::new(defs.clone());
+  let term = ctx.insert_metas(term);
+  synth(&ctx, &term)?;
+  let holes = ctx.holes.borrow().clone();
+  for hole in holes {
+    let typ = match ctx.metas.borrow().get(hole.meta) {
+      Some(meta) if meta.name.as_deref() == Some(name) => meta.typ.clone(),
+      _ => continue,
+    };
+    return Ok(typ.and_then(|(typ, _)| search(&ctx.at(&hole), &typ, DEPTH)));
+  }
+  Ok(None)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      check::CheckErrorKind,
+      elab::elab_term,
+    },
+    parse::{
+      span::Span,
+      term::{
+        parse,
+        parse_expression,
+      },
+    },
+    term::Def,
+  };
+  use im::HashMap;
+
+  fn found(defs: &Defs, goal: &str) -> Option<String> {
+    let ctx = Ctx::new(defs.clone());
+    let (_, goal) = parse(goal).unwrap();
+    let goal = ctx.eval(&goal);
+    search(&ctx, &goal, DEPTH).map(|term| format!("{}", term))
+  }
+
+  #[test]
+  fn searches() {
+    let defs = HashMap::new();
+    assert_eq!(
+      found(&defs, "∀ (A: Type) (x: A) -> A"),
+      Some(String::from("λ A x => x"))
+    );
+    assert_eq!(
+      found(&defs, "∀ (A: Type) (B: Type) (f: ∀ (x: A) -> B) (x: A) -> B"),
+      Some(String::from("λ A B f x => f x"))
+    );
+    assert_eq!(
+      found(&defs, "∀ (A: Type) (x: A) -> Σ (y: A) -> A"),
+      Some(String::from("λ A x => (x, x)"))
+    );
+    assert_eq!(found(&defs, "∀ (A: Type) -> A"), None);
+    assert_eq!(found(&defs, "#Natural"), None);
+  }
+
+  #[test]
+  fn searches_references() {
+    let mut defs = HashMap::new();
+    let refs = HashMap::new();
+    let parse = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      expr(Span::new(i)).unwrap().1
+    };
+    let one = Def::new(
+      None,
+      Name::from("one"),
+      String::new(),
+      parse("#Natural"),
+      parse("1"),
+    );
+    let (d, ..) = one.clone().embed();
+    defs.insert(d.encode().link(), one);
+    assert_eq!(found(&defs, "#Natural"), Some(String::from("one")));
+  }
+
+  #[test]
+  fn fills_holes() {
+    let id = "((λ A x => x) :: ∀ (A: Type) (x: A) -> A)";
+    let (_, term) = parse(&format!("{} _ ?auto", id)).unwrap();
+    assert_eq!(fill(&HashMap::new(), &term, "auto"), Ok(None));
+    let (_, term) =
+      parse("((λ x => ?h) :: ∀ (x: #Natural) -> #Natural)").unwrap();
+    let filled = fill(&HashMap::new(), &term, "h").unwrap();
+    assert_eq!(filled.map(|term| format!("{}", term)), Some(String::from("x")));
+    let nat = "(∀ (x: #Natural) -> #Natural)";
+    let (_, term) = parse(&format!("{} {} ?auto", id, nat)).unwrap();
+    let term = elab_term(&HashMap::new(), &term).unwrap();
+    assert_eq!(
+      format!("{}", term),
+      "((λ A x => x) :: (∀ (A: Type) (x: A) -> A)) \
+       (∀ (x: #Natural) -> #Natural) (λ x => x)"
+    );
+    let (_, term) = parse(&format!("{} #Text ?auto", id)).unwrap();
+    match elab_term(&HashMap::new(), &term).map_err(|e| e.kind) {
+      Err(CheckErrorKind::UnfilledHoles(_)) => (),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+}
@@ -0,0 +1,200 @@
+use crate::{
+  core::run::{
+    run,
+    Stats,
+    Strategy,
+  },
+  term::{
+    Defs,
+    Term,
+  },
+};
+
+use std::{
+  alloc::{
+    GlobalAlloc,
+    Layout,
+    System,
+  },
+  fmt,
+  sync::atomic::{
+    AtomicU64,
+    Ordering,
+  },
+  time::{
+    Duration,
+    Instant,
+  },
+};
+
+// Benchmarking normalization, so that changes to the evaluator can be
+// measured on real code. A term is run to its normal form a number of times
+// by each strategy, after some runs to warm up, and each run's time,
+// reductions and allocations are recorded. Allocations are only counted
+// when the program's global allocator is `Counting`, as the command line's
+// is when built with the `count-allocations` feature; otherwise they are
+// reported as none.
+
+// The system allocator, counting the allocations made through it
+pub struct Counting;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn realloc(
+    &self,
+    ptr: *mut u8,
+    layout: Layout,
+    size: usize,
+  ) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    ALLOCATED.fetch_add(size as u64, Ordering::Relaxed);
+    System.realloc(ptr, layout, size)
+  }
+}
+
+// The number of allocations made so far, and the bytes they allocated
+pub fn allocations() -> (u64, u64) {
+  (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED.load(Ordering::Relaxed))
+}
+
+// A run of a benchmark
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+  pub time: Duration,
+  pub reductions: u64,
+  pub allocations: u64,
+  pub bytes: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Bench {
+  pub name: String,
+  pub strategy: Strategy,
+  pub samples: Vec<Sample>,
+  // Why the term could not be run, if it could not be
+  pub error: Option<String>,
+}
+
+impl Bench {
+  pub fn min(&self) -> Duration {
+    self.samples.iter().map(|s| s.time).min().unwrap_or_default()
+  }
+
+  pub fn max(&self) -> Duration {
+    self.samples.iter().map(|s| s.time).max().unwrap_or_default()
+  }
+
+  pub fn mean(&self) -> Duration {
+    let total: Duration = self.samples.iter().map(|s| s.time).sum();
+    total / (self.samples.len().max(1) as u32)
+  }
+
+  // The mean of something counted in each run
+  pub fn per_run(&self, count: impl Fn(&Sample) -> u64) -> u64 {
+    let total: u64 = self.samples.iter().map(count).sum();
+    total / (self.samples.len().max(1) as u64)
+  }
+}
+
+impl fmt::Display for Bench {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} ({})", self.name, self.strategy)?;
+    if let Some(e) = &self.error {
+      return write!(f, ": {}", e);
+    }
+    write!(
+      f,
+      ": {} runs, mean {:?}, min {:?}, max {:?}\n  \
+       {} reductions, {} allocations, {} bytes allocated per run",
+      self.samples.len(),
+      self.mean(),
+      self.min(),
+      self.max(),
+      self.per_run(|s| s.reductions),
+      self.per_run(|s| s.allocations),
+      self.per_run(|s| s.bytes),
+    )
+  }
+}
+
+// Run `term`, named `name`, to its normal form by `strategy`, `warmup` times
+// and then `runs` times, recording each of the latter, each run limited to
+// `fuel` reductions
+pub fn bench(
+  defs: &Defs,
+  name: &str,
+  term: &Term,
+  strategy: Strategy,
+  warmup: usize,
+  runs: usize,
+  fuel: Option<u64>,
+) -> Bench {
+  let mut bench = Bench {
+    name: String::from(name),
+    strategy,
+    samples: Vec::new(),
+    error: None,
+  };
+  for i in 0..warmup + runs {
+    let mut stats = Stats::new(fuel);
+    let (allocs, bytes) = allocations();
+    let start = Instant::now();
+    let res = run(defs, term, strategy, &mut stats);
+    let time = start.elapsed();
+    let (allocs_after, bytes_after) = allocations();
+    if let Err(e) = res {
+      bench.error = Some(e.to_string());
+      bench.samples.clear();
+      return bench;
+    }
+    if i >= warmup {
+      bench.samples.push(Sample {
+        time,
+        reductions: stats.total(),
+        allocations: allocs_after - allocs,
+        bytes: bytes_after - bytes,
+      });
+    }
+  }
+  bench
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::parse::term::parse;
+  use im::HashMap;
+
+  #[test]
+  fn benches() {
+    let (_, term) = parse("(λ s z => s (s z)) (λ x => x) Type").unwrap();
+    for &strategy in &[Strategy::Lazy, Strategy::Step] {
+      let bench = bench(&HashMap::new(), "two", &term, strategy, 1, 3, None);
+      assert_eq!(bench.error, None);
+      assert_eq!(bench.samples.len(), 3);
+      assert!(bench.samples.iter().all(|s| s.reductions == 4));
+      assert_eq!(bench.per_run(|s| s.reductions), 4);
+      assert!(bench.min() <= bench.mean() && bench.mean() <= bench.max());
+      assert!(format!("{}", bench).contains("3 runs"));
+    }
+    let defs = HashMap::new();
+    let out = bench(&defs, "two", &term, Strategy::Lazy, 0, 2, Some(1));
+    assert!(out.samples.is_empty());
+    assert_eq!(
+      format!("{}", out),
+      "two (lazy): Evaluation ran out of gas (limit 1)"
+    );
+  }
+}
@@ -0,0 +1,1509 @@
+use crate::{
+  core::{
+    auto,
+    datatype::Datatype,
+    derivation::{
+      Derivation,
+      Judgment,
+      Recorder,
+    },
+    literal::LitType,
+    nbe::{
+      self,
+      eval,
+      Closure,
+      Env,
+      Value,
+    },
+    primop::{
+      type_of_op,
+      PrimOp,
+    },
+    unify::{
+      self,
+      Metas,
+    },
+    uses::Uses,
+  },
+  explain::Code,
+  name::Name,
+  print::{
+    self,
+    Options,
+  },
+  term::{
+    Def,
+    Defs,
+    Link,
+    Term,
+  },
+};
+
+use hashexpr::position::Pos;
+
+use im::{
+  HashMap,
+  Vector,
+};
+
+use std::{
+  cell::RefCell,
+  fmt,
+  rc::Rc,
+};
+
+#[cfg(feature = "native")]
+use std::time::Instant;
+
+// Bidirectional type checking for the core calculus. Terms are either checked
+// against a known type or have their type synthesized: lambdas, data and
+// pairs are only checked, while variables, references, applications,
+// projections and annotations synthesize. An annotation is how a term which
+// can only be checked is switched into synthesis mode.
+//
+// Types are represented as values of the normalization-by-evaluation
+// normalizer, and two types are equal when they are convertible: when their
+// normal forms agree up to renaming and eta.
+//
+// Universes form a predicative, cumulative hierarchy: `Type@n : Type@(n+1)`,
+// the level of a ∀ or self type is the greatest level of its components, and
+// a type in a universe also lives in every universe above it. Cumulativity
+// extends to function types covariantly in their image.
+//
+// `Prop` is the universe of propositions. It lives in `Type`, and is below
+// it, so that a proposition is also a type. It is impredicative: a ∀ is a
+// proposition when its image is one, whatever its domain, and a Σ is one when
+// both of its components are. Proofs are irrelevant, so arguments which are
+// proofs are not compared by conversion.
+//
+// A placeholder `_` stands for a term to be inferred. It is replaced with a
+// metavariable before checking, which is solved by unification when types
+// are compared. A placeholder checked against a type takes on that type,
+// while one in synthesis mode gets a type which is itself a metavariable.
+// A variable bound with 0 uses is erased: it may only appear in irrelevant
+// positions, which are types and the arguments of erased binders, so that
+// it can be removed from the term at runtime.
+//
+// A named hole `?name` is a placeholder whose goal is reported: checking
+// fails with its expected type, the variables in scope and the references
+// that have the expected type. The hole `?auto` is instead filled by a
+// bounded search for a term of its type, and only reported when none is found.
+
+// The universe a type lives in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sort {
+  Prop,
+  Type(u64),
+}
+
+impl Sort {
+  // The level of the universe, `Prop` being at the lowest one
+  pub fn level(self) -> u64 {
+    match self {
+      Self::Prop => 0,
+      Self::Type(lvl) => lvl,
+    }
+  }
+
+  pub fn value(self) -> Value {
+    match self {
+      Self::Prop => Value::Prp,
+      Self::Type(lvl) => Value::Typ(lvl),
+    }
+  }
+}
+
+// The direction in which a term was being typed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+  Check,
+  Synth,
+}
+
+impl fmt::Display for Mode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Check => write!(f, "checking"),
+      Self::Synth => write!(f, "synthesizing"),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CheckErrorKind {
+  UnboundVariable(String, u64),
+  // An erased variable used in a relevant position
+  ErasedVariable(String),
+  UndefinedReference(String, Link),
+  // A term checked against a type, and the different type it synthesized
+  TypeMismatch(Term, Term, Term),
+  // A lambda checked against a type that is not a function type
+  LamNotAFunction(Term),
+  // An application whose head has the given non-function type
+  NotAFunction(Term),
+  // A `data` checked against a type that is not a self type
+  DatNotASelf(Term),
+  // A `case` of a term with the given non-self type
+  CseNotASelf(Term),
+  // A pair checked against a type that is not a Σ type
+  ParNotASigma(Term),
+  // A projection of a term with the given non-Σ type
+  PrjNotASigma(Term),
+  // A term which can only be checked, found in synthesis mode
+  CannotSynth(Term),
+  // A term used as a type, and its type, which is not a universe
+  NotAType(Term, Term),
+  InvalidPrimOp(PrimOp, Vec<Term>),
+  // A placeholder whose metavariable was left unsolved, and its type
+  CouldNotInfer(Option<Term>),
+  // The goals of the named holes of a term
+  UnfilledHoles(Vec<Goal>),
+  // A term whose type could not be compared with the expected one before
+  // the fuel for unfolding definitions ran out
+  OutOfFuel(Term),
+}
+
+impl CheckErrorKind {
+  pub fn code(&self) -> Code {
+    match self {
+      Self::UnboundVariable(..) => Code(101),
+      Self::ErasedVariable(..) => Code(102),
+      Self::UndefinedReference(..) => Code(103),
+      Self::TypeMismatch(..) => Code(104),
+      Self::LamNotAFunction(..) => Code(105),
+      Self::NotAFunction(..) => Code(106),
+      Self::DatNotASelf(..) => Code(107),
+      Self::CseNotASelf(..) => Code(108),
+      Self::ParNotASigma(..) => Code(109),
+      Self::PrjNotASigma(..) => Code(110),
+      Self::CannotSynth(..) => Code(111),
+      Self::NotAType(..) => Code(112),
+      Self::InvalidPrimOp(..) => Code(113),
+      Self::CouldNotInfer(..) => Code(114),
+      Self::UnfilledHoles(..) => Code(115),
+      Self::OutOfFuel(..) => Code(116),
+    }
+  }
+
+  // The direction in which the failing term was being typed
+  pub fn mode(&self) -> Mode {
+    match self {
+      Self::TypeMismatch(..) => Mode::Check,
+      Self::LamNotAFunction(..) => Mode::Check,
+      Self::DatNotASelf(..) => Mode::Check,
+      Self::ParNotASigma(..) => Mode::Check,
+      Self::CouldNotInfer(..) => Mode::Check,
+      Self::UnfilledHoles(..) => Mode::Check,
+      Self::OutOfFuel(..) => Mode::Check,
+      _ => Mode::Synth,
+    }
+  }
+}
+
+// Render two types for comparison from the point where they differ, eliding
+// a long common prefix, so that a mismatch deep inside a large type stands
+// out
+pub fn elide(expected: &str, found: &str) -> (String, String) {
+  const MIN_ELIDED: usize = 16;
+  let common = expected
+    .char_indices()
+    .zip(found.chars())
+    .find(|((_, x), y)| x != y)
+    .map_or(expected.len().min(found.len()), |((idx, _), _)| idx);
+  // Cut the prefix at a space, so that no token is split
+  match expected[..common].rfind(' ') {
+    Some(cut) if cut >= MIN_ELIDED && expected != found => (
+      format!("...{}", &expected[cut..]),
+      format!("...{}", &found[cut..]),
+    ),
+    _ => (String::from(expected), String::from(found)),
+  }
+}
+
+// A term as it is shown in a message
+fn show(term: &Term) -> String { print::pretty(term, &Options::message()) }
+
+impl fmt::Display for CheckErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnboundVariable(nam, idx) => {
+        write!(f, "Unbound variable {} (index {})", nam, idx)
+      }
+      Self::ErasedVariable(nam) => write!(
+        f,
+        "The erased variable {} cannot be used outside of types and erased \
+         arguments",
+        nam
+      ),
+      Self::UndefinedReference(nam, link) => {
+        write!(f, "Undefined reference {} ({})", nam, link)
+      }
+      Self::TypeMismatch(term, expected, found) => {
+        let (expected, found) = elide(&show(expected), &show(found));
+        write!(
+          f,
+          "Type mismatch in {}\n  expected:    {}\n  synthesized: {}",
+          show(term),
+          expected,
+          found
+        )
+      }
+      Self::LamNotAFunction(typ) => {
+        write!(
+          f,
+          "A lambda was checked against the non-function type {}",
+          show(typ)
+        )
+      }
+      Self::NotAFunction(typ) => {
+        write!(f, "Cannot apply a term of the non-function type {}", show(typ))
+      }
+      Self::DatNotASelf(typ) => {
+        write!(
+          f,
+          "A data term was checked against the non-self type {}",
+          show(typ)
+        )
+      }
+      Self::CseNotASelf(typ) => {
+        write!(f, "Cannot case on a term of the non-self type {}", show(typ))
+      }
+      Self::ParNotASigma(typ) => {
+        write!(f, "A pair was checked against the non-Σ type {}", show(typ))
+      }
+      Self::PrjNotASigma(typ) => {
+        write!(
+          f,
+          "Cannot project out of a term of the non-Σ type {}",
+          show(typ)
+        )
+      }
+      Self::CannotSynth(term) => {
+        let reason = match term {
+          Term::Lam(..) => "a lambda can only be checked against a ∀ type",
+          Term::Dat(..) => "data can only be checked against a self type",
+          Term::Par(..) => "a pair can only be checked against a Σ type",
+          Term::Hol(..) | Term::Met(..) => "it is an unelaborated placeholder",
+          _ => "a primitive operation must be applied to all its arguments",
+        };
+        write!(f, "Cannot synthesize the type of {}: {}", show(term), reason)?;
+        write!(f, ", try annotating it with `::`")
+      }
+      Self::NotAType(term, typ) => {
+        write!(
+          f,
+          "{} is used as a type, but has the type {}",
+          show(term),
+          show(typ)
+        )
+      }
+      Self::InvalidPrimOp(opr, args) => {
+        write!(f, "{} cannot be applied to arguments of types", opr)?;
+        for arg in args {
+          write!(f, " {}", show(arg))?;
+        }
+        Ok(())
+      }
+      Self::CouldNotInfer(typ) => {
+        write!(f, "Could not infer the placeholder `_`")?;
+        match typ {
+          Some(typ) => write!(f, " of type {}", show(typ)),
+          None => Ok(()),
+        }
+      }
+      Self::UnfilledHoles(goals) => {
+        write!(f, "Unfilled holes")?;
+        for goal in goals {
+          write!(f, "\n{}", goal)?;
+        }
+        Ok(())
+      }
+      Self::OutOfFuel(term) => write!(
+        f,
+        "Ran out of fuel comparing the type of {} with the expected one, \
+         try making some of the definitions it unfolds opaque",
+        show(term)
+      ),
+    }
+  }
+}
+
+// A type error, at the position of the offending subterm, together with the
+// variables in scope there and their types, outermost first, and the other
+// places in the source which it is because of, with why
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckError {
+  pub pos: Option<Pos>,
+  pub ctx: Vec<(String, Term)>,
+  pub kind: CheckErrorKind,
+  pub related: Vec<(Pos, String)>,
+}
+
+impl CheckError {
+  pub fn new(pos: Option<Pos>, kind: CheckErrorKind) -> Self {
+    CheckError { pos, ctx: Vec::new(), kind, related: Vec::new() }
+  }
+
+  // Locate an error without a position at `pos`, that of the nearest
+  // enclosing term which has one
+  pub fn or_pos(self, pos: Option<Pos>) -> Self {
+    CheckError { pos: self.pos.or(pos), ..self }
+  }
+
+  pub fn mode(&self) -> Mode { self.kind.mode() }
+}
+
+impl fmt::Display for CheckError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(pos) = self.pos {
+      write!(f, "{}: ", pos)?;
+    }
+    write!(f, "Error {}: {}", self.mode(), self.kind)?;
+    if !self.ctx.is_empty() {
+      write!(f, "\nIn the context:")?;
+      for (nam, typ) in &self.ctx {
+        let nam = if nam.is_empty() { "_" } else { nam };
+        write!(f, "\n  {}: {}", nam, show(typ))?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for CheckError {}
+
+// The goal of a named hole: its expected type, the variables in scope with
+// their types, outermost first, and the references of the expected type
+#[derive(Clone, Debug, PartialEq)]
+pub struct Goal {
+  pub pos: Option<Pos>,
+  pub name: String,
+  pub typ: Term,
+  pub ctx: Vec<(String, Term)>,
+  pub candidates: Vec<String>,
+}
+
+impl fmt::Display for Goal {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "?{}: {}", self.name, show(&self.typ))?;
+    for (nam, typ) in &self.ctx {
+      let nam = if nam.is_empty() { "_" } else { nam };
+      write!(f, "\n  {}: {}", nam, show(typ))?;
+    }
+    if !self.candidates.is_empty() {
+      write!(f, "\n  candidates: {}", self.candidates.join(", "))?;
+    }
+    Ok(())
+  }
+}
+
+// A named hole met during checking, with the context it was met in
+#[derive(Clone, Debug)]
+pub struct Hole {
+  pub meta: u64,
+  pub names: Vector<Name>,
+  pub env: Env,
+  pub types: Vector<Value>,
+  pub erased: Vector<bool>,
+  pub relevant: bool,
+}
+
+// The typing context: the names, values and types of the variables in
+// scope, indexed by de Bruijn index, whether each is erased, whether the
+// term being checked is in a relevant position, and the metavariables, named
+// holes and derivations being recorded, if any, shared by all the contexts of
+// a term being checked
+#[derive(Clone, Debug)]
+pub struct Ctx {
+  pub defs: Defs,
+  pub names: Vector<Name>,
+  pub env: Env,
+  pub types: Vector<Value>,
+  pub erased: Vector<bool>,
+  pub relevant: bool,
+  pub metas: Rc<RefCell<Metas>>,
+  pub holes: Rc<RefCell<Vec<Hole>>>,
+  pub recorder: Option<Rc<RefCell<Recorder>>>,
+}
+
+impl Ctx {
+  pub fn new(defs: Defs) -> Self {
+    Ctx {
+      defs,
+      names: Vector::new(),
+      env: Vector::new(),
+      types: Vector::new(),
+      erased: Vector::new(),
+      relevant: true,
+      metas: Rc::new(RefCell::new(Metas::new())),
+      holes: Rc::new(RefCell::new(Vec::new())),
+      recorder: None,
+    }
+  }
+
+  // Record the derivations of the judgments established in this context
+  pub fn recording(self) -> Self {
+    let recorder = Some(Rc::new(RefCell::new(Recorder::new())));
+    Ctx { recorder, ..self }
+  }
+
+  // Stop recording, returning the derivations of the judgments established
+  // outside of any other
+  pub fn derivations(&self) -> Vec<Derivation> {
+    match &self.recorder {
+      Some(recorder) => recorder.borrow_mut().finish(),
+      None => Vec::new(),
+    }
+  }
+
+  // Start establishing a judgment
+  fn enter(&self) {
+    if let Some(recorder) = &self.recorder {
+      recorder.borrow_mut().enter();
+    }
+  }
+
+  // Finish establishing a judgment, which is only computed when recording
+  fn leave(&self, judgment: impl FnOnce() -> Option<Judgment>) {
+    if let Some(recorder) = &self.recorder {
+      let judgment = judgment();
+      recorder.borrow_mut().leave(judgment);
+    }
+  }
+
+  // Limit the number of transparent definitions unfolded while checking
+  pub fn with_fuel(self, fuel: u64) -> Self {
+    self.metas.borrow().fuel.set(fuel);
+    self
+  }
+
+  pub fn depth(&self) -> u64 { self.env.len() as u64 }
+
+  // Extend the context with a variable of the given value and type
+  pub fn define(&self, nam: &str, val: Value, typ: Value) -> Self {
+    let mut ctx = self.clone();
+    ctx.names.push_front(Name::from(nam));
+    ctx.erased.push_front(false);
+    ctx.env.push_front(val);
+    ctx.types.push_front(typ);
+    ctx
+  }
+
+  // Extend the context with an opaque variable of the given type
+  pub fn bind(&self, nam: &str, typ: Value) -> Self {
+    self.define(nam, Value::Var(Name::from(nam), self.depth()), typ)
+  }
+
+  // Erase the innermost variable, if it is bound with 0 uses
+  pub fn with_uses(mut self, uses: Uses) -> Self {
+    if let Some(erased) = self.erased.front_mut() {
+      *erased = uses == Uses::None;
+    }
+    self
+  }
+
+  // The context of an irrelevant position, where erased variables may occur
+  pub fn irrelevant(&self) -> Self { Ctx { relevant: false, ..self.clone() } }
+
+  pub fn eval(&self, term: &Term) -> Value {
+    eval(&self.defs, self.env.clone(), term)
+  }
+
+  pub fn force(&self, val: Value) -> Value {
+    unify::force(&self.defs, &self.metas.borrow(), val)
+  }
+
+  // Read a value back into a term for display, without unfolding references
+  pub fn quote(&self, val: &Value) -> Term {
+    unify::quote(&HashMap::new(), &self.metas.borrow(), self.depth(), val)
+  }
+
+  pub fn conv(&self, a: &Value, b: &Value) -> bool {
+    self.enter();
+    let res = {
+      let mut metas = self.metas.borrow_mut();
+      unify::unify(&self.defs, &mut metas, &levels(&self.types), a, b)
+    };
+    self.leave(|| res.then(|| Judgment::Conv(self.quote(a), self.quote(b))));
+    res
+  }
+
+  pub fn sub(&self, a: &Value, b: &Value) -> bool {
+    self.enter();
+    let res = {
+      let mut metas = self.metas.borrow_mut();
+      sub(&self.defs, &mut metas, &levels(&self.types), a, b)
+    };
+    self.leave(|| res.then(|| Judgment::Sub(self.quote(a), self.quote(b))));
+    res
+  }
+
+  // Whether a type is a proposition, whose proofs are irrelevant
+  pub fn is_prop(&self, typ: &Value) -> bool {
+    let metas = self.metas.borrow();
+    unify::is_prop(&self.defs, &metas, &levels(&self.types), typ)
+  }
+
+  // Replace the placeholders of a term in this context with metavariables
+  pub fn insert_metas(&self, term: &Term) -> Term {
+    let ctx = self
+      .env
+      .iter()
+      .map(|val| match val {
+        Value::Var(nam, _) => (nam.clone(), true),
+        _ => (Name::default(), false),
+      })
+      .collect();
+    self.metas.borrow_mut().insert(&ctx, term)
+  }
+
+  // The bound variables in scope, outermost first
+  fn vars(&self) -> Vec<Value> {
+    self
+      .env
+      .iter()
+      .rev()
+      .filter(|val| matches!(val, Value::Var(..)))
+      .cloned()
+      .collect()
+  }
+
+  // A fresh metavariable, applied to the bound variables in scope
+  pub fn fresh_meta(&self, pos: Option<Pos>) -> Value {
+    let arity = self.vars().len() as u64;
+    self.meta_app(self.metas.borrow_mut().fresh(pos, arity))
+  }
+
+  // A metavariable created in this context, applied to the bound variables
+  // in scope
+  pub fn meta_app(&self, idx: u64) -> Value {
+    self.vars().into_iter().fold(Value::Meta(idx), |acc, var| {
+      Value::App(Rc::new(acc), Rc::new(var))
+    })
+  }
+
+  // The metavariable a placeholder was replaced with, if `term` is one
+  fn hole(&self, term: &Term) -> Option<u64> {
+    let mut head = term;
+    let mut arity = 0;
+    while let Term::App(_, terms) = head {
+      head = &terms.0;
+      arity += 1;
+    }
+    match head {
+      Term::Met(_, idx) => match self.metas.borrow().get(*idx) {
+        Some(meta) if meta.arity == arity => Some(*idx),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  // Record the context of a placeholder's metavariable, if it is for a named
+  // hole met for the first time
+  fn record_hole(&self, idx: u64) {
+    let named = match self.metas.borrow().get(idx) {
+      Some(meta) => meta.name.is_some(),
+      None => false,
+    };
+    let mut holes = self.holes.borrow_mut();
+    if named && holes.iter().all(|hole| hole.meta != idx) {
+      holes.push(Hole {
+        meta: idx,
+        names: self.names.clone(),
+        env: self.env.clone(),
+        types: self.types.clone(),
+        erased: self.erased.clone(),
+        relevant: self.relevant,
+      });
+    }
+  }
+
+  // The context a hole was met in
+  pub fn at(&self, hole: &Hole) -> Self {
+    Ctx {
+      names: hole.names.clone(),
+      env: hole.env.clone(),
+      types: hole.types.clone(),
+      erased: hole.erased.clone(),
+      relevant: hole.relevant,
+      ..self.clone()
+    }
+  }
+
+  // Fill the holes named `?auto` met so far by search, leaving those for
+  // which no term is found to be reported
+  pub fn fill_auto(&self) {
+    let holes = self.holes.borrow().clone();
+    for hole in holes {
+      let (solved, typ) = match self.metas.borrow().get(hole.meta) {
+        Some(meta) if meta.name.as_deref() == Some(auto::AUTO) => {
+          (meta.solution.is_some(), meta.typ.clone())
+        }
+        _ => continue,
+      };
+      let filled = solved
+        || match typ {
+          Some((typ, _)) => {
+            let ctx = self.at(&hole);
+            match auto::search(&ctx, &typ, auto::DEPTH) {
+              Some(term) => {
+                ctx.conv(&ctx.meta_app(hole.meta), &ctx.eval(&term))
+              }
+              None => false,
+            }
+          }
+          None => false,
+        };
+      if filled {
+        self.holes.borrow_mut().retain(|other| other.meta != hole.meta);
+      }
+    }
+  }
+
+  // The goals of the named holes met so far
+  pub fn goals(&self) -> Vec<Goal> {
+    let metas = self.metas.borrow();
+    let quote = |depth, val: &Value| {
+      unify::quote(&HashMap::new(), &metas, depth, val)
+    };
+    let mut goals = Vec::new();
+    for hole in self.holes.borrow().iter() {
+      let meta = match metas.get(hole.meta) {
+        Some(meta) => meta,
+        None => continue,
+      };
+      let (typ, depth) = match &meta.typ {
+        Some((typ, depth)) => (typ, *depth),
+        None => continue,
+      };
+      let ctx = telescope(&metas, &hole.names, &hole.types);
+      // A type which is yet unknown would match every reference
+      let forced = unify::force_metas(&self.defs, &metas, typ.clone());
+      let mut candidates = Vec::new();
+      if unify::flex(&forced).is_none() {
+        for def in self.defs.values() {
+          let def_typ = eval(&self.defs, Vector::new(), &def.typ_);
+          // Matching is tried on a copy, so that it solves no metavariables
+          let mut metas = metas.clone();
+          let types = levels(&hole.types);
+          if unify::unify(&self.defs, &mut metas, &types, &def_typ, typ) {
+            candidates.push(def.name.to_string());
+          }
+        }
+      }
+      candidates.sort();
+      candidates.dedup();
+      goals.push(Goal {
+        pos: meta.pos,
+        name: meta.name.clone().unwrap_or_default(),
+        typ: quote(depth, typ),
+        ctx,
+        candidates,
+      });
+    }
+    goals
+  }
+
+  // Fail with the goals of the named holes met so far, if any
+  pub fn filled(&self) -> Result<(), CheckError> {
+    let goals = self.goals();
+    match goals.first() {
+      Some(goal) => {
+        let pos = goal.pos;
+        Err(CheckError::new(pos, CheckErrorKind::UnfilledHoles(goals)))
+      }
+      None => Ok(()),
+    }
+  }
+
+  // An error at `pos` in this context
+  pub fn error(&self, pos: Option<Pos>, kind: CheckErrorKind) -> CheckError {
+    let metas = self.metas.borrow();
+    let ctx = telescope(&metas, &self.names, &self.types);
+    CheckError { pos, ctx, kind, related: Vec::new() }
+  }
+
+  // Replace the solved metavariables of a term with their solutions
+  pub fn zonk(&self, term: &Term) -> Term {
+    unify::zonk(&HashMap::new(), &self.metas.borrow(), term)
+  }
+
+  // Fail on the first metavariable left unsolved
+  pub fn solved(&self) -> Result<(), CheckError> {
+    let metas = self.metas.borrow();
+    match metas.unsolved() {
+      Some(meta) => {
+        let typ = meta.typ.as_ref().map(|(typ, depth)| {
+          unify::quote(&HashMap::new(), &metas, *depth, typ)
+        });
+        Err(CheckError::new(meta.pos, CheckErrorKind::CouldNotInfer(typ)))
+      }
+      None => Ok(()),
+    }
+  }
+}
+
+// The variables of a context, outermost first, with their types
+fn telescope(
+  metas: &Metas,
+  names: &Vector<Name>,
+  types: &Vector<Value>,
+) -> Vec<(String, Term)> {
+  (0..names.len())
+    .rev()
+    .map(|idx| {
+      let lvl = (names.len() - 1 - idx) as u64;
+      let typ = unify::quote(&HashMap::new(), metas, lvl, &types[idx]);
+      (names[idx].to_string(), typ)
+    })
+    .collect()
+}
+
+// The types of the variables in scope of a context, by de Bruijn level
+fn levels(types: &Vector<Value>) -> unify::Types {
+  types.iter().rev().cloned().map(Some).collect()
+}
+
+// Whether `a` is a subtype of `b` under binders of the given types, by
+// cumulativity. Types headed by an unsolved metavariable are unified rather
+// than unfolded.
+pub fn sub(
+  defs: &Defs,
+  metas: &mut Metas,
+  types: &unify::Types,
+  a: &Value,
+  b: &Value,
+) -> bool {
+  let a = unify::force_metas(defs, metas, a.clone());
+  let b = unify::force_metas(defs, metas, b.clone());
+  if unify::flex(&a).is_some() || unify::flex(&b).is_some() {
+    return unify::unify(defs, metas, types, &a, &b);
+  }
+  match (unify::force(defs, metas, a), unify::force(defs, metas, b)) {
+    (Value::Typ(x), Value::Typ(y)) => x <= y,
+    (Value::Prp, Value::Typ(_)) => true,
+    (Value::All(ux, nam, dx, ix), Value::All(uy, _, dy, iy)) => {
+      let var = Value::Var(nam, types.len() as u64);
+      let mut img_types = types.clone();
+      img_types.push_back(Some((*dx).clone()));
+      ux == uy
+        && unify::unify(defs, metas, types, &dx, &dy)
+        && sub(
+          defs,
+          metas,
+          &img_types,
+          &ix.apply(defs, var.clone()),
+          &iy.apply(defs, var),
+        )
+    }
+    (a, b) => unify::unify(defs, metas, types, &a, &b),
+  }
+}
+
+// Check that `term` has type `typ`. An error in a subterm without a position,
+// such as one produced by elaboration, is located at the nearest enclosing
+// term which has one.
+pub fn check(ctx: &Ctx, term: &Term, typ: &Value) -> Result<(), CheckError> {
+  ctx.enter();
+  let res = check_term(ctx, term, typ).map_err(|e| e.or_pos(term.pos()));
+  ctx.leave(|| {
+    res.is_ok().then(|| Judgment::Check(term.clone(), ctx.quote(typ)))
+  });
+  res
+}
+
+fn check_term(ctx: &Ctx, term: &Term, typ: &Value) -> Result<(), CheckError> {
+  if let Some(idx) = ctx.hole(term) {
+    ctx.metas.borrow_mut().set_type(idx, typ.clone(), ctx.depth());
+    ctx.record_hole(idx);
+    return Ok(());
+  }
+  match term {
+    Term::Lam(pos, nam, bod) => match ctx.force(typ.clone()) {
+      Value::All(uses, _, dom, img) => {
+        let ctx2 = ctx.bind(nam, (*dom).clone()).with_uses(uses);
+        let img = img.apply(&ctx.defs, Value::Var(nam.clone(), ctx.depth()));
+        check(&ctx2, bod, &img)
+      }
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::LamNotAFunction(typ)))
+      }
+    },
+    Term::Dat(pos, bod) => match ctx.force(typ.clone()) {
+      Value::Slf(_, clos) => {
+        let this = Value::Dat(Rc::new(ctx.eval(bod)));
+        check(ctx, bod, &clos.apply(&ctx.defs, this))
+      }
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::DatNotASelf(typ)))
+      }
+    },
+    // The type of the second component depends on the first
+    Term::Par(pos, terms) => match ctx.force(typ.clone()) {
+      Value::Sig(_, dom, img) => {
+        check(ctx, &terms.0, &dom)?;
+        check(ctx, &terms.1, &img.apply(&ctx.defs, ctx.eval(&terms.0)))
+      }
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::ParNotASigma(typ)))
+      }
+    },
+    // The expected type is propagated into the body of a let
+    Term::Let(_, rec, uses, nam, terms) => {
+      let ctx = let_binding(ctx, *rec, *uses, nam, &terms.0, &terms.1)?;
+      check(&ctx, &terms.2, typ)
+    }
+    // Switch to synthesis mode and compare the types
+    _ => {
+      let found = synth(ctx, term)?;
+      if ctx.sub(&found, typ) {
+        Ok(())
+      }
+      else if ctx.metas.borrow().out_of_fuel() {
+        let kind = CheckErrorKind::OutOfFuel(term.clone());
+        Err(ctx.error(term.pos(), kind))
+      }
+      else {
+        let kind = CheckErrorKind::TypeMismatch(
+          term.clone(),
+          ctx.quote(typ),
+          ctx.quote(&found),
+        );
+        Err(ctx.error(term.pos(), kind))
+      }
+    }
+  }
+}
+
+// Relate a mismatch with the type `typ` to the annotation `ann` it comes
+// from, when the mismatch is with the type as a whole
+fn annotated(ctx: &Ctx, ann: &Term, typ: &Value, e: CheckError) -> CheckError {
+  match (&e.kind, ann.pos()) {
+    (CheckErrorKind::TypeMismatch(_, expected, _), Some(pos))
+      if e.related.is_empty() && *expected == ctx.quote(typ) =>
+    {
+      let why = String::from("expected because of the annotation here");
+      CheckError { related: vec![(pos, why)], ..e }
+    }
+    _ => e,
+  }
+}
+
+// Check a let binding's type and expression, and extend the context with it
+fn let_binding(
+  ctx: &Ctx,
+  rec: bool,
+  uses: Uses,
+  nam: &str,
+  typ: &Term,
+  exp: &Term,
+) -> Result<Ctx, CheckError> {
+  check_type(ctx, typ)?;
+  let typ = ctx.eval(typ);
+  // The expression of an erased binding is irrelevant
+  let exp_ctx = if uses == Uses::None { ctx.irrelevant() } else { ctx.clone() };
+  let val = if rec {
+    let rec_ctx = exp_ctx.bind(nam, typ.clone()).with_uses(uses);
+    check(&rec_ctx, exp, &typ)?;
+    let clos = Closure::new(ctx.env.clone(), exp.clone());
+    Value::Fix(Name::from(nam), clos)
+  }
+  else {
+    check(&exp_ctx, exp, &typ)?;
+    ctx.eval(exp)
+  };
+  Ok(ctx.define(nam, val, typ).with_uses(uses))
+}
+
+// Check that `term` is a type, returning its universe. There are no
+// metavariables for levels, so a type whose universe is yet unknown is taken
+// to be in the lowest `Type`.
+pub fn synth_sort(ctx: &Ctx, term: &Term) -> Result<Sort, CheckError> {
+  let ctx = &ctx.irrelevant();
+  let typ = synth(ctx, term)?;
+  if unify::flex(&ctx.force(typ.clone())).is_some() {
+    ctx.conv(&typ, &Value::Typ(0));
+  }
+  match ctx.force(typ) {
+    Value::Typ(lvl) => Ok(Sort::Type(lvl)),
+    Value::Prp => Ok(Sort::Prop),
+    typ => {
+      let kind = CheckErrorKind::NotAType(term.clone(), ctx.quote(&typ));
+      Err(ctx.error(term.pos(), kind))
+    }
+  }
+}
+
+// Check that `term` is a type
+pub fn check_type(ctx: &Ctx, term: &Term) -> Result<(), CheckError> {
+  synth_sort(ctx, term).map(|_| ())
+}
+
+// Synthesize the type of `term`
+pub fn synth(ctx: &Ctx, term: &Term) -> Result<Value, CheckError> {
+  ctx.enter();
+  let res = synth_term(ctx, term).map_err(|e| e.or_pos(term.pos()));
+  ctx.leave(|| {
+    let typ = res.as_ref().ok()?;
+    Some(Judgment::Synth(term.clone(), ctx.quote(typ)))
+  });
+  res
+}
+
+fn synth_term(ctx: &Ctx, term: &Term) -> Result<Value, CheckError> {
+  if let Some(idx) = ctx.hole(term) {
+    let typ = ctx.fresh_meta(term.pos());
+    ctx.metas.borrow_mut().set_type(idx, typ.clone(), ctx.depth());
+    ctx.record_hole(idx);
+    return Ok(typ);
+  }
+  match term {
+    Term::Var(pos, nam, idx) => match ctx.types.get(*idx as usize) {
+      Some(_) if ctx.relevant && ctx.erased[*idx as usize] => {
+        Err(ctx.error(*pos, CheckErrorKind::ErasedVariable(nam.to_string())))
+      }
+      Some(typ) => Ok(typ.clone()),
+      None => {
+        let kind = CheckErrorKind::UnboundVariable(nam.to_string(), *idx);
+        Err(ctx.error(*pos, kind))
+      }
+    },
+    Term::Ref(pos, nam, def_link, _) => match ctx.defs.get(def_link) {
+      Some(def) => Ok(eval(&ctx.defs, Vector::new(), &def.typ_)),
+      None => {
+        let nam = nam.to_string();
+        let kind = CheckErrorKind::UndefinedReference(nam, *def_link);
+        Err(ctx.error(*pos, kind))
+      }
+    },
+    Term::Typ(_, lvl) => Ok(Value::Typ(lvl + 1)),
+    Term::Prp(_) => Ok(Value::Typ(0)),
+    Term::LTy(..) => Ok(Value::Typ(0)),
+    Term::Lit(_, lit) => Ok(Value::LTy(lit.lit_type())),
+    Term::All(_, _, nam, terms) => {
+      let dom_sort = synth_sort(ctx, &terms.0)?;
+      let dom = ctx.eval(&terms.0);
+      match synth_sort(&ctx.bind(nam, dom), &terms.1)? {
+        Sort::Prop => Ok(Value::Prp),
+        img_sort => Ok(Value::Typ(dom_sort.level().max(img_sort.level()))),
+      }
+    }
+    Term::Sig(_, nam, terms) => {
+      let dom_sort = synth_sort(ctx, &terms.0)?;
+      let dom = ctx.eval(&terms.0);
+      match (dom_sort, synth_sort(&ctx.bind(nam, dom), &terms.1)?) {
+        (Sort::Prop, Sort::Prop) => Ok(Value::Prp),
+        (dom_sort, img_sort) => {
+          Ok(Value::Typ(dom_sort.level().max(img_sort.level())))
+        }
+      }
+    }
+    Term::Fst(pos, bod) => match ctx.force(synth(ctx, bod)?) {
+      Value::Sig(_, dom, _) => Ok((*dom).clone()),
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::PrjNotASigma(typ)))
+      }
+    },
+    Term::Snd(pos, bod) => match ctx.force(synth(ctx, bod)?) {
+      Value::Sig(_, _, img) => {
+        Ok(img.apply(&ctx.defs, nbe::fst(&ctx.defs, ctx.eval(bod))))
+      }
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::PrjNotASigma(typ)))
+      }
+    },
+    Term::Slf(_, nam, bod) => {
+      // The bound variable stands for a term of the self type itself
+      let slf = ctx.eval(term);
+      Ok(synth_sort(&ctx.bind(nam, slf), bod)?.value())
+    }
+    Term::App(pos, terms) => {
+      if let Some(typ) = synth_opr(ctx, term)? {
+        return Ok(typ);
+      }
+      let fun_typ = synth(ctx, &terms.0)?;
+      match ctx.force(fun_typ) {
+        Value::All(uses, _, dom, img) => {
+          // The argument of an erased binder is irrelevant
+          if uses == Uses::None {
+            check(&ctx.irrelevant(), &terms.1, &dom)?;
+          }
+          else {
+            check(ctx, &terms.1, &dom)?;
+          }
+          Ok(img.apply(&ctx.defs, ctx.eval(&terms.1)))
+        }
+        typ => {
+          let typ = ctx.quote(&typ);
+          Err(ctx.error(*pos, CheckErrorKind::NotAFunction(typ)))
+        }
+      }
+    }
+    Term::Cse(pos, bod) => match ctx.force(synth(ctx, bod)?) {
+      Value::Slf(_, clos) => Ok(clos.apply(&ctx.defs, ctx.eval(bod))),
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::CseNotASelf(typ)))
+      }
+    },
+    Term::Let(_, rec, uses, nam, terms) => {
+      let ctx = let_binding(ctx, *rec, *uses, nam, &terms.0, &terms.1)?;
+      synth(&ctx, &terms.2)
+    }
+    // An annotation switches its term to checking mode
+    Term::Ann(_, terms) => {
+      check_type(ctx, &terms.0)?;
+      let typ = ctx.eval(&terms.0);
+      check(ctx, &terms.1, &typ)
+        .map_err(|e| annotated(ctx, &terms.0, &typ, e))?;
+      Ok(typ)
+    }
+    Term::Lam(pos, ..)
+    | Term::Dat(pos, ..)
+    | Term::Par(pos, ..)
+    | Term::Opr(pos, ..)
+    | Term::Hol(pos, _)
+    | Term::Met(pos, _) => {
+      Err(ctx.error(*pos, CheckErrorKind::CannotSynth(term.clone())))
+    }
+  }
+}
+
+// Synthesize the type of a saturated application of a primitive operation,
+// which is overloaded on the literal types of its arguments
+fn synth_opr(ctx: &Ctx, term: &Term) -> Result<Option<Value>, CheckError> {
+  let mut head = term;
+  let mut args = Vec::new();
+  while let Term::App(_, terms) = head {
+    args.push(&terms.1);
+    head = &terms.0;
+  }
+  args.reverse();
+  let opr = match head {
+    Term::Opr(_, opr) if args.len() as u64 == opr.arity() => *opr,
+    _ => return Ok(None),
+  };
+  let mut typs = Vec::new();
+  for arg in args {
+    typs.push(ctx.force(synth(ctx, arg)?));
+  }
+  // Arguments whose types are still to be solved, such as those of bound
+  // variables annotated with a placeholder, are taken to have the literal
+  // type of the others, as no operation mixes literal types but the shifts
+  let known = typs.iter().find_map(|typ| match typ {
+    Value::LTy(lty) => Some(*lty),
+    _ => None,
+  });
+  if let Some(lty) = known {
+    for typ in typs.iter_mut() {
+      if unify::flex(typ).is_some() && ctx.conv(typ, &Value::LTy(lty)) {
+        *typ = Value::LTy(lty);
+      }
+    }
+  }
+  let ltys: Option<Vec<LitType>> = typs
+    .iter()
+    .map(|typ| match typ {
+      Value::LTy(lty) => Some(*lty),
+      _ => None,
+    })
+    .collect();
+  match ltys.and_then(|ltys| type_of_op(opr, &ltys)) {
+    Some(lty) => Ok(Some(Value::LTy(lty))),
+    None => {
+      let typs = typs.iter().map(|typ| ctx.quote(typ)).collect();
+      Err(ctx.error(term.pos(), CheckErrorKind::InvalidPrimOp(opr, typs)))
+    }
+  }
+}
+
+// Infer the type of a closed term, as a term
+pub fn infer_term(defs: &Defs, term: &Term) -> Result<Term, CheckError> {
+  let ctx = Ctx::new(defs.clone());
+  let term = ctx.insert_metas(term);
+  let typ = synth(&ctx, &term)?;
+  ctx.fill_auto();
+  ctx.filled()?;
+  ctx.solved()?;
+  Ok(ctx.quote(&typ))
+}
+
+// Check a definition against its declared type, returning it with its
+// placeholders filled in. The definition's body refers to itself through its
+// outermost variable, which is bound to a reference to the definition so
+// that it can be unfolded during conversion.
+pub fn elab_def(defs: &Defs, def: &Def) -> Result<Def, CheckError> {
+  let name = &def.name;
+  let _span = debug_span!(target: "check", "elaborate", %name).entered();
+  // There is no clock to time the elaboration by in the browser
+  #[cfg(feature = "native")]
+  let start = Instant::now();
+  let res = elab_in(Ctx::new(defs.clone()), def);
+  #[cfg(feature = "native")]
+  debug!(
+    target: "check",
+    %name,
+    time = ?start.elapsed(),
+    ok = res.is_ok(),
+    "Elaborated"
+  );
+  res
+}
+
+// Elaborate a definition in an empty context
+pub fn elab_in(ctx: Ctx, def: &Def) -> Result<Def, CheckError> {
+  elab(ctx, def).map_err(|e| e.or_pos(def.pos))
+}
+
+fn elab(ctx: Ctx, def: &Def) -> Result<Def, CheckError> {
+  let (d, _, a) = def.clone().embed();
+  let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+  let defs = ctx.defs.clone();
+  let typ_ = ctx.insert_metas(&def.typ_);
+  check_type(&ctx, &typ_)?;
+  let typ = ctx.eval(&typ_);
+  let this = Value::Ref(def.name.clone(), def_link, ast_link);
+  let ctx = ctx.define(&def.name, this, typ.clone());
+  let term = ctx.insert_metas(&def.term);
+  // The definition unfolds to its body with the placeholders replaced
+  let def = Def { typ_, term, ..def.clone() };
+  let ctx = Ctx { defs: defs.update(def_link, def.clone()), ..ctx };
+  check(&ctx, &def.term, &typ)
+    .map_err(|e| annotated(&ctx, &def.typ_, &typ, e))?;
+  ctx.fill_auto();
+  ctx.filled()?;
+  ctx.solved()?;
+  Ok(Def { typ_: ctx.zonk(&def.typ_), term: ctx.zonk(&def.term), ..def })
+}
+
+pub fn check_def(defs: &Defs, def: &Def) -> Result<(), CheckError> {
+  elab_def(defs, def).map(|_| ())
+}
+
+// Check the definitions a datatype declaration elaborates to
+pub fn check_data(defs: &Defs, data: &Datatype) -> Result<(), CheckError> {
+  let mut defs = defs.clone();
+  for def in data.defs() {
+    check_def(&defs, &def)?;
+    let (d, ..) = def.clone().embed();
+    defs.insert(d.encode().link(), def);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    definition::Transparency,
+    parse::term::parse,
+  };
+
+  fn infer_assert(input: &str, expected: &str) {
+    let (_, term) = parse(input).unwrap();
+    match infer_term(&HashMap::new(), &term) {
+      Ok(typ) => assert_eq!(format!("{}", typ), expected),
+      Err(e) => panic!("{}: {}", input, e),
+    }
+  }
+
+  fn infer_fails(input: &str) -> CheckErrorKind {
+    let (_, term) = parse(input).unwrap();
+    infer_term(&HashMap::new(), &term).unwrap_err().kind
+  }
+
+  #[test]
+  fn relates_mismatches_to_annotations() {
+    let (_, term) = parse("\"two\" :: #Natural").unwrap();
+    let e = infer_term(&HashMap::new(), &term).unwrap_err();
+    assert!(matches!(e.kind, CheckErrorKind::TypeMismatch(..)));
+    let at = |(pos, _): &(Pos, String)| (pos.from_line, pos.from_column);
+    assert_eq!(e.related.iter().map(at).collect::<Vec<_>>(), vec![(1, 10)]);
+    // A mismatch within the annotated term is not with the annotation
+    let (_, term) = parse("(λ x => x) :: ∀ (x: #Natural) -> #Text").unwrap();
+    let e = infer_term(&HashMap::new(), &term).unwrap_err();
+    assert!(matches!(e.kind, CheckErrorKind::TypeMismatch(..)));
+    assert!(e.related.is_empty());
+  }
+
+  #[test]
+  fn infers() {
+    infer_assert("Type", "Type@1");
+    infer_assert("Type@3", "Type@4");
+    infer_assert("#Natural", "Type");
+    infer_assert("1", "#Natural");
+    infer_assert("-7", "#Integer");
+    infer_assert("+7 :: #Integer", "#Integer");
+    infer_assert("#div 6.02e23 2.0", "#Float");
+    infer_assert("#add 1 2", "#Natural");
+    infer_assert("#eql \"a\" \"b\"", "#BitString");
+    infer_assert("∀ (A: Type) -> A", "Type@1");
+    infer_assert("∀ (x: #Natural) -> #Natural", "Type");
+    let id_typ = "∀ (A: Type) (x: A) -> A";
+    infer_assert(&format!("(λ A x => x) :: {}", id_typ), id_typ);
+    let id = format!("((λ A x => x) :: {})", id_typ);
+    infer_assert(&format!("{} #Natural 1", id), "#Natural");
+    infer_assert("let x: #Natural = 2; #mul x x", "#Natural");
+  }
+
+  #[test]
+  fn propagates_expected_types() {
+    let typ = "∀ (y: #Natural) -> #Natural";
+    infer_assert(&format!("(let x: Type = #Natural; λ y => y) :: {}", typ), typ);
+    let typ = "∀ (A: Type) (f: ∀ (x: A) -> A) (x: A) -> A";
+    infer_assert(&format!("(λ A f x => f (f x)) :: {}", typ), typ);
+  }
+
+  #[test]
+  fn universes() {
+    // Cumulativity
+    infer_assert("Type :: Type@2", "Type@2");
+    infer_assert("#Natural :: Type@1", "Type@1");
+    let id_typ = "∀ (A: Type@1) (x: A) -> A";
+    let id = format!("((λ A x => x) :: {})", id_typ);
+    infer_assert(&format!("{} Type #Natural", id), "Type");
+    // No universe contains itself
+    match infer_fails("Type :: Type") {
+      CheckErrorKind::TypeMismatch(_, expected, found) => {
+        assert_eq!(format!("{}", expected), "Type");
+        assert_eq!(format!("{}", found), "Type@1");
+      }
+      e => panic!("unexpected error {}", e),
+    }
+    match infer_fails("∀ (x: 1) -> Type") {
+      CheckErrorKind::NotAType(..) => (),
+      e => panic!("unexpected error {}", e),
+    }
+  }
+
+  #[test]
+  fn propositions() {
+    infer_assert("Prop", "Type");
+    // Propositions are impredicative
+    infer_assert("∀ (P: Prop) (p: P) -> P", "Prop");
+    infer_assert("∀ (A: Type@1) (P: Prop) -> P", "Prop");
+    infer_assert("Σ (P: Prop) -> P", "Type");
+    // A proposition is also a type
+    infer_assert("(∀ (P: Prop) (p: P) -> P) :: Type", "Type");
+    // Any two proofs of a proposition are convertible
+    let typ = "∀ (P: Prop) (p: P) (q: P) (F: ∀ (p: P) -> Type) (x: F p) -> F q";
+    infer_assert(&format!("(λ P p q F x => x) :: {}", typ), typ);
+    let typ = "∀ (P: Type) (p: P) (q: P) (F: ∀ (p: P) -> Type) (x: F p) -> F q";
+    match infer_fails(&format!("(λ P p q F x => x) :: {}", typ)) {
+      CheckErrorKind::TypeMismatch(..) => (),
+      e => panic!("unexpected error {}", e),
+    }
+  }
+
+  #[test]
+  fn sigmas() {
+    infer_assert("Σ (A: Type) -> A", "Type@1");
+    infer_assert("Σ (x: #Natural) (y: #Natural) -> #Text", "Type");
+    let pkg = "((#Natural, 1) :: Σ (A: Type) -> A)";
+    infer_assert(pkg, "Σ (A: Type) -> A");
+    infer_assert(&format!("fst {}", pkg), "Type");
+    // The type of the second projection mentions the first
+    infer_assert(&format!("snd {}", pkg), "#Natural");
+    let unpack = "(λ p => snd p) :: ∀ (p: Σ (A: Type) -> A) -> fst p";
+    infer_assert(unpack, "∀ (p: Σ (A: Type) -> A) -> fst p");
+    match infer_fails("(1, 2) :: #Natural") {
+      CheckErrorKind::ParNotASigma(typ) => {
+        assert_eq!(format!("{}", typ), "#Natural")
+      }
+      e => panic!("unexpected error {}", e),
+    }
+    match infer_fails("snd 1") {
+      CheckErrorKind::PrjNotASigma(..) => (),
+      e => panic!("unexpected error {}", e),
+    }
+    match infer_fails("(1, \"a\") :: Σ (x: #Natural) -> #Natural") {
+      CheckErrorKind::TypeMismatch(..) => (),
+      e => panic!("unexpected error {}", e),
+    }
+  }
+
+  #[test]
+  fn rejects() {
+    match infer_fails("λ x => x") {
+      CheckErrorKind::CannotSynth(..) => (),
+      e => panic!("unexpected error {}", e),
+    }
+    match infer_fails("#add 1 \"a\"") {
+      CheckErrorKind::InvalidPrimOp(PrimOp::Add, _) => (),
+      e => panic!("unexpected error {}", e),
+    }
+    match infer_fails("7 :: #Integer") {
+      CheckErrorKind::TypeMismatch(..) => (),
+      e => panic!("unexpected error {}", e),
+    }
+    match infer_fails("(λ x => x) :: Type") {
+      CheckErrorKind::LamNotAFunction(..) => (),
+      e => panic!("unexpected error {}", e),
+    }
+    let id = "((λ A x => x) :: ∀ (A: Type) (x: A) -> A)";
+    let (_, term) = parse(&format!("{} #Natural Type", id)).unwrap();
+    let e = infer_term(&HashMap::new(), &term).unwrap_err();
+    assert!(e.pos.is_some());
+    assert_eq!(e.mode(), Mode::Check);
+    match e.kind {
+      CheckErrorKind::TypeMismatch(term, expected, _) => {
+        assert_eq!(format!("{}", term), "Type");
+        assert_eq!(format!("{}", expected), "#Natural");
+      }
+      e => panic!("unexpected error {}", e),
+    }
+  }
+
+  #[test]
+  fn error_messages() {
+    let (expected, found) = elide(
+      "∀ (x: #Natural) (y: #Natural) -> #Natural",
+      "∀ (x: #Natural) (y: #Natural) -> #Text",
+    );
+    assert_eq!(expected, "... #Natural");
+    assert_eq!(found, "... #Text");
+    // Short types are shown in full
+    let (expected, found) = elide("Type", "Type@1");
+    assert_eq!((expected.as_str(), found.as_str()), ("Type", "Type@1"));
+    // Errors report the variables in scope
+    let typ = "∀ (A: Type) (x: A) -> A";
+    let (_, term) = parse(&format!("(λ A x => #add x 1) :: {}", typ)).unwrap();
+    let e = infer_term(&HashMap::new(), &term).unwrap_err();
+    assert!(e.pos.is_some());
+    assert!(matches!(e.kind, CheckErrorKind::InvalidPrimOp(..)));
+    let msg = format!("{}", e);
+    assert!(msg.ends_with("\nIn the context:\n  A: Type\n  x: A"));
+    // An error in a term without positions is located at its definition
+    let pos = Pos {
+      from_offset: 0,
+      from_line: 1,
+      from_column: 1,
+      upto_offset: 4,
+      upto_line: 1,
+      upto_column: 5,
+    };
+    let typ = Term::Typ(None, 0);
+    let app = Term::App(None, Box::new((typ.clone(), typ.clone())));
+    let def = Def::new(Some(pos), Name::from("f"), String::new(), typ, app);
+    assert_eq!(elab_def(&HashMap::new(), &def).unwrap_err().pos, Some(pos));
+  }
+
+  #[test]
+  fn placeholders() {
+    let id = "((λ A x => x) :: ∀ (A: Type) (x: A) -> A)";
+    infer_assert(&format!("{} _ 1", id), "#Natural");
+    let typ = "∀ (x: #Natural) -> _";
+    let inc = format!("(λ x => #add x 1) :: {}", typ);
+    infer_assert(&inc, "∀ (x: #Natural) -> #Natural");
+    match infer_fails(&format!("{} _", id)) {
+      CheckErrorKind::CouldNotInfer(Some(typ)) => {
+        assert_eq!(format!("{}", typ), "Type")
+      }
+      e => panic!("unexpected error {}", e),
+    }
+    let (_, typ) = parse("∀ (A: Type) (x: A) -> A").unwrap();
+    let (_, term) = parse("(λ B y => y) :: ∀ (B: _) (y: B) -> _").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, term);
+    let def = elab_def(&HashMap::new(), &def).unwrap();
+    assert_eq!(
+      format!("{}", def.term),
+      "(λ B y => y) :: (∀ (B: Type) (y: B) -> B)"
+    );
+  }
+
+  #[test]
+  fn erasure() {
+    let id = "((λ A x => x) :: ∀ (0 A: Type) (x: A) -> A)";
+    infer_assert(&format!("{} #Natural 1", id), "#Natural");
+    infer_assert("let 0 A: Type = #Natural; (1 :: A)", "#Natural");
+    match infer_fails("let 0 n: #Natural = 1; #add n n") {
+      CheckErrorKind::ErasedVariable(nam) => assert_eq!(nam, "n"),
+      e => panic!("unexpected error {}", e),
+    }
+  }
+
+  #[test]
+  fn holes() {
+    let id = "((λ A x => x) :: ∀ (A: Type) (x: A) -> A)";
+    match infer_fails(&format!("{} #Natural ?h", id)) {
+      CheckErrorKind::UnfilledHoles(goals) => {
+        assert_eq!(goals.len(), 1);
+        assert_eq!(goals[0].name, "h");
+        assert!(goals[0].pos.is_some());
+        assert_eq!(format!("{}", goals[0]), "?h: #Natural");
+      }
+      e => panic!("unexpected error {}", e),
+    }
+    let (_, typ) = parse("#Natural").unwrap();
+    let (_, term) = parse("1").unwrap();
+    let one = Def::new(None, Name::from("one"), String::new(), typ, term);
+    let (d, ..) = one.clone().embed();
+    let defs = HashMap::new().update(d.encode().link(), one);
+    let (_, typ) = parse("∀ (A: Type) (x: #Natural) -> #Natural").unwrap();
+    let (_, term) = parse("λ A x => ?goal").unwrap();
+    let def = Def::new(None, Name::from("f"), String::new(), typ, term);
+    match elab_def(&defs, &def).map_err(|e| e.kind) {
+      Err(CheckErrorKind::UnfilledHoles(goals)) => {
+        let ctx: Vec<(&str, String)> = goals[0]
+          .ctx
+          .iter()
+          .map(|(nam, typ)| (nam.as_str(), format!("{}", typ)))
+          .collect();
+        assert_eq!(ctx, vec![
+          ("f", String::from("∀ (A: Type) (x: #Natural) -> #Natural")),
+          ("A", String::from("Type")),
+          ("x", String::from("#Natural")),
+        ]);
+        assert_eq!(goals[0].candidates, vec![String::from("one")]);
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[test]
+  fn definitions() {
+    let (_, typ) = parse("∀ (A: Type) (x: A) -> A").unwrap();
+    let (_, term) = parse("λ A x => x").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, term);
+    assert_eq!(check_def(&HashMap::new(), &def), Ok(()));
+    let (_, typ) = parse("∀ (A: Type) (x: A) -> Type").unwrap();
+    let (_, term) = parse("λ A x => x").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, term);
+    assert!(check_def(&HashMap::new(), &def).is_err());
+  }
+
+  #[test]
+  fn fuel() {
+    let (_, one) = parse("1").unwrap();
+    let nat = |transparency| {
+      let (_, typ) = parse("Type").unwrap();
+      let (_, term) = parse("#Natural").unwrap();
+      let nam = Name::from("Nat");
+      let def = Def::new(None, nam.clone(), String::new(), typ, term);
+      let def = Def { transparency, ..def };
+      let (d, _, a) = def.clone().embed();
+      let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+      let ctx = Ctx::new(HashMap::new().update(def_link, def));
+      let typ = ctx.eval(&Term::Ref(None, nam, def_link, ast_link));
+      (ctx, typ)
+    };
+    let (ctx, typ) = nat(Transparency::Transparent);
+    assert_eq!(check(&ctx.with_fuel(1), &one, &typ), Ok(()));
+    let (ctx, typ) = nat(Transparency::Transparent);
+    match check(&ctx.with_fuel(0), &one, &typ).map_err(|e| e.kind) {
+      Err(CheckErrorKind::OutOfFuel(_)) => (),
+      res => panic!("unexpected result {:?}", res),
+    }
+    let (ctx, typ) = nat(Transparency::Opaque);
+    match check(&ctx, &one, &typ).map_err(|e| e.kind) {
+      Err(CheckErrorKind::TypeMismatch(..)) => (),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+}
@@ -0,0 +1,333 @@
+use crate::{
+  core::{
+    datatype::{
+      alls,
+      apps,
+      lams,
+      names,
+      var,
+      DataError,
+      Telescope,
+    },
+    elab,
+  },
+  name::Name,
+  term::{
+    Def,
+    Term,
+    Uses,
+  },
+};
+
+use hashexpr::position::Pos;
+
+// Coinductive datatypes, given by the fields observed of their values rather
+// than by the ways of building them. A declaration
+//
+//   codata Stream (A: Type): Type {
+//     head: A,
+//     tail: Stream A,
+//   }
+//
+// becomes the recursive dependent pair type
+//
+//   Stream = λ A => Σ (head: A) -> Stream A
+//
+// together with a destructor for each field, such as
+//
+//   Stream.tail: ∀ (A: Type) (x: Stream A) -> Stream A
+//   Stream.tail = λ A x => snd x
+//
+// The type of a field may depend on the fields before it, which become
+// observations of `x` in the type of its destructor. Values are built with
+// pairs, and a corecursive definition is accepted when its recursive calls
+// are guarded by a pair, as in
+//
+//   def nats (n: #Natural): Stream #Natural = (n, nats (#add n 1))
+//
+// Applications of definitions are only unfolded when observed, so a stream
+// is evaluated no further than its destructors demand. A codatatype has no
+// recursor, so unlike a datatype it may live in any universe.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+  pub pos: Option<Pos>,
+  pub name: Name,
+  // The field's type, in the scope of the fields before it, of the
+  // codatatype's parameters and of the codatatype itself
+  pub typ: Term,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Codatatype {
+  pub pos: Option<Pos>,
+  pub name: Name,
+  // The documentation of the type, from the comments before its declaration
+  pub docs: String,
+  pub params: Telescope,
+  pub level: u64,
+  pub fields: Vec<Field>,
+}
+
+impl Codatatype {
+  pub fn new(
+    pos: Option<Pos>,
+    name: Name,
+    params: Telescope,
+    sort: Term,
+    fields: Vec<(Option<Pos>, Name, Term)>,
+  ) -> Result<Self, DataError> {
+    let level = match sort {
+      Term::Typ(_, lvl) => lvl,
+      sort => return Err(DataError::NotAUniverse(pos, sort)),
+    };
+    if fields.is_empty() {
+      return Err(DataError::NoFields(pos));
+    }
+    let typs = params.iter().map(|(_, _, typ)| typ);
+    let typs = typs.chain(fields.iter().map(|(_, _, typ)| typ));
+    if let Some(hole) = typs.filter_map(elab::surface).next() {
+      return Err(DataError::Placeholder(hole.pos(), hole.clone()));
+    }
+    let mut fs: Vec<Field> = Vec::new();
+    for (pos, name, typ) in fields {
+      if fs.iter().any(|f| f.name == name) {
+        return Err(DataError::DuplicateField(pos, name.to_string()));
+      }
+      fs.push(Field { pos, name, typ });
+    }
+    Ok(Codatatype {
+      pos,
+      name,
+      docs: String::new(),
+      params,
+      level,
+      fields: fs,
+    })
+  }
+
+  pub fn field_name(&self, field: &Field) -> Name {
+    Name::from(format!("{}.{}", self.name, field.name))
+  }
+
+  // The parameters, under `depth` binders below them
+  fn param_vars(&self, depth: u64) -> Vec<Term> {
+    let n = self.params.len() as u64;
+    names(&self.params)
+      .iter()
+      .enumerate()
+      .map(|(i, nam)| var(nam, depth + n - 1 - i as u64))
+      .collect()
+  }
+
+  // The definition of the codatatype as nested dependent pairs of its fields
+  pub fn type_def(&self) -> Def {
+    // Each field's type is already in the scope of the fields before it, the
+    // parameters and the codatatype, which the pairs and the definition bind
+    let mut fields = self.fields.iter().rev();
+    let last = fields.next().expect("a codatatype has fields").typ.clone();
+    let sig = fields.fold(last, |acc, field| {
+      Term::Sig(None, field.name.clone(), Box::new((field.typ.clone(), acc)))
+    });
+    let term = lams(names(&self.params), sig);
+    let typ_ = alls(self.params.clone(), Term::Typ(None, self.level));
+    Def::new(self.pos, self.name.clone(), self.docs.clone(), typ_, term)
+  }
+
+  // The observation of the field with index `idx` of the value `x`, bound
+  // innermost
+  fn observe(&self, idx: usize) -> Term {
+    let mut obs = var("x", 0);
+    for _ in 0..idx {
+      obs = Term::Snd(None, Box::new(obs));
+    }
+    if idx + 1 < self.fields.len() {
+      obs = Term::Fst(None, Box::new(obs));
+    }
+    obs
+  }
+
+  // The definition of the destructor of the field with index `idx`, given
+  // references to the codatatype and to the destructors before it
+  pub fn dtor_def(&self, idx: usize, data: &Term, dtors: &[Term]) -> Def {
+    let n = self.params.len() as u64;
+    let field = &self.fields[idx];
+    let t = idx as u64;
+    // Bind `x` between the fields before this one and the parameters, then
+    // replace these fields, innermost first, with their observations of `x`
+    let mut typ = field.typ.subst(t + n, data).shift(1, t);
+    for j in (0..t).rev() {
+      let mut args = self.param_vars(j + 1);
+      args.push(var("x", j));
+      let obs = apps(dtors[j as usize].clone(), args);
+      typ = typ.subst(0, &obs.shift(1, 0)).shift(-1, 0);
+    }
+    let x = Term::All(
+      None,
+      Uses::Many,
+      Name::from("x"),
+      Box::new((apps(data.clone(), self.param_vars(0)), typ)),
+    );
+    let typ_ = alls(self.params.clone(), x);
+    let mut binders = names(&self.params);
+    binders.push(Name::from("x"));
+    let term = lams(binders, self.observe(idx));
+    Def::new(field.pos, self.field_name(field), String::new(), typ_, term)
+  }
+
+  // The codatatype's definition followed by those of its destructors
+  pub fn defs(&self) -> Vec<Def> {
+    let link = |def: &Def| {
+      let (d, _, a) = def.clone().embed();
+      Term::Ref(None, def.name.clone(), d.encode().link(), a.encode().link())
+    };
+    let def = self.type_def();
+    let data = link(&def);
+    let mut defs = vec![def];
+    let mut dtors = Vec::new();
+    for idx in 0..self.fields.len() {
+      let def = self.dtor_def(idx, &data, &dtors);
+      dtors.push(link(&def));
+      defs.push(def);
+    }
+    defs
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      check::check_def,
+      dag::DAG,
+      eval,
+      nbe,
+      termination::check_termination,
+    },
+    parse::{
+      span::Span,
+      term::parse_expression,
+    },
+    term::{
+      Defs,
+      Refs,
+    },
+  };
+  use im::{
+    HashMap,
+    Vector,
+  };
+
+  // Parse field types in the scope of the fields before them, of the
+  // parameters and of the codatatype
+  pub fn codatatype(
+    name: &str,
+    params: &[(&str, &str)],
+    sort: &str,
+    fields: &[(&str, &str)],
+  ) -> Result<Codatatype, DataError> {
+    let parse = |ctx: &Vector<Name>, i: &str| {
+      let expr = parse_expression(HashMap::new(), HashMap::new(), ctx.clone());
+      expr(Span::new(i)).unwrap().1
+    };
+    let mut ctx = Vector::new();
+    let mut tele = Vec::new();
+    for (nam, typ) in params {
+      tele.push((Uses::Many, Name::from(*nam), parse(&ctx, typ)));
+      ctx.push_front(Name::from(*nam));
+    }
+    let sort = parse(&ctx, sort);
+    ctx.push_back(Name::from(name));
+    let mut fs = Vec::new();
+    for (nam, typ) in fields {
+      fs.push((None, Name::from(*nam), parse(&ctx, typ)));
+      ctx.push_front(Name::from(*nam));
+    }
+    Codatatype::new(None, Name::from(name), tele, sort, fs)
+  }
+
+  fn stream() -> Codatatype {
+    codatatype("Stream", &[("A", "Type")], "Type", &[
+      ("head", "A"),
+      ("tail", "Stream A"),
+    ])
+    .unwrap()
+  }
+
+  #[test]
+  fn elaborates() {
+    let stream = stream();
+    assert_eq!(
+      format!("{}", stream.type_def().term),
+      "λ A => Σ (head: A) -> Stream A"
+    );
+    let defs = stream.defs();
+    assert_eq!(defs[1].name, "Stream.head");
+    assert_eq!(format!("{}", defs[1].term), "λ A x => fst x");
+    assert_eq!(
+      format!("{}", defs[2].typ_),
+      "∀ (A: Type) (x: Stream A) -> Stream A"
+    );
+    assert_eq!(format!("{}", defs[2].term), "λ A x => snd x");
+    // Later fields see the earlier ones through their destructors
+    let sized = codatatype("Sized", &[], "Type@1", &[
+      ("size", "#Natural"),
+      ("kind", "Type"),
+      ("elem", "kind"),
+    ])
+    .unwrap();
+    let defs = sized.defs();
+    assert_eq!(format!("{}", defs[2].term), "λ x => fst (snd x)");
+    assert_eq!(format!("{}", defs[3].typ_), "∀ (x: Sized) -> Sized.kind x");
+    assert_eq!(format!("{}", defs[3].term), "λ x => snd (snd x)");
+    let (mut defs, mut refs) = (HashMap::new(), HashMap::new());
+    for def in sized.defs() {
+      define(&mut defs, &mut refs, def);
+    }
+  }
+
+  #[test]
+  fn rejects() {
+    let res = codatatype("Empty", &[], "Type", &[]);
+    assert_eq!(res, Err(DataError::NoFields(None)));
+    let res = codatatype("Pair", &[], "Type", &[("x", "Type"), ("x", "x")]);
+    assert_eq!(res, Err(DataError::DuplicateField(None, String::from("x"))));
+    let res = codatatype("Stream", &[], "#Natural", &[("head", "#Natural")]);
+    assert!(matches!(res, Err(DataError::NotAUniverse(..))));
+  }
+
+  // Check a definition and add it to `defs` and `refs`
+  fn define(defs: &mut Defs, refs: &mut Refs, def: Def) {
+    assert_eq!(check_def(defs, &def), Ok(()));
+    let (d, _, a) = def.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    refs.insert(def.name.clone(), (def_link, ast_link));
+    defs.insert(def_link, def);
+  }
+
+  #[test]
+  fn streams() {
+    let mut defs = HashMap::new();
+    let mut refs = HashMap::new();
+    for def in stream().defs() {
+      define(&mut defs, &mut refs, def);
+    }
+    let parse = |refs: &Refs, ctx: &[&str], i: &str| {
+      let ctx = ctx.iter().map(|nam| Name::from(*nam)).collect();
+      let expr = parse_expression(refs.clone(), HashMap::new(), ctx);
+      expr(Span::new(i)).unwrap().1
+    };
+    let typ_ = parse(&refs, &[], "∀ (n: #Natural) -> Stream #Natural");
+    let term = parse(&refs, &["nats"], "λ n => (n, nats (#add n 1))");
+    let nats = Def::new(None, Name::from("nats"), String::new(), typ_, term);
+    assert_eq!(check_termination(&nats), Ok(()));
+    define(&mut defs, &mut refs, nats);
+    let input = "Stream.head #Natural (Stream.tail #Natural (Stream.tail \
+                 #Natural (nats 0)))";
+    let term = parse(&refs, &[], input);
+    assert_eq!(format!("{}", nbe::norm(&defs, &term)), "2");
+    let dag = eval::norm(&defs, DAG::from_term(term).unwrap());
+    assert_eq!(format!("{}", dag), "2");
+  }
+}
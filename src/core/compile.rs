@@ -0,0 +1,1498 @@
+use crate::{
+  core::{
+    check::{
+      CheckError,
+      Ctx,
+    },
+    literal::{
+      LitType,
+      Literal,
+    },
+    nbe::Value,
+    primop::PrimOp,
+    uses::Uses,
+  },
+  name::Name,
+  term::{
+    Defs,
+    Link,
+    Term,
+  },
+};
+
+use std::{
+  collections::{
+    BTreeSet,
+    HashMap,
+  },
+  convert::TryInto,
+  fmt,
+};
+
+// Compilation of checked definitions to WebAssembly. Each definition is
+// erased, and its runtime term compiled to a module which exports the chosen
+// definitions as functions of numbers and characters, so that they can be
+// called from a browser or any other WebAssembly host.
+//
+// Values live in the module's memory, each as a tagged object: a closure or
+// a suspended computation is the index of its code in the function table
+// followed by the values of its free variables, a pair holds its two
+// components, and literals hold their contents. Evaluation is lazy, as that
+// of the evaluator: arguments, `let` bindings and the components of pairs
+// are suspended, and a suspension is overwritten with an indirection to its
+// value once it has been forced, so that its work is shared. Definitions are
+// suspensions in the module's data, which are forced at most once.
+//
+// Naturals and integers are 64 bits wide, and an operation whose result does
+// not fit, as a literal which does not, traps rather than wrap around, as
+// does a `#pre` or `#sub` below zero. The bitwise operations are those of
+// bitstrings, and shifts those of numbers. Memory is allocated from the end
+// of the module's data and never freed, which suits the short runs of a call
+// from a host.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+  // A definition which does not typecheck, and so cannot be erased
+  Check(String, CheckError),
+  UndefinedReference(String, Link),
+  // A literal whose runtime representation it does not fit
+  LiteralTooLarge(Literal),
+  // An exported definition whose type takes or returns something other than
+  // naturals, integers and characters, and its type
+  NotExportable(String, Term),
+  // A term with no runtime form, such as a hole
+  Unsupported(Term),
+}
+
+impl fmt::Display for CompileError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Check(nam, err) => write!(f, "In {}: {}", nam, err),
+      Self::UndefinedReference(nam, link) => {
+        write!(f, "Undefined reference {} to {}", nam, link)
+      }
+      Self::LiteralTooLarge(lit) => {
+        write!(f, "The literal {} does not fit in 64 bits", lit)
+      }
+      Self::NotExportable(nam, typ) => write!(
+        f,
+        "Cannot export {} of type {}: only functions of #Natural, #Integer \
+         and #Char to one of them can be exported",
+        nam, typ
+      ),
+      Self::Unsupported(term) => write!(f, "Cannot compile {}", term),
+    }
+  }
+}
+
+impl std::error::Error for CompileError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Check(_, e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+// The tags of runtime objects
+const CLO: i32 = 0;
+const PAR: i32 = 1;
+const NAT: i32 = 2;
+const INT: i32 = 3;
+const CHR: i32 = 4;
+const TXT: i32 = 5;
+const BIT: i32 = 6;
+const TYP: i32 = 7;
+// A suspension, one being forced, and one which has been
+const THK: i32 = 8;
+const BLK: i32 = 9;
+const IND: i32 = 10;
+
+// Where the module's data starts, leaving address 0 unused
+const DATA_BASE: u32 = 8;
+
+// The indices of the runtime's functions, which come first in the module,
+// followed by those of the primitive operations in the order of `OPS`
+const ALLOC: u32 = 0;
+const FORCE: u32 = 1;
+const APPLY: u32 = 2;
+const CMP: u32 = 3;
+const BOOL: u32 = 4;
+const NUM: u32 = 5;
+const CHAR: u32 = 6;
+const BYTES: u32 = 7;
+const COPY: u32 = 8;
+const FST: u32 = 9;
+const SND: u32 = 10;
+const PRIMS: u32 = 11;
+
+const OPS: [PrimOp; 20] = [
+  PrimOp::Eql,
+  PrimOp::Lth,
+  PrimOp::Lte,
+  PrimOp::Gth,
+  PrimOp::Gte,
+  PrimOp::Bor,
+  PrimOp::And,
+  PrimOp::Xor,
+  PrimOp::Not,
+  PrimOp::Suc,
+  PrimOp::Pre,
+  PrimOp::Add,
+  PrimOp::Sub,
+  PrimOp::Mul,
+  PrimOp::Div,
+  PrimOp::Mod,
+  PrimOp::Shl,
+  PrimOp::Shr,
+  PrimOp::Len,
+  PrimOp::Cat,
+];
+
+// The functions of the table, which are the code of closures, suspensions
+// and definitions, come after the runtime's
+const TABLE_BASE: u32 = PRIMS + OPS.len() as u32;
+
+// Value types
+const I32: u8 = 0x7f;
+const I64: u8 = 0x7e;
+
+// Block types
+const EMPTY: u8 = 0x40;
+
+mod op {
+  pub const UNREACHABLE: u8 = 0x00;
+  pub const BLOCK: u8 = 0x02;
+  pub const LOOP: u8 = 0x03;
+  pub const IF: u8 = 0x04;
+  pub const ELSE: u8 = 0x05;
+  pub const END: u8 = 0x0b;
+  pub const BR: u8 = 0x0c;
+  pub const BR_IF: u8 = 0x0d;
+  pub const RETURN: u8 = 0x0f;
+  pub const CALL: u8 = 0x10;
+  pub const CALL_INDIRECT: u8 = 0x11;
+  pub const SELECT: u8 = 0x1b;
+  pub const LOCAL_GET: u8 = 0x20;
+  pub const LOCAL_SET: u8 = 0x21;
+  pub const LOCAL_TEE: u8 = 0x22;
+  pub const GLOBAL_GET: u8 = 0x23;
+  pub const GLOBAL_SET: u8 = 0x24;
+  pub const I32_LOAD: u8 = 0x28;
+  pub const I64_LOAD: u8 = 0x29;
+  pub const I32_LOAD8_U: u8 = 0x2d;
+  pub const I32_STORE: u8 = 0x36;
+  pub const I64_STORE: u8 = 0x37;
+  pub const I32_STORE8: u8 = 0x3a;
+  pub const MEMORY_SIZE: u8 = 0x3f;
+  pub const MEMORY_GROW: u8 = 0x40;
+  pub const I32_CONST: u8 = 0x41;
+  pub const I64_CONST: u8 = 0x42;
+  pub const I32_EQZ: u8 = 0x45;
+  pub const I32_EQ: u8 = 0x46;
+  pub const I32_NE: u8 = 0x47;
+  pub const I32_LT_S: u8 = 0x48;
+  pub const I32_LT_U: u8 = 0x49;
+  pub const I32_GT_S: u8 = 0x4a;
+  pub const I32_GT_U: u8 = 0x4b;
+  pub const I32_LE_S: u8 = 0x4c;
+  pub const I32_LE_U: u8 = 0x4d;
+  pub const I32_GE_S: u8 = 0x4e;
+  pub const I32_GE_U: u8 = 0x4f;
+  pub const I64_EQZ: u8 = 0x50;
+  pub const I64_EQ: u8 = 0x51;
+  pub const I64_NE: u8 = 0x52;
+  pub const I64_LT_S: u8 = 0x53;
+  pub const I64_LT_U: u8 = 0x54;
+  pub const I64_GT_S: u8 = 0x55;
+  pub const I64_GT_U: u8 = 0x56;
+  pub const I64_GE_U: u8 = 0x5a;
+  pub const I32_ADD: u8 = 0x6a;
+  pub const I32_SUB: u8 = 0x6b;
+  pub const I32_AND: u8 = 0x71;
+  pub const I32_OR: u8 = 0x72;
+  pub const I32_XOR: u8 = 0x73;
+  pub const I32_SHL: u8 = 0x74;
+  pub const I32_SHR_U: u8 = 0x76;
+  pub const I64_CLZ: u8 = 0x79;
+  pub const I64_ADD: u8 = 0x7c;
+  pub const I64_SUB: u8 = 0x7d;
+  pub const I64_MUL: u8 = 0x7e;
+  pub const I64_DIV_S: u8 = 0x7f;
+  pub const I64_DIV_U: u8 = 0x80;
+  pub const I64_REM_S: u8 = 0x81;
+  pub const I64_REM_U: u8 = 0x82;
+  pub const I64_AND: u8 = 0x83;
+  pub const I64_XOR: u8 = 0x85;
+  pub const I64_SHL: u8 = 0x86;
+  pub const I64_SHR_S: u8 = 0x87;
+  pub const I64_SHR_U: u8 = 0x88;
+  pub const I64_EXTEND_I32_U: u8 = 0xad;
+}
+
+fn uleb(out: &mut Vec<u8>, mut x: u64) {
+  loop {
+    let byte = (x & 0x7f) as u8;
+    x >>= 7;
+    if x == 0 {
+      out.push(byte);
+      return;
+    }
+    out.push(byte | 0x80);
+  }
+}
+
+fn sleb(out: &mut Vec<u8>, mut x: i64) {
+  loop {
+    let byte = (x & 0x7f) as u8;
+    x >>= 7;
+    if (x == 0 && byte & 0x40 == 0) || (x == -1 && byte & 0x40 != 0) {
+      out.push(byte);
+      return;
+    }
+    out.push(byte | 0x80);
+  }
+}
+
+// A vector of the binary format: its length followed by its items
+fn vec(out: &mut Vec<u8>, len: usize, items: &[u8]) {
+  uleb(out, len as u64);
+  out.extend_from_slice(items);
+}
+
+// The instructions of a function body
+#[derive(Clone, Debug, Default)]
+struct Code(Vec<u8>);
+
+impl Code {
+  fn op(&mut self, op: u8) -> &mut Self {
+    self.0.push(op);
+    self
+  }
+
+  fn idx(&mut self, op: u8, idx: u32) -> &mut Self {
+    self.0.push(op);
+    uleb(&mut self.0, idx as u64);
+    self
+  }
+
+  fn block(&mut self, op: u8, typ: u8) -> &mut Self {
+    self.0.push(op);
+    self.0.push(typ);
+    self
+  }
+
+  // A load or store with its natural alignment
+  fn mem(&mut self, op: u8, offset: u32) -> &mut Self {
+    let align = match op {
+      op::I64_LOAD | op::I64_STORE => 3,
+      op::I32_LOAD8_U | op::I32_STORE8 => 0,
+      _ => 2,
+    };
+    self.0.push(op);
+    uleb(&mut self.0, align);
+    uleb(&mut self.0, offset as u64);
+    self
+  }
+
+  fn i32(&mut self, x: i32) -> &mut Self {
+    self.0.push(op::I32_CONST);
+    sleb(&mut self.0, x as i64);
+    self
+  }
+
+  fn i64(&mut self, x: i64) -> &mut Self {
+    self.0.push(op::I64_CONST);
+    sleb(&mut self.0, x);
+    self
+  }
+
+  fn get(&mut self, local: u32) -> &mut Self { self.idx(op::LOCAL_GET, local) }
+
+  fn set(&mut self, local: u32) -> &mut Self { self.idx(op::LOCAL_SET, local) }
+
+  fn tee(&mut self, local: u32) -> &mut Self { self.idx(op::LOCAL_TEE, local) }
+
+  fn call(&mut self, fun: u32) -> &mut Self { self.idx(op::CALL, fun) }
+
+  // Call the code of the closure or suspension on the stack below its index
+  fn call_code(&mut self) -> &mut Self {
+    self.op(op::CALL_INDIRECT).op(0).op(0)
+  }
+
+  fn memory(&mut self, op: u8) -> &mut Self { self.op(op).op(0) }
+
+  // Trap unless the condition on the stack holds
+  fn assert(&mut self) -> &mut Self {
+    self.op(op::I32_EQZ).block(op::IF, EMPTY).op(op::UNREACHABLE).op(op::END)
+  }
+
+  // Trap if the condition on the stack holds
+  fn refute(&mut self) -> &mut Self {
+    self.block(op::IF, EMPTY).op(op::UNREACHABLE).op(op::END)
+  }
+
+  // Trap unless the object in `local` has the given tag
+  fn tagged(&mut self, local: u32, tag: i32) -> &mut Self {
+    self.get(local).mem(op::I32_LOAD, 0).i32(tag).op(op::I32_EQ).assert()
+  }
+
+  // Run `body` for each `i` from 0 to `n`, at a depth of two blocks
+  fn each(
+    &mut self,
+    i: u32,
+    n: u32,
+    body: impl FnOnce(&mut Self),
+  ) -> &mut Self {
+    self.i32(0).set(i);
+    self.block(op::BLOCK, EMPTY).block(op::LOOP, EMPTY);
+    self.get(i).get(n).op(op::I32_GE_U).idx(op::BR_IF, 1);
+    body(self);
+    self.get(i).i32(1).op(op::I32_ADD).set(i).idx(op::BR, 0);
+    self.op(op::END).op(op::END)
+  }
+}
+
+// A function of the module, with its extra locals
+#[derive(Clone, Debug)]
+struct Fun {
+  params: Vec<u8>,
+  results: Vec<u8>,
+  locals: Vec<u8>,
+  code: Code,
+}
+
+impl Fun {
+  fn new(params: &[u8], results: &[u8], locals: &[u8]) -> Self {
+    Fun {
+      params: params.to_vec(),
+      results: results.to_vec(),
+      locals: locals.to_vec(),
+      code: Code::default(),
+    }
+  }
+
+  // The code of a closure or suspension, which is given the object it is the
+  // code of and the argument it is applied to, if any
+  fn code() -> Self { Fun::new(&[I32, I32], &[I32], &[]) }
+
+  // A fresh local
+  fn local(&mut self, typ: u8) -> u32 {
+    self.locals.push(typ);
+    (self.params.len() + self.locals.len() - 1) as u32
+  }
+
+  fn encode(&self) -> Vec<u8> {
+    let mut groups: Vec<(u32, u8)> = Vec::new();
+    for typ in &self.locals {
+      match groups.last_mut() {
+        Some((count, last)) if last == typ => *count += 1,
+        _ => groups.push((1, *typ)),
+      }
+    }
+    let mut body = Vec::new();
+    uleb(&mut body, groups.len() as u64);
+    for (count, typ) in groups {
+      uleb(&mut body, count as u64);
+      body.push(typ);
+    }
+    body.extend_from_slice(&self.code.0);
+    body.push(op::END);
+    let mut out = Vec::new();
+    vec(&mut out, body.len(), &body);
+    out
+  }
+}
+
+// Where the value of a variable is found in the code of a closure
+#[derive(Clone, Copy, Debug)]
+enum Loc {
+  // The argument the closure is applied to
+  Arg,
+  // A free variable, stored in the closure
+  Env(u32),
+  Local(u32),
+  // A definition, at its address in the module's data
+  Static(u32),
+  // A variable the code does not refer to
+  Dead,
+}
+
+// The variables of a runtime term which are free under `depth` binders, by
+// their de Bruijn indices outside of them
+fn free_vars(term: &Term, depth: u64, free: &mut BTreeSet<u64>) {
+  match term {
+    Term::Var(_, _, idx) if *idx >= depth => {
+      free.insert(idx - depth);
+    }
+    Term::Lam(_, _, bod) => free_vars(bod, depth + 1, free),
+    Term::App(_, terms) | Term::Par(_, terms) | Term::Ann(_, terms) => {
+      free_vars(&terms.0, depth, free);
+      free_vars(&terms.1, depth, free);
+    }
+    Term::Fst(_, bod)
+    | Term::Snd(_, bod)
+    | Term::Dat(_, bod)
+    | Term::Cse(_, bod) => free_vars(bod, depth, free),
+    Term::Let(_, rec, _, _, terms) => {
+      free_vars(&terms.1, if *rec { depth + 1 } else { depth }, free);
+      free_vars(&terms.2, depth + 1, free);
+    }
+    _ => (),
+  }
+}
+
+// Eta-expand a primitive operation applied to fewer arguments than it takes
+fn saturate(opr: &Term, arity: u64, args: &[&Term]) -> Term {
+  let missing = arity - args.len() as u64;
+  let mut term = opr.clone();
+  for arg in args {
+    term = Term::App(None, Box::new((term, arg.shift(missing as i64, 0))));
+  }
+  for idx in (0..missing).rev() {
+    let var = Term::Var(None, Name::from(format!("x{}", idx)), idx);
+    term = Term::App(None, Box::new((term, var)));
+  }
+  for idx in 0..missing {
+    term = Term::Lam(None, Name::from(format!("x{}", idx)), Box::new(term));
+  }
+  term
+}
+
+// The type of a parameter or result of an exported function
+fn export_type(lit: LitType) -> Option<u8> {
+  match lit {
+    LitType::Natural | LitType::Integer => Some(I64),
+    LitType::Char => Some(I32),
+    _ => None,
+  }
+}
+
+// The relevant parameters and the result of an exported definition's type
+fn signature(
+  defs: &Defs,
+  nam: &str,
+  typ: &Term,
+) -> Result<(Vec<LitType>, LitType), CompileError> {
+  let err = || CompileError::NotExportable(String::from(nam), typ.clone());
+  let mut ctx = Ctx::new(defs.clone());
+  let mut val = ctx.eval(typ);
+  let mut params = Vec::new();
+  loop {
+    match ctx.force(val) {
+      Value::All(uses, nam, dom, img) => {
+        let var = Value::Var(nam.clone(), ctx.depth());
+        if uses != Uses::None && !ctx.is_prop(&dom) {
+          match ctx.force((*dom).clone()) {
+            Value::LTy(lit) if export_type(lit).is_some() => params.push(lit),
+            _ => return Err(err()),
+          }
+        }
+        val = img.apply(&ctx.defs, var);
+        ctx = ctx.bind(&nam, (*dom).clone());
+      }
+      Value::LTy(lit) if export_type(lit).is_some() => {
+        return Ok((params, lit));
+      }
+      _ => return Err(err()),
+    }
+  }
+}
+
+struct Compiler<'a> {
+  defs: &'a Defs,
+  // The functions of the table, those of definitions only once compiled
+  table: Vec<Option<Fun>>,
+  data: Vec<u8>,
+  // The address of the suspension of each definition referred to
+  refs: HashMap<Link, u32>,
+  // The definitions referred to but not yet compiled, with the indices of
+  // their code and their addresses
+  pending: Vec<(String, Link, u32, u32)>,
+  typ: u32,
+  ff: u32,
+  tt: u32,
+}
+
+impl<'a> Compiler<'a> {
+  fn new(defs: &'a Defs) -> Self {
+    let mut compiler = Compiler {
+      defs,
+      table: Vec::new(),
+      data: Vec::new(),
+      refs: HashMap::new(),
+      pending: Vec::new(),
+      typ: 0,
+      ff: 0,
+      tt: 0,
+    };
+    compiler.typ = compiler.object(TYP, &[]);
+    compiler.ff = compiler.bytes(BIT, &[0]);
+    compiler.tt = compiler.bytes(BIT, &[1]);
+    compiler
+  }
+
+  // Place an object with the given tag and contents in the module's data,
+  // returning its address
+  fn object(&mut self, tag: i32, contents: &[u8]) -> u32 {
+    self.data.resize((self.data.len() + 7) & !7, 0);
+    let addr = DATA_BASE + self.data.len() as u32;
+    self.data.extend_from_slice(&tag.to_le_bytes());
+    self.data.extend_from_slice(contents);
+    addr
+  }
+
+  fn bytes(&mut self, tag: i32, bytes: &[u8]) -> u32 {
+    let mut contents = (bytes.len() as u32).to_le_bytes().to_vec();
+    contents.extend_from_slice(bytes);
+    self.object(tag, &contents)
+  }
+
+  fn literal(&mut self, lit: &Literal) -> Result<u32, CompileError> {
+    let too_large = || CompileError::LiteralTooLarge(lit.clone());
+    match lit {
+      Literal::Natural(x) => {
+        let x: u64 = x.try_into().map_err(|_| too_large())?;
+        let mut contents = vec![0; 4];
+        contents.extend_from_slice(&x.to_le_bytes());
+        Ok(self.object(NAT, &contents))
+      }
+      Literal::Integer(x) => {
+        let x: i64 = x.try_into().map_err(|_| too_large())?;
+        let mut contents = vec![0; 4];
+        contents.extend_from_slice(&x.to_le_bytes());
+        Ok(self.object(INT, &contents))
+      }
+      Literal::Char(x) => Ok(self.object(CHR, &(*x as u32).to_le_bytes())),
+      Literal::Text(x) => Ok(self.bytes(TXT, x.as_bytes())),
+      Literal::BitString(x) => Ok(self.bytes(BIT, x)),
+      // The runtime has no floats
+      Literal::Float(_) => {
+        Err(CompileError::Unsupported(Term::Lit(None, lit.clone())))
+      }
+    }
+  }
+
+  // The address of a definition's suspension, scheduling it for compilation
+  // the first time it is referred to
+  fn reference(&mut self, nam: &str, link: Link) -> u32 {
+    if let Some(addr) = self.refs.get(&link) {
+      return *addr;
+    }
+    let idx = self.table.len() as u32;
+    self.table.push(None);
+    let addr = self.object(THK, &idx.to_le_bytes());
+    self.refs.insert(link, addr);
+    self.pending.push((String::from(nam), link, idx, addr));
+    addr
+  }
+
+  fn compile_pending(&mut self) -> Result<(), CompileError> {
+    let defs = self.defs;
+    while let Some((nam, link, idx, addr)) = self.pending.pop() {
+      let def = defs
+        .get(&link)
+        .ok_or(CompileError::UndefinedReference(nam, link))?;
+      let term = def
+        .erase(defs)
+        .map_err(|e| CompileError::Check(def.name.to_string(), e))?;
+      let mut fun = Fun::code();
+      self.value(&mut fun, &[Loc::Static(addr)], &term)?;
+      self.table[idx as usize] = Some(fun);
+    }
+    Ok(())
+  }
+
+  fn load(
+    &self,
+    fun: &mut Fun,
+    loc: Loc,
+    term: &Term,
+  ) -> Result<(), CompileError> {
+    match loc {
+      Loc::Arg => fun.code.get(1),
+      Loc::Env(slot) => fun.code.get(0).mem(op::I32_LOAD, 8 + 4 * slot),
+      Loc::Local(local) => fun.code.get(local),
+      Loc::Static(addr) => fun.code.i32(addr as i32),
+      Loc::Dead => return Err(CompileError::Unsupported(term.clone())),
+    };
+    Ok(())
+  }
+
+  fn var(
+    &self,
+    fun: &mut Fun,
+    ctx: &[Loc],
+    term: &Term,
+  ) -> Result<(), CompileError> {
+    match term {
+      Term::Var(_, _, idx) if (*idx as usize) < ctx.len() => {
+        self.load(fun, ctx[ctx.len() - 1 - *idx as usize], term)
+      }
+      _ => Err(CompileError::Unsupported(term.clone())),
+    }
+  }
+
+  // Allocate a closure or suspension of `term`, with the values of its free
+  // variables, into `obj` or else a fresh local, which is returned. The term
+  // is the body of a lambda when `lam` is set.
+  fn closure(
+    &mut self,
+    fun: &mut Fun,
+    ctx: &[Loc],
+    tag: i32,
+    term: &Term,
+    lam: bool,
+    obj: Option<u32>,
+  ) -> Result<u32, CompileError> {
+    let mut free = BTreeSet::new();
+    free_vars(term, if lam { 1 } else { 0 }, &mut free);
+    let mut inner = vec![Loc::Dead; ctx.len()];
+    let mut captured = Vec::new();
+    for idx in free {
+      if idx as usize >= ctx.len() {
+        return Err(CompileError::Unsupported(term.clone()));
+      }
+      let pos = ctx.len() - 1 - idx as usize;
+      inner[pos] = match ctx[pos] {
+        Loc::Static(addr) => Loc::Static(addr),
+        loc => {
+          captured.push(loc);
+          Loc::Env(captured.len() as u32 - 1)
+        }
+      };
+    }
+    if lam {
+      inner.push(Loc::Arg);
+    }
+    let mut code = Fun::code();
+    self.value(&mut code, &inner, term)?;
+    let idx = self.table.len() as u32;
+    self.table.push(Some(code));
+    let obj = obj.unwrap_or_else(|| fun.local(I32));
+    fun.code.i32(8 + 4 * captured.len() as i32).call(ALLOC).set(obj);
+    fun.code.get(obj).i32(tag).mem(op::I32_STORE, 0);
+    fun.code.get(obj).i32(idx as i32).mem(op::I32_STORE, 4);
+    for (slot, loc) in captured.into_iter().enumerate() {
+      fun.code.get(obj);
+      self.load(fun, loc, term)?;
+      fun.code.mem(op::I32_STORE, 8 + 4 * slot as u32);
+    }
+    Ok(obj)
+  }
+
+  // Compile a term to code which leaves its value, possibly suspended, on
+  // the stack
+  fn delay(
+    &mut self,
+    fun: &mut Fun,
+    ctx: &[Loc],
+    term: &Term,
+  ) -> Result<(), CompileError> {
+    match term {
+      Term::Var(..) => self.var(fun, ctx, term),
+      Term::Lam(..)
+      | Term::Lit(..)
+      | Term::Opr(..)
+      | Term::Typ(..)
+      | Term::LTy(..)
+      | Term::All(..)
+      | Term::Slf(..)
+      | Term::Sig(..)
+      | Term::Prp(..) => self.value(fun, ctx, term),
+      Term::Ref(_, nam, link, _) => {
+        let addr = self.reference(nam, *link);
+        fun.code.i32(addr as i32);
+        Ok(())
+      }
+      _ => {
+        let obj = self.closure(fun, ctx, THK, term, false, None)?;
+        fun.code.get(obj);
+        Ok(())
+      }
+    }
+  }
+
+  // Compile a term to code which leaves its value, evaluated to weak head
+  // normal form, on the stack
+  fn value(
+    &mut self,
+    fun: &mut Fun,
+    ctx: &[Loc],
+    term: &Term,
+  ) -> Result<(), CompileError> {
+    match term {
+      Term::Var(..) => {
+        self.var(fun, ctx, term)?;
+        fun.code.call(FORCE);
+      }
+      Term::Lam(_, _, bod) => {
+        let obj = self.closure(fun, ctx, CLO, bod, true, None)?;
+        fun.code.get(obj);
+      }
+      Term::App(..) => {
+        let mut head = term;
+        let mut args = Vec::new();
+        while let Term::App(_, terms) = head {
+          args.push(&terms.1);
+          head = &terms.0;
+        }
+        args.reverse();
+        match head {
+          Term::Opr(_, opr) if (args.len() as u64) < opr.arity() => {
+            return self.value(fun, ctx, &saturate(head, opr.arity(), &args));
+          }
+          Term::Opr(_, opr) => {
+            let arity = opr.arity() as usize;
+            for arg in &args[..arity] {
+              self.value(fun, ctx, arg)?;
+            }
+            let idx = OPS.iter().position(|x| x == opr).unwrap() as u32;
+            fun.code.call(PRIMS + idx);
+            for arg in &args[arity..] {
+              self.delay(fun, ctx, arg)?;
+              fun.code.call(APPLY);
+            }
+          }
+          _ => {
+            self.value(fun, ctx, head)?;
+            for arg in args {
+              self.delay(fun, ctx, arg)?;
+              fun.code.call(APPLY);
+            }
+          }
+        }
+      }
+      Term::Let(_, rec, _, _, terms) => {
+        let local = fun.local(I32);
+        let mut bod_ctx = ctx.to_vec();
+        bod_ctx.push(Loc::Local(local));
+        if *rec {
+          match &terms.1 {
+            Term::Lam(_, _, bod) => {
+              self.closure(fun, &bod_ctx, CLO, bod, true, Some(local))?
+            }
+            exp => self.closure(fun, &bod_ctx, THK, exp, false, Some(local))?,
+          };
+        }
+        else {
+          self.delay(fun, ctx, &terms.1)?;
+          fun.code.set(local);
+        }
+        self.value(fun, &bod_ctx, &terms.2)?;
+      }
+      Term::Par(_, terms) => {
+        let obj = fun.local(I32);
+        fun.code.i32(12).call(ALLOC).set(obj);
+        fun.code.get(obj).i32(PAR).mem(op::I32_STORE, 0).get(obj);
+        self.delay(fun, ctx, &terms.0)?;
+        fun.code.mem(op::I32_STORE, 4).get(obj);
+        self.delay(fun, ctx, &terms.1)?;
+        fun.code.mem(op::I32_STORE, 8).get(obj);
+      }
+      Term::Fst(_, bod) => {
+        self.value(fun, ctx, bod)?;
+        fun.code.call(FST);
+      }
+      Term::Snd(_, bod) => {
+        self.value(fun, ctx, bod)?;
+        fun.code.call(SND);
+      }
+      Term::Ref(_, nam, link, _) => {
+        let addr = self.reference(nam, *link);
+        fun.code.i32(addr as i32).call(FORCE);
+      }
+      Term::Lit(_, lit) => {
+        let addr = self.literal(lit)?;
+        fun.code.i32(addr as i32);
+      }
+      Term::Opr(_, opr) => {
+        return self.value(fun, ctx, &saturate(term, opr.arity(), &[]));
+      }
+      Term::Typ(..)
+      | Term::LTy(..)
+      | Term::All(..)
+      | Term::Slf(..)
+      | Term::Sig(..)
+      | Term::Prp(..) => {
+        fun.code.i32(self.typ as i32);
+      }
+      Term::Ann(_, terms) => return self.value(fun, ctx, &terms.1),
+      Term::Dat(_, bod) | Term::Cse(_, bod) => {
+        return self.value(fun, ctx, bod);
+      }
+      Term::Hol(..) | Term::Met(..) => {
+        return Err(CompileError::Unsupported(term.clone()));
+      }
+    }
+    Ok(())
+  }
+
+  // A function calling an exported definition with its arguments
+  fn export(&mut self, nam: &str, link: Link) -> Result<Fun, CompileError> {
+    let defs = self.defs;
+    let undefined = CompileError::UndefinedReference(String::from(nam), link);
+    let def = defs.get(&link).ok_or(undefined)?;
+    let (params, result) = signature(defs, &def.name, &def.typ_)?;
+    let addr = self.reference(nam, link);
+    let types: Vec<u8> =
+      params.iter().filter_map(|lit| export_type(*lit)).collect();
+    let mut fun = Fun::new(&types, &[export_type(result).unwrap()], &[]);
+    let val = fun.local(I32);
+    fun.code.i32(addr as i32).call(FORCE).set(val);
+    for (local, lit) in params.iter().enumerate() {
+      fun.code.get(val);
+      match lit {
+        LitType::Char => fun.code.get(local as u32).call(CHAR),
+        LitType::Integer => fun.code.i32(INT).get(local as u32).call(NUM),
+        _ => fun.code.i32(NAT).get(local as u32).call(NUM),
+      };
+      fun.code.call(APPLY).set(val);
+    }
+    match result {
+      LitType::Char => fun.code.tagged(val, CHR).get(val).mem(op::I32_LOAD, 4),
+      LitType::Integer => {
+        fun.code.tagged(val, INT).get(val).mem(op::I64_LOAD, 8)
+      }
+      _ => fun.code.tagged(val, NAT).get(val).mem(op::I64_LOAD, 8),
+    };
+    Ok(fun)
+  }
+
+  fn finish(self, exports: Vec<(String, Fun)>) -> Vec<u8> {
+    let mut funs = runtime(self.tt, self.ff);
+    funs.extend(self.table.into_iter().map(|fun| fun.unwrap()));
+    let export_base = funs.len();
+    let mut names = Vec::new();
+    for (nam, fun) in exports {
+      names.push(nam);
+      funs.push(fun);
+    }
+    let mut types: Vec<(Vec<u8>, Vec<u8>)> = vec![(vec![I32, I32], vec![I32])];
+    let mut fun_types = Vec::new();
+    for fun in &funs {
+      let typ = (fun.params.clone(), fun.results.clone());
+      match types.iter().position(|x| *x == typ) {
+        Some(idx) => fun_types.push(idx),
+        None => {
+          fun_types.push(types.len());
+          types.push(typ);
+        }
+      }
+    }
+    let mut heap = DATA_BASE as u64 + self.data.len() as u64;
+    heap += (8 - heap % 8) % 8;
+    let table_len = funs.len() as u64 - TABLE_BASE as u64 - names.len() as u64;
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    let mut section = |id: u8, len: usize, items: Vec<u8>| {
+      let mut contents = Vec::new();
+      vec(&mut contents, len, &items);
+      module.push(id);
+      vec(&mut module, contents.len(), &contents);
+    };
+    let mut items = Vec::new();
+    for (params, results) in &types {
+      items.push(0x60);
+      vec(&mut items, params.len(), params);
+      vec(&mut items, results.len(), results);
+    }
+    section(1, types.len(), items);
+    let mut items = Vec::new();
+    for typ in &fun_types {
+      uleb(&mut items, *typ as u64);
+    }
+    section(3, fun_types.len(), items);
+    let mut items = vec![0x70, 0x01];
+    uleb(&mut items, table_len);
+    uleb(&mut items, table_len);
+    section(4, 1, items);
+    let mut items = vec![0x00];
+    uleb(&mut items, heap / 65536 + 1);
+    section(5, 1, items);
+    let mut items = vec![I32, 0x01, op::I32_CONST];
+    sleb(&mut items, heap as i64);
+    items.push(op::END);
+    section(6, 1, items);
+    let mut items = Vec::new();
+    vec(&mut items, 6, b"memory");
+    items.extend_from_slice(&[0x02, 0x00]);
+    for (idx, nam) in names.iter().enumerate() {
+      vec(&mut items, nam.len(), nam.as_bytes());
+      items.push(0x00);
+      uleb(&mut items, (export_base + idx) as u64);
+    }
+    section(7, names.len() + 1, items);
+    let mut items = vec![0x00, op::I32_CONST, 0x00, op::END];
+    uleb(&mut items, table_len);
+    for idx in 0..table_len {
+      uleb(&mut items, TABLE_BASE as u64 + idx);
+    }
+    section(9, 1, items);
+    let mut items = Vec::new();
+    for fun in &funs {
+      items.extend(fun.encode());
+    }
+    section(10, funs.len(), items);
+    let mut items = vec![0x00, op::I32_CONST];
+    sleb(&mut items, DATA_BASE as i64);
+    items.push(op::END);
+    vec(&mut items, self.data.len(), &self.data);
+    section(11, 1, items);
+    module
+  }
+}
+
+// The runtime's functions, in the order of their indices
+fn runtime(tt: u32, ff: u32) -> Vec<Fun> {
+  let mut funs = vec![
+    alloc(),
+    force(),
+    apply(),
+    cmp(),
+    boolean(tt, ff),
+    num(),
+    character(),
+    bytes(),
+    copy(),
+    proj(4),
+    proj(8),
+  ];
+  funs.extend(OPS.iter().map(|opr| prim(*opr)));
+  funs
+}
+
+// Allocate the given number of bytes, growing the memory when it is full
+fn alloc() -> Fun {
+  let mut fun = Fun::new(&[I32], &[I32], &[]);
+  let obj = fun.local(I32);
+  let c = &mut fun.code;
+  c.idx(op::GLOBAL_GET, 0).set(obj);
+  c.idx(op::GLOBAL_GET, 0).get(0).op(op::I32_ADD);
+  c.i32(7).op(op::I32_ADD).i32(-8).op(op::I32_AND).idx(op::GLOBAL_SET, 0);
+  c.block(op::BLOCK, EMPTY);
+  c.idx(op::GLOBAL_GET, 0).memory(op::MEMORY_SIZE).i32(16).op(op::I32_SHL);
+  c.op(op::I32_LE_U).idx(op::BR_IF, 0);
+  c.idx(op::GLOBAL_GET, 0).i32(65535).op(op::I32_ADD).i32(16);
+  c.op(op::I32_SHR_U).memory(op::MEMORY_SIZE).op(op::I32_SUB);
+  c.memory(op::MEMORY_GROW).i32(-1).op(op::I32_NE).idx(op::BR_IF, 0);
+  c.op(op::UNREACHABLE).op(op::END);
+  c.get(obj);
+  fun
+}
+
+// Follow indirections and run suspensions, overwriting each with an
+// indirection to its value. A suspension forced while it is being forced
+// would loop, and traps.
+fn force() -> Fun {
+  let mut fun = Fun::new(&[I32], &[I32], &[]);
+  let (tag, val) = (fun.local(I32), fun.local(I32));
+  let c = &mut fun.code;
+  c.block(op::LOOP, EMPTY);
+  c.get(0).mem(op::I32_LOAD, 0).set(tag);
+  c.get(tag).i32(IND).op(op::I32_EQ).block(op::IF, EMPTY);
+  c.get(0).mem(op::I32_LOAD, 4).set(0).idx(op::BR, 1).op(op::END);
+  c.get(tag).i32(BLK).op(op::I32_EQ).refute();
+  c.get(tag).i32(THK).op(op::I32_EQ).block(op::IF, EMPTY);
+  c.get(0).i32(BLK).mem(op::I32_STORE, 0);
+  c.get(0).i32(0).get(0).mem(op::I32_LOAD, 4).call_code().set(val);
+  c.get(0).i32(IND).mem(op::I32_STORE, 0);
+  c.get(0).get(val).mem(op::I32_STORE, 4);
+  c.get(val).op(op::RETURN).op(op::END);
+  c.op(op::END);
+  c.get(0);
+  fun
+}
+
+fn apply() -> Fun {
+  let mut fun = Fun::new(&[I32, I32], &[I32], &[]);
+  let c = &mut fun.code;
+  c.get(0).call(FORCE).set(0).tagged(0, CLO);
+  c.get(0).get(1).get(0).mem(op::I32_LOAD, 4).call_code();
+  fun
+}
+
+// Compare two literals of the same type, to -1, 0 or 1. Texts and
+// bitstrings are compared lexicographically, by their bytes.
+fn cmp() -> Fun {
+  let mut fun = Fun::new(&[I32, I32], &[I32], &[]);
+  let tag = fun.local(I32);
+  let (len, i, x, y) =
+    (fun.local(I32), fun.local(I32), fun.local(I32), fun.local(I32));
+  let c = &mut fun.code;
+  c.get(0).mem(op::I32_LOAD, 0).tee(tag).get(1).mem(op::I32_LOAD, 0);
+  c.op(op::I32_NE).refute();
+  for (tags, load, gt, lt) in &[
+    (NAT, op::I64_LOAD, op::I64_GT_U, op::I64_LT_U),
+    (INT, op::I64_LOAD, op::I64_GT_S, op::I64_LT_S),
+    (CHR, op::I32_LOAD, op::I32_GT_U, op::I32_LT_U),
+  ] {
+    let offset = if *load == op::I64_LOAD { 8 } else { 4 };
+    c.get(tag).i32(*tags).op(op::I32_EQ).block(op::IF, EMPTY);
+    c.get(0).mem(*load, offset).get(1).mem(*load, offset).op(*gt);
+    c.get(0).mem(*load, offset).get(1).mem(*load, offset).op(*lt);
+    c.op(op::I32_SUB).op(op::RETURN).op(op::END);
+  }
+  c.get(tag).i32(TXT).op(op::I32_EQ).get(tag).i32(BIT).op(op::I32_EQ);
+  c.op(op::I32_OR).assert();
+  c.get(0).mem(op::I32_LOAD, 4).get(1).mem(op::I32_LOAD, 4);
+  c.get(0).mem(op::I32_LOAD, 4).get(1).mem(op::I32_LOAD, 4);
+  c.op(op::I32_LT_U).op(op::SELECT).set(len);
+  c.each(i, len, |c| {
+    c.get(0).get(i).op(op::I32_ADD).mem(op::I32_LOAD8_U, 8).set(x);
+    c.get(1).get(i).op(op::I32_ADD).mem(op::I32_LOAD8_U, 8).set(y);
+    c.get(x).get(y).op(op::I32_NE).block(op::IF, EMPTY);
+    c.get(x).get(y).op(op::I32_GT_U).get(x).get(y).op(op::I32_LT_U);
+    c.op(op::I32_SUB).op(op::RETURN).op(op::END);
+  });
+  c.get(0).mem(op::I32_LOAD, 4).get(1).mem(op::I32_LOAD, 4);
+  c.op(op::I32_GT_U);
+  c.get(0).mem(op::I32_LOAD, 4).get(1).mem(op::I32_LOAD, 4);
+  c.op(op::I32_LT_U).op(op::I32_SUB);
+  fun
+}
+
+// The bitstring of a condition, as the results of comparisons are
+fn boolean(tt: u32, ff: u32) -> Fun {
+  let mut fun = Fun::new(&[I32], &[I32], &[]);
+  fun.code.i32(tt as i32).i32(ff as i32).get(0).op(op::SELECT);
+  fun
+}
+
+// A natural or integer, given its tag
+fn num() -> Fun {
+  let mut fun = Fun::new(&[I32, I64], &[I32], &[]);
+  let obj = fun.local(I32);
+  let c = &mut fun.code;
+  c.i32(16).call(ALLOC).tee(obj).get(0).mem(op::I32_STORE, 0);
+  c.get(obj).get(1).mem(op::I64_STORE, 8).get(obj);
+  fun
+}
+
+fn character() -> Fun {
+  let mut fun = Fun::new(&[I32], &[I32], &[]);
+  let obj = fun.local(I32);
+  let c = &mut fun.code;
+  c.i32(8).call(ALLOC).tee(obj).i32(CHR).mem(op::I32_STORE, 0);
+  c.get(obj).get(0).mem(op::I32_STORE, 4).get(obj);
+  fun
+}
+
+// A text or bitstring of the given tag and length, with its bytes left to
+// be filled
+fn bytes() -> Fun {
+  let mut fun = Fun::new(&[I32, I32], &[I32], &[]);
+  let obj = fun.local(I32);
+  let c = &mut fun.code;
+  c.get(1).i32(8).op(op::I32_ADD).call(ALLOC).tee(obj);
+  c.get(0).mem(op::I32_STORE, 0);
+  c.get(obj).get(1).mem(op::I32_STORE, 4).get(obj);
+  fun
+}
+
+// Copy a number of bytes from an address to another
+fn copy() -> Fun {
+  let mut fun = Fun::new(&[I32, I32, I32], &[], &[]);
+  let i = fun.local(I32);
+  fun.code.each(i, 2, |c| {
+    c.get(0).get(i).op(op::I32_ADD);
+    c.get(1).get(i).op(op::I32_ADD).mem(op::I32_LOAD8_U, 0);
+    c.mem(op::I32_STORE8, 0);
+  });
+  fun
+}
+
+// The component of a pair at the given offset, forced
+fn proj(offset: u32) -> Fun {
+  let mut fun = Fun::new(&[I32], &[I32], &[]);
+  fun.code.tagged(0, PAR).get(0).mem(op::I32_LOAD, offset).call(FORCE);
+  fun
+}
+
+// The locals of a primitive operation on numbers: the tag of its first
+// argument, the contents of its arguments, and its result
+struct Regs {
+  tag: u32,
+  a: u32,
+  b: u32,
+  r: u32,
+}
+
+// A primitive operation on naturals and integers, given the code computing
+// the result of each from `a` and `b` into `r`. The arguments of binary
+// operations other than shifts must be of the same type, and the amount of
+// a shift must be a natural.
+fn arith(
+  arity: usize,
+  shift: bool,
+  nat: impl FnOnce(&mut Code, &Regs),
+  int: impl FnOnce(&mut Code, &Regs),
+) -> Fun {
+  let mut fun = Fun::new(&vec![I32; arity], &[I32], &[]);
+  let regs = Regs {
+    tag: fun.local(I32),
+    a: fun.local(I64),
+    b: fun.local(I64),
+    r: fun.local(I64),
+  };
+  let c = &mut fun.code;
+  c.get(0).mem(op::I32_LOAD, 0).set(regs.tag);
+  if arity == 2 {
+    if shift {
+      c.tagged(1, NAT);
+    }
+    else {
+      c.get(regs.tag).get(1).mem(op::I32_LOAD, 0).op(op::I32_EQ).assert();
+    }
+  }
+  arith_case(c, &regs, arity, NAT, nat);
+  arith_case(c, &regs, arity, INT, int);
+  c.op(op::UNREACHABLE);
+  fun
+}
+
+// Return the result of an operation on numbers of the given tag, if its
+// first argument is one
+fn arith_case(
+  c: &mut Code,
+  regs: &Regs,
+  arity: usize,
+  tag: i32,
+  code: impl FnOnce(&mut Code, &Regs),
+) {
+  c.get(regs.tag).i32(tag).op(op::I32_EQ).block(op::IF, EMPTY);
+  c.get(0).mem(op::I64_LOAD, 8).set(regs.a);
+  if arity == 2 {
+    c.get(1).mem(op::I64_LOAD, 8).set(regs.b);
+  }
+  code(c, regs);
+  c.i32(tag).get(regs.r).call(NUM).op(op::RETURN).op(op::END);
+}
+
+// A primitive operation on the bytes of texts or bitstrings of the same
+// type, with the given tags, whose result has as many bytes as the first
+// argument, and whose bytes are computed by `byte` from the argument's
+fn bitwise(arity: usize, byte: impl Fn(&mut Code)) -> Fun {
+  let mut fun = Fun::new(&vec![I32; arity], &[I32], &[]);
+  let (len, i, obj) = (fun.local(I32), fun.local(I32), fun.local(I32));
+  let c = &mut fun.code;
+  c.tagged(0, BIT).get(0).mem(op::I32_LOAD, 4).set(len);
+  if arity == 2 {
+    c.tagged(1, BIT);
+    c.get(1).mem(op::I32_LOAD, 4).get(len).get(1).mem(op::I32_LOAD, 4);
+    c.get(len).op(op::I32_LT_U).op(op::SELECT).set(len);
+  }
+  c.i32(BIT).get(len).call(BYTES).set(obj);
+  c.each(i, len, |c| {
+    c.get(obj).get(i).op(op::I32_ADD);
+    c.get(0).get(i).op(op::I32_ADD).mem(op::I32_LOAD8_U, 8);
+    if arity == 2 {
+      c.get(1).get(i).op(op::I32_ADD).mem(op::I32_LOAD8_U, 8);
+    }
+    byte(c);
+    c.mem(op::I32_STORE8, 8);
+  });
+  c.get(obj);
+  fun
+}
+
+// The code of a primitive operation, applied to the values of its arguments
+fn prim(opr: PrimOp) -> Fun {
+  use PrimOp::*;
+  let comparison = |test: u8| {
+    let mut fun = Fun::new(&[I32, I32], &[I32], &[]);
+    fun.code.get(0).get(1).call(CMP).i32(0).op(test).call(BOOL);
+    fun
+  };
+  match opr {
+    Eql => {
+      let mut fun = Fun::new(&[I32, I32], &[I32], &[]);
+      fun.code.get(0).get(1).call(CMP).op(op::I32_EQZ).call(BOOL);
+      fun
+    }
+    Lth => comparison(op::I32_LT_S),
+    Lte => comparison(op::I32_LE_S),
+    Gth => comparison(op::I32_GT_S),
+    Gte => comparison(op::I32_GE_S),
+    Bor => bitwise(2, |c| {
+      c.op(op::I32_OR);
+    }),
+    And => bitwise(2, |c| {
+      c.op(op::I32_AND);
+    }),
+    Xor => bitwise(2, |c| {
+      c.op(op::I32_XOR);
+    }),
+    Not => bitwise(1, |c| {
+      c.i32(0xff).op(op::I32_XOR);
+    }),
+    Suc => arith(
+      1,
+      false,
+      |c, r| {
+        c.get(r.a).i64(1).op(op::I64_ADD).tee(r.r).op(op::I64_EQZ).refute();
+      },
+      |c, r| {
+        c.get(r.a).i64(i64::MAX).op(op::I64_EQ).refute();
+        c.get(r.a).i64(1).op(op::I64_ADD).set(r.r);
+      },
+    ),
+    Pre => arith(
+      1,
+      false,
+      |c, r| {
+        c.get(r.a).op(op::I64_EQZ).refute();
+        c.get(r.a).i64(1).op(op::I64_SUB).set(r.r);
+      },
+      |c, r| {
+        c.get(r.a).i64(i64::MIN).op(op::I64_EQ).refute();
+        c.get(r.a).i64(1).op(op::I64_SUB).set(r.r);
+      },
+    ),
+    Add => arith(
+      2,
+      false,
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_ADD).tee(r.r);
+        c.get(r.a).op(op::I64_LT_U).refute();
+      },
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_ADD).set(r.r);
+        c.get(r.a).get(r.r).op(op::I64_XOR).get(r.b).get(r.r).op(op::I64_XOR);
+        c.op(op::I64_AND).i64(0).op(op::I64_LT_S).refute();
+      },
+    ),
+    Sub => arith(
+      2,
+      false,
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_LT_U).refute();
+        c.get(r.a).get(r.b).op(op::I64_SUB).set(r.r);
+      },
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_SUB).set(r.r);
+        c.get(r.a).get(r.b).op(op::I64_XOR).get(r.a).get(r.r).op(op::I64_XOR);
+        c.op(op::I64_AND).i64(0).op(op::I64_LT_S).refute();
+      },
+    ),
+    // A product overflows when dividing it by a factor does not give the
+    // other, or, for integers, when the division itself overflows
+    Mul => arith(
+      2,
+      false,
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_MUL).set(r.r);
+        c.get(r.a).op(op::I64_EQZ).op(op::I32_EQZ).block(op::IF, EMPTY);
+        c.get(r.r).get(r.a).op(op::I64_DIV_U).get(r.b).op(op::I64_NE);
+        c.refute().op(op::END);
+      },
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_MUL).set(r.r);
+        c.get(r.a).op(op::I64_EQZ).op(op::I32_EQZ).block(op::IF, EMPTY);
+        c.get(r.r).get(r.a).op(op::I64_DIV_S).get(r.b).op(op::I64_NE);
+        c.refute().op(op::END);
+      },
+    ),
+    Div => arith(
+      2,
+      false,
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_DIV_U).set(r.r);
+      },
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_DIV_S).set(r.r);
+      },
+    ),
+    Mod => arith(
+      2,
+      false,
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_REM_U).set(r.r);
+      },
+      |c, r| {
+        c.get(r.a).get(r.b).op(op::I64_REM_S).set(r.r);
+      },
+    ),
+    // A left shift overflows when shifting back does not give the shifted
+    // number
+    Shl => {
+      let shl = |shr: u8| {
+        move |c: &mut Code, r: &Regs| {
+          c.get(r.b).i64(64).op(op::I64_GE_U).block(op::IF, EMPTY);
+          c.get(r.a).op(op::I64_EQZ).assert().i64(0).set(r.r);
+          c.op(op::ELSE);
+          c.get(r.a).get(r.b).op(op::I64_SHL).tee(r.r).get(r.b).op(shr);
+          c.get(r.a).op(op::I64_NE).refute();
+          c.op(op::END);
+        }
+      };
+      arith(2, true, shl(op::I64_SHR_U), shl(op::I64_SHR_S))
+    }
+    Shr => arith(
+      2,
+      true,
+      |c, r| {
+        c.get(r.b).i64(64).op(op::I64_GE_U).block(op::IF, EMPTY);
+        c.i64(0).set(r.r).op(op::ELSE);
+        c.get(r.a).get(r.b).op(op::I64_SHR_U).set(r.r).op(op::END);
+      },
+      |c, r| {
+        c.get(r.a).i64(63).get(r.b).get(r.b).i64(63).op(op::I64_GT_U);
+        c.op(op::SELECT).op(op::I64_SHR_S).set(r.r);
+      },
+    ),
+    // The number of bits of a number's magnitude, of a bitstring, of a
+    // character, or the number of characters of a text
+    Len => {
+      let mut fun = Fun::new(&[I32], &[I32], &[]);
+      let (tag, len, i, count) =
+        (fun.local(I32), fun.local(I32), fun.local(I32), fun.local(I32));
+      let a = fun.local(I64);
+      let c = &mut fun.code;
+      c.get(0).mem(op::I32_LOAD, 0).set(tag);
+      c.get(tag).i32(NAT).op(op::I32_EQ).block(op::IF, EMPTY);
+      c.i32(NAT).i64(64).get(0).mem(op::I64_LOAD, 8).op(op::I64_CLZ);
+      c.op(op::I64_SUB).call(NUM).op(op::RETURN).op(op::END);
+      c.get(tag).i32(INT).op(op::I32_EQ).block(op::IF, EMPTY);
+      c.get(0).mem(op::I64_LOAD, 8).set(a);
+      c.i32(NAT).i64(64).i64(0).get(a).op(op::I64_SUB).get(a);
+      c.get(a).i64(0).op(op::I64_LT_S).op(op::SELECT).op(op::I64_CLZ);
+      c.op(op::I64_SUB).call(NUM).op(op::RETURN).op(op::END);
+      c.get(tag).i32(CHR).op(op::I32_EQ).block(op::IF, EMPTY);
+      c.i32(NAT).i64(32).call(NUM).op(op::RETURN).op(op::END);
+      c.get(tag).i32(BIT).op(op::I32_EQ).block(op::IF, EMPTY);
+      c.i32(NAT).get(0).mem(op::I32_LOAD, 4).op(op::I64_EXTEND_I32_U);
+      c.i64(8).op(op::I64_MUL).call(NUM).op(op::RETURN).op(op::END);
+      // Count the bytes which do not continue a character
+      c.tagged(0, TXT).i32(0).set(count);
+      c.get(0).mem(op::I32_LOAD, 4).set(len);
+      c.each(i, len, |c| {
+        c.get(count);
+        c.get(0).get(i).op(op::I32_ADD).mem(op::I32_LOAD8_U, 8);
+        c.i32(0xc0).op(op::I32_AND).i32(0x80).op(op::I32_NE);
+        c.op(op::I32_ADD).set(count);
+      });
+      c.i32(NAT).get(count).op(op::I64_EXTEND_I32_U).call(NUM);
+      fun
+    }
+    Cat => {
+      let mut fun = Fun::new(&[I32, I32], &[I32], &[]);
+      let (tag, obj) = (fun.local(I32), fun.local(I32));
+      let c = &mut fun.code;
+      c.get(0).mem(op::I32_LOAD, 0).tee(tag).get(1).mem(op::I32_LOAD, 0);
+      c.op(op::I32_EQ).assert();
+      c.get(tag).i32(TXT).op(op::I32_EQ).get(tag).i32(BIT).op(op::I32_EQ);
+      c.op(op::I32_OR).assert();
+      c.get(tag).get(0).mem(op::I32_LOAD, 4).get(1).mem(op::I32_LOAD, 4);
+      c.op(op::I32_ADD).call(BYTES).set(obj);
+      c.get(obj).i32(8).op(op::I32_ADD).get(0).i32(8).op(op::I32_ADD);
+      c.get(0).mem(op::I32_LOAD, 4).call(COPY);
+      c.get(obj).i32(8).op(op::I32_ADD).get(0).mem(op::I32_LOAD, 4);
+      c.op(op::I32_ADD).get(1).i32(8).op(op::I32_ADD);
+      c.get(1).mem(op::I32_LOAD, 4).call(COPY);
+      c.get(obj);
+      fun
+    }
+  }
+}
+
+// Compile the given definitions, and those they refer to, to a WebAssembly
+// module exporting each under its name, along with the module's memory
+pub fn compile(
+  defs: &Defs,
+  exports: &[(String, Link)],
+) -> Result<Vec<u8>, CompileError> {
+  let mut compiler = Compiler::new(defs);
+  let mut funs = Vec::new();
+  for (nam, link) in exports {
+    funs.push((nam.clone(), compiler.export(nam, *link)?));
+  }
+  compiler.compile_pending()?;
+  Ok(compiler.finish(funs))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    parse::term::parse,
+    term::Def,
+  };
+  use im::HashMap;
+
+  fn make_def(name: &str, typ: &str, term: &str) -> (Link, Def) {
+    let (_, typ) = parse(typ).unwrap();
+    let (_, term) = parse(term).unwrap();
+    let def = Def::new(None, Name::from(name), String::new(), typ, term);
+    let (d, ..) = def.clone().embed();
+    (d.encode().link(), def)
+  }
+
+  fn compile_defs(
+    defs: &[(&str, &str, &str)],
+  ) -> Result<Vec<u8>, CompileError> {
+    let mut map = HashMap::new();
+    let mut exports = Vec::new();
+    for (name, typ, term) in defs {
+      let (link, def) = make_def(name, typ, term);
+      map.insert(link, def);
+      exports.push((String::from(*name), link));
+    }
+    compile(&map, &exports)
+  }
+
+  #[test]
+  fn encodes_leb128() {
+    let encode = |f: fn(&mut Vec<u8>, i64), x| {
+      let mut out = Vec::new();
+      f(&mut out, x);
+      out
+    };
+    let uleb = |out: &mut Vec<u8>, x: i64| uleb(out, x as u64);
+    assert_eq!(encode(uleb, 3), vec![0x03]);
+    assert_eq!(encode(uleb, 624485), vec![0xe5, 0x8e, 0x26]);
+    assert_eq!(encode(sleb, -1), vec![0x7f]);
+    assert_eq!(encode(sleb, 63), vec![0x3f]);
+    assert_eq!(encode(sleb, 64), vec![0xc0, 0x00]);
+    assert_eq!(encode(sleb, -123456), vec![0xc0, 0xbb, 0x78]);
+  }
+
+  #[test]
+  fn compiles_exports() {
+    let module = compile_defs(&[
+      ("inc", "∀ (x: #Natural) -> #Natural", "λ x => #add x 1"),
+      ("id", "∀ (0 A: Type) (x: #Char) -> #Char", "λ A x => x"),
+    ])
+    .unwrap();
+    assert_eq!(&module[..8], b"\0asm\x01\0\0\0");
+    for name in &["memory", "inc", "id"] {
+      let mut export = vec![name.len() as u8];
+      export.extend_from_slice(name.as_bytes());
+      assert!(module.windows(export.len()).any(|x| x == &export[..]));
+    }
+  }
+
+  #[test]
+  fn rejects_what_cannot_be_compiled() {
+    let typ = "∀ (0 A: Type) (x: A) -> A";
+    match compile_defs(&[("id", typ, "λ A x => x")]) {
+      Err(CompileError::NotExportable(name, _)) => assert_eq!(name, "id"),
+      res => panic!("unexpected result {:?}", res),
+    }
+    let typ = "∀ (x: #Natural) -> #Natural";
+    match compile_defs(&[("big", typ, "λ x => 18446744073709551616")]) {
+      Err(CompileError::LiteralTooLarge(_)) => (),
+      res => panic!("unexpected result {:?}", res),
+    }
+    match compile_defs(&[("bad", typ, "λ x => #add x \"one\"")]) {
+      Err(CompileError::Check(name, _)) => assert_eq!(name, "bad"),
+      res => panic!("unexpected result {:?}", res),
+    }
+    let (link, _) = make_def("inc", typ, "λ x => #add x 1");
+    let exports = vec![(String::from("inc"), link)];
+    match compile(&HashMap::new(), &exports) {
+      Err(CompileError::UndefinedReference(name, _)) => assert_eq!(name, "inc"),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+}
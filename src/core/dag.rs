@@ -3,7 +3,12 @@
 use crate::{
   core::{
     dll::*,
-    eval,
+    elab::surface,
+    eval::{
+      self,
+      malformed,
+      EvalError,
+    },
     literal::{
       LitType,
       Literal,
@@ -11,6 +16,7 @@ use crate::{
     primop::PrimOp,
     uses::Uses,
   },
+  name::Name,
   term::{
     Link,
     Term,
@@ -61,12 +67,16 @@ pub struct Leaf {
 
 #[derive(Clone)]
 pub enum LeafTag {
-  Typ,
+  Typ(u64),
+  Prp,
   LTy(LitType),
   Lit(Literal),
   Opr(PrimOp),
-  Var(String),
-  Ref(String, Link, Link),
+  Var(Name),
+  // A variable bound outside of the term, with its de Bruijn index relative
+  // to the term's outermost binder
+  Free(Name, u64),
+  Ref(Name, Link, Link),
 }
 
 pub struct Single {
@@ -84,6 +94,8 @@ pub enum SingleTag {
   Slf,
   Cse,
   Dat,
+  Fst,
+  Snd,
 }
 
 pub struct Branch {
@@ -102,6 +114,11 @@ pub enum BranchTag {
   App,
   Ann,
   All(Uses),
+  Sig,
+  Par,
+  // `let x: A = e; b`, held as the redex of `λ x => b` on the left applied
+  // to `e :: A` on the right. A recursive `let` binds `e` in a fixpoint.
+  Let(bool, Uses),
 }
 
 // Get the parents of a term.
@@ -216,7 +233,7 @@ pub fn free_dead_node(node: DAG) {
     match var {
       Some(var) => unsafe {
         if (*var.as_ptr()).parents.is_none() {
-          free_dead_node(DAG::Leaf(var))
+          dealloc(var.as_ptr() as *mut u8, Layout::new::<Leaf>());
         }
       },
       None => (),
@@ -254,7 +271,10 @@ pub fn free_dead_node(node: DAG) {
         dealloc(link.as_ptr() as *mut u8, Layout::new::<Branch>());
       }
       DAG::Leaf(link) => {
-        dealloc(link.as_ptr() as *mut u8, Layout::new::<Leaf>());
+        // A variable is still pointed to by its binder, which frees it
+        if !matches!((*link.as_ptr()).tag, LeafTag::Var(_)) {
+          dealloc(link.as_ptr() as *mut u8, Layout::new::<Leaf>());
+        }
       }
     }
   }
@@ -431,18 +451,28 @@ impl fmt::Debug for DAG {
           }
         },
         DAG::Single(link) => unsafe {
-          let Single { var, parents, body, .. } = *link.as_ptr();
+          let Single { var, parents, body, tag, .. } = *link.as_ptr();
           let name = match var {
             Some(var_link) => match &(*var_link.as_ptr()).tag {
-              LeafTag::Var(name) => name.clone(),
-              _ => panic!("TODO"),
+              LeafTag::Var(name) => name.to_string(),
+              _ => String::from("?"),
             },
-            _ => panic!("TODO"),
+            _ => String::from(""),
+          };
+          let tag = match tag {
+            SingleTag::Lam => "Lam",
+            SingleTag::Fix => "Fix",
+            SingleTag::Slf => "Slf",
+            SingleTag::Cse => "Cse",
+            SingleTag::Dat => "Dat",
+            SingleTag::Fst => "Fst",
+            SingleTag::Snd => "Snd",
           };
           if set.get(&(link.as_ptr() as u64)).is_none() {
             set.insert(link.as_ptr() as u64);
             format!(
-              "\nLam<{}> {} parents: {}{}",
+              "\n{}<{}> {} parents: {}{}",
+              tag,
               link.as_ptr() as u64,
               name,
               stringify_parents(parents),
@@ -470,7 +500,24 @@ impl fmt::Debug for DAG {
                 format!("\nSHARE<{}>", link.as_ptr() as u64)
               }
             }
-            _ => panic!("TODO"),
+            tag => {
+              let tag = match tag {
+                LeafTag::Typ(lvl) => format!("Type@{}", lvl),
+                LeafTag::Prp => String::from("Prop"),
+                LeafTag::LTy(lty) => format!("{}", lty),
+                LeafTag::Lit(lit) => format!("{}", lit),
+                LeafTag::Opr(opr) => format!("{}", opr),
+                LeafTag::Ref(nam, ..) => nam.to_string(),
+                LeafTag::Var(nam) => nam.to_string(),
+                LeafTag::Free(nam, idx) => format!("{}^{}", nam, idx),
+              };
+              format!(
+                "\nLeaf<{}> {} parents: {}",
+                link.as_ptr() as u64,
+                tag,
+                stringify_parents(parents)
+              )
+            }
           }
         },
       }
@@ -498,7 +545,8 @@ impl DAG {
         DAG::Leaf(link) => {
           let Leaf { tag, .. } = unsafe { &*link.as_ptr() };
           match tag {
-            LeafTag::Typ => Term::Typ(None),
+            LeafTag::Typ(lvl) => Term::Typ(None, *lvl),
+            LeafTag::Prp => Term::Prp(None),
             LeafTag::LTy(lty) => Term::LTy(None, *lty),
             LeafTag::Lit(lit) => Term::Lit(None, lit.clone()),
             LeafTag::Opr(opr) => Term::Opr(None, *opr),
@@ -509,6 +557,9 @@ impl DAG {
               let level = map.get(&link.as_ptr()).unwrap();
               Term::Var(None, nam.to_owned(), depth - level - 1)
             }
+            LeafTag::Free(nam, idx) => {
+              Term::Var(None, nam.to_owned(), depth + idx)
+            }
           }
         }
 
@@ -532,7 +583,16 @@ impl DAG {
                   let body = go(body, &mut map, depth + 1);
                   Term::Slf(None, name.clone(), Box::new(body))
                 }
-                SingleTag::Fix => panic!("TODO: Add Fix to Term."),
+                // A fixpoint left by a recursive `let`, which is that `let`
+                // of its own name
+                SingleTag::Fix => {
+                  map.insert(var_link.as_ptr(), depth);
+                  let body = go(body, &mut map, depth + 1);
+                  let var = Term::Var(None, name.clone(), 0);
+                  let typ = Term::Hol(None, None);
+                  let terms = Box::new((typ, body, var));
+                  Term::Let(None, true, Uses::Many, name.clone(), terms)
+                }
                 _ => panic!("Malformed DAG."),
               }
             }
@@ -543,6 +603,12 @@ impl DAG {
               SingleTag::Dat => {
                 Term::Dat(None, Box::new(go(body, &mut map, depth)))
               }
+              SingleTag::Fst => {
+                Term::Fst(None, Box::new(go(body, &mut map, depth)))
+              }
+              SingleTag::Snd => {
+                Term::Snd(None, Box::new(go(body, &mut map, depth)))
+              }
               _ => panic!("Malformed DAG."),
             },
           }
@@ -568,6 +634,12 @@ impl DAG {
                     Box::new((dom, img))
                   )
                 }
+                BranchTag::Sig => {
+                  map.insert(var_link.as_ptr(), depth);
+                  let dom = go(left, &mut map, depth);
+                  let img = go(right, &mut map, depth + 1);
+                  Term::Sig(None, name.clone(), Box::new((dom, img)))
+                }
                 _ => panic!("Malformed DAG."),
               }
             }
@@ -582,6 +654,37 @@ impl DAG {
                 let trm = go(right, &mut map, depth);
                 Term::Ann(None, Box::new((typ, trm)))
               }
+              BranchTag::Par => {
+                let fst = go(left, &mut map, depth);
+                let snd = go(right, &mut map, depth);
+                Term::Par(None, Box::new((fst, snd)))
+              }
+              BranchTag::Let(rec, uses) => {
+                let (name, bod) = match go(left, &mut map, depth) {
+                  Term::Lam(_, name, bod) => (name, *bod),
+                  _ => panic!("Malformed DAG."),
+                };
+                let (typ, exp) = match right {
+                  DAG::Branch(ann) => {
+                    let Branch { left: typ, right: exp, .. } =
+                      unsafe { &*ann.as_ptr() };
+                    let typ = go(typ, &mut map, depth);
+                    let exp = match exp {
+                      DAG::Single(fix) if *rec => {
+                        let Single { var, body, .. } =
+                          unsafe { &*fix.as_ptr() };
+                        let var = var.expect("Malformed DAG.");
+                        map.insert(var.as_ptr(), depth);
+                        go(body, &mut map, depth + 1)
+                      }
+                      _ => go(exp, &mut map, depth),
+                    };
+                    (typ, exp)
+                  }
+                  _ => panic!("Malformed DAG."),
+                };
+                Term::Let(None, *rec, *uses, name, Box::new((typ, exp, bod)))
+              }
               _ => panic!("Malformed DAG."),
             },
           }
@@ -591,7 +694,8 @@ impl DAG {
     go(&self, &mut map, 0)
   }
 
-  pub fn from_term(tree: Term) -> DAG {
+  // The graph of a term, which must not have holes or metavariables left
+  pub fn from_term(tree: Term) -> Result<DAG, EvalError> {
     pub fn go(
       tree: Term,
       mut ctx: Vector<NonNull<Leaf>>,
@@ -600,7 +704,7 @@ impl DAG {
       match tree {
         Term::Lam(_, name, body) => {
           // Allocate nodes
-          let var = new_leaf(LeafTag::Var(name.clone()));
+          let var = new_leaf(LeafTag::Var(name));
           let sons_parents = alloc_uninit();
           let lam = alloc_val(Single {
             var: Some(var),
@@ -627,7 +731,7 @@ impl DAG {
         }
 
         Term::Slf(_, name, body) => {
-          let var = new_leaf(LeafTag::Var(name.clone()));
+          let var = new_leaf(LeafTag::Var(name));
           let sons_parents = alloc_uninit();
           let lam = alloc_val(Single {
             var: Some(var),
@@ -686,7 +790,7 @@ impl DAG {
         Term::All(_, uses, name, terms) => {
           // Allocation and updates
           let (dom, img) = *terms;
-          let var = new_leaf(LeafTag::Var(name.clone()));
+          let var = new_leaf(LeafTag::Var(name));
           let dom_parents = alloc_uninit();
           let img_parents = alloc_uninit();
           let all = alloc_val(Branch {
@@ -772,6 +876,165 @@ impl DAG {
           DAG::Branch(ann)
         }
 
+        Term::Let(_, rec, uses, name, terms) => {
+          let (typ, exp, bod) = *terms;
+          let bod_parents = alloc_uninit();
+          let ann_parents = alloc_uninit();
+          let let_ = alloc_val(Branch {
+            var: None,
+            tag: BranchTag::Let(rec, uses),
+            left: DAG::Leaf(NonNull::dangling()),
+            right: DAG::Leaf(NonNull::dangling()),
+            left_ref: bod_parents,
+            right_ref: ann_parents,
+            copy: None,
+            parents: Some(parents),
+          });
+          unsafe {
+            *bod_parents.as_ptr() = DLL::singleton(ParentCell::Left(let_));
+            *ann_parents.as_ptr() = DLL::singleton(ParentCell::Right(let_));
+          }
+          let lam = Term::Lam(None, name.clone(), Box::new(bod));
+          let bod = go(lam, ctx.clone(), bod_parents);
+          let ann = if rec {
+            let typ_parents = alloc_uninit();
+            let fix_parents = alloc_uninit();
+            let ann = alloc_val(Branch {
+              var: None,
+              tag: BranchTag::Ann,
+              left: DAG::Leaf(NonNull::dangling()),
+              right: DAG::Leaf(NonNull::dangling()),
+              left_ref: typ_parents,
+              right_ref: fix_parents,
+              copy: None,
+              parents: Some(ann_parents),
+            });
+            let var = new_leaf(LeafTag::Var(name));
+            let exp_parents = alloc_uninit();
+            let fix = alloc_val(Single {
+              var: Some(var),
+              tag: SingleTag::Fix,
+              body: DAG::Leaf(NonNull::dangling()),
+              body_ref: exp_parents,
+              parents: Some(fix_parents),
+            });
+            unsafe {
+              *typ_parents.as_ptr() = DLL::singleton(ParentCell::Left(ann));
+              *fix_parents.as_ptr() = DLL::singleton(ParentCell::Right(ann));
+              *exp_parents.as_ptr() = DLL::singleton(ParentCell::Body(fix));
+            }
+            let mut exp_ctx = ctx.clone();
+            let typ = go(typ, ctx, typ_parents);
+            exp_ctx.push_front(var);
+            let exp = go(exp, exp_ctx, exp_parents);
+            unsafe {
+              (*fix.as_ptr()).body = exp;
+              (*ann.as_ptr()).left = typ;
+              (*ann.as_ptr()).right = DAG::Single(fix);
+            }
+            DAG::Branch(ann)
+          }
+          else {
+            go(Term::Ann(None, Box::new((typ, exp))), ctx, ann_parents)
+          };
+          unsafe {
+            (*let_.as_ptr()).left = bod;
+            (*let_.as_ptr()).right = ann;
+          }
+          DAG::Branch(let_)
+        }
+
+        Term::Fst(_, body) => {
+          let sons_parents = alloc_uninit();
+          let fst = alloc_val(Single {
+            var: None,
+            tag: SingleTag::Fst,
+            body: DAG::Leaf(NonNull::dangling()),
+            body_ref: sons_parents,
+            parents: Some(parents),
+          });
+          unsafe {
+            *sons_parents.as_ptr() = DLL::singleton(ParentCell::Body(fst));
+          }
+          let body = go(*body, ctx, sons_parents);
+          unsafe {
+            (*fst.as_ptr()).body = body;
+          }
+          DAG::Single(fst)
+        }
+        Term::Snd(_, body) => {
+          let sons_parents = alloc_uninit();
+          let snd = alloc_val(Single {
+            var: None,
+            tag: SingleTag::Snd,
+            body: DAG::Leaf(NonNull::dangling()),
+            body_ref: sons_parents,
+            parents: Some(parents),
+          });
+          unsafe {
+            *sons_parents.as_ptr() = DLL::singleton(ParentCell::Body(snd));
+          }
+          let body = go(*body, ctx, sons_parents);
+          unsafe {
+            (*snd.as_ptr()).body = body;
+          }
+          DAG::Single(snd)
+        }
+        Term::Sig(_, name, terms) => {
+          let (dom, img) = *terms;
+          let var = new_leaf(LeafTag::Var(name));
+          let dom_parents = alloc_uninit();
+          let img_parents = alloc_uninit();
+          let sig = alloc_val(Branch {
+            var: Some(var),
+            tag: BranchTag::Sig,
+            left: DAG::Leaf(NonNull::dangling()),
+            right: DAG::Leaf(NonNull::dangling()),
+            left_ref: dom_parents,
+            right_ref: img_parents,
+            copy: None,
+            parents: Some(parents),
+          });
+          unsafe {
+            *dom_parents.as_ptr() = DLL::singleton(ParentCell::Left(sig));
+            *img_parents.as_ptr() = DLL::singleton(ParentCell::Right(sig));
+          }
+          let mut img_ctx = ctx.clone();
+          let dom = go(dom, ctx, dom_parents);
+          img_ctx.push_front(var);
+          let img = go(img, img_ctx, img_parents);
+          unsafe {
+            (*sig.as_ptr()).left = dom;
+            (*sig.as_ptr()).right = img;
+          }
+          DAG::Branch(sig)
+        }
+        Term::Par(_, terms) => {
+          let (fst, snd) = *terms;
+          let fst_parents = alloc_uninit();
+          let snd_parents = alloc_uninit();
+          let par = alloc_val(Branch {
+            var: None,
+            tag: BranchTag::Par,
+            left: DAG::Leaf(NonNull::dangling()),
+            right: DAG::Leaf(NonNull::dangling()),
+            left_ref: fst_parents,
+            right_ref: snd_parents,
+            copy: None,
+            parents: Some(parents),
+          });
+          unsafe {
+            *fst_parents.as_ptr() = DLL::singleton(ParentCell::Left(par));
+            *snd_parents.as_ptr() = DLL::singleton(ParentCell::Right(par));
+          }
+          let fst = go(fst, ctx.clone(), fst_parents);
+          let snd = go(snd, ctx, snd_parents);
+          unsafe {
+            (*par.as_ptr()).left = fst;
+            (*par.as_ptr()).right = snd;
+          }
+          DAG::Branch(par)
+        }
         Term::Var(_, name, idx) => {
           let var = match ctx.get(idx as usize) {
             Some(var) => unsafe {
@@ -780,14 +1043,19 @@ impl DAG {
               *var
             },
             None => {
-              let tag = LeafTag::Var(name.clone());
+              let free = idx - ctx.len() as u64;
+              let tag = LeafTag::Free(name, free);
               alloc_val(Leaf { tag, parents: Some(parents) })
             }
           };
           DAG::Leaf(var)
         }
-        Term::Typ(_) => DAG::Leaf(alloc_val(Leaf {
-          tag: LeafTag::Typ,
+        Term::Typ(_, lvl) => DAG::Leaf(alloc_val(Leaf {
+          tag: LeafTag::Typ(lvl),
+          parents: Some(parents),
+        })),
+        Term::Prp(_) => DAG::Leaf(alloc_val(Leaf {
+          tag: LeafTag::Prp,
           parents: Some(parents),
         })),
         Term::LTy(_, lty) => DAG::Leaf(alloc_val(Leaf {
@@ -806,11 +1074,16 @@ impl DAG {
           tag: LeafTag::Ref(name, def_link, ast_link),
           parents: Some(parents),
         })),
-        _ => panic!("TODO: implement Term::to_dag variants"),
+        Term::Hol(..) | Term::Met(..) => {
+          unreachable!("holes and metavariables are refused before")
+        }
       }
     }
+    if let Some(hole) = surface(&tree) {
+      return Err(malformed(hole));
+    }
     let root = alloc_val(DLL::singleton(ParentCell::Root));
-    go(tree, Vector::new(), root)
+    Ok(go(tree, Vector::new(), root))
   }
 }
 
@@ -825,14 +1098,25 @@ mod test {
       parse("(λ _z => (λ _a => ∀ (1 _x: _a) -> #Natural) Type)").unwrap();
     println!("{:?}", parse("(λ _a => ∀ (1 _a: _a) -> #Natural)"));
     // assert_eq!(true, false)
-    assert_eq!(x, DAG::to_term(&DAG::from_term(x.clone())));
+    assert_eq!(x, DAG::to_term(&DAG::from_term(x.clone()).unwrap()));
+    let (_, x) = parse("let x : Type = Type; x").unwrap();
+    assert_eq!(x, DAG::to_term(&DAG::from_term(x.clone()).unwrap()));
+    let (_, x) = parse("letrec f (x: Type) : Type = f x; f").unwrap();
+    assert_eq!(x, DAG::to_term(&DAG::from_term(x.clone()).unwrap()));
+  }
+
+  #[test]
+  fn refuses_holes() {
+    let (_, x) = parse("λ x => x ?goal").unwrap();
+    let hole = String::from("the hole ?goal");
+    assert_eq!(DAG::from_term(x).err(), Some(EvalError::Malformed(hole)));
   }
 
   #[quickcheck]
   fn term_encode_decode(x: Term) -> bool {
     println!("x: {}", x);
     println!("x: {:?}", x);
-    let y = DAG::to_term(&DAG::from_term(x.clone()));
+    let y = DAG::to_term(&DAG::from_term(x.clone()).unwrap());
     println!("y: {}", y);
     println!("y: {:?}", y);
     x == y
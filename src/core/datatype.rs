@@ -0,0 +1,731 @@
+use crate::{
+  core::elab,
+  name::Name,
+  term::{
+    Def,
+    Term,
+    Uses,
+  },
+};
+
+use hashexpr::position::Pos;
+
+use std::fmt;
+
+// Inductive datatypes, elaborated into ordinary definitions. A declaration
+//
+//   data List (A: Type): Type@1 {
+//     nil: List A,
+//     cons: ∀ (x: A) (xs: List A) -> List A,
+//   }
+//
+// becomes the self type
+//
+//   List = λ A => @self ∀ (P: ∀ (x: List A) -> Type)
+//     (nil: P (data λ P nil cons => nil))
+//     (cons: ∀ (x: A) (xs: List A) -> P (data λ P nil cons => cons x xs))
+//     -> P self
+//
+// together with a definition for each constructor, such as
+// `List.nil = λ A => data λ P nil cons => nil`, and a recursor
+//
+//   List.rec: ∀ (A: Type) (P: ∀ (x: List A) -> Type)
+//     (nil: P (List.nil A))
+//     (cons: ∀ (x: A) (xs: List A) (xs_ih: P xs) -> P (List.cons A x xs))
+//     (x: List A) -> P x
+//
+// which passes the results of recursive calls on the recursive fields of a
+// constructor to its case. A datatype may be a family indexed by the binders
+// of its sort, which each constructor instantiates as it likes:
+//
+//   data Eq (A: Type) (a: A): ∀ (b: A) -> Type@1 {
+//     refl: Eq A a a,
+//   }
+//
+// The motive then abstracts over the indices as well as the value, so that
+// `Eq.rec` eliminates `Eq A a b` into `P b e` from a case for `P a Eq.refl`.
+// The constructors and the recursor only refer to the datatype, never the
+// other way around, so all of them can be stored in the hashspace like any
+// other definition. A datatype in `Type@n` can be eliminated into
+// `Type@(n-1)`, so its level must be at least 1.
+
+// A list of binders, each type in the scope of the binders before it
+pub type Telescope = Vec<(Uses, Name, Term)>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ctor {
+  pub pos: Option<Pos>,
+  pub name: Name,
+  // The constructor's fields, in the scope of the datatype's parameters and
+  // of the datatype itself
+  pub fields: Telescope,
+  // The indices of the datatype the constructor returns, in the scope of its
+  // fields too
+  pub indices: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Datatype {
+  pub pos: Option<Pos>,
+  pub name: Name,
+  // The documentation of the type, from the comments before its declaration
+  pub docs: String,
+  pub params: Telescope,
+  // The binders of the datatype's sort, which unlike the parameters may
+  // differ between constructors
+  pub indices: Telescope,
+  pub level: u64,
+  pub ctors: Vec<Ctor>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataError {
+  NotAUniverse(Option<Pos>, Term),
+  LevelZero(Option<Pos>),
+  CtorReturnType(Option<Pos>, String, Term),
+  DuplicateCtor(Option<Pos>, String),
+  DuplicateField(Option<Pos>, String),
+  // A codatatype without fields, which would have no values
+  NoFields(Option<Pos>),
+  // A placeholder, which cannot be filled in before the datatype is defined
+  Placeholder(Option<Pos>, Term),
+  // A field of a record whose type refers to the record
+  RecursiveRecord(Option<Pos>, String),
+}
+
+impl fmt::Display for DataError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NotAUniverse(_, typ) => {
+        write!(f, "A datatype must have a universe as its type, not {}", typ)
+      }
+      Self::LevelZero(_) => write!(
+        f,
+        "A datatype must live in Type@1 or above, as its elimination \
+         quantifies over Type"
+      ),
+      Self::CtorReturnType(_, nam, typ) => write!(
+        f,
+        "Constructor {} must return its datatype applied to its parameters \
+         and indices, not {}",
+        nam, typ
+      ),
+      Self::DuplicateCtor(_, nam) => write!(f, "Duplicate constructor {}", nam),
+      Self::DuplicateField(_, nam) => write!(f, "Duplicate field {}", nam),
+      Self::NoFields(_) => {
+        write!(f, "A codatatype must have at least one field")
+      }
+      Self::Placeholder(_, term) => write!(
+        f,
+        "The placeholder {} cannot be filled in within a datatype declaration",
+        term
+      ),
+      Self::RecursiveRecord(_, nam) => write!(
+        f,
+        "Field {} refers to its record, which only a codatatype may do",
+        nam
+      ),
+    }
+  }
+}
+
+impl std::error::Error for DataError {}
+
+pub fn var(nam: &str, idx: u64) -> Term {
+  Term::Var(None, Name::from(nam), idx)
+}
+
+pub fn apps(fun: Term, args: Vec<Term>) -> Term {
+  args.into_iter().fold(fun, |acc, arg| Term::App(None, Box::new((acc, arg))))
+}
+
+pub fn lams(nams: Vec<Name>, bod: Term) -> Term {
+  nams
+    .into_iter()
+    .rev()
+    .fold(bod, |acc, nam| Term::Lam(None, nam, Box::new(acc)))
+}
+
+pub fn alls(tele: Telescope, bod: Term) -> Term {
+  tele.into_iter().rev().fold(bod, |acc, (uses, nam, typ)| {
+    Term::All(None, uses, nam, Box::new((typ, acc)))
+  })
+}
+
+pub fn names(tele: &Telescope) -> Vec<Name> {
+  tele.iter().map(|(_, nam, _)| nam.clone()).collect()
+}
+
+const MOTIVE: &str = "P";
+const SELF: &str = "self";
+
+// The arguments to the datatype's indices, if `term` is the datatype applied
+// to its parameters and to `indices` further arguments, in the scope of the
+// datatype, its `params` parameters and `depth` further binders
+fn self_indices(
+  term: &Term,
+  depth: u64,
+  params: u64,
+  indices: u64,
+) -> Option<Vec<Term>> {
+  let mut head = term;
+  let mut args = Vec::new();
+  while let Term::App(_, terms) = head {
+    args.push(&terms.1);
+    head = &terms.0;
+  }
+  args.reverse();
+  let is_data = match head {
+    Term::Var(_, _, idx) => *idx == depth + params,
+    _ => false,
+  };
+  let is_param = |(i, arg): (usize, &&Term)| match arg {
+    Term::Var(_, _, idx) => *idx == depth + params - 1 - i as u64,
+    _ => false,
+  };
+  let is_self = is_data
+    && args.len() as u64 == params + indices
+    && args.iter().take(params as usize).enumerate().all(is_param);
+  if is_self {
+    Some(args[params as usize..].iter().map(|arg| (*arg).clone()).collect())
+  }
+  else {
+    None
+  }
+}
+
+impl Ctor {
+  // Split a constructor's type into its fields, checking that it returns the
+  // datatype applied to its parameters and to as many indices as it has.
+  // The type is in the scope of the parameters and, outermost, of the
+  // datatype.
+  pub fn from_type(
+    pos: Option<Pos>,
+    name: Name,
+    typ: Term,
+    params: u64,
+    indices: u64,
+  ) -> Result<Self, DataError> {
+    let mut fields = Vec::new();
+    let mut res = typ;
+    while let Term::All(_, uses, nam, terms) = res {
+      let (dom, img) = *terms;
+      fields.push((uses, nam, dom));
+      res = img;
+    }
+    match self_indices(&res, fields.len() as u64, params, indices) {
+      Some(indices) => Ok(Ctor { pos, name, fields, indices }),
+      None => Err(DataError::CtorReturnType(pos, name.to_string(), res)),
+    }
+  }
+
+  // The fields whose type is the datatype itself, with the indices it is
+  // applied to there
+  fn recursive_fields(
+    &self,
+    params: u64,
+    indices: u64,
+  ) -> Vec<(usize, Vec<Term>)> {
+    (0..self.fields.len())
+      .filter_map(|t| {
+        self_indices(&self.fields[t].2, t as u64, params, indices)
+          .map(|idxs| (t, idxs))
+      })
+      .collect()
+  }
+
+  // The constructor applied to its fields: `data λ P c.. => c_j f..`, in the
+  // scope of the fields
+  fn value(&self, idx: usize, ctors: &[Ctor]) -> Term {
+    let m = ctors.len() as u64;
+    let k = self.fields.len() as u64;
+    let args = names(&self.fields)
+      .iter()
+      .enumerate()
+      .map(|(t, nam)| var(nam, m + 1 + (k - 1 - t as u64)))
+      .collect();
+    let mut binders = vec![Name::from(MOTIVE)];
+    binders.extend(ctors.iter().map(|c| c.name.clone()));
+    let bod = apps(var(&self.name, m - 1 - idx as u64), args);
+    Term::Dat(None, Box::new(lams(binders, bod)))
+  }
+}
+
+impl Datatype {
+  pub fn new(
+    pos: Option<Pos>,
+    name: Name,
+    params: Telescope,
+    sort: Term,
+    ctors: Vec<(Option<Pos>, Name, Term)>,
+  ) -> Result<Self, DataError> {
+    // The binders of the sort are the datatype's indices
+    let mut indices = Vec::new();
+    let mut sort = sort;
+    while let Term::All(_, uses, nam, terms) = sort {
+      let (dom, img) = *terms;
+      indices.push((uses, nam, dom));
+      sort = img;
+    }
+    let level = match sort {
+      Term::Typ(_, 0) => return Err(DataError::LevelZero(pos)),
+      Term::Typ(_, lvl) => lvl,
+      sort => return Err(DataError::NotAUniverse(pos, sort)),
+    };
+    let typs = params.iter().chain(indices.iter()).map(|(_, _, typ)| typ);
+    let typs = typs.chain(ctors.iter().map(|(_, _, typ)| typ));
+    if let Some(hole) = typs.filter_map(elab::surface).next() {
+      return Err(DataError::Placeholder(hole.pos(), hole.clone()));
+    }
+    let (n, r) = (params.len() as u64, indices.len() as u64);
+    let mut cs: Vec<Ctor> = Vec::new();
+    for (pos, nam, typ) in ctors {
+      if cs.iter().any(|c| c.name == nam) {
+        return Err(DataError::DuplicateCtor(pos, nam.to_string()));
+      }
+      cs.push(Ctor::from_type(pos, nam, typ, n, r)?);
+    }
+    let docs = String::new();
+    Ok(Datatype { pos, name, docs, params, indices, level, ctors: cs })
+  }
+
+  pub fn ctor_name(&self, ctor: &Ctor) -> Name {
+    Name::from(format!("{}.{}", self.name, ctor.name))
+  }
+
+  // The definition of the datatype as a self type
+  pub fn type_def(&self) -> Def {
+    let n = self.params.len() as u64;
+    let r = self.indices.len() as u64;
+    let m = self.ctors.len() as u64;
+    // The motive's indices, in the scope of `self`, the indices, the
+    // parameters and the datatype
+    let indices: Telescope = self
+      .indices
+      .iter()
+      .enumerate()
+      .map(|(k, (uses, nam, typ))| {
+        (*uses, nam.clone(), typ.shift(r as i64 + 1, k as u64))
+      })
+      .collect();
+    let mut args: Vec<Term> = names(&self.params)
+      .iter()
+      .enumerate()
+      .map(|(i, nam)| var(nam, 2 * r + n - i as u64))
+      .collect();
+    args.extend(
+      names(&self.indices)
+        .iter()
+        .enumerate()
+        .map(|(k, nam)| var(nam, r - 1 - k as u64)),
+    );
+    let motive = alls(
+      indices,
+      Term::All(
+        None,
+        Uses::Many,
+        Name::from("x"),
+        Box::new((
+          apps(var(&self.name, 2 * r + n + 1), args),
+          Term::Typ(None, self.level - 1),
+        )),
+      ),
+    );
+    let mut cases = vec![(Uses::Many, Name::from(MOTIVE), motive)];
+    for (j, ctor) in self.ctors.iter().enumerate() {
+      // Fields move under the indices, the previous cases, the motive and
+      // `self`
+      let fields: Telescope = ctor
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(t, (uses, nam, typ))| {
+          (*uses, nam.clone(), typ.shift((r + j as u64) as i64 + 2, t as u64))
+        })
+        .collect();
+      let k = fields.len() as u64;
+      let mut args: Vec<Term> = ctor
+        .indices
+        .iter()
+        .map(|idx| idx.shift((r + j as u64) as i64 + 2, k))
+        .collect();
+      args.push(ctor.value(j, &self.ctors));
+      let res = apps(var(MOTIVE, k + j as u64), args);
+      cases.push((Uses::Many, ctor.name.clone(), alls(fields, res)));
+    }
+    let mut args: Vec<Term> = names(&self.indices)
+      .iter()
+      .enumerate()
+      .map(|(k, nam)| var(nam, m + 2 + (r - 1 - k as u64)))
+      .collect();
+    args.push(var(SELF, m + 1));
+    let res = apps(var(MOTIVE, m), args);
+    let slf = Term::Slf(None, Name::from(SELF), Box::new(alls(cases, res)));
+    let mut binders = names(&self.params);
+    binders.extend(names(&self.indices));
+    let term = lams(binders, slf);
+    let sort = alls(self.indices.clone(), Term::Typ(None, self.level));
+    let typ_ = alls(self.params.clone(), sort);
+    Def::new(self.pos, self.name.clone(), self.docs.clone(), typ_, term)
+  }
+
+  // The definition of the constructor with index `idx`, given a reference
+  // to the datatype
+  pub fn ctor_def(&self, idx: usize, data: &Term) -> Def {
+    let n = self.params.len() as u64;
+    let ctor = &self.ctors[idx];
+    let k = ctor.fields.len() as u64;
+    let fields: Telescope = ctor
+      .fields
+      .iter()
+      .enumerate()
+      .map(|(t, (uses, nam, typ))| {
+        (*uses, nam.clone(), typ.subst(t as u64 + n, data))
+      })
+      .collect();
+    let mut args: Vec<Term> = names(&self.params)
+      .iter()
+      .enumerate()
+      .map(|(i, nam)| var(nam, k + n - 1 - i as u64))
+      .collect();
+    args.extend(ctor.indices.iter().map(|idx| idx.subst(k + n, data)));
+    let res = apps(data.clone(), args);
+    let typ_ = alls(self.params.clone(), alls(fields, res));
+    let mut binders = names(&self.params);
+    binders.extend(names(&ctor.fields));
+    let term = lams(binders, ctor.value(idx, &self.ctors));
+    Def::new(ctor.pos, self.ctor_name(ctor), String::new(), typ_, term)
+  }
+
+  pub fn rec_name(&self) -> Name {
+    Name::from(format!("{}.rec", self.name))
+  }
+
+  // The definition of the recursor, given references to the datatype and to
+  // its constructors
+  pub fn rec_def(&self, data: &Term, ctors: &[Term]) -> Def {
+    let n = self.params.len() as u64;
+    let r = self.indices.len() as u64;
+    let m = self.ctors.len() as u64;
+    // The parameters, under `depth` binders below them
+    let params = |depth: u64| -> Vec<Term> {
+      names(&self.params)
+        .iter()
+        .enumerate()
+        .map(|(i, nam)| var(nam, depth + n - 1 - i as u64))
+        .collect()
+    };
+    // The indices, bound under `depth` binders below the parameters
+    let indices = |depth: u64| -> Telescope {
+      self
+        .indices
+        .iter()
+        .enumerate()
+        .map(|(k, (uses, nam, typ))| {
+          (*uses, nam.clone(), typ.shift(depth as i64, k as u64))
+        })
+        .collect()
+    };
+    // The datatype at the indices last bound, themselves bound under `depth`
+    // binders below the parameters
+    let at_indices = |depth: u64| -> Term {
+      let mut args = params(depth + r);
+      args.extend(
+        names(&self.indices)
+          .iter()
+          .enumerate()
+          .map(|(k, nam)| var(nam, r - 1 - k as u64)),
+      );
+      apps(data.clone(), args)
+    };
+    let motive = alls(
+      indices(0),
+      Term::All(
+        None,
+        Uses::Many,
+        Name::from("x"),
+        Box::new((at_indices(0), Term::Typ(None, self.level - 1))),
+      ),
+    );
+    let mut tele = self.params.clone();
+    tele.push((Uses::Many, Name::from(MOTIVE), motive));
+    let mut arms = Vec::new();
+    for (j, ctor) in self.ctors.iter().enumerate() {
+      let j = j as u64;
+      let k = ctor.fields.len() as u64;
+      let recs = ctor.recursive_fields(n, r);
+      let q = recs.len() as u64;
+      // Fields move under the motive and the previous cases
+      let mut case: Telescope = ctor
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(t, (uses, nam, typ))| {
+          let typ = typ.subst(t as u64 + n, data).shift(j as i64 + 1, t as u64);
+          (*uses, nam.clone(), typ)
+        })
+        .collect();
+      for (h, (t, idxs)) in recs.iter().enumerate() {
+        let (t, h) = (*t as u64, h as u64);
+        let (_, nam, _) = &ctor.fields[t as usize];
+        let mut args: Vec<Term> = idxs
+          .iter()
+          .map(|idx| {
+            idx
+              .subst(t + n, data)
+              .shift(j as i64 + 1, t)
+              .shift((k - t + h) as i64, 0)
+          })
+          .collect();
+        args.push(var(nam, k - 1 - t + h));
+        let ih = apps(var(MOTIVE, k + h + j), args);
+        case.push((Uses::Many, Name::from(format!("{}_ih", nam)), ih));
+      }
+      let mut args = params(q + k + j + 1);
+      args.extend(
+        names(&ctor.fields)
+          .iter()
+          .enumerate()
+          .map(|(t, nam)| var(nam, q + k - 1 - t as u64)),
+      );
+      let mut idxs: Vec<Term> = ctor
+        .indices
+        .iter()
+        .map(|idx| {
+          idx.subst(k + n, data).shift(j as i64 + 1, k).shift(q as i64, 0)
+        })
+        .collect();
+      idxs.push(apps(ctors[j as usize].clone(), args));
+      let res = apps(var(MOTIVE, q + k + j), idxs);
+      tele.push((Uses::Many, ctor.name.clone(), alls(case, res)));
+      // The case's argument, in the scope of the recursor itself, its
+      // parameters, the motive, the cases, the indices and the eliminated
+      // value
+      let rec = |depth: u64| -> Vec<Term> {
+        let mut args = params(depth + m + r + 2);
+        args.push(var(MOTIVE, depth + m + r + 1));
+        args.extend(
+          self
+            .ctors
+            .iter()
+            .enumerate()
+            .map(|(i, c)| var(&c.name, depth + m + r - i as u64)),
+        );
+        args
+      };
+      let mut args: Vec<Term> = names(&ctor.fields)
+        .iter()
+        .enumerate()
+        .map(|(t, nam)| var(nam, k - 1 - t as u64))
+        .collect();
+      for (t, idxs) in recs {
+        let t = t as u64;
+        let (_, nam, _) = &ctor.fields[t as usize];
+        let mut ih = rec(k);
+        ih.extend(idxs.iter().map(|idx| {
+          idx
+            .subst(t + n, data)
+            .shift((m + r) as i64 + 2, t)
+            .shift((k - t) as i64, 0)
+        }));
+        ih.push(var(nam, k - 1 - t));
+        args.push(apps(var(&self.rec_name(), k + m + n + r + 2), ih));
+      }
+      let bod = apps(var(&ctor.name, k + m + r - j), args);
+      arms.push(lams(names(&ctor.fields), bod));
+    }
+    tele.extend(indices(m + 1));
+    tele.push((Uses::Many, Name::from("x"), at_indices(m + 1)));
+    let mut args: Vec<Term> = names(&self.indices)
+      .iter()
+      .enumerate()
+      .map(|(k, nam)| var(nam, r - k as u64))
+      .collect();
+    args.push(var("x", 0));
+    let res = apps(var(MOTIVE, m + r + 1), args);
+    let typ_ = alls(tele.clone(), res);
+    let mut args = vec![var(MOTIVE, m + r + 1)];
+    args.extend(arms);
+    let bod = apps(Term::Cse(None, Box::new(var("x", 0))), args);
+    let term = lams(tele.into_iter().map(|(_, nam, _)| nam).collect(), bod);
+    Def::new(self.pos, self.rec_name(), String::new(), typ_, term)
+  }
+
+  // The datatype's definition followed by those of its constructors and of
+  // its recursor
+  pub fn defs(&self) -> Vec<Def> {
+    let link = |def: &Def| {
+      let (d, _, a) = def.clone().embed();
+      Term::Ref(None, def.name.clone(), d.encode().link(), a.encode().link())
+    };
+    let def = self.type_def();
+    let data = link(&def);
+    let mut defs = vec![def];
+    let mut ctors = Vec::new();
+    for idx in 0..self.ctors.len() {
+      let def = self.ctor_def(idx, &data);
+      ctors.push(link(&def));
+      defs.push(def);
+    }
+    defs.push(self.rec_def(&data, &ctors));
+    defs
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      check::check_data,
+      nbe,
+    },
+    parse::{
+      span::Span,
+      term::parse_expression,
+    },
+  };
+  use im::{
+    HashMap,
+    Vector,
+  };
+
+  // Parse constructor types in the scope of the datatype and its parameters
+  pub fn datatype(
+    name: &str,
+    params: &[(&str, &str)],
+    sort: &str,
+    ctors: &[(&str, &str)],
+  ) -> Result<Datatype, DataError> {
+    let parse = |ctx: &Vector<Name>, i: &str| {
+      let expr = parse_expression(HashMap::new(), HashMap::new(), ctx.clone());
+      expr(Span::new(i)).unwrap().1
+    };
+    let mut ctx = Vector::new();
+    let mut tele = Vec::new();
+    for (nam, typ) in params {
+      tele.push((Uses::Many, Name::from(*nam), parse(&ctx, typ)));
+      ctx.push_front(Name::from(*nam));
+    }
+    let sort = parse(&ctx, sort);
+    ctx.push_back(Name::from(name));
+    let ctors = ctors
+      .iter()
+      .map(|(nam, typ)| (None, Name::from(*nam), parse(&ctx, typ)))
+      .collect();
+    Datatype::new(None, Name::from(name), tele, sort, ctors)
+  }
+
+  #[test]
+  fn elaborates() {
+    let bool_ = datatype("Bool", &[], "Type@1", &[
+      ("true", "Bool"),
+      ("false", "Bool"),
+    ])
+    .unwrap();
+    assert_eq!(
+      format!("{}", bool_.type_def().term),
+      "@self ∀ (P: ∀ (x: Bool) -> Type) (true: P (data λ P true false => \
+       true)) (false: P (data λ P true false => false)) -> P self"
+    );
+    let defs = bool_.defs();
+    assert_eq!(defs[1].name, "Bool.true");
+    assert_eq!(format!("{}", defs[1].typ_), "Bool");
+    assert_eq!(format!("{}", defs[2].term), "data λ P true false => false");
+  }
+
+  #[test]
+  fn typechecks() {
+    let nat = datatype("Nat", &[], "Type@1", &[
+      ("zero", "Nat"),
+      ("succ", "∀ (pred: Nat) -> Nat"),
+    ])
+    .unwrap();
+    assert_eq!(check_data(&HashMap::new(), &nat), Ok(()));
+    let list = datatype("List", &[("A", "Type")], "Type@1", &[
+      ("nil", "List A"),
+      ("cons", "∀ (x: A) (xs: List A) -> List A"),
+    ])
+    .unwrap();
+    assert_eq!(check_data(&HashMap::new(), &list), Ok(()));
+    let cons = &list.defs()[2];
+    assert_eq!(
+      format!("{}", cons.typ_),
+      "∀ (A: Type) (x: A) (xs: List A) -> List A"
+    );
+    let rec = &list.defs()[3];
+    assert_eq!(rec.name, "List.rec");
+    assert_eq!(
+      format!("{}", rec.typ_),
+      "∀ (A: Type) (P: ∀ (x: List A) -> Type) (nil: P (List.nil A)) (cons: \
+       ∀ (x: A) (xs: List A) (xs_ih: P xs) -> P (List.cons A x xs)) (x: List \
+       A) -> P x"
+    );
+  }
+
+  #[test]
+  fn indexes() {
+    let params = [("A", "Type"), ("a", "A")];
+    let sort = "∀ (b: A) -> Type@1";
+    let eq = datatype("Eq", &params, sort, &[("refl", "Eq A a a")]).unwrap();
+    assert_eq!(eq.indices.len(), 1);
+    assert_eq!(check_data(&HashMap::new(), &eq), Ok(()));
+    let defs = eq.defs();
+    let typ_ = "∀ (A: Type) (a: A) (b: A) -> Type@1";
+    assert_eq!(format!("{}", defs[0].typ_), typ_);
+    assert_eq!(format!("{}", defs[1].typ_), "∀ (A: Type) (a: A) -> Eq A a a");
+    assert_eq!(
+      format!("{}", defs[2].typ_),
+      "∀ (A: Type) (a: A) (P: ∀ (b: A) (x: Eq A a b) -> Type) (refl: P a \
+       (Eq.refl A a)) (b: A) (x: Eq A a b) -> P b x"
+    );
+    // A constructor must give each index
+    let res = datatype("Eq", &params, sort, &[("refl", "Eq A a")]);
+    assert!(matches!(res, Err(DataError::CtorReturnType(..))));
+  }
+
+  #[test]
+  fn recurses() {
+    let nat = datatype("Nat", &[], "Type@1", &[
+      ("zero", "Nat"),
+      ("succ", "∀ (pred: Nat) -> Nat"),
+    ])
+    .unwrap();
+    let mut defs = HashMap::new();
+    let mut refs = HashMap::new();
+    for def in nat.defs() {
+      let (d, _, a) = def.clone().embed();
+      let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+      refs.insert(def.name.clone(), (def_link, ast_link));
+      defs.insert(def_link, def);
+    }
+    let parse = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      expr(Span::new(i)).unwrap().1
+    };
+    let double = parse(
+      "Nat.rec (λ x => Nat) Nat.zero (λ pred ih => Nat.succ (Nat.succ ih)) \
+       (Nat.succ Nat.zero)",
+    );
+    let two = parse("Nat.succ (Nat.succ Nat.zero)");
+    assert_eq!(nbe::norm(&defs, &double), nbe::norm(&defs, &two));
+  }
+
+  #[test]
+  fn rejects() {
+    let res = datatype("Nat", &[], "Type@1", &[("zero", "Type")]);
+    match res {
+      Err(DataError::CtorReturnType(_, nam, _)) => assert_eq!(nam, "zero"),
+      res => panic!("unexpected result {:?}", res),
+    }
+    let res = datatype("List", &[("A", "Type")], "Type@1", &[("nil", "List")]);
+    assert!(matches!(res, Err(DataError::CtorReturnType(..))));
+    let res = datatype("Unit", &[], "Type", &[("unit", "Unit")]);
+    assert_eq!(res, Err(DataError::LevelZero(None)));
+    let res = datatype("Unit", &[], "Type@1", &[("u", "Unit"), ("u", "Unit")]);
+    assert_eq!(res, Err(DataError::DuplicateCtor(None, String::from("u"))));
+    let ctors = [("box", "∀ (x: A) -> Box A")];
+    let res = datatype("Box", &[("A", "_")], "Type@1", &ctors);
+    assert!(matches!(res, Err(DataError::Placeholder(..))));
+  }
+}
@@ -0,0 +1,184 @@
+use crate::{
+  core::check::{
+    self,
+    CheckError,
+    Ctx,
+  },
+  hashspace,
+  term::{
+    Def,
+    Defs,
+    Link,
+    Term,
+  },
+};
+
+use hashexpr::{
+  atom,
+  Expr,
+};
+
+// Typing derivations. Checking a definition with a recording context keeps
+// a tree of the judgments the checker established: that a term checks
+// against a type, that it synthesizes a type, or that two types are
+// convertible or one is a subtype of the other, each with the judgments it
+// was established from. Storing the tree in the hashspace lets a third party
+// verify that a definition checks by replaying the derivation rule by rule,
+// without trusting the checker that produced it.
+//
+// A derivation is taken of the elaborated definition, which has no
+// placeholders, so that it does not depend on the search for their solutions
+// and mentions no metavariables. Types are recorded as they were read back at
+// the point of the judgment, with variables as de Bruijn indices relative to
+// the context there, which is given by the binders the derivation has gone
+// under. Terms and types are stored as anonymous terms, by link, so that the
+// many occurrences of a type in a derivation are stored once.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Judgment {
+  // A term which checks against a type
+  Check(Term, Term),
+  // A term which synthesizes a type
+  Synth(Term, Term),
+  // Two convertible types
+  Conv(Term, Term),
+  // A type which is a subtype of another
+  Sub(Term, Term),
+}
+
+impl Judgment {
+  pub fn encode(self) -> Expr {
+    let (tag, a, b) = match self {
+      Self::Check(a, b) => ("check", a, b),
+      Self::Synth(a, b) => ("synth", a, b),
+      Self::Conv(a, b) => ("conv", a, b),
+      Self::Sub(a, b) => ("sub", a, b),
+    };
+    cons!(None, text!(String::from(tag)), link!(put(a)), link!(put(b)))
+  }
+}
+
+// Store the anonymous form of a term
+fn put(term: Term) -> Link { hashspace::put(term.embed().0.encode()) }
+
+// A judgment and the derivations of the judgments it was established from
+#[derive(Clone, Debug, PartialEq)]
+pub struct Derivation {
+  pub judgment: Judgment,
+  pub premises: Vec<Derivation>,
+}
+
+impl Derivation {
+  // Encode the derivation, storing the terms it refers to
+  pub fn encode(self) -> Expr {
+    let premises = self.premises.into_iter().map(|p| p.encode()).collect();
+    cons!(None, self.judgment.encode(), Expr::Cons(None, premises))
+  }
+
+  // The number of judgments in the derivation
+  pub fn size(&self) -> usize {
+    1 + self.premises.iter().map(|p| p.size()).sum::<usize>()
+  }
+}
+
+// The derivations being recorded, as a stack of the premises of the
+// judgments which are yet to be established
+#[derive(Clone, Debug)]
+pub struct Recorder {
+  frames: Vec<Vec<Derivation>>,
+}
+
+impl Recorder {
+  pub fn new() -> Self { Recorder { frames: vec![Vec::new()] } }
+
+  // Start establishing a judgment
+  pub fn enter(&mut self) { self.frames.push(Vec::new()) }
+
+  // Finish establishing a judgment, from the premises recorded since it was
+  // entered, keeping it if it holds
+  pub fn leave(&mut self, judgment: Option<Judgment>) {
+    let premises = self.frames.pop().unwrap_or_default();
+    if let (Some(judgment), Some(frame)) = (judgment, self.frames.last_mut()) {
+      frame.push(Derivation { judgment, premises });
+    }
+  }
+
+  // Stop recording, returning the derivations of the judgments established
+  // outside of any other
+  pub fn finish(&mut self) -> Vec<Derivation> {
+    self.frames.drain(..).next().unwrap_or_default()
+  }
+}
+
+// The derivation of a definition: those of its type being a type and of its
+// term checking against it
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefDerivation {
+  pub def: Link,
+  pub derivations: Vec<Derivation>,
+}
+
+impl DefDerivation {
+  pub fn encode(self) -> Expr {
+    let derivations =
+      self.derivations.into_iter().map(|d| d.encode()).collect();
+    cons!(
+      None,
+      text!("derivation"),
+      link!(self.def),
+      Expr::Cons(None, derivations)
+    )
+  }
+
+  pub fn store(self) -> Link { hashspace::put(self.encode()) }
+}
+
+// Check a definition, returning the derivation of its elaborated form
+pub fn derive_def(defs: &Defs, def: &Def) -> Result<DefDerivation, CheckError> {
+  let def = check::elab_def(defs, def)?;
+  let ctx = Ctx::new(defs.clone()).recording();
+  check::elab_in(ctx.clone(), &def)?;
+  let derivations = ctx.derivations();
+  let (d, ..) = def.embed();
+  Ok(DefDerivation { def: d.encode().link(), derivations })
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::term::parse,
+  };
+  use im::HashMap;
+
+  #[test]
+  fn derives() {
+    let (_, typ) = parse("∀ (A: Type) (x: A) -> A").unwrap();
+    let (_, term) = parse("λ A x => x").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, term);
+    let deriv = derive_def(&HashMap::new(), &def).unwrap();
+    // The type is a type, and the term checks against it
+    assert_eq!(deriv.derivations.len(), 2);
+    let root = &deriv.derivations[1];
+    let typ = format!("{}", def.typ_);
+    match &root.judgment {
+      Judgment::Check(term, t) => {
+        assert_eq!(format!("{}", term), "λ A x => x");
+        assert_eq!(format!("{}", t), typ);
+      }
+      j => panic!("unexpected judgment {:?}", j),
+    }
+    // `λ A x => x` checks by `λ x => x`, which checks by `x` synthesizing
+    // `A`, which is a subtype of `A`
+    assert_eq!(root.size(), 5);
+    let leaf = &root.premises[0].premises[0].premises[1];
+    assert_eq!(leaf.judgment, Judgment::Sub(
+      Term::Var(None, Name::from("A"), 1),
+      Term::Var(None, Name::from("A"), 1)
+    ));
+    let (_, term) = parse("λ A x => A").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), def.typ_, term);
+    assert!(derive_def(&HashMap::new(), &def).is_err());
+  }
+}
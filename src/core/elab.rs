@@ -0,0 +1,221 @@
+use crate::{
+  core::check::{
+    self,
+    synth,
+    CheckError,
+    Ctx,
+  },
+  term::{
+    Def,
+    Defs,
+    Term,
+  },
+};
+
+// Elaboration of the surface terms produced by the parser into terms of the
+// core calculus. The only surface construct is the placeholder, `_` or a
+// named hole `?name`, which elaboration fills in by type checking. The
+// implicit arguments of a definition are left out where it is used, and are
+// first inserted as placeholders, to be filled in the same way. Stored
+// definitions are always elaborated first, so that their content addresses
+// depend only on their core terms. A term without placeholders is already a
+// core term, and is left unchecked: well-typedness is not a requirement for
+// storing a definition.
+
+// The first placeholder or metavariable of a term, in which case it is not a
+// core term
+pub fn surface(term: &Term) -> Option<&Term> {
+  match term {
+    Term::Hol(..) | Term::Met(..) => Some(term),
+    Term::Lam(_, _, bod)
+    | Term::Slf(_, _, bod)
+    | Term::Dat(_, bod)
+    | Term::Cse(_, bod)
+    | Term::Fst(_, bod)
+    | Term::Snd(_, bod) => surface(bod),
+    Term::App(_, terms)
+    | Term::Ann(_, terms)
+    | Term::Par(_, terms)
+    | Term::Sig(_, _, terms)
+    | Term::All(_, _, _, terms) => {
+      surface(&terms.0).or_else(|| surface(&terms.1))
+    }
+    Term::Let(_, _, _, _, terms) => surface(&terms.0)
+      .or_else(|| surface(&terms.1))
+      .or_else(|| surface(&terms.2)),
+    _ => None,
+  }
+}
+
+pub fn is_core(term: &Term) -> bool { surface(term).is_none() }
+
+// The implicit binders of the head of an application, which are those of
+// the definition it refers to. The definition being elaborated is the
+// variable `rec` of its own term, if it is in scope.
+fn head_implicits<'a>(
+  defs: &'a Defs,
+  own: &'a [bool],
+  rec: Option<u64>,
+  head: &Term,
+) -> &'a [bool] {
+  match head {
+    Term::Ref(_, _, def, _) => match defs.get(def) {
+      Some(def) => &def.implicits,
+      None => &[],
+    },
+    Term::Var(_, _, idx) if Some(*idx) == rec => own,
+    _ => &[],
+  }
+}
+
+// Insert a placeholder for each implicit argument of an application. The
+// arguments given fill the explicit binders, and placeholders are inserted
+// up to the last implicit binder, or up to the first explicit binder left
+// without an argument, so that a definition used on its own has its leading
+// implicit arguments filled in.
+fn implicits(
+  defs: &Defs,
+  own: &[bool],
+  rec: Option<u64>,
+  term: &Term,
+) -> Term {
+  let go = |term: &Term| implicits(defs, own, rec, term);
+  let under = |term: &Term| implicits(defs, own, rec.map(|r| r + 1), term);
+  match term {
+    Term::App(..) | Term::Ref(..) | Term::Var(..) => {
+      let mut head = term;
+      let mut args = Vec::new();
+      while let Term::App(pos, terms) = head {
+        args.push((*pos, go(&terms.1)));
+        head = &terms.0;
+      }
+      let mut args = args.into_iter().rev();
+      let mut term = match head {
+        Term::Ref(..) | Term::Var(..) => head.clone(),
+        _ => go(head),
+      };
+      for implicit in head_implicits(defs, own, rec, head) {
+        let (pos, arg) = if *implicit {
+          (head.pos(), Term::Hol(head.pos(), None))
+        }
+        else {
+          match args.next() {
+            Some(arg) => arg,
+            None => break,
+          }
+        };
+        term = Term::App(pos, Box::new((term, arg)));
+      }
+      args.fold(term, |f, (pos, arg)| Term::App(pos, Box::new((f, arg))))
+    }
+    Term::Lam(pos, nam, bod) => {
+      Term::Lam(*pos, nam.clone(), Box::new(under(bod)))
+    }
+    Term::Slf(pos, nam, bod) => {
+      Term::Slf(*pos, nam.clone(), Box::new(under(bod)))
+    }
+    Term::Dat(pos, bod) => Term::Dat(*pos, Box::new(go(bod))),
+    Term::Cse(pos, bod) => Term::Cse(*pos, Box::new(go(bod))),
+    Term::Fst(pos, bod) => Term::Fst(*pos, Box::new(go(bod))),
+    Term::Snd(pos, bod) => Term::Snd(*pos, Box::new(go(bod))),
+    Term::Ann(pos, terms) => {
+      Term::Ann(*pos, Box::new((go(&terms.0), go(&terms.1))))
+    }
+    Term::Par(pos, terms) => {
+      Term::Par(*pos, Box::new((go(&terms.0), go(&terms.1))))
+    }
+    Term::Sig(pos, nam, terms) => {
+      Term::Sig(*pos, nam.clone(), Box::new((go(&terms.0), under(&terms.1))))
+    }
+    Term::All(pos, uses, nam, terms) => {
+      let terms = (go(&terms.0), under(&terms.1));
+      Term::All(*pos, *uses, nam.clone(), Box::new(terms))
+    }
+    Term::Let(pos, letrec, uses, nam, terms) => {
+      let exp = if *letrec { under(&terms.1) } else { go(&terms.1) };
+      let terms = (go(&terms.0), exp, under(&terms.2));
+      Term::Let(*pos, *letrec, *uses, nam.clone(), Box::new(terms))
+    }
+    _ => term.clone(),
+  }
+}
+
+// Insert a placeholder for each implicit argument left out of a closed term
+pub fn insert_implicits(defs: &Defs, term: &Term) -> Term {
+  implicits(defs, &[], None, term)
+}
+
+// Elaborate a definition, filling in its placeholders and implicit arguments.
+// The definition's own term refers to it as its outermost variable.
+pub fn elab_def(defs: &Defs, def: Def) -> Result<Def, CheckError> {
+  let typ_ = insert_implicits(defs, &def.typ_);
+  let term = implicits(defs, &def.implicits, Some(0), &def.term);
+  let def = Def { typ_, term, ..def };
+  if is_core(&def.typ_) && is_core(&def.term) {
+    Ok(def)
+  }
+  else {
+    check::elab_def(defs, &def)
+  }
+}
+
+// Elaborate a closed term, filling in its placeholders and implicit
+// arguments
+pub fn elab_term(defs: &Defs, term: &Term) -> Result<Term, CheckError> {
+  let term = insert_implicits(defs, term);
+  if is_core(&term) {
+    return Ok(term);
+  }
+  let ctx = Ctx::new(defs.clone());
+  let term = ctx.insert_metas(&term);
+  synth(&ctx, &term)?;
+  ctx.fill_auto();
+  ctx.filled()?;
+  ctx.solved()?;
+  Ok(ctx.zonk(&term))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::check::CheckErrorKind,
+    name::Name,
+    parse::term::parse,
+  };
+  use im::HashMap;
+
+  #[test]
+  fn elaborates() {
+    let (_, term) = parse("λ x => x").unwrap();
+    assert_eq!(elab_term(&HashMap::new(), &term), Ok(term.clone()));
+    let id = "((λ A x => x) :: ∀ (A: Type) (x: A) -> A)";
+    let (_, term) = parse(&format!("{} _ 1", id)).unwrap();
+    let term = elab_term(&HashMap::new(), &term).unwrap();
+    assert!(is_core(&term));
+    assert_eq!(
+      format!("{}", term),
+      "((λ A x => x) :: (∀ (A: Type) (x: A) -> A)) #Natural 1"
+    );
+    let (_, term) = parse(&format!("{} #Natural ?h", id)).unwrap();
+    match elab_term(&HashMap::new(), &term).map_err(|e| e.kind) {
+      Err(CheckErrorKind::UnfilledHoles(_)) => (),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[test]
+  fn stores_core_definitions() {
+    // An ill-typed definition without placeholders is left as is
+    let (_, typ) = parse("#Natural").unwrap();
+    let (_, term) = parse("Type").unwrap();
+    let def = Def::new(None, Name::from("x"), String::new(), typ, term);
+    let elab = elab_def(&HashMap::new(), def.clone()).unwrap();
+    assert_eq!(elab.term, def.term);
+    let (_, typ) = parse("∀ (x: _) -> #Natural").unwrap();
+    let (_, term) = parse("λ x => #add x 1").unwrap();
+    let def = Def::new(None, Name::from("inc"), String::new(), typ, term);
+    let elab = elab_def(&HashMap::new(), def).unwrap();
+    assert_eq!(format!("{}", elab.typ_), "∀ (x: #Natural) -> #Natural");
+  }
+}
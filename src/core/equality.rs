@@ -0,0 +1,119 @@
+use crate::{
+  core::{
+    nbe::eval,
+    unify::{
+      unify,
+      Metas,
+      Types,
+    },
+  },
+  definition::Transparency,
+  term::{
+    Defs,
+    Term,
+  },
+};
+
+use im::Vector;
+
+// Equality of core terms, for tools which compare terms outside of type
+// checking. Two terms are syntactically equal when they are the same up to
+// the names of their bound variables, which is when their anonymous forms,
+// and so their links, are equal. They are definitionally equal when they are
+// convertible: when their normal forms are syntactically equal, up to eta,
+// once the references to definitions at least as transparent as a given
+// transparency are unfolded. Comparing at `Transparency::Transparent` is
+// what type checking does, while `Transparency::Opaque` unfolds every
+// definition and `Transparency::Reducible` only the reducible ones.
+//
+// Both sides must be closed core terms, without placeholders. As in type
+// checking, the unfolding of definitions other than reducible ones is
+// limited by fuel, and terms which can only be compared by unfolding more of
+// them are taken to be different.
+
+// Whether two terms are equal up to the names of their bound variables
+pub fn syntactically_equal(a: &Term, b: &Term) -> bool {
+  a.clone().embed().0 == b.clone().embed().0
+}
+
+// Whether two terms are convertible, unfolding the definitions of `defs`
+// which are at least as transparent as `transparency`
+pub fn definitionally_equal(
+  defs: &Defs,
+  a: &Term,
+  b: &Term,
+  transparency: Transparency,
+) -> bool {
+  let mut metas = Metas::new();
+  metas.unfolding = transparency;
+  let a = eval(defs, Vector::new(), a);
+  let b = eval(defs, Vector::new(), b);
+  unify(defs, &mut metas, &Types::new(), &a, &b)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::{
+      span::Span,
+      term::parse_expression,
+    },
+    term::{
+      Def,
+      Refs,
+    },
+  };
+  use im::HashMap;
+
+  // Add a definition, which may refer to those before it, to `defs`
+  fn define(
+    defs: &mut Defs,
+    refs: &mut Refs,
+    nam: &str,
+    transparency: Transparency,
+    term: &str,
+  ) {
+    let parse = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      expr(Span::new(i)).unwrap().1
+    };
+    let def = Def::new(
+      None,
+      Name::from(nam),
+      String::new(),
+      parse("#Natural"),
+      parse(term),
+    );
+    let def = Def { transparency, ..def };
+    let (d, _, a) = def.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    refs.insert(Name::from(nam), (def_link, ast_link));
+    defs.insert(def_link, def);
+  }
+
+  #[test]
+  fn compares() {
+    let (mut defs, mut refs) = (HashMap::new(), HashMap::new());
+    define(&mut defs, &mut refs, "one", Transparency::Opaque, "1");
+    define(&mut defs, &mut refs, "two", Transparency::Transparent, "2");
+    define(&mut defs, &mut refs, "three", Transparency::Reducible, "3");
+    let parse = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      expr(Span::new(i)).unwrap().1
+    };
+    assert!(syntactically_equal(&parse("λ x => x"), &parse("λ y => y")));
+    assert!(!syntactically_equal(&parse("(λ x => x) 1"), &parse("1")));
+    let equal = |a: &str, b: &str, transparency| {
+      definitionally_equal(&defs, &parse(a), &parse(b), transparency)
+    };
+    assert!(equal("(λ x => x) 1", "1", Transparency::Reducible));
+    assert!(equal("λ f => f", "λ f x => f x", Transparency::Reducible));
+    assert!(!equal("one", "1", Transparency::Transparent));
+    assert!(equal("one", "1", Transparency::Opaque));
+    assert!(equal("two", "2", Transparency::Transparent));
+    assert!(!equal("two", "2", Transparency::Reducible));
+    assert!(equal("three", "3", Transparency::Reducible));
+  }
+}
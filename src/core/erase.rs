@@ -0,0 +1,247 @@
+use crate::{
+  core::{
+    check::{
+      check_def,
+      synth,
+      CheckError,
+      CheckErrorKind,
+      Ctx,
+    },
+    nbe::{
+      Closure,
+      Value,
+    },
+    uses::Uses,
+  },
+  term::{
+    Def,
+    Defs,
+    Term,
+  },
+};
+
+use std::rc::Rc;
+
+// Erasure of well-typed terms into runtime terms. Lambdas carry no uses of
+// their own, so which binders are erased is read off the types the checker
+// gives them: a lambda checked against `∀ (0 x: A) -> B` is removed along
+// with the arguments it is applied to, as is a `let 0` binding. Annotations,
+// `data` and `case` have no runtime behaviour and are removed too, and types
+// are erased to `Type`, as nothing can compute with them.
+//
+// Proofs of propositions are irrelevant, so they are erased as well: binders
+// whose type is a proposition are removed like those with 0 uses, and any
+// other proof becomes `Type`.
+
+// Erase a term of the given type
+fn erase(ctx: &Ctx, term: &Term, typ: &Value) -> Result<Term, CheckError> {
+  if ctx.is_prop(typ) {
+    return Ok(Term::Typ(None, 0));
+  }
+  match term {
+    Term::Lam(pos, nam, bod) => match ctx.force(typ.clone()) {
+      Value::All(uses, _, dom, img) => {
+        let var = Value::Var(nam.clone(), ctx.depth());
+        let ctx2 = ctx.bind(nam, (*dom).clone()).with_uses(uses);
+        let bod = erase(&ctx2, bod, &img.apply(&ctx.defs, var))?;
+        if uses == Uses::None || ctx.is_prop(&dom) {
+          Ok(bod.shift(-1, 0))
+        }
+        else {
+          Ok(Term::Lam(*pos, nam.clone(), Box::new(bod)))
+        }
+      }
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::LamNotAFunction(typ)))
+      }
+    },
+    Term::App(pos, terms) => {
+      if let Some(term) = erase_opr(ctx, term)? {
+        return Ok(term);
+      }
+      let fun_typ = synth(ctx, &terms.0)?;
+      let fun = erase(ctx, &terms.0, &fun_typ)?;
+      match ctx.force(fun_typ) {
+        Value::All(uses, _, dom, _)
+          if uses == Uses::None || ctx.is_prop(&dom) =>
+        {
+          Ok(fun)
+        }
+        Value::All(_, _, dom, _) => {
+          let arg = erase(ctx, &terms.1, &dom)?;
+          Ok(Term::App(*pos, Box::new((fun, arg))))
+        }
+        typ => {
+          let typ = ctx.quote(&typ);
+          Err(ctx.error(*pos, CheckErrorKind::NotAFunction(typ)))
+        }
+      }
+    }
+    Term::Let(pos, rec, uses, nam, terms) => {
+      let ann = ctx.eval(&terms.0);
+      let bod_ctx = if *rec {
+        let clos = Closure::new(ctx.env.clone(), terms.1.clone());
+        let val = Value::Fix(nam.clone(), clos);
+        ctx.define(nam, val, ann.clone()).with_uses(*uses)
+      }
+      else {
+        ctx.define(nam, ctx.eval(&terms.1), ann.clone()).with_uses(*uses)
+      };
+      let bod = erase(&bod_ctx, &terms.2, typ)?;
+      if *uses == Uses::None || ctx.is_prop(&ann) {
+        return Ok(bod.shift(-1, 0));
+      }
+      let exp = if *rec {
+        erase(&ctx.bind(nam, ann.clone()).with_uses(*uses), &terms.1, &ann)?
+      }
+      else {
+        erase(ctx, &terms.1, &ann)?
+      };
+      let terms = Box::new((Term::Typ(None, 0), exp, bod));
+      Ok(Term::Let(*pos, *rec, *uses, nam.clone(), terms))
+    }
+    Term::Ann(_, terms) => erase(ctx, &terms.1, &ctx.eval(&terms.0)),
+    Term::Dat(pos, bod) => match ctx.force(typ.clone()) {
+      Value::Slf(_, clos) => {
+        let this = Value::Dat(Rc::new(ctx.eval(bod)));
+        erase(ctx, bod, &clos.apply(&ctx.defs, this))
+      }
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::DatNotASelf(typ)))
+      }
+    },
+    Term::Cse(_, bod) => erase(ctx, bod, &synth(ctx, bod)?),
+    Term::Par(pos, terms) => match ctx.force(typ.clone()) {
+      Value::Sig(_, dom, img) => {
+        let fst = erase(ctx, &terms.0, &dom)?;
+        let img = img.apply(&ctx.defs, ctx.eval(&terms.0));
+        let snd = erase(ctx, &terms.1, &img)?;
+        Ok(Term::Par(*pos, Box::new((fst, snd))))
+      }
+      typ => {
+        let typ = ctx.quote(&typ);
+        Err(ctx.error(*pos, CheckErrorKind::ParNotASigma(typ)))
+      }
+    },
+    Term::Fst(pos, bod) => {
+      let bod = erase(ctx, bod, &synth(ctx, bod)?)?;
+      Ok(Term::Fst(*pos, Box::new(bod)))
+    }
+    Term::Snd(pos, bod) => {
+      let bod = erase(ctx, bod, &synth(ctx, bod)?)?;
+      Ok(Term::Snd(*pos, Box::new(bod)))
+    }
+    Term::All(..)
+    | Term::Slf(..)
+    | Term::Sig(..)
+    | Term::Typ(..)
+    | Term::LTy(..) => Ok(Term::Typ(None, 0)),
+    _ => Ok(term.clone()),
+  }
+}
+
+// Erase the arguments of a saturated application of a primitive operation
+fn erase_opr(ctx: &Ctx, term: &Term) -> Result<Option<Term>, CheckError> {
+  let mut head = term;
+  let mut args = Vec::new();
+  while let Term::App(pos, terms) = head {
+    args.push((*pos, &terms.1));
+    head = &terms.0;
+  }
+  if let Term::Opr(..) = head {
+    let mut term = head.clone();
+    for (pos, arg) in args.into_iter().rev() {
+      let arg = erase(ctx, arg, &synth(ctx, arg)?)?;
+      term = Term::App(pos, Box::new((term, arg)));
+    }
+    Ok(Some(term))
+  }
+  else {
+    Ok(None)
+  }
+}
+
+impl Term {
+  // The runtime form of a closed, well-typed term
+  pub fn erase(&self, defs: &Defs) -> Result<Term, CheckError> {
+    let ctx = Ctx::new(defs.clone());
+    let typ = synth(&ctx, self)?;
+    erase(&ctx, self, &typ)
+  }
+}
+
+impl Def {
+  // The runtime form of a well-typed definition's term, which refers to the
+  // definition itself through its outermost variable
+  pub fn erase(&self, defs: &Defs) -> Result<Term, CheckError> {
+    check_def(defs, self)?;
+    let (d, _, a) = self.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    let ctx = Ctx::new(defs.update(def_link, self.clone()));
+    let typ = ctx.eval(&self.typ_);
+    let this = Value::Ref(self.name.clone(), def_link, ast_link);
+    let ctx = ctx.define(&self.name, this, typ.clone());
+    erase(&ctx, &self.term, &typ)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::term::parse,
+  };
+  use im::HashMap;
+
+  fn erase_assert(input: &str, expected: &str) {
+    let (_, term) = parse(input).unwrap();
+    match term.erase(&HashMap::new()) {
+      Ok(term) => assert_eq!(format!("{}", term), expected),
+      Err(e) => panic!("{}: {}", input, e),
+    }
+  }
+
+  #[test]
+  fn erases() {
+    let id = "((λ A x => x) :: ∀ (0 A: Type) (x: A) -> A)";
+    erase_assert(id, "λ x => x");
+    erase_assert(&format!("{} #Natural 1", id), "(λ x => x) 1");
+    // Relevant binders are kept, but types are erased
+    let id = "((λ A x => x) :: ∀ (A: Type) (x: A) -> A)";
+    erase_assert(&format!("{} #Natural 1", id), "(λ A x => x) Type 1");
+    erase_assert("let 0 n: #Natural = 1; #add 2 3", "#add 2 3");
+    let pkg = "((#Natural, 1) :: Σ (A: Type) -> A)";
+    erase_assert(&format!("snd {}", pkg), "snd (Type, 1)");
+    erase_assert("let n: #Natural = 1; #add n n", "let n: Type := 1; #add n n");
+  }
+
+  #[test]
+  fn erases_proofs() {
+    let f = "((λ P p x => x) :: ∀ (P: Prop) (p: P) (x: #Natural) -> #Natural)";
+    erase_assert(f, "λ P x => x");
+    let prop = "(∀ (Q: Prop) (q: Q) -> Q)";
+    let proof = "(λ Q q => q)";
+    erase_assert(&format!("{} {} {} 1", f, prop, proof), "(λ P x => x) Type 1");
+    // A proof is erased entirely
+    erase_assert(&format!("({} :: {})", proof, prop), "Type");
+  }
+
+  #[test]
+  fn erases_definitions() {
+    let (_, typ) = parse("∀ (0 A: Type) (x: A) -> A").unwrap();
+    let (_, term) = parse("λ A x => ((λ y => y) :: ∀ (y: A) -> A) x").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, term);
+    let term = def.erase(&HashMap::new()).unwrap();
+    assert_eq!(format!("{}", term), "λ x => (λ y => y) x");
+    let (_, typ) = parse("∀ (0 A: Type) (x: A) -> Type").unwrap();
+    let (_, term) = parse("λ A x => A").unwrap();
+    let def = Def::new(None, Name::from("f"), String::new(), typ, term);
+    match def.erase(&HashMap::new()).map_err(|e| e.kind) {
+      Err(CheckErrorKind::ErasedVariable(nam)) => assert_eq!(nam, "A"),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+}
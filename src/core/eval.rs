@@ -3,6 +3,7 @@ use core::ptr::NonNull;
 use crate::{
   core::{
     dag::{
+      add_to_parents,
       clear_copies,
       free_dead_node,
       new_branch,
@@ -22,16 +23,82 @@ use crate::{
     primop::{
       apply_bin_op,
       apply_una_op,
+      PrimOp,
     },
   },
+  name::Name,
   term::{
     Def,
     Link,
+    Term,
   },
 };
 
 use im::HashMap;
 
+use std::{
+  collections::HashSet,
+  fmt,
+};
+
+// A reduction about to be performed by the evaluator
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reduction {
+  Beta,
+  Unfold(Name, Link),
+  // The projection of a component out of a pair
+  Proj,
+  // A primitive operation, with the total size in bytes of its operands
+  PrimOp(PrimOp, u64),
+  // The removal of a type annotation
+  Ann,
+  // The substitution of a local definition into its body
+  Let,
+  // The unrolling of a recursive local definition
+  Fix,
+}
+
+// Why evaluation was halted before reaching a normal form, or could not
+// start
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+  OutOfGas(u64),
+  // A variable not bound by any binder around it
+  UnboundVariable(String),
+  // A reference to a definition which is not among those evaluated with
+  MissingLink(String, Link),
+  // A term the graph of the evaluator cannot represent, as it says
+  Malformed(String),
+}
+
+impl fmt::Display for EvalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::OutOfGas(limit) => {
+        write!(f, "Evaluation ran out of gas (limit {})", limit)
+      }
+      Self::UnboundVariable(name) => write!(f, "Unbound variable {}", name),
+      Self::MissingLink(name, link) => {
+        write!(f, "The definition of {} is missing ({})", name, link)
+      }
+      Self::Malformed(what) => write!(f, "Cannot evaluate {}", what),
+    }
+  }
+}
+
+impl std::error::Error for EvalError {}
+
+// Hooks into the evaluator, called before every reduction. Returning an
+// error halts evaluation.
+pub trait Observer {
+  fn observe(&mut self, red: Reduction) -> Result<(), EvalError>;
+}
+
+// The trivial observer, which never halts evaluation
+impl Observer for () {
+  fn observe(&mut self, _red: Reduction) -> Result<(), EvalError> { Ok(()) }
+}
+
 // The core up-copy function.
 pub fn upcopy(new_child: DAG, cc: ParentCell) {
   unsafe {
@@ -146,18 +213,52 @@ pub fn reduce_lam(redex: NonNull<Branch>, lam: NonNull<Single>) -> DAG {
 }
 
 // Reduce term to its weak head normal form
-pub fn whnf(defs: &HashMap<Link, Def>, mut node: DAG) -> DAG {
+pub fn whnf(defs: &HashMap<Link, Def>, node: DAG) -> DAG {
+  match whnf_observed(defs, node, &mut ()) {
+    Ok(node) => node,
+    // Only an observer stops evaluation, and `()` never does
+    Err(e) => unreachable!("{}", e),
+  }
+}
+
+// Reduce term to its weak head normal form, reporting every reduction to
+// `obs`
+pub fn whnf_observed(
+  defs: &HashMap<Link, Def>,
+  mut node: DAG,
+  obs: &mut dyn Observer,
+) -> Result<DAG, EvalError> {
   let mut trail = vec![];
   loop {
     match node {
       DAG::Branch(link) => unsafe {
-        let Branch { left, tag, .. } = &*link.as_ptr();
+        let Branch { left, right, tag, .. } = &*link.as_ptr();
+        let (left, right) = (*left, *right);
         match tag {
           BranchTag::App => {
             trail.push(link);
-            node = *left;
+            node = left;
+          }
+          BranchTag::Ann => {
+            obs.observe(Reduction::Ann)?;
+            replace_child(node, right);
+            free_dead_node(node);
+            node = right;
+          }
+          BranchTag::Let(..) => {
+            obs.observe(Reduction::Let)?;
+            // The annotation of the bound expression is dropped with the
+            // definition, so it isn't charged again at every use
+            if let DAG::Branch(ann) = right {
+              let exp = (*ann.as_ptr()).right;
+              replace_child(right, exp);
+              free_dead_node(right);
+            }
+            match left {
+              DAG::Single(lam) => node = reduce_lam(link, lam),
+              _ => break,
+            }
           }
-          // TODO: Add the `Ann` and `Let` cases
           _ => break,
         }
       },
@@ -166,36 +267,73 @@ pub fn whnf(defs: &HashMap<Link, Def>, mut node: DAG) -> DAG {
         match tag {
           SingleTag::Lam => {
             if let Some(app_link) = trail.pop() {
+              obs.observe(Reduction::Beta)?;
               node = reduce_lam(app_link, link);
             }
             else {
               break;
             }
           }
-          // TODO: Add the `Fix` case.
+          SingleTag::Fst | SingleTag::Snd => {
+            let fst = matches!(tag, SingleTag::Fst);
+            let pair = whnf_observed(defs, (*link.as_ptr()).body, obs)?;
+            match pair {
+              DAG::Branch(par) => match (*par.as_ptr()).tag {
+                BranchTag::Par => {
+                  obs.observe(Reduction::Proj)?;
+                  let Branch { left, right, .. } = *par.as_ptr();
+                  let prj = if fst { left } else { right };
+                  replace_child(node, prj);
+                  free_dead_node(node);
+                  node = prj;
+                }
+                _ => break,
+              },
+              _ => break,
+            }
+          }
+          SingleTag::Fix => {
+            obs.observe(Reduction::Fix)?;
+            // `fix x => e` unrolls to `e[x := fix x => e]`, the contraction
+            // of the redex `(fix x => e) (fix x => e)`. As the fixpoint is
+            // shared by both sides of the redex, `reduce_lam` copies its body
+            // instead of consuming it.
+            let app = new_branch(None, node, node, BranchTag::App);
+            replace_child(node, DAG::Branch(app));
+            add_to_parents(node, (*app.as_ptr()).left_ref);
+            add_to_parents(node, (*app.as_ptr()).right_ref);
+            node = reduce_lam(app, link);
+          }
           _ => break,
         }
       },
       DAG::Leaf(link) => unsafe {
         let Leaf { tag, .. } = &*link.as_ptr();
         match tag {
-          LeafTag::Ref(nam, def_link, _) => {
+          LeafTag::Ref(nam, def_link, ast_link) => {
+            // References without a definition are left as inert heads
             if let Some(def) = defs.get(def_link) {
-              node = DAG::from_term(def.clone().term)
+              obs.observe(Reduction::Unfold(nam.clone(), *def_link))?;
+              // A definition's term is closed over a variable standing for
+              // the definition itself
+              let this = Term::Ref(None, nam.clone(), *def_link, *ast_link);
+              node = DAG::from_term(def.term.subst(0, &this))?
             }
             else {
-              panic!("undefined runtime reference: {}, {}", nam, def_link);
+              break;
             }
           }
           LeafTag::Opr(opr) => {
             let len = trail.len();
             if len >= 1 && opr.arity() == 1 {
-              let arg = whnf(defs, (*trail[len - 1].as_ptr()).right);
+              let arg =
+                whnf_observed(defs, (*trail[len - 1].as_ptr()).right, obs)?;
               match arg {
                 DAG::Leaf(x) => {
                   let x = (*x.as_ptr()).tag.clone();
                   match x {
                     LeafTag::Lit(x) => {
+                      obs.observe(Reduction::PrimOp(*opr, x.byte_len()))?;
                       let res = apply_una_op(*opr, x);
                       if let Some(res) = res {
                         trail.pop();
@@ -215,14 +353,18 @@ pub fn whnf(defs: &HashMap<Link, Def>, mut node: DAG) -> DAG {
               }
             }
             else if len >= 2 && opr.arity() == 2 {
-              let arg1 = whnf(defs, (*trail[len - 2].as_ptr()).right);
-              let arg2 = whnf(defs, (*trail[len - 1].as_ptr()).right);
+              let arg1 =
+                whnf_observed(defs, (*trail[len - 2].as_ptr()).right, obs)?;
+              let arg2 =
+                whnf_observed(defs, (*trail[len - 1].as_ptr()).right, obs)?;
               match (arg1, arg2) {
                 (DAG::Leaf(x), DAG::Leaf(y)) => {
                   let x = (*x.as_ptr()).tag.clone();
                   let y = (*y.as_ptr()).tag.clone();
                   match (x, y) {
                     (LeafTag::Lit(x), LeafTag::Lit(y)) => {
+                      let size = x.byte_len() + y.byte_len();
+                      obs.observe(Reduction::PrimOp(*opr, size))?;
                       let res = apply_bin_op(*opr, y, x);
                       if let Some(res) = res {
                         trail.pop();
@@ -268,38 +410,147 @@ pub fn whnf(defs: &HashMap<Link, Def>, mut node: DAG) -> DAG {
     }
   }
   if trail.is_empty() {
-    return node;
+    return Ok(node);
   }
-  DAG::Branch(trail[0])
+  Ok(DAG::Branch(trail[0]))
 }
 
 // Reduce term to its normal form
-pub fn norm(defs: &HashMap<Link, Def>, mut top_node: DAG) -> DAG {
-  top_node = whnf(defs, top_node);
+pub fn norm(defs: &HashMap<Link, Def>, top_node: DAG) -> DAG {
+  match norm_observed(defs, top_node, &mut ()) {
+    Ok(node) => node,
+    // Only an observer stops evaluation, and `()` never does
+    Err(e) => unreachable!("{}", e),
+  }
+}
+
+// Reduce term to its normal form, reporting every reduction to `obs`
+pub fn norm_observed(
+  defs: &HashMap<Link, Def>,
+  mut top_node: DAG,
+  obs: &mut dyn Observer,
+) -> Result<DAG, EvalError> {
+  top_node = whnf_observed(defs, top_node, obs)?;
   let mut trail = vec![top_node];
   while let Some(node) = trail.pop() {
     match node {
       DAG::Branch(link) => unsafe {
         let branch = &mut *link.as_ptr();
-        trail.push(whnf(defs, branch.left));
-        trail.push(whnf(defs, branch.right));
+        trail.push(whnf_observed(defs, branch.left, obs)?);
+        trail.push(whnf_observed(defs, branch.right, obs)?);
       },
       DAG::Single(link) => unsafe {
         let single = &mut *link.as_ptr();
-        trail.push(whnf(defs, single.body));
+        trail.push(whnf_observed(defs, single.body, obs)?);
       },
       _ => (),
     }
   }
-  top_node
+  Ok(top_node)
+}
+
+// Why a term the graph cannot represent, a hole or a metavariable, cannot be
+// evaluated
+pub fn malformed(term: &Term) -> EvalError {
+  match term {
+    Term::Hol(_, Some(name)) => {
+      EvalError::Malformed(format!("the hole ?{}", name))
+    }
+    Term::Hol(_, None) => EvalError::Malformed(String::from("a placeholder _")),
+    Term::Met(_, idx) => {
+      EvalError::Malformed(format!("the metavariable ?{}", idx))
+    }
+    term => EvalError::Malformed(format!("the term {}", term)),
+  }
+}
+
+// Check that a term can be evaluated with `defs`: that its variables are
+// bound, that the definitions it refers to, and those they refer to in turn,
+// are among `defs`, and that it has no terms the graph cannot represent,
+// such as holes, which `DAG::from_term` refuses
+pub fn validate(
+  defs: &HashMap<Link, Def>,
+  term: &Term,
+) -> Result<(), EvalError> {
+  fn go(
+    defs: &HashMap<Link, Def>,
+    term: &Term,
+    depth: u64,
+    seen: &mut HashSet<Link>,
+  ) -> Result<(), EvalError> {
+    match term {
+      Term::Var(_, name, idx) if *idx >= depth => {
+        Err(EvalError::UnboundVariable(name.to_string()))
+      }
+      Term::Ref(_, name, def_link, _) => {
+        if !seen.insert(*def_link) {
+          return Ok(());
+        }
+        match defs.get(def_link) {
+          // A definition's term is closed over the definition itself
+          Some(def) => go(defs, &def.term, 1, seen),
+          None => Err(EvalError::MissingLink(name.to_string(), *def_link)),
+        }
+      }
+      Term::Lam(_, _, body) | Term::Slf(_, _, body) => {
+        go(defs, body, depth + 1, seen)
+      }
+      Term::Dat(_, body)
+      | Term::Cse(_, body)
+      | Term::Fst(_, body)
+      | Term::Snd(_, body) => go(defs, body, depth, seen),
+      Term::All(_, _, _, terms) | Term::Sig(_, _, terms) => {
+        go(defs, &terms.0, depth, seen)?;
+        go(defs, &terms.1, depth + 1, seen)
+      }
+      Term::App(_, terms) | Term::Ann(_, terms) | Term::Par(_, terms) => {
+        go(defs, &terms.0, depth, seen)?;
+        go(defs, &terms.1, depth, seen)
+      }
+      Term::Let(_, rec, _, _, terms) => {
+        go(defs, &terms.0, depth, seen)?;
+        go(defs, &terms.1, if *rec { depth + 1 } else { depth }, seen)?;
+        go(defs, &terms.2, depth + 1, seen)
+      }
+      Term::Hol(..) | Term::Met(..) => Err(malformed(term)),
+      Term::Var(..)
+      | Term::Typ(..)
+      | Term::Prp(_)
+      | Term::Lit(..)
+      | Term::LTy(..)
+      | Term::Opr(..) => Ok(()),
+    }
+  }
+  go(defs, term, 0, &mut HashSet::new())
+}
+
+// Reduce a term to its normal form, reporting every reduction to `obs`,
+// failing rather than panicking if it cannot be evaluated with `defs`
+pub fn eval(
+  defs: &HashMap<Link, Def>,
+  term: &Term,
+  obs: &mut dyn Observer,
+) -> Result<Term, EvalError> {
+  validate(defs, term)?;
+  let dag = norm_observed(defs, DAG::from_term(term.clone())?, obs)?;
+  Ok(dag.to_term())
 }
 
 #[cfg(test)]
 mod test {
   use super::{
+    eval,
     norm,
+    EvalError,
     DAG,
   };
+  use crate::{
+    name::Name,
+    term::{
+      Link,
+      Term,
+    },
+  };
   use hashexpr::span::Span;
   use im::HashMap;
 
@@ -309,7 +560,7 @@ mod test {
     let (i, tree) = crate::parse::term::parse(i)?;
     let (i, _) = nom::character::complete::multispace0(i)?;
     let (i, _) = nom::combinator::eof(i)?;
-    let dag = DAG::from_term(tree);
+    let dag = DAG::from_term(tree).unwrap();
     Ok((i, dag))
   }
 
@@ -366,6 +617,30 @@ mod test {
     let (_, trm) = parse(trm_str).unwrap();
     println!("{:?}", DAG::to_term(&trm));
     // assert_eq!(true, false);
-    norm_assert(trm_str, id)
+    norm_assert(trm_str, id);
+    // Annotations and local definitions
+    norm_assert("(λ x => x) :: Type", "λ x => x");
+    norm_assert("let id : Type = λ x => x; id id", "λ x => x");
+    norm_assert("letrec f (g: Type) : Type = g f; f (λ h => Type)", "Type");
+  }
+
+  #[test]
+  pub fn fails_gracefully() {
+    let defs = HashMap::new();
+    let (_, id) = crate::parse::term::parse("λ x => x").unwrap();
+    let res = eval(&defs, &id, &mut ()).map(|term| format!("{}", term));
+    assert_eq!(res, Ok(String::from("λ x => x")));
+    let var = Term::Var(None, Name::from("y"), 0);
+    let res = eval(&defs, &var, &mut ());
+    assert_eq!(res, Err(EvalError::UnboundVariable(String::from("y"))));
+    let link = Link::make(b"missing");
+    let name = String::from("missing");
+    let missing = Term::Ref(None, Name::from(&name), link, link);
+    let app = Term::App(None, Box::new((id, missing)));
+    let res = eval(&defs, &app, &mut ());
+    assert_eq!(res, Err(EvalError::MissingLink(name, link)));
+    let hole = Term::Hol(None, Some(String::from("goal")));
+    let e = eval(&defs, &hole, &mut ()).unwrap_err();
+    assert_eq!(e.to_string(), "Cannot evaluate the hole ?goal");
   }
 }
@@ -0,0 +1,149 @@
+use crate::{
+  core::{
+    dag::DAG,
+    eval::{
+      norm_observed,
+      whnf_observed,
+      EvalError,
+      Observer,
+      Reduction,
+    },
+  },
+  term::Defs,
+};
+
+// Deterministic gas metering. Every reduction has a fixed cost which depends
+// only on the reduction and, for primitive operations, on the byte size of
+// the operands, so a given term consumes exactly the same gas on every
+// platform. The evaluator's reduction order is itself deterministic, which
+// makes metered evaluation suitable for consensus-critical contexts.
+
+// The cost in gas of each kind of reduction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Costs {
+  pub beta: u64,
+  pub unfold: u64,
+  pub primop: u64,
+  pub primop_byte: u64,
+}
+
+impl Default for Costs {
+  fn default() -> Self {
+    Costs { beta: 1, unfold: 2, primop: 1, primop_byte: 1 }
+  }
+}
+
+impl Costs {
+  pub fn cost(&self, red: &Reduction) -> u64 {
+    match red {
+      // Projecting out of a pair, dropping an annotation and substituting a
+      // local definition are charged as beta reductions
+      Reduction::Beta
+      | Reduction::Proj
+      | Reduction::Ann
+      | Reduction::Let => self.beta,
+      // Unrolling a recursive local definition is charged as unfolding a
+      // global one
+      Reduction::Unfold(..) | Reduction::Fix => self.unfold,
+      Reduction::PrimOp(_, size) => {
+        self.primop.saturating_add(self.primop_byte.saturating_mul(*size))
+      }
+    }
+  }
+}
+
+// A gas meter which halts evaluation once its limit would be exceeded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gas {
+  pub limit: u64,
+  pub used: u64,
+  pub costs: Costs,
+}
+
+impl Gas {
+  pub fn new(limit: u64) -> Self { Gas::with_costs(limit, Costs::default()) }
+
+  pub fn with_costs(limit: u64, costs: Costs) -> Self {
+    Gas { limit, used: 0, costs }
+  }
+
+  pub fn remaining(&self) -> u64 { self.limit - self.used }
+}
+
+impl Observer for Gas {
+  fn observe(&mut self, red: Reduction) -> Result<(), EvalError> {
+    let cost = self.costs.cost(&red);
+    if cost > self.remaining() {
+      // A failed charge consumes the rest of the gas, so the amount used is
+      // the same whether or not evaluation runs out
+      self.used = self.limit;
+      return Err(EvalError::OutOfGas(self.limit));
+    }
+    self.used += cost;
+    Ok(())
+  }
+}
+
+// Reduce term to its weak head normal form, charging `gas` for every
+// reduction
+pub fn whnf(defs: &Defs, node: DAG, gas: &mut Gas) -> Result<DAG, EvalError> {
+  whnf_observed(defs, node, gas)
+}
+
+// Reduce term to its normal form, charging `gas` for every reduction
+pub fn norm(defs: &Defs, node: DAG, gas: &mut Gas) -> Result<DAG, EvalError> {
+  norm_observed(defs, node, gas)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::nbe::tests::AffineTerm,
+    parse::term::parse,
+  };
+  use im::HashMap;
+
+  fn gas_used(input: &str) -> u64 {
+    let (_, term) = parse(input).unwrap();
+    let mut gas = Gas::new(u64::MAX);
+    norm(&HashMap::new(), DAG::from_term(term).unwrap(), &mut gas).unwrap();
+    gas.used
+  }
+
+  #[test]
+  fn costs() {
+    assert_eq!(gas_used("λ x => x"), 0);
+    assert_eq!(gas_used("(λ x => x) Type"), 1);
+    assert_eq!(gas_used("#add 1 2"), 3);
+    assert_eq!(gas_used("#add 256 2"), 4);
+    assert_eq!(gas_used("Type :: Type"), 1);
+    assert_eq!(gas_used("let x : Type = Type; x"), 1);
+    assert_eq!(gas_used("letrec f (x: Type) : Type = x; f Type"), 4);
+  }
+
+  #[test]
+  fn out_of_gas() {
+    let (_, term) = parse("(λ x y => y) Type Type").unwrap();
+    let mut gas = Gas::new(1);
+    let dag = DAG::from_term(term.clone()).unwrap();
+    let res = norm(&HashMap::new(), dag, &mut gas);
+    assert_eq!(res.err(), Some(EvalError::OutOfGas(1)));
+    assert_eq!(gas.used, 1);
+    let mut gas = Gas::new(2);
+    let res = norm(&HashMap::new(), DAG::from_term(term).unwrap(), &mut gas);
+    assert_eq!(format!("{}", res.unwrap()), "Type");
+    assert_eq!(gas.used, 2);
+  }
+
+  #[quickcheck]
+  fn gas_is_deterministic(x: AffineTerm) -> bool {
+    let run = || {
+      let mut gas = Gas::new(u64::MAX);
+      let dag = DAG::from_term(x.0.clone()).unwrap();
+      norm(&HashMap::new(), dag, &mut gas).unwrap();
+      gas.used
+    };
+    run() == run()
+  }
+}
@@ -20,24 +20,33 @@ use num_bigint::{
   Sign,
 };
 
-use std::fmt;
+use std::{
+  convert::TryInto,
+  fmt,
+};
 
 #[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
   Natural(BigUint),
   Integer(BigInt),
   BitString(Vec<u8>),
   Text(String),
   Char(char),
+  // An IEEE-754 double, kept as its bits so that literals compare by their
+  // representation. Only finite values are literals.
+  Float(u64),
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LitType {
   Natural,
   Integer,
   BitString,
   Text,
   Char,
+  Float,
 }
 
 impl fmt::Display for Literal {
@@ -53,8 +62,13 @@ impl fmt::Display for Literal {
         let x: &[u8] = x.as_ref();
         write!(f, "~\"{}\"", Base::encode(&Base::_64, x))
       }
-      Text(x) => write!(f, "\"{}\"", x.escape_default()),
-      Char(x) => write!(f, "'{}'", x.escape_default()),
+      // Printable characters are kept as they are, and the rest written with
+      // the escapes the parser reads
+      Text(x) => write!(f, "\"{}\"", x.escape_debug()),
+      Char(x) => write!(f, "'{}'", x.escape_debug()),
+      // The debug form always has a point or an exponent, which tells it apart
+      // from a natural
+      Float(x) => write!(f, "{:?}", f64::from_bits(*x)),
     }
   }
 }
@@ -67,6 +81,10 @@ impl Literal {
       Self::BitString(x) => bits!(x),
       Self::Text(x) => text!(x),
       Self::Char(x) => char!(x),
+      // Hashexpr has no floating point atom
+      Self::Float(x) => {
+        cons!(None, text!("#Float"), bits!(x.to_be_bytes().to_vec()))
+      }
     }
   }
 
@@ -77,9 +95,43 @@ impl Literal {
       Atom(_, Bits(x)) => Ok(Self::BitString(x)),
       Atom(_, Text(x)) => Ok(Self::Text(x)),
       Atom(_, Char(x)) => Ok(Self::Char(x)),
+      Cons(pos, xs) => match xs.as_slice() {
+        [Atom(_, Text(tag)), Atom(_, Bits(x))] if tag == "#Float" => {
+          let x = x.as_slice().try_into().map(u64::from_be_bytes);
+          match x {
+            Ok(x) if f64::from_bits(x).is_finite() => Ok(Self::Float(x)),
+            _ => Err(DecodeError::new(pos, vec![Expected::Literal])),
+          }
+        }
+        _ => Err(DecodeError::new(pos, vec![Expected::Literal])),
+      },
       _ => Err(DecodeError::new(x.position(), vec![Expected::Literal])),
     }
   }
+
+  pub fn lit_type(&self) -> LitType {
+    match self {
+      Self::Natural(_) => LitType::Natural,
+      Self::Integer(_) => LitType::Integer,
+      Self::BitString(_) => LitType::BitString,
+      Self::Text(_) => LitType::Text,
+      Self::Char(_) => LitType::Char,
+      Self::Float(_) => LitType::Float,
+    }
+  }
+
+  // The size of the literal's canonical byte encoding, which is independent
+  // of the platform's word size
+  pub fn byte_len(&self) -> u64 {
+    match self {
+      Self::Natural(x) => x.to_bytes_be().len() as u64,
+      Self::Integer(x) => x.to_signed_bytes_be().len() as u64,
+      Self::BitString(x) => x.len() as u64,
+      Self::Text(x) => x.len() as u64,
+      Self::Char(x) => x.len_utf8() as u64,
+      Self::Float(_) => 8,
+    }
+  }
 }
 
 impl LitType {
@@ -90,6 +142,7 @@ impl LitType {
       Self::BitString => text!("#BitString"),
       Self::Text => text!("#Text"),
       Self::Char => text!("#Char"),
+      Self::Float => text!("#Float"),
     }
   }
 
@@ -102,6 +155,7 @@ impl LitType {
       }
       Atom(_, Text(n)) if *n == String::from("#Text") => Ok(Self::Text),
       Atom(_, Text(n)) if *n == String::from("#Char") => Ok(Self::Char),
+      Atom(_, Text(n)) if *n == String::from("#Float") => Ok(Self::Float),
       _ => Err(DecodeError::new(x.position(), vec![Expected::LitType])),
     }
   }
@@ -115,6 +169,7 @@ impl fmt::Display for LitType {
       Self::BitString => write!(f, "#BitString"),
       Self::Text => write!(f, "#Text"),
       Self::Char => write!(f, "#Char"),
+      Self::Float => write!(f, "#Float"),
     }
   }
 }
@@ -161,6 +216,13 @@ pub mod tests {
     })
   }
 
+  pub fn arbitrary_float() -> Box<dyn Fn(&mut Gen) -> Literal> {
+    Box::new(move |g: &mut Gen| {
+      let x: f64 = Arbitrary::arbitrary(g);
+      Literal::Float(if x.is_finite() { x } else { 0.0 }.to_bits())
+    })
+  }
+
   impl Arbitrary for Literal {
     fn arbitrary(g: &mut Gen) -> Self {
       frequency(g, vec![
@@ -168,7 +230,8 @@ pub mod tests {
         (1, arbitrary_int()),
         (1, arbitrary_bits()),
         (1, arbitrary_text()),
-        (1, Box::new(|g| Self::Char(Arbitrary::arbitrary(g))))
+        (1, Box::new(|g| Self::Char(Arbitrary::arbitrary(g)))),
+        (1, arbitrary_float()),
       ])
     }
   }
@@ -189,6 +252,7 @@ pub mod tests {
         (1, Box::new(|_| Self::BitString)),
         (1, Box::new(|_| Self::Text)),
         (1, Box::new(|_| Self::Char)),
+        (1, Box::new(|_| Self::Float)),
         ];
       frequency(g, input)
     }
@@ -0,0 +1,432 @@
+use crate::{
+  core::datatype::{
+    apps,
+    lams,
+  },
+  name::Name,
+  term::{
+    Def,
+    Refs,
+    Term,
+  },
+};
+
+use hashexpr::position::Pos;
+
+use std::fmt;
+
+// Pattern matching, compiled into the eliminations of datatypes. A match
+//
+//   match n return #Natural {
+//     Nat.zero => 0,
+//     Nat.succ Nat.zero => 1,
+//     Nat.succ (Nat.succ m) => 2,
+//   }
+//
+// becomes a `case` of its scrutinee with an arm for each constructor, in
+// which the fields a clause's patterns match further are matched in turn:
+//
+//   (case n) (λ x => #Natural) 0
+//     (λ pred => (case pred) (λ x => #Natural) 1 (λ pred => 2))
+//
+// Clauses are tried in order, the first clause which matches a value being
+// the one taken, and a variable or `_` matches any value. The return type
+// does not depend on the matched value, so that the motive of each `case`
+// ignores it, and every value must be matched by a clause. The datatypes
+// matched are read from the definitions of the constructors' types, so that
+// they need not be known otherwise.
+
+// The constructors of a datatype as `case` eliminates it: their names, as
+// defined, and the names of their fields, with the names of its indices
+#[derive(Clone, Debug, PartialEq)]
+pub struct Family {
+  pub name: Name,
+  pub indices: Vec<Name>,
+  pub ctors: Vec<(Name, Vec<Name>)>,
+}
+
+impl Family {
+  // The family of the constructor `ctor`, if it is one, read from the
+  // definition of the datatype it is named after
+  pub fn of_ctor(refs: &Refs, ctor: &str) -> Option<(Family, usize)> {
+    let name = &ctor[..ctor.rfind('.')?];
+    let (def, _) = refs.get(name)?;
+    let def = Def::get_link(*def).ok()?;
+    // The datatype's self type, under its parameters and indices
+    let mut term = &def.term;
+    while let Term::Lam(_, _, bod) = term {
+      term = bod;
+    }
+    let mut cases = match term {
+      Term::Slf(_, _, bod) => &**bod,
+      _ => return None,
+    };
+    let (mut motive, rest) = match cases {
+      Term::All(_, _, _, terms) => (&terms.0, &terms.1),
+      _ => return None,
+    };
+    cases = rest;
+    let mut indices = Vec::new();
+    while let Term::All(_, _, nam, terms) = motive {
+      indices.push(nam.clone());
+      motive = &terms.1;
+    }
+    // The last binder of the motive is the value eliminated
+    indices.pop()?;
+    let mut ctors = Vec::new();
+    while let Term::All(_, _, nam, terms) = cases {
+      let mut fields = Vec::new();
+      let mut typ = &terms.0;
+      while let Term::All(_, _, field, terms) = typ {
+        fields.push(field.clone());
+        typ = &terms.1;
+      }
+      ctors.push((Name::from(format!("{}.{}", name, nam)), fields));
+      cases = &terms.1;
+    }
+    let idx = ctors.iter().position(|(nam, _)| *nam == ctor)?;
+    Some((Family { name: Name::from(name), indices, ctors }, idx))
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+  // A variable, or `_`, which matches any value
+  Var(Option<Pos>, Name),
+  // The constructor with index `idx` of a family, with patterns for each of
+  // its fields
+  Ctor(Option<Pos>, Family, usize, Vec<Pattern>),
+}
+
+impl Pattern {
+  pub fn pos(&self) -> Option<Pos> {
+    match self {
+      Self::Var(pos, _) | Self::Ctor(pos, ..) => *pos,
+    }
+  }
+
+  // The variables the pattern binds, in order
+  pub fn vars(&self) -> Vec<(Option<Pos>, Name)> {
+    match self {
+      Self::Var(_, nam) if *nam == "_" => Vec::new(),
+      Self::Var(pos, nam) => vec![(*pos, nam.clone())],
+      Self::Ctor(_, _, _, args) => {
+        args.iter().flat_map(|pat| pat.vars()).collect()
+      }
+    }
+  }
+}
+
+// A clause of a match, whose body is in the scope of the variables of its
+// pattern, the last one innermost
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clause {
+  pub pattern: Pattern,
+  pub body: Term,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchError {
+  // A constructor given the wrong number of patterns for its fields
+  CtorArity(Option<Pos>, String, usize, usize),
+  // A variable bound twice by the same pattern
+  DuplicateVar(Option<Pos>, String),
+  // Patterns of values of different datatypes in the same position
+  MixedDatatypes(Option<Pos>, String, String),
+  // A constructor, where the values built with it are matched by no clause
+  NonExhaustive(Option<Pos>, String),
+}
+
+impl MatchError {
+  pub fn pos(&self) -> Option<Pos> {
+    match self {
+      Self::CtorArity(pos, ..)
+      | Self::DuplicateVar(pos, _)
+      | Self::MixedDatatypes(pos, ..)
+      | Self::NonExhaustive(pos, _) => *pos,
+    }
+  }
+}
+
+impl fmt::Display for MatchError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::CtorArity(_, nam, expected, given) => write!(
+        f,
+        "Constructor {} has {} fields, but is matched with {} patterns",
+        nam, expected, given
+      ),
+      Self::DuplicateVar(_, nam) => {
+        write!(f, "Variable {} is bound twice in the same pattern", nam)
+      }
+      Self::MixedDatatypes(_, a, b) => write!(
+        f,
+        "Values of {} and of {} are matched in the same position",
+        a, b
+      ),
+      Self::NonExhaustive(_, nam) => {
+        write!(f, "No clause matches the values built with {}", nam)
+      }
+    }
+  }
+}
+
+impl std::error::Error for MatchError {}
+
+// A value matched: a term in the scope the match is in, or a field bound by
+// the compiled match, by its level below that scope
+#[derive(Clone, Debug)]
+enum Occurrence {
+  Term(Term),
+  Field(Name, u64),
+}
+
+impl Occurrence {
+  // The value, under the `depth` binders of the compiled match
+  fn to_term(&self, depth: u64) -> Term {
+    match self {
+      Self::Term(term) => term.shift(depth as i64, 0),
+      Self::Field(nam, lvl) => Term::Var(None, nam.clone(), depth - 1 - lvl),
+    }
+  }
+}
+
+// A row of the clause matrix: the patterns left to match, each against the
+// occurrence in its column, and the variables already matched
+#[derive(Clone, Debug)]
+struct Row {
+  pats: Vec<Pattern>,
+  binds: Vec<(Name, Occurrence)>,
+  clause: usize,
+}
+
+// Compile the match of `scrut` against `clauses` into eliminations with the
+// non-dependent return type `typ`. The scrutinee and the type are in the
+// scope the match is in.
+pub fn compile(
+  pos: Option<Pos>,
+  scrut: Term,
+  typ: Term,
+  clauses: &[Clause],
+) -> Result<Term, MatchError> {
+  for clause in clauses {
+    let vars = clause.pattern.vars();
+    for (i, (pos, nam)) in vars.iter().enumerate() {
+      if vars[..i].iter().any(|(_, other)| other == nam) {
+        return Err(MatchError::DuplicateVar(*pos, nam.to_string()));
+      }
+    }
+  }
+  let rows = clauses
+    .iter()
+    .enumerate()
+    .map(|(clause, c)| Row {
+      pats: vec![c.pattern.clone()],
+      binds: Vec::new(),
+      clause,
+    })
+    .collect();
+  let occs = vec![Occurrence::Term(scrut)];
+  let matcher = Matcher { pos, typ, clauses };
+  matcher.compile(&occs, rows, 0, "any constructor")
+}
+
+struct Matcher<'a> {
+  pos: Option<Pos>,
+  typ: Term,
+  clauses: &'a [Clause],
+}
+
+impl<'a> Matcher<'a> {
+  // The elimination of the occurrences `occs` by the rows, under `depth`
+  // binders, where `ctor` is the constructor the rows were last specialized
+  // to
+  fn compile(
+    &self,
+    occs: &[Occurrence],
+    rows: Vec<Row>,
+    depth: u64,
+    ctor: &str,
+  ) -> Result<Term, MatchError> {
+    let row = match rows.first() {
+      Some(row) => row,
+      None => {
+        return Err(MatchError::NonExhaustive(self.pos, ctor.to_string()));
+      }
+    };
+    let col = row.pats.iter().position(|p| matches!(p, Pattern::Ctor(..)));
+    let col = match col {
+      Some(col) => col,
+      None => return Ok(self.body(row, occs, depth)),
+    };
+    let family = match &row.pats[col] {
+      Pattern::Ctor(_, family, ..) => family.clone(),
+      Pattern::Var(..) => unreachable!(),
+    };
+    let mut arms = Vec::new();
+    for (j, (nam, fields)) in family.ctors.iter().enumerate() {
+      let k = fields.len();
+      let mut specialized = Vec::new();
+      for row in &rows {
+        let mut pats = row.pats.clone();
+        let mut binds = row.binds.clone();
+        match pats.remove(col) {
+          Pattern::Ctor(pos, other, ..) if other.name != family.name => {
+            let (a, b) = (family.name.to_string(), other.name.to_string());
+            return Err(MatchError::MixedDatatypes(pos, a, b));
+          }
+          Pattern::Ctor(_, _, idx, args) if idx == j => pats.extend(args),
+          Pattern::Ctor(..) => continue,
+          Pattern::Var(pos, var) => {
+            if var != "_" {
+              binds.push((var, occs[col].clone()));
+            }
+            pats.extend((0..k).map(|_| Pattern::Var(pos, Name::from("_"))));
+          }
+        }
+        specialized.push(Row { pats, binds, clause: row.clause });
+      }
+      let mut occs2 = occs.to_vec();
+      occs2.remove(col);
+      occs2.extend(
+        fields
+          .iter()
+          .enumerate()
+          .map(|(t, field)| Occurrence::Field(field.clone(), depth + t as u64)),
+      );
+      let depth2 = depth + k as u64;
+      let bod = self.compile(&occs2, specialized, depth2, nam)?;
+      arms.push(lams(fields.clone(), bod));
+    }
+    let r = family.indices.len() as u64;
+    let mut binders = family.indices.clone();
+    binders.push(Name::from("x"));
+    let motive = lams(binders, self.typ.shift((depth + r + 1) as i64, 0));
+    let scrut = occs[col].to_term(depth);
+    let mut args = vec![motive];
+    args.extend(arms);
+    Ok(apps(Term::Cse(self.pos, Box::new(scrut)), args))
+  }
+
+  // The body of the clause of `row`, whose patterns are all variables, with
+  // its variables replaced with the values they match
+  fn body(&self, row: &Row, occs: &[Occurrence], depth: u64) -> Term {
+    let mut binds = row.binds.clone();
+    for (pat, occ) in row.pats.iter().zip(occs) {
+      if let Pattern::Var(_, var) = pat {
+        if *var != "_" {
+          binds.push((var.clone(), occ.clone()));
+        }
+      }
+    }
+    let clause = &self.clauses[row.clause];
+    let vars = clause.pattern.vars();
+    let p = vars.len() as u64;
+    // Bind the fields of the compiled match between the scope of the match
+    // and the variables, then replace the variables, innermost first, with
+    // the values they match
+    let mut term = clause.body.shift(depth as i64, p);
+    for (q, (_, var)) in vars.iter().enumerate().rev() {
+      let occ = binds
+        .iter()
+        .find(|(nam, _)| nam == var)
+        .map(|(_, occ)| occ)
+        .expect("a pattern variable is matched");
+      let val = occ.to_term(depth).shift(q as i64, 0);
+      term = term.subst(0, &val.shift(1, 0)).shift(-1, 0);
+    }
+    term
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      check::infer_term,
+      datatype::tests::datatype,
+      nbe,
+    },
+    package::Declaration,
+    parse::{
+      error::ParseErrorKind,
+      package::store_def,
+      span::Span,
+      term::parse_expression,
+    },
+    term::Defs,
+  };
+  use im::{
+    HashMap,
+    Vector,
+  };
+  use nom::Err;
+
+  // The definitions of `Nat`, stored in the hashspace, and their scope
+  fn nat() -> (Defs, Refs) {
+    let nat = datatype("Nat", &[], "Type@1", &[
+      ("zero", "Nat"),
+      ("succ", "∀ (pred: Nat) -> Nat"),
+    ])
+    .unwrap();
+    let mut defs = HashMap::new();
+    let mut refs = HashMap::new();
+    for def in nat.defs() {
+      if let Declaration::Defn { name, defn, term, .. } = store_def(def) {
+        refs.insert(name, (defn, term));
+        defs.insert(defn, Def::get_link(defn).unwrap());
+      }
+    }
+    (defs, refs)
+  }
+
+  #[test]
+  fn compiles_nested_patterns() {
+    let (defs, refs) = nat();
+    let parse = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      expr(Span::new(i)).unwrap().1
+    };
+    // A `Nat` in `Type@1` is only eliminated into `Type@0`
+    let small = parse(
+      "λ n => match n return #Natural {\n  Nat.succ (Nat.succ m) => 2,\n  \
+       _ => 0,\n}",
+    );
+    let typ = parse("∀ (n: Nat) -> #Natural");
+    let ann = Term::Ann(None, Box::new((typ, small)));
+    assert!(infer_term(&defs, &ann).is_ok());
+    let pred2 = parse(
+      "λ n => match n return Nat {\n  Nat.succ (Nat.succ m) => m,\n  _ => \
+       Nat.zero,\n}",
+    );
+    let three = parse("Nat.succ (Nat.succ (Nat.succ Nat.zero))");
+    let app = Term::App(None, Box::new((pred2, three)));
+    let one = parse("Nat.succ Nat.zero");
+    assert_eq!(nbe::norm(&defs, &app), nbe::norm(&defs, &one));
+  }
+
+  #[test]
+  fn rejects() {
+    let (_, refs) = nat();
+    let error = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      match expr(Span::new(i)) {
+        Err(Err::Failure(e)) => e
+          .errors
+          .iter()
+          .find_map(|e| match e {
+            ParseErrorKind::MalformedMatch(e) => Some(e.clone()),
+            _ => None,
+          })
+          .unwrap_or_else(|| panic!("unexpected errors {:?}", e.errors)),
+        res => panic!("unexpected result {:?}", res),
+      }
+    };
+    let e = error("λ n => match n return Nat { Nat.zero => Nat.zero }");
+    let succ = String::from("Nat.succ");
+    assert!(matches!(e, MatchError::NonExhaustive(_, nam) if nam == succ));
+    let e = error("λ n => match n return Nat { Nat.succ => n, _ => n }");
+    assert!(matches!(e, MatchError::CtorArity(_, _, 1, 0)));
+  }
+}
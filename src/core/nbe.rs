@@ -0,0 +1,447 @@
+use crate::{
+  core::{
+    literal::{
+      LitType,
+      Literal,
+    },
+    primop::{
+      apply_bin_op,
+      apply_una_op,
+      PrimOp,
+    },
+    uses::Uses,
+  },
+  name::Name,
+  term::{
+    Defs,
+    Link,
+    Term,
+  },
+};
+
+use im::Vector;
+use std::rc::Rc;
+
+// A straightforward normalization-by-evaluation normalizer. It makes no
+// attempt at sharing, which makes it slow but easy to trust, so it serves as
+// the reference semantics against which the DAG evaluator is tested.
+
+// Values are indexed in the environment by de Bruijn index
+pub type Env = Vector<Value>;
+
+// A term suspended in the environment it was defined in.
+#[derive(Clone, Debug)]
+pub struct Closure {
+  pub env: Env,
+  pub body: Rc<Term>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Value {
+  Lam(Name, Closure),
+  All(Uses, Name, Rc<Value>, Closure),
+  Slf(Name, Closure),
+  Dat(Rc<Value>),
+  Cse(Rc<Value>),
+  Sig(Name, Rc<Value>, Closure),
+  Par(Rc<Value>, Rc<Value>),
+  Typ(u64),
+  Prp,
+  Lit(Literal),
+  LTy(LitType),
+  // A primitive operation together with the arguments it has been applied to
+  Opr(PrimOp, Vector<Value>),
+  // A reference, unfolded only when forced. Applications of references are
+  // likewise left unevaluated until forced, so that conversion can compare
+  // identical applications, such as those of a recursive type to its
+  // parameters, without unfolding them
+  Ref(Name, Link, Link),
+  // A recursive `letrec` binding, unfolded only when forced
+  Fix(Name, Closure),
+  // A variable bound during quotation, identified by its de Bruijn level
+  Var(Name, u64),
+  // A variable free in the term being normalized, identified by its de
+  // Bruijn index relative to the term's outermost binder
+  Free(Name, u64),
+  // A metavariable of the type checker, unfolded only when solved
+  Meta(u64),
+  // A stuck application, or the application of a reference
+  App(Rc<Value>, Rc<Value>),
+  // Stuck projections
+  Fst(Rc<Value>),
+  Snd(Rc<Value>),
+}
+
+impl Closure {
+  pub fn new(env: Env, body: Term) -> Self {
+    Closure { env, body: Rc::new(body) }
+  }
+
+  // Instantiate the closure's bound variable with `arg`
+  pub fn apply(&self, defs: &Defs, arg: Value) -> Value {
+    let mut env = self.env.clone();
+    env.push_front(arg);
+    eval(defs, env, &self.body)
+  }
+}
+
+pub fn eval(defs: &Defs, env: Env, term: &Term) -> Value {
+  match term {
+    Term::Var(_, nam, idx) => match env.get(*idx as usize) {
+      Some(val) => val.clone(),
+      None => Value::Free(nam.clone(), *idx - env.len() as u64),
+    },
+    Term::Lam(_, nam, bod) => {
+      Value::Lam(nam.clone(), Closure::new(env, (**bod).clone()))
+    }
+    Term::App(_, terms) => {
+      let fun = eval(defs, env.clone(), &terms.0);
+      let arg = eval(defs, env, &terms.1);
+      if is_reference(&fun) {
+        Value::App(Rc::new(fun), Rc::new(arg))
+      }
+      else {
+        apply(defs, fun, arg)
+      }
+    }
+    Term::All(_, uses, nam, terms) => {
+      let dom = eval(defs, env.clone(), &terms.0);
+      Value::All(
+        *uses,
+        nam.clone(),
+        Rc::new(dom),
+        Closure::new(env, terms.1.clone()),
+      )
+    }
+    Term::Slf(_, nam, bod) => {
+      Value::Slf(nam.clone(), Closure::new(env, (**bod).clone()))
+    }
+    Term::Dat(_, bod) => Value::Dat(Rc::new(eval(defs, env, bod))),
+    Term::Cse(_, bod) => match force(defs, eval(defs, env, bod)) {
+      Value::Dat(val) => (*val).clone(),
+      val => Value::Cse(Rc::new(val)),
+    },
+    Term::Sig(_, nam, terms) => {
+      let dom = eval(defs, env.clone(), &terms.0);
+      Value::Sig(nam.clone(), Rc::new(dom), Closure::new(env, terms.1.clone()))
+    }
+    Term::Par(_, terms) => {
+      let fst = eval(defs, env.clone(), &terms.0);
+      Value::Par(Rc::new(fst), Rc::new(eval(defs, env, &terms.1)))
+    }
+    Term::Fst(_, bod) => fst(defs, eval(defs, env, bod)),
+    Term::Snd(_, bod) => snd(defs, eval(defs, env, bod)),
+    Term::Ref(_, nam, def, ast) => Value::Ref(nam.clone(), *def, *ast),
+    Term::Let(_, false, _, _, terms) => {
+      let exp = eval(defs, env.clone(), &terms.1);
+      let mut env = env;
+      env.push_front(exp);
+      eval(defs, env, &terms.2)
+    }
+    Term::Let(_, true, _, nam, terms) => {
+      let clos = Closure::new(env.clone(), terms.1.clone());
+      let fix = Value::Fix(nam.clone(), clos);
+      let mut env = env;
+      env.push_front(fix);
+      eval(defs, env, &terms.2)
+    }
+    Term::Typ(_, lvl) => Value::Typ(*lvl),
+    Term::Prp(_) => Value::Prp,
+    Term::Ann(_, terms) => eval(defs, env, &terms.1),
+    Term::Lit(_, lit) => Value::Lit(lit.clone()),
+    Term::LTy(_, lty) => Value::LTy(*lty),
+    Term::Opr(_, opr) => Value::Opr(*opr, Vector::new()),
+    Term::Met(_, idx) => Value::Meta(*idx),
+    Term::Hol(..) => {
+      panic!("A placeholder must be elaborated before evaluation")
+    }
+  }
+}
+
+// Whether a value is a reference applied to zero or more arguments
+fn is_reference(val: &Value) -> bool {
+  let mut head = val;
+  while let Value::App(fun, _) = head {
+    head = fun;
+  }
+  matches!(head, Value::Ref(..))
+}
+
+// Unfold references and recursive bindings in head position
+pub fn force(defs: &Defs, val: Value) -> Value {
+  match val {
+    Value::Ref(nam, def_link, ast_link) => match defs.get(&def_link) {
+      Some(def) => {
+        // A definition's body is closed over a variable standing for itself
+        let mut env = Vector::new();
+        env.push_front(Value::Ref(nam, def_link, ast_link));
+        force(defs, eval(defs, env, &def.term))
+      }
+      None => Value::Ref(nam, def_link, ast_link),
+    },
+    Value::Fix(nam, clos) => {
+      let fix = Value::Fix(nam, clos.clone());
+      force(defs, clos.apply(defs, fix))
+    }
+    Value::App(fun, arg) => match force(defs, (*fun).clone()) {
+      fun @ Value::Lam(..) | fun @ Value::Opr(..) => {
+        force(defs, apply(defs, fun, (*arg).clone()))
+      }
+      fun => Value::App(Rc::new(fun), arg),
+    },
+    val => val,
+  }
+}
+
+pub fn apply(defs: &Defs, fun: Value, arg: Value) -> Value {
+  match force(defs, fun) {
+    Value::Lam(_, clos) => clos.apply(defs, arg),
+    Value::Opr(opr, args) => {
+      let mut args = args;
+      args.push_back(arg);
+      if args.len() as u64 == opr.arity() {
+        apply_opr(defs, opr, args)
+      }
+      else {
+        Value::Opr(opr, args)
+      }
+    }
+    fun => Value::App(Rc::new(fun), Rc::new(arg)),
+  }
+}
+
+pub fn fst(defs: &Defs, val: Value) -> Value {
+  match force(defs, val) {
+    Value::Par(fst, _) => (*fst).clone(),
+    val => Value::Fst(Rc::new(val)),
+  }
+}
+
+pub fn snd(defs: &Defs, val: Value) -> Value {
+  match force(defs, val) {
+    Value::Par(_, snd) => (*snd).clone(),
+    val => Value::Snd(Rc::new(val)),
+  }
+}
+
+// Run a saturated primitive operation, which is stuck unless all its
+// arguments evaluate to literals
+fn apply_opr(defs: &Defs, opr: PrimOp, args: Vector<Value>) -> Value {
+  let lits: Vec<Option<Literal>> = args
+    .iter()
+    .map(|arg| match force(defs, arg.clone()) {
+      Value::Lit(lit) => Some(lit),
+      _ => None,
+    })
+    .collect();
+  let res = match lits.as_slice() {
+    [Some(x)] => apply_una_op(opr, x.clone()),
+    [Some(x), Some(y)] => apply_bin_op(opr, x.clone(), y.clone()),
+    _ => None,
+  };
+  match res {
+    Some(lit) => Value::Lit(lit),
+    None => Value::Opr(opr, args),
+  }
+}
+
+// Read a value back into a term under `depth` binders. References in the
+// result are unfolded when their definitions are found in `defs`.
+pub fn quote(defs: &Defs, depth: u64, val: &Value) -> Term {
+  match val {
+    Value::Var(nam, lvl) => Term::Var(None, nam.clone(), depth - lvl - 1),
+    Value::Free(nam, idx) => Term::Var(None, nam.clone(), depth + idx),
+    Value::Lam(nam, clos) => {
+      let bod = clos.apply(defs, Value::Var(nam.clone(), depth));
+      Term::Lam(None, nam.clone(), Box::new(quote(defs, depth + 1, &bod)))
+    }
+    Value::All(uses, nam, dom, clos) => {
+      let img = clos.apply(defs, Value::Var(nam.clone(), depth));
+      Term::All(
+        None,
+        *uses,
+        nam.clone(),
+        Box::new((quote(defs, depth, dom), quote(defs, depth + 1, &img))),
+      )
+    }
+    Value::Slf(nam, clos) => {
+      let bod = clos.apply(defs, Value::Var(nam.clone(), depth));
+      Term::Slf(None, nam.clone(), Box::new(quote(defs, depth + 1, &bod)))
+    }
+    Value::Dat(bod) => Term::Dat(None, Box::new(quote(defs, depth, bod))),
+    Value::Cse(bod) => Term::Cse(None, Box::new(quote(defs, depth, bod))),
+    Value::Sig(nam, dom, clos) => {
+      let bod = clos.apply(defs, Value::Var(nam.clone(), depth));
+      Term::Sig(
+        None,
+        nam.clone(),
+        Box::new((quote(defs, depth, dom), quote(defs, depth + 1, &bod))),
+      )
+    }
+    Value::Par(fst, snd) => Term::Par(
+      None,
+      Box::new((quote(defs, depth, fst), quote(defs, depth, snd))),
+    ),
+    Value::Fst(bod) => Term::Fst(None, Box::new(quote(defs, depth, bod))),
+    Value::Snd(bod) => Term::Snd(None, Box::new(quote(defs, depth, bod))),
+    Value::Typ(lvl) => Term::Typ(None, *lvl),
+    Value::Prp => Term::Prp(None),
+    Value::Lit(lit) => Term::Lit(None, lit.clone()),
+    Value::LTy(lty) => Term::LTy(None, *lty),
+    Value::Opr(opr, args) => {
+      args.iter().fold(Term::Opr(None, *opr), |acc, arg| {
+        Term::App(None, Box::new((acc, quote(defs, depth, arg))))
+      })
+    }
+    Value::Ref(nam, def_link, ast_link) => {
+      if defs.contains_key(def_link) {
+        quote(defs, depth, &force(defs, val.clone()))
+      }
+      else {
+        Term::Ref(None, nam.clone(), *def_link, *ast_link)
+      }
+    }
+    Value::Fix(..) => quote(defs, depth, &force(defs, val.clone())),
+    Value::Meta(idx) => Term::Met(None, *idx),
+    Value::App(..) => match force(defs, val.clone()) {
+      Value::App(fun, arg) => Term::App(
+        None,
+        Box::new((quote(defs, depth, &fun), quote(defs, depth, &arg))),
+      ),
+      val => quote(defs, depth, &val),
+    },
+  }
+}
+
+// Reduce a term to its normal form. Free variables and references without a
+// definition are treated as inert heads.
+pub fn norm(defs: &Defs, term: &Term) -> Term {
+  quote(defs, 0, &eval(defs, Vector::new(), term))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      dag::DAG,
+      eval,
+    },
+    parse::term::parse,
+  };
+  use im::HashMap;
+  use quickcheck::{
+    Arbitrary,
+    Gen,
+  };
+  use rand::Rng;
+
+  // A term in which every bound variable occurs at most once. Such terms are
+  // strongly normalizing, so both evaluators are guaranteed to terminate.
+  #[derive(Clone, Debug)]
+  pub struct AffineTerm(pub Term);
+
+  fn arbitrary_affine(
+    g: &mut Gen,
+    depth: u64,
+    free: &mut Vec<u64>,
+    size: u32,
+  ) -> Term {
+    let mut rng = rand::thread_rng();
+    let choice: u32 = rng.gen_range(0..3);
+    if (size == 0 || choice == 0) && !free.is_empty() {
+      let lvl = free.remove(rng.gen_range(0..free.len()));
+      Term::Var(None, Name::from(format!("x{}", lvl)), depth - lvl - 1)
+    }
+    else if size == 0 || choice == 1 {
+      free.push(depth);
+      let bod = arbitrary_affine(g, depth + 1, free, size.saturating_sub(1));
+      free.retain(|lvl| *lvl != depth);
+      Term::Lam(None, Name::from(format!("x{}", depth)), Box::new(bod))
+    }
+    else {
+      let fun = arbitrary_affine(g, depth, free, size / 2);
+      let arg = arbitrary_affine(g, depth, free, size / 2);
+      Term::App(None, Box::new((fun, arg)))
+    }
+  }
+
+  impl Arbitrary for AffineTerm {
+    fn arbitrary(g: &mut Gen) -> Self {
+      let mut rng = rand::thread_rng();
+      let size = rng.gen_range(0..16);
+      AffineTerm(arbitrary_affine(g, 0, &mut Vec::new(), size))
+    }
+  }
+
+  fn dag_norm(term: Term) -> Term {
+    eval::norm(&HashMap::new(), DAG::from_term(term).unwrap()).to_term()
+  }
+
+  #[quickcheck]
+  fn nbe_agrees_with_dag(x: AffineTerm) -> bool {
+    let expected = norm(&HashMap::new(), &x.0);
+    let actual = dag_norm(x.0.clone());
+    if expected != actual {
+      println!("term: {}", x.0);
+      println!("nbe:  {}", expected);
+      println!("dag:  {}", actual);
+    }
+    expected == actual
+  }
+
+  #[test]
+  fn reducer() {
+    fn norm_assert(input: &str, result: &str) {
+      let (_, term) = parse(input).unwrap();
+      assert_eq!(format!("{}", norm(&HashMap::new(), &term)), result)
+    }
+    norm_assert("λ x => x", "λ x => x");
+    norm_assert("λ y => (λ x => x) y", "λ y => y");
+    norm_assert("λ y => (λ z => z z) ((λ x => x) y)", "λ y => y y");
+    let three = "λ s z => s (s (s z))";
+    let four = "λ s z => s (s (s (s z)))";
+    let seven = "λ s z => s (s (s (s (s (s (s z))))))";
+    let add = "λ m n s z => m s (n s z)";
+    norm_assert(&format!("(({}) ({}) {})", add, four, three), seven);
+    norm_assert("#add 1 2", "3");
+    norm_assert("let x: #Natural = 2; #mul x x", "4");
+    norm_assert("λ x => #add x 1", "λ x => #add x 1");
+    norm_assert("snd (1, (λ x => x) 2)", "2");
+    norm_assert("λ p => (fst p, snd (fst (p, 3)))", "λ p => (fst p, snd p)");
+  }
+
+  #[test]
+  fn open_terms() {
+    let x = Term::Var(None, Name::from("x"), 0);
+    let y = Term::Var(None, Name::from("y"), 1);
+    let (_, id) = parse("λ z => z").unwrap();
+    let app = |f: Term, a: Term| Term::App(None, Box::new((f, a)));
+    let lam = |b: Term| Term::Lam(None, Name::from("w"), Box::new(b));
+    // (λ z => z) x ~> x
+    let term = app(id.clone(), x.clone());
+    assert_eq!(norm(&HashMap::new(), &term), x);
+    assert_eq!(dag_norm(term), x);
+    // λ w => (λ z => z) (x y) ~> λ w => x' y', shifted under the binder
+    let term = lam(app(id, app(x.shift(1, 0), y.shift(1, 0))));
+    let expected = lam(app(x.shift(1, 0), y.shift(1, 0)));
+    assert_eq!(norm(&HashMap::new(), &term), expected);
+    assert_eq!(dag_norm(term), expected);
+    // #add x 1 is stuck on its free argument
+    let (_, add) = parse("#add").unwrap();
+    let one = Term::Lit(None, Literal::Natural(1u64.into()));
+    let term = app(app(add, x), one);
+    assert_eq!(norm(&HashMap::new(), &term), term);
+    assert_eq!(dag_norm(term.clone()), term);
+  }
+
+  #[test]
+  fn church_arithmetic_agrees_with_dag() {
+    let three = "λ s z => s (s (s z))";
+    let id = "λ x => x";
+    let input =
+      format!("(({n}) (({m}) ({id})) {id})", n = three, m = three, id = id);
+    let (_, term) = parse(&input).unwrap();
+    assert_eq!(norm(&HashMap::new(), &term), dag_norm(term));
+  }
+}
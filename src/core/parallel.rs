@@ -0,0 +1,377 @@
+use crate::{
+  core::check::CheckError,
+  term::{
+    Def,
+    Defs,
+    Link,
+    Term,
+  },
+};
+
+use im::{
+  HashMap,
+  HashSet,
+};
+
+use std::{
+  collections::VecDeque,
+  sync::{
+    Arc,
+    Condvar,
+    Mutex,
+  },
+  thread,
+};
+
+#[cfg(feature = "native")]
+use std::time::Instant;
+
+// Parallel checking of a set of definitions. A definition depends on those
+// its type and term refer to, and definitions which depend on each other
+// form a strongly connected component of the dependency graph. Each
+// component is checked as soon as the components it depends on have been,
+// on a pool of worker threads, so that independent components are checked
+// concurrently. As definitions refer to each other by the links of their
+// contents, the graph of a well-formed set of definitions is acyclic, and
+// each component is a single definition.
+//
+// A definition which fails to check does not stop the others: its error is
+// recorded, and the definitions depending on it are still checked, assuming
+// the type it is declared with, so that all of the errors of a package are
+// found in one pass.
+
+// The outcome of checking a definition
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+  Checked,
+  // Not checked, as it was already known to check
+  Certified,
+  Failed(CheckError),
+}
+
+impl Outcome {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Checked => "checked",
+      Self::Certified => "certified",
+      Self::Failed(_) => "failed",
+    }
+  }
+}
+
+// A way of checking a definition, telling whether it was actually checked
+pub type Checker = fn(&Defs, &Def) -> Result<bool, CheckError>;
+
+// Add the links of the definitions `term` refers to
+fn references(term: &Term, links: &mut Vec<Link>) {
+  match term {
+    Term::Ref(_, _, def_link, _) => links.push(*def_link),
+    Term::Lam(_, _, bod)
+    | Term::Slf(_, _, bod)
+    | Term::Dat(_, bod)
+    | Term::Cse(_, bod)
+    | Term::Fst(_, bod)
+    | Term::Snd(_, bod) => references(bod, links),
+    Term::App(_, terms)
+    | Term::Par(_, terms)
+    | Term::Ann(_, terms)
+    | Term::All(_, _, _, terms)
+    | Term::Sig(_, _, terms) => {
+      references(&terms.0, links);
+      references(&terms.1, links);
+    }
+    Term::Let(_, _, _, _, terms) => {
+      references(&terms.0, links);
+      references(&terms.1, links);
+      references(&terms.2, links);
+    }
+    _ => (),
+  }
+}
+
+// The dependencies of each of `links` among them
+fn dependencies(defs: &Defs, links: &[Link]) -> HashMap<Link, Vec<Link>> {
+  let set: HashSet<Link> = links.iter().cloned().collect();
+  let mut edges = HashMap::new();
+  for link in links {
+    let def = &defs[link];
+    let mut refs = Vec::new();
+    references(&def.typ_, &mut refs);
+    references(&def.term, &mut refs);
+    let deps: HashSet<Link> =
+      refs.into_iter().filter(|dep| set.contains(dep)).collect();
+    edges.insert(*link, deps.into_iter().collect());
+  }
+  edges
+}
+
+// The strongly connected components of a dependency graph, by Tarjan's
+// algorithm, each coming after those it depends on
+fn components(
+  links: &[Link],
+  edges: &HashMap<Link, Vec<Link>>,
+) -> Vec<Vec<Link>> {
+  struct Tarjan<'a> {
+    edges: &'a HashMap<Link, Vec<Link>>,
+    index: HashMap<Link, usize>,
+    low: HashMap<Link, usize>,
+    stack: Vec<Link>,
+    on_stack: HashSet<Link>,
+    comps: Vec<Vec<Link>>,
+  }
+
+  impl<'a> Tarjan<'a> {
+    fn visit(&mut self, link: Link) {
+      let idx = self.index.len();
+      self.index.insert(link, idx);
+      self.low.insert(link, idx);
+      self.stack.push(link);
+      self.on_stack.insert(link);
+      let edges = self.edges;
+      for dep in &edges[&link] {
+        if !self.index.contains_key(dep) {
+          self.visit(*dep);
+          let low = self.low[&link].min(self.low[dep]);
+          self.low.insert(link, low);
+        }
+        else if self.on_stack.contains(dep) {
+          let low = self.low[&link].min(self.index[dep]);
+          self.low.insert(link, low);
+        }
+      }
+      if self.low[&link] == self.index[&link] {
+        let mut comp = Vec::new();
+        while let Some(top) = self.stack.pop() {
+          self.on_stack.remove(&top);
+          comp.push(top);
+          if top == link {
+            break;
+          }
+        }
+        self.comps.push(comp);
+      }
+    }
+  }
+
+  let mut tarjan = Tarjan {
+    edges,
+    index: HashMap::new(),
+    low: HashMap::new(),
+    stack: Vec::new(),
+    on_stack: HashSet::new(),
+    comps: Vec::new(),
+  };
+  for link in links {
+    if !tarjan.index.contains_key(link) {
+      tarjan.visit(*link);
+    }
+  }
+  tarjan.comps
+}
+
+// The progress of the workers, shared between them
+struct State {
+  // The components whose dependencies are all done
+  ready: VecDeque<usize>,
+  // The number of dependencies of each component not yet done
+  pending: Vec<usize>,
+  remaining: usize,
+  outcomes: HashMap<Link, Outcome>,
+}
+
+// Check the definitions of `links`, which must all be in `defs`, on `jobs`
+// threads
+pub fn check_defs(
+  defs: &Defs,
+  links: &[Link],
+  jobs: usize,
+  check: Checker,
+) -> HashMap<Link, Outcome> {
+  let count = links.len();
+  let span = info_span!(target: "check", "check_defs", count, jobs);
+  let _entered = span.enter();
+  #[cfg(feature = "native")]
+  let start = Instant::now();
+  let edges = dependencies(defs, links);
+  let comps = components(links, &edges);
+  let mut comp_of = HashMap::new();
+  for (idx, comp) in comps.iter().enumerate() {
+    for link in comp {
+      comp_of.insert(*link, idx);
+    }
+  }
+  let mut pending = vec![0; comps.len()];
+  let mut dependents = vec![Vec::new(); comps.len()];
+  for (idx, comp) in comps.iter().enumerate() {
+    let mut ds: Vec<usize> = comp
+      .iter()
+      .flat_map(|link| edges[link].iter().map(|dep| comp_of[dep]))
+      .filter(|dep| *dep != idx)
+      .collect();
+    ds.sort_unstable();
+    ds.dedup();
+    for dep in &ds {
+      dependents[*dep].push(idx);
+    }
+    pending[idx] = ds.len();
+  }
+  let comps_len = comps.len();
+  let pool = Arc::new(Pool {
+    defs: defs.clone(),
+    comps,
+    dependents,
+    check,
+    span: span.clone(),
+    state: Mutex::new(State {
+      ready: (0..comps_len).filter(|idx| pending[*idx] == 0).collect(),
+      pending,
+      remaining: comps_len,
+      outcomes: HashMap::new(),
+    }),
+    done: Condvar::new(),
+  });
+  let workers: Vec<thread::JoinHandle<()>> = (0..jobs.max(1))
+    .map(|_| {
+      let pool = pool.clone();
+      thread::spawn(move || pool.work())
+    })
+    .collect();
+  for worker in workers {
+    worker.join().unwrap();
+  }
+  #[cfg(feature = "native")]
+  info!(target: "check", count, time = ?start.elapsed(), "Checked definitions");
+  let mut state = pool.state.lock().unwrap();
+  std::mem::take(&mut state.outcomes)
+}
+
+// The components to check, and the progress of the workers checking them,
+// shared between them
+struct Pool {
+  defs: Defs,
+  comps: Vec<Vec<Link>>,
+  // The components depending on each
+  dependents: Vec<Vec<usize>>,
+  check: Checker,
+  span: tracing::Span,
+  state: Mutex<State>,
+  done: Condvar,
+}
+
+impl Pool {
+  // Check the components as they are ready, until all are done
+  fn work(&self) {
+    // Each worker's checks are in the span of the whole
+    let _span = self.span.enter();
+    loop {
+      let comp = {
+        let mut state = self.state.lock().unwrap();
+        loop {
+          if let Some(comp) = state.ready.pop_front() {
+            break comp;
+          }
+          if state.remaining == 0 {
+            return;
+          }
+          state = self.done.wait(state).unwrap();
+        }
+      };
+      let mut outcomes = Vec::new();
+      for link in &self.comps[comp] {
+        let def = &self.defs[link];
+        #[cfg(feature = "native")]
+        let start = Instant::now();
+        let outcome = match (self.check)(&self.defs, def) {
+          Ok(true) => Outcome::Checked,
+          Ok(false) => Outcome::Certified,
+          Err(e) => Outcome::Failed(e),
+        };
+        #[cfg(feature = "native")]
+        debug!(
+          target: "check",
+          name = %def.name,
+          %link,
+          time = ?start.elapsed(),
+          outcome = outcome.name(),
+          "Checked"
+        );
+        outcomes.push((*link, outcome));
+      }
+      let mut state = self.state.lock().unwrap();
+      state.outcomes.extend(outcomes);
+      for dependent in &self.dependents[comp] {
+        state.pending[*dependent] -= 1;
+        if state.pending[*dependent] == 0 {
+          state.ready.push_back(*dependent);
+        }
+      }
+      state.remaining -= 1;
+      self.done.notify_all();
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::check::check_def,
+    name::Name,
+    parse::{
+      span::Span,
+      term::parse_expression,
+    },
+    term::Refs,
+  };
+  use im::Vector;
+
+  // Add a definition, which may refer to those before it, to `defs`
+  fn define(
+    defs: &mut Defs,
+    refs: &mut Refs,
+    nam: &str,
+    typ: &str,
+    term: &str,
+  ) -> Link {
+    let parse = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      expr(Span::new(i)).unwrap().1
+    };
+    let def =
+      Def::new(None, Name::from(nam), String::new(), parse(typ), parse(term));
+    let (d, _, a) = def.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    refs.insert(Name::from(nam), (def_link, ast_link));
+    defs.insert(def_link, def);
+    def_link
+  }
+
+  fn check(defs: &Defs, def: &Def) -> Result<bool, CheckError> {
+    check_def(defs, def).map(|()| true)
+  }
+
+  #[test]
+  fn checks_in_parallel() {
+    let (mut defs, mut refs) = (HashMap::new(), HashMap::new());
+    let one = define(&mut defs, &mut refs, "one", "#Natural", "1");
+    let two = define(&mut defs, &mut refs, "two", "#Natural", "#add one one");
+    let bad = define(&mut defs, &mut refs, "bad", "#Natural", "\"a\"");
+    let worse = define(&mut defs, &mut refs, "worse", "#Natural", "#add bad 1");
+    let worst = define(&mut defs, &mut refs, "worst", "#Text", "worse");
+    let links = vec![worst, worse, two, bad, one];
+    let comps = components(&links, &dependencies(&defs, &links));
+    let pos = |link| comps.iter().position(|comp| comp.contains(&link));
+    assert_eq!(comps.len(), 5);
+    assert!(pos(one) < pos(two));
+    assert!(pos(bad) < pos(worse));
+    for jobs in &[1, 4] {
+      let outcomes = check_defs(&defs, &links, *jobs, check);
+      assert_eq!(outcomes.get(&one), Some(&Outcome::Checked));
+      assert_eq!(outcomes.get(&two), Some(&Outcome::Checked));
+      assert!(matches!(outcomes.get(&bad), Some(Outcome::Failed(_))));
+      // A failed definition is assumed to have its declared type
+      assert_eq!(outcomes.get(&worse), Some(&Outcome::Checked));
+      assert!(matches!(outcomes.get(&worst), Some(Outcome::Failed(_))));
+    }
+  }
+}
@@ -6,7 +6,10 @@ use num_bigint::{
   BigUint,
 };
 
-use crate::core::literal::Literal;
+use crate::core::literal::{
+  LitType,
+  Literal,
+};
 
 use crate::decode_error::{
   DecodeError,
@@ -20,6 +23,7 @@ use hashexpr::{
 use std::fmt;
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PrimOp {
   /// equality
   Eql,
@@ -145,6 +149,38 @@ impl fmt::Display for PrimOp {
   }
 }
 
+// The type of the result of a saturated primitive operation, given the types
+// of its arguments
+pub fn type_of_op(opr: PrimOp, args: &[LitType]) -> Option<LitType> {
+  use LitType::*;
+  use PrimOp::*;
+  match (opr, args) {
+    (Not, [BitString]) => Some(BitString),
+    (Suc, [x @ Natural]) | (Suc, [x @ Integer]) => Some(*x),
+    (Pre, [x @ Natural]) | (Pre, [x @ Integer]) => Some(*x),
+    (Len, [x]) if *x != Float => Some(Natural),
+    (Eql, [x, y]) | (Lth, [x, y]) | (Lte, [x, y]) if x == y => Some(BitString),
+    (Gth, [x, y]) | (Gte, [x, y]) if x == y => Some(BitString),
+    (Bor, [BitString, BitString]) => Some(BitString),
+    (And, [BitString, BitString]) => Some(BitString),
+    (Xor, [BitString, BitString]) => Some(BitString),
+    (Add, [x, y]) | (Sub, [x, y]) | (Mul, [x, y]) | (Div, [x, y])
+      if x == y && (*x == Natural || *x == Integer || *x == Float) =>
+    {
+      Some(*x)
+    }
+    (Mod, [x, y]) if x == y && (*x == Natural || *x == Integer) => Some(*x),
+    (Shl, [x, Natural]) | (Shr, [x, Natural])
+      if *x != Text && *x != Char && *x != Float =>
+    {
+      Some(*x)
+    }
+    (Cat, [BitString, BitString]) => Some(BitString),
+    (Cat, [Text, Text]) => Some(Text),
+    _ => None,
+  }
+}
+
 pub fn apply_una_op(opr: PrimOp, x: Literal) -> Option<Literal> {
   use Literal::*;
   use PrimOp::*;
@@ -177,6 +213,10 @@ pub fn apply_bin_op(opr: PrimOp, x: Literal, y: Literal) -> Option<Literal> {
   let tt = BitString(vec![1]);
   let ff = BitString(vec![0]);
   let ite = |c| if c { tt } else { ff };
+  // Floats are compared and computed on as numbers, and arithmetic which
+  // overflows to an infinity or has no result is stuck
+  let num = f64::from_bits;
+  let flt = |z: f64| z.is_finite().then(|| Float(z.to_bits()));
   match (opr, x, y) {
     // Eql
     (Eql, Natural(x), Natural(y)) => Some(ite(x == y)),
@@ -184,30 +224,35 @@ pub fn apply_bin_op(opr: PrimOp, x: Literal, y: Literal) -> Option<Literal> {
     (Eql, BitString(x), BitString(y)) => Some(ite(x == y)),
     (Eql, Text(x), Text(y)) => Some(ite(x == y)),
     (Eql, Char(x), Char(y)) => Some(ite(x == y)),
+    (Eql, Float(x), Float(y)) => Some(ite(num(x) == num(y))),
     // Lth
     (Lth, Natural(x), Natural(y)) => Some(ite(x < y)),
     (Lth, Integer(x), Integer(y)) => Some(ite(x < y)),
     (Lth, BitString(x), BitString(y)) => Some(ite(x < y)),
     (Lth, Text(x), Text(y)) => Some(ite(x < y)),
     (Lth, Char(x), Char(y)) => Some(ite(x < y)),
+    (Lth, Float(x), Float(y)) => Some(ite(num(x) < num(y))),
     // Lte
     (Lte, Natural(x), Natural(y)) => Some(ite(x <= y)),
     (Lte, Integer(x), Integer(y)) => Some(ite(x <= y)),
     (Lte, BitString(x), BitString(y)) => Some(ite(x <= y)),
     (Lte, Text(x), Text(y)) => Some(ite(x <= y)),
     (Lte, Char(x), Char(y)) => Some(ite(x <= y)),
+    (Lte, Float(x), Float(y)) => Some(ite(num(x) <= num(y))),
     // Gth
     (Gth, Natural(x), Natural(y)) => Some(ite(x > y)),
     (Gth, Integer(x), Integer(y)) => Some(ite(x > y)),
     (Gth, BitString(x), BitString(y)) => Some(ite(x > y)),
     (Gth, Text(x), Text(y)) => Some(ite(x > y)),
     (Gth, Char(x), Char(y)) => Some(ite(x > y)),
+    (Gth, Float(x), Float(y)) => Some(ite(num(x) > num(y))),
     // Gte
     (Gte, Natural(x), Natural(y)) => Some(ite(x >= y)),
     (Gte, Integer(x), Integer(y)) => Some(ite(x >= y)),
     (Gte, BitString(x), BitString(y)) => Some(ite(x >= y)),
     (Gte, Text(x), Text(y)) => Some(ite(x >= y)),
     (Gte, Char(x), Char(y)) => Some(ite(x >= y)),
+    (Gte, Float(x), Float(y)) => Some(ite(num(x) >= num(y))),
     // Bor
     (Bor, BitString(x), BitString(y)) => {
       let z = x.iter().zip(y.iter()).map(|(a, b)| a | b).collect();
@@ -226,17 +271,21 @@ pub fn apply_bin_op(opr: PrimOp, x: Literal, y: Literal) -> Option<Literal> {
     // Add
     (Add, Natural(x), Natural(y)) => Some(Natural(x + y)),
     (Add, Integer(x), Integer(y)) => Some(Integer(x + y)),
+    (Add, Float(x), Float(y)) => flt(num(x) + num(y)),
     // Sub
     (Sub, Natural(x), Natural(y)) if x >= y => Some(Natural(x - y)),
     (Sub, Integer(x), Integer(y)) => Some(Integer(x - y)),
+    (Sub, Float(x), Float(y)) => flt(num(x) - num(y)),
     // Mul
     (Mul, Natural(x), Natural(y)) => Some(Natural(x * y)),
     (Mul, Integer(x), Integer(y)) => Some(Integer(x * y)),
+    (Mul, Float(x), Float(y)) => flt(num(x) * num(y)),
     // Div
     (Div, Natural(x), Natural(y)) if y != (0 as u64).into() => {
       Some(Natural(x * y))
     }
     (Div, Integer(x), Integer(y)) if y != 0.into() => Some(Integer(x / y)),
+    (Div, Float(x), Float(y)) => flt(num(x) / num(y)),
     // Mod
     (Mod, Natural(x), Natural(y)) if y != (0 as u64).into() => {
       Some(Natural(x * y))
@@ -323,6 +372,31 @@ pub mod tests {
       }
     }
   }
+  #[test]
+  fn op_types() {
+    use LitType::*;
+    assert_eq!(type_of_op(PrimOp::Add, &[Natural, Natural]), Some(Natural));
+    assert_eq!(type_of_op(PrimOp::Add, &[Natural, Integer]), None);
+    assert_eq!(type_of_op(PrimOp::Eql, &[Text, Text]), Some(BitString));
+    assert_eq!(type_of_op(PrimOp::Shl, &[Integer, Natural]), Some(Integer));
+    assert_eq!(type_of_op(PrimOp::Len, &[Char]), Some(Natural));
+    assert_eq!(type_of_op(PrimOp::Not, &[Natural]), None);
+    assert_eq!(type_of_op(PrimOp::Div, &[Float, Float]), Some(Float));
+    assert_eq!(type_of_op(PrimOp::Shl, &[Float, Natural]), None);
+  }
+
+  #[test]
+  fn float_ops() {
+    let flt = |x: f64| Literal::Float(x.to_bits());
+    let add = apply_bin_op(PrimOp::Add, flt(1.5), flt(2.25));
+    assert_eq!(add, Some(flt(3.75)));
+    let lth = apply_bin_op(PrimOp::Lth, flt(-1.0), flt(0.5));
+    assert_eq!(lth, Some(Literal::BitString(vec![1])));
+    let eql = apply_bin_op(PrimOp::Eql, flt(0.0), flt(-0.0));
+    assert_eq!(eql, Some(Literal::BitString(vec![1])));
+    assert_eq!(apply_bin_op(PrimOp::Div, flt(1.0), flt(0.0)), None);
+  }
+
   #[quickcheck]
   fn primop_type_encode_decode(x: PrimOp) -> bool {
     match PrimOp::decode(x.clone().encode()) {
@@ -0,0 +1,175 @@
+use crate::{
+  core::{
+    dag::DAG,
+    eval::{
+      norm_observed,
+      EvalError,
+      Observer,
+      Reduction,
+    },
+  },
+  term::{
+    Defs,
+    Link,
+  },
+};
+
+use im::HashMap;
+
+use std::{
+  fmt,
+  time::{
+    Duration,
+    Instant,
+  },
+};
+
+// Per-definition evaluation profiling. The evaluator is lazy, so a
+// definition has no well-defined extent once unfolded; instead, every
+// reduction and the time elapsed until it are charged to the definition
+// unfolded most recently. Reductions performed before any unfolding are
+// charged to the top-level term.
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+  pub unfolds: u64,
+  pub reductions: u64,
+  pub time: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct Profiler {
+  pub entries: HashMap<Option<(String, Link)>, Entry>,
+  current: Option<(String, Link)>,
+  last: Instant,
+}
+
+impl Profiler {
+  pub fn new() -> Self {
+    Profiler { entries: HashMap::new(), current: None, last: Instant::now() }
+  }
+
+  // The entry of the definition currently being charged
+  fn entry(&mut self) -> &mut Entry {
+    self.entries.entry(self.current.clone()).or_insert_with(Entry::default)
+  }
+
+  // Charge the time elapsed since the last event to the current definition
+  fn tick(&mut self) {
+    let now = Instant::now();
+    let elapsed = now - self.last;
+    self.entry().time += elapsed;
+    self.last = now;
+  }
+
+  // Close the profile, charging any remaining time
+  pub fn finish(mut self) -> Report {
+    self.tick();
+    let mut rows: Vec<(Option<(String, Link)>, Entry)> =
+      self.entries.into_iter().collect();
+    let name = |def: &Option<(String, Link)>| def.clone().map(|(nam, _)| nam);
+    rows.sort_by(|a, b| {
+      b.1.time.cmp(&a.1.time).then_with(|| name(&a.0).cmp(&name(&b.0)))
+    });
+    Report { rows }
+  }
+}
+
+impl Default for Profiler {
+  fn default() -> Self { Self::new() }
+}
+
+impl Observer for Profiler {
+  fn observe(&mut self, red: Reduction) -> Result<(), EvalError> {
+    self.tick();
+    if let Reduction::Unfold(nam, link) = red {
+      self.current = Some((nam.to_string(), link));
+      self.entry().unfolds += 1;
+    }
+    self.entry().reductions += 1;
+    Ok(())
+  }
+}
+
+// A profile, with its entries ordered by decreasing time
+#[derive(Clone, Debug)]
+pub struct Report {
+  pub rows: Vec<(Option<(String, Link)>, Entry)>,
+}
+
+impl Report {
+  pub fn get(&self, name: &str) -> Option<&Entry> {
+    self.rows.iter().find_map(|(def, entry)| match def {
+      Some((nam, _)) if nam == name => Some(entry),
+      _ => None,
+    })
+  }
+
+  pub fn total_reductions(&self) -> u64 {
+    self.rows.iter().map(|(_, entry)| entry.reductions).sum()
+  }
+}
+
+impl fmt::Display for Report {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(
+      f,
+      "{:>12} {:>10} {:>8}  definition",
+      "time", "reductions", "unfolds"
+    )?;
+    for (def, entry) in &self.rows {
+      let name = match def {
+        Some((nam, _)) => nam.as_str(),
+        None => "<top>",
+      };
+      writeln!(
+        f,
+        "{:>12?} {:>10} {:>8}  {}",
+        entry.time, entry.reductions, entry.unfolds, name
+      )?;
+    }
+    Ok(())
+  }
+}
+
+// Normalize a term, profiling the definitions it unfolds
+pub fn profile(defs: &Defs, node: DAG) -> Result<(DAG, Report), EvalError> {
+  let mut profiler = Profiler::new();
+  let node = norm_observed(defs, node, &mut profiler)?;
+  Ok((node, profiler.finish()))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::term::parse,
+    term::{
+      Def,
+      Term,
+    },
+  };
+
+  #[test]
+  fn counts_per_definition() {
+    let (_, id) = parse("λ x => x").unwrap();
+    let (_, typ) = parse("Type").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, id);
+    let (d, _, a) = def.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    let mut defs = HashMap::new();
+    defs.insert(def_link, def);
+    let id_ref = Term::Ref(None, Name::from("id"), def_link, ast_link);
+    // (λ y => y) (id Type)
+    let (_, id_lam) = parse("λ y => y").unwrap();
+    let inner = Term::App(None, Box::new((id_ref, Term::Typ(None, 0))));
+    let term = Term::App(None, Box::new((id_lam, inner)));
+    let (node, report) = profile(&defs, DAG::from_term(term).unwrap()).unwrap();
+    assert_eq!(format!("{}", node), "Type");
+    let id = report.get("id").unwrap();
+    assert_eq!(id.unfolds, 1);
+    assert_eq!(id.reductions, 2);
+    assert_eq!(report.total_reductions(), 3);
+  }
+}
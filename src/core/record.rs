@@ -0,0 +1,170 @@
+use crate::{
+  core::{
+    codatatype::Codatatype,
+    datatype::{
+      alls,
+      apps,
+      lams,
+      names,
+      var,
+      DataError,
+      Telescope,
+    },
+  },
+  formats::occurs,
+  name::Name,
+  term::{
+    Def,
+    Term,
+    Uses,
+  },
+};
+
+use hashexpr::position::Pos;
+
+// Records, codatatypes whose fields do not refer to the record itself. A
+// declaration
+//
+//   record Pair (A: Type) (B: Type): Type {
+//     fst: A,
+//     snd: B,
+//   }
+//
+// becomes the codatatype of its fields, with a projection for each of them,
+// such as `Pair.snd: ∀ (A: Type) (B: Type) (x: Pair A B) -> B`, and a
+// constructor from the values of all of its fields
+//
+//   Pair.new: ∀ (A: Type) (B: Type) (fst: A) (snd: B) -> Pair A B
+//   Pair.new = λ A B fst snd => (fst, snd)
+//
+// As the type of a field may depend on the fields before it, so may the
+// type of each of the constructor's arguments.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+  pub codata: Codatatype,
+}
+
+impl Record {
+  pub fn new(
+    pos: Option<Pos>,
+    name: Name,
+    params: Telescope,
+    sort: Term,
+    fields: Vec<(Option<Pos>, Name, Term)>,
+  ) -> Result<Self, DataError> {
+    let n = params.len() as u64;
+    for (t, (pos, nam, typ)) in fields.iter().enumerate() {
+      if occurs(typ, t as u64 + n) {
+        return Err(DataError::RecursiveRecord(*pos, nam.to_string()));
+      }
+    }
+    let codata = Codatatype::new(pos, name, params, sort, fields)?;
+    Ok(Record { codata })
+  }
+
+  pub fn ctor_name(&self) -> Name {
+    Name::from(format!("{}.new", self.codata.name))
+  }
+
+  // The definition of the constructor, given a reference to the record
+  pub fn ctor_def(&self, data: &Term) -> Def {
+    let codata = &self.codata;
+    let n = codata.params.len() as u64;
+    let k = codata.fields.len() as u64;
+    let fields: Telescope = codata
+      .fields
+      .iter()
+      .enumerate()
+      .map(|(t, field)| {
+        (Uses::Many, field.name.clone(), field.typ.subst(t as u64 + n, data))
+      })
+      .collect();
+    let params = names(&codata.params)
+      .iter()
+      .enumerate()
+      .map(|(i, nam)| var(nam, k + n - 1 - i as u64))
+      .collect();
+    let res = apps(data.clone(), params);
+    let typ_ = alls(codata.params.clone(), alls(fields.clone(), res));
+    // The fields, paired from the last
+    let mut vals = names(&fields)
+      .into_iter()
+      .enumerate()
+      .map(|(t, nam)| var(&nam, k - 1 - t as u64))
+      .rev();
+    let last = vals.next().expect("a record has fields");
+    let term = vals.fold(last, |acc, val| {
+      Term::Par(None, Box::new((val, acc)))
+    });
+    let mut binders = names(&codata.params);
+    binders.extend(names(&fields));
+    let term = lams(binders, term);
+    Def::new(codata.pos, self.ctor_name(), String::new(), typ_, term)
+  }
+
+  // The record's definition followed by those of its projections and of
+  // its constructor
+  pub fn defs(&self) -> Vec<Def> {
+    let mut defs = self.codata.defs();
+    let (d, _, a) = defs[0].clone().embed();
+    let name = self.codata.name.clone();
+    let data = Term::Ref(None, name, d.encode().link(), a.encode().link());
+    defs.push(self.ctor_def(&data));
+    defs
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::core::{
+    check::check_def,
+    codatatype::tests::codatatype,
+  };
+  use im::HashMap;
+
+  fn record(
+    name: &str,
+    params: &[(&str, &str)],
+    fields: &[(&str, &str)],
+  ) -> Result<Record, DataError> {
+    let codata = codatatype(name, params, "Type", fields)?;
+    let fields = codata
+      .fields
+      .iter()
+      .map(|field| (field.pos, field.name.clone(), field.typ.clone()))
+      .collect();
+    Record::new(None, codata.name, codata.params, Term::Typ(None, 0), fields)
+  }
+
+  #[test]
+  fn constructs() {
+    let pair = record("Pair", &[("A", "Type"), ("B", "Type")], &[
+      ("fst", "A"),
+      ("snd", "B"),
+    ])
+    .unwrap();
+    let defs = pair.defs();
+    let mut scope = HashMap::new();
+    for def in &defs {
+      assert_eq!(check_def(&scope, def), Ok(()));
+      let (d, ..) = def.clone().embed();
+      scope.insert(d.encode().link(), def.clone());
+    }
+    let new = &defs[3];
+    assert_eq!(new.name, "Pair.new");
+    assert_eq!(
+      format!("{}", new.typ_),
+      "∀ (A: Type) (B: Type) (fst: A) (snd: B) -> Pair A B"
+    );
+    assert_eq!(format!("{}", new.term), "λ A B fst snd => (fst, snd)");
+  }
+
+  #[test]
+  fn rejects_recursion() {
+    let res = record("Loop", &[], &[("next", "Loop")]);
+    let name = String::from("next");
+    assert_eq!(res, Err(DataError::RecursiveRecord(None, name)));
+  }
+}
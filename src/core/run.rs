@@ -0,0 +1,202 @@
+use crate::{
+  core::{
+    eval::{
+      eval,
+      EvalError,
+      Observer,
+      Reduction,
+    },
+    smallstep,
+  },
+  term::{
+    Defs,
+    Term,
+  },
+};
+
+use std::{
+  collections::BTreeMap,
+  fmt,
+  str::FromStr,
+};
+
+#[cfg(feature = "native")]
+use std::time::Instant;
+
+// Running a term to its normal form, for the command line. A term can be
+// run by lazy graph reduction, which shares the work done on duplicated
+// arguments, or by stepping the small-step semantics, which contracts one
+// leftmost-outermost redex at a time and so gives the length of the
+// reduction sequence. Either way the reductions are counted by kind, and may
+// be limited to a number of them, the fuel.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+  Lazy,
+  Step,
+}
+
+impl FromStr for Strategy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "lazy" => Ok(Self::Lazy),
+      "step" => Ok(Self::Step),
+      _ => Err(format!("Unknown evaluation strategy {}", s)),
+    }
+  }
+}
+
+impl fmt::Display for Strategy {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Lazy => write!(f, "lazy"),
+      Self::Step => write!(f, "step"),
+    }
+  }
+}
+
+// The number of reductions of each kind, named as the rules of the
+// small-step semantics, performed so far, and how many may be performed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+  pub counts: BTreeMap<&'static str, u64>,
+  pub fuel: Option<u64>,
+}
+
+impl Stats {
+  pub fn new(fuel: Option<u64>) -> Self {
+    Stats { counts: BTreeMap::new(), fuel }
+  }
+
+  pub fn total(&self) -> u64 { self.counts.values().sum() }
+
+  // Count a reduction which is about to be performed, if there is fuel left
+  // for it
+  pub fn count(&mut self, rule: &'static str) -> Result<(), EvalError> {
+    match self.fuel {
+      Some(fuel) if self.total() >= fuel => Err(EvalError::OutOfGas(fuel)),
+      _ => {
+        *self.counts.entry(rule).or_insert(0) += 1;
+        Ok(())
+      }
+    }
+  }
+}
+
+impl Observer for Stats {
+  fn observe(&mut self, red: Reduction) -> Result<(), EvalError> {
+    let rule = match red {
+      Reduction::Beta => "beta",
+      Reduction::Unfold(name, link) => {
+        trace!(target: "eval", %name, %link, "Unfolding");
+        "delta"
+      }
+      Reduction::Proj => "proj",
+      Reduction::PrimOp(..) => "prim",
+      Reduction::Ann => "ann",
+      Reduction::Let => "let",
+      Reduction::Fix => "fix",
+    };
+    self.count(rule)
+  }
+}
+
+impl fmt::Display for Stats {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} reductions", self.total())?;
+    for (rule, count) in &self.counts {
+      write!(f, "\n  {:<6} {}", rule, count)?;
+    }
+    Ok(())
+  }
+}
+
+fn normalize(
+  defs: &Defs,
+  term: &Term,
+  strategy: Strategy,
+  stats: &mut Stats,
+) -> Result<Term, EvalError> {
+  match strategy {
+    Strategy::Lazy => eval(defs, term, stats),
+    Strategy::Step => {
+      let mut term = term.clone();
+      while let Some((next, rule, _)) = smallstep::step(defs, &term) {
+        stats.count(rule.name())?;
+        term = next;
+      }
+      Ok(term)
+    }
+  }
+}
+
+// Reduce a term to its normal form by `strategy`, counting the reductions
+// in `stats`
+pub fn run(
+  defs: &Defs,
+  term: &Term,
+  strategy: Strategy,
+  stats: &mut Stats,
+) -> Result<Term, EvalError> {
+  let _span = info_span!(target: "eval", "run", %strategy).entered();
+  // There is no clock to time the run by in the browser
+  #[cfg(feature = "native")]
+  let start = Instant::now();
+  let res = normalize(defs, term, strategy, stats);
+  #[cfg(feature = "native")]
+  info!(
+    target: "eval",
+    %strategy,
+    time = ?start.elapsed(),
+    reductions = stats.total(),
+    "Ran"
+  );
+  res
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::parse::term::parse;
+  use im::HashMap;
+
+  fn run_str(
+    input: &str,
+    strategy: Strategy,
+    fuel: Option<u64>,
+  ) -> (Result<String, EvalError>, Stats) {
+    let (_, term) = parse(input).unwrap();
+    let mut stats = Stats::new(fuel);
+    let res = run(&HashMap::new(), &term, strategy, &mut stats);
+    (res.map(|term| format!("{}", term)), stats)
+  }
+
+  #[test]
+  fn runs() {
+    let two = "(λ s z => s (s z))";
+    let input = format!("{} {} (λ x => x)", two, two);
+    for &strategy in &[Strategy::Lazy, Strategy::Step] {
+      let (res, stats) = run_str(&input, strategy, None);
+      assert_eq!(res, Ok(String::from("λ z => z")));
+      assert_eq!(stats.total(), stats.counts["beta"]);
+    }
+    let (res, stats) = run_str("#add 1 2", Strategy::Step, None);
+    assert_eq!(res, Ok(String::from("3")));
+    assert_eq!(stats.counts["prim"], 1);
+    assert_eq!(format!("{}", stats), "1 reductions\n  prim   1");
+  }
+
+  #[test]
+  fn runs_out_of_fuel() {
+    let input = "(λ x y => y) Type Type";
+    for &strategy in &[Strategy::Lazy, Strategy::Step] {
+      let (res, stats) = run_str(input, strategy, Some(1));
+      assert_eq!(res, Err(EvalError::OutOfGas(1)));
+      assert_eq!(stats.total(), 1);
+      let (res, _) = run_str(input, strategy, Some(2));
+      assert_eq!(res, Ok(String::from("Type")));
+    }
+  }
+}
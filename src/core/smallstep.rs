@@ -0,0 +1,317 @@
+use crate::{
+  core::{
+    literal::Literal,
+    primop::{
+      apply_bin_op,
+      apply_una_op,
+      PrimOp,
+    },
+  },
+  name::Name,
+  term::{
+    Defs,
+    Link,
+    Term,
+  },
+};
+
+use std::fmt;
+
+// A small-step operational semantics for terms. A step contracts the
+// leftmost-outermost redex, reducing under binders, and is labelled with the
+// rule that justifies it.
+
+// The rules of the small-step semantics, one for each kind of redex
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rule {
+  Beta,
+  Unfold(Name, Link),
+  Let,
+  LetRec,
+  Ann,
+  Case,
+  Proj,
+  PrimOp(PrimOp),
+}
+
+// The position of a subterm, as the list of child indices leading to it
+pub type Path = Vec<usize>;
+
+impl Rule {
+  // The rule's name, as used in traces
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Beta => "beta",
+      Self::Unfold(..) => "delta",
+      Self::Let => "zeta",
+      Self::LetRec => "fix",
+      Self::Ann => "ann",
+      Self::Case => "case",
+      Self::Proj => "proj",
+      Self::PrimOp(_) => "prim",
+    }
+  }
+}
+
+impl fmt::Display for Rule {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Beta => write!(f, "beta reduction"),
+      Self::Unfold(nam, link) => write!(f, "unfold {} ({})", nam, link),
+      Self::Let => write!(f, "let substitution"),
+      Self::LetRec => write!(f, "letrec unrolling"),
+      Self::Ann => write!(f, "annotation erasure"),
+      Self::Case => write!(f, "case of data"),
+      Self::Proj => write!(f, "projection of a pair"),
+      Self::PrimOp(opr) => write!(f, "primitive operation {}", opr),
+    }
+  }
+}
+
+// Split an application spine into its head and arguments
+fn spine(term: &Term) -> (&Term, Vec<&Term>) {
+  let mut head = term;
+  let mut args = Vec::new();
+  while let Term::App(_, terms) = head {
+    args.push(&terms.1);
+    head = &terms.0;
+  }
+  args.reverse();
+  (head, args)
+}
+
+fn lit_args(args: &[&Term]) -> Option<Vec<Literal>> {
+  args
+    .iter()
+    .map(|arg| match arg {
+      Term::Lit(_, lit) => Some(lit.clone()),
+      _ => None,
+    })
+    .collect()
+}
+
+// Contract the redex at the root of `term`, if there is one
+pub fn contract(defs: &Defs, term: &Term) -> Option<(Term, Rule)> {
+  match term {
+    Term::App(_, terms) => match &terms.0 {
+      Term::Lam(_, _, bod) => Some((bod.instantiate(&terms.1), Rule::Beta)),
+      _ => {
+        let (head, args) = spine(term);
+        match head {
+          Term::Opr(_, opr) if args.len() as u64 == opr.arity() => {
+            let res = match lit_args(&args)?.as_slice() {
+              [x] => apply_una_op(*opr, x.clone()),
+              [x, y] => apply_bin_op(*opr, x.clone(), y.clone()),
+              _ => None,
+            }?;
+            Some((Term::Lit(None, res), Rule::PrimOp(*opr)))
+          }
+          _ => None,
+        }
+      }
+    },
+    Term::Ref(_, nam, def_link, _) => {
+      let def = defs.get(def_link)?;
+      // A definition's body is closed over a variable standing for itself
+      Some((def.term.instantiate(term), Rule::Unfold(nam.clone(), *def_link)))
+    }
+    Term::Let(_, false, _, _, terms) => {
+      Some((terms.2.instantiate(&terms.1), Rule::Let))
+    }
+    Term::Let(pos, true, uses, nam, terms) => {
+      let fix = Term::Let(
+        *pos,
+        true,
+        *uses,
+        nam.clone(),
+        Box::new((
+          terms.0.clone(),
+          terms.1.clone(),
+          Term::Var(None, nam.clone(), 0),
+        )),
+      );
+      Some((terms.2.instantiate(&terms.1.instantiate(&fix)), Rule::LetRec))
+    }
+    Term::Ann(_, terms) => Some((terms.1.clone(), Rule::Ann)),
+    Term::Cse(_, bod) => match &**bod {
+      Term::Dat(_, val) => Some(((**val).clone(), Rule::Case)),
+      _ => None,
+    },
+    Term::Fst(_, bod) => match &**bod {
+      Term::Par(_, terms) => Some((terms.0.clone(), Rule::Proj)),
+      _ => None,
+    },
+    Term::Snd(_, bod) => match &**bod {
+      Term::Par(_, terms) => Some((terms.1.clone(), Rule::Proj)),
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+// The immediate subterms of a term, in leftmost-outermost order
+fn children(term: &Term) -> Vec<&Term> {
+  match term {
+    Term::Lam(_, _, bod) => vec![&**bod],
+    Term::Slf(_, _, bod) => vec![&**bod],
+    Term::Dat(_, bod) => vec![&**bod],
+    Term::Cse(_, bod) => vec![&**bod],
+    Term::Fst(_, bod) => vec![&**bod],
+    Term::Snd(_, bod) => vec![&**bod],
+    Term::Par(_, terms) => vec![&terms.0, &terms.1],
+    Term::Sig(_, _, terms) => vec![&terms.0, &terms.1],
+    Term::App(_, terms) => vec![&terms.0, &terms.1],
+    Term::Ann(_, terms) => vec![&terms.0, &terms.1],
+    Term::All(_, _, _, terms) => vec![&terms.0, &terms.1],
+    Term::Let(_, _, _, _, terms) => vec![&terms.0, &terms.1, &terms.2],
+    _ => vec![],
+  }
+}
+
+pub fn subterm<'a>(term: &'a Term, path: &[usize]) -> Option<&'a Term> {
+  match path.split_first() {
+    None => Some(term),
+    Some((idx, rest)) => subterm(children(term).get(*idx)?, rest),
+  }
+}
+
+// Rebuild `term` with the subterm at `path` replaced by `new`
+pub fn replace_at(term: &Term, path: &[usize], new: Term) -> Term {
+  let (idx, rest) = match path.split_first() {
+    None => return new,
+    Some((idx, rest)) => (*idx, rest),
+  };
+  let go = |child: &Term, i: usize| {
+    if i == idx { replace_at(child, rest, new.clone()) } else { child.clone() }
+  };
+  match term {
+    Term::Lam(pos, nam, bod) => {
+      Term::Lam(*pos, nam.clone(), Box::new(go(&**bod, 0)))
+    }
+    Term::Slf(pos, nam, bod) => {
+      Term::Slf(*pos, nam.clone(), Box::new(go(&**bod, 0)))
+    }
+    Term::Dat(pos, bod) => Term::Dat(*pos, Box::new(go(&**bod, 0))),
+    Term::Cse(pos, bod) => Term::Cse(*pos, Box::new(go(&**bod, 0))),
+    Term::Fst(pos, bod) => Term::Fst(*pos, Box::new(go(&**bod, 0))),
+    Term::Snd(pos, bod) => Term::Snd(*pos, Box::new(go(&**bod, 0))),
+    Term::Par(pos, terms) => {
+      Term::Par(*pos, Box::new((go(&terms.0, 0), go(&terms.1, 1))))
+    }
+    Term::Sig(pos, nam, terms) => Term::Sig(
+      *pos,
+      nam.clone(),
+      Box::new((go(&terms.0, 0), go(&terms.1, 1))),
+    ),
+    Term::App(pos, terms) => {
+      Term::App(*pos, Box::new((go(&terms.0, 0), go(&terms.1, 1))))
+    }
+    Term::Ann(pos, terms) => {
+      Term::Ann(*pos, Box::new((go(&terms.0, 0), go(&terms.1, 1))))
+    }
+    Term::All(pos, uses, nam, terms) => Term::All(
+      *pos,
+      *uses,
+      nam.clone(),
+      Box::new((go(&terms.0, 0), go(&terms.1, 1))),
+    ),
+    Term::Let(pos, rec, uses, nam, terms) => Term::Let(
+      *pos,
+      *rec,
+      *uses,
+      nam.clone(),
+      Box::new((go(&terms.0, 0), go(&terms.1, 1), go(&terms.2, 2))),
+    ),
+    _ => term.clone(),
+  }
+}
+
+// Find the leftmost-outermost redex of `term` and contract it
+pub fn step(defs: &Defs, term: &Term) -> Option<(Term, Rule, Path)> {
+  fn go(defs: &Defs, term: &Term, path: &mut Path) -> Option<(Term, Rule)> {
+    if let Some(res) = contract(defs, term) {
+      return Some(res);
+    }
+    for (idx, child) in children(term).into_iter().enumerate() {
+      path.push(idx);
+      if let Some((new, rule)) = go(defs, child, path) {
+        return Some((replace_at(term, &[idx], new), rule));
+      }
+      path.pop();
+    }
+    None
+  }
+  let mut path = Vec::new();
+  let (new, rule) = go(defs, term, &mut path)?;
+  Some((new, rule, path))
+}
+
+// Step `term` until it is in normal form or `limit` steps have been taken,
+// recording each rule applied together with the term it produced
+pub fn trace(defs: &Defs, term: &Term, limit: usize) -> Vec<(Rule, Term)> {
+  let mut steps = Vec::new();
+  let mut term = term.clone();
+  while steps.len() < limit {
+    match step(defs, &term) {
+      Some((next, rule, _)) => {
+        steps.push((rule, next.clone()));
+        term = next;
+      }
+      None => break,
+    }
+  }
+  steps
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::nbe,
+    parse::term::parse,
+  };
+  use im::HashMap;
+
+  fn step_assert(input: &str, rule: Rule, result: &str) {
+    let (_, term) = parse(input).unwrap();
+    let (next, r, _) = step(&HashMap::new(), &term).unwrap();
+    assert_eq!(r, rule);
+    assert_eq!(format!("{}", next), result);
+  }
+
+  #[test]
+  fn rules() {
+    step_assert("(λ x => x) Type", Rule::Beta, "Type");
+    step_assert("let x: Type = Type; x", Rule::Let, "Type");
+    step_assert("(Type :: Type)", Rule::Ann, "Type");
+    step_assert("#add 1 2", Rule::PrimOp(PrimOp::Add), "3");
+    step_assert("snd (Type, 1)", Rule::Proj, "1");
+    step_assert("λ y => (λ x => x) y", Rule::Beta, "λ y => y");
+  }
+
+  #[test]
+  fn normal_forms() {
+    for input in &["λ x => x", "Type", "λ x => #add x 1", "1"] {
+      let (_, term) = parse(input).unwrap();
+      assert_eq!(step(&HashMap::new(), &term), None);
+    }
+  }
+
+  #[test]
+  fn leftmost_outermost() {
+    let (_, term) = parse("(λ x y => y) ((λ z => z) Type)").unwrap();
+    let (_, rule, path) = step(&HashMap::new(), &term).unwrap();
+    assert_eq!(rule, Rule::Beta);
+    assert_eq!(path, Vec::<usize>::new());
+  }
+
+  #[quickcheck]
+  fn trace_reaches_nbe_normal_form(x: nbe::tests::AffineTerm) -> bool {
+    let defs = HashMap::new();
+    let last = match trace(&defs, &x.0, 1000).pop() {
+      Some((_, term)) => term,
+      None => x.0.clone(),
+    };
+    last == nbe::norm(&defs, &x.0)
+  }
+}
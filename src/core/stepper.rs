@@ -0,0 +1,218 @@
+use crate::{
+  core::{
+    dag::DAG,
+    eval::EvalError,
+    smallstep::{
+      replace_at,
+      step,
+      subterm,
+      Rule,
+    },
+  },
+  term::{
+    Defs,
+    Link,
+    Term,
+  },
+};
+
+use im::HashSet;
+
+// The default number of steps a single `step_over` or `resume` may take
+pub const FUEL: u64 = 100_000;
+
+// Why the stepper stopped running
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stop {
+  Normal,
+  Breakpoint(String, Link),
+  // The steps which may be taken at once ran out, as on a term which does
+  // not terminate
+  OutOfFuel(u64),
+}
+
+// An interactive evaluator which reduces a term one step at a time, pausing
+// whenever a reference with a breakpoint set on it is about to be unfolded.
+// Running on without pausing takes at most `fuel` steps.
+pub struct Stepper {
+  pub defs: Defs,
+  pub term: Term,
+  pub breakpoints: HashSet<String>,
+  pub steps: u64,
+  pub fuel: u64,
+  paused: bool,
+}
+
+impl Stepper {
+  pub fn new(defs: Defs, term: Term) -> Self {
+    Stepper {
+      defs,
+      term,
+      breakpoints: HashSet::new(),
+      steps: 0,
+      fuel: FUEL,
+      paused: false,
+    }
+  }
+
+  pub fn add_breakpoint(&mut self, name: String) {
+    self.breakpoints.insert(name);
+  }
+
+  pub fn remove_breakpoint(&mut self, name: &str) -> bool {
+    self.breakpoints.remove(name).is_some()
+  }
+
+  // The rule the next call to `step` would perform
+  pub fn peek(&self) -> Option<Rule> {
+    step(&self.defs, &self.term).map(|(_, rule, _)| rule)
+  }
+
+  // Build the DAG of the current term, for inspecting the paused state
+  pub fn dag(&self) -> Result<DAG, EvalError> {
+    DAG::from_term(self.term.clone())
+  }
+
+  pub fn step(&mut self) -> Option<Rule> {
+    let (term, rule, _) = step(&self.defs, &self.term)?;
+    self.term = term;
+    self.steps += 1;
+    self.paused = false;
+    Some(rule)
+  }
+
+  // Perform the next step. If it unfolds a reference, keep reducing the
+  // unfolded subterm to normal form without stopping at breakpoints. If the
+  // fuel runs out first, the subterm is left as far as it was reduced.
+  pub fn step_over(&mut self) -> Result<Option<Rule>, Stop> {
+    let (term, rule, path) = match step(&self.defs, &self.term) {
+      Some(next) => next,
+      None => return Ok(None),
+    };
+    self.steps += 1;
+    self.paused = false;
+    let sub = match rule {
+      Rule::Unfold(..) => subterm(&term, &path).cloned(),
+      _ => None,
+    };
+    let mut sub = match sub {
+      Some(sub) => sub,
+      None => {
+        self.term = term;
+        return Ok(Some(rule));
+      }
+    };
+    let mut fuel = self.fuel;
+    while let Some((next, ..)) = step(&self.defs, &sub) {
+      if fuel == 0 {
+        self.term = replace_at(&term, &path, sub);
+        return Err(Stop::OutOfFuel(self.fuel));
+      }
+      sub = next;
+      fuel -= 1;
+      self.steps += 1;
+    }
+    self.term = replace_at(&term, &path, sub);
+    Ok(Some(rule))
+  }
+
+  // Run until the term is in normal form, a breakpoint is hit or the fuel
+  // runs out. A resumed stepper does not stop again at the breakpoint it is
+  // paused on.
+  pub fn resume(&mut self) -> Stop {
+    let mut fuel = self.fuel;
+    loop {
+      match self.peek() {
+        None => return Stop::Normal,
+        Some(Rule::Unfold(nam, link))
+          if !self.paused && self.breakpoints.contains(nam.as_str()) =>
+        {
+          self.paused = true;
+          return Stop::Breakpoint(nam.to_string(), link);
+        }
+        Some(_) if fuel == 0 => return Stop::OutOfFuel(self.fuel),
+        Some(_) => {
+          self.step();
+          fuel -= 1;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      nbe,
+      primop::PrimOp,
+    },
+    name::Name,
+    parse::term::parse,
+    term::Def,
+  };
+  use im::HashMap;
+
+  fn stepper(input: &str) -> Stepper {
+    let (_, term) = parse(input).unwrap();
+    Stepper::new(HashMap::new(), term)
+  }
+
+  #[test]
+  fn steps_to_normal_form() {
+    let mut st = stepper("(λ x y => x) (λ z => z)");
+    assert_eq!(st.step(), Some(Rule::Beta));
+    assert_eq!(format!("{}", st.term), "λ y z => z");
+    assert_eq!(st.step(), None);
+    let mut st = stepper("#add 1 (#mul 2 3)");
+    assert_eq!(st.step(), Some(Rule::PrimOp(PrimOp::Mul)));
+    assert_eq!(st.step(), Some(Rule::PrimOp(PrimOp::Add)));
+    assert_eq!(format!("{}", st.term), "7");
+  }
+
+  #[test]
+  fn breakpoints() {
+    let (_, id) = parse("λ x => x").unwrap();
+    let (_, typ) = parse("Type").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, id);
+    let (d, _, a) = def.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    let mut defs = HashMap::new();
+    defs.insert(def_link, def);
+    let id_ref = Term::Ref(None, Name::from("id"), def_link, ast_link);
+    let term = Term::App(None, Box::new((id_ref, Term::Typ(None, 0))));
+    let mut st = Stepper::new(defs.clone(), term.clone());
+    st.add_breakpoint(String::from("id"));
+    assert_eq!(st.resume(), Stop::Breakpoint(String::from("id"), def_link));
+    assert_eq!(st.term, term);
+    assert_eq!(st.resume(), Stop::Normal);
+    assert_eq!(st.term, nbe::norm(&defs, &term));
+    let mut st = Stepper::new(defs, term);
+    let unfold = Rule::Unfold(Name::from("id"), def_link);
+    assert_eq!(st.step_over(), Ok(Some(unfold)));
+    assert_eq!(format!("{}", st.term), "(λ x => x) Type");
+  }
+
+  #[test]
+  fn runs_out_of_fuel() {
+    let omega = "(λ x => x x) (λ x => x x)";
+    let mut st = stepper(omega);
+    st.fuel = 10;
+    assert_eq!(st.resume(), Stop::OutOfFuel(10));
+    assert_eq!(st.steps, 10);
+    // A loop behind a reference is stepped over as far as the fuel goes
+    let (_, typ) = parse("Type").unwrap();
+    let (_, term) = parse(omega).unwrap();
+    let def = Def::new(None, Name::from("loop"), String::new(), typ, term);
+    let (d, _, a) = def.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    let mut defs = HashMap::new();
+    defs.insert(def_link, def);
+    let term = Term::Ref(None, Name::from("loop"), def_link, ast_link);
+    let mut st = Stepper::new(defs, term);
+    st.fuel = 10;
+    assert_eq!(st.step_over(), Err(Stop::OutOfFuel(10)));
+    assert_eq!(st.steps, 11);
+  }
+}
@@ -0,0 +1,433 @@
+use crate::{
+  formats::occurs,
+  name::Name,
+  term::{
+    Def,
+    Term,
+  },
+};
+
+use hashexpr::position::Pos;
+
+use im::{
+  HashSet,
+  Vector,
+};
+
+use std::fmt;
+
+// Structural termination checking. A definition refers to itself through
+// the outermost variable of its term, and its parameters are the variables
+// bound by the lambdas its term starts with. It is accepted when there is a
+// parameter on which every recursive call is made with a strict subterm of
+// that parameter. The strict subterms of a variable are the fields bound by
+// the branches of a `case` on it: the lambdas the `case` is applied to after
+// its motive. In
+//
+//   λ P z n => (case n) P z (λ pred => f P z pred)
+//
+// `pred` is smaller than `n`, so the call `f P z pred` decreases. Types are
+// not evaluated at runtime, so references in them, such as those of an
+// inductive type to itself, are not calls. Recursion through local `letrec`
+// bindings is not considered, and a definition can opt out of the check by
+// being declared `partial`.
+//
+// Mutually recursive definitions refer to each other through the outermost
+// variables of their terms, one for each of them, and are checked together:
+// there must be a position at which every call among them, to any of them,
+// passes a strict subterm of the caller's parameter at that position. Only
+// the calls which may come back to their caller are recursive: a call to a
+// definition which never leads back to the caller need not decrease.
+//
+// A corecursive definition need not terminate, but it must be productive: a
+// call which decreases nothing is accepted when it is guarded by a pair, as
+// in `λ n => (n, nats (#add n 1))`. Such a call is only unfolded once the
+// component it lies in is observed, so each observation of the result takes
+// finitely many steps. A guarded call may be under lambdas, `let` bodies and
+// the branches of a `case`, but not in an argument of a function, even within
+// a pair, nor under a projection, which could observe it before anything is
+// produced.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TerminationError {
+  // A recursive call which decreases none of the parameters decreased by
+  // every call before it
+  NonDecreasing(Option<Pos>, String, Term),
+}
+
+impl TerminationError {
+  pub fn pos(&self) -> Option<Pos> {
+    match self {
+      Self::NonDecreasing(pos, ..) => *pos,
+    }
+  }
+}
+
+impl fmt::Display for TerminationError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NonDecreasing(_, nam, call) => write!(
+        f,
+        "Cannot show that {} terminates: the recursive call {} does not \
+         structurally decrease a parameter decreased by every other call, \
+         and is not guarded by a pair. Declare it `partial` to skip this \
+         check",
+        nam, call
+      ),
+    }
+  }
+}
+
+impl std::error::Error for TerminationError {}
+
+// What is known of the size of a variable: the parameter it is, if any, and
+// the parameters it is strictly smaller than
+#[derive(Clone, Debug, Default)]
+struct Size {
+  param: Option<usize>,
+  below: HashSet<usize>,
+}
+
+// Where a term lies: in an argument, whose value may be observed by the
+// function it is passed to, in the result of the definition, or in a
+// component of a pair in its result
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Position {
+  Argument,
+  Result,
+  Guarded,
+}
+
+struct Checker {
+  name: Name,
+  // The number of definitions checked together, which are called through the
+  // outermost variables
+  members: usize,
+  // Whether a call to each of them may come back to this one
+  recursive: Vec<bool>,
+  params: usize,
+  // The parameters decreased by every recursive call so far
+  decreasing: HashSet<usize>,
+}
+
+impl Checker {
+  fn is_member(&self, ctx: &Vector<Size>, idx: u64) -> bool {
+    match (idx as usize).checked_sub(ctx.len() - self.members) {
+      Some(member) => self.recursive.get(member) == Some(&true),
+      None => false,
+    }
+  }
+
+  fn call(
+    &mut self,
+    ctx: &Vector<Size>,
+    call: &Term,
+    args: &[&Term],
+  ) -> Result<(), TerminationError> {
+    let decreasing = (0..self.params.min(args.len()))
+      .filter(|param| match args[*param] {
+        Term::Var(_, _, idx) => match ctx.get(*idx as usize) {
+          Some(size) => size.below.contains(param),
+          None => false,
+        },
+        _ => false,
+      })
+      .collect();
+    self.decreasing = self.decreasing.clone().intersection(decreasing);
+    if self.decreasing.is_empty() {
+      Err(TerminationError::NonDecreasing(
+        call.pos(),
+        self.name.to_string(),
+        call.clone(),
+      ))
+    }
+    else {
+      Ok(())
+    }
+  }
+
+  // A branch of a `case`, binding fields of the given size
+  fn branch(
+    &mut self,
+    ctx: &Vector<Size>,
+    term: &Term,
+    size: &Size,
+    at: Position,
+  ) -> Result<(), TerminationError> {
+    match term {
+      Term::Lam(_, _, bod) => {
+        let mut ctx = ctx.clone();
+        ctx.push_front(size.clone());
+        self.branch(&ctx, bod, size, at)
+      }
+      _ => self.term(ctx, term, at),
+    }
+  }
+
+  fn term(
+    &mut self,
+    ctx: &Vector<Size>,
+    term: &Term,
+    at: Position,
+  ) -> Result<(), TerminationError> {
+    let under = |ctx: &Vector<Size>| {
+      let mut ctx = ctx.clone();
+      ctx.push_front(Size::default());
+      ctx
+    };
+    match term {
+      Term::Var(_, _, idx) if self.is_member(ctx, *idx) => {
+        if at == Position::Guarded {
+          Ok(())
+        }
+        else {
+          self.call(ctx, term, &[])
+        }
+      }
+      Term::App(..) => {
+        let mut head = term;
+        let mut args = Vec::new();
+        while let Term::App(_, terms) = head {
+          args.push(&terms.1);
+          head = &terms.0;
+        }
+        args.reverse();
+        match head {
+          Term::Var(_, _, idx) if self.is_member(ctx, *idx) => {
+            if at != Position::Guarded {
+              self.call(ctx, term, &args)?;
+            }
+          }
+          Term::Cse(_, scrut) => {
+            self.term(ctx, scrut, Position::Argument)?;
+            let mut fields = Size::default();
+            if let Term::Var(_, _, idx) = **scrut {
+              if let Some(size) = ctx.get(idx as usize) {
+                fields.below = size.below.clone();
+                fields.below.extend(size.param);
+              }
+            }
+            // The first argument is the motive, the rest are the branches
+            if let Some((motive, branches)) = args.split_first() {
+              self.term(ctx, motive, Position::Argument)?;
+              for branch in branches {
+                self.branch(ctx, branch, &fields, at)?;
+              }
+            }
+            return Ok(());
+          }
+          _ => self.term(ctx, head, Position::Argument)?,
+        }
+        for arg in args {
+          self.term(ctx, arg, Position::Argument)?;
+        }
+        Ok(())
+      }
+      Term::Lam(_, _, bod) => self.term(&under(ctx), bod, at),
+      Term::Dat(_, bod)
+      | Term::Cse(_, bod)
+      | Term::Fst(_, bod)
+      | Term::Snd(_, bod) => self.term(ctx, bod, Position::Argument),
+      Term::Par(_, terms) => {
+        let at = match at {
+          Position::Argument => Position::Argument,
+          _ => Position::Guarded,
+        };
+        self.term(ctx, &terms.0, at)?;
+        self.term(ctx, &terms.1, at)
+      }
+      // The type of an annotation and type formers are not evaluated
+      Term::Ann(_, terms) => self.term(ctx, &terms.1, at),
+      Term::All(..) | Term::Slf(..) | Term::Sig(..) => Ok(()),
+      Term::Let(_, rec, _, _, terms) => {
+        let exp_ctx = if *rec { under(ctx) } else { ctx.clone() };
+        self.term(&exp_ctx, &terms.1, Position::Argument)?;
+        self.term(&under(ctx), &terms.2, at)
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+// Check that every recursive call of a definition structurally decreases
+// one of its parameters
+pub fn check_termination(def: &Def) -> Result<(), TerminationError> {
+  check_mutual_termination(&[(def.name.clone(), &def.term)])
+}
+
+// Check that every call among mutually recursive definitions, each of which
+// refers to them all, structurally decreases the parameter at one position
+pub fn check_mutual_termination(
+  defs: &[(Name, &Term)],
+) -> Result<(), TerminationError> {
+  let mut bodies = Vec::new();
+  for (name, term) in defs {
+    let mut ctx = Vector::new();
+    for _ in defs {
+      ctx.push_front(Size::default());
+    }
+    let mut params = 0;
+    let mut term = *term;
+    loop {
+      match term {
+        Term::Lam(_, _, bod) => {
+          let mut size = Size::default();
+          size.param = Some(params);
+          ctx.push_front(size);
+          params += 1;
+          term = bod;
+        }
+        Term::Ann(_, terms) => term = &terms.1,
+        _ => break,
+      }
+    }
+    bodies.push((name.clone(), ctx, params, term));
+  }
+  // Which definitions each one calls, directly or through the others
+  let members = defs.len();
+  let mut reaches: Vec<Vec<bool>> = defs
+    .iter()
+    .map(|(_, term)| (0..members).map(|k| occurs(term, k as u64)).collect())
+    .collect();
+  for via in 0..members {
+    for from in 0..members {
+      if reaches[from][via] {
+        for to in 0..members {
+          if reaches[via][to] {
+            reaches[from][to] = true;
+          }
+        }
+      }
+    }
+  }
+  let most = bodies.iter().map(|body| body.2).max().unwrap_or(0);
+  let mut decreasing = (0..most).collect();
+  for (caller, (name, ctx, params, term)) in bodies.into_iter().enumerate() {
+    let recursive =
+      (0..members).map(|k| k == caller || reaches[k][caller]).collect();
+    let mut checker =
+      Checker { name, members, recursive, params, decreasing };
+    checker.term(&ctx, term, Position::Result)?;
+    decreasing = checker.decreasing;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::datatype::tests::datatype,
+    parse::{
+      span::Span,
+      term::parse_expression,
+    },
+  };
+  use im::HashMap;
+
+  fn def(nam: &str, term: &str) -> Def {
+    let ctx = Vector::from(vec![Name::from(nam)]);
+    let expr = parse_expression(HashMap::new(), HashMap::new(), ctx);
+    let (_, term) = expr(Span::new(term)).unwrap();
+    Def::new(None, Name::from(nam), String::new(), Term::Typ(None, 0), term)
+  }
+
+  #[test]
+  fn accepts() {
+    assert_eq!(check_termination(&def("id", "λ x => x")), Ok(()));
+    let def = def("f", "λ P z n => (case n) P z (λ pred => f P z pred)");
+    assert_eq!(check_termination(&def), Ok(()));
+    let nat = datatype("Nat", &[], "Type@1", &[
+      ("zero", "Nat"),
+      ("succ", "∀ (pred: Nat) -> Nat"),
+    ])
+    .unwrap();
+    for def in nat.defs() {
+      assert_eq!(check_termination(&def), Ok(()));
+    }
+  }
+
+  #[test]
+  fn ignores_types() {
+    let typ = def("T", "λ A => @self ∀ (P: ∀ (x: T A) -> Type) -> P self");
+    assert_eq!(check_termination(&typ), Ok(()));
+    let ann = def("f", "λ x => (x :: f x)");
+    assert_eq!(check_termination(&ann), Ok(()));
+  }
+
+  #[test]
+  fn productive() {
+    let nats = def("nats", "λ n => (n, nats (#add n 1))");
+    assert_eq!(check_termination(&nats), Ok(()));
+    let f = def(
+      "f",
+      "λ P n => (case n) P (1, f P n) (λ pred => (pred, f P pred))",
+    );
+    assert_eq!(check_termination(&f), Ok(()));
+    // A projection may observe the call before anything is produced
+    match check_termination(&def("f", "λ n => snd (1, f n)")) {
+      Err(TerminationError::NonDecreasing(_, _, call)) => {
+        assert_eq!(format!("{}", call), "f n")
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+    match check_termination(&def("f", "λ g n => g (1, f g n)")) {
+      Err(TerminationError::NonDecreasing(_, _, call)) => {
+        assert_eq!(format!("{}", call), "f g n")
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[test]
+  fn checks_mutual_recursion() {
+    let ctx = Vector::from(vec![Name::from("even"), Name::from("odd")]);
+    let parse = |term: &str| {
+      let expr = parse_expression(HashMap::new(), HashMap::new(), ctx.clone());
+      expr(Span::new(term)).unwrap().1
+    };
+    let even = parse("λ P n => (case n) P 1 (λ pred => odd P pred)");
+    let odd = parse("λ P n => (case n) P 0 (λ pred => even P pred)");
+    let defs = [(Name::from("even"), &even), (Name::from("odd"), &odd)];
+    assert_eq!(check_mutual_termination(&defs), Ok(()));
+    let odd = parse("λ P n => even P n");
+    let defs = [(Name::from("even"), &even), (Name::from("odd"), &odd)];
+    match check_mutual_termination(&defs) {
+      Err(TerminationError::NonDecreasing(_, nam, call)) => {
+        assert_eq!(nam, "odd");
+        assert_eq!(format!("{}", call), "even P n");
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+    // A call which never comes back to its caller is not recursive
+    let zero = parse("λ P n => 0");
+    let one = parse("λ P n => even P n");
+    let defs = [(Name::from("even"), &zero), (Name::from("odd"), &one)];
+    assert_eq!(check_mutual_termination(&defs), Ok(()));
+  }
+
+  #[test]
+  fn rejects() {
+    match check_termination(&def("loop", "λ n => loop n")) {
+      Err(TerminationError::NonDecreasing(Some(_), nam, call)) => {
+        assert_eq!(nam, "loop");
+        assert_eq!(format!("{}", call), "loop n");
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+    // Each call decreases a different parameter
+    let def = def(
+      "f",
+      concat!(
+        "λ P n m => (case n) P ",
+        "(λ a => (case m) P (λ b => f P a m) (λ c => f P n c))"
+      ),
+    );
+    match check_termination(&def) {
+      Err(TerminationError::NonDecreasing(_, _, call)) => {
+        assert_eq!(format!("{}", call), "f P n c")
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+}
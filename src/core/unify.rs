@@ -0,0 +1,869 @@
+use crate::{
+  core::nbe::{
+    self,
+    apply,
+    eval,
+    Closure,
+    Value,
+  },
+  definition::Transparency,
+  name::Name,
+  term::{
+    Defs,
+    Term,
+  },
+};
+
+use hashexpr::position::Pos;
+
+use im::{
+  HashMap,
+  Vector,
+};
+
+use std::{
+  cell::Cell,
+  rc::Rc,
+};
+
+// Higher-order pattern unification. Before checking, each placeholder `_` is
+// replaced with a fresh metavariable applied to the variables bound around
+// it, so that `λ x y => _` becomes `λ x y => ?0 x y`. Conversion checking
+// then solves metavariables: an equation `?m x1 .. xn = t`, where the xs are
+// distinct bound variables, has the unique solution `?m = λ x1 .. xn => t`,
+// provided that t mentions no other bound variables and not ?m itself.
+// Equations outside of this pattern fragment are not solved, and fail to
+// unify.
+//
+// Proofs of propositions are irrelevant: two applications of the same head
+// are convertible when their arguments are, except for those which the type
+// of the head says are proofs, which are not compared at all. This needs the
+// types of the variables bound around the values being unified, and an
+// argument is only recognized as a proof when its head's type is known.
+//
+// References are unfolded according to the transparency of their
+// definitions: opaque definitions are not unfolded, reducible ones always
+// are, and unfolding a transparent one uses up a unit of fuel. Once the fuel
+// runs out, transparent definitions are no longer unfolded either, so that
+// types which could only be compared by unfolding many definitions fail to
+// unify rather than taking too long to. Conversion may also be asked to
+// unfold opaque definitions, using up fuel likewise, or only reducible ones.
+
+// The default number of transparent definitions unfolded while checking a
+// definition
+pub const FUEL: u64 = 100_000;
+
+// How deep the unfoldings of a reference applied to different arguments on
+// both sides of a conversion may be nested
+pub const NESTING: u64 = 64;
+
+// The types of the variables bound around a value, by de Bruijn level, where
+// they are known
+pub type Types = Vector<Option<Value>>;
+
+#[derive(Clone, Debug)]
+pub struct Meta {
+  pub pos: Option<Pos>,
+  // The name of the hole `?name` the metavariable was created for
+  pub name: Option<String>,
+  // The number of variables the metavariable is applied to
+  pub arity: u64,
+  // The type the metavariable is expected to have, together with the depth
+  // of the context it was created in, once known
+  pub typ: Option<(Value, u64)>,
+  pub solution: Option<Value>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Metas {
+  pub metas: Vec<Meta>,
+  // The number of transparent definitions which may still be unfolded
+  pub fuel: Cell<u64>,
+  // How deep the unfoldings of a reference applied to different arguments
+  // are nested around the conversion being checked
+  pub nesting: Cell<u64>,
+  // The least transparent definitions which are unfolded
+  pub unfolding: Transparency,
+}
+
+impl Metas {
+  pub fn new() -> Self {
+    Metas {
+      metas: Vec::new(),
+      fuel: Cell::new(FUEL),
+      nesting: Cell::new(0),
+      unfolding: Transparency::Transparent,
+    }
+  }
+
+  // Use up a unit of fuel, if there is any left
+  fn burn(&self) -> bool {
+    let fuel = self.fuel.get();
+    self.fuel.set(fuel.saturating_sub(1));
+    fuel > 0
+  }
+
+  pub fn out_of_fuel(&self) -> bool { self.fuel.get() == 0 }
+
+  pub fn fresh(&mut self, pos: Option<Pos>, arity: u64) -> u64 {
+    let meta = Meta { pos, name: None, arity, typ: None, solution: None };
+    self.metas.push(meta);
+    self.metas.len() as u64 - 1
+  }
+
+  pub fn get(&self, idx: u64) -> Option<&Meta> { self.metas.get(idx as usize) }
+
+  pub fn solution(&self, idx: u64) -> Option<&Value> {
+    self.get(idx).and_then(|meta| meta.solution.as_ref())
+  }
+
+  pub fn set_type(&mut self, idx: u64, typ: Value, depth: u64) {
+    if let Some(meta) = self.metas.get_mut(idx as usize) {
+      meta.typ = Some((typ, depth));
+    }
+  }
+
+  fn solve(&mut self, idx: u64, val: Value) {
+    if let Some(meta) = self.metas.get_mut(idx as usize) {
+      meta.solution = Some(val);
+    }
+  }
+
+  // The first metavariable left unsolved
+  pub fn unsolved(&self) -> Option<&Meta> {
+    self.metas.iter().find(|meta| meta.solution.is_none())
+  }
+
+  // Replace each placeholder in `term` with a fresh metavariable applied to
+  // the bound variables in scope. `ctx` gives the name of each variable in
+  // scope, by de Bruijn index, and whether it is bound rather than defined.
+  pub fn insert(&mut self, ctx: &Vector<(Name, bool)>, term: &Term) -> Term {
+    let under = |nam: &Name, bound: bool| {
+      let mut ctx = ctx.clone();
+      ctx.push_front((nam.clone(), bound));
+      ctx
+    };
+    match term {
+      Term::Hol(pos, nam) => {
+        let vars: Vec<Term> = (0..ctx.len())
+          .rev()
+          .filter(|idx| ctx[*idx].1)
+          .map(|idx| Term::Var(None, ctx[idx].0.clone(), idx as u64))
+          .collect();
+        let idx = self.fresh(*pos, vars.len() as u64);
+        self.metas[idx as usize].name = nam.clone();
+        vars.into_iter().fold(Term::Met(*pos, idx), |acc, var| {
+          Term::App(None, Box::new((acc, var)))
+        })
+      }
+      Term::Lam(pos, nam, bod) => {
+        let bod = self.insert(&under(nam, true), bod);
+        Term::Lam(*pos, nam.clone(), Box::new(bod))
+      }
+      Term::Slf(pos, nam, bod) => {
+        let bod = self.insert(&under(nam, true), bod);
+        Term::Slf(*pos, nam.clone(), Box::new(bod))
+      }
+      Term::Dat(pos, bod) => Term::Dat(*pos, Box::new(self.insert(ctx, bod))),
+      Term::Cse(pos, bod) => Term::Cse(*pos, Box::new(self.insert(ctx, bod))),
+      Term::Fst(pos, bod) => Term::Fst(*pos, Box::new(self.insert(ctx, bod))),
+      Term::Snd(pos, bod) => Term::Snd(*pos, Box::new(self.insert(ctx, bod))),
+      Term::Par(pos, terms) => {
+        let fst = self.insert(ctx, &terms.0);
+        Term::Par(*pos, Box::new((fst, self.insert(ctx, &terms.1))))
+      }
+      Term::Sig(pos, nam, terms) => {
+        let dom = self.insert(ctx, &terms.0);
+        let bod = self.insert(&under(nam, true), &terms.1);
+        Term::Sig(*pos, nam.clone(), Box::new((dom, bod)))
+      }
+      Term::App(pos, terms) => {
+        let fun = self.insert(ctx, &terms.0);
+        Term::App(*pos, Box::new((fun, self.insert(ctx, &terms.1))))
+      }
+      Term::Ann(pos, terms) => {
+        let typ = self.insert(ctx, &terms.0);
+        Term::Ann(*pos, Box::new((typ, self.insert(ctx, &terms.1))))
+      }
+      Term::All(pos, uses, nam, terms) => {
+        let dom = self.insert(ctx, &terms.0);
+        let img = self.insert(&under(nam, true), &terms.1);
+        Term::All(*pos, *uses, nam.clone(), Box::new((dom, img)))
+      }
+      Term::Let(pos, rec, uses, nam, terms) => {
+        let typ = self.insert(ctx, &terms.0);
+        // A recursive binding's expression is checked with the binding bound
+        let exp_ctx = if *rec { under(nam, true) } else { ctx.clone() };
+        let exp = self.insert(&exp_ctx, &terms.1);
+        let bod = self.insert(&under(nam, false), &terms.2);
+        Term::Let(*pos, *rec, *uses, nam.clone(), Box::new((typ, exp, bod)))
+      }
+      _ => term.clone(),
+    }
+  }
+}
+
+// Unfold solved metavariables in head position
+pub fn force_metas(defs: &Defs, metas: &Metas, val: Value) -> Value {
+  match val {
+    Value::Meta(idx) => match metas.solution(idx) {
+      Some(sol) => force_metas(defs, metas, sol.clone()),
+      None => Value::Meta(idx),
+    },
+    Value::App(fun, arg) => match force_metas(defs, metas, (*fun).clone()) {
+      fun @ Value::Lam(..) | fun @ Value::Opr(..) | fun @ Value::Fix(..) => {
+        force_metas(defs, metas, apply(defs, fun, (*arg).clone()))
+      }
+      fun => Value::App(Rc::new(fun), arg),
+    },
+    Value::Cse(bod) => match force_metas(defs, metas, (*bod).clone()) {
+      Value::Dat(val) => force_metas(defs, metas, (*val).clone()),
+      bod => Value::Cse(Rc::new(bod)),
+    },
+    Value::Fst(bod) => match force_metas(defs, metas, (*bod).clone()) {
+      Value::Par(fst, _) => force_metas(defs, metas, (*fst).clone()),
+      bod => Value::Fst(Rc::new(bod)),
+    },
+    Value::Snd(bod) => match force_metas(defs, metas, (*bod).clone()) {
+      Value::Par(_, snd) => force_metas(defs, metas, (*snd).clone()),
+      bod => Value::Snd(Rc::new(bod)),
+    },
+    val => val,
+  }
+}
+
+// Unfold solved metavariables, and the references which may be unfolded and
+// recursive bindings in head position
+pub fn force(defs: &Defs, metas: &Metas, val: Value) -> Value {
+  let val = force_metas(defs, metas, val);
+  match unfold(defs, metas, &val) {
+    Some(val) => force(defs, metas, val),
+    None => val,
+  }
+}
+
+// Unfold the reference or recursive binding in head position once, if it
+// may be unfolded
+fn unfold(defs: &Defs, metas: &Metas, val: &Value) -> Option<Value> {
+  match val {
+    Value::Ref(_, def_link, _) => {
+      let def = defs.get(def_link)?;
+      let unfolds = match def.transparency {
+        transparency if transparency < metas.unfolding => false,
+        Transparency::Reducible => true,
+        _ => metas.burn(),
+      };
+      if !unfolds {
+        return None;
+      }
+      // A definition's body is closed over a variable standing for itself
+      let mut env = Vector::new();
+      env.push_front(val.clone());
+      Some(eval(defs, env, &def.term))
+    }
+    Value::Fix(_, clos) => Some(clos.apply(defs, val.clone())),
+    Value::App(fun, arg) => {
+      match force_metas(defs, metas, unfold(defs, metas, fun)?) {
+        fun @ Value::Lam(..) | fun @ Value::Opr(..) => {
+          Some(apply(defs, fun, (**arg).clone()))
+        }
+        fun => Some(Value::App(Rc::new(fun), arg.clone())),
+      }
+    }
+    _ => None,
+  }
+}
+
+// The head of a value and the arguments it is applied to
+fn spine(val: &Value) -> (Value, Vec<Value>) {
+  let mut head = val;
+  let mut args = Vec::new();
+  while let Value::App(fun, arg) = head {
+    args.push((**arg).clone());
+    head = fun;
+  }
+  args.reverse();
+  (head.clone(), args)
+}
+
+// The unsolved metavariable at the head of a value, and its arguments
+pub fn flex(val: &Value) -> Option<(u64, Vec<Value>)> {
+  match spine(val) {
+    (Value::Meta(idx), args) => Some((idx, args)),
+    _ => None,
+  }
+}
+
+// The type of `head` applied to `args`, if the type of `head` is known
+fn spine_type(
+  defs: &Defs,
+  metas: &Metas,
+  types: &Types,
+  head: &Value,
+  args: &[Value],
+) -> Option<Value> {
+  let mut typ = match head {
+    Value::Var(_, lvl) => types.get(*lvl as usize)?.clone()?,
+    Value::Ref(_, def_link, _) => {
+      eval(defs, Vector::new(), &defs.get(def_link)?.typ_)
+    }
+    _ => return None,
+  };
+  for arg in args {
+    typ = match force(defs, metas, typ) {
+      Value::All(_, _, _, img) => img.apply(defs, arg.clone()),
+      _ => return None,
+    };
+  }
+  Some(typ)
+}
+
+// Whether a type is a proposition, a type whose sort is `Prop`. A ∀ is one
+// when its image is, whatever its domain, and a Σ when both its components
+// are. A type whose sort is unknown is taken not to be one.
+pub fn is_prop(defs: &Defs, metas: &Metas, types: &Types, typ: &Value) -> bool {
+  let under = |nam: &Name, dom: Value, clos: &Closure| {
+    let var = Value::Var(nam.clone(), types.len() as u64);
+    let mut types = types.clone();
+    types.push_back(Some(dom));
+    is_prop(defs, metas, &types, &clos.apply(defs, var))
+  };
+  match force(defs, metas, typ.clone()) {
+    Value::All(_, nam, dom, img) => under(&nam, (*dom).clone(), &img),
+    Value::Sig(nam, dom, img) => {
+      is_prop(defs, metas, types, &dom) && under(&nam, (*dom).clone(), &img)
+    }
+    // The bound variable stands for a term of the self type itself
+    Value::Slf(nam, bod) => {
+      let slf = Value::Slf(nam.clone(), bod.clone());
+      under(&nam, slf, &bod)
+    }
+    typ => {
+      let (head, args) = spine(&typ);
+      match spine_type(defs, metas, types, &head, &args) {
+        Some(sort) => matches!(force(defs, metas, sort), Value::Prp),
+        None => false,
+      }
+    }
+  }
+}
+
+// Unify the arguments of two applications of `head`, skipping the proofs
+fn unify_args(
+  defs: &Defs,
+  metas: &mut Metas,
+  types: &Types,
+  head: &Value,
+  xs: &[Value],
+  ys: &[Value],
+) -> bool {
+  if xs.len() != ys.len() {
+    return false;
+  }
+  let mut typ = spine_type(defs, metas, types, head, &[]);
+  for (x, y) in xs.iter().zip(ys.iter()) {
+    let (proof, img) = match typ.map(|typ| force(defs, metas, typ)) {
+      Some(Value::All(_, _, dom, img)) => {
+        (is_prop(defs, metas, types, &dom), Some(img.apply(defs, x.clone())))
+      }
+      _ => (false, None),
+    };
+    if !proof && !unify(defs, metas, types, x, y) {
+      return false;
+    }
+    typ = img;
+  }
+  true
+}
+
+// A renaming of the variables of a context of length `cod` into one of length
+// `dom`, or the identity when `ren` is `None`. Reading a value back under a
+// renaming fails when it mentions a variable outside of the renaming, or the
+// metavariable being solved.
+struct Renaming {
+  meta: Option<u64>,
+  dom: u64,
+  cod: u64,
+  ren: Option<HashMap<u64, u64>>,
+}
+
+impl Renaming {
+  // Extend the renaming under a binder
+  fn lift(&self) -> Self {
+    let mut ren = self.ren.clone();
+    if let Some(ren) = &mut ren {
+      ren.insert(self.cod, self.dom);
+    }
+    Renaming { meta: self.meta, dom: self.dom + 1, cod: self.cod + 1, ren }
+  }
+}
+
+fn read_back(
+  defs: &Defs,
+  metas: &Metas,
+  pren: &Renaming,
+  val: &Value,
+) -> Option<Term> {
+  let go = |val: &Value| read_back(defs, metas, pren, val);
+  let under = |nam: &Name, clos: &Closure| {
+    let bod = clos.apply(defs, Value::Var(nam.clone(), pren.cod));
+    read_back(defs, metas, &pren.lift(), &bod)
+  };
+  match force_metas(defs, metas, val.clone()) {
+    Value::Var(nam, lvl) => {
+      let lvl = match &pren.ren {
+        Some(ren) => *ren.get(&lvl)?,
+        None => lvl,
+      };
+      Some(Term::Var(None, nam, pren.dom - lvl - 1))
+    }
+    Value::Free(nam, idx) => Some(Term::Var(None, nam, pren.dom + idx)),
+    Value::Meta(idx) if pren.meta == Some(idx) => None,
+    Value::Meta(idx) => Some(Term::Met(None, idx)),
+    Value::Lam(nam, clos) => {
+      let bod = under(&nam, &clos)?;
+      Some(Term::Lam(None, nam, Box::new(bod)))
+    }
+    Value::All(uses, nam, dom, clos) => {
+      let img = under(&nam, &clos)?;
+      Some(Term::All(None, uses, nam, Box::new((go(&dom)?, img))))
+    }
+    Value::Slf(nam, clos) => {
+      let bod = under(&nam, &clos)?;
+      Some(Term::Slf(None, nam, Box::new(bod)))
+    }
+    Value::Dat(bod) => Some(Term::Dat(None, Box::new(go(&bod)?))),
+    Value::Cse(bod) => Some(Term::Cse(None, Box::new(go(&bod)?))),
+    Value::Sig(nam, dom, clos) => {
+      let bod = under(&nam, &clos)?;
+      Some(Term::Sig(None, nam, Box::new((go(&dom)?, bod))))
+    }
+    Value::Par(fst, snd) => {
+      Some(Term::Par(None, Box::new((go(&fst)?, go(&snd)?))))
+    }
+    Value::Fst(bod) => Some(Term::Fst(None, Box::new(go(&bod)?))),
+    Value::Snd(bod) => Some(Term::Snd(None, Box::new(go(&bod)?))),
+    Value::Typ(lvl) => Some(Term::Typ(None, lvl)),
+    Value::Prp => Some(Term::Prp(None)),
+    Value::Lit(lit) => Some(Term::Lit(None, lit)),
+    Value::LTy(lty) => Some(Term::LTy(None, lty)),
+    Value::Opr(opr, args) => {
+      args.iter().try_fold(Term::Opr(None, opr), |acc, arg| {
+        Some(Term::App(None, Box::new((acc, go(arg)?))))
+      })
+    }
+    Value::Ref(nam, def_link, ast_link) => {
+      Some(Term::Ref(None, nam, def_link, ast_link))
+    }
+    fix @ Value::Fix(..) => go(&nbe::force(defs, fix)),
+    Value::App(fun, arg) => {
+      Some(Term::App(None, Box::new((go(&fun)?, go(&arg)?))))
+    }
+  }
+}
+
+// Read a value back into a term under `depth` binders, unfolding solved
+// metavariables but not references
+pub fn quote(defs: &Defs, metas: &Metas, depth: u64, val: &Value) -> Term {
+  let pren = Renaming { meta: None, dom: depth, cod: depth, ren: None };
+  read_back(defs, metas, &pren, val).expect("the identity renaming is total")
+}
+
+// Replace the solved metavariables of a term with their solutions
+pub fn zonk(defs: &Defs, metas: &Metas, term: &Term) -> Term {
+  // Also tell whether the term is a solution, so that the redexes formed by
+  // applying a solution to its arguments are contracted, and no others
+  fn go(defs: &Defs, metas: &Metas, term: &Term) -> (Term, bool) {
+    match term {
+      Term::Met(_, idx) => match metas.solution(*idx) {
+        Some(sol) => (quote(defs, metas, 0, sol), true),
+        None => (term.clone(), false),
+      },
+      Term::App(pos, terms) => {
+        let arg = zonk(defs, metas, &terms.1);
+        match go(defs, metas, &terms.0) {
+          (Term::Lam(_, _, bod), true) => (bod.instantiate(&arg), true),
+          (fun, _) => (Term::App(*pos, Box::new((fun, arg))), false),
+        }
+      }
+      _ => (zonk(defs, metas, term), false),
+    }
+  }
+  let rec = |term: &Term| zonk(defs, metas, term);
+  match term {
+    Term::Met(..) | Term::App(..) => go(defs, metas, term).0,
+    Term::Lam(pos, nam, bod) => {
+      Term::Lam(*pos, nam.clone(), Box::new(rec(bod)))
+    }
+    Term::Slf(pos, nam, bod) => {
+      Term::Slf(*pos, nam.clone(), Box::new(rec(bod)))
+    }
+    Term::Dat(pos, bod) => Term::Dat(*pos, Box::new(rec(bod))),
+    Term::Cse(pos, bod) => Term::Cse(*pos, Box::new(rec(bod))),
+    Term::Fst(pos, bod) => Term::Fst(*pos, Box::new(rec(bod))),
+    Term::Snd(pos, bod) => Term::Snd(*pos, Box::new(rec(bod))),
+    Term::Par(pos, terms) => {
+      Term::Par(*pos, Box::new((rec(&terms.0), rec(&terms.1))))
+    }
+    Term::Sig(pos, nam, terms) => {
+      Term::Sig(*pos, nam.clone(), Box::new((rec(&terms.0), rec(&terms.1))))
+    }
+    Term::Ann(pos, terms) => {
+      Term::Ann(*pos, Box::new((rec(&terms.0), rec(&terms.1))))
+    }
+    Term::All(pos, uses, nam, terms) => Term::All(
+      *pos,
+      *uses,
+      nam.clone(),
+      Box::new((rec(&terms.0), rec(&terms.1))),
+    ),
+    Term::Let(pos, is_rec, uses, nam, terms) => Term::Let(
+      *pos,
+      *is_rec,
+      *uses,
+      nam.clone(),
+      Box::new((rec(&terms.0), rec(&terms.1), rec(&terms.2))),
+    ),
+    _ => term.clone(),
+  }
+}
+
+// Solve `?meta spine = rhs` under `depth` binders, if it is a pattern
+fn solve(
+  defs: &Defs,
+  metas: &mut Metas,
+  depth: u64,
+  meta: u64,
+  spine: &[Value],
+  rhs: &Value,
+) -> bool {
+  let mut ren = HashMap::new();
+  let mut nams = Vec::new();
+  for (idx, arg) in spine.iter().enumerate() {
+    match force_metas(defs, metas, arg.clone()) {
+      Value::Var(nam, lvl) if !ren.contains_key(&lvl) => {
+        ren.insert(lvl, idx as u64);
+        nams.push(nam);
+      }
+      _ => return false,
+    }
+  }
+  let dom = spine.len() as u64;
+  let pren = Renaming { meta: Some(meta), dom, cod: depth, ren: Some(ren) };
+  match read_back(defs, metas, &pren, rhs) {
+    Some(bod) => {
+      let sol = nams
+        .into_iter()
+        .rev()
+        .fold(bod, |acc, nam| Term::Lam(None, nam, Box::new(acc)));
+      metas.solve(meta, eval(defs, Vector::new(), &sol));
+      true
+    }
+    None => false,
+  }
+}
+
+// Whether `a` and `b` are convertible under binders of the given types,
+// solving metavariables as needed. Without metavariables, two values are
+// convertible when their normal forms agree up to renaming, eta and the
+// irrelevance of proofs.
+pub fn unify(
+  defs: &Defs,
+  metas: &mut Metas,
+  types: &Types,
+  a: &Value,
+  b: &Value,
+) -> bool {
+  let a = force_metas(defs, metas, a.clone());
+  let b = force_metas(defs, metas, b.clone());
+  // Identical references applied to convertible arguments are convertible
+  // without unfolding them, which is how recursive types are compared.
+  // Otherwise both sides are unfolded, as a reference may ignore some of its
+  // arguments.
+  let ((hx, xs), (hy, ys)) = (spine(&a), spine(&b));
+  if let (Value::Ref(_, x, _), Value::Ref(_, y, _)) = (&hx, &hy) {
+    if x == y {
+      if unify_args(defs, metas, types, &hx, &xs, &ys) {
+        return true;
+      }
+      // A recursive type applied to different arguments unfolds to itself
+      // applied to them again, so these unfoldings are only nested so deep
+      let nesting = metas.nesting.get();
+      if nesting >= NESTING {
+        return false;
+      }
+      metas.nesting.set(nesting + 1);
+      let res = unify_unfolded(defs, metas, types, a, b);
+      metas.nesting.set(nesting);
+      return res;
+    }
+  }
+  unify_unfolded(defs, metas, types, a, b)
+}
+
+// Whether `a` and `b`, whose heads are not the same reference applied to
+// convertible arguments, are convertible
+fn unify_unfolded(
+  defs: &Defs,
+  metas: &mut Metas,
+  types: &Types,
+  a: Value,
+  b: Value,
+) -> bool {
+  let depth = types.len() as u64;
+  // Metavariables are solved before unfolding any references
+  match (flex(&a), flex(&b)) {
+    (Some((x, xs)), Some((y, ys))) if x == y => {
+      return xs.len() == ys.len()
+        && xs
+          .iter()
+          .zip(ys.iter())
+          .all(|(x, y)| unify(defs, metas, types, x, y));
+    }
+    // When both sides are flexible, either may be the one in the pattern
+    // fragment
+    (Some((x, xs)), other) => {
+      return solve(defs, metas, depth, x, &xs, &b)
+        || match other {
+          Some((y, ys)) => solve(defs, metas, depth, y, &ys, &a),
+          None => false,
+        };
+    }
+    (None, Some((y, ys))) => return solve(defs, metas, depth, y, &ys, &a),
+    (None, None) => (),
+  }
+  let a = force(defs, metas, a);
+  let b = force(defs, metas, b);
+  let bind = |nam: &Name, clos: &Closure| {
+    clos.apply(defs, Value::Var(nam.clone(), depth))
+  };
+  // The types extended with a binder of the given type
+  let under = |typ: Option<Value>| {
+    let mut types = types.clone();
+    types.push_back(typ);
+    types
+  };
+  match (&a, &b) {
+    (Value::Typ(x), Value::Typ(y)) => x == y,
+    (Value::Prp, Value::Prp) => true,
+    (Value::Lit(x), Value::Lit(y)) => x == y,
+    (Value::LTy(x), Value::LTy(y)) => x == y,
+    (Value::Var(_, x), Value::Var(_, y)) => x == y,
+    (Value::Free(_, x), Value::Free(_, y)) => x == y,
+    (Value::Ref(_, x, _), Value::Ref(_, y, _)) => x == y,
+    (Value::Lam(nam, x), Value::Lam(_, y)) => {
+      unify(defs, metas, &under(None), &bind(nam, x), &bind(nam, y))
+    }
+    // Eta conversion
+    (Value::Lam(nam, x), _) => {
+      let var = Value::Var(nam.clone(), depth);
+      let b = apply(defs, b.clone(), var);
+      unify(defs, metas, &under(None), &bind(nam, x), &b)
+    }
+    (_, Value::Lam(nam, y)) => {
+      let var = Value::Var(nam.clone(), depth);
+      let a = apply(defs, a.clone(), var);
+      unify(defs, metas, &under(None), &a, &bind(nam, y))
+    }
+    (Value::Par(fx, sx), Value::Par(fy, sy)) => {
+      unify(defs, metas, types, fx, fy) && unify(defs, metas, types, sx, sy)
+    }
+    // Eta conversion for pairs
+    (Value::Par(fx, sx), _) => {
+      unify(defs, metas, types, fx, &nbe::fst(defs, b.clone()))
+        && unify(defs, metas, types, sx, &nbe::snd(defs, b.clone()))
+    }
+    (_, Value::Par(fy, sy)) => {
+      unify(defs, metas, types, &nbe::fst(defs, a.clone()), fy)
+        && unify(defs, metas, types, &nbe::snd(defs, a.clone()), sy)
+    }
+    (Value::All(ux, nam, dx, ix), Value::All(uy, _, dy, iy)) => {
+      let types2 = under(Some((**dx).clone()));
+      ux == uy
+        && unify(defs, metas, types, dx, dy)
+        && unify(defs, metas, &types2, &bind(nam, ix), &bind(nam, iy))
+    }
+    (Value::Sig(nam, dx, ix), Value::Sig(_, dy, iy)) => {
+      let types2 = under(Some((**dx).clone()));
+      unify(defs, metas, types, dx, dy)
+        && unify(defs, metas, &types2, &bind(nam, ix), &bind(nam, iy))
+    }
+    (Value::Slf(nam, x), Value::Slf(_, y)) => {
+      let types2 = under(Some(a.clone()));
+      unify(defs, metas, &types2, &bind(nam, x), &bind(nam, y))
+    }
+    (Value::Dat(x), Value::Dat(y)) => unify(defs, metas, types, x, y),
+    (Value::Cse(x), Value::Cse(y)) => unify(defs, metas, types, x, y),
+    (Value::Fst(x), Value::Fst(y)) => unify(defs, metas, types, x, y),
+    (Value::Snd(x), Value::Snd(y)) => unify(defs, metas, types, x, y),
+    (Value::Opr(ox, xs), Value::Opr(oy, ys)) => {
+      ox == oy
+        && xs.len() == ys.len()
+        && xs
+          .iter()
+          .zip(ys.iter())
+          .all(|(x, y)| unify(defs, metas, types, x, y))
+    }
+    (Value::App(..), Value::App(..)) => {
+      let ((hx, xs), (hy, ys)) = (spine(&a), spine(&b));
+      unify(defs, metas, types, &hx, &hy)
+        && unify_args(defs, metas, types, &hx, &xs, &ys)
+    }
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    parse::{
+      span::Span,
+      term::{
+        parse,
+        parse_expression,
+      },
+    },
+    term::Def,
+  };
+
+  // Evaluate a term under the bound variables `x0`, ..., `x(depth-1)`
+  fn value(metas: &mut Metas, depth: u64, input: &str) -> Value {
+    let nams: Vector<Name> = (0..depth)
+      .map(|idx| Name::from(format!("x{}", depth - idx - 1)))
+      .collect();
+    let expr = parse_expression(HashMap::new(), HashMap::new(), nams.clone());
+    let (_, term) = expr(Span::new(input)).unwrap();
+    let ctx = nams.iter().map(|nam| (nam.clone(), true)).collect();
+    let term = metas.insert(&ctx, &term);
+    let env = nams
+      .iter()
+      .enumerate()
+      .map(|(idx, nam)| Value::Var(nam.clone(), depth - idx as u64 - 1))
+      .collect();
+    eval(&HashMap::new(), env, &term)
+  }
+
+  // The types of `depth` variables, all unknown
+  fn scope(depth: u64) -> Types { (0..depth).map(|_| None).collect() }
+
+  #[test]
+  fn solves_patterns() {
+    let defs = HashMap::new();
+    let mut metas = Metas::new();
+    let a = value(&mut metas, 0, "∀ (x: #Natural) -> _");
+    let b = value(&mut metas, 0, "∀ (y: #Natural) -> #BitString");
+    assert!(unify(&defs, &mut metas, &scope(0), &a, &b));
+    let sol = quote(&defs, &metas, 0, metas.solution(0).unwrap());
+    assert_eq!(format!("{}", sol), "λ x => #BitString");
+    // The solution may only mention the variables the meta is applied to
+    let a = value(&mut metas, 0, "λ f => _");
+    let b = value(&mut metas, 0, "λ f => f");
+    assert!(unify(&defs, &mut metas, &scope(0), &a, &b));
+    assert_eq!(metas.unsolved().map(|meta| meta.arity), None);
+  }
+
+  #[test]
+  fn pair_eta() {
+    let defs = HashMap::new();
+    let mut metas = Metas::new();
+    let a = value(&mut metas, 0, "λ p => p");
+    let b = value(&mut metas, 0, "λ p => (fst p, snd p)");
+    assert!(unify(&defs, &mut metas, &scope(0), &a, &b));
+    let b = value(&mut metas, 0, "λ p => (snd p, fst p)");
+    assert!(!unify(&defs, &mut metas, &scope(0), &a, &b));
+  }
+
+  #[test]
+  fn irrelevance() {
+    let defs = HashMap::new();
+    let mut metas = Metas::new();
+    // x0: Prop, x1: ∀ (p: x0) -> #Natural, and x2, x3: x0
+    let mut types = Types::new();
+    types.push_back(Some(Value::Prp));
+    types.push_back(Some(value(&mut metas, 1, "∀ (p: x0) -> #Natural")));
+    types.push_back(Some(value(&mut metas, 2, "x0")));
+    types.push_back(Some(value(&mut metas, 3, "x0")));
+    let prop = value(&mut metas, 4, "∀ (A: Type) (x: A) -> x0");
+    assert!(is_prop(&defs, &metas, &types, &prop));
+    let sig = value(&mut metas, 4, "Σ (p: x0) -> #Natural");
+    assert!(!is_prop(&defs, &metas, &types, &sig));
+    let a = value(&mut metas, 4, "x1 x2");
+    let b = value(&mut metas, 4, "x1 x3");
+    assert!(unify(&defs, &mut metas, &types, &a, &b));
+    // Arguments are compared when the type of their head is unknown, or when
+    // they are not proofs
+    assert!(!unify(&defs, &mut metas, &scope(4), &a, &b));
+    types[0] = Some(Value::Typ(0));
+    assert!(!unify(&defs, &mut metas, &types, &a, &b));
+  }
+
+  // Add the definition `one : #Natural = 1`, returning a reference to it
+  fn define_one(defs: &mut Defs, transparency: Transparency) -> Value {
+    let (_, typ) = parse("#Natural").unwrap();
+    let (_, term) = parse("1").unwrap();
+    let nam = Name::from("one");
+    let def = Def::new(None, nam.clone(), String::new(), typ, term);
+    let def = Def { transparency, ..def };
+    let (d, _, a) = def.clone().embed();
+    let (def_link, ast_link) = (d.encode().link(), a.encode().link());
+    defs.insert(def_link, def);
+    Value::Ref(nam, def_link, ast_link)
+  }
+
+  #[test]
+  fn transparency() {
+    let mut defs = HashMap::new();
+    let mut metas = Metas::new();
+    let lit = value(&mut metas, 0, "1");
+    let one = define_one(&mut defs, Transparency::Transparent);
+    assert!(unify(&defs, &mut metas, &scope(0), &one, &lit));
+    assert_eq!(metas.fuel.get(), FUEL - 1);
+    metas.fuel.set(0);
+    assert!(!unify(&defs, &mut metas, &scope(0), &one, &lit));
+    assert!(unify(&defs, &mut metas, &scope(0), &one, &one));
+    let one = define_one(&mut defs, Transparency::Reducible);
+    assert!(unify(&defs, &mut metas, &scope(0), &one, &lit));
+    metas.fuel.set(FUEL);
+    let one = define_one(&mut defs, Transparency::Opaque);
+    assert!(!unify(&defs, &mut metas, &scope(0), &one, &lit));
+    assert!(unify(&defs, &mut metas, &scope(0), &one, &one));
+    assert_eq!(metas.fuel.get(), FUEL);
+  }
+
+  #[test]
+  fn rejects() {
+    let defs = HashMap::new();
+    let mut metas = Metas::new();
+    // Occurs check
+    let a = value(&mut metas, 0, "_");
+    let b = Value::Dat(Rc::new(Value::Meta(0)));
+    assert!(!unify(&defs, &mut metas, &scope(0), &a, &b));
+    // Scope escape: the metavariable is not applied to the bound variable
+    let a = Value::Meta(metas.fresh(None, 0));
+    let b = value(&mut metas, 1, "x0");
+    assert!(!unify(&defs, &mut metas, &scope(1), &a, &b));
+    // Non-linear spines are outside of the pattern fragment
+    let a = value(&mut metas, 1, "_ x0");
+    assert!(!unify(&defs, &mut metas, &scope(1), &a, &Value::Typ(0)));
+    assert_eq!(metas.unsolved().map(|meta| meta.arity), Some(0));
+  }
+
+  #[test]
+  fn zonks() {
+    let defs = HashMap::new();
+    let mut metas = Metas::new();
+    let (_, term) = parse("λ x => _").unwrap();
+    let term = metas.insert(&Vector::new(), &term);
+    assert_eq!(format!("{}", term), "λ x => ?0 x");
+    let a = value(&mut metas, 1, "_");
+    let b = value(&mut metas, 1, "#add x0 1");
+    // `?1 x0 = #add x0 1`, `?0 x = ?1 x`
+    assert!(unify(&defs, &mut metas, &scope(1), &a, &b));
+    let x = Value::Var(Name::from("x"), 0);
+    let lhs = Value::App(Rc::new(Value::Meta(0)), Rc::new(x.clone()));
+    let rhs = Value::App(Rc::new(Value::Meta(1)), Rc::new(x));
+    assert!(unify(&defs, &mut metas, &scope(1), &lhs, &rhs));
+    assert_eq!(format!("{}", zonk(&defs, &metas, &term)), "λ x => #add x 1");
+  }
+}
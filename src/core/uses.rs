@@ -9,6 +9,7 @@ use hashexpr::{
   Expr,
 };
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Uses {
   None,
   Affi,
@@ -1,5 +1,7 @@
 use hashexpr::position::Pos;
 
+use std::fmt;
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Expected {
   Uses,
@@ -19,6 +21,7 @@ pub enum Expected {
   Case,
   Data,
   Constructor,
+  Term,
   TermDef,
   TermDefContents,
   Definition,
@@ -37,6 +40,9 @@ pub enum Expected {
   AnonTermAtom,
   AnonTermVariU64,
   MetaTerm,
+  Certificate,
+  Transparency,
+  Positions,
 }
 
 #[derive(PartialEq, Clone, Debug)]
@@ -92,6 +98,20 @@ impl DecodeError {
     }
   }
 }
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let expected: Vec<String> =
+      self.expected.iter().map(|e| format!("{:?}", e)).collect();
+    write!(f, "Expected {}", expected.join(" or "))?;
+    if let Some(pos) = self.position {
+      write!(f, " at {}:{}", pos.from_line, pos.from_column)?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for DecodeError {}
+
 pub fn or_else_join<A>(
   a: Result<A, DecodeError>,
   b: Result<A, DecodeError>,
@@ -18,11 +18,56 @@ use hashexpr::{
   Link,
 };
 
+use std::fmt;
+
+// How freely the type checker unfolds a definition when comparing types. An
+// opaque definition is never unfolded, so that it is only convertible with
+// itself. A transparent one is unfolded as needed, each time using up some
+// of the checker's fuel, and a reducible one is unfolded for free, which
+// suits small definitions such as type aliases. Transparencies are ordered
+// from the most opaque to the most transparent.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transparency {
+  Opaque,
+  Transparent,
+  Reducible,
+}
+
+impl Default for Transparency {
+  fn default() -> Self { Self::Transparent }
+}
+
+impl Transparency {
+  pub fn encode(self) -> Expr { text!(format!("{}", self)) }
+
+  pub fn decode(expr: Expr) -> Result<Self, DecodeError> {
+    match &expr {
+      Atom(_, Text(t)) if t == "opaque" => Ok(Self::Opaque),
+      Atom(_, Text(t)) if t == "transparent" => Ok(Self::Transparent),
+      Atom(_, Text(t)) if t == "reducible" => Ok(Self::Reducible),
+      _ => Err(DecodeError::new(expr.position(), vec![Expected::Transparency])),
+    }
+  }
+}
+
+impl fmt::Display for Transparency {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Opaque => write!(f, "opaque"),
+      Self::Transparent => write!(f, "transparent"),
+      Self::Reducible => write!(f, "reducible"),
+    }
+  }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct Definition {
   pub name: String,
   pub pos: Option<Pos>,
   pub docs: String,
+  pub transparency: Transparency,
+  pub implicits: Vec<bool>,
   pub type_anon: Link,
   pub term_anon: Link,
   pub type_meta: MetaTerm,
@@ -34,40 +79,67 @@ impl Definition {
     name: String,
     pos: Option<Pos>,
     docs: String,
+    transparency: Transparency,
+    implicits: Vec<bool>,
     type_anon: Link,
     term_anon: Link,
     type_meta: MetaTerm,
     term_meta: MetaTerm,
   ) -> Self {
-    Definition { name, pos, docs, type_anon, term_anon, type_meta, term_meta }
+    Definition {
+      name,
+      pos,
+      docs,
+      transparency,
+      implicits,
+      type_anon,
+      term_anon,
+      type_meta,
+      term_meta,
+    }
   }
 
+  // The implicit binders are only encoded when there are any, so that the
+  // encoding, and so the link, of a definition without them is unchanged
   pub fn encode(self) -> Expr {
-    cons!(
-      self.pos,
+    let mut xs = vec![
       text!("def"),
       text!(self.name),
       text!(self.docs),
+      self.transparency.encode(),
       link!(self.type_anon),
       link!(self.term_anon),
       self.type_meta.encode(),
-      self.term_meta.encode()
-    )
+      self.term_meta.encode(),
+    ];
+    if !self.implicits.is_empty() {
+      xs.push(bits!(self.implicits.iter().map(|i| *i as u8).collect()));
+    }
+    Cons(self.pos, xs)
   }
 
   pub fn decode(expr: Expr) -> Result<Self, DecodeError> {
     match expr {
       Cons(pos, xs) => match xs.as_slice() {
         [Atom(_, Text(c)), tail @ ..] if *c == String::from("def") => {
+          let (tail, implicits) = match tail {
+            [init @ .., Atom(_, Bits(is))] if tail.len() == 8 => {
+              (init, is.iter().map(|i| *i != 0).collect())
+            }
+            _ => (tail, Vec::new()),
+          };
           match tail {
-            [Atom(_, Text(n)), Atom(_, Text(d)), Atom(_, Link(t)), Atom(_, Link(x)), tm, xm] =>
+            [Atom(_, Text(n)), Atom(_, Text(d)), r, Atom(_, Link(t)), Atom(_, Link(x)), tm, xm] =>
             {
+              let transparency = Transparency::decode(r.to_owned())?;
               let type_meta = MetaTerm::decode(tm.to_owned())?;
               let term_meta = MetaTerm::decode(xm.to_owned())?;
               Ok(Definition {
                 name: n.to_owned(),
                 pos,
                 docs: d.to_owned(),
+                transparency,
+                implicits,
                 type_anon: *t,
                 term_anon: *x,
                 type_meta,
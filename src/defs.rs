@@ -143,7 +143,7 @@ pub mod tests {
       None,
       Uses::Many,
       String::from("A"),
-      Box::new(Typ(None)),
+      Box::new(Typ(None, 0)),
       Box::new(Var(None, String::from("A"), 0)),
     );
     let term =
@@ -0,0 +1,403 @@
+use crate::{
+  hashspace::inspect::{
+    inspect,
+    Content,
+  },
+  json::Json,
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Def,
+    Link,
+    Term,
+  },
+  unembed_error::UnembedError,
+};
+
+use std::fmt;
+
+// Diffs between stored packages, or definitions, by their links. As
+// everything is addressed by its contents, two versions of a package differ
+// exactly in the declarations whose links differ, which are found by name
+// without comparing any terms. A definition which changed is compared term
+// by term, down to the smallest subterms which differ, and a definition
+// which only refers to a changed one differs from its old version in the
+// link of that reference alone.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiffError {
+  Unembed(UnembedError),
+  // Two links which are not both of packages or both of definitions, by
+  // what they are of
+  Incomparable(&'static str, &'static str),
+}
+
+impl fmt::Display for DiffError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Unembed(e) => write!(f, "{}", e),
+      Self::Incomparable(a, b) => {
+        write!(f, "Cannot compare a {} to a {}", a, b)
+      }
+    }
+  }
+}
+
+impl std::error::Error for DiffError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Unembed(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<UnembedError> for DiffError {
+  fn from(e: UnembedError) -> Self { Self::Unembed(e) }
+}
+
+// The smallest subterms in which a term differs from another, as pairs of
+// the old subterm and the new
+pub type TermDiff = Vec<(Term, Term)>;
+
+pub fn term_diff(old: &Term, new: &Term) -> TermDiff {
+  fn go(old: &Term, new: &Term, diff: &mut TermDiff) {
+    if old == new {
+      return;
+    }
+    match (old, new) {
+      (Term::Lam(_, na, ba), Term::Lam(_, nb, bb))
+      | (Term::Slf(_, na, ba), Term::Slf(_, nb, bb))
+        if na == nb =>
+      {
+        go(ba, bb, diff)
+      }
+      (Term::Dat(_, ba), Term::Dat(_, bb))
+      | (Term::Cse(_, ba), Term::Cse(_, bb))
+      | (Term::Fst(_, ba), Term::Fst(_, bb))
+      | (Term::Snd(_, ba), Term::Snd(_, bb)) => go(ba, bb, diff),
+      (Term::App(_, ta), Term::App(_, tb))
+      | (Term::Par(_, ta), Term::Par(_, tb))
+      | (Term::Ann(_, ta), Term::Ann(_, tb)) => {
+        go(&ta.0, &tb.0, diff);
+        go(&ta.1, &tb.1, diff);
+      }
+      (Term::Sig(_, na, ta), Term::Sig(_, nb, tb)) if na == nb => {
+        go(&ta.0, &tb.0, diff);
+        go(&ta.1, &tb.1, diff);
+      }
+      (Term::All(_, ua, na, ta), Term::All(_, ub, nb, tb))
+        if ua == ub && na == nb =>
+      {
+        go(&ta.0, &tb.0, diff);
+        go(&ta.1, &tb.1, diff);
+      }
+      (Term::Let(_, ra, ua, na, ta), Term::Let(_, rb, ub, nb, tb))
+        if ra == rb && ua == ub && na == nb =>
+      {
+        go(&ta.0, &tb.0, diff);
+        go(&ta.1, &tb.1, diff);
+        go(&ta.2, &tb.2, diff);
+      }
+      _ => diff.push((old.clone(), new.clone())),
+    }
+  }
+  let mut diff = Vec::new();
+  go(old, new, &mut diff);
+  diff
+}
+
+// How the type and term of a definition differ from another's
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefDiff {
+  pub typ: TermDiff,
+  pub term: TermDiff,
+}
+
+pub fn def_diff(old: &Def, new: &Def) -> DefDiff {
+  DefDiff {
+    typ: term_diff(&old.typ_, &new.typ_),
+    term: term_diff(&old.term, &new.term),
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+  Added(Link),
+  Removed(Link),
+  Changed(Link, Link),
+}
+
+// A declaration which differs between two packages, by its kind and name,
+// with how its definition differs if it is a changed one and that was asked
+// for
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+  pub kind: &'static str,
+  pub name: String,
+  pub status: Status,
+  pub diff: Option<DefDiff>,
+}
+
+// A declaration, by its kind, name and link
+type Decl<'a> = (&'static str, &'a str, Link);
+
+fn decls(package: &Package) -> Vec<Decl> {
+  package
+    .decls
+    .iter()
+    .map(|decl| match decl {
+      Declaration::Defn { name, defn, .. } => ("def", name.as_str(), *defn),
+      Declaration::Open { name, from, .. } => ("open", name.as_str(), *from),
+      Declaration::Test { name, test, .. } => ("test", name.as_str(), *test),
+    })
+    .collect()
+}
+
+// The declarations which differ between two packages, in the order they
+// are declared in the new one, followed by those it removed, with how
+// changed definitions and tests differ if `terms`
+pub fn package_diff(
+  old: &Package,
+  new: &Package,
+  terms: bool,
+) -> Result<Vec<Change>, UnembedError> {
+  let (olds, news) = (decls(old), decls(new));
+  let find = |decls: &[Decl], kind: &str, name: &str| {
+    decls.iter().find(|(k, n, _)| *k == kind && *n == name).map(|d| d.2)
+  };
+  let mut changes = Vec::new();
+  for (kind, name, link) in &news {
+    let status = match find(&olds, *kind, *name) {
+      None => Status::Added(*link),
+      Some(old) if old != *link => Status::Changed(old, *link),
+      Some(_) => continue,
+    };
+    let diff = match (&status, *kind) {
+      (Status::Changed(a, b), "def") | (Status::Changed(a, b), "test")
+        if terms =>
+      {
+        Some(def_diff(&Def::get_link(*a)?, &Def::get_link(*b)?))
+      }
+      _ => None,
+    };
+    let name = String::from(*name);
+    changes.push(Change { kind: *kind, name, status, diff });
+  }
+  for (kind, name, link) in &olds {
+    if find(&news, *kind, *name).is_none() {
+      let (name, status) = (String::from(*name), Status::Removed(*link));
+      changes.push(Change { kind: *kind, name, status, diff: None });
+    }
+  }
+  Ok(changes)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diff {
+  Packages(Vec<Change>),
+  Defs(DefDiff),
+}
+
+// The diff between what is stored at two links, which are either both of
+// packages or both of definitions
+pub fn diff(old: Link, new: Link, terms: bool) -> Result<Diff, DiffError> {
+  match (inspect(old)?, inspect(new)?) {
+    (Content::Package(a), Content::Package(b)) => {
+      Ok(Diff::Packages(package_diff(&a, &b, terms)?))
+    }
+    (Content::Def(a), Content::Def(b)) => Ok(Diff::Defs(def_diff(&a, &b))),
+    (a, b) => Err(DiffError::Incomparable(a.kind(), b.kind())),
+  }
+}
+
+// A pair of subterms, with the links of references which differ only by
+// link
+fn subterms(old: &Term, new: &Term) -> (String, String) {
+  match (old, new) {
+    (Term::Ref(_, na, da, _), Term::Ref(_, nb, db, _)) if na == nb => {
+      (format!("{} {}", na, da), format!("{} {}", nb, db))
+    }
+    _ => (format!("{}", old), format!("{}", new)),
+  }
+}
+
+impl fmt::Display for DefDiff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (part, diff) in &[("type", &self.typ), ("term", &self.term)] {
+      if !diff.is_empty() {
+        writeln!(f, "  {}:", part)?;
+      }
+      for (old, new) in diff.iter() {
+        let (old, new) = subterms(old, new);
+        writeln!(f, "  - {}\n  + {}", old, new)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Change {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.status {
+      Status::Added(link) => {
+        write!(f, "+ {} {} {}", self.kind, self.name, link)
+      }
+      Status::Removed(link) => {
+        write!(f, "- {} {} {}", self.kind, self.name, link)
+      }
+      Status::Changed(old, new) => {
+        write!(f, "~ {} {} {} -> {}", self.kind, self.name, old, new)
+      }
+    }?;
+    if let Some(diff) = &self.diff {
+      write!(f, "\n{}", diff.to_string().trim_end())?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Diff {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Packages(changes) if changes.is_empty() => {
+        write!(f, "No changes")
+      }
+      Self::Packages(changes) => {
+        let lines: Vec<String> =
+          changes.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+      }
+      Self::Defs(diff) if diff.typ.is_empty() && diff.term.is_empty() => {
+        write!(f, "No changes")
+      }
+      Self::Defs(diff) => write!(f, "{}", diff.to_string().trim_end()),
+    }
+  }
+}
+
+fn term_diff_json(diff: &TermDiff) -> Json {
+  Json::Arr(
+    diff
+      .iter()
+      .map(|(old, new)| {
+        let (old, new) = subterms(old, new);
+        Json::obj(vec![("old", old.into()), ("new", new.into())])
+      })
+      .collect(),
+  )
+}
+
+fn def_diff_json(diff: &DefDiff) -> Vec<(&'static str, Json)> {
+  vec![
+    ("type", term_diff_json(&diff.typ)),
+    ("term", term_diff_json(&diff.term)),
+  ]
+}
+
+pub fn json(diff: &Diff) -> Json {
+  match diff {
+    Diff::Packages(changes) => {
+      let changes = changes
+        .iter()
+        .map(|change| {
+          let mut fields = vec![
+            ("kind", change.kind.into()),
+            ("name", change.name.as_str().into()),
+          ];
+          match change.status {
+            Status::Added(link) => {
+              fields.push(("status", "added".into()));
+              fields.push(("new", Json::str(link)));
+            }
+            Status::Removed(link) => {
+              fields.push(("status", "removed".into()));
+              fields.push(("old", Json::str(link)));
+            }
+            Status::Changed(old, new) => {
+              fields.push(("status", "changed".into()));
+              fields.push(("old", Json::str(old)));
+              fields.push(("new", Json::str(new)));
+            }
+          }
+          if let Some(diff) = &change.diff {
+            fields.extend(def_diff_json(diff));
+          }
+          Json::obj(fields)
+        })
+        .collect();
+      Json::obj(vec![("changes", Json::Arr(changes))])
+    }
+    Diff::Defs(diff) => Json::obj(def_diff_json(diff)),
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::query::Database;
+  use std::{
+    env,
+    fs,
+  };
+
+  #[test]
+  fn diffs() {
+    // The two versions of the package, each in a directory of its own
+    let parse = |version: &str, source: &str| {
+      let dir = env::temp_dir().join("yatima-diff").join(version);
+      fs::create_dir_all(&dir).unwrap();
+      let path = dir.join("Diff.ya");
+      fs::write(&path, source).unwrap();
+      Database::new().parse(&path).unwrap()
+    };
+    let old = parse(
+      "old",
+      "package Diff where\n\
+       def one : #Natural = 1\n\
+       def two : #Natural = #add one one\n\
+       def id (A: Type) (x: A) : A = x\n\
+       def gone : #Natural = 0\n",
+    );
+    let new = parse(
+      "new",
+      "package Diff where\n\
+       def one : #Natural = 2\n\
+       def two : #Natural = #add one one\n\
+       def id (A: Type) (x: A) : A = x\n\
+       def three : #Natural = 3\n",
+    );
+    let link = |parsed: &crate::query::Parsed, name: &str| parsed.refs[name].0;
+    let changes = package_diff(&old.package, &new.package, true).unwrap();
+    let summary: Vec<(&str, &Status)> =
+      changes.iter().map(|c| (c.name.as_str(), &c.status)).collect();
+    assert_eq!(summary, vec![
+      ("one", &Status::Changed(link(&old, "one"), link(&new, "one"))),
+      ("two", &Status::Changed(link(&old, "two"), link(&new, "two"))),
+      ("three", &Status::Added(link(&new, "three"))),
+      ("gone", &Status::Removed(link(&old, "gone"))),
+    ]);
+    let one = changes[0].diff.as_ref().unwrap();
+    assert!(one.typ.is_empty());
+    assert_eq!(one.term.len(), 1);
+    assert_eq!(format!("{}", one), "  term:\n  - 1\n  + 2\n");
+    // `two` only changed in the link of the `one` it refers to
+    let two = changes[1].diff.as_ref().unwrap();
+    assert_eq!(two.term.len(), 2);
+    let (a, b) = (link(&old, "one"), link(&new, "one"));
+    let refs = format!("  - one {}\n  + one {}\n", a, b);
+    assert!(format!("{}", two).contains(&refs));
+    let same = diff(link(&old, "id"), link(&new, "id"), false).unwrap();
+    assert_eq!(format!("{}", same), "No changes");
+    assert_eq!(
+      diff(old.link, link(&new, "id"), false),
+      Err(DiffError::Incomparable("package", "definition"))
+    );
+    let json = json(&diff(old.link, new.link, false).unwrap());
+    assert!(format!("{}", json).starts_with(
+      "{\"changes\":[{\"kind\":\"def\",\"name\":\"one\",\"status\":\"changed\""
+    ));
+  }
+}
@@ -0,0 +1,328 @@
+use crate::{
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Def,
+    Link,
+    Term,
+  },
+  unembed_error::UnembedError,
+};
+
+use im::HashMap;
+
+use std::{
+  fmt,
+  str::FromStr,
+};
+
+// Documentation generation. Documentation is generated from a stored
+// package, by its link, so that a library published by link alone can be
+// browsed: a page for the package and, optionally, one for each package it
+// opens, transitively, with an entry for each of their definitions. An entry
+// gives the definition's type, its documentation, which is taken from the
+// comments just before its declaration, and its link and position in the
+// source. References to definitions are resolved by link, to their entries
+// when they are documented.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+  Markdown,
+  Html,
+}
+
+impl Format {
+  // The extension of the files of pages
+  pub fn extension(&self) -> &'static str {
+    match self {
+      Self::Markdown => "md",
+      Self::Html => "html",
+    }
+  }
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "markdown" | "md" => Ok(Self::Markdown),
+      "html" => Ok(Self::Html),
+      _ => Err(format!("Unknown documentation format {}", s)),
+    }
+  }
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Markdown => write!(f, "markdown"),
+      Self::Html => write!(f, "html"),
+    }
+  }
+}
+
+// The documentation of a package
+#[derive(Clone, Debug)]
+pub struct Page {
+  pub link: Link,
+  pub package: Package,
+  // Each definition the package declares, with its link
+  pub entries: Vec<(Link, Def)>,
+}
+
+impl Page {
+  // The name of the file of the page
+  pub fn file(&self, format: Format) -> String {
+    format!("{}.{}", self.package.name, format.extension())
+  }
+}
+
+// The pages of the package at `link` and, if `imports`, of the packages it
+// opens, in the order they are opened
+pub fn pages(link: Link, imports: bool) -> Result<Vec<Page>, UnembedError> {
+  fn go(
+    link: Link,
+    imports: bool,
+    pages: &mut Vec<Page>,
+  ) -> Result<(), UnembedError> {
+    if pages.iter().any(|page| page.link == link) {
+      return Ok(());
+    }
+    let package = Package::get_link(link)?;
+    let mut entries = Vec::new();
+    let mut opens = Vec::new();
+    for decl in &package.decls {
+      match decl {
+        Declaration::Defn { defn, .. } => {
+          entries.push((*defn, Def::get_link(*defn)?));
+        }
+        Declaration::Open { from, .. } => opens.push(*from),
+        Declaration::Test { .. } => (),
+      }
+    }
+    pages.push(Page { link, package, entries });
+    if imports {
+      for from in opens {
+        go(from, imports, pages)?;
+      }
+    }
+    Ok(())
+  }
+  let mut pages = Vec::new();
+  go(link, imports, &mut pages)?;
+  Ok(pages)
+}
+
+// The definitions a term refers to, each once, in the order they first occur
+pub fn references(term: &Term) -> Vec<(String, Link)> {
+  fn go(term: &Term, refs: &mut Vec<(String, Link)>) {
+    match term {
+      Term::Ref(_, nam, def_link, _) => {
+        if !refs.iter().any(|(_, link)| link == def_link) {
+          refs.push((nam.to_string(), *def_link));
+        }
+      }
+      Term::Lam(_, _, bod)
+      | Term::Slf(_, _, bod)
+      | Term::Dat(_, bod)
+      | Term::Cse(_, bod)
+      | Term::Fst(_, bod)
+      | Term::Snd(_, bod) => go(bod, refs),
+      Term::App(_, terms)
+      | Term::All(_, _, _, terms)
+      | Term::Sig(_, _, terms)
+      | Term::Par(_, terms)
+      | Term::Ann(_, terms) => {
+        go(&terms.0, refs);
+        go(&terms.1, refs);
+      }
+      Term::Let(_, _, _, _, terms) => {
+        go(&terms.0, refs);
+        go(&terms.1, refs);
+        go(&terms.2, refs);
+      }
+      _ => (),
+    }
+  }
+  let mut refs = Vec::new();
+  go(term, &mut refs);
+  refs
+}
+
+fn escape(s: &str) -> String {
+  s.replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+// The signature of a definition, as it is declared
+fn signature(def: &Def) -> String {
+  let mut sig = Def { docs: String::new(), ..def.clone() };
+  sig.term = Term::Hol(None, None);
+  let sig = format!("{}", sig);
+  String::from(sig.trim_end_matches(" = _"))
+}
+
+// Render the pages of documentation, returning the name and contents of the
+// file of each. A reference to a documented definition is a hyperlink to
+// its entry, by the definition's link.
+pub fn render(pages: &[Page], format: Format) -> Vec<(String, String)> {
+  let mut index: HashMap<Link, String> = HashMap::new();
+  for page in pages {
+    for (link, _) in &page.entries {
+      index.insert(*link, page.file(format));
+    }
+  }
+  let target = |page: &Page, link: &Link| {
+    index.get(link).map(|file| {
+      if *file == page.file(format) {
+        format!("#{}", link)
+      }
+      else {
+        format!("{}#{}", file, link)
+      }
+    })
+  };
+  pages
+    .iter()
+    .map(|page| {
+      let contents = match format {
+        Format::Markdown => markdown(page, &|link| target(page, link)),
+        Format::Html => html(page, &|link| target(page, link)),
+      };
+      (page.file(format), contents)
+    })
+    .collect()
+}
+
+fn markdown(page: &Page, target: &dyn Fn(&Link) -> Option<String>) -> String {
+  let mut out = format!("# Package {}\n\n", page.package.name);
+  if !page.package.docs.is_empty() {
+    out.push_str(&format!("{}\n\n", page.package.docs));
+  }
+  out.push_str(&format!("Link: `{}`\n\n", page.link));
+  out.push_str(&format!("Source: `{}`\n\n", page.package.source));
+  for (link, def) in &page.entries {
+    out.push_str(&format!("<a id=\"{}\"></a>\n\n### {}\n\n", link, def.name));
+    out.push_str(&format!("```\n{}\n```\n\n", signature(def)));
+    if !def.docs.is_empty() {
+      out.push_str(&format!("{}\n\n", def.docs));
+    }
+    out.push_str(&format!("Link: `{}`", link));
+    if let Some(pos) = def.pos {
+      out.push_str(&format!(", line {}", pos.from_line));
+    }
+    out.push_str("\n\n");
+    let refs: Vec<String> = references(&def.typ_)
+      .into_iter()
+      .map(|(nam, link)| match target(&link) {
+        Some(target) => format!("[{}]({})", nam, target),
+        None => format!("`{}`", nam),
+      })
+      .collect();
+    if !refs.is_empty() {
+      out.push_str(&format!("References: {}\n\n", refs.join(", ")));
+    }
+  }
+  out
+}
+
+fn html(page: &Page, target: &dyn Fn(&Link) -> Option<String>) -> String {
+  let name = escape(&page.package.name);
+  let mut out = format!(
+    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+     <title>{}</title>\n</head>\n<body>\n<h1>Package {}</h1>\n",
+    name, name
+  );
+  if !page.package.docs.is_empty() {
+    out.push_str(&format!("<p>{}</p>\n", escape(&page.package.docs)));
+  }
+  out.push_str(&format!("<p>Link: <code>{}</code></p>\n", page.link));
+  out.push_str(&format!(
+    "<p>Source: <code>{}</code></p>\n",
+    page.package.source
+  ));
+  for (link, def) in &page.entries {
+    out.push_str(&format!("<h3 id=\"{}\">{}</h3>\n", link, escape(&def.name)));
+    out.push_str(&format!("<pre>{}</pre>\n", escape(&signature(def))));
+    if !def.docs.is_empty() {
+      out.push_str(&format!("<p>{}</p>\n", escape(&def.docs)));
+    }
+    out.push_str(&format!("<p>Link: <code>{}</code>", link));
+    if let Some(pos) = def.pos {
+      out.push_str(&format!(", line {}", pos.from_line));
+    }
+    out.push_str("</p>\n");
+    let refs: Vec<String> = references(&def.typ_)
+      .into_iter()
+      .map(|(nam, link)| match target(&link) {
+        Some(target) => format!("<a href=\"{}\">{}</a>", target, escape(&nam)),
+        None => format!("<code>{}</code>", escape(&nam)),
+      })
+      .collect();
+    if !refs.is_empty() {
+      out.push_str(&format!("<p>References: {}</p>\n", refs.join(", ")));
+    }
+  }
+  out.push_str("</body>\n</html>\n");
+  out
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::query::Database;
+  use std::{
+    env,
+    fs,
+  };
+
+  #[test]
+  fn documents() {
+    let dir = env::temp_dir().join("yatima-doc");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+      dir.join("DocLib.ya"),
+      "// Numbers\npackage DocLib where\n\
+       // The number one\n\
+       def one : #Natural = 1\n",
+    )
+    .unwrap();
+    let path = dir.join("Doc.ya");
+    fs::write(
+      &path,
+      "package Doc where\nopen DocLib\n\
+       def Num : Type = #Natural\n\
+       // The number one, again\n\
+       // as a `Num`\n\
+       def uno : Num = one\n\
+       def same : Eq Num uno one = Eq.refl Num uno\n",
+    )
+    .unwrap();
+    let parsed = Database::new().parse(&path).unwrap();
+    assert_eq!(pages(parsed.link, false).unwrap().len(), 1);
+    let pages = pages(parsed.link, true).unwrap();
+    let names: Vec<&str> =
+      pages.iter().map(|page| page.package.name.as_str()).collect();
+    assert_eq!(names, vec!["Doc", "DocLib"]);
+    assert_eq!(pages[1].package.docs, "Numbers");
+    let (uno, def) = &pages[0].entries[1];
+    assert_eq!(def.docs, "The number one, again\nas a `Num`");
+    assert_eq!(signature(def), "def uno : Num");
+    let (one, _) = &pages[1].entries[0];
+    let files = render(&pages, Format::Markdown);
+    assert_eq!(files[0].0, "Doc.md");
+    let doc = &files[0].1;
+    assert!(doc.contains("The number one, again\nas a `Num`"));
+    assert!(doc.contains(&format!("[uno](#{})", uno)));
+    assert!(doc.contains(&format!("[one](DocLib.md#{})", one)));
+    assert!(doc.contains("`Eq`"));
+    let files = render(&pages, Format::Html);
+    assert_eq!(files[1].0, "DocLib.html");
+    assert!(files[1].1.contains(&format!("<h3 id=\"{}\">one</h3>", one)));
+  }
+}
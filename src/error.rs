@@ -0,0 +1,224 @@
+use crate::{
+  core::{
+    check::CheckError,
+    eval::EvalError,
+  },
+  decode_error::DecodeError,
+  hashspace::ipld::IpldError,
+  parse::{
+    error::{
+      ParseError,
+      ParseErrorKind,
+    },
+    span::Span,
+  },
+  unembed_error::UnembedError,
+};
+
+#[cfg(feature = "native")]
+use crate::hashspace::remote::RemoteError;
+
+use nom::Err;
+
+use std::{
+  error,
+  fmt,
+  io,
+};
+
+// The errors of the crate, as one type, for applications which use more than
+// one part of it. Each module keeps its own error type, which converts into
+// this one, so that `?` works across them, and is its source, so that the
+// whole chain of causes can be reported.
+
+#[derive(Debug)]
+pub enum YatimaError {
+  // A source which does not parse, the line and column it does not parse
+  // at, if known, and why, or no reason if it ends too soon
+  Parse(Option<(u32, usize)>, Vec<ParseErrorKind>),
+  // A term, definition or package which cannot be read from its embedding
+  Embed(UnembedError),
+  Hashspace(HashspaceError),
+  Eval(EvalError),
+  Check(CheckError),
+  Io(io::Error),
+}
+
+// An error in exchanging the contents of the hashspace
+#[derive(Clone, Debug, PartialEq)]
+pub enum HashspaceError {
+  Ipld(IpldError),
+  #[cfg(feature = "native")]
+  Remote(RemoteError),
+}
+
+impl fmt::Display for HashspaceError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Ipld(e) => write!(f, "{}", e),
+      #[cfg(feature = "native")]
+      Self::Remote(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl error::Error for HashspaceError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      Self::Ipld(e) => Some(e),
+      #[cfg(feature = "native")]
+      Self::Remote(e) => Some(e),
+    }
+  }
+}
+
+impl fmt::Display for YatimaError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Parse(_, errors) if errors.is_empty() => {
+        write!(f, "Incomplete input")
+      }
+      Self::Parse(at, errors) => {
+        if let Some((line, column)) = at {
+          write!(f, "{}:{}: ", line, column)?;
+        }
+        let errors: Vec<String> =
+          errors.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", errors.join(", "))
+      }
+      Self::Embed(e) => write!(f, "{}", e),
+      Self::Hashspace(e) => write!(f, "{}", e),
+      Self::Eval(e) => write!(f, "{}", e),
+      Self::Check(e) => write!(f, "{}", e),
+      Self::Io(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl error::Error for YatimaError {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match self {
+      // The first reason is the source, as the others are reported alike
+      Self::Parse(_, errors) => {
+        errors.first().map(|e| e as &(dyn error::Error + 'static))
+      }
+      Self::Embed(e) => Some(e),
+      Self::Hashspace(e) => Some(e),
+      Self::Eval(e) => Some(e),
+      Self::Check(e) => Some(e),
+      Self::Io(e) => Some(e),
+    }
+  }
+}
+
+impl From<ParseErrorKind> for YatimaError {
+  fn from(e: ParseErrorKind) -> Self { YatimaError::Parse(None, vec![e]) }
+}
+
+// The reasons of a parse error, but those which only say which parser
+// failed, as long as there are others
+impl<'a> From<ParseError<Span<'a>>> for YatimaError {
+  fn from(e: ParseError<Span<'a>>) -> Self {
+    let at = (e.input.location_line(), e.input.get_utf8_column());
+    let mut errors = e.errors;
+    if errors.iter().any(|e| !e.is_nom_err()) {
+      errors.retain(|e| !e.is_nom_err());
+    }
+    YatimaError::Parse(Some(at), errors)
+  }
+}
+
+impl<'a> From<Err<ParseError<Span<'a>>>> for YatimaError {
+  fn from(e: Err<ParseError<Span<'a>>>) -> Self {
+    match e {
+      Err::Incomplete(_) => YatimaError::Parse(None, Vec::new()),
+      Err::Error(e) | Err::Failure(e) => YatimaError::from(e),
+    }
+  }
+}
+
+impl From<UnembedError> for YatimaError {
+  fn from(e: UnembedError) -> Self { YatimaError::Embed(e) }
+}
+
+impl From<DecodeError> for YatimaError {
+  fn from(e: DecodeError) -> Self {
+    YatimaError::Embed(UnembedError::DecodeError(e))
+  }
+}
+
+impl From<HashspaceError> for YatimaError {
+  fn from(e: HashspaceError) -> Self { YatimaError::Hashspace(e) }
+}
+
+impl From<IpldError> for YatimaError {
+  fn from(e: IpldError) -> Self {
+    YatimaError::Hashspace(HashspaceError::Ipld(e))
+  }
+}
+
+#[cfg(feature = "native")]
+impl From<RemoteError> for YatimaError {
+  fn from(e: RemoteError) -> Self {
+    YatimaError::Hashspace(HashspaceError::Remote(e))
+  }
+}
+
+impl From<EvalError> for YatimaError {
+  fn from(e: EvalError) -> Self { YatimaError::Eval(e) }
+}
+
+impl From<CheckError> for YatimaError {
+  fn from(e: CheckError) -> Self { YatimaError::Check(e) }
+}
+
+impl From<io::Error> for YatimaError {
+  fn from(e: io::Error) -> Self { YatimaError::Io(e) }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      check::infer_term,
+      dag::DAG,
+      gas::{
+        self,
+        Gas,
+      },
+    },
+    parse::{
+      package::parse_input,
+      prelude::prelude,
+    },
+    term::Term,
+  };
+  use std::error::Error;
+
+  fn eval(input: &str, limit: u64) -> Result<Term, YatimaError> {
+    let (defs, refs) = prelude();
+    let term = parse_input(&defs, &refs, input).unwrap();
+    infer_term(&defs, &term)?;
+    let dag = gas::norm(&defs, DAG::from_term(term)?, &mut Gas::new(limit))?;
+    Ok(dag.to_term())
+  }
+
+  #[test]
+  fn chains_errors() {
+    let three = eval("#add 1 2", 100).map(|term| term.to_string());
+    assert_eq!(three.ok(), Some(String::from("3")));
+    let e = eval("#add 1 2", 0).unwrap_err();
+    assert!(matches!(e, YatimaError::Eval(EvalError::OutOfGas(0))));
+    assert_eq!(e.to_string(), EvalError::OutOfGas(0).to_string());
+    let e = YatimaError::from(ParseErrorKind::UnknownBaseCode);
+    let source = e.source().and_then(|e| e.downcast_ref::<ParseErrorKind>());
+    assert_eq!(source, Some(&ParseErrorKind::UnknownBaseCode));
+    let e = YatimaError::from(UnembedError::DecodeError(DecodeError::new(
+      None,
+      Vec::new(),
+    )));
+    let decode = e.source().and_then(|e| e.source());
+    assert!(decode.and_then(|e| e.downcast_ref::<DecodeError>()).is_some());
+  }
+}
@@ -0,0 +1,99 @@
+use std::{
+  fmt,
+  str::FromStr,
+};
+
+// The codes of errors, which stay the same across versions, so that they can
+// be looked up and searched for, and their explanations. Parse errors have
+// codes from Y0001 and type errors from Y0101, each kind of error its own.
+// The explanations are the sections of `codes.md`, each headed by its code
+// and title, followed by what the error means and how to fix it, with
+// examples.
+
+const CODES: &str = include_str!("explain/codes.md");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Code(pub u16);
+
+impl fmt::Display for Code {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Y{:04}", self.0)
+  }
+}
+
+impl FromStr for Code {
+  type Err = String;
+
+  // A code, with or without its `Y`, as `Y0007` or `7`
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let digits = s.strip_prefix(|c| c == 'Y' || c == 'y').unwrap_or(s);
+    digits.parse().map(Code).map_err(|_| format!("Invalid error code {}", s))
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Explanation {
+  pub code: Code,
+  pub title: &'static str,
+  pub text: &'static str,
+}
+
+impl fmt::Display for Explanation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}\n\n{}", self.code, self.title, self.text)
+  }
+}
+
+// The explanations of every code, in order
+pub fn explanations() -> Vec<Explanation> {
+  CODES
+    .split("\n## ")
+    .skip(1)
+    .filter_map(|section| {
+      let mut section = section.splitn(2, '\n');
+      let mut head = section.next()?.splitn(2, ' ');
+      let (code, title) = (head.next()?, head.next()?);
+      let text = section.next()?;
+      Some(Explanation { code: code.parse().ok()?, title, text: text.trim() })
+    })
+    .collect()
+}
+
+pub fn explain(code: Code) -> Option<Explanation> {
+  explanations().into_iter().find(|e| e.code == code)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::check::CheckErrorKind,
+    parse::error::ParseErrorKind,
+  };
+
+  #[test]
+  fn explains() {
+    let codes: Vec<Code> = explanations().iter().map(|e| e.code).collect();
+    let mut sorted = codes.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(codes, sorted);
+    // Each kind of error has its code, which has an explanation
+    for code in (1..=37).chain(101..=116) {
+      assert!(codes.contains(&Code(code)), "{} is unexplained", Code(code));
+    }
+    assert_eq!(codes.len(), 37 + 16);
+    assert_eq!(ParseErrorKind::UnknownBaseCode.code(), Code(6));
+    let kind = CheckErrorKind::ErasedVariable(String::from("x"));
+    assert_eq!(kind.code(), Code(102));
+    assert_eq!("Y0007".parse(), Ok(Code(7)));
+    let code = "y12".parse::<Code>().unwrap();
+    assert_eq!(code.to_string(), "Y0012");
+    let e = explain(Code(7)).unwrap();
+    assert_eq!(e.title, "Not a single character");
+    assert!(e.text.contains("def c : #Char = 'ab'"));
+    let head = "Y0007: Not a single character\n\nA character literal";
+    assert!(e.to_string().starts_with(head));
+    assert_eq!(explain(Code(38)), None);
+  }
+}
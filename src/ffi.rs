@@ -0,0 +1,403 @@
+#![allow(clippy::missing_safety_doc)]
+
+use crate::{
+  core::{
+    check::infer_term,
+    run::{
+      run,
+      Stats,
+      Strategy,
+    },
+  },
+  hashspace,
+  name::Name,
+  package::{
+    merge_defs,
+    merge_refs,
+    Package,
+  },
+  parse::{
+    package::{
+      parse_input,
+      parse_package,
+      PackageEnv,
+    },
+    prelude::prelude,
+    span::Span,
+  },
+  print::{
+    pretty,
+    Options,
+  },
+  term::{
+    Defs,
+    Link,
+    Refs,
+    Term,
+  },
+};
+
+use hashexpr::Expr;
+
+use nom::Err;
+
+use std::{
+  ffi::{
+    CStr,
+    CString,
+  },
+  os::raw::c_char,
+  panic::{
+    self,
+    AssertUnwindSafe,
+  },
+  path::PathBuf,
+  ptr,
+  slice,
+};
+
+// A C interface, for embedding Yatima in programs in other languages, as
+// declared in `include/yatima.h`. Packages are parsed into a scope, of the
+// definitions and the names in scope, in which expressions are parsed into
+// terms, which are normalized and checked, and expressions are put in and got
+// from the hashspace by their links.
+//
+// Scopes and terms are passed as opaque handles, made by the functions of
+// this module and freed by their `_free` functions. The strings and bytes
+// returned are owned by the caller, and are freed by `yatima_string_free` and
+// `yatima_bytes_free`. A function which fails returns null, and points its
+// `error` at a string saying why, unless `error` is null. Every handle passed
+// in must be one made here and not yet freed, every string must be UTF-8
+// ending in NUL, and a panic is caught and returned as an error rather than
+// unwound into the caller.
+
+pub struct YatimaScope {
+  pub defs: Defs,
+  pub refs: Refs,
+}
+
+pub struct YatimaTerm {
+  pub term: Term,
+}
+
+// A string for the caller, without the NULs C strings cannot have
+fn c_string(s: String) -> *mut c_char {
+  CString::new(s.replace('\0', "")).unwrap_or_default().into_raw()
+}
+
+unsafe fn text<'a>(s: *const c_char) -> Result<&'a str, String> {
+  if s.is_null() {
+    return Err(String::from("Null string"));
+  }
+  CStr::from_ptr(s).to_str().map_err(|e| format!("Invalid UTF-8: {}", e))
+}
+
+unsafe fn scope_ref<'a>(
+  scope: *const YatimaScope,
+) -> Result<&'a YatimaScope, String> {
+  scope.as_ref().ok_or_else(|| String::from("Null scope"))
+}
+
+unsafe fn term_ref<'a>(
+  term: *const YatimaTerm,
+) -> Result<&'a YatimaTerm, String> {
+  term.as_ref().ok_or_else(|| String::from("Null term"))
+}
+
+// Run `f`, catching a panic as an error, and point `error` at the error if
+// there is one
+unsafe fn guard<T>(
+  error: *mut *mut c_char,
+  f: impl FnOnce() -> Result<T, String>,
+) -> Option<T> {
+  let res = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|e| {
+    let msg = match (e.downcast_ref::<String>(), e.downcast_ref::<&str>()) {
+      (Some(msg), _) => msg.clone(),
+      (_, Some(msg)) => String::from(*msg),
+      _ => String::from("Panicked"),
+    };
+    Err(msg)
+  });
+  match res {
+    Ok(x) => Some(x),
+    Err(e) => {
+      if !error.is_null() {
+        *error = c_string(e);
+      }
+      None
+    }
+  }
+}
+
+fn handle<T>(x: Option<T>) -> *mut T {
+  x.map_or(ptr::null_mut(), |x| Box::into_raw(Box::new(x)))
+}
+
+// The scope of the prelude
+#[no_mangle]
+pub extern "C" fn yatima_scope_new() -> *mut YatimaScope {
+  let (defs, refs) = prelude();
+  handle(Some(YatimaScope { defs, refs }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yatima_scope_free(scope: *mut YatimaScope) {
+  if !scope.is_null() {
+    drop(Box::from_raw(scope));
+  }
+}
+
+// Parse the source of the package `name`, storing it in the hashspace, into
+// a new scope, of `scope` and the package's definitions, pointing `link`, if
+// it is not null, at the package's link
+#[no_mangle]
+pub unsafe extern "C" fn yatima_scope_parse(
+  scope: *const YatimaScope,
+  name: *const c_char,
+  source: *const c_char,
+  link: *mut *mut c_char,
+  error: *mut *mut c_char,
+) -> *mut YatimaScope {
+  handle(guard(error, || {
+    let scope = scope_ref(scope)?;
+    let (name, source) = (text(name)?, text(source)?);
+    let env = PackageEnv::new(PathBuf::from(format!("{}.ya", name)));
+    let source_link = hashspace::put_text(source);
+    let (pack_link, _, defs, refs) =
+      match parse_package(env, source_link)(Span::new(source)) {
+        Ok((_, parsed)) => parsed,
+        Err(Err::Incomplete(_)) => return Err(String::from("Incomplete")),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+          return Err(e.to_string());
+        }
+      };
+    if !link.is_null() {
+      *link = c_string(pack_link.to_string());
+    }
+    Ok(YatimaScope {
+      defs: merge_defs(scope.defs.clone(), defs),
+      refs: merge_refs(scope.refs.clone(), refs, Name::default(), None),
+    })
+  }))
+}
+
+// Open the package at `link` in the hashspace into a new scope, of `scope`
+// and the package's definitions
+#[no_mangle]
+pub unsafe extern "C" fn yatima_scope_open(
+  scope: *const YatimaScope,
+  link: *const c_char,
+  error: *mut *mut c_char,
+) -> *mut YatimaScope {
+  handle(guard(error, || {
+    let scope = scope_ref(scope)?;
+    let link = text(link)?;
+    let link = match Link::parse(link) {
+      Ok((_, link)) => link,
+      Err(_) => return Err(format!("Invalid link {}", link)),
+    };
+    let (refs, defs) = Package::get_link(link)
+      .and_then(Package::refs_defs)
+      .map_err(|e| format!("Cannot open {}: {:?}", link, e))?;
+    Ok(YatimaScope {
+      defs: merge_defs(scope.defs.clone(), defs),
+      refs: merge_refs(scope.refs.clone(), refs, Name::default(), None),
+    })
+  }))
+}
+
+// Parse and elaborate an expression in `scope`
+#[no_mangle]
+pub unsafe extern "C" fn yatima_term_parse(
+  scope: *const YatimaScope,
+  expr: *const c_char,
+  error: *mut *mut c_char,
+) -> *mut YatimaTerm {
+  handle(guard(error, || {
+    let scope = scope_ref(scope)?;
+    let term = parse_input(&scope.defs, &scope.refs, text(expr)?)?;
+    Ok(YatimaTerm { term })
+  }))
+}
+
+// The normal form of a term, reduced lazily with at most `fuel` reductions,
+// or with no limit if `fuel` is 0
+#[no_mangle]
+pub unsafe extern "C" fn yatima_term_normalize(
+  scope: *const YatimaScope,
+  term: *const YatimaTerm,
+  fuel: u64,
+  error: *mut *mut c_char,
+) -> *mut YatimaTerm {
+  handle(guard(error, || {
+    let (scope, term) = (scope_ref(scope)?, term_ref(term)?);
+    let mut stats = Stats::new(if fuel == 0 { None } else { Some(fuel) });
+    let norm = run(&scope.defs, &term.term, Strategy::Lazy, &mut stats)
+      .map_err(|e| e.to_string())?;
+    Ok(YatimaTerm { term: norm })
+  }))
+}
+
+// The type of a term
+#[no_mangle]
+pub unsafe extern "C" fn yatima_term_check(
+  scope: *const YatimaScope,
+  term: *const YatimaTerm,
+  error: *mut *mut c_char,
+) -> *mut YatimaTerm {
+  handle(guard(error, || {
+    let (scope, term) = (scope_ref(scope)?, term_ref(term)?);
+    let typ =
+      infer_term(&scope.defs, &term.term).map_err(|e| e.to_string())?;
+    Ok(YatimaTerm { term: typ })
+  }))
+}
+
+// A term, laid out to `width` columns, or on one line if `width` is 0
+#[no_mangle]
+pub unsafe extern "C" fn yatima_term_print(
+  term: *const YatimaTerm,
+  width: usize,
+  error: *mut *mut c_char,
+) -> *mut c_char {
+  guard(error, || {
+    let term = term_ref(term)?;
+    let opts = match width {
+      0 => Options::message(),
+      width => Options { width, ..Options::message() },
+    };
+    Ok(c_string(pretty(&term.term, &opts)))
+  })
+  .unwrap_or(ptr::null_mut())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yatima_term_free(term: *mut YatimaTerm) {
+  if !term.is_null() {
+    drop(Box::from_raw(term));
+  }
+}
+
+// Put the serialized expression of `len` bytes at `data` in the hashspace,
+// returning its link
+#[no_mangle]
+pub unsafe extern "C" fn yatima_hashspace_put(
+  data: *const u8,
+  len: usize,
+  error: *mut *mut c_char,
+) -> *mut c_char {
+  guard(error, || {
+    if data.is_null() {
+      return Err(String::from("Null data"));
+    }
+    let expr = match Expr::deserialize(slice::from_raw_parts(data, len)) {
+      Ok((rest, expr)) if rest.is_empty() => expr,
+      Ok(_) => return Err(String::from("Bytes after the expression")),
+      Err(e) => return Err(format!("Invalid expression: {}", e)),
+    };
+    Ok(c_string(hashspace::put(expr).to_string()))
+  })
+  .unwrap_or(ptr::null_mut())
+}
+
+// The serialized expression at `link` in the hashspace, pointing `len` at
+// its number of bytes
+#[no_mangle]
+pub unsafe extern "C" fn yatima_hashspace_get(
+  link: *const c_char,
+  len: *mut usize,
+  error: *mut *mut c_char,
+) -> *mut u8 {
+  guard(error, || {
+    let text = text(link)?;
+    let link = match Link::parse(text) {
+      Ok((_, link)) => link,
+      Err(_) => return Err(format!("Invalid link {}", text)),
+    };
+    let expr = hashspace::get(link)
+      .ok_or_else(|| format!("No expression at {}", link))?;
+    let bytes = expr.serialize().into_boxed_slice();
+    if !len.is_null() {
+      *len = bytes.len();
+    }
+    Ok(Box::into_raw(bytes) as *mut u8)
+  })
+  .unwrap_or(ptr::null_mut())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yatima_string_free(s: *mut c_char) {
+  if !s.is_null() {
+    drop(CString::from_raw(s));
+  }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yatima_bytes_free(data: *mut u8, len: usize) {
+  if !data.is_null() {
+    drop(Box::from_raw(slice::from_raw_parts_mut(data, len)));
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  // A string returned, freed once it is read
+  unsafe fn take(s: *mut c_char) -> String {
+    assert!(!s.is_null());
+    let owned = String::from(CStr::from_ptr(s).to_str().unwrap());
+    yatima_string_free(s);
+    owned
+  }
+
+  #[test]
+  fn embeds() {
+    unsafe {
+      let mut error = ptr::null_mut();
+      let prelude = yatima_scope_new();
+      let name = CString::new("Ffi").unwrap();
+      let source = CString::new(
+        "package Ffi where\n\
+         def id (A: Type) (x: A): A = x\n\
+         def two : #Natural = id #Natural 2\n",
+      )
+      .unwrap();
+      let mut link = ptr::null_mut();
+      let scope = yatima_scope_parse(
+        prelude,
+        name.as_ptr(),
+        source.as_ptr(),
+        &mut link,
+        &mut error,
+      );
+      assert!(!scope.is_null());
+      let link = CString::new(take(link)).unwrap();
+      let expr = CString::new("id #Natural two").unwrap();
+      let term = yatima_term_parse(scope, expr.as_ptr(), &mut error);
+      let norm = yatima_term_normalize(scope, term, 0, &mut error);
+      assert_eq!(take(yatima_term_print(norm, 0, &mut error)), "2");
+      let typ = yatima_term_check(scope, term, &mut error);
+      assert_eq!(take(yatima_term_print(typ, 80, &mut error)), "#Natural");
+      for term in vec![term, norm, typ] {
+        yatima_term_free(term);
+      }
+      let opened = yatima_scope_open(prelude, link.as_ptr(), &mut error);
+      let term = yatima_term_parse(opened, expr.as_ptr(), &mut error);
+      assert!(!term.is_null());
+      yatima_term_free(term);
+      let bad = CString::new("id Type (").unwrap();
+      assert!(yatima_term_parse(scope, bad.as_ptr(), &mut error).is_null());
+      assert!(!take(error).is_empty());
+      let mut len = 0;
+      let bytes = yatima_hashspace_get(link.as_ptr(), &mut len, &mut error);
+      assert!(!bytes.is_null());
+      let put = yatima_hashspace_put(bytes, len, &mut error);
+      assert_eq!(CString::new(take(put)).unwrap(), link);
+      yatima_bytes_free(bytes, len);
+      for scope in vec![prelude, scope, opened] {
+        yatima_scope_free(scope);
+      }
+    }
+  }
+}
@@ -0,0 +1,731 @@
+use crate::{
+  name::Name,
+  parse::{
+    error::ParseError,
+    package::{
+      parse_alias,
+      parse_ctor,
+      parse_fixity,
+      parse_link,
+      parse_package,
+      parse_transparency,
+      parse_with,
+      trailing_comments,
+      PackageEnv,
+    },
+    span::Span,
+    term::{
+      is_valid_symbol_char,
+      parse_binders,
+      parse_expression,
+      parse_name,
+      parse_space,
+      parse_space1,
+      parse_typed_definition,
+      parse_untyped_definition,
+      Ops,
+    },
+  },
+  print::{
+    binder_doc,
+    cat,
+    group,
+    implicit_binder_doc,
+    nest,
+    render,
+    term_doc,
+    text,
+    Doc,
+    Options,
+  },
+  query::{
+    Database,
+    QueryError,
+  },
+  term::{
+    Refs,
+    Term,
+    Uses,
+  },
+};
+
+use im::Vector;
+
+use nom::{
+  branch::alt,
+  bytes::complete::tag,
+  character::complete::multispace1,
+  combinator::{
+    eof,
+    opt,
+    recognize,
+  },
+  sequence::terminated,
+  Err,
+  IResult,
+  Slice,
+};
+
+use std::{
+  fmt,
+  fs,
+  path::Path,
+};
+
+// The canonical source formatter. A package is read declaration by
+// declaration, as the parser reads it, and each declaration is printed back
+// from what it parsed to: terms are printed as their `Display` does, so that
+// the Unicode forms of binders are always used, but broken over lines where
+// they do not fit in the width of `Options::source`. The binders a
+// definition's type and term share are printed as binders of the definition,
+// and datatypes with one constructor or field per line.
+//
+// Comments between declarations are kept, each on the lines just before the
+// declaration that follows it. The parser does not keep the comments within
+// a declaration, so a declaration with comments in it is left as written.
+// Formatting only changes layout, so formatted source parses to the same
+// definitions, and formatting it again leaves it unchanged.
+
+// The document of a definition, or of a test, declared by `head`, with the
+// binders its type and term share as its own, the first of which are
+// implicit as set by `implicits`
+pub fn defn_doc(
+  head: &str,
+  nam: &str,
+  implicits: &[bool],
+  typ: &Term,
+  term: &Term,
+) -> Doc {
+  let src = Options::source();
+  let mut binders = Vec::new();
+  let (mut typ, mut term) = (typ, term);
+  let mut implicits = implicits.iter();
+  while let (Term::All(_, use_, nam, typs), Term::Lam(_, nam2, bod)) =
+    (typ, term)
+  {
+    if nam.is_empty() || nam != nam2 {
+      break;
+    }
+    let binder = match implicits.next() {
+      Some(true) => implicit_binder_doc(use_, nam, &typs.0, &src),
+      _ => binder_doc(use_, nam, &typs.0, &src),
+    };
+    binders.extend(vec![Doc::Line, binder]);
+    typ = &typs.1;
+    term = &**bod;
+  }
+  let colon = if binders.is_empty() { " :" } else { ":" };
+  let head = Doc::Text(format!("{} {}", head, nam));
+  let sig = group(cat(vec![
+    group(cat(vec![head, nest(cat(binders))])),
+    text(colon),
+    nest(cat(vec![Doc::Line, term_doc(typ, &src)])),
+  ]));
+  group(cat(vec![
+    sig,
+    text(" ="),
+    nest(cat(vec![Doc::Line, term_doc(term, &src)])),
+  ]))
+}
+
+// The document of a datatype or codatatype declaration
+fn data_doc(
+  kw: &str,
+  nam: &str,
+  params: &[(Uses, Name, Term)],
+  sort: &Term,
+  ctors: &[(Name, Term)],
+) -> Doc {
+  let src = Options::source();
+  let mut docs = vec![Doc::Text(format!("{} {}", kw, nam))];
+  for (use_, nam, typ) in params {
+    docs.extend(vec![text(" "), binder_doc(use_, nam, typ, &src)]);
+  }
+  docs.extend(vec![text(": "), term_doc(sort, &src), text(" {")]);
+  if ctors.is_empty() {
+    docs.push(text("}"));
+    return cat(docs);
+  }
+  let mut body = Vec::new();
+  for (nam, typ) in ctors {
+    let ctor = Doc::Text(format!("{}: ", nam));
+    body.extend(vec![Doc::Line, ctor, term_doc(typ, &src), text(",")]);
+  }
+  docs.extend(vec![nest(cat(body)), Doc::Line, text("}")]);
+  cat(docs)
+}
+
+// The keywords of syntax which the parser compiles away, so that a
+// declaration using them cannot be laid out from what it parses to
+const SUGAR: [&str; 3] = ["match", "if", "do"];
+
+// The quotes of a raw text, which is laid out as an escaped text otherwise
+const RAW_QUOTES: &str = "\"\"\"";
+
+// Whether source has syntax in it which the parser compiles away, such as a
+// list
+pub fn has_sugar(source: &str) -> bool {
+  source.contains(RAW_QUOTES)
+    || source.contains('[')
+    || source
+      .split(|c: char| !is_valid_symbol_char(c))
+      .any(|word| SUGAR.contains(&word))
+}
+
+// Whether source has a comment in it, outside of its text literals
+pub fn has_comment(source: &str) -> bool {
+  let mut chars = source.chars().peekable();
+  let mut in_text = false;
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' if in_text => {
+        chars.next();
+      }
+      // A raw text has no escapes, and ends at the next triple quotes
+      '"' if !in_text && chars.clone().take(2).eq("\"\"".chars()) => {
+        let rest: String = chars.by_ref().skip(2).collect();
+        match rest.find(RAW_QUOTES) {
+          Some(end) => return has_comment(&rest[end + RAW_QUOTES.len()..]),
+          None => return false,
+        }
+      }
+      '"' => in_text = !in_text,
+      '/' if !in_text && chars.peek() == Some(&'/') => return true,
+      _ => (),
+    }
+  }
+  false
+}
+
+fn format_defn(
+  refs: Refs,
+  ops: Ops,
+) -> impl Fn(Span) -> IResult<Span, Doc, ParseError<Span>> {
+  move |i: Span| {
+    let (i, private) = opt(terminated(tag("private"), parse_space1))(i)?;
+    let (i, partial) = opt(terminated(tag("partial"), parse_space1))(i)?;
+    let (i, transparency) =
+      opt(terminated(parse_transparency, parse_space1))(i)?;
+    let (i, _) = tag("def")(i)?;
+    let (i, _) = parse_space(i)?;
+    let mut head = String::new();
+    if private.is_some() {
+      head.push_str("private ");
+    }
+    if partial.is_some() {
+      head.push_str("partial ");
+    }
+    if let Some(transparency) = transparency {
+      head.push_str(&format!("{} ", transparency));
+    }
+    head.push_str("def");
+    let (mut upto, doc) = format_member(&refs, &ops, &head, i)?;
+    // The definitions of a mutually recursive block after the first, each
+    // on a line of its own
+    let mut docs = vec![doc];
+    loop {
+      let (i, _) = parse_space(upto)?;
+      let and: IResult<Span, Span, ParseError<Span>> =
+        terminated(tag("and"), parse_space1)(i);
+      let i = match and {
+        Ok((i, _)) => i,
+        Err(_) => return Ok((upto, cat(docs))),
+      };
+      let (i, doc) = format_member(&refs, &ops, "and", i)?;
+      docs.extend(vec![Doc::Line, doc]);
+      upto = i;
+    }
+  }
+}
+
+// A definition declared by `head`, whose type may be given by a signature
+fn format_member<'a>(
+  refs: &Refs,
+  ops: &Ops,
+  head: &str,
+  i: Span<'a>,
+) -> IResult<Span<'a>, Doc, ParseError<Span<'a>>> {
+  let typed = parse_typed_definition(
+    refs.to_owned(),
+    ops.to_owned(),
+    Vector::new(),
+    true,
+    true,
+  )(i);
+  match typed {
+    Ok((upto, (nam, term, typ, implicits))) => {
+      Ok((upto, defn_doc(head, &nam, &implicits, &typ, &term)))
+    }
+    Err(Err::Error(e)) => {
+      let untyped = parse_untyped_definition(
+        refs.to_owned(),
+        ops.to_owned(),
+        Vector::new(),
+        true,
+        true,
+      );
+      let (upto, (nam, params, term)) = untyped(i).map_err(|e2| match e2 {
+        Err::Error(_) => Err::Error(e),
+        e2 => e2,
+      })?;
+      Ok((upto, untyped_doc(head, &nam, &params, &term)))
+    }
+    Err(e) => Err(e),
+  }
+}
+
+// The document of a definition declared by `head` whose type is given by a
+// signature, with its parameters
+fn untyped_doc(head: &str, nam: &str, params: &[Name], term: &Term) -> Doc {
+  let mut term = term;
+  for _ in params {
+    if let Term::Lam(_, _, bod) = term {
+      term = &**bod;
+    }
+  }
+  let mut head = format!("{} {}", head, nam);
+  for param in params {
+    head.push_str(&format!(" {}", param));
+  }
+  group(cat(vec![
+    Doc::Text(head),
+    text(" ="),
+    nest(cat(vec![Doc::Line, term_doc(term, &Options::source())])),
+  ]))
+}
+
+// A type signature, with its type broken over lines as a definition's is
+fn format_sig(
+  refs: Refs,
+  ops: Ops,
+) -> impl Fn(Span) -> IResult<Span, Doc, ParseError<Span>> {
+  move |i: Span| {
+    let (i, _) = terminated(tag("sig"), parse_space1)(i)?;
+    let (i, nam) = parse_name(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = tag(":")(i)?;
+    let (i, _) = parse_space(i)?;
+    let (upto, typ) =
+      parse_expression(refs.to_owned(), ops.to_owned(), Vector::new())(i)?;
+    let typ = term_doc(&typ, &Options::source());
+    let head = Doc::Text(format!("sig {} :", nam));
+    Ok((upto, group(cat(vec![head, nest(cat(vec![Doc::Line, typ]))]))))
+  }
+}
+
+fn format_test(
+  refs: Refs,
+  ops: Ops,
+) -> impl Fn(Span) -> IResult<Span, Doc, ParseError<Span>> {
+  move |i: Span| {
+    let (i, _) = terminated(tag("test"), parse_space1)(i)?;
+    let (upto, (nam, term, typ, implicits)) = parse_typed_definition(
+      refs.to_owned(),
+      ops.to_owned(),
+      Vector::new(),
+      false,
+      true,
+    )(i)?;
+    Ok((upto, defn_doc("test", &nam, &implicits, &typ, &term)))
+  }
+}
+
+fn format_check(
+  refs: Refs,
+  ops: Ops,
+) -> impl Fn(Span) -> IResult<Span, Doc, ParseError<Span>> {
+  move |i: Span| {
+    let (i, _) = tag("#check")(i)?;
+    let (i, _) = parse_space1(i)?;
+    let (upto, term) =
+      parse_expression(refs.to_owned(), ops.to_owned(), Vector::new())(i)?;
+    let term = term_doc(&term, &Options::source());
+    Ok((upto, cat(vec![text("#check "), term])))
+  }
+}
+
+// The name, parameters and sort of a datatype or codatatype, and the
+// context its constructors or fields are in
+fn format_data_head<'a>(
+  refs: &Refs,
+  ops: &Ops,
+  kw: &str,
+  i: Span<'a>,
+) -> IResult<
+  Span<'a>,
+  (Name, Vec<(Uses, Name, Term)>, Term, Vector<Name>),
+  ParseError<Span<'a>>,
+> {
+  let (i, _) = tag(kw)(i)?;
+  let (i, _) = parse_space(i)?;
+  let (i, nam) = parse_name(i)?;
+  let (i, _) = parse_space(i)?;
+  let (i, params) = opt(terminated(
+    parse_binders(refs.to_owned(), ops.to_owned(), Vector::new(), false),
+    parse_space,
+  ))(i)?;
+  let params = params.unwrap_or_default();
+  let mut ctx = Vector::new();
+  for (_, n, _) in params.iter() {
+    ctx.push_front(n.clone());
+  }
+  let (i, _) = tag(":")(i)?;
+  let (i, _) = parse_space(i)?;
+  let (i, sort) =
+    parse_expression(refs.to_owned(), ops.to_owned(), ctx.clone())(i)?;
+  let (i, _) = parse_space(i)?;
+  let (i, _) = tag("{")(i)?;
+  ctx.push_back(nam.clone());
+  Ok((i, (nam, params, sort, ctx)))
+}
+
+fn format_data(
+  refs: Refs,
+  ops: Ops,
+) -> impl Fn(Span) -> IResult<Span, Doc, ParseError<Span>> {
+  move |i: Span| {
+    let (mut i, (nam, params, sort, ctx)) =
+      format_data_head(&refs, &ops, "data", i)?;
+    let mut ctors = Vec::new();
+    loop {
+      let (i2, _) = parse_space(i)?;
+      let (i2, (_, ctor, typ)) =
+        match parse_ctor(refs.to_owned(), ops.to_owned(), ctx.clone())(i2) {
+          Ok(res) => res,
+          Err(Err::Error(_)) => break,
+          Err(e) => return Err(e),
+        };
+      ctors.push((ctor, typ));
+      let (i2, _) = parse_space(i2)?;
+      let (i2, comma) = opt(tag(","))(i2)?;
+      i = i2;
+      if comma.is_none() {
+        break;
+      }
+    }
+    let (i, _) = parse_space(i)?;
+    let (upto, _) = tag("}")(i)?;
+    Ok((upto, data_doc("data", &nam, &params, &sort, &ctors)))
+  }
+}
+
+// A codatatype or record declaration, with `kw` its keyword. Each field is
+// in the scope of the fields before it.
+fn format_codata(
+  refs: Refs,
+  ops: Ops,
+  kw: &'static str,
+) -> impl Fn(Span) -> IResult<Span, Doc, ParseError<Span>> {
+  move |i: Span| {
+    let (mut i, (nam, params, sort, mut ctx)) =
+      format_data_head(&refs, &ops, kw, i)?;
+    let mut fields = Vec::new();
+    loop {
+      let (i2, _) = parse_space(i)?;
+      let (i2, (_, field, typ)) =
+        match parse_ctor(refs.to_owned(), ops.to_owned(), ctx.clone())(i2) {
+          Ok(res) => res,
+          Err(Err::Error(_)) => break,
+          Err(e) => return Err(e),
+        };
+      ctx.push_front(field.clone());
+      fields.push((field, typ));
+      let (i2, _) = parse_space(i2)?;
+      let (i2, comma) = opt(tag(","))(i2)?;
+      i = i2;
+      if comma.is_none() {
+        break;
+      }
+    }
+    let (i, _) = parse_space(i)?;
+    let (upto, _) = tag("}")(i)?;
+    Ok((upto, data_doc(kw, &nam, &params, &sort, &fields)))
+  }
+}
+
+fn format_open(i: Span) -> IResult<Span, Doc, ParseError<Span>> {
+  let (i, _) = tag("open")(i)?;
+  let (i, _) = parse_space(i)?;
+  let (i, nam) = parse_name(i)?;
+  let mut open = format!("open {}", nam);
+  let (i, _) = parse_space(i)?;
+  let (i, alias) = opt(terminated(parse_alias, parse_space))(i)?;
+  if let Some(alias) = alias {
+    open.push_str(&format!(" as {}", alias));
+  }
+  let (i, with) = opt(terminated(parse_with, parse_space))(i)?;
+  if let Some(with) = with {
+    open.push_str(&format!(" ({})", with.join(", ")));
+  }
+  let (i, link) = opt(recognize(parse_link))(i)?;
+  if let Some(link) = link {
+    open.push_str(&format!(" {}", link.fragment()));
+  }
+  Ok((i, Doc::Text(open)))
+}
+
+// Whether source applies any of the operators `ops`, which the parser reads
+// as the applications of the definitions they name
+pub fn has_operator(source: &str, ops: &Ops) -> bool {
+  source
+    .split(|c: char| !is_valid_symbol_char(c))
+    .any(|word| ops.contains_key(word))
+}
+
+// A declaration, laid out from what it parses to, unless it has comments,
+// syntax the parser compiles away or operators in it. The comments after it
+// are left to be read before the next one.
+fn format_decl(
+  refs: Refs,
+  ops: Ops,
+) -> impl Fn(Span) -> IResult<Span, String, ParseError<Span>> {
+  move |from: Span| {
+    let (upto, doc) = alt((
+      format_defn(refs.to_owned(), ops.to_owned()),
+      format_sig(refs.to_owned(), ops.to_owned()),
+      format_data(refs.to_owned(), ops.to_owned()),
+      format_codata(refs.to_owned(), ops.to_owned(), "codata"),
+      format_codata(refs.to_owned(), ops.to_owned(), "record"),
+      format_open,
+      format_test(refs.to_owned(), ops.to_owned()),
+      format_check(refs.to_owned(), ops.to_owned()),
+    ))(from)?;
+    let len = upto.location_offset() - from.location_offset();
+    let (len, _) = trailing_comments(&from.fragment()[..len]);
+    let source = &from.fragment()[..len];
+    let as_written =
+      has_comment(source) || has_sugar(source) || has_operator(source, &ops);
+    let decl = if as_written {
+      String::from(source)
+    }
+    else {
+      render(&doc, &Options::source())
+    };
+    Ok((from.slice(len..), decl))
+  }
+}
+
+fn push_comments(out: &mut String, comments: &[Span]) {
+  for com in comments {
+    out.push_str(&format!("//{}\n", com.fragment().trim_end()));
+  }
+}
+
+// Format the source of a package, in which the references `refs` are in
+// scope
+pub fn format_package(
+  refs: Refs,
+) -> impl Fn(Span) -> IResult<Span, String, ParseError<Span>> {
+  move |i: Span| {
+    let mut out = String::new();
+    let (i, comments) = parse_space(i)?;
+    push_comments(&mut out, &comments);
+    let (i, _) = tag("package")(i)?;
+    let (i, _) = multispace1(i)?;
+    let (i, nam) = parse_name(i)?;
+    let (i, _) = multispace1(i)?;
+    let (mut i, _) = tag("where")(i)?;
+    out.push_str(&format!("package {} where\n", nam));
+    let mut ops = Ops::new();
+    loop {
+      let (i2, comments) = parse_space(i)?;
+      let end: IResult<Span, Span, ParseError<Span>> = eof(i2);
+      if end.is_ok() {
+        if !comments.is_empty() {
+          out.push('\n');
+          push_comments(&mut out, &comments);
+        }
+        return Ok((i2, out));
+      }
+      out.push('\n');
+      push_comments(&mut out, &comments);
+      let infix: IResult<Span, Span, ParseError<Span>> = tag("infix")(i2);
+      let (i2, decl) = if infix.is_ok() {
+        let (i2, (nam, op)) = parse_fixity(&refs)(i2)?;
+        ops.insert(nam.clone(), op.clone());
+        (i2, format!("{} {} {} = {}", op.assoc, op.prec, nam, op.name))
+      }
+      else {
+        format_decl(refs.to_owned(), ops.to_owned())(i2)?
+      };
+      out.push_str(&decl);
+      out.push('\n');
+      i = i2;
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormatError {
+  Query(QueryError),
+  // A definition which the formatted source would parse to differently
+  Changed(String),
+}
+
+impl fmt::Display for FormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Query(e) => write!(f, "{}", e),
+      Self::Changed(nam) => {
+        write!(f, "Formatting would change the definition {}", nam)
+      }
+    }
+  }
+}
+
+impl std::error::Error for FormatError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Query(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+// Format the package in the file at `path`, returning its formatted source
+// after checking that it parses to the same definitions
+pub fn format_file(path: &Path) -> Result<String, FormatError> {
+  let parsed = Database::new().parse(path).map_err(FormatError::Query)?;
+  let parse_error =
+    |e: String| FormatError::Query(QueryError::Parse(None, None, e));
+  let source = fs::read_to_string(path)
+    .map_err(|e| {
+      FormatError::Query(QueryError::Io(format!("{}: {}", path.display(), e)))
+    })?;
+  let formatted = match format_package(parsed.refs.clone())(Span::new(&source))
+  {
+    Ok((_, formatted)) => formatted,
+    Err(Err::Incomplete(_)) => {
+      return Err(parse_error(String::from("Incomplete")));
+    }
+    Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+      return Err(parse_error(format!("{}", e)));
+    }
+  };
+  let env = PackageEnv::new(path.to_path_buf());
+  let source_link = parsed.package.source;
+  let (_, (_, _, defs, refs)) =
+    parse_package(env, source_link)(Span::new(&formatted))
+      .map_err(|e| parse_error(format!("{}", e)))?;
+  for (nam, (def_link, ast_link)) in parsed.refs.iter() {
+    let same = match refs.get(nam) {
+      Some((def_link2, ast_link2)) => {
+        let (def, def2) = (&parsed.defs[def_link], &defs[def_link2]);
+        ast_link == ast_link2
+          && def.transparency == def2.transparency
+          && def.typ_.clone().embed().0 == def2.typ_.clone().embed().0
+      }
+      None => false,
+    };
+    if !same {
+      return Err(FormatError::Changed(nam.to_string()));
+    }
+  }
+  Ok(formatted)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::parse::term::{
+    Assoc,
+    Operator,
+  };
+  use im::HashMap;
+  use std::env;
+
+  #[quickcheck]
+  fn flat_is_display(x: Term) -> bool {
+    let doc = term_doc(&x, &Options::source());
+    render(&doc, &Options::line()) == format!("{}", x)
+  }
+
+  #[test]
+  fn breaks_lines() {
+    let refs: Refs = HashMap::new();
+    let parse = |i: &str| {
+      let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+      expr(Span::new(i)).unwrap().1
+    };
+    let width = |width| Options { width, ..Options::source() };
+    let term = term_doc(&parse("λ f x => f x x x"), &Options::source());
+    assert_eq!(render(&term, &width(80)), "λ f x => f x x x");
+    assert_eq!(render(&term, &width(12)), "λ f x =>\n  f x x x");
+    assert_eq!(
+      render(&term, &width(6)),
+      "λ f x =>\n  f\n    x\n    x\n    x"
+    );
+    let typ = parse("∀ (A: Type) (x: A) -> A");
+    let term = parse("λ A x => x");
+    let doc = defn_doc("def", "id", &[], &typ, &term);
+    assert_eq!(render(&doc, &width(80)), "def id (A: Type) (x: A): A = x");
+    assert_eq!(render(&doc, &width(29)), "def id (A: Type) (x: A): A =\n  x");
+    let broken = "def id (A: Type) (x: A):\n  A =\n  x";
+    assert_eq!(render(&doc, &width(26)), broken);
+    let doc = defn_doc("def", "id", &[true], &typ, &term);
+    assert_eq!(render(&doc, &width(80)), "def id {A: Type} (x: A): A = x");
+    let doc =
+      defn_doc("opaque def", "one", &[], &parse("#Natural"), &parse("1"));
+    assert_eq!(render(&doc, &width(80)), "opaque def one : #Natural = 1");
+    assert!(has_comment("def a : #Text = \"//\" // a comment"));
+    assert!(!has_comment("def a : #Text = \"// \\\" //\""));
+    assert!(!has_comment("def a : #Text = \"\"\"\\\" // \"\"\""));
+    assert!(has_comment("def a : #Text = \"\"\" \" \"\"\" // a comment"));
+    assert!(has_sugar("def a : #Text = \"\"\"raw\"\"\""));
+    assert!(has_sugar("def a : List #Natural = [1, 2]"));
+    assert!(has_sugar("def a : Nat = match b return Nat { _ => b }"));
+    assert!(!has_sugar("def matches : Nat = b"));
+    let ops: Ops = vec![(Name::from("+"), Operator {
+      assoc: Assoc::Left,
+      prec: 6,
+      name: Name::from("add"),
+    })]
+    .into_iter()
+    .collect();
+    assert!(has_operator("def a : Nat = (+) b c", &ops));
+    assert!(!has_operator("def a : Nat = b++ c", &ops));
+  }
+
+  #[test]
+  fn formats() {
+    let dir = env::temp_dir().join("yatima-format");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("Format.ya");
+    let source = "// The package\npackage Format   where\n\
+      def id  (A: Type) (x: A): A =x\n\
+      // Numbers\n\
+      data Nat : Type@1 { zero: Nat, succ: ∀ (n: Nat) -> Nat }\n\
+      opaque def one : Nat\n  = Nat.succ Nat.zero\n\
+      def two : Nat = Nat.succ // two\n  one\n\
+      def first (a: Nat) (b: Nat): Nat = a\n\
+      infixl  6 & = first\n\
+      def three : Nat = two & one\n\
+      private   def four : Nat = three\n\
+      sig pred :  ∀ (n: Nat) -> Nat\n\
+      def   pred n   =  n\n\
+      partial def even (n: Nat): Nat = odd n\n\
+      and   odd (n: Nat) : Nat =even n\n";
+    fs::write(&path, source).unwrap();
+    let formatted = format_file(&path).unwrap();
+    assert_eq!(
+      formatted,
+      "// The package\n\
+       package Format where\n\n\
+       def id (A: Type) (x: A): A = x\n\n\
+       // Numbers\n\
+       data Nat: Type@1 {\n  zero: Nat,\n  succ: ∀ (n: Nat) -> Nat,\n}\n\n\
+       opaque def one : Nat = Nat.succ Nat.zero\n\n\
+       def two : Nat = Nat.succ // two\n  one\n\n\
+       def first (a: Nat) (b: Nat): Nat = a\n\n\
+       infixl 6 & = first\n\n\
+       def three : Nat = two & one\n\n\
+       private def four : Nat = three\n\n\
+       sig pred : ∀ (n: Nat) -> Nat\n\n\
+       def pred n = n\n\n\
+       partial def even (n: Nat): Nat = odd n\n\
+       and odd (n: Nat): Nat = even n\n"
+    );
+    fs::write(&path, &formatted).unwrap();
+    assert_eq!(format_file(&path).unwrap(), formatted);
+  }
+}
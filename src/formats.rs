@@ -0,0 +1,234 @@
+use crate::{
+  name::Name,
+  term::{
+    Refs,
+    Term,
+  },
+};
+
+pub mod dhall;
+pub mod lambda;
+pub mod lean;
+
+// Terms in the notations of other lambda calculi, so that existing corpora
+// of terms can be read as Yatima terms, to test with or to store in the
+// hashspace, and terms can be written back for the tools of those calculi.
+// Each format is parsed by hand, as JSON is, rather than with the combinators
+// of Yatima's own syntax. Names not bound in a term are looked up in the
+// references given, and anything else is an error. Terms read have no
+// positions.
+
+// A cursor over the characters of a source
+pub struct Source {
+  chars: Vec<char>,
+  pos: usize,
+}
+
+impl Source {
+  pub fn new(input: &str) -> Self {
+    Source { chars: input.chars().collect(), pos: 0 }
+  }
+
+  pub fn peek(&self) -> Option<char> { self.peek_nth(0) }
+
+  // The `n`th character ahead, counting the next as the 0th
+  pub fn peek_nth(&self, n: usize) -> Option<char> {
+    self.chars.get(self.pos + n).copied()
+  }
+
+  pub fn at_end(&self) -> bool { self.pos >= self.chars.len() }
+
+  pub fn looking_at(&self, s: &str) -> bool {
+    s.chars().enumerate().all(|(i, c)| self.peek_nth(i) == Some(c))
+  }
+
+  // Whether the source continues with the keyword `kw`, not followed by a
+  // character satisfying `cont` that would make it part of a longer name
+  pub fn looking_at_word(&self, kw: &str, cont: impl Fn(char) -> bool) -> bool {
+    self.looking_at(kw)
+      && !matches!(self.peek_nth(kw.chars().count()), Some(c) if cont(c))
+  }
+
+  // Consume `s` if the source continues with it
+  pub fn eat(&mut self, s: &str) -> bool {
+    if self.looking_at(s) {
+      self.pos += s.chars().count();
+      true
+    }
+    else {
+      false
+    }
+  }
+
+  // Consume the keyword `kw`, as by `looking_at_word`
+  pub fn eat_word(&mut self, kw: &str, cont: impl Fn(char) -> bool) -> bool {
+    if self.looking_at_word(kw, cont) {
+      self.pos += kw.chars().count();
+      true
+    }
+    else {
+      false
+    }
+  }
+
+  pub fn expect(&mut self, s: &str) -> Result<(), String> {
+    if self.eat(s) {
+      Ok(())
+    }
+    else {
+      Err(self.error(&format!("Expected {}", s)))
+    }
+  }
+
+  // Consume the next character, returning it
+  pub fn advance(&mut self) -> Option<char> {
+    let c = self.peek();
+    self.pos += 1;
+    c
+  }
+
+  // Skip whitespace and `--` line comments
+  pub fn skip_space(&mut self) {
+    loop {
+      match self.peek() {
+        Some(c) if c.is_whitespace() => self.pos += 1,
+        Some('-') if self.looking_at("--") => {
+          while !matches!(self.peek(), None | Some('\n')) {
+            self.pos += 1;
+          }
+        }
+        _ => return,
+      }
+    }
+  }
+
+  // The longest run of characters satisfying `cont`, if it starts with one
+  // satisfying `start`
+  pub fn ident(
+    &mut self,
+    start: impl Fn(char) -> bool,
+    cont: impl Fn(char) -> bool,
+  ) -> Option<String> {
+    match self.peek() {
+      Some(c) if start(c) => {
+        let from = self.pos;
+        while matches!(self.peek(), Some(c) if cont(c)) {
+          self.pos += 1;
+        }
+        Some(self.chars[from..self.pos].iter().collect())
+      }
+      _ => None,
+    }
+  }
+
+  // An error at the current position, by line and column
+  pub fn error(&self, msg: &str) -> String {
+    let before = &self.chars[..self.pos.min(self.chars.len())];
+    let line = before.iter().filter(|c| **c == '\n').count() + 1;
+    let col = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+    format!("{}:{}: {}", line, col, msg)
+  }
+}
+
+// The variable a name is bound to in `ctx`, whose innermost binder is last,
+// or else the reference it names
+pub fn resolve(nam: &str, ctx: &[String], refs: &Refs) -> Option<Term> {
+  match ctx.iter().rev().position(|x| x == nam) {
+    Some(idx) => Some(Term::Var(None, Name::from(nam), idx as u64)),
+    None => refs
+      .get(nam)
+      .map(|(def, ast)| Term::Ref(None, Name::from(nam), *def, *ast)),
+  }
+}
+
+// Whether the variable of index `idx` occurs in a term
+pub fn occurs(term: &Term, idx: u64) -> bool {
+  match term {
+    Term::Var(_, _, i) => *i == idx,
+    Term::Lam(_, _, bod) | Term::Slf(_, _, bod) => occurs(bod, idx + 1),
+    Term::Dat(_, bod)
+    | Term::Cse(_, bod)
+    | Term::Fst(_, bod)
+    | Term::Snd(_, bod) => occurs(bod, idx),
+    Term::App(_, terms) | Term::Par(_, terms) | Term::Ann(_, terms) => {
+      occurs(&terms.0, idx) || occurs(&terms.1, idx)
+    }
+    Term::All(_, _, _, terms) | Term::Sig(_, _, terms) => {
+      occurs(&terms.0, idx) || occurs(&terms.1, idx + 1)
+    }
+    Term::Let(_, rec, _, _, terms) => {
+      occurs(&terms.0, idx)
+        || occurs(&terms.1, if *rec { idx + 1 } else { idx })
+        || occurs(&terms.2, idx + 1)
+    }
+    _ => false,
+  }
+}
+
+// The names of the binders in scope of a term being printed, renamed apart
+// so that none captures a variable or reference of the same name
+pub struct Names {
+  names: Vec<String>,
+  refs: Vec<String>,
+}
+
+impl Names {
+  // The names in scope of a term, which must not be taken by its binders
+  pub fn new(term: &Term) -> Self {
+    fn refs(term: &Term, acc: &mut Vec<String>) {
+      match term {
+        Term::Ref(_, nam, ..) => acc.push(nam.to_string()),
+        Term::Lam(_, _, bod)
+        | Term::Slf(_, _, bod)
+        | Term::Dat(_, bod)
+        | Term::Cse(_, bod)
+        | Term::Fst(_, bod)
+        | Term::Snd(_, bod) => refs(bod, acc),
+        Term::App(_, terms)
+        | Term::Par(_, terms)
+        | Term::Ann(_, terms)
+        | Term::All(_, _, _, terms)
+        | Term::Sig(_, _, terms) => {
+          refs(&terms.0, acc);
+          refs(&terms.1, acc);
+        }
+        Term::Let(_, _, _, _, terms) => {
+          refs(&terms.0, acc);
+          refs(&terms.1, acc);
+          refs(&terms.2, acc);
+        }
+        _ => (),
+      }
+    }
+    let mut acc = Vec::new();
+    refs(term, &mut acc);
+    Names { names: Vec::new(), refs: acc }
+  }
+
+  // Bind a name, numbered if it is taken, returning it
+  pub fn bind(&mut self, nam: &str) -> String {
+    let base = if nam.is_empty() { "x" } else { nam };
+    let taken = |x: &String| self.names.contains(x) || self.refs.contains(x);
+    let mut fresh = String::from(base);
+    let mut n = 0;
+    while taken(&fresh) {
+      n += 1;
+      fresh = format!("{}{}", base, n);
+    }
+    self.names.push(fresh.clone());
+    fresh
+  }
+
+  pub fn unbind(&mut self) { self.names.pop(); }
+
+  // The name of a variable, by its de Bruijn index
+  pub fn var(&self, idx: u64) -> Result<&String, String> {
+    let len = self.names.len() as u64;
+    if idx < len {
+      Ok(&self.names[(len - 1 - idx) as usize])
+    }
+    else {
+      Err(format!("Free variable of index {}", idx))
+    }
+  }
+}
@@ -0,0 +1,519 @@
+use crate::{
+  formats::{
+    occurs,
+    resolve,
+    Names,
+    Source,
+  },
+  name::Name,
+  term::{
+    LitType,
+    Literal,
+    PrimOp,
+    Refs,
+    Term,
+    Uses,
+  },
+};
+
+use num_bigint::{
+  BigInt,
+  BigUint,
+};
+
+// Simple Dhall-like expressions, as in `\(x : Natural) -> x + 1`: typed
+// lambdas, `forall` and arrow types, `let` chains, annotations `e : T`, the
+// universes `Type`, `Kind` and `Sort`, the types `Natural`, `Integer` and
+// `Text` with their literals, and the operators `+`, `++` and `*`. A typed
+// lambda is read as the untyped lambda of Yatima annotated with a function
+// type, which is how it is written back. Imports, records, unions and the
+// builtin functions of Dhall are not supported.
+
+fn is_start(c: char) -> bool { c.is_alphabetic() || c == '_' }
+
+fn is_cont(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+const KEYWORDS: [&str; 3] = ["let", "in", "forall"];
+
+fn parse_name(src: &mut Source) -> Result<String, String> {
+  src.skip_space();
+  match src.ident(is_start, is_cont) {
+    Some(nam) if KEYWORDS.contains(&nam.as_str()) => {
+      Err(src.error(&format!("Unexpected keyword {}", nam)))
+    }
+    Some(nam) => Ok(nam),
+    None => Err(src.error("Expected a name")),
+  }
+}
+
+fn expect_arrow(src: &mut Source) -> Result<(), String> {
+  src.skip_space();
+  if src.eat("->") || src.eat("→") {
+    Ok(())
+  }
+  else {
+    Err(src.error("Expected ->"))
+  }
+}
+
+// A binder `(x : T)`
+fn parse_binder(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<(String, Term), String> {
+  src.skip_space();
+  src.expect("(")?;
+  let nam = parse_name(src)?;
+  src.skip_space();
+  src.expect(":")?;
+  let typ = parse_expr(src, ctx, refs)?;
+  src.skip_space();
+  src.expect(")")?;
+  Ok((nam, typ))
+}
+
+// The body of a binder, with its name in scope
+fn parse_body(
+  nam: &str,
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  ctx.push(String::from(nam));
+  let bod = parse_expr(src, ctx, refs);
+  ctx.pop();
+  bod
+}
+
+fn parse_expr(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  src.skip_space();
+  if src.eat("\\") || src.eat("λ") {
+    let (nam, typ) = parse_binder(src, ctx, refs)?;
+    expect_arrow(src)?;
+    let bod = parse_body(&nam, src, ctx, refs)?;
+    let hol = Term::Hol(None, None);
+    let nam = Name::from(nam);
+    let all = Term::All(None, Uses::Many, nam.clone(), Box::new((typ, hol)));
+    let lam = Term::Lam(None, nam, Box::new(bod));
+    Ok(Term::Ann(None, Box::new((all, lam))))
+  }
+  else if src.eat_word("forall", is_cont) || src.eat("∀") {
+    let (nam, typ) = parse_binder(src, ctx, refs)?;
+    expect_arrow(src)?;
+    let bod = parse_body(&nam, src, ctx, refs)?;
+    Ok(Term::All(None, Uses::Many, Name::from(nam), Box::new((typ, bod))))
+  }
+  else if src.eat_word("let", is_cont) {
+    parse_let(src, ctx, refs)
+  }
+  else {
+    let term = parse_plus(src, ctx, refs)?;
+    src.skip_space();
+    if src.looking_at("->") || src.looking_at("→") {
+      expect_arrow(src)?;
+      let bod = parse_body("", src, ctx, refs)?;
+      Ok(Term::All(None, Uses::Many, Name::default(), Box::new((term, bod))))
+    }
+    else if src.eat(":") {
+      let typ = parse_expr(src, ctx, refs)?;
+      Ok(Term::Ann(None, Box::new((typ, term))))
+    }
+    else {
+      Ok(term)
+    }
+  }
+}
+
+// The rest of a `let`, whose keyword has been read, which may be followed by
+// further `let`s before the `in` of the last
+fn parse_let(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  let nam = parse_name(src)?;
+  src.skip_space();
+  let typ = if src.eat(":") {
+    parse_expr(src, ctx, refs)?
+  }
+  else {
+    Term::Hol(None, None)
+  };
+  src.skip_space();
+  src.expect("=")?;
+  let exp = parse_expr(src, ctx, refs)?;
+  src.skip_space();
+  ctx.push(nam.clone());
+  let bod = if src.eat_word("let", is_cont) {
+    parse_let(src, ctx, refs)
+  }
+  else if src.eat_word("in", is_cont) {
+    parse_expr(src, ctx, refs)
+  }
+  else {
+    Err(src.error("Expected in"))
+  };
+  ctx.pop();
+  let nam = Name::from(nam);
+  Ok(Term::Let(None, false, Uses::Many, nam, Box::new((typ, exp, bod?))))
+}
+
+fn binary(op: PrimOp, lhs: Term, rhs: Term) -> Term {
+  let opr = Term::App(None, Box::new((Term::Opr(None, op), lhs)));
+  Term::App(None, Box::new((opr, rhs)))
+}
+
+fn parse_plus(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  let mut lhs = parse_cat(src, ctx, refs)?;
+  loop {
+    src.skip_space();
+    if src.looking_at("+") && !src.looking_at("++") {
+      src.eat("+");
+      let rhs = parse_cat(src, ctx, refs)?;
+      lhs = binary(PrimOp::Add, lhs, rhs);
+    }
+    else {
+      return Ok(lhs);
+    }
+  }
+}
+
+fn parse_cat(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  let mut lhs = parse_times(src, ctx, refs)?;
+  loop {
+    src.skip_space();
+    if src.eat("++") {
+      let rhs = parse_times(src, ctx, refs)?;
+      lhs = binary(PrimOp::Cat, lhs, rhs);
+    }
+    else {
+      return Ok(lhs);
+    }
+  }
+}
+
+fn parse_times(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  let mut lhs = parse_app(src, ctx, refs)?;
+  loop {
+    src.skip_space();
+    if src.eat("*") {
+      let rhs = parse_app(src, ctx, refs)?;
+      lhs = binary(PrimOp::Mul, lhs, rhs);
+    }
+    else {
+      return Ok(lhs);
+    }
+  }
+}
+
+// Whether an atom follows, so that an application continues. A sign directly
+// before a digit starts an integer, as in `f +1`, rather than an operator.
+fn at_atom(src: &Source) -> bool {
+  match src.peek() {
+    Some('(') | Some('"') => true,
+    Some(c) if c.is_ascii_digit() => true,
+    Some('+') | Some('-') => {
+      matches!(src.peek_nth(1), Some(c) if c.is_ascii_digit())
+    }
+    Some(c) if is_start(c) => {
+      !KEYWORDS.iter().any(|kw| src.looking_at_word(kw, is_cont))
+    }
+    _ => false,
+  }
+}
+
+fn parse_app(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  let mut fun = parse_atom(src, ctx, refs)?;
+  loop {
+    src.skip_space();
+    if at_atom(src) {
+      let arg = parse_atom(src, ctx, refs)?;
+      fun = Term::App(None, Box::new((fun, arg)));
+    }
+    else {
+      return Ok(fun);
+    }
+  }
+}
+
+fn parse_digits(src: &mut Source) -> BigUint {
+  let digits = src.ident(|c| c.is_ascii_digit(), |c| c.is_ascii_digit());
+  // At least one digit has been seen by the caller
+  digits.unwrap_or_default().parse().unwrap_or_default()
+}
+
+fn parse_text(src: &mut Source) -> Result<String, String> {
+  let mut txt = String::new();
+  loop {
+    match src.advance() {
+      None => return Err(src.error("Unterminated text literal")),
+      Some('"') => return Ok(txt),
+      Some('$') if src.peek() == Some('{') => {
+        return Err(src.error("Text interpolation is not supported"));
+      }
+      Some('\\') => match src.advance() {
+        Some('"') => txt.push('"'),
+        Some('\\') => txt.push('\\'),
+        Some('$') => txt.push('$'),
+        Some('n') => txt.push('\n'),
+        Some('r') => txt.push('\r'),
+        Some('t') => txt.push('\t'),
+        _ => return Err(src.error("Unknown escape sequence")),
+      },
+      Some(c) => txt.push(c),
+    }
+  }
+}
+
+fn parse_atom(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  src.skip_space();
+  match src.peek() {
+    Some('(') => {
+      src.eat("(");
+      let term = parse_expr(src, ctx, refs)?;
+      src.skip_space();
+      src.expect(")")?;
+      Ok(term)
+    }
+    Some('"') => {
+      src.eat("\"");
+      Ok(Term::Lit(None, Literal::Text(parse_text(src)?)))
+    }
+    Some(c) if c.is_ascii_digit() => {
+      Ok(Term::Lit(None, Literal::Natural(parse_digits(src))))
+    }
+    Some(c @ '+') | Some(c @ '-')
+      if matches!(src.peek_nth(1), Some(d) if d.is_ascii_digit()) =>
+    {
+      src.advance();
+      let x = BigInt::from(parse_digits(src));
+      Ok(Term::Lit(None, Literal::Integer(if c == '-' { -x } else { x })))
+    }
+    _ => {
+      let nam = parse_name(src)?;
+      match nam.as_str() {
+        "Type" => Ok(Term::Typ(None, 0)),
+        "Kind" => Ok(Term::Typ(None, 1)),
+        "Sort" => Ok(Term::Typ(None, 2)),
+        "Natural" => Ok(Term::LTy(None, LitType::Natural)),
+        "Integer" => Ok(Term::LTy(None, LitType::Integer)),
+        "Text" => Ok(Term::LTy(None, LitType::Text)),
+        "Double" => Ok(Term::LTy(None, LitType::Float)),
+        _ => resolve(&nam, ctx, refs)
+          .ok_or_else(|| src.error(&format!("Unbound variable {}", nam))),
+      }
+    }
+  }
+}
+
+// Read a Dhall-like expression
+pub fn parse(input: &str, refs: &Refs) -> Result<Term, String> {
+  let mut src = Source::new(input);
+  let term = parse_expr(&mut src, &mut Vec::new(), refs)?;
+  src.skip_space();
+  if src.at_end() { Ok(term) } else { Err(src.error("Unexpected input")) }
+}
+
+fn print_text(txt: &str) -> String {
+  let mut res = String::from("\"");
+  for c in txt.chars() {
+    match c {
+      '"' => res.push_str("\\\""),
+      '\\' => res.push_str("\\\\"),
+      '$' => res.push_str("\\$"),
+      '\n' => res.push_str("\\n"),
+      '\r' => res.push_str("\\r"),
+      '\t' => res.push_str("\\t"),
+      c => res.push(c),
+    }
+  }
+  res.push('"');
+  res
+}
+
+// The body of a binder, printed with its name in scope
+fn print_body(
+  nam: &str,
+  bod: &Term,
+  names: &mut Names,
+) -> Result<(String, String), String> {
+  let nam = names.bind(nam);
+  let bod = print_term(bod, names, 0);
+  names.unbind();
+  Ok((nam, bod?))
+}
+
+// Print a term at a precedence of 0 for an expression, 1 to 3 for the
+// operands of `+`, `++` and `*`, 4 for the function of an application and 5
+// for its argument
+fn print_term(
+  term: &Term,
+  names: &mut Names,
+  prec: u8,
+) -> Result<String, String> {
+  let wrap = |lvl: u8, s: String| {
+    if prec > lvl { format!("({})", s) } else { s }
+  };
+  match term {
+    Term::Var(_, _, idx) => Ok(names.var(*idx)?.clone()),
+    Term::Ref(_, nam, ..) => Ok(nam.to_string()),
+    Term::Typ(_, 0) => Ok(String::from("Type")),
+    Term::Typ(_, 1) => Ok(String::from("Kind")),
+    Term::Typ(_, 2) => Ok(String::from("Sort")),
+    Term::LTy(_, LitType::Natural) => Ok(String::from("Natural")),
+    Term::LTy(_, LitType::Integer) => Ok(String::from("Integer")),
+    Term::LTy(_, LitType::Text) => Ok(String::from("Text")),
+    Term::LTy(_, LitType::Float) => Ok(String::from("Double")),
+    Term::Lit(_, lit @ Literal::Natural(_))
+    | Term::Lit(_, lit @ Literal::Integer(_))
+    | Term::Lit(_, lit @ Literal::Float(_)) => Ok(format!("{}", lit)),
+    Term::Lit(_, Literal::Text(txt)) => Ok(print_text(txt)),
+    Term::Ann(_, terms) => match &**terms {
+      (Term::All(_, Uses::Many, _, dom_cod), Term::Lam(_, nam, bod))
+        if matches!(dom_cod.1, Term::Hol(_, None)) =>
+      {
+        let typ = print_term(&dom_cod.0, names, 0)?;
+        let (nam, bod) = print_body(nam, bod, names)?;
+        Ok(wrap(0, format!("\\({} : {}) -> {}", nam, typ, bod)))
+      }
+      (typ, trm) => {
+        let trm = print_term(trm, names, 1)?;
+        let typ = print_term(typ, names, 0)?;
+        Ok(wrap(0, format!("{} : {}", trm, typ)))
+      }
+    },
+    Term::All(_, Uses::Many, nam, terms) => {
+      let (dom, cod) = &**terms;
+      if nam.is_empty() || !occurs(cod, 0) {
+        let dom = print_term(dom, names, 1)?;
+        let (_, cod) = print_body(nam, cod, names)?;
+        Ok(wrap(0, format!("{} -> {}", dom, cod)))
+      }
+      else {
+        let dom = print_term(dom, names, 0)?;
+        let (nam, cod) = print_body(nam, cod, names)?;
+        Ok(wrap(0, format!("forall ({} : {}) -> {}", nam, dom, cod)))
+      }
+    }
+    Term::Let(_, false, Uses::Many, nam, terms) => {
+      let (typ, exp, bod) = &**terms;
+      let typ = match typ {
+        Term::Hol(_, None) => String::new(),
+        typ => format!(" : {}", print_term(typ, names, 0)?),
+      };
+      let exp = print_term(exp, names, 0)?;
+      let (nam, bod) = print_body(nam, bod, names)?;
+      Ok(wrap(0, format!("let {}{} = {} in {}", nam, typ, exp, bod)))
+    }
+    Term::App(_, terms) => {
+      let (fun, arg) = &**terms;
+      let op = match fun {
+        Term::App(_, opr_lhs) => match &opr_lhs.0 {
+          Term::Opr(_, PrimOp::Add) => Some(("+", 1)),
+          Term::Opr(_, PrimOp::Cat) => Some(("++", 2)),
+          Term::Opr(_, PrimOp::Mul) => Some(("*", 3)),
+          _ => None,
+        },
+        _ => None,
+      };
+      match (op, fun) {
+        (Some((sym, lvl)), Term::App(_, opr_lhs)) => {
+          let lhs = print_term(&opr_lhs.1, names, lvl)?;
+          let rhs = print_term(arg, names, lvl + 1)?;
+          Ok(wrap(lvl, format!("{} {} {}", lhs, sym, rhs)))
+        }
+        _ => {
+          let fun = print_term(fun, names, 4)?;
+          let arg = print_term(arg, names, 5)?;
+          Ok(wrap(4, format!("{} {}", fun, arg)))
+        }
+      }
+    }
+    _ => Err(format!("{} has no Dhall-like form", term)),
+  }
+}
+
+// Write a Dhall-like expression
+pub fn print(term: &Term) -> Result<String, String> {
+  print_term(term, &mut Names::new(term), 0)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::parse::term::parse as parse_yatima;
+  use im::HashMap;
+
+  #[test]
+  fn parses_dhall_expressions() {
+    let refs = HashMap::new();
+    let (_, typ) = parse_yatima("∀ (a: Type) -> ∀ a -> a").unwrap();
+    assert_eq!(parse("forall (a : Type) -> a -> a", &refs), Ok(typ));
+    let (_, sum) = parse_yatima("#add 1 (#mul 2 3)").unwrap();
+    assert_eq!(parse("1 + 2 * 3", &refs), Ok(sum));
+    let (_, app) = parse_yatima("#cat \"a\" \"b\" :: #Text").unwrap();
+    assert_eq!(parse("\"a\" ++ \"b\" : Text", &refs), Ok(app));
+    let (_, int) = parse_yatima("-1").unwrap();
+    assert_eq!(parse("-1", &refs), Ok(int));
+    let id = parse("\\(x : Natural) -> x", &refs).unwrap();
+    let (_, typ) = parse_yatima("∀ (x: #Natural) -> _").unwrap();
+    let (_, lam) = parse_yatima("λ x => x").unwrap();
+    assert_eq!(id, Term::Ann(None, Box::new((typ, lam))));
+    let lets = parse("let x = 1 let y : Natural = x in x + y", &refs).unwrap();
+    match lets {
+      Term::Let(_, false, Uses::Many, x, terms) => {
+        assert_eq!(x, "x");
+        assert!(matches!(terms.2, Term::Let(_, false, _, _, _)));
+      }
+      _ => panic!("Expected a let, found {}", lets),
+    }
+    assert!(parse("\\x -> x", &refs).is_err());
+    assert!(parse("let x = 1", &refs).is_err());
+    assert!(parse("\"${x}\"", &refs).is_err());
+  }
+
+  #[test]
+  fn prints_dhall_expressions() {
+    let refs = HashMap::new();
+    let inputs = [
+      "\\(a : Type) -> \\(x : a) -> x",
+      "forall (a : Type) -> a -> a",
+      "let x = 1 in let y : Natural = x in (x + y) * y",
+      "\"a\\n\" ++ \"b\" : Text",
+      "+2 : Integer",
+    ];
+    for input in &inputs {
+      let term = parse(input, &refs).unwrap();
+      assert_eq!(print(&term).as_deref(), Ok(*input));
+    }
+    let (_, term) = parse_yatima("λ x => x").unwrap();
+    assert!(print(&term).is_err());
+  }
+}
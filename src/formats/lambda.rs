@@ -0,0 +1,168 @@
+use crate::{
+  formats::{
+    resolve,
+    Names,
+    Source,
+  },
+  name::Name,
+  term::{
+    Refs,
+    Term,
+  },
+};
+
+// The plain untyped lambda calculus, as in `\f x. f (f x)`: a lambda is a
+// backslash or `λ`, one or more names, a dot and its body, which extends as
+// far as possible, and application is juxtaposition. Names are letters,
+// digits, underscores and primes, starting with a letter or an underscore.
+// Only variables, lambdas, applications and references can be written back.
+
+fn is_start(c: char) -> bool { c.is_alphabetic() || c == '_' }
+
+fn is_cont(c: char) -> bool { c.is_alphanumeric() || c == '_' || c == '\'' }
+
+fn parse_expr(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  src.skip_space();
+  if src.eat("\\") || src.eat("λ") {
+    let mut nams = Vec::new();
+    loop {
+      src.skip_space();
+      match src.ident(is_start, is_cont) {
+        Some(nam) => nams.push(nam),
+        None if nams.is_empty() => return Err(src.error("Expected a name")),
+        None => break,
+      }
+    }
+    src.expect(".")?;
+    let len = ctx.len();
+    ctx.extend(nams.iter().cloned());
+    let bod = parse_expr(src, ctx, refs);
+    ctx.truncate(len);
+    let lam = |bod, nam| Term::Lam(None, Name::from(nam), Box::new(bod));
+    Ok(nams.into_iter().rev().fold(bod?, lam))
+  }
+  else {
+    let mut fun = parse_atom(src, ctx, refs)?;
+    loop {
+      src.skip_space();
+      match src.peek() {
+        None | Some(')') => return Ok(fun),
+        Some('\\') | Some('λ') => {
+          let arg = parse_expr(src, ctx, refs)?;
+          return Ok(Term::App(None, Box::new((fun, arg))));
+        }
+        _ => {
+          let arg = parse_atom(src, ctx, refs)?;
+          fun = Term::App(None, Box::new((fun, arg)));
+        }
+      }
+    }
+  }
+}
+
+fn parse_atom(
+  src: &mut Source,
+  ctx: &mut Vec<String>,
+  refs: &Refs,
+) -> Result<Term, String> {
+  src.skip_space();
+  if src.eat("(") {
+    let term = parse_expr(src, ctx, refs)?;
+    src.skip_space();
+    src.expect(")")?;
+    return Ok(term);
+  }
+  match src.ident(is_start, is_cont) {
+    Some(nam) => resolve(&nam, ctx, refs)
+      .ok_or_else(|| src.error(&format!("Unbound variable {}", nam))),
+    None => Err(src.error("Expected a name, a lambda or a parenthesis")),
+  }
+}
+
+// Read a term of the untyped lambda calculus
+pub fn parse(input: &str, refs: &Refs) -> Result<Term, String> {
+  let mut src = Source::new(input);
+  let term = parse_expr(&mut src, &mut Vec::new(), refs)?;
+  src.skip_space();
+  if src.at_end() { Ok(term) } else { Err(src.error("Unexpected input")) }
+}
+
+// Print a term at a precedence of 0 for the body of a lambda, 1 for the
+// function of an application and 2 for its argument
+fn print_term(
+  term: &Term,
+  names: &mut Names,
+  prec: u8,
+) -> Result<String, String> {
+  match term {
+    Term::Var(_, _, idx) => Ok(names.var(*idx)?.clone()),
+    Term::Ref(_, nam, ..) => Ok(nam.to_string()),
+    Term::Lam(..) => {
+      let mut bod = term;
+      let mut nams = Vec::new();
+      while let Term::Lam(_, nam, next) = bod {
+        nams.push(names.bind(nam));
+        bod = next;
+      }
+      let bod = print_term(bod, names, 0);
+      for _ in &nams {
+        names.unbind();
+      }
+      let lam = format!("\\{}. {}", nams.join(" "), bod?);
+      Ok(if prec > 0 { format!("({})", lam) } else { lam })
+    }
+    Term::App(_, terms) => {
+      let fun = print_term(&terms.0, names, 1)?;
+      let arg = print_term(&terms.1, names, 2)?;
+      let app = format!("{} {}", fun, arg);
+      Ok(if prec > 1 { format!("({})", app) } else { app })
+    }
+    _ => Err(format!("{} is not a term of the untyped lambda calculus", term)),
+  }
+}
+
+// Write a term of the untyped lambda calculus
+pub fn print(term: &Term) -> Result<String, String> {
+  print_term(term, &mut Names::new(term), 0)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::parse::term::parse as parse_yatima;
+  use im::HashMap;
+
+  #[test]
+  fn parses_lambda_terms() {
+    let refs = HashMap::new();
+    let (_, two) = parse_yatima("λ f x => f (f x)").unwrap();
+    assert_eq!(parse("\\f x. f (f x)", &refs), Ok(two.clone()));
+    assert_eq!(parse("λf. λx. f (f x) -- two", &refs), Ok(two));
+    // A lambda extends as far as possible, even as an argument
+    let (_, app) = parse_yatima("λ x => x (λ y => y x)").unwrap();
+    assert_eq!(parse("\\x. x \\y. y x", &refs), Ok(app));
+    let (_, app) = parse_yatima("λ x => x (λ y => y) x").unwrap();
+    assert_eq!(parse("\\x. x (\\y. y) x", &refs), Ok(app));
+    assert!(parse("\\x. y", &refs).is_err());
+    assert!(parse("\\x. x)", &refs).is_err());
+  }
+
+  #[test]
+  fn prints_lambda_terms() {
+    let refs = HashMap::new();
+    let inputs = ["\\f x. f (f x)", "\\x. x (\\y. y) x", "(\\x. x x) (\\x. x)"];
+    for input in &inputs {
+      let term = parse(input, &refs).unwrap();
+      assert_eq!(print(&term).as_deref(), Ok(*input));
+    }
+    // Shadowing binders are renamed apart
+    let (_, term) = parse_yatima("λ x x => x").unwrap();
+    assert_eq!(print(&term).as_deref(), Ok("\\x x1. x1"));
+    let (_, term) = parse_yatima("λ x => #add x 1").unwrap();
+    assert!(print(&term).is_err());
+  }
+}
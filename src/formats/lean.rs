@@ -0,0 +1,335 @@
+use crate::{
+  formats::{
+    occurs,
+    Names,
+  },
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Def,
+    LitType,
+    Link,
+    Literal,
+    PrimOp,
+    Term,
+    Uses,
+  },
+  unembed_error::UnembedError,
+};
+
+use std::collections::HashSet;
+
+// Lean 4 source translated from checked packages, so that what is proven in
+// Yatima can be checked again by a mature proof assistant. The translation
+// is best effort: functions, dependent function and pair types, universes,
+// `let`s, literals and the arithmetic primitives have direct counterparts,
+// and quantities are dropped, as every variable of Lean may be used freely.
+// Self types, data and case, which Lean's inductive types replace, and
+// anything else without a counterpart, are translated as `sorry`, noted in a
+// comment above the definition, so that the rest can still be checked. A
+// package is a namespace, after those of the packages it opens, which it
+// opens in turn.
+
+const KEYWORDS: [&str; 24] = [
+  "by", "def", "do", "else", "end", "example", "fun", "have", "if", "import",
+  "in", "inductive", "instance", "let", "match", "namespace", "open",
+  "section", "show", "structure", "then", "theorem", "where", "with",
+];
+
+// A name as a Lean identifier, escaped if it is a keyword or has characters
+// Lean does not allow in one
+fn ident(nam: &str) -> String {
+  let plain = nam.split('.').all(|part| {
+    let mut cs = part.chars();
+    matches!(cs.next(), Some(c) if c.is_alphabetic() || c == '_')
+      && cs.all(|c| c.is_alphanumeric() || c == '_' || c == '\'')
+  });
+  if plain && !KEYWORDS.contains(&nam) && nam != "_" {
+    String::from(nam)
+  }
+  else {
+    format!("«{}»", nam)
+  }
+}
+
+fn quote(s: &str, delim: char) -> String {
+  let mut res = String::new();
+  res.push(delim);
+  for c in s.chars() {
+    match c {
+      '\\' => res.push_str("\\\\"),
+      '\n' => res.push_str("\\n"),
+      '\t' => res.push_str("\\t"),
+      '\r' => res.push_str("\\r"),
+      c if c == delim => {
+        res.push('\\');
+        res.push(c);
+      }
+      c => res.push(c),
+    }
+  }
+  res.push(delim);
+  res
+}
+
+// The Lean function of a primitive operation, for those which have one
+fn primop(op: PrimOp) -> Option<&'static str> {
+  match op {
+    PrimOp::Add => Some("HAdd.hAdd"),
+    PrimOp::Sub => Some("HSub.hSub"),
+    PrimOp::Mul => Some("HMul.hMul"),
+    PrimOp::Div => Some("HDiv.hDiv"),
+    PrimOp::Mod => Some("HMod.hMod"),
+    PrimOp::Cat => Some("HAppend.hAppend"),
+    PrimOp::Suc => Some("Nat.succ"),
+    PrimOp::Pre => Some("Nat.pred"),
+    _ => None,
+  }
+}
+
+struct Translator {
+  names: Names,
+  // What could not be translated, each once
+  notes: Vec<String>,
+}
+
+impl Translator {
+  fn note(&mut self, what: String) {
+    if !self.notes.contains(&what) {
+      self.notes.push(what);
+    }
+  }
+
+  fn sorry(&mut self, what: String) -> String {
+    self.note(what);
+    String::from("sorry")
+  }
+
+  fn bind(&mut self, nam: &str) -> String {
+    self.names.bind(if nam == "_" { "" } else { nam })
+  }
+
+  // The body of a binder, translated with its name in scope
+  fn body(&mut self, nam: &str, bod: &Term) -> (String, String) {
+    let nam = self.bind(nam);
+    let bod = self.term(bod, 0);
+    self.names.unbind();
+    (ident(&nam), bod)
+  }
+
+  // Translate a term at a precedence of 0 for an expression, 1 for the
+  // function of an application and 2 for its argument
+  fn term(&mut self, term: &Term, prec: u8) -> String {
+    let wrap = |lvl: u8, s: String| {
+      if prec > lvl { format!("({})", s) } else { s }
+    };
+    match term {
+      Term::Var(_, _, idx) => match self.names.var(*idx).map(|n| ident(n)) {
+        Ok(nam) => nam,
+        Err(e) => self.sorry(e),
+      },
+      Term::Ref(_, nam, ..) => ident(nam),
+      Term::Lam(_, nam, bod) => {
+        let (nam, bod) = self.body(nam, bod);
+        wrap(0, format!("fun {} => {}", nam, bod))
+      }
+      Term::App(_, terms) => {
+        let fun = self.term(&terms.0, 1);
+        let arg = self.term(&terms.1, 2);
+        wrap(1, format!("{} {}", fun, arg))
+      }
+      Term::All(_, uses, nam, terms) => {
+        if *uses != Uses::Many {
+          self.note(String::from("quantities are dropped"));
+        }
+        let (dom, cod) = &**terms;
+        if !occurs(cod, 0) {
+          let dom = self.term(dom, 1);
+          let (_, cod) = self.body(nam, cod);
+          wrap(0, format!("{} → {}", dom, cod))
+        }
+        else {
+          let dom = self.term(dom, 0);
+          let (nam, cod) = self.body(nam, cod);
+          wrap(0, format!("({} : {}) → {}", nam, dom, cod))
+        }
+      }
+      Term::Sig(_, nam, terms) => {
+        let dom = self.term(&terms.0, 0);
+        let (nam, cod) = self.body(nam, &terms.1);
+        wrap(0, format!("({} : {}) × {}", nam, dom, cod))
+      }
+      Term::Par(_, terms) => {
+        let fst = self.term(&terms.0, 0);
+        let snd = self.term(&terms.1, 0);
+        format!("⟨{}, {}⟩", fst, snd)
+      }
+      Term::Fst(_, bod) => format!("{}.1", self.term(bod, 2)),
+      Term::Snd(_, bod) => format!("{}.2", self.term(bod, 2)),
+      Term::Let(_, false, _, nam, terms) => {
+        let (typ, exp, bod) = &**terms;
+        let typ = match typ {
+          Term::Hol(..) => String::new(),
+          typ => format!(" : {}", self.term(typ, 0)),
+        };
+        let exp = self.term(exp, 0);
+        let (nam, bod) = self.body(nam, bod);
+        wrap(0, format!("let {}{} := {}; {}", nam, typ, exp, bod))
+      }
+      Term::Let(_, true, ..) => {
+        self.sorry(String::from("a recursive let has no counterpart"))
+      }
+      Term::Ann(_, terms) => {
+        let trm = self.term(&terms.1, 0);
+        let typ = self.term(&terms.0, 0);
+        format!("({} : {})", trm, typ)
+      }
+      Term::Typ(_, 0) => String::from("Type"),
+      Term::Typ(_, lvl) => wrap(1, format!("Type {}", lvl)),
+      Term::Prp(_) => String::from("Prop"),
+      Term::Lit(_, lit) => match lit {
+        Literal::Natural(x) => x.to_string(),
+        Literal::Integer(x) => format!("({} : Int)", x),
+        Literal::Text(x) => quote(x, '"'),
+        Literal::Char(x) => quote(&x.to_string(), '\''),
+        Literal::Float(_) => format!("({} : Float)", lit),
+        Literal::BitString(_) => {
+          self.sorry(String::from("bit strings have no counterpart"))
+        }
+      },
+      Term::LTy(_, typ) => match typ {
+        LitType::Natural => String::from("Nat"),
+        LitType::Integer => String::from("Int"),
+        LitType::Text => String::from("String"),
+        LitType::Char => String::from("Char"),
+        LitType::Float => String::from("Float"),
+        LitType::BitString => {
+          self.sorry(String::from("bit strings have no counterpart"))
+        }
+      },
+      Term::Opr(_, op) => match primop(*op) {
+        Some(fun) => String::from(fun),
+        None => self.sorry(format!("{} has no counterpart", op.symbol())),
+      },
+      Term::Hol(..) | Term::Met(..) => String::from("_"),
+      Term::Slf(..) | Term::Dat(..) | Term::Cse(..) => self.sorry(
+        String::from("self types, data and case have no counterpart"),
+      ),
+    }
+  }
+}
+
+// A definition as a Lean `def`, or a `partial def` if it refers to itself
+pub fn def(def: &Def) -> String {
+  let mut tr = Translator { names: Names::new(&def.typ_), notes: Vec::new() };
+  let typ = tr.term(&def.typ_, 0);
+  // The term of a definition refers to the definition itself as the
+  // variable outside all of its binders
+  tr.names = Names::new(&def.term);
+  tr.names.bind(&def.name);
+  let term = tr.term(&def.term, 0);
+  let mut out = String::new();
+  if !def.docs.trim().is_empty() {
+    out.push_str(&format!("/-- {} -/\n", def.docs.trim()));
+  }
+  for note in &tr.notes {
+    out.push_str(&format!("-- Not translated: {}\n", note));
+  }
+  let kw = if occurs(&def.term, 0) { "partial def" } else { "def" };
+  out.push_str(&format!(
+    "{} {} : {} :=\n  {}\n",
+    kw,
+    ident(&def.name),
+    typ,
+    term
+  ));
+  out
+}
+
+// The package at `link`, and the packages it opens before it, each as a
+// Lean namespace
+pub fn package(link: Link) -> Result<String, UnembedError> {
+  fn go(
+    link: Link,
+    done: &mut HashSet<Link>,
+    out: &mut String,
+  ) -> Result<(), UnembedError> {
+    if !done.insert(link) {
+      return Ok(());
+    }
+    let pack = Package::get_link(link)?;
+    for decl in &pack.decls {
+      if let Declaration::Open { from, .. } = decl {
+        go(*from, done, out)?;
+      }
+    }
+    out.push_str(&format!("namespace {}\n\n", ident(&pack.name)));
+    for decl in &pack.decls {
+      match decl {
+        Declaration::Open { name, alias, .. } if alias.is_empty() => {
+          out.push_str(&format!("open {}\n\n", ident(name)));
+        }
+        // The names of a package opened under an alias are qualified by
+        // it, and are found if it is the package's own name
+        Declaration::Open { name, alias, .. } if alias != name => {
+          out.push_str(&format!("-- open {} as {}\n\n", name, alias));
+        }
+        Declaration::Open { .. } => (),
+        Declaration::Defn { defn, .. } => {
+          out.push_str(&def(&Def::get_link(*defn)?));
+          out.push('\n');
+        }
+        Declaration::Test { .. } => (),
+      }
+    }
+    out.push_str(&format!("end {}\n\n", ident(&pack.name)));
+    Ok(())
+  }
+  let mut out = String::from(
+    "-- Translated from Yatima, with `sorry` for what Lean has no \
+     counterpart of\n\n",
+  );
+  go(link, &mut HashSet::new(), &mut out)?;
+  Ok(out)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::term::parse,
+  };
+
+  fn def_of(name: &str, typ: &str, term: &str) -> Def {
+    let (_, typ) = parse(typ).unwrap();
+    let (_, term) = parse(term).unwrap();
+    Def::new(None, Name::from(name), String::new(), typ, term)
+  }
+
+  #[test]
+  fn translates_definitions() {
+    let id = def_of("id", "∀ (0 A: Type) (x: A) -> A", "λ A x => x");
+    assert_eq!(
+      def(&id),
+      "-- Not translated: quantities are dropped\n\
+       def id : (A : Type) → A → A :=\n  fun A => fun x => x\n"
+    );
+    let two = def_of("two", "#Natural", "let x: #Natural = 1; #add x x");
+    assert_eq!(
+      def(&two),
+      "def two : Nat :=\n  let x : Nat := 1; HAdd.hAdd x x\n"
+    );
+    let bits = def_of("bits", "#BitString", "#len \"a\"");
+    assert_eq!(
+      def(&bits),
+      "-- Not translated: bit strings have no counterpart\n\
+       -- Not translated: #len has no counterpart\n\
+       def bits : sorry :=\n  sorry \"a\"\n"
+    );
+    assert_eq!(ident("fun"), "«fun»");
+    assert_eq!(ident("Nat.add'"), "Nat.add'");
+  }
+}
@@ -0,0 +1,75 @@
+use std::{
+  ptr,
+  sync::{
+    atomic::{
+      AtomicPtr,
+      Ordering,
+    },
+    Once,
+  },
+};
+
+// A value of the process, made once, when it is first set or read, for the
+// statics of the configuration, the logger and the interned names. The value
+// is boxed, and the box freed if the global itself is ever dropped.
+pub struct Global<T> {
+  once: Once,
+  value: AtomicPtr<T>,
+}
+
+// The value is only written once, under `once`, before any reference to it
+// is handed out
+unsafe impl<T: Send + Sync> Sync for Global<T> {}
+
+impl<T> Global<T> {
+  pub const fn new() -> Self {
+    Global { once: Once::new(), value: AtomicPtr::new(ptr::null_mut()) }
+  }
+
+  // The value, made with `make` if it is not yet
+  pub fn get_or_init(&self, make: impl FnOnce() -> T) -> &T {
+    self.once.call_once(|| {
+      let value = Box::into_raw(Box::new(make()));
+      self.value.store(value, Ordering::Release);
+    });
+    unsafe { &*self.value.load(Ordering::Acquire) }
+  }
+
+  // Set the value, or give it back if it already is
+  pub fn set(&self, value: T) -> Result<(), T> {
+    let mut value = Some(value);
+    self.get_or_init(|| value.take().expect("the value to set"));
+    match value {
+      None => Ok(()),
+      Some(value) => Err(value),
+    }
+  }
+}
+
+impl<T> Default for Global<T> {
+  fn default() -> Self { Self::new() }
+}
+
+impl<T> Drop for Global<T> {
+  fn drop(&mut self) {
+    let value = *self.value.get_mut();
+    if !value.is_null() {
+      drop(unsafe { Box::from_raw(value) });
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn sets_once() {
+    let global = Global::new();
+    assert_eq!(global.set(1), Ok(()));
+    assert_eq!(global.set(2), Err(2));
+    assert_eq!(*global.get_or_init(|| 3), 1);
+    let global: Global<Vec<u8>> = Global::default();
+    assert!(global.get_or_init(Vec::new).is_empty());
+  }
+}
@@ -0,0 +1,402 @@
+use crate::{
+  doc::{
+    pages,
+    references,
+  },
+  json::Json,
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Link,
+    Term,
+  },
+  unembed_error::UnembedError,
+};
+
+use std::{
+  fmt,
+  str::FromStr,
+};
+
+// Dependency graphs, of a stored package, by its link, for auditing and
+// visualizing what depends on what. The graph of a package is of the
+// packages it opens, transitively, and, optionally, of the definitions they
+// declare and the definitions each refers to in its type or term. Each node
+// is annotated with the link of its content, so that two graphs can be
+// compared by link as well as by name.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+  Dot,
+  Json,
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "dot" => Ok(Self::Dot),
+      "json" => Ok(Self::Json),
+      _ => Err(format!("Unknown graph format {}", s)),
+    }
+  }
+}
+
+impl fmt::Display for Format {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Dot => write!(f, "dot"),
+      Self::Json => write!(f, "json"),
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+  Package,
+  Def,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+  pub kind: Kind,
+  // The name of a package, or of a definition qualified by its package
+  pub name: String,
+  pub link: Link,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+  // From a package to a package it opens
+  Opens,
+  // From a package to a definition it declares
+  Declares,
+  // From a definition to a definition its type or term refers to
+  RefersTo,
+}
+
+impl Edge {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Opens => "opens",
+      Self::Declares => "declares",
+      Self::RefersTo => "refers to",
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Graph {
+  pub nodes: Vec<Node>,
+  // Each edge by the links of the nodes it is from and to
+  pub edges: Vec<(Link, Link, Edge)>,
+}
+
+impl Graph {
+  fn node(&mut self, kind: Kind, name: String, link: Link) {
+    if !self.nodes.iter().any(|node| node.link == link) {
+      self.nodes.push(Node { kind, name, link });
+    }
+  }
+
+  fn edge(&mut self, from: Link, to: Link, edge: Edge) {
+    if !self.edges.contains(&(from, to, edge)) {
+      self.edges.push((from, to, edge));
+    }
+  }
+}
+
+// The graph of the package at `link` and the packages it opens, and, if
+// `defs`, of their definitions
+pub fn graph(link: Link, defs: bool) -> Result<Graph, UnembedError> {
+  let pages = pages(link, true)?;
+  let mut graph = Graph::default();
+  for page in &pages {
+    graph.node(Kind::Package, page.package.name.clone(), page.link);
+  }
+  for page in &pages {
+    for decl in &page.package.decls {
+      if let Declaration::Open { from, .. } = decl {
+        graph.edge(page.link, *from, Edge::Opens);
+      }
+    }
+  }
+  if defs {
+    for page in &pages {
+      for (link, def) in &page.entries {
+        let name = format!("{}.{}", page.package.name, def.name);
+        graph.node(Kind::Def, name, *link);
+        graph.edge(page.link, *link, Edge::Declares);
+      }
+    }
+    for page in &pages {
+      for (link, def) in &page.entries {
+        let mut refs = references(&def.typ_);
+        refs.extend(references(&def.term));
+        for (name, to) in refs {
+          // A definition which no package in the graph declares, such as
+          // one generated from a data type, is named as it is referred to
+          graph.node(Kind::Def, name, to);
+          graph.edge(*link, to, Edge::RefersTo);
+        }
+      }
+    }
+  }
+  Ok(graph)
+}
+
+fn escape(s: &str) -> String {
+  s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn quote(s: &str) -> String { format!("\"{}\"", escape(s)) }
+
+// The graph in the Graphviz DOT language, with nodes identified by link
+pub fn dot(graph: &Graph) -> String {
+  let mut out = String::from("digraph {\n");
+  for node in &graph.nodes {
+    let shape = match node.kind {
+      Kind::Package => "box",
+      Kind::Def => "ellipse",
+    };
+    out.push_str(&format!(
+      "  {} [label={}, shape={}];\n",
+      quote(&node.link.to_string()),
+      // The line break is DOT's own escape, so it is left unescaped
+      format!("\"{}\\n{}\"", escape(&node.name), node.link),
+      shape
+    ));
+  }
+  for (from, to, edge) in &graph.edges {
+    let style = match edge {
+      Edge::Opens => "solid",
+      Edge::Declares => "dotted",
+      Edge::RefersTo => "dashed",
+    };
+    out.push_str(&format!(
+      "  {} -> {} [label={}, style={}];\n",
+      quote(&from.to_string()),
+      quote(&to.to_string()),
+      quote(edge.name()),
+      style
+    ));
+  }
+  out.push_str("}\n");
+  out
+}
+
+pub fn json(graph: &Graph) -> Json {
+  let nodes = graph
+    .nodes
+    .iter()
+    .map(|node| {
+      let kind = match node.kind {
+        Kind::Package => "package",
+        Kind::Def => "definition",
+      };
+      Json::obj(vec![
+        ("kind", kind.into()),
+        ("name", node.name.as_str().into()),
+        ("link", Json::str(node.link)),
+      ])
+    })
+    .collect();
+  let edges = graph
+    .edges
+    .iter()
+    .map(|(from, to, edge)| {
+      Json::obj(vec![
+        ("from", Json::str(from)),
+        ("to", Json::str(to)),
+        ("kind", edge.name().into()),
+      ])
+    })
+    .collect();
+  Json::obj(vec![("nodes", Json::Arr(nodes)), ("edges", Json::Arr(edges))])
+}
+
+// The graph rendered in `format`
+pub fn render(graph: &Graph, format: Format) -> String {
+  match format {
+    Format::Dot => dot(graph),
+    Format::Json => format!("{}\n", json(graph)),
+  }
+}
+
+impl Package {
+  // The graph of the package, which must be stored, and the packages it
+  // opens, and, if `defs`, of their definitions, in the DOT language
+  pub fn to_dot(&self, defs: bool) -> Result<String, UnembedError> {
+    let link = self.clone().encode().link();
+    Ok(dot(&graph(link, defs)?))
+  }
+}
+
+// The label of a term's node, and its immediate subterms, each with the
+// label of its edge and whether it is in scope of the term's binder
+fn children(term: &Term) -> (String, Vec<(&'static str, &Term, bool)>) {
+  let label = |s: &str| String::from(s);
+  match term {
+    Term::Var(_, nam, _) => (nam.to_string(), vec![]),
+    Term::Lam(_, nam, bod) => {
+      (format!("λ {}", nam), vec![("body", &**bod, true)])
+    }
+    Term::App(_, terms) => {
+      (label("@"), vec![("fun", &terms.0, false), ("arg", &terms.1, false)])
+    }
+    Term::All(_, _, nam, terms) => {
+      (format!("∀ {}", nam), vec![
+        ("dom", &terms.0, false),
+        ("cod", &terms.1, true),
+      ])
+    }
+    Term::Sig(_, nam, terms) => {
+      (format!("Σ {}", nam), vec![
+        ("dom", &terms.0, false),
+        ("cod", &terms.1, true),
+      ])
+    }
+    Term::Slf(_, nam, bod) => {
+      (format!("self {}", nam), vec![("body", &**bod, true)])
+    }
+    Term::Dat(_, bod) => (label("data"), vec![("body", &**bod, false)]),
+    Term::Cse(_, bod) => (label("case"), vec![("body", &**bod, false)]),
+    Term::Par(_, terms) => {
+      (label("pair"), vec![("fst", &terms.0, false), ("snd", &terms.1, false)])
+    }
+    Term::Fst(_, bod) => (label("fst"), vec![("body", &**bod, false)]),
+    Term::Snd(_, bod) => (label("snd"), vec![("body", &**bod, false)]),
+    Term::Let(_, rec, _, nam, terms) => {
+      let kw = if *rec { "letrec" } else { "let" };
+      (format!("{} {}", kw, nam), vec![
+        ("type", &terms.0, false),
+        ("expr", &terms.1, *rec),
+        ("body", &terms.2, true),
+      ])
+    }
+    Term::Ann(_, terms) => {
+      (label("::"), vec![("type", &terms.0, false), ("term", &terms.1, false)])
+    }
+    _ => (format!("{}", term), vec![]),
+  }
+}
+
+impl Term {
+  // The binder and application structure of the term in the DOT language: a
+  // node for each subterm, an edge to each of its immediate subterms, and a
+  // dashed edge from each bound variable back to its binder
+  pub fn to_dot(&self) -> String {
+    fn go(
+      term: &Term,
+      binders: &mut Vec<usize>,
+      count: &mut usize,
+      out: &mut String,
+    ) -> usize {
+      let id = *count;
+      *count += 1;
+      let (label, subterms) = children(term);
+      out.push_str(&format!("  n{} [label={}];\n", id, quote(&label)));
+      if let Term::Var(_, _, idx) = term {
+        let idx = *idx as usize;
+        if idx < binders.len() {
+          let binder = binders[binders.len() - 1 - idx];
+          out.push_str(&format!("  n{} -> n{} [style=dashed];\n", id, binder));
+        }
+      }
+      for (edge, subterm, bound) in subterms {
+        if bound {
+          binders.push(id);
+        }
+        let sub = go(subterm, binders, count, out);
+        if bound {
+          binders.pop();
+        }
+        let edge = quote(edge);
+        out.push_str(&format!("  n{} -> n{} [label={}];\n", id, sub, edge));
+      }
+      id
+    }
+    let mut out = String::from("digraph {\n");
+    go(self, &mut Vec::new(), &mut 0, &mut out);
+    out.push_str("}\n");
+    out
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::query::Database;
+  use std::{
+    env,
+    fs,
+  };
+
+  #[test]
+  fn graphs() {
+    let dir = env::temp_dir().join("yatima-graph");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+      dir.join("GraphLib.ya"),
+      "package GraphLib where\ndef one : #Natural = 1\n",
+    )
+    .unwrap();
+    let path = dir.join("Graph.ya");
+    fs::write(
+      &path,
+      "package Graph where\nopen GraphLib\n\
+       def two : #Natural = #add one one\n",
+    )
+    .unwrap();
+    let parsed = Database::new().parse(&path).unwrap();
+    let lib = match &parsed.package.decls[0] {
+      Declaration::Open { from, .. } => *from,
+      _ => panic!("expected an open"),
+    };
+    let (one, _) = parsed.refs["one"];
+    let (two, _) = parsed.refs["two"];
+    let imports = graph(parsed.link, false).unwrap();
+    let names: Vec<&str> =
+      imports.nodes.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(names, vec!["Graph", "GraphLib"]);
+    assert_eq!(imports.edges, vec![(parsed.link, lib, Edge::Opens)]);
+    let defs = graph(parsed.link, true).unwrap();
+    assert_eq!(defs.nodes.len(), 4);
+    assert!(defs.edges.contains(&(parsed.link, two, Edge::Declares)));
+    assert!(defs.edges.contains(&(lib, one, Edge::Declares)));
+    assert!(defs.edges.contains(&(two, one, Edge::RefersTo)));
+    let dot = render(&defs, Format::Dot);
+    assert!(dot.starts_with("digraph {\n"));
+    assert!(dot.contains(&format!(
+      "  \"{}\" [label=\"Graph.two\\n{}\", shape=ellipse];\n",
+      two, two
+    )));
+    assert!(dot.contains(&format!(
+      "  \"{}\" -> \"{}\" [label=\"opens\", style=solid];\n",
+      parsed.link, lib
+    )));
+    assert_eq!(parsed.package.to_dot(true), Ok(dot));
+    let json = render(&imports, Format::Json);
+    assert!(json.starts_with(&format!(
+      "{{\"nodes\":[{{\"kind\":\"package\",\"name\":\"Graph\",\
+       \"link\":\"{}\"}}",
+      parsed.link
+    )));
+  }
+
+  #[test]
+  fn draws_terms() {
+    let (_, term) = crate::parse::term::parse("λ f x => f x").unwrap();
+    assert_eq!(
+      term.to_dot(),
+      "digraph {\n  n0 [label=\"λ f\"];\n  n1 [label=\"λ x\"];\n  n2 \
+       [label=\"@\"];\n  n3 [label=\"f\"];\n  n3 -> n0 [style=dashed];\n  n2 \
+       -> n3 [label=\"fun\"];\n  n4 [label=\"x\"];\n  n4 -> n1 \
+       [style=dashed];\n  n2 -> n4 [label=\"arg\"];\n  n1 -> n2 \
+       [label=\"body\"];\n  n0 -> n1 [label=\"body\"];\n}\n"
+    );
+  }
+}
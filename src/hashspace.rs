@@ -1,22 +1,57 @@
+#[cfg(feature = "native")]
+use crate::config;
+#[cfg(not(feature = "native"))]
+use crate::hashspace::cache::Cache;
+#[cfg(feature = "native")]
 use directories_next::ProjectDirs;
 use hashexpr::{
   link::Link,
   Expr,
 };
+#[cfg(not(feature = "native"))]
+use std::cell::RefCell;
+#[cfg(feature = "native")]
 use std::{
   fs,
   path::{
     Path,
     PathBuf,
   },
+  process,
+  sync::atomic::{
+    AtomicU64,
+    Ordering,
+  },
 };
 
 pub mod cache;
+pub mod certificate;
+#[cfg(feature = "native")]
+pub mod inspect;
+pub mod ipld;
+#[cfg(feature = "native")]
+pub mod remote;
+#[cfg(feature = "native")]
 pub mod server;
+pub mod wire;
+
+// Without the `native` feature, as in the browser, there is no filesystem to
+// keep the hashspace in, and it is kept in memory instead, for as long as the
+// program runs
+#[cfg(not(feature = "native"))]
+thread_local! {
+  static STORE: RefCell<Cache> = RefCell::new(Cache::new());
+}
 
-// TODO: Add custom directory option
-/// Returns the hashspace directory. This function panics if the directory
-/// cannot be created, read from or written to.
+// The number of temporary files written by `put` so far, which tells apart
+// those of different threads
+#[cfg(feature = "native")]
+static TEMPORARIES: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the hashspace directory, the configured one if there is one. This
+/// function panics if the directory cannot be created, read from or written
+/// to.
+#[cfg(feature = "native")]
 pub fn hashspace_directory() -> PathBuf {
   let proj_dir =
     ProjectDirs::from("io", "yatima", "hashspace")
@@ -25,7 +60,10 @@ pub fn hashspace_directory() -> PathBuf {
         operating system. Please open an issue at \
         \"https://github.com/yatima-inc/yatima/issues\" \
         if you see this message.");
-  let path = proj_dir.cache_dir();
+  let path = match &config::get().hashspace {
+    Some(dir) => dir.as_path(),
+    None => proj_dir.cache_dir(),
+  };
   match fs::read_dir(&path) {
     Ok(_) => (),
     Err(_) => {
@@ -34,7 +72,9 @@ pub fn hashspace_directory() -> PathBuf {
            Please open an issue at \
           \"https://github.com/yatima-inc/yatima/issues\" \
           if you see this message", path.to_string_lossy()));
-      println!("Creating new hashspace at {}", path_name);
+      if config::verbosity() > config::Verbosity::Quiet {
+        eprintln!("Creating new hashspace at {}", path_name);
+      }
       fs::create_dir_all(path).expect(&format!(
         "Error: cannot create hashspace path {}, likely due to lacking \
          sufficient filesystem permissions. \
@@ -56,26 +96,71 @@ pub fn hashspace_directory() -> PathBuf {
   PathBuf::from(path)
 }
 
+// The expression at `link`, if it is stored. An entry which does not
+// deserialize, such as one truncated by an interrupted write, is as if it
+// were not stored, so that it can be put again.
+#[cfg(feature = "native")]
 pub fn get(link: Link) -> Option<Expr> {
   let dir = hashspace_directory();
   let path = dir.as_path().join(Path::new(&link.to_string()));
   let file = fs::read(path).ok()?;
-  // println!("file {:?}", file);
+  trace!(target: "hashspace", %link, "Read");
   match Expr::deserialize(&file) {
     Ok((_, x)) => Some(x),
-    Err(e) => panic!("deserialization error: {}", e),
+    Err(e) => {
+      warn!(target: "hashspace", %link, error = %e, "Cannot deserialize");
+      None
+    }
   }
 }
 
-pub fn put(expr: Expr) -> Link {
+#[cfg(feature = "native")]
+pub fn put(expr: Expr) -> Link { put_serialized(expr.serialize()) }
+
+// Store a text, such as the source of a package, without copying it into an
+// expression first
+#[cfg(feature = "native")]
+pub fn put_text(text: &str) -> Link {
+  put_serialized(Expr::serialize_text(text))
+}
+
+// An expression is written to a temporary file beside its entry, which then
+// replaces the entry, so that threads and processes putting and getting the
+// same expression at once never read an entry which is partly written
+#[cfg(feature = "native")]
+fn put_serialized(bytes: Vec<u8>) -> Link {
   let dir = hashspace_directory();
-  let link = expr.link();
+  let link = Link::make(&bytes);
   let path = dir.as_path().join(Path::new(&link.to_string()));
-  fs::write(path, expr.serialize()).expect(&format!(
+  let count = TEMPORARIES.fetch_add(1, Ordering::Relaxed);
+  let temp = format!(".{}.{}.{}", link, process::id(), count);
+  let temp = dir.as_path().join(Path::new(&temp));
+  let res = fs::write(&temp, bytes).and_then(|()| fs::rename(&temp, path));
+  res.expect(&format!(
     "Error: cannot write to hashspace path {}. \
      Please open an issue at \
      \"https://github.com/yatima-inc/yatima/issues\" \
      if you see this message",
     link));
+  trace!(target: "hashspace", %link, "Stored");
   link
 }
+
+#[cfg(not(feature = "native"))]
+pub fn get(link: Link) -> Option<Expr> {
+  let expr = STORE.with(|store| store.borrow().get(&link).cloned())?;
+  trace!(target: "hashspace", %link, "Read");
+  Some(expr)
+}
+
+#[cfg(not(feature = "native"))]
+pub fn put(expr: Expr) -> Link {
+  let link = expr.link();
+  STORE.with(|store| store.borrow_mut().insert(link, expr));
+  trace!(target: "hashspace", %link, "Stored");
+  link
+}
+
+// A text kept in memory is kept as an expression, so it is copied into one
+#[cfg(not(feature = "native"))]
+pub fn put_text(text: &str) -> Link { put(Expr::from_string(text)) }
@@ -0,0 +1,111 @@
+use crate::{
+  core::check::{
+    self,
+    CheckError,
+  },
+  decode_error::{
+    DecodeError,
+    Expected,
+  },
+  hashspace,
+  term::{
+    Def,
+    Defs,
+    Link,
+  },
+};
+
+use hashexpr::{
+  atom,
+  atom::Atom::*,
+  Expr,
+  Expr::{
+    Atom,
+    Cons,
+  },
+};
+
+// Typechecking certificates. Once the definition at link `L` checks with the
+// type at link `T`, the record `("checked" L T)` is stored in the hashspace.
+// Since a definition refers to the definitions it depends on by their links,
+// whether it checks is fixed by its own link, and a definition whose
+// certificate is present need not be checked again. The link of a
+// certificate is determined by the definition alone, so finding one is a
+// single lookup.
+//
+// Certificates are not signed, so they are only as trustworthy as the
+// hashspace they are read from.
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Certificate {
+  pub def: Link,
+  pub typ: Link,
+}
+
+impl Certificate {
+  // The certificate a definition would have once checked
+  pub fn of(def: &Def) -> Self {
+    let (d, ..) = def.clone().embed();
+    Certificate { typ: d.type_anon, def: d.encode().link() }
+  }
+
+  pub fn encode(self) -> Expr {
+    cons!(None, text!("checked"), link!(self.def), link!(self.typ))
+  }
+
+  pub fn decode(expr: Expr) -> Result<Self, DecodeError> {
+    match expr {
+      Cons(pos, xs) => match xs.as_slice() {
+        [Atom(_, Text(c)), Atom(_, Link(def)), Atom(_, Link(typ))]
+          if *c == String::from("checked") =>
+        {
+          Ok(Certificate { def: *def, typ: *typ })
+        }
+        _ => Err(DecodeError::new(pos, vec![Expected::Certificate])),
+      },
+      _ => Err(DecodeError::new(expr.position(), vec![Expected::Certificate])),
+    }
+  }
+
+  // Whether the certificate is stored in the hashspace
+  pub fn is_stored(&self) -> bool {
+    hashspace::get(self.clone().encode().link()).is_some()
+  }
+
+  pub fn store(self) -> Link { hashspace::put(self.encode()) }
+}
+
+// Check a definition, unless its certificate is already stored, and store its
+// certificate once it checks. Returns whether the definition was checked.
+pub fn check_def(defs: &Defs, def: &Def) -> Result<bool, CheckError> {
+  let cert = Certificate::of(def);
+  if cert.is_stored() {
+    return Ok(false);
+  }
+  check::check_def(defs, def)?;
+  cert.store();
+  Ok(true)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::term::parse,
+  };
+
+  #[test]
+  fn encodes() {
+    let (_, typ) = parse("∀ (A: Type) (x: A) -> A").unwrap();
+    let (_, term) = parse("λ A x => x").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, term);
+    let cert = Certificate::of(&def);
+    let (d, ..) = def.embed();
+    assert_eq!(cert.def, d.clone().encode().link());
+    assert_eq!(cert.typ, d.type_anon);
+    let expr = cert.clone().encode();
+    assert_eq!(Certificate::decode(expr), Ok(cert));
+    assert!(Certificate::decode(text!("checked")).is_err());
+  }
+}
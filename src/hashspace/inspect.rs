@@ -0,0 +1,220 @@
+use crate::{
+  anon_term::AnonTerm,
+  definition::Definition,
+  hashspace::{
+    self,
+    certificate::Certificate,
+    hashspace_directory,
+  },
+  package::{
+    Declaration,
+    Package,
+  },
+  positions::Positions,
+  term::{
+    Def,
+    Link,
+  },
+  unembed_error::UnembedError,
+};
+
+use hashexpr::{
+  atom::Atom,
+  Expr,
+};
+
+use std::{
+  fmt,
+  fs,
+};
+
+// Inspecting what is stored at a link. What a link is of is told by
+// decoding it: a package, a definition, the source positions of one, a
+// typechecking certificate or the text of a source. The type and term of a
+// definition are stored without their names, as anonymous terms, and are
+// told apart by the definition referring to them, if there is one in the
+// local hashspace, which also gives them back their names.
+
+#[derive(Clone, Debug)]
+pub enum Content {
+  Package(Package),
+  Def(Def),
+  // The type or term of the definition at a link
+  Type(Link, Def),
+  Term(Link, Def),
+  // An anonymous term which no stored definition refers to
+  Anon(AnonTerm),
+  Certificate(Certificate),
+  Positions(Positions),
+  Source(String),
+  Expr(Expr),
+}
+
+impl Content {
+  // What sort of content it is
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Self::Package(_) => "package",
+      Self::Def(_) => "definition",
+      Self::Type(..) => "type",
+      Self::Term(..) => "term",
+      Self::Anon(_) => "anonymous term",
+      Self::Certificate(_) => "certificate",
+      Self::Positions(_) => "positions",
+      Self::Source(_) => "source",
+      Self::Expr(_) => "expression",
+    }
+  }
+}
+
+impl fmt::Display for Content {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Package(p) => {
+        write!(f, "package {}", p.name)?;
+        if !p.docs.is_empty() {
+          write!(f, "\n  docs: {}", p.docs)?;
+        }
+        write!(f, "\n  source: {}", p.source)?;
+        for decl in &p.decls {
+          match decl {
+            Declaration::Defn { name, defn, .. } => {
+              write!(f, "\n  def {} {}", name, defn)?;
+            }
+            Declaration::Open { name, alias, from, .. } => {
+              if alias.is_empty() {
+                write!(f, "\n  open {} {}", name, from)?;
+              }
+              else {
+                write!(f, "\n  open {} as {} {}", name, alias, from)?;
+              }
+            }
+            Declaration::Test { name, test, .. } => {
+              write!(f, "\n  test {} {}", name, test)?;
+            }
+          }
+        }
+        Ok(())
+      }
+      Self::Def(def) => write!(f, "{}", def),
+      Self::Type(link, def) => {
+        write!(f, "{}\n  the type of {} {}", def.typ_, def.name, link)
+      }
+      Self::Term(link, def) => {
+        write!(f, "{}\n  the term of {} {}", def.term, def.name, link)
+      }
+      Self::Anon(anon) => write!(f, "{}", anon),
+      Self::Certificate(cert) => {
+        write!(f, "{} checks with the type {}", cert.def, cert.typ)
+      }
+      Self::Positions(posns) => {
+        match posns.def {
+          Some(pos) => write!(f, "positions of a definition at {}", pos)?,
+          None => write!(f, "positions of a definition")?,
+        }
+        let count = |posns: &[Option<_>]| posns.iter().flatten().count();
+        write!(f, "\n  {} in its type", count(&posns.typ))?;
+        write!(f, "\n  {} in its term", count(&posns.term))
+      }
+      Self::Source(source) => write!(f, "{}", source),
+      Self::Expr(expr) => write!(f, "{}", expr),
+    }
+  }
+}
+
+// The link of a definition in the local hashspace whose type or term is the
+// anonymous term at `link`, and whether it is its type
+fn owner(link: Link) -> Option<(Link, bool)> {
+  let entries = fs::read_dir(hashspace_directory()).ok()?;
+  for entry in entries.filter_map(|entry| entry.ok()) {
+    let name = entry.file_name();
+    let def_link = match Link::parse(&name.to_string_lossy()) {
+      Ok((_, def_link)) => def_link,
+      Err(_) => continue,
+    };
+    let def = hashspace::get(def_link).map(Definition::decode);
+    if let Some(Ok(def)) = def {
+      if def.type_anon == link || def.term_anon == link {
+        return Some((def_link, def.type_anon == link));
+      }
+    }
+  }
+  None
+}
+
+// What is stored at `link`
+pub fn inspect(link: Link) -> Result<Content, UnembedError> {
+  let expr = hashspace::get(link).ok_or(UnembedError::UnknownLink(link))?;
+  if let Ok(package) = Package::decode(expr.clone()) {
+    return Ok(Content::Package(package));
+  }
+  if Definition::decode(expr.clone()).is_ok() {
+    return Ok(Content::Def(Def::get_link(link)?));
+  }
+  if let Ok(cert) = Certificate::decode(expr.clone()) {
+    return Ok(Content::Certificate(cert));
+  }
+  if let Ok(posns) = Positions::decode(expr.clone()) {
+    return Ok(Content::Positions(posns));
+  }
+  if let Expr::Atom(_, Atom::Text(source)) = &expr {
+    return Ok(Content::Source(source.clone()));
+  }
+  match AnonTerm::decode(expr.clone()) {
+    Ok(anon) => match owner(link) {
+      Some((def_link, typ)) => {
+        let def = Def::get_link(def_link)?;
+        if typ {
+          Ok(Content::Type(def_link, def))
+        }
+        else {
+          Ok(Content::Term(def_link, def))
+        }
+      }
+      None => Ok(Content::Anon(anon)),
+    },
+    Err(_) => Ok(Content::Expr(expr)),
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::query::Database;
+  use std::env;
+
+  #[test]
+  fn inspects() {
+    let dir = env::temp_dir().join("yatima-inspect");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("Inspect.ya");
+    let source = "package Inspect where\n\
+                  def inspected (A: Type) (x: A): A = x\n";
+    fs::write(&path, source).unwrap();
+    let parsed = Database::new().parse(&path).unwrap();
+    let package = inspect(parsed.link).unwrap();
+    assert_eq!(package.kind(), "package");
+    let (def_link, ast_link) = parsed.refs["inspected"];
+    let shown = format!("{}", package);
+    assert!(shown.contains(&format!("def inspected {}", def_link)));
+    let def = inspect(def_link).unwrap();
+    assert_eq!(def.kind(), "definition");
+    assert!(format!("{}", def).starts_with("def inspected : "));
+    let term = inspect(ast_link).unwrap();
+    assert_eq!(term.kind(), "term");
+    assert!(format!("{}", term).contains("the term of"));
+    let (d, ..) = parsed.defs[&def_link].clone().embed();
+    assert_eq!(inspect(d.type_anon).unwrap().kind(), "type");
+    let positions = parsed.package.decls.iter().find_map(|decl| match decl {
+      Declaration::Defn { positions, .. } => *positions,
+      _ => None,
+    });
+    let positions = inspect(positions.unwrap()).unwrap();
+    assert_eq!(positions.kind(), "positions");
+    let shown = format!("{}", positions);
+    assert!(shown.starts_with("positions of a definition at 2:"));
+    let source = inspect(parsed.package.source).unwrap();
+    assert_eq!(source.kind(), "source");
+    assert_eq!(format!("{}", source), fs::read_to_string(&path).unwrap());
+  }
+}
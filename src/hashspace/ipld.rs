@@ -0,0 +1,585 @@
+use crate::{
+  hashspace,
+  term::Link,
+};
+
+use hashexpr::{
+  atom,
+  atom::Atom,
+  Expr,
+};
+
+use num_bigint::{
+  BigInt,
+  BigUint,
+};
+
+use std::{
+  collections::HashMap,
+  convert::TryInto,
+  fmt,
+  str::FromStr,
+};
+
+// IPLD blocks, so that what is stored in the hashspace can be read by the
+// IPFS ecosystem. Each expression is a block encoded as DAG-CBOR: a list is
+// an array, text is a text string, bits are a byte string and a link is a
+// CID, tag 42, of the block of the expression it links to, so that links can
+// be followed by IPLD. Characters, naturals and integers, which DAG-CBOR has
+// no exact form for, are maps of one key, `char`, `nat` or `int`, to the text
+// of the character or the big-endian bytes of the number. A block is
+// addressed by a version 1 CID of its BLAKE3 hash, as a link is, but of the
+// block rather than of the expression, so the CID of an expression is not its
+// link. Reading a block back gives the very expression it was made of, and
+// so the same link. A package and its closure are exchanged as a CAR file, a
+// header naming the root followed by the blocks.
+
+// The multicodec of DAG-CBOR
+pub const DAG_CBOR: u64 = 0x71;
+// The multicodec of BLAKE3, as a multihash
+pub const BLAKE3: u64 = 0x1e;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IpldError {
+  // A link in a closure which the local hashspace does not have
+  Missing(Link),
+  // A CID which no block given has
+  UnknownCid(Cid),
+  // A block whose contents do not hash to its CID
+  Mismatch(Cid),
+  // A CID which is not of a BLAKE3 hash of a DAG-CBOR block
+  BadCid(String),
+  // A block or CAR file which cannot be decoded
+  Decode(String),
+}
+
+impl fmt::Display for IpldError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Missing(link) => write!(f, "Nothing is stored at {}", link),
+      Self::UnknownCid(cid) => write!(f, "No block has CID {}", cid),
+      Self::Mismatch(cid) => {
+        write!(f, "The block with CID {} is not what it addresses", cid)
+      }
+      Self::BadCid(e) => write!(f, "Unsupported CID: {}", e),
+      Self::Decode(e) => write!(f, "Invalid block: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for IpldError {}
+
+fn write_varint(mut x: u64, out: &mut Vec<u8>) {
+  while x >= 0x80 {
+    out.push((x as u8 & 0x7f) | 0x80);
+    x >>= 7;
+  }
+  out.push(x as u8);
+}
+
+fn read_varint(i: &[u8]) -> Option<(u64, &[u8])> {
+  let mut x = 0;
+  for (n, b) in i.iter().enumerate().take(10) {
+    x |= u64::from(b & 0x7f) << (7 * n);
+    if b & 0x80 == 0 {
+      return Some((x, &i[n + 1..]));
+    }
+  }
+  None
+}
+
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+// A CID of a block of DAG-CBOR, by the BLAKE3 hash of the block
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cid(pub [u8; 32]);
+
+impl Cid {
+  pub fn of(data: &[u8]) -> Self { Cid(*blake3::hash(data).as_bytes()) }
+
+  // The binary form, of version 1, the codec and the multihash
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(1, &mut out);
+    write_varint(DAG_CBOR, &mut out);
+    write_varint(BLAKE3, &mut out);
+    write_varint(32, &mut out);
+    out.extend_from_slice(&self.0);
+    out
+  }
+
+  // Read the binary form from the start of `i`, returning what follows it
+  pub fn read(i: &[u8]) -> Result<(Self, &[u8]), IpldError> {
+    let bad = |e: &str| IpldError::BadCid(String::from(e));
+    let (version, i) = read_varint(i).ok_or_else(|| bad("truncated"))?;
+    let (codec, i) = read_varint(i).ok_or_else(|| bad("truncated"))?;
+    let (hash, i) = read_varint(i).ok_or_else(|| bad("truncated"))?;
+    let (len, i) = read_varint(i).ok_or_else(|| bad("truncated"))?;
+    if version != 1 {
+      Err(bad(&format!("version {}", version)))
+    }
+    else if codec != DAG_CBOR {
+      Err(bad(&format!("codec {:#x}", codec)))
+    }
+    else if hash != BLAKE3 || len != 32 {
+      Err(bad(&format!("multihash {:#x} of {} bytes", hash, len)))
+    }
+    else if i.len() < 32 {
+      Err(bad("truncated"))
+    }
+    else {
+      Ok((Cid(i[..32].try_into().unwrap()), &i[32..]))
+    }
+  }
+}
+
+// In base32, with the multibase prefix `b`
+impl fmt::Display for Cid {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut out = String::from("b");
+    let (mut acc, mut bits) = (0u32, 0);
+    for b in self.to_bytes() {
+      acc = (acc << 8) | u32::from(b);
+      bits += 8;
+      while bits >= 5 {
+        bits -= 5;
+        out.push(BASE32[(acc >> bits) as usize & 31] as char);
+      }
+    }
+    if bits > 0 {
+      out.push(BASE32[(acc << (5 - bits)) as usize & 31] as char);
+    }
+    write!(f, "{}", out)
+  }
+}
+
+impl FromStr for Cid {
+  type Err = IpldError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let bad = || IpldError::BadCid(String::from(s));
+    let digits = s.strip_prefix('b').ok_or_else(bad)?;
+    let mut bytes = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in digits.bytes() {
+      let x = BASE32.iter().position(|d| *d == c).ok_or_else(bad)?;
+      acc = (acc << 5) | x as u32;
+      bits += 5;
+      if bits >= 8 {
+        bits -= 8;
+        bytes.push((acc >> bits) as u8);
+      }
+    }
+    match Cid::read(&bytes)? {
+      (cid, []) => Ok(cid),
+      _ => Err(bad()),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+  pub cid: Cid,
+  pub data: Vec<u8>,
+}
+
+// The head of a CBOR item, of major type `major` and argument `x`, in its
+// shortest form, as DAG-CBOR requires
+fn write_head(major: u8, x: u64, out: &mut Vec<u8>) {
+  let major = major << 5;
+  if x < 24 {
+    out.push(major | x as u8);
+  }
+  else if x <= 0xff {
+    out.push(major | 24);
+    out.push(x as u8);
+  }
+  else if x <= 0xffff {
+    out.push(major | 25);
+    out.extend_from_slice(&(x as u16).to_be_bytes());
+  }
+  else if x <= 0xffff_ffff {
+    out.push(major | 26);
+    out.extend_from_slice(&(x as u32).to_be_bytes());
+  }
+  else {
+    out.push(major | 27);
+    out.extend_from_slice(&x.to_be_bytes());
+  }
+}
+
+fn write_bytes(major: u8, bytes: &[u8], out: &mut Vec<u8>) {
+  write_head(major, bytes.len() as u64, out);
+  out.extend_from_slice(bytes);
+}
+
+// A map of the single key `key` to a byte or text string
+fn write_tagged(key: &str, major: u8, bytes: &[u8], out: &mut Vec<u8>) {
+  write_head(5, 1, out);
+  write_bytes(3, key.as_bytes(), out);
+  write_bytes(major, bytes, out);
+}
+
+// Encode an expression as DAG-CBOR, with the CID of each expression it links
+// to given by `cid`
+pub fn encode(
+  expr: &Expr,
+  cid: &impl Fn(Link) -> Option<Cid>,
+) -> Result<Vec<u8>, IpldError> {
+  fn go(
+    expr: &Expr,
+    cid: &impl Fn(Link) -> Option<Cid>,
+    out: &mut Vec<u8>,
+  ) -> Result<(), IpldError> {
+    match expr {
+      Expr::Cons(_, xs) => {
+        write_head(4, xs.len() as u64, out);
+        for x in xs {
+          go(x, cid, out)?;
+        }
+      }
+      Expr::Atom(_, Atom::Link(link)) => {
+        let cid = cid(*link).ok_or(IpldError::Missing(*link))?;
+        // A CID is a byte string of a zero byte and its binary form
+        let mut bytes = vec![0];
+        bytes.extend(cid.to_bytes());
+        write_head(6, 42, out);
+        write_bytes(2, &bytes, out);
+      }
+      Expr::Atom(_, Atom::Bits(x)) => write_bytes(2, x, out),
+      Expr::Atom(_, Atom::Text(x)) => write_bytes(3, x.as_bytes(), out),
+      Expr::Atom(_, Atom::Char(x)) => {
+        write_tagged("char", 3, x.to_string().as_bytes(), out)
+      }
+      Expr::Atom(_, Atom::Nat(x)) => {
+        write_tagged("nat", 2, &x.to_bytes_be(), out)
+      }
+      Expr::Atom(_, Atom::Int(x)) => {
+        write_tagged("int", 2, &x.to_signed_bytes_be(), out)
+      }
+    }
+    Ok(())
+  }
+  let mut out = Vec::new();
+  go(expr, cid, &mut out)?;
+  Ok(out)
+}
+
+// The major type and argument of the CBOR item at the start of `i`, and what
+// follows its head
+fn read_head(i: &[u8]) -> Result<(u8, u64, &[u8]), IpldError> {
+  let bad = || IpldError::Decode(String::from("truncated item"));
+  let (b, i) = i.split_first().ok_or_else(bad)?;
+  let (major, info) = (b >> 5, b & 31);
+  let len = match info {
+    0..=23 => return Ok((major, u64::from(info), i)),
+    24 => 1,
+    25 => 2,
+    26 => 4,
+    27 => 8,
+    _ => {
+      let e = format!("unsupported item {:#04x}", b);
+      return Err(IpldError::Decode(e));
+    }
+  };
+  if i.len() < len {
+    return Err(bad());
+  }
+  let x = i[..len].iter().fold(0, |x, b| (x << 8) | u64::from(*b));
+  Ok((major, x, &i[len..]))
+}
+
+fn read_bytes(major: u8, i: &[u8]) -> Result<(&[u8], &[u8]), IpldError> {
+  match read_head(i)? {
+    (m, len, i) if m == major && i.len() as u64 >= len => {
+      Ok(i.split_at(len as usize))
+    }
+    (m, ..) if m == major => {
+      Err(IpldError::Decode(String::from("truncated string")))
+    }
+    (m, ..) => {
+      let e = format!("expected major type {}, not {}", major, m);
+      Err(IpldError::Decode(e))
+    }
+  }
+}
+
+fn read_text(i: &[u8]) -> Result<(String, &[u8]), IpldError> {
+  let (bytes, i) = read_bytes(3, i)?;
+  match String::from_utf8(bytes.to_vec()) {
+    Ok(s) => Ok((s, i)),
+    Err(e) => Err(IpldError::Decode(e.to_string())),
+  }
+}
+
+// Decode an expression from DAG-CBOR, with the link of each CID in it given
+// by `link`
+pub fn decode(
+  data: &[u8],
+  link: &mut impl FnMut(&Cid) -> Option<Link>,
+) -> Result<Expr, IpldError> {
+  fn go<'a>(
+    i: &'a [u8],
+    link: &mut impl FnMut(&Cid) -> Option<Link>,
+  ) -> Result<(Expr, &'a [u8]), IpldError> {
+    match read_head(i)? {
+      (2, ..) => {
+        let (x, i) = read_bytes(2, i)?;
+        Ok((bits!(x.to_vec()), i))
+      }
+      (3, ..) => {
+        let (x, i) = read_text(i)?;
+        Ok((text!(x), i))
+      }
+      (4, len, mut i) => {
+        let mut xs = Vec::new();
+        for _ in 0..len {
+          let (x, rest) = go(i, link)?;
+          xs.push(x);
+          i = rest;
+        }
+        Ok((Expr::Cons(None, xs), i))
+      }
+      (5, 1, i) => {
+        let (key, i) = read_text(i)?;
+        match key.as_str() {
+          "char" => {
+            let (x, i) = read_text(i)?;
+            let mut cs = x.chars();
+            match (cs.next(), cs.next()) {
+              (Some(c), None) => Ok((char!(c), i)),
+              _ => Err(IpldError::Decode(format!("invalid char {:?}", x))),
+            }
+          }
+          "nat" => {
+            let (x, i) = read_bytes(2, i)?;
+            Ok((nat!(BigUint::from_bytes_be(x)), i))
+          }
+          "int" => {
+            let (x, i) = read_bytes(2, i)?;
+            Ok((int!(BigInt::from_signed_bytes_be(x)), i))
+          }
+          _ => Err(IpldError::Decode(format!("unexpected key {:?}", key))),
+        }
+      }
+      (6, 42, i) => match read_bytes(2, i)? {
+        ([0, bytes @ ..], i) => match Cid::read(bytes)? {
+          (cid, []) => {
+            let to = link(&cid).ok_or(IpldError::UnknownCid(cid))?;
+            Ok((link!(to), i))
+          }
+          _ => Err(IpldError::BadCid(String::from("trailing bytes"))),
+        },
+        _ => Err(IpldError::BadCid(String::from("no multibase prefix"))),
+      },
+      (major, x, _) => {
+        let e = format!("unexpected item of major type {} and {}", major, x);
+        Err(IpldError::Decode(e))
+      }
+    }
+  }
+  match go(data, link)? {
+    (expr, []) => Ok(expr),
+    _ => Err(IpldError::Decode(String::from("trailing bytes"))),
+  }
+}
+
+// The blocks of the closure of `link` in the local hashspace, each once, in
+// an order in which each block comes after those it links to, so that the
+// block of `link` is last
+pub fn export(link: Link) -> Result<Vec<Block>, IpldError> {
+  fn go(
+    link: Link,
+    cids: &mut HashMap<Link, Cid>,
+    blocks: &mut Vec<Block>,
+  ) -> Result<Cid, IpldError> {
+    if let Some(cid) = cids.get(&link) {
+      return Ok(*cid);
+    }
+    let expr = hashspace::get(link).ok_or(IpldError::Missing(link))?;
+    for next in links(&expr) {
+      go(next, cids, blocks)?;
+    }
+    let data = encode(&expr, &|link| cids.get(&link).copied())?;
+    let cid = Cid::of(&data);
+    cids.insert(link, cid);
+    blocks.push(Block { cid, data });
+    Ok(cid)
+  }
+  let mut blocks = Vec::new();
+  go(link, &mut HashMap::new(), &mut blocks)?;
+  Ok(blocks)
+}
+
+// The links in an expression
+fn links(expr: &Expr) -> Vec<Link> {
+  match expr {
+    Expr::Atom(_, Atom::Link(link)) => vec![*link],
+    Expr::Atom(..) => vec![],
+    Expr::Cons(_, xs) => xs.iter().flat_map(links).collect(),
+  }
+}
+
+// The CIDs in a block, each as often as it occurs
+fn cids(data: &[u8]) -> Result<Vec<Cid>, IpldError> {
+  let mut found = Vec::new();
+  decode(data, &mut |cid| {
+    found.push(*cid);
+    // Any link will do, as the expression is not kept
+    Some(Link::make(&cid.0))
+  })?;
+  Ok(found)
+}
+
+// Put the closure of the block `root` among `blocks` in the local hashspace,
+// in any order, checking each against its CID, and return the link of the
+// expression of `root`
+pub fn import(root: Cid, blocks: &[Block]) -> Result<Link, IpldError> {
+  fn go(
+    cid: Cid,
+    blocks: &HashMap<Cid, &[u8]>,
+    links: &mut HashMap<Cid, Link>,
+  ) -> Result<Link, IpldError> {
+    if let Some(link) = links.get(&cid) {
+      return Ok(*link);
+    }
+    let data = *blocks.get(&cid).ok_or(IpldError::UnknownCid(cid))?;
+    if Cid::of(data) != cid {
+      return Err(IpldError::Mismatch(cid));
+    }
+    for next in cids(data)? {
+      go(next, blocks, links)?;
+    }
+    let expr = decode(data, &mut |cid| links.get(cid).copied())?;
+    let link = hashspace::put(expr);
+    links.insert(cid, link);
+    Ok(link)
+  }
+  let blocks =
+    blocks.iter().map(|block| (block.cid, block.data.as_slice())).collect();
+  go(root, &blocks, &mut HashMap::new())
+}
+
+// A CARv1 file of `blocks` with the root `root`: a DAG-CBOR header
+// `{"roots": [root], "version": 1}`, then each block as its CID and data,
+// each of these prefixed by its length as a varint
+pub fn write_car(root: Cid, blocks: &[Block]) -> Vec<u8> {
+  let mut header = Vec::new();
+  write_head(5, 2, &mut header);
+  write_bytes(3, b"roots", &mut header);
+  write_head(4, 1, &mut header);
+  let mut cid = vec![0];
+  cid.extend(root.to_bytes());
+  write_head(6, 42, &mut header);
+  write_bytes(2, &cid, &mut header);
+  write_bytes(3, b"version", &mut header);
+  write_head(0, 1, &mut header);
+  let mut out = Vec::new();
+  write_varint(header.len() as u64, &mut out);
+  out.extend(header);
+  for block in blocks {
+    let cid = block.cid.to_bytes();
+    write_varint((cid.len() + block.data.len()) as u64, &mut out);
+    out.extend(cid);
+    out.extend_from_slice(&block.data);
+  }
+  out
+}
+
+// The root and the blocks of a CARv1 file
+pub fn read_car(car: &[u8]) -> Result<(Cid, Vec<Block>), IpldError> {
+  let bad = |e: &str| IpldError::Decode(format!("CAR file: {}", e));
+  let (len, i) = read_varint(car).ok_or_else(|| bad("truncated"))?;
+  if (i.len() as u64) < len {
+    return Err(bad("truncated header"));
+  }
+  let (header, mut i) = i.split_at(len as usize);
+  let root = match read_head(header)? {
+    (5, 2, i) => match read_text(i)? {
+      (key, i) if key == "roots" => match read_head(i)? {
+        (4, n, i) if n > 0 => match read_head(i)? {
+          (6, 42, i) => match read_bytes(2, i)? {
+            ([0, cid @ ..], _) => Cid::read(cid)?.0,
+            _ => return Err(bad("invalid root")),
+          },
+          _ => return Err(bad("invalid root")),
+        },
+        _ => return Err(bad("no root")),
+      },
+      _ => return Err(bad("no roots in header")),
+    },
+    _ => return Err(bad("invalid header")),
+  };
+  let mut blocks = Vec::new();
+  while !i.is_empty() {
+    let (len, rest) = read_varint(i).ok_or_else(|| bad("truncated"))?;
+    if (rest.len() as u64) < len {
+      return Err(bad("truncated block"));
+    }
+    let (section, rest) = rest.split_at(len as usize);
+    let (cid, data) = Cid::read(section)?;
+    blocks.push(Block { cid, data: data.to_vec() });
+    i = rest;
+  }
+  Ok((root, blocks))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn encodes_cids() {
+    let cid = Cid::of(b"yatima");
+    let text = cid.to_string();
+    // Version 1, DAG-CBOR and BLAKE3, in base32
+    assert!(text.starts_with("bafyr4i"));
+    assert_eq!(text.parse(), Ok(cid));
+    assert!(matches!("zafyr4i".parse::<Cid>(), Err(IpldError::BadCid(_))));
+  }
+
+  #[test]
+  fn encodes_exprs() {
+    let expr = cons!(
+      None,
+      text!(String::from("yatima")),
+      bits!(vec![1, 2]),
+      char!('λ'),
+      nat!(BigUint::from(300u32)),
+      int!(BigInt::from(-1))
+    );
+    let data = encode(&expr, &|_| None).unwrap();
+    assert_eq!(data[..8], [0x85, 0x66, b'y', b'a', b't', b'i', b'm', b'a']);
+    assert_eq!(decode(&data, &mut |_| None), Ok(expr));
+    let link = text!(String::from("yatima")).link();
+    let linked = link!(link);
+    let cid = Cid::of(b"yatima");
+    assert_eq!(encode(&linked, &|_| None), Err(IpldError::Missing(link)));
+    let data = encode(&linked, &|_| Some(cid)).unwrap();
+    assert_eq!(cids(&data), Ok(vec![cid]));
+    assert_eq!(decode(&data, &mut |_| Some(link)), Ok(linked));
+  }
+
+  #[test]
+  fn exports_and_imports() {
+    let lib = hashspace::put(text!(String::from("yatima-ipld")));
+    let one = cons!(None, link!(lib), nat!(BigUint::from(1u32)));
+    let one = hashspace::put(one);
+    let root = hashspace::put(cons!(None, link!(lib), link!(one)));
+    let blocks = export(root).unwrap();
+    assert_eq!(blocks.len(), 3);
+    let cid = blocks[2].cid;
+    let (read_root, mut read) = read_car(&write_car(cid, &blocks)).unwrap();
+    assert_eq!(read_root, cid);
+    assert_eq!(read, blocks);
+    // The order of the blocks does not matter
+    read.reverse();
+    assert_eq!(import(cid, &read), Ok(root));
+    read.pop();
+    assert_eq!(import(cid, &read), Err(IpldError::UnknownCid(blocks[0].cid)));
+    let mut forged = blocks.clone();
+    forged[0].data.push(0);
+    let cid = forged[0].cid;
+    assert_eq!(import(cid, &forged), Err(IpldError::Mismatch(cid)));
+  }
+}
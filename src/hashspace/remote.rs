@@ -0,0 +1,479 @@
+use crate::{
+  config,
+  hashspace::{
+    self,
+    wire::Encoding,
+  },
+  package::{
+    Declaration,
+    Package,
+  },
+  parse::package::import_path,
+  term::Link,
+};
+
+use hashexpr::{
+  atom::Atom,
+  Expr,
+};
+
+use std::{
+  collections::{
+    HashSet,
+    VecDeque,
+  },
+  env,
+  fmt,
+  fs,
+  io::{
+    Read,
+    Write,
+  },
+  net::{
+    TcpStream,
+    ToSocketAddrs,
+  },
+  path::{
+    Path,
+    PathBuf,
+  },
+  str::FromStr,
+  time::{
+    Duration,
+    Instant,
+  },
+};
+
+// Remote hashspaces, to publish packages to and get them from. A remote is
+// either a directory, such as a shared or mounted one, laid out as the local
+// hashspace is, or a hashspace server, which stores an expression when it
+// is put to `/store` and serves it at `/store/<link>`. An expression is put
+// along with everything it links to, transitively, its closure, so that a
+// package can be gotten by its link alone. As expressions are addressed by
+// their contents, what is gotten is checked against the link it was asked
+// for, and a remote need not be trusted. A server may allow puts only with
+// a token, which is sent from `YATIMA_TOKEN`, or else the configuration, but
+// only to a server on this machine, as plain HTTP would show it to anyone
+// in between. Expressions are sent to a server in the configured encoding,
+// and gotten in whichever encoding it answers with.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Remote {
+  Dir(PathBuf),
+  // A server, by its address and the path its store is under
+  Http { addr: String, path: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RemoteError {
+  // A link in a closure which neither the local hashspace nor the remote has
+  Missing(Link),
+  // What a remote gave for a link, which is not what the link is of
+  Mismatch(Link),
+  Io(String),
+}
+
+impl fmt::Display for RemoteError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Missing(link) => write!(f, "Nothing is stored at {}", link),
+      Self::Mismatch(link) => {
+        write!(f, "The remote gave something other than {}", link)
+      }
+      Self::Io(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for RemoteError {}
+
+impl FromStr for Remote {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.strip_prefix("http://") {
+      Some(rest) => {
+        let (addr, path) = match rest.find('/') {
+          Some(idx) => (&rest[..idx], rest[idx..].trim_end_matches('/')),
+          None => (rest, ""),
+        };
+        if addr.is_empty() {
+          return Err(format!("No host in remote {}", s));
+        }
+        let addr = if addr.contains(':') {
+          String::from(addr)
+        }
+        else {
+          format!("{}:80", addr)
+        };
+        Ok(Self::Http { addr, path: String::from(path) })
+      }
+      None if s.contains("://") => Err(format!("Unsupported remote {}", s)),
+      None => Ok(Self::Dir(PathBuf::from(s))),
+    }
+  }
+}
+
+impl fmt::Display for Remote {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Dir(dir) => write!(f, "{}", dir.display()),
+      Self::Http { addr, path } => write!(f, "http://{}{}", addr, path),
+    }
+  }
+}
+
+// The token to put to a server with, if one is set
+fn token() -> Option<String> {
+  env::var("YATIMA_TOKEN").ok().or_else(|| config::get().token.clone())
+}
+
+// The encoding to send expressions to a server in
+fn encoding() -> Encoding { config::get().encoding.unwrap_or_default() }
+
+// How long a server may take to read a request or to answer it
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+// The most a response may hold
+const MAX_RESPONSE: u64 = 256 * 1024 * 1024;
+
+// Whether `addr` is only ever this machine
+fn is_loopback(addr: &str) -> bool {
+  match addr.to_socket_addrs() {
+    Ok(addrs) => {
+      let addrs: Vec<_> = addrs.collect();
+      !addrs.is_empty() && addrs.iter().all(|addr| addr.ip().is_loopback())
+    }
+    Err(_) => false,
+  }
+}
+
+// The status, content type and body of the response to an HTTP/1.0 request,
+// which, unlike HTTP/1.1, is never answered in chunks
+fn request(
+  addr: &str,
+  head: &str,
+  body: &[u8],
+) -> Result<(u32, Option<String>, Vec<u8>), RemoteError> {
+  let io = |e: std::io::Error| RemoteError::Io(format!("{}: {}", addr, e));
+  let mut stream = TcpStream::connect(addr).map_err(io)?;
+  stream.set_read_timeout(Some(TIMEOUT)).map_err(io)?;
+  stream.set_write_timeout(Some(TIMEOUT)).map_err(io)?;
+  let host = addr.split(':').next().unwrap_or(addr);
+  let head = format!(
+    "{}\r\nHost: {}\r\nContent-Length: {}\r\n\r\n",
+    head,
+    host,
+    body.len()
+  );
+  stream.write_all(head.as_bytes()).map_err(io)?;
+  stream.write_all(body).map_err(io)?;
+  let mut response = Vec::new();
+  stream.take(MAX_RESPONSE + 1).read_to_end(&mut response).map_err(io)?;
+  if response.len() as u64 > MAX_RESPONSE {
+    let e = format!("{}: response larger than {} bytes", addr, MAX_RESPONSE);
+    return Err(RemoteError::Io(e));
+  }
+  let malformed = || RemoteError::Io(format!("{}: malformed response", addr));
+  let end = response
+    .windows(4)
+    .position(|w| w == b"\r\n\r\n")
+    .ok_or_else(malformed)?;
+  let head = String::from_utf8_lossy(&response[..end]);
+  let status = head
+    .split_whitespace()
+    .nth(1)
+    .and_then(|code| code.parse().ok())
+    .ok_or_else(malformed)?;
+  let media = head.lines().skip(1).find_map(|line| {
+    let mut field = line.splitn(2, ':');
+    let name = field.next()?;
+    let value = field.next()?;
+    if name.trim().eq_ignore_ascii_case("content-type") {
+      Some(String::from(value.trim()))
+    }
+    else {
+      None
+    }
+  });
+  Ok((status, media, response[end + 4..].to_vec()))
+}
+
+impl Remote {
+  // The expression the remote has at `link`, if it has one
+  pub fn get(&self, link: Link) -> Result<Option<Expr>, RemoteError> {
+    let (encoding, bytes) = match self {
+      Self::Dir(dir) => match fs::read(dir.join(link.to_string())) {
+        Ok(bytes) => (Encoding::Hashexpr, bytes),
+        Err(_) => return Ok(None),
+      },
+      Self::Http { addr, path } => {
+        let head = format!(
+          "GET {}/store/{} HTTP/1.0\r\nAccept: {}",
+          path,
+          link,
+          encoding().media_type()
+        );
+        match request(addr, &head, &[])? {
+          (200, media, body) => {
+            let encoding =
+              media.as_deref().and_then(Encoding::from_media_types);
+            (encoding.unwrap_or_default(), body)
+          }
+          (404, ..) => return Ok(None),
+          (status, ..) => {
+            let e = format!("{}: status {} getting {}", addr, status, link);
+            return Err(RemoteError::Io(e));
+          }
+        }
+      }
+    };
+    match encoding.decode(&bytes) {
+      Ok(expr) if expr.link() == link => Ok(Some(expr)),
+      _ => Err(RemoteError::Mismatch(link)),
+    }
+  }
+
+  pub fn put(&self, expr: &Expr) -> Result<(), RemoteError> {
+    let link = expr.link();
+    match self {
+      Self::Dir(dir) => {
+        let io = |e: std::io::Error| {
+          RemoteError::Io(format!("{}: {}", dir.display(), e))
+        };
+        fs::create_dir_all(dir).map_err(io)?;
+        fs::write(dir.join(link.to_string()), expr.serialize()).map_err(io)
+      }
+      Self::Http { addr, path } => {
+        let encoding = encoding();
+        let mut head = format!(
+          "PUT {}/store HTTP/1.0\r\nContent-Type: {}",
+          path,
+          encoding.media_type()
+        );
+        let token = token();
+        let loopback = is_loopback(addr);
+        if let Some(token) = token.as_ref().filter(|_| loopback) {
+          head.push_str(&format!("\r\nAuthorization: Bearer {}", token));
+        }
+        match request(addr, &head, &encoding.encode(expr))? {
+          // A server answers with the link of what it stored, which is not
+          // the link of the expression if it could not decode it
+          (200, _, body)
+            if !String::from_utf8_lossy(&body).contains(&link.to_string()) =>
+          {
+            Err(RemoteError::Io(format!(
+              "{}: the remote does not accept {} expressions",
+              addr, encoding
+            )))
+          }
+          (200, ..) => Ok(()),
+          (401, ..) if token.is_some() && !loopback => {
+            Err(RemoteError::Io(format!(
+              "{}: not allowed to put {} without its token, which is only \
+               sent over plain HTTP to this machine",
+              addr, link
+            )))
+          }
+          (401, ..) => Err(RemoteError::Io(format!(
+            "{}: not allowed to put {} without its token",
+            addr, link
+          ))),
+          (403, ..) => {
+            Err(RemoteError::Io(format!("{}: the remote is read-only", addr)))
+          }
+          (status, ..) => {
+            let e = format!("{}: status {} putting {}", addr, status, link);
+            Err(RemoteError::Io(e))
+          }
+        }
+      }
+    }
+  }
+}
+
+// The links in an expression
+pub fn links(expr: &Expr) -> Vec<Link> {
+  fn go(expr: &Expr, links: &mut Vec<Link>) {
+    match expr {
+      Expr::Atom(_, Atom::Link(link)) => links.push(*link),
+      Expr::Atom(..) => (),
+      Expr::Cons(_, xs) => {
+        for x in xs {
+          go(x, links);
+        }
+      }
+    }
+  }
+  let mut links = Vec::new();
+  go(expr, &mut links);
+  links
+}
+
+// The closure of `link`, each expression in it once, found by `get`
+fn closure(
+  link: Link,
+  mut get: impl FnMut(Link) -> Result<Option<Expr>, RemoteError>,
+) -> Result<Vec<Expr>, RemoteError> {
+  let mut seen = HashSet::new();
+  let mut queue = VecDeque::new();
+  let mut exprs = Vec::new();
+  seen.insert(link);
+  queue.push_back(link);
+  while let Some(link) = queue.pop_front() {
+    let expr = get(link)?.ok_or(RemoteError::Missing(link))?;
+    for next in links(&expr) {
+      if seen.insert(next) {
+        queue.push_back(next);
+      }
+    }
+    exprs.push(expr);
+  }
+  Ok(exprs)
+}
+
+// Put the closure of `link` in the local hashspace to the remote, returning
+// the number of expressions put
+pub fn publish(remote: &Remote, link: Link) -> Result<usize, RemoteError> {
+  let _span = info_span!(target: "hashspace", "publish", %link).entered();
+  let start = Instant::now();
+  let exprs = closure(link, |link| Ok(hashspace::get(link)))?;
+  for expr in &exprs {
+    remote.put(expr)?;
+    debug!(target: "hashspace", link = %expr.link(), "Published");
+  }
+  let (count, time) = (exprs.len(), start.elapsed());
+  info!(target: "hashspace", count, ?time, "Published expressions");
+  Ok(exprs.len())
+}
+
+// Get the closure of `link` from the remote into the local hashspace,
+// returning the number of expressions which were not already there
+pub fn fetch(remote: &Remote, link: Link) -> Result<usize, RemoteError> {
+  let _span = info_span!(target: "hashspace", "fetch", %link).entered();
+  let start = Instant::now();
+  let mut fetched = 0;
+  closure(link, |link| match hashspace::get(link) {
+    Some(expr) => Ok(Some(expr)),
+    None => {
+      let expr = remote.get(link)?;
+      if let Some(expr) = &expr {
+        hashspace::put(expr.clone());
+        debug!(target: "hashspace", %link, "Fetched");
+        fetched += 1;
+      }
+      Ok(expr)
+    }
+  })?;
+  let time = start.elapsed();
+  info!(target: "hashspace", count = fetched, ?time, "Fetched expressions");
+  Ok(fetched)
+}
+
+// Write the sources of the package at `link`, and of the packages it opens,
+// to the files they would be parsed from, the package's own being in `dir`,
+// returning their paths. A package whose source is not stored is written as
+// its declarations.
+pub fn materialize(
+  dir: &Path,
+  link: Link,
+) -> Result<Vec<PathBuf>, RemoteError> {
+  fn go(
+    path: PathBuf,
+    link: Link,
+    paths: &mut Vec<PathBuf>,
+  ) -> Result<(), RemoteError> {
+    if paths.contains(&path) {
+      return Ok(());
+    }
+    let package = Package::get_link(link)
+      .map_err(|e| RemoteError::Io(format!("{}: {:?}", link, e)))?;
+    let source = match hashspace::get(package.source) {
+      Some(Expr::Atom(_, Atom::Text(source))) => source,
+      _ => format!("{}", package),
+    };
+    let io = |e: std::io::Error| {
+      RemoteError::Io(format!("{}: {}", path.display(), e))
+    };
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).map_err(io)?;
+    }
+    fs::write(&path, source).map_err(io)?;
+    paths.push(path.clone());
+    for decl in &package.decls {
+      if let Declaration::Open { name, from, .. } = decl {
+        go(import_path(&path, name), *from, paths)?;
+      }
+    }
+    Ok(())
+  }
+  let package = Package::get_link(link)
+    .map_err(|e| RemoteError::Io(format!("{}: {:?}", link, e)))?;
+  let mut paths = Vec::new();
+  go(dir.join(format!("{}.ya", package.name)), link, &mut paths)?;
+  Ok(paths)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::query::Database;
+  use hashexpr::atom;
+  use std::env;
+
+  #[test]
+  fn parses_remotes() {
+    let remote = |s: &str| s.parse::<Remote>();
+    assert_eq!(remote("/srv/yatima"), Ok(Remote::Dir("/srv/yatima".into())));
+    assert_eq!(
+      remote("http://localhost:8000/"),
+      Ok(Remote::Http { addr: "localhost:8000".into(), path: "".into() })
+    );
+    assert_eq!(
+      remote("http://example.com/yatima"),
+      Ok(Remote::Http {
+        addr: "example.com:80".into(),
+        path: "/yatima".into()
+      })
+    );
+    assert!(remote("https://example.com").is_err());
+  }
+
+  #[test]
+  fn sends_tokens_to_loopback_only() {
+    assert!(is_loopback("127.0.0.1:8000"));
+    assert!(is_loopback("[::1]:8000"));
+    assert!(!is_loopback("192.0.2.1:80"));
+    assert!(!is_loopback("not an address"));
+  }
+
+  #[test]
+  fn publishes_and_gets() {
+    let dir = env::temp_dir().join("yatima-remote");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let lib = "package RemoteLib where\ndef one : #Natural = 1\n";
+    fs::write(dir.join("src").join("RemoteLib.ya"), lib).unwrap();
+    let source = "package Remote where\nopen RemoteLib\n\
+                  def two : #Natural = #add one one\n";
+    let path = dir.join("src").join("Remote.ya");
+    fs::write(&path, source).unwrap();
+    let parsed = Database::new().parse(&path).unwrap();
+    let remote = Remote::Dir(dir.join("remote"));
+    let count = publish(&remote, parsed.link).unwrap();
+    assert!(count > 0);
+    assert_eq!(fetch(&remote, parsed.link), Ok(0));
+    let got = closure(parsed.link, |link| remote.get(link)).unwrap();
+    assert_eq!(got.len(), count);
+    let unknown = text!(String::from("not in yatima-remote")).link();
+    let none = Remote::Dir(dir.join("none"));
+    assert_eq!(fetch(&none, unknown), Err(RemoteError::Missing(unknown)));
+    let paths = materialize(&dir.join("out"), parsed.link).unwrap();
+    assert_eq!(paths, vec![
+      dir.join("out").join("Remote.ya"),
+      dir.join("out").join("RemoteLib.ya"),
+    ]);
+    assert_eq!(fs::read_to_string(&paths[0]).unwrap(), source);
+    assert_eq!(fs::read_to_string(&paths[1]).unwrap(), lib);
+  }
+}
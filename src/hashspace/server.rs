@@ -1,7 +1,90 @@
-use crate::hashspace;
+use crate::hashspace::{
+  self,
+  wire::Encoding,
+};
 use hashexpr::Expr;
 
-use rocket::Data;
+use rocket::{
+  config::{
+    Config,
+    Environment,
+  },
+  http::{
+    ContentType,
+    Status,
+  },
+  request::{
+    self,
+    FromRequest,
+  },
+  response::content::Content,
+  Data,
+  Outcome,
+  Request,
+  State,
+};
+
+use std::io::Read;
+
+// How the server is run: the URL it is served at, and whether and with what
+// token it allows expressions to be put
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Settings {
+  pub url: String,
+  pub read_only: bool,
+  pub token: Option<String>,
+}
+
+impl Settings {
+  // Whether a put with the token `token` is allowed, or else the status it
+  // is refused with
+  pub fn authorize(&self, token: Option<&str>) -> Result<(), Status> {
+    if self.read_only {
+      return Err(Status::Forbidden);
+    }
+    match &self.token {
+      Some(allowed) if token != Some(allowed.as_str()) => {
+        Err(Status::Unauthorized)
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+// The bearer token a request is authorized with, if any
+struct Bearer(Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Bearer {
+  type Error = ();
+
+  fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+    let token = request
+      .headers()
+      .get_one("Authorization")
+      .and_then(|auth| auth.strip_prefix("Bearer "))
+      .map(String::from);
+    Outcome::Success(Bearer(token))
+  }
+}
+
+// The encoding a request asks to be answered in, by its `Accept` header, and
+// the encoding of its body, by its `Content-Type` header, if either is known
+struct Wire {
+  accept: Encoding,
+  content: Option<Encoding>,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Wire {
+  type Error = ();
+
+  fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+    let media = |name| {
+      request.headers().get_one(name).and_then(Encoding::from_media_types)
+    };
+    let accept = media("Accept").unwrap_or_default();
+    Outcome::Success(Wire { accept, content: media("Content-Type") })
+  }
+}
 
 #[get("/")]
 fn index() -> &'static str {
@@ -17,30 +100,57 @@ fn index() -> &'static str {
       GET /store/<hash>
 
           retrieves the content for the paste with id `<hash>`
+
+      A server may be read-only, or allow puts only with the header
+      `Authorization: Bearer <token>`.
+
+      Content is sent and served as hashexpr, `application/octet-stream`,
+      or as CBOR, `application/cbor`, by the `Content-Type` of a put and the
+      `Accept` header of a get.
     "
 }
 
 #[get("/store/<hash>")]
-fn get(hash: String) -> Option<Vec<u8>> {
+fn get(hash: String, wire: Wire) -> Option<Content<Vec<u8>>> {
   let (_, link) = hashexpr::link::Link::parse(&hash).ok()?;
   info!("Your link {}", link);
   let expr = hashspace::get(link)?;
-  Some(expr.serialize())
+  let media = ContentType::parse_flexible(wire.accept.media_type())
+    .unwrap_or(ContentType::Binary);
+  Some(Content(media, wire.accept.encode(&expr)))
 }
 
 #[put("/store", data = "<data>")]
-fn put(data: Data) -> Result<String, std::io::Error> {
-  let stream: &[u8] = data.peek();
+fn put(
+  settings: State<Settings>,
+  bearer: Bearer,
+  wire: Wire,
+  data: Data,
+) -> Result<String, Status> {
+  settings.authorize(bearer.0.as_deref())?;
+  // Read the whole body, as only the start of it can be peeked at
+  let mut stream = Vec::new();
+  data
+    .open()
+    .read_to_end(&mut stream)
+    .map_err(|_| Status::InternalServerError)?;
 
-  let expr = match Expr::deserialize(stream) {
-    Ok((_, x)) => x,
-    _ => Expr::from_bits(stream.clone().as_ref()),
+  // Raw data which is not hashexpr is stored as bits, but what is sent as
+  // CBOR must be an expression
+  let expr = match wire.content {
+    Some(Encoding::Cbor) => {
+      Encoding::Cbor.decode(&stream).map_err(|_| Status::BadRequest)?
+    }
+    _ => match Expr::deserialize(&stream) {
+      Ok((_, x)) => x,
+      _ => Expr::from_bits(&stream),
+    },
   };
   let hash = expr.link().to_string();
 
   let url = format!(
     "{host}/store/{hash}\n",
-    host = "http://localhost:8000",
+    host = settings.url,
     hash = hash
   );
 
@@ -49,5 +159,45 @@ fn put(data: Data) -> Result<String, std::io::Error> {
   Ok(format!("Your hash {} at {}", hash, url))
 }
 
-#[allow(dead_code)]
-fn main() { rocket::ignite().mount("/", routes![index, get, put]).launch(); }
+// Serve the local hashspace at `address` and `port`, refusing puts if
+// `read_only` or, if there is a `token`, those without it, returning why the
+// server could not be started or stopped
+pub fn serve(
+  address: &str,
+  port: u16,
+  read_only: bool,
+  token: Option<String>,
+) -> String {
+  let settings =
+    Settings { url: format!("http://{}:{}", address, port), read_only, token };
+  let config =
+    Config::build(Environment::Development).address(address).port(port);
+  match config.finalize() {
+    Ok(config) => rocket::custom(config)
+      .manage(settings)
+      .mount("/", routes![index, get, put])
+      .launch()
+      .to_string(),
+    Err(e) => e.to_string(),
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn authorizes() {
+    let url = String::from("http://localhost:8000");
+    let open = Settings { url, read_only: false, token: None };
+    assert_eq!(open.authorize(None), Ok(()));
+    assert_eq!(open.authorize(Some("x")), Ok(()));
+    let token = Some(String::from("secret"));
+    let closed = Settings { token, ..open.clone() };
+    assert_eq!(closed.authorize(Some("secret")), Ok(()));
+    assert_eq!(closed.authorize(Some("x")), Err(Status::Unauthorized));
+    assert_eq!(closed.authorize(None), Err(Status::Unauthorized));
+    let read_only = Settings { read_only: true, ..closed };
+    assert_eq!(read_only.authorize(Some("secret")), Err(Status::Forbidden));
+  }
+}
@@ -0,0 +1,138 @@
+use crate::{
+  hashspace::ipld::{
+    self,
+    Cid,
+  },
+  term::Link,
+};
+
+use hashexpr::Expr;
+
+use std::{
+  fmt,
+  str::FromStr,
+};
+
+// How an expression is sent to and gotten from a hashspace server. Either
+// way, it is stored, and its link computed, as its hashexpr serialization,
+// so the encoding only changes what is sent. CBOR is written as IPLD blocks
+// are, but with each link as a CID of the hash it has, which, unlike the CID
+// of a block, is the hash of the hashexpr of what it links to, so that no
+// other expression is needed to encode or decode one. The encoding is agreed
+// on with the media type of the request and the response, and a server which
+// does not know of CBOR answers with hashexpr, which is always accepted.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+  Hashexpr,
+  Cbor,
+}
+
+impl Default for Encoding {
+  fn default() -> Self { Self::Hashexpr }
+}
+
+impl FromStr for Encoding {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "hashexpr" => Ok(Self::Hashexpr),
+      "cbor" => Ok(Self::Cbor),
+      _ => Err(format!("Unknown encoding {}", s)),
+    }
+  }
+}
+
+impl fmt::Display for Encoding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Hashexpr => write!(f, "hashexpr"),
+      Self::Cbor => write!(f, "cbor"),
+    }
+  }
+}
+
+impl Encoding {
+  // The media type of an expression in this encoding
+  pub fn media_type(self) -> &'static str {
+    match self {
+      Self::Hashexpr => "application/octet-stream",
+      Self::Cbor => "application/cbor",
+    }
+  }
+
+  // The first encoding among the media types of an `Accept` or
+  // `Content-Type` header, if any is one
+  pub fn from_media_types(header: &str) -> Option<Self> {
+    header.split(',').find_map(|media| {
+      let media = media.split(';').next().unwrap_or("").trim();
+      match media.to_ascii_lowercase().as_str() {
+        "application/octet-stream" => Some(Self::Hashexpr),
+        "application/cbor" => Some(Self::Cbor),
+        _ => None,
+      }
+    })
+  }
+
+  pub fn encode(self, expr: &Expr) -> Vec<u8> {
+    match self {
+      Self::Hashexpr => expr.serialize(),
+      Self::Cbor => {
+        let cid = |link: Link| Some(Cid(*link.as_hash().as_bytes()));
+        ipld::encode(expr, &cid).expect("every link has a CID")
+      }
+    }
+  }
+
+  pub fn decode(self, data: &[u8]) -> Result<Expr, String> {
+    match self {
+      Self::Hashexpr => match Expr::deserialize(data) {
+        Ok(([], expr)) => Ok(expr),
+        Ok(_) => Err(String::from("trailing bytes")),
+        Err(e) => Err(format!("{:?}", e)),
+      },
+      Self::Cbor => {
+        ipld::decode(data, &mut |cid| Some(Link::from(cid.0)))
+          .map_err(|e| e.to_string())
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use hashexpr::atom;
+  use num_bigint::{
+    BigInt,
+    BigUint,
+  };
+
+  #[test]
+  fn encodes_the_same_expression() {
+    let linked = text!(String::from("linked")).link();
+    let expr = cons!(
+      None,
+      text!(String::from("entry")),
+      link!(linked),
+      bits!(vec![0, 1, 2]),
+      char!('λ'),
+      nat!(BigUint::from(256u32)),
+      int!(BigInt::from(-3)),
+      Expr::Cons(None, vec![])
+    );
+    for encoding in &[Encoding::Hashexpr, Encoding::Cbor] {
+      let data = encoding.encode(&expr);
+      let read = encoding.decode(&data).unwrap();
+      assert_eq!(read.link(), expr.link());
+    }
+    assert!(Encoding::Cbor.decode(&expr.serialize()).is_err());
+    assert_eq!(
+      Encoding::from_media_types("text/html, application/cbor;q=0.9"),
+      Some(Encoding::Cbor)
+    );
+    assert_eq!(Encoding::from_media_types("*/*"), None);
+    assert_eq!("cbor".parse(), Ok(Encoding::Cbor));
+  }
+}
@@ -0,0 +1,88 @@
+use crate::parse::{
+  span::Span,
+  term::parse_name,
+};
+
+use std::{
+  fmt,
+  fs,
+  path::{
+    Path,
+    PathBuf,
+  },
+};
+
+// Scaffolding for a new package. A package must be declared in a file named
+// after it, so the file is created with the name it is declared with, along
+// with a `main` definition to run.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InitError {
+  // A name which the parser would not read as a package name
+  InvalidName(String),
+  Exists(PathBuf),
+  Io(String),
+}
+
+impl fmt::Display for InitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::InvalidName(name) => write!(f, "Invalid package name {}", name),
+      Self::Exists(path) => write!(f, "{} already exists", path.display()),
+      Self::Io(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for InitError {}
+
+// The source of a new package `name`
+pub fn template(name: &str) -> String {
+  format!(
+    "package {} where\n\ndef main : #Text = \"Hello, world!\"\n",
+    name
+  )
+}
+
+// Create the package `name` in the directory `dir`, returning the path of
+// its file
+pub fn init(dir: &Path, name: &str) -> Result<PathBuf, InitError> {
+  let invalid = || InitError::InvalidName(String::from(name));
+  match parse_name(Span::new(name)) {
+    Ok((rest, _)) if rest.fragment().is_empty() => (),
+    _ => return Err(invalid()),
+  }
+  if name.contains(std::path::is_separator) {
+    return Err(invalid());
+  }
+  let path = dir.join(format!("{}.ya", name));
+  if path.exists() {
+    return Err(InitError::Exists(path));
+  }
+  fs::write(&path, template(name))
+    .map_err(|e| InitError::Io(format!("{}: {}", path.display(), e)))?;
+  Ok(path)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::query::Database;
+  use std::env;
+
+  #[test]
+  fn inits() {
+    let dir = env::temp_dir().join("yatima-init");
+    fs::create_dir_all(&dir).unwrap();
+    let _ = fs::remove_file(dir.join("Hello.ya"));
+    let path = init(&dir, "Hello").unwrap();
+    assert_eq!(path, dir.join("Hello.ya"));
+    let outcomes = Database::new().check_package(&path).unwrap();
+    assert!(outcomes.iter().any(|(name, res)| name == "main" && res.is_ok()));
+    assert_eq!(init(&dir, "Hello"), Err(InitError::Exists(path)));
+    for name in &["", "def", "#Hello", "Hello World", "a/b"] {
+      let err = InitError::InvalidName(String::from(*name));
+      assert_eq!(init(&dir, name), Err(err));
+    }
+  }
+}
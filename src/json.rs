@@ -0,0 +1,322 @@
+use std::{
+  fmt,
+  iter::Peekable,
+  str::Chars,
+};
+
+pub mod ast;
+pub mod diagnostic;
+
+// JSON output, for the command line, so that editors and other tools can
+// read what a command found rather than scraping what it prints for people.
+// Values are built directly and printed on one line. JSON is also read, for
+// the messages of the language server protocol, whose numbers are all
+// integers, so a number with a fraction is read as its integer part.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Num(i64),
+  Str(String),
+  Arr(Vec<Json>),
+  // An object, with its keys in the order they are printed
+  Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+  pub fn str(s: impl fmt::Display) -> Self { Self::Str(format!("{}", s)) }
+
+  pub fn obj(fields: Vec<(&str, Json)>) -> Self {
+    Self::Obj(fields.into_iter().map(|(k, v)| (String::from(k), v)).collect())
+  }
+
+  // The value of the field `key`, if this is an object with one
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Self::Obj(fields) => fields.iter().find(|(k, _)| k == key).map(|f| &f.1),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Self::Str(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn as_i64(&self) -> Option<i64> {
+    match self {
+      Self::Num(x) => Some(*x),
+      _ => None,
+    }
+  }
+
+  pub fn parse(s: &str) -> Result<Json, String> {
+    let mut chars = s.chars().peekable();
+    let json = parse_value(&mut chars)?;
+    skip_space(&mut chars);
+    match chars.next() {
+      None => Ok(json),
+      Some(c) => Err(format!("Unexpected {:?} after JSON value", c)),
+    }
+  }
+}
+
+fn skip_space(chars: &mut Peekable<Chars>) {
+  while let Some(' ') | Some('\t') | Some('\r') | Some('\n') = chars.peek() {
+    chars.next();
+  }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+  let hex = |chars: &mut Peekable<Chars>| {
+    let digits: String = chars.take(4).collect();
+    u32::from_str_radix(&digits, 16)
+      .map_err(|_| format!("Invalid escape \\u{}", digits))
+  };
+  let mut s = String::new();
+  loop {
+    match chars.next() {
+      Some('"') => return Ok(s),
+      Some('\\') => match chars.next() {
+        Some('"') => s.push('"'),
+        Some('\\') => s.push('\\'),
+        Some('/') => s.push('/'),
+        Some('b') => s.push('\u{8}'),
+        Some('f') => s.push('\u{c}'),
+        Some('n') => s.push('\n'),
+        Some('r') => s.push('\r'),
+        Some('t') => s.push('\t'),
+        Some('u') => {
+          let mut code = hex(chars)?;
+          // A character outside the basic plane, as a surrogate pair
+          if (0xd800..0xdc00).contains(&code) {
+            if chars.next() != Some('\\') || chars.next() != Some('u') {
+              return Err(String::from("Unpaired surrogate"));
+            }
+            let low = hex(chars)?;
+            if !(0xdc00..0xe000).contains(&low) {
+              return Err(String::from("Unpaired surrogate"));
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+          }
+          s.push(char::from_u32(code).ok_or("Invalid character")?);
+        }
+        c => return Err(format!("Unknown escape {:?}", c)),
+      },
+      Some(c) => s.push(c),
+      None => return Err(String::from("Unclosed string")),
+    }
+  }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+  skip_space(chars);
+  match chars.peek().copied() {
+    Some('"') => {
+      chars.next();
+      Ok(Json::Str(parse_string(chars)?))
+    }
+    Some('[') => {
+      chars.next();
+      let mut xs = Vec::new();
+      skip_space(chars);
+      if chars.peek() == Some(&']') {
+        chars.next();
+        return Ok(Json::Arr(xs));
+      }
+      loop {
+        xs.push(parse_value(chars)?);
+        skip_space(chars);
+        match chars.next() {
+          Some(',') => (),
+          Some(']') => return Ok(Json::Arr(xs)),
+          _ => return Err(String::from("Expected , or ] in array")),
+        }
+      }
+    }
+    Some('{') => {
+      chars.next();
+      let mut fields = Vec::new();
+      skip_space(chars);
+      if chars.peek() == Some(&'}') {
+        chars.next();
+        return Ok(Json::Obj(fields));
+      }
+      loop {
+        skip_space(chars);
+        if chars.next() != Some('"') {
+          return Err(String::from("Expected a key in object"));
+        }
+        let key = parse_string(chars)?;
+        skip_space(chars);
+        if chars.next() != Some(':') {
+          return Err(String::from("Expected : in object"));
+        }
+        fields.push((key, parse_value(chars)?));
+        skip_space(chars);
+        match chars.next() {
+          Some(',') => (),
+          Some('}') => return Ok(Json::Obj(fields)),
+          _ => return Err(String::from("Expected , or } in object")),
+        }
+      }
+    }
+    Some(c) if c == '-' || c.is_ascii_digit() => {
+      let mut s = String::new();
+      while let Some(c) = chars.peek().copied() {
+        if !c.is_ascii_digit() && !"-+.eE".contains(c) {
+          break;
+        }
+        s.push(c);
+        chars.next();
+      }
+      match s.parse::<i64>() {
+        Ok(x) => Ok(Json::Num(x)),
+        Err(_) => s
+          .parse::<f64>()
+          .map(|x| Json::Num(x as i64))
+          .map_err(|_| format!("Invalid number {}", s)),
+      }
+    }
+    Some(_) => {
+      let mut word = String::new();
+      while let Some(c) = chars.peek().copied() {
+        if !c.is_ascii_alphabetic() {
+          break;
+        }
+        word.push(c);
+        chars.next();
+      }
+      match word.as_str() {
+        "null" => Ok(Json::Null),
+        "true" => Ok(Json::Bool(true)),
+        "false" => Ok(Json::Bool(false)),
+        _ => Err(format!("Invalid JSON value {}", word)),
+      }
+    }
+    None => Err(String::from("Expected a JSON value")),
+  }
+}
+
+impl<A: Into<Json>> From<Option<A>> for Json {
+  fn from(x: Option<A>) -> Self { x.map_or(Json::Null, Into::into) }
+}
+
+impl From<bool> for Json {
+  fn from(x: bool) -> Self { Json::Bool(x) }
+}
+
+impl From<u64> for Json {
+  fn from(x: u64) -> Self { Json::Num(x as i64) }
+}
+
+impl From<usize> for Json {
+  fn from(x: usize) -> Self { Json::Num(x as i64) }
+}
+
+impl From<i64> for Json {
+  fn from(x: i64) -> Self { Json::Num(x) }
+}
+
+impl From<String> for Json {
+  fn from(x: String) -> Self { Json::Str(x) }
+}
+
+impl From<&str> for Json {
+  fn from(x: &str) -> Self { Json::Str(String::from(x)) }
+}
+
+fn escape(s: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  write!(f, "\"")?;
+  for c in s.chars() {
+    match c {
+      '"' => write!(f, "\\\"")?,
+      '\\' => write!(f, "\\\\")?,
+      '\n' => write!(f, "\\n")?,
+      '\r' => write!(f, "\\r")?,
+      '\t' => write!(f, "\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Null => write!(f, "null"),
+      Self::Bool(x) => write!(f, "{}", x),
+      Self::Num(x) => write!(f, "{}", x),
+      Self::Str(x) => escape(x, f),
+      Self::Arr(xs) => {
+        write!(f, "[")?;
+        for (i, x) in xs.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?;
+          }
+          write!(f, "{}", x)?;
+        }
+        write!(f, "]")
+      }
+      Self::Obj(fields) => {
+        write!(f, "{{")?;
+        for (i, (k, v)) in fields.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?;
+          }
+          escape(k, f)?;
+          write!(f, ":{}", v)?;
+        }
+        write!(f, "}}")
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn prints() {
+    let json = Json::obj(vec![
+      ("name", "a \"b\"\n".into()),
+      ("count", 2u64.into()),
+      ("ok", true.into()),
+      ("link", Option::<String>::None.into()),
+      ("xs", Json::Arr(vec![Json::Num(-1), Json::str('\u{1}')])),
+      ("empty", Json::obj(vec![])),
+    ]);
+    assert_eq!(
+      format!("{}", json),
+      "{\"name\":\"a \\\"b\\\"\\n\",\"count\":2,\"ok\":true,\"link\":null,\
+       \"xs\":[-1,\"\\u0001\"],\"empty\":{}}"
+    );
+  }
+
+  #[test]
+  fn parses() {
+    let json = Json::obj(vec![
+      ("name", "a \"b\"\n\u{1f600}".into()),
+      ("count", 2u64.into()),
+      ("ok", true.into()),
+      ("link", Json::Null),
+      ("xs", Json::Arr(vec![Json::Num(-1), Json::Arr(vec![])])),
+      ("empty", Json::obj(vec![])),
+    ]);
+    assert_eq!(Json::parse(&format!("{}", json)), Ok(json.clone()));
+    let spaced = " { \"name\" : \"a \\\"b\\\"\\n\\ud83d\\ude00\",\
+                  \"count\": 2.5, \"ok\":true, \"link\":null,\
+                  \"xs\": [ -1, [ ] ], \"empty\": { } } ";
+    assert_eq!(Json::parse(spaced), Ok(json.clone()));
+    assert_eq!(json.get("count").and_then(Json::as_i64), Some(2));
+    assert_eq!(json.get("name").and_then(Json::as_str), Some("a \"b\"\n😀"));
+    assert_eq!(json.get("none"), None);
+    assert!(Json::parse("{\"a\": 1").is_err());
+    assert!(Json::parse("[1] 2").is_err());
+  }
+}
@@ -0,0 +1,557 @@
+use crate::{
+  core::{
+    literal::{
+      LitType,
+      Literal,
+    },
+    primop::PrimOp,
+    uses::Uses,
+  },
+  definition::Transparency,
+  json::Json,
+  name::Name,
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Def,
+    Link,
+    Term,
+  },
+};
+
+use num_bigint::{
+  BigInt,
+  BigUint,
+};
+
+// Terms, definitions and packages as JSON, so that tools in other languages
+// can read and write them without implementing hashexpr. This schema is
+// stable: fields may be added to it, but not removed or changed. Positions
+// are not part of it, and terms read from JSON have none.
+//
+// A term is an object whose `tag` says which it is, with these fields:
+//
+//   var      name, index          a variable, by its de Bruijn index
+//   lam      name, body
+//   app      fun, arg
+//   all      uses, name, dom, img a dependent function type
+//   self     name, body
+//   data     body
+//   case     body
+//   sigma    name, fst, snd       a dependent pair type
+//   pair     fst, snd
+//   first    body
+//   second   body
+//   ref      name, def, ast       a reference, with the links of its
+//                                 definition and of its term's AST
+//   let      rec, uses, name, type, term, body
+//   type     level
+//   prop
+//   ann      type, term
+//   lit      type, value
+//   lit_type type
+//   op       op                   a primitive operation, such as "#add"
+//   hole     name                 null for a placeholder `_`
+//   meta     index
+//
+// Uses are "0", "&", "1" or "ω". Links are strings, as they are printed.
+// A literal's type is one of "natural", "integer", "bitstring", "text" and
+// "char", which are also the types of `lit_type`, and its value is a string
+// of decimal digits for a natural or integer, with a sign for a negative
+// integer, an array of bytes for a bitstring, or a string for a text or a
+// character.
+//
+// A definition is an object with the fields name, docs, transparency, which
+// is "opaque", "transparent" or "reducible", implicits, an array of whether
+// each leading binder is implicit, which may be left out, type and term. A
+// package is an object with the fields name, docs, source, the link of its
+// source, and decls, each an object whose `kind` is one of
+//
+//   def      name, def, ast, private   private may be left out
+//   open     name, alias, with, from   with is null, or an array of names
+//   test     name, test
+
+fn field<'a>(json: &'a Json, key: &str) -> Result<&'a Json, String> {
+  json.get(key).ok_or_else(|| format!("Missing field {}", key))
+}
+
+fn string(json: &Json, key: &str) -> Result<String, String> {
+  field(json, key)?
+    .as_str()
+    .map(String::from)
+    .ok_or_else(|| format!("Field {} is not a string", key))
+}
+
+fn name(json: &Json, key: &str) -> Result<Name, String> {
+  string(json, key).map(Name::from)
+}
+
+fn natural(json: &Json, key: &str) -> Result<u64, String> {
+  match field(json, key)?.as_i64() {
+    Some(x) if x >= 0 => Ok(x as u64),
+    _ => Err(format!("Field {} is not a natural number", key)),
+  }
+}
+
+fn boolean(json: &Json, key: &str) -> Result<bool, String> {
+  match field(json, key)? {
+    Json::Bool(x) => Ok(*x),
+    _ => Err(format!("Field {} is not a boolean", key)),
+  }
+}
+
+fn link(json: &Json, key: &str) -> Result<Link, String> {
+  let s = string(json, key)?;
+  match Link::parse(&s) {
+    Ok((i, link)) if i.fragment().is_empty() => Ok(link),
+    _ => Err(format!("Invalid link {}", s)),
+  }
+}
+
+// A link which may be left out, or null
+fn optional_link(json: &Json, key: &str) -> Result<Option<Link>, String> {
+  match json.get(key) {
+    None | Some(Json::Null) => Ok(None),
+    Some(_) => link(json, key).map(Some),
+  }
+}
+
+fn term(json: &Json, key: &str) -> Result<Term, String> {
+  Term::from_json(field(json, key)?)
+}
+
+fn term_box(json: &Json, key: &str) -> Result<Box<Term>, String> {
+  term(json, key).map(Box::new)
+}
+
+fn uses_name(uses: Uses) -> &'static str {
+  match uses {
+    Uses::None => "0",
+    Uses::Affi => "&",
+    Uses::Once => "1",
+    Uses::Many => "ω",
+  }
+}
+
+fn uses(json: &Json) -> Result<Uses, String> {
+  match string(json, "uses")?.as_str() {
+    "0" => Ok(Uses::None),
+    "&" => Ok(Uses::Affi),
+    "1" => Ok(Uses::Once),
+    "ω" => Ok(Uses::Many),
+    u => Err(format!("Unknown uses {}", u)),
+  }
+}
+
+fn lit_type_name(lty: LitType) -> &'static str {
+  match lty {
+    LitType::Natural => "natural",
+    LitType::Integer => "integer",
+    LitType::BitString => "bitstring",
+    LitType::Text => "text",
+    LitType::Char => "char",
+    LitType::Float => "float",
+  }
+}
+
+fn lit_type(json: &Json) -> Result<LitType, String> {
+  match string(json, "type")?.as_str() {
+    "natural" => Ok(LitType::Natural),
+    "integer" => Ok(LitType::Integer),
+    "bitstring" => Ok(LitType::BitString),
+    "text" => Ok(LitType::Text),
+    "char" => Ok(LitType::Char),
+    "float" => Ok(LitType::Float),
+    t => Err(format!("Unknown literal type {}", t)),
+  }
+}
+
+fn literal_json(lit: &Literal) -> (LitType, Json) {
+  match lit {
+    Literal::Natural(x) => (LitType::Natural, Json::str(x)),
+    Literal::Integer(x) => (LitType::Integer, Json::str(x)),
+    Literal::BitString(xs) => {
+      let bytes = xs.iter().map(|x| Json::Num(*x as i64)).collect();
+      (LitType::BitString, Json::Arr(bytes))
+    }
+    Literal::Text(x) => (LitType::Text, x.as_str().into()),
+    Literal::Char(x) => (LitType::Char, Json::str(x)),
+    Literal::Float(_) => (LitType::Float, Json::str(lit)),
+  }
+}
+
+fn literal(json: &Json) -> Result<Literal, String> {
+  let invalid = || String::from("Invalid literal value");
+  let value = field(json, "value")?;
+  match (lit_type(json)?, value) {
+    (LitType::Natural, Json::Str(x)) => {
+      x.parse::<BigUint>().map(Literal::Natural).map_err(|_| invalid())
+    }
+    (LitType::Integer, Json::Str(x)) => {
+      x.parse::<BigInt>().map(Literal::Integer).map_err(|_| invalid())
+    }
+    (LitType::BitString, Json::Arr(xs)) => xs
+      .iter()
+      .map(|x| match x {
+        Json::Num(x) if (0..256).contains(x) => Ok(*x as u8),
+        _ => Err(invalid()),
+      })
+      .collect::<Result<Vec<u8>, String>>()
+      .map(Literal::BitString),
+    (LitType::Text, Json::Str(x)) => Ok(Literal::Text(x.clone())),
+    (LitType::Char, Json::Str(x)) => {
+      let mut chars = x.chars();
+      match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Literal::Char(c)),
+        _ => Err(invalid()),
+      }
+    }
+    (LitType::Float, Json::Str(x)) => match x.parse::<f64>() {
+      Ok(x) if x.is_finite() => Ok(Literal::Float(x.to_bits())),
+      _ => Err(invalid()),
+    },
+    _ => Err(invalid()),
+  }
+}
+
+impl Term {
+  pub fn to_json(&self) -> Json {
+    let obj = |tag: &str, mut fields: Vec<(&str, Json)>| {
+      fields.insert(0, ("tag", tag.into()));
+      Json::obj(fields)
+    };
+    match self {
+      Self::Var(_, nam, idx) => obj("var", vec![
+        ("name", nam.as_str().into()),
+        ("index", (*idx).into()),
+      ]),
+      Self::Lam(_, nam, bod) => obj("lam", vec![
+        ("name", nam.as_str().into()),
+        ("body", bod.to_json()),
+      ]),
+      Self::App(_, terms) => obj("app", vec![
+        ("fun", terms.0.to_json()),
+        ("arg", terms.1.to_json()),
+      ]),
+      Self::All(_, u, nam, terms) => obj("all", vec![
+        ("uses", uses_name(*u).into()),
+        ("name", nam.as_str().into()),
+        ("dom", terms.0.to_json()),
+        ("img", terms.1.to_json()),
+      ]),
+      Self::Slf(_, nam, bod) => obj("self", vec![
+        ("name", nam.as_str().into()),
+        ("body", bod.to_json()),
+      ]),
+      Self::Dat(_, bod) => obj("data", vec![("body", bod.to_json())]),
+      Self::Cse(_, bod) => obj("case", vec![("body", bod.to_json())]),
+      Self::Sig(_, nam, terms) => obj("sigma", vec![
+        ("name", nam.as_str().into()),
+        ("fst", terms.0.to_json()),
+        ("snd", terms.1.to_json()),
+      ]),
+      Self::Par(_, terms) => obj("pair", vec![
+        ("fst", terms.0.to_json()),
+        ("snd", terms.1.to_json()),
+      ]),
+      Self::Fst(_, bod) => obj("first", vec![("body", bod.to_json())]),
+      Self::Snd(_, bod) => obj("second", vec![("body", bod.to_json())]),
+      Self::Ref(_, nam, def, ast) => obj("ref", vec![
+        ("name", nam.as_str().into()),
+        ("def", Json::str(def)),
+        ("ast", Json::str(ast)),
+      ]),
+      Self::Let(_, rec, u, nam, terms) => obj("let", vec![
+        ("rec", (*rec).into()),
+        ("uses", uses_name(*u).into()),
+        ("name", nam.as_str().into()),
+        ("type", terms.0.to_json()),
+        ("term", terms.1.to_json()),
+        ("body", terms.2.to_json()),
+      ]),
+      Self::Typ(_, lvl) => obj("type", vec![("level", (*lvl).into())]),
+      Self::Prp(_) => obj("prop", vec![]),
+      Self::Ann(_, terms) => obj("ann", vec![
+        ("type", terms.0.to_json()),
+        ("term", terms.1.to_json()),
+      ]),
+      Self::Lit(_, lit) => {
+        let (lty, value) = literal_json(lit);
+        let lty = lit_type_name(lty).into();
+        obj("lit", vec![("type", lty), ("value", value)])
+      }
+      Self::LTy(_, lty) => {
+        obj("lit_type", vec![("type", lit_type_name(*lty).into())])
+      }
+      Self::Opr(_, opr) => obj("op", vec![("op", opr.symbol().into())]),
+      Self::Hol(_, nam) => obj("hole", vec![("name", nam.clone().into())]),
+      Self::Met(_, idx) => obj("meta", vec![("index", (*idx).into())]),
+    }
+  }
+
+  pub fn from_json(json: &Json) -> Result<Self, String> {
+    let pair = |a: &str, b: &str| -> Result<Box<(Term, Term)>, String> {
+      Ok(Box::new((term(json, a)?, term(json, b)?)))
+    };
+    match string(json, "tag")?.as_str() {
+      "var" => {
+        Ok(Self::Var(None, name(json, "name")?, natural(json, "index")?))
+      }
+      "lam" => {
+        Ok(Self::Lam(None, name(json, "name")?, term_box(json, "body")?))
+      }
+      "app" => Ok(Self::App(None, pair("fun", "arg")?)),
+      "all" => Ok(Self::All(
+        None,
+        uses(json)?,
+        name(json, "name")?,
+        pair("dom", "img")?,
+      )),
+      "self" => {
+        Ok(Self::Slf(None, name(json, "name")?, term_box(json, "body")?))
+      }
+      "data" => Ok(Self::Dat(None, term_box(json, "body")?)),
+      "case" => Ok(Self::Cse(None, term_box(json, "body")?)),
+      "sigma" => {
+        Ok(Self::Sig(None, name(json, "name")?, pair("fst", "snd")?))
+      }
+      "pair" => Ok(Self::Par(None, pair("fst", "snd")?)),
+      "first" => Ok(Self::Fst(None, term_box(json, "body")?)),
+      "second" => Ok(Self::Snd(None, term_box(json, "body")?)),
+      "ref" => Ok(Self::Ref(
+        None,
+        name(json, "name")?,
+        link(json, "def")?,
+        link(json, "ast")?,
+      )),
+      "let" => Ok(Self::Let(
+        None,
+        boolean(json, "rec")?,
+        uses(json)?,
+        name(json, "name")?,
+        Box::new((
+          term(json, "type")?,
+          term(json, "term")?,
+          term(json, "body")?,
+        )),
+      )),
+      "type" => Ok(Self::Typ(None, natural(json, "level")?)),
+      "prop" => Ok(Self::Prp(None)),
+      "ann" => Ok(Self::Ann(None, pair("type", "term")?)),
+      "lit" => Ok(Self::Lit(None, literal(json)?)),
+      "lit_type" => Ok(Self::LTy(None, lit_type(json)?)),
+      "op" => {
+        let op = string(json, "op")?;
+        match PrimOp::from_symbol(op.clone()) {
+          Some(op) => Ok(Self::Opr(None, op)),
+          None => Err(format!("Unknown operation {}", op)),
+        }
+      }
+      "hole" => match field(json, "name")? {
+        Json::Null => Ok(Self::Hol(None, None)),
+        _ => Ok(Self::Hol(None, Some(string(json, "name")?))),
+      },
+      "meta" => Ok(Self::Met(None, natural(json, "index")?)),
+      tag => Err(format!("Unknown term tag {}", tag)),
+    }
+  }
+}
+
+impl Def {
+  pub fn to_json(&self) -> Json {
+    Json::obj(vec![
+      ("name", self.name.as_str().into()),
+      ("docs", self.docs.as_str().into()),
+      ("transparency", Json::str(self.transparency)),
+      (
+        "implicits",
+        Json::Arr(self.implicits.iter().map(|i| (*i).into()).collect()),
+      ),
+      ("type", self.typ_.to_json()),
+      ("term", self.term.to_json()),
+    ])
+  }
+
+  pub fn from_json(json: &Json) -> Result<Self, String> {
+    let transparency = match string(json, "transparency")?.as_str() {
+      "opaque" => Transparency::Opaque,
+      "transparent" => Transparency::Transparent,
+      "reducible" => Transparency::Reducible,
+      t => return Err(format!("Unknown transparency {}", t)),
+    };
+    let flag = |x: &Json| match x {
+      Json::Bool(x) => Some(*x),
+      _ => None,
+    };
+    let implicits = match json.get("implicits") {
+      None => Some(Vec::new()),
+      Some(Json::Arr(xs)) => xs.iter().map(flag).collect(),
+      Some(_) => None,
+    }
+    .ok_or("Field implicits is not an array of booleans")?;
+    Ok(Def {
+      pos: None,
+      name: name(json, "name")?,
+      docs: string(json, "docs")?,
+      transparency,
+      implicits,
+      typ_: term(json, "type")?,
+      term: term(json, "term")?,
+    })
+  }
+}
+
+impl Declaration {
+  pub fn to_json(&self) -> Json {
+    match self {
+      Self::Defn { name, defn, term, positions, private } => Json::obj(vec![
+        ("kind", "def".into()),
+        ("name", name.as_str().into()),
+        ("def", Json::str(defn)),
+        ("ast", Json::str(term)),
+        ("positions", positions.map(Json::str).into()),
+        ("private", (*private).into()),
+      ]),
+      Self::Open { name, alias, with, from } => {
+        let with = with.as_ref().map(|with| {
+          Json::Arr(with.iter().map(|nam| nam.as_str().into()).collect())
+        });
+        Json::obj(vec![
+          ("kind", "open".into()),
+          ("name", name.as_str().into()),
+          ("alias", alias.as_str().into()),
+          ("with", with.into()),
+          ("from", Json::str(from)),
+        ])
+      }
+      Self::Test { name, test, positions } => Json::obj(vec![
+        ("kind", "test".into()),
+        ("name", name.as_str().into()),
+        ("test", Json::str(test)),
+        ("positions", positions.map(Json::str).into()),
+      ]),
+    }
+  }
+
+  pub fn from_json(json: &Json) -> Result<Self, String> {
+    match string(json, "kind")?.as_str() {
+      "def" => Ok(Self::Defn {
+        name: name(json, "name")?,
+        defn: link(json, "def")?,
+        term: link(json, "ast")?,
+        positions: optional_link(json, "positions")?,
+        private: match json.get("private") {
+          None => false,
+          Some(_) => boolean(json, "private")?,
+        },
+      }),
+      "open" => {
+        let with = match field(json, "with")? {
+          Json::Null => None,
+          Json::Arr(names) => Some(
+            names
+              .iter()
+              .map(|nam| nam.as_str().map(Name::from))
+              .collect::<Option<Vec<Name>>>()
+              .ok_or("Field with is not an array of strings")?,
+          ),
+          _ => return Err(String::from("Field with is not an array")),
+        };
+        Ok(Self::Open {
+          name: name(json, "name")?,
+          alias: name(json, "alias")?,
+          with,
+          from: link(json, "from")?,
+        })
+      }
+      "test" => Ok(Self::Test {
+        name: name(json, "name")?,
+        test: link(json, "test")?,
+        positions: optional_link(json, "positions")?,
+      }),
+      kind => Err(format!("Unknown declaration kind {}", kind)),
+    }
+  }
+}
+
+impl Package {
+  pub fn to_json(&self) -> Json {
+    let decls = self.decls.iter().map(Declaration::to_json).collect();
+    Json::obj(vec![
+      ("name", self.name.as_str().into()),
+      ("docs", self.docs.as_str().into()),
+      ("source", Json::str(self.source)),
+      ("decls", Json::Arr(decls)),
+    ])
+  }
+
+  pub fn from_json(json: &Json) -> Result<Self, String> {
+    let decls = match field(json, "decls")? {
+      Json::Arr(decls) => decls
+        .iter()
+        .map(Declaration::from_json)
+        .collect::<Result<Vec<Declaration>, String>>()?,
+      _ => return Err(String::from("Field decls is not an array")),
+    };
+    Ok(Package {
+      name: string(json, "name")?,
+      docs: string(json, "docs")?,
+      source: link(json, "source")?,
+      decls,
+    })
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    package::tests::test_package,
+    term::tests::{
+      arbitrary_def,
+      test_refs,
+    },
+  };
+  use quickcheck::Gen;
+
+  #[test]
+  fn prints_terms() {
+    let var = Term::Var(None, Name::from("x"), 0);
+    let lam = Term::Lam(None, Name::from("x"), Box::new(var));
+    let lit = Term::Lit(None, Literal::Integer(BigInt::from(-3)));
+    let term = Term::App(None, Box::new((lam, lit)));
+    assert_eq!(
+      format!("{}", term.to_json()),
+      "{\"tag\":\"app\",\"fun\":{\"tag\":\"lam\",\"name\":\"x\",\"body\":\
+       {\"tag\":\"var\",\"name\":\"x\",\"index\":0}},\"arg\":{\"tag\":\
+       \"lit\",\"type\":\"integer\",\"value\":\"-3\"}}"
+    );
+    let hole = Json::obj(vec![("tag", "hole".into()), ("name", Json::Null)]);
+    assert_eq!(Term::from_json(&hole), Ok(Term::Hol(None, None)));
+    let unknown = Json::obj(vec![("tag", "nope".into())]);
+    assert!(Term::from_json(&unknown).is_err());
+  }
+
+  #[quickcheck]
+  fn term_json_roundtrip(x: Term) -> bool {
+    let json = Json::parse(&format!("{}", x.to_json())).unwrap();
+    Term::from_json(&json) == Ok(x)
+  }
+
+  #[test]
+  fn reads_defs_and_packages() {
+    let mut g = Gen::new(10);
+    for _ in 0..10 {
+      let def = arbitrary_def(&mut g, test_refs(), Name::from("test"));
+      let json = Json::parse(&format!("{}", def.to_json())).unwrap();
+      let read = Def::from_json(&json).unwrap();
+      assert_eq!((read.name, read.docs), (def.name, def.docs));
+      assert_eq!((read.typ_, read.term), (def.typ_, def.term));
+    }
+    let package = test_package();
+    let json = Json::parse(&format!("{}", package.to_json())).unwrap();
+    assert_eq!(Package::from_json(&json), Ok(package));
+  }
+}
@@ -0,0 +1,253 @@
+use crate::{
+  core::check::{
+    CheckError,
+    CheckErrorKind,
+  },
+  explain::Code,
+  json::Json,
+  warning::{
+    Level,
+    Warning,
+  },
+};
+
+#[cfg(feature = "native")]
+use crate::query::QueryError;
+
+use hashexpr::position::Pos;
+
+// Diagnostics as tools read them: the errors of parsing and checking, and
+// the warnings of lints, each with where it is, how severe it is, its code,
+// its message and the places related to it. The command line's `--json`
+// output and the language server both print them as this one type does, in
+// the shape of the language server protocol's diagnostics, so that they
+// always agree:
+//
+//   {
+//     "range": {"start": {"line": 1, "character": 21},
+//               "end": {"line": 1, "character": 26}},
+//     "severity": 1,
+//     "code": "Y0104",
+//     "source": "yatima",
+//     "message": "Error checking: Type mismatch ...",
+//     "relatedInformation": [
+//       {"location": {"uri": "file:///Math.ya", "range": ...},
+//        "message": "expected because of the annotation here"}
+//     ]
+//   }
+//
+// Lines and characters count from zero, as the protocol's do, while those of
+// source positions count from one. The code is that of the error, as
+// `yatima explain` explains it, or the name of the lint of a warning, and is
+// left out if there is none.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+  Error = 1,
+  Warning = 2,
+}
+
+// A place related to a diagnostic, such as the annotation of a type which a
+// term does not have, and what it has to do with it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Related {
+  pub range: Option<Pos>,
+  pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+  pub range: Option<Pos>,
+  pub severity: Severity,
+  pub code: Option<String>,
+  pub message: String,
+  pub related: Vec<Related>,
+}
+
+fn position(line: u64, character: u64) -> Json {
+  Json::obj(vec![
+    ("line", line.saturating_sub(1).into()),
+    ("character", character.saturating_sub(1).into()),
+  ])
+}
+
+// The range of a source position, whose lines and columns count from one,
+// as the protocol's, which count from zero
+pub fn range(pos: Option<Pos>) -> Json {
+  let (from, upto) = match pos {
+    Some(pos) => (
+      position(pos.from_line, pos.from_column),
+      position(pos.upto_line, pos.upto_column),
+    ),
+    None => (position(1, 1), position(1, 1)),
+  };
+  Json::obj(vec![("start", from), ("end", upto)])
+}
+
+impl Diagnostic {
+  pub fn error(range: Option<Pos>, message: impl Into<String>) -> Self {
+    Diagnostic {
+      range,
+      severity: Severity::Error,
+      code: None,
+      message: message.into(),
+      related: Vec::new(),
+    }
+  }
+
+  // A type error, without its position in its message, as its range is
+  // where it is, related to the holes it reports and what it is because of
+  pub fn of_check(e: &CheckError) -> Self {
+    let message = CheckError { pos: None, ..e.clone() }.to_string();
+    let mut diagnostic = Diagnostic::error(e.pos, message);
+    diagnostic.code = Some(e.kind.code().to_string());
+    if let CheckErrorKind::UnfilledHoles(goals) = &e.kind {
+      for goal in goals {
+        let message = format!("?{}", goal.name);
+        diagnostic.related.push(Related { range: goal.pos, message });
+      }
+    }
+    for (pos, why) in &e.related {
+      let message = why.clone();
+      diagnostic.related.push(Related { range: Some(*pos), message });
+    }
+    diagnostic
+  }
+
+  // A source which does not parse at the line and column `at`, if known,
+  // with the code of the error, if it has one, and why
+  pub fn of_parse(
+    at: Option<(u32, usize)>,
+    code: Option<Code>,
+    message: &str,
+  ) -> Self {
+    let range = at.map(|(line, column)| {
+      let (line, column) = (u64::from(line), column as u64);
+      Pos {
+        from_offset: 0,
+        from_line: line,
+        from_column: column,
+        upto_offset: 0,
+        upto_line: line,
+        upto_column: column + 1,
+      }
+    });
+    let mut diagnostic = Diagnostic::error(range, message);
+    diagnostic.code = code.map(|code| code.to_string());
+    diagnostic
+  }
+
+  // A warning, reported at `level`, which is an error if it is denied
+  pub fn of_warning(w: &Warning, level: Level) -> Self {
+    let severity =
+      if level == Level::Deny { Severity::Error } else { Severity::Warning };
+    Diagnostic {
+      range: w.pos,
+      severity,
+      code: Some(w.lint.to_string()),
+      message: w.message.clone(),
+      related: Vec::new(),
+    }
+  }
+
+  #[cfg(feature = "native")]
+  pub fn of_query(e: &QueryError) -> Self {
+    match e {
+      QueryError::Parse(at, code, message) => {
+        Diagnostic::of_parse(*at, *code, message)
+      }
+      QueryError::Check(e) => Diagnostic::of_check(e),
+      e => Diagnostic::error(None, e.to_string()),
+    }
+  }
+
+  // The diagnostic as the protocol has it, in the file at `uri`, if known,
+  // which the locations of its related places are in
+  pub fn to_json(&self, uri: Option<&str>) -> Json {
+    let mut fields =
+      vec![("range", range(self.range)), ("severity", self.severity.into())];
+    fields.extend(self.code.as_deref().map(|code| ("code", code.into())));
+    fields.push(("source", "yatima".into()));
+    fields.push(("message", self.message.as_str().into()));
+    if !self.related.is_empty() {
+      let related = self
+        .related
+        .iter()
+        .map(|related| {
+          let mut location = Vec::new();
+          location.extend(uri.map(|uri| ("uri", uri.into())));
+          location.push(("range", range(related.range)));
+          Json::obj(vec![
+            ("location", Json::obj(location)),
+            ("message", related.message.as_str().into()),
+          ])
+        })
+        .collect();
+      fields.push(("relatedInformation", Json::Arr(related)));
+    }
+    Json::obj(fields)
+  }
+}
+
+impl From<Severity> for Json {
+  fn from(severity: Severity) -> Self { Json::Num(severity as i64) }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    term::Term,
+    warning::Lint,
+  };
+
+  fn pos(line: u64, from: u64, upto: u64) -> Pos {
+    Pos {
+      from_offset: 0,
+      from_line: line,
+      from_column: from,
+      upto_offset: 0,
+      upto_line: line,
+      upto_column: upto,
+    }
+  }
+
+  #[test]
+  fn serializes() {
+    let kind = CheckErrorKind::NotAFunction(Term::Typ(None, 0));
+    let mut e = CheckError::new(Some(pos(2, 22, 25)), kind);
+    e.related.push((pos(2, 11, 19), String::from("annotated here")));
+    let diagnostic = Diagnostic::of_check(&e);
+    assert!(!diagnostic.message.starts_with("2:"), "{}", diagnostic.message);
+    let json = diagnostic.to_json(Some("file:///Math.ya"));
+    let start =
+      Json::obj(vec![("line", 1u64.into()), ("character", 21u64.into())]);
+    assert_eq!(json.get("range").and_then(|r| r.get("start")), Some(&start));
+    assert_eq!(json.get("severity"), Some(&Json::Num(1)));
+    assert_eq!(json.get("code"), Some(&Json::str("Y0106")));
+    let related = match json.get("relatedInformation") {
+      Some(Json::Arr(related)) => related[0].clone(),
+      _ => panic!("expected related information"),
+    };
+    let location = related.get("location").unwrap();
+    assert_eq!(location.get("uri"), Some(&Json::str("file:///Math.ya")));
+    assert_eq!(related.get("message"), Some(&Json::str("annotated here")));
+    let warning = Warning {
+      lint: Lint::Confusable,
+      pos: None,
+      message: String::from("The character U+0430 looks like a"),
+    };
+    let json = Diagnostic::of_warning(&warning, Level::Warn).to_json(None);
+    assert_eq!(json.get("severity"), Some(&Json::Num(2)));
+    assert_eq!(json.get("code"), Some(&Json::str("confusable")));
+    assert_eq!(json.get("relatedInformation"), None);
+    let json = Diagnostic::of_parse(None, None, "Incomplete").to_json(None);
+    assert_eq!(json.get("code"), None);
+    assert_eq!(
+      json.to_string(),
+      "{\"range\":{\"start\":{\"line\":0,\"character\":0},\"end\":{\"line\":0,\
+       \"character\":0}},\"severity\":1,\"source\":\"yatima\",\"message\":\
+       \"Incomplete\"}"
+    );
+  }
+}
@@ -0,0 +1,685 @@
+use crate::{
+  json::Json,
+  parse::term::is_valid_symbol_char,
+  print::{
+    self,
+    Options,
+  },
+  repl::session::{
+    Output,
+    Session,
+  },
+  term::Term,
+};
+
+use std::{
+  convert::TryFrom,
+  fs,
+  io,
+  net::{
+    TcpListener,
+    TcpStream,
+  },
+  path::{
+    Path,
+    PathBuf,
+  },
+  sync::{
+    mpsc,
+    Arc,
+    Mutex,
+  },
+  thread,
+  time::{
+    Duration,
+    SystemTime,
+    UNIX_EPOCH,
+  },
+};
+
+pub mod sign;
+pub mod zmtp;
+
+// A Jupyter kernel, so that Yatima can be used from notebooks. The kernel
+// listens on the sockets its connection file names, speaking the Jupyter
+// messaging protocol over ZMTP: requests come in on the shell and control
+// sockets, and are answered on the socket they came in on, while what the
+// kernel is doing and what it outputs are published on the IOPub socket,
+// and the heartbeat socket echoes what it is sent. Each cell is entered in
+// a REPL session, as declarations, brought into the scope of the cells after
+// it, or an expression, whose normal form and type are its result, or as
+// `:load <file>`, to load a package. Names in scope are completed and
+// inspected as in the REPL.
+//
+// The kernel is installed by putting a `kernel.json` of
+//
+//   {"argv": ["yatima", "kernel", "{connection_file}"],
+//    "display_name": "Yatima", "language": "yatima"}
+//
+// in a `yatima` directory of Jupyter's kernels.
+
+pub const PROTOCOL_VERSION: &str = "5.3";
+
+// Where a message is sent: in reply to the request, or published on IOPub
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+  Reply,
+  Publish,
+}
+
+pub struct Kernel {
+  session: Session,
+  count: u64,
+}
+
+// The name in `code` around the character `cursor`, and where it starts
+fn name_at(code: &str, cursor: usize) -> (String, usize) {
+  let code: Vec<char> = code.chars().collect();
+  let at = cursor.min(code.len());
+  let mut start = at;
+  while start > 0 && is_valid_symbol_char(code[start - 1]) {
+    start -= 1;
+  }
+  let mut end = at;
+  while end < code.len() && is_valid_symbol_char(code[end]) {
+    end += 1;
+  }
+  (code[start..end].iter().collect(), start)
+}
+
+// The character the cursor of a request is at, clamped to `code`
+fn cursor(content: &Json) -> usize {
+  let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+  let cursor = content.get("cursor_pos").and_then(Json::as_i64).unwrap_or(0);
+  (cursor.max(0) as usize).min(code.chars().count())
+}
+
+fn status(state: &str) -> (Channel, String, Json) {
+  let content = Json::obj(vec![("execution_state", state.into())]);
+  (Channel::Publish, String::from("status"), content)
+}
+
+fn text(s: String) -> Json { Json::obj(vec![("text/plain", s.into())]) }
+
+impl Kernel {
+  pub fn new(dir: Option<PathBuf>) -> Self {
+    Kernel { session: Session::new(dir), count: 0 }
+  }
+
+  fn kernel_info(&self) -> Json {
+    let version = env!("CARGO_PKG_VERSION");
+    Json::obj(vec![
+      ("status", "ok".into()),
+      ("protocol_version", PROTOCOL_VERSION.into()),
+      ("implementation", "yatima".into()),
+      ("implementation_version", version.into()),
+      (
+        "language_info",
+        Json::obj(vec![
+          ("name", "yatima".into()),
+          ("version", version.into()),
+          ("mimetype", "text/x-yatima".into()),
+          ("file_extension", ".ya".into()),
+        ]),
+      ),
+      ("banner", format!("Yatima {}", version).into()),
+      ("help_links", Json::Arr(Vec::new())),
+    ])
+  }
+
+  // Run a cell, returning what it outputs, or why it fails
+  fn run(&mut self, code: &str) -> Result<Option<Json>, String> {
+    let show = |term: &Term| print::pretty(term, &Options::source());
+    if let Some(path) = code.trim().strip_prefix(":load") {
+      let name = self.session.load(Path::new(path.trim()))?;
+      return Ok(Some(text(format!("Loaded package {}", name))));
+    }
+    match self.session.eval(code)? {
+      Output::Declared(names) if names.is_empty() => Ok(None),
+      Output::Declared(names) => {
+        Ok(Some(text(format!("Declared {}", names.join(", ")))))
+      }
+      Output::Value(term, typ) => {
+        Ok(Some(text(format!("{} : {}", show(&term), show(&typ)))))
+      }
+    }
+  }
+
+  fn execute(&mut self, content: &Json) -> Vec<(Channel, String, Json)> {
+    let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+    let silent = content.get("silent") == Some(&Json::Bool(true));
+    if !silent {
+      self.count += 1;
+    }
+    let count = Json::from(self.count);
+    let mut msgs = Vec::new();
+    if !silent {
+      msgs.push((
+        Channel::Publish,
+        String::from("execute_input"),
+        Json::obj(vec![
+          ("code", code.into()),
+          ("execution_count", count.clone()),
+        ]),
+      ));
+    }
+    let reply = match self.run(code) {
+      Ok(data) => {
+        if let (Some(data), false) = (data, silent) {
+          msgs.push((
+            Channel::Publish,
+            String::from("execute_result"),
+            Json::obj(vec![
+              ("execution_count", count.clone()),
+              ("data", data),
+              ("metadata", Json::obj(vec![])),
+            ]),
+          ));
+        }
+        Json::obj(vec![
+          ("status", "ok".into()),
+          ("execution_count", count),
+          ("payload", Json::Arr(Vec::new())),
+          ("user_expressions", Json::obj(vec![])),
+        ])
+      }
+      Err(e) => {
+        let error = vec![
+          ("ename", "Error".into()),
+          ("evalue", e.clone().into()),
+          ("traceback", Json::Arr(vec![e.into()])),
+        ];
+        msgs.push((
+          Channel::Publish,
+          String::from("error"),
+          Json::obj(error.clone()),
+        ));
+        let mut reply =
+          vec![("status", "error".into()), ("execution_count", count)];
+        reply.extend(error);
+        Json::obj(reply)
+      }
+    };
+    msgs.push((Channel::Reply, String::from("execute_reply"), reply));
+    msgs
+  }
+
+  fn complete(&self, content: &Json) -> Json {
+    let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+    let cursor = cursor(content);
+    let (_, start) = name_at(code, cursor);
+    let prefix: String =
+      code.chars().skip(start).take(cursor - start).collect();
+    let matches = self.session.complete(&prefix);
+    Json::obj(vec![
+      ("status", "ok".into()),
+      ("matches", Json::Arr(matches.into_iter().map(Json::from).collect())),
+      ("cursor_start", start.into()),
+      ("cursor_end", cursor.into()),
+      ("metadata", Json::obj(vec![])),
+    ])
+  }
+
+  fn inspect(&self, content: &Json) -> Json {
+    let code = content.get("code").and_then(Json::as_str).unwrap_or("");
+    let (name, _) = name_at(code, cursor(content));
+    let data = match self.session.lookup(&name) {
+      Some(def) => {
+        let typ = print::pretty(&def.typ_, &Options::source());
+        let mut info = format!("{} : {}", name, typ);
+        if !def.docs.is_empty() {
+          info.push_str(&format!("\n\n{}", def.docs));
+        }
+        text(info)
+      }
+      None => Json::obj(vec![]),
+    };
+    Json::obj(vec![
+      ("status", "ok".into()),
+      ("found", (data != Json::obj(vec![])).into()),
+      ("data", data),
+      ("metadata", Json::obj(vec![])),
+    ])
+  }
+
+  // The messages to send in answer to a request of type `msg_type`, each
+  // with where it is sent, its type and its content, and whether to shut
+  // down
+  pub fn handle(
+    &mut self,
+    msg_type: &str,
+    content: &Json,
+  ) -> (Vec<(Channel, String, Json)>, bool) {
+    let reply = |msg_type: &str, content: Json| {
+      vec![(Channel::Reply, String::from(msg_type), content)]
+    };
+    let ok = || Json::obj(vec![("status", "ok".into())]);
+    let mut shutdown = false;
+    let msgs = match msg_type {
+      "kernel_info_request" => reply("kernel_info_reply", self.kernel_info()),
+      "execute_request" => self.execute(content),
+      "complete_request" => reply("complete_reply", self.complete(content)),
+      "inspect_request" => reply("inspect_reply", self.inspect(content)),
+      "is_complete_request" => reply(
+        "is_complete_reply",
+        Json::obj(vec![("status", "complete".into())]),
+      ),
+      "history_request" => reply(
+        "history_reply",
+        Json::obj(vec![
+          ("status", "ok".into()),
+          ("history", Json::Arr(Vec::new())),
+        ]),
+      ),
+      "comm_info_request" => reply(
+        "comm_info_reply",
+        Json::obj(vec![("status", "ok".into()), ("comms", Json::obj(vec![]))]),
+      ),
+      "interrupt_request" => reply("interrupt_reply", ok()),
+      "shutdown_request" => {
+        shutdown = true;
+        let restart = content.get("restart").cloned();
+        reply(
+          "shutdown_reply",
+          Json::obj(vec![
+            ("status", "ok".into()),
+            ("restart", restart.unwrap_or(Json::Bool(false))),
+          ]),
+        )
+      }
+      // A request this kernel does not answer, which is ignored
+      _ => return (Vec::new(), false),
+    };
+    let mut all = vec![status("busy")];
+    all.extend(msgs);
+    all.push(status("idle"));
+    (all, shutdown)
+  }
+}
+
+// The current time, in ISO 8601, as messages are dated
+fn now() -> String {
+  iso_date(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+// The time `time` after the epoch, in ISO 8601
+fn iso_date(time: Duration) -> String {
+  let secs = time.as_secs();
+  let (days, rest) = ((secs / 86400) as i64, secs % 86400);
+  // The civil date of a day since the epoch, by Howard Hinnant's algorithm
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z - era * 146097;
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+    year,
+    month,
+    day,
+    rest / 3600,
+    rest / 60 % 60,
+    rest % 60,
+    time.subsec_micros()
+  )
+}
+
+// The settings of a connection file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Connection {
+  pub ip: String,
+  pub shell_port: u16,
+  pub iopub_port: u16,
+  pub stdin_port: u16,
+  pub control_port: u16,
+  pub hb_port: u16,
+  pub key: String,
+}
+
+impl Connection {
+  pub fn parse(source: &str) -> Result<Self, String> {
+    let json = Json::parse(source)?;
+    let field = |key: &str| {
+      json.get(key).ok_or_else(|| format!("No {} in the connection file", key))
+    };
+    let string = |key: &str| {
+      field(key)?.as_str().map(String::from).ok_or_else(|| {
+        format!("The {} of the connection file is not a string", key)
+      })
+    };
+    let port = |key: &str| {
+      field(key)?.as_i64().and_then(|port| u16::try_from(port).ok()).ok_or_else(
+        || format!("The {} of the connection file is not a port", key),
+      )
+    };
+    match json.get("transport").and_then(Json::as_str) {
+      Some("tcp") | None => (),
+      Some(transport) => {
+        return Err(format!("Unsupported transport {}", transport));
+      }
+    }
+    match json.get("signature_scheme").and_then(Json::as_str) {
+      Some("hmac-sha256") | None => (),
+      Some(scheme) => {
+        return Err(format!("Unsupported signature scheme {}", scheme));
+      }
+    }
+    Ok(Connection {
+      ip: string("ip")?,
+      shell_port: port("shell_port")?,
+      iopub_port: port("iopub_port")?,
+      stdin_port: port("stdin_port")?,
+      control_port: port("control_port")?,
+      hb_port: port("hb_port")?,
+      key: json.get("key").and_then(Json::as_str).unwrap_or("").to_owned(),
+    })
+  }
+}
+
+// A message of the protocol, as it is sent: the identities it is routed by,
+// and its header, parent header, metadata and content, each as JSON
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+  pub ids: Vec<Vec<u8>>,
+  pub parts: [Vec<u8>; 4],
+}
+
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+impl Message {
+  // The signature of the message by `key`, or none without a key
+  fn signature(&self, key: &[u8]) -> String {
+    if key.is_empty() {
+      return String::new();
+    }
+    let parts: Vec<&[u8]> = self.parts.iter().map(|p| p.as_slice()).collect();
+    sign::hex(&sign::hmac(key, &parts))
+  }
+
+  pub fn to_frames(&self, key: &[u8]) -> Vec<Vec<u8>> {
+    let mut frames = self.ids.clone();
+    frames.push(DELIMITER.to_vec());
+    frames.push(self.signature(key).into_bytes());
+    frames.extend(self.parts.iter().cloned());
+    frames
+  }
+
+  // The message of `frames`, if it is signed by `key`
+  pub fn from_frames(frames: Vec<Vec<u8>>, key: &[u8]) -> Result<Self, String> {
+    let at = frames
+      .iter()
+      .position(|frame| frame == DELIMITER)
+      .ok_or_else(|| String::from("No delimiter in the message"))?;
+    if frames.len() < at + 6 {
+      return Err(String::from("Missing parts of the message"));
+    }
+    let parts = [
+      frames[at + 2].clone(),
+      frames[at + 3].clone(),
+      frames[at + 4].clone(),
+      frames[at + 5].clone(),
+    ];
+    let msg = Message { ids: frames[..at].to_vec(), parts };
+    if !sign::verify(msg.signature(key).as_bytes(), &frames[at + 1]) {
+      return Err(String::from("Invalid signature"));
+    }
+    Ok(msg)
+  }
+
+  pub fn part(&self, n: usize) -> Result<Json, String> {
+    Json::parse(&String::from_utf8_lossy(&self.parts[n]))
+  }
+}
+
+// What the kernel sends, under one session
+struct Sender {
+  key: Vec<u8>,
+  session: String,
+  count: u64,
+  subscribers: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl Sender {
+  fn message(
+    &mut self,
+    ids: Vec<Vec<u8>>,
+    parent: &[u8],
+    msg_type: &str,
+    content: &Json,
+  ) -> Message {
+    self.count += 1;
+    let header = Json::obj(vec![
+      ("msg_id", format!("{}-{}", self.session, self.count).into()),
+      ("session", self.session.as_str().into()),
+      ("username", "yatima".into()),
+      ("date", now().into()),
+      ("msg_type", msg_type.into()),
+      ("version", PROTOCOL_VERSION.into()),
+    ]);
+    let parts = [
+      header.to_string().into_bytes(),
+      parent.to_vec(),
+      b"{}".to_vec(),
+      content.to_string().into_bytes(),
+    ];
+    Message { ids, parts }
+  }
+
+  // Publish a message to every subscriber, forgetting those which are gone
+  fn publish(&mut self, parent: &[u8], msg_type: &str, content: &Json) {
+    let topic = vec![format!("kernel.{}", msg_type).into_bytes()];
+    let frames =
+      self.message(topic, parent, msg_type, content).to_frames(&self.key);
+    if let Ok(mut subscribers) = self.subscribers.lock() {
+      subscribers
+        .retain(|mut stream| zmtp::send(&mut stream, &frames).is_ok());
+    }
+  }
+}
+
+// A request, with the connection it came in on
+type Request = (Arc<Mutex<TcpStream>>, Vec<Vec<u8>>);
+
+// Accept connections on `listener` for a socket of type `socket`, calling
+// `serve` with each once it is ready, on a thread of its own
+fn accept(
+  listener: TcpListener,
+  socket: &'static str,
+  serve: impl Fn(TcpStream) -> io::Result<()> + Send + Clone + 'static,
+) {
+  thread::spawn(move || {
+    for stream in listener.incoming() {
+      let serve = serve.clone();
+      thread::spawn(move || {
+        let mut stream = stream?;
+        zmtp::handshake(&mut stream, socket)?;
+        serve(stream)
+      });
+    }
+  });
+}
+
+// Forward the requests of a connection to `requests`
+fn forward(
+  requests: mpsc::Sender<Request>,
+) -> impl Fn(TcpStream) -> io::Result<()> + Send + Clone + 'static {
+  move |stream| {
+    let mut reader = stream.try_clone()?;
+    let stream = Arc::new(Mutex::new(stream));
+    loop {
+      let frames = zmtp::recv(&mut reader)?;
+      if requests.send((stream.clone(), frames)).is_err() {
+        return Ok(());
+      }
+    }
+  }
+}
+
+// Serve as a kernel on the sockets of the connection file `file`, loading
+// packages relative to `dir`, until asked to shut down
+pub fn serve(file: &Path, dir: Option<PathBuf>) -> Result<(), String> {
+  let source = fs::read_to_string(file)
+    .map_err(|e| format!("{}: {}", file.display(), e))?;
+  let conn = Connection::parse(&source)?;
+  let bind = |port: u16| {
+    TcpListener::bind((conn.ip.as_str(), port))
+      .map_err(|e| format!("{}:{}: {}", conn.ip, port, e))
+  };
+  let (requests, incoming) = mpsc::channel();
+  accept(bind(conn.shell_port)?, "ROUTER", forward(requests.clone()));
+  accept(bind(conn.control_port)?, "ROUTER", forward(requests));
+  // Nothing is asked of the user, so what comes in on stdin is dropped
+  accept(bind(conn.stdin_port)?, "ROUTER", |mut stream| loop {
+    zmtp::recv(&mut stream)?;
+  });
+  accept(bind(conn.hb_port)?, "REP", |mut stream| loop {
+    let frames = zmtp::recv(&mut stream)?;
+    zmtp::send(&mut stream, &frames)?;
+  });
+  let subscribers = Arc::new(Mutex::new(Vec::new()));
+  let subscribed = subscribers.clone();
+  // Subscriptions are read but not kept, as everything is published to
+  // every subscriber
+  accept(bind(conn.iopub_port)?, "PUB", move |mut stream| {
+    if let Ok(mut subscribers) = subscribed.lock() {
+      subscribers.push(stream.try_clone()?);
+    }
+    loop {
+      zmtp::recv(&mut stream)?;
+    }
+  });
+  let key = conn.key.into_bytes();
+  let seed = format!("{:?} {}", SystemTime::now(), std::process::id());
+  let session = sign::hex(&blake3::hash(seed.as_bytes()).as_bytes()[..16]);
+  let mut sender = Sender { key: key.clone(), session, count: 0, subscribers };
+  let mut kernel = Kernel::new(dir);
+  sender.publish(b"{}", "status", &status("starting").2);
+  for (stream, frames) in incoming {
+    let request = match Message::from_frames(frames, &key) {
+      Ok(request) => request,
+      Err(e) => {
+        warn!(target: "kernel", "Dropped a message: {}", e);
+        continue;
+      }
+    };
+    let (header, content) = match (request.part(0), request.part(3)) {
+      (Ok(header), Ok(content)) => (header, content),
+      (Err(e), _) | (_, Err(e)) => {
+        warn!(target: "kernel", "Dropped a message: {}", e);
+        continue;
+      }
+    };
+    let msg_type = header.get("msg_type").and_then(Json::as_str).unwrap_or("");
+    debug!(target: "kernel", "Received {}", msg_type);
+    let parent = request.parts[0].clone();
+    let (msgs, shutdown) = kernel.handle(msg_type, &content);
+    for (channel, msg_type, content) in msgs {
+      match channel {
+        Channel::Publish => sender.publish(&parent, &msg_type, &content),
+        Channel::Reply => {
+          let ids = request.ids.clone();
+          let reply = sender.message(ids, &parent, &msg_type, &content);
+          let frames = reply.to_frames(&key);
+          if let Ok(mut stream) = stream.lock() {
+            let _ = zmtp::send(&mut *stream, &frames);
+          }
+        }
+      }
+    }
+    if shutdown {
+      break;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  fn types(msgs: &[(Channel, String, Json)]) -> Vec<&str> {
+    msgs.iter().map(|(_, msg_type, _)| msg_type.as_str()).collect()
+  }
+
+  #[test]
+  fn handles_requests() {
+    let mut kernel = Kernel::new(None);
+    let (msgs, _) = kernel.handle("kernel_info_request", &Json::obj(vec![]));
+    assert_eq!(types(&msgs), vec!["status", "kernel_info_reply", "status"]);
+    let execute = |code: &str| {
+      Json::obj(vec![("code", code.into()), ("silent", false.into())])
+    };
+    let (msgs, _) =
+      kernel.handle("execute_request", &execute("def two : #Natural = 2"));
+    assert_eq!(types(&msgs), vec![
+      "status",
+      "execute_input",
+      "execute_result",
+      "execute_reply",
+      "status"
+    ]);
+    let (msgs, _) = kernel.handle("execute_request", &execute("#add two 1"));
+    let result = msgs[2].2.get("data").and_then(|d| d.get("text/plain"));
+    assert_eq!(result, Some(&Json::from("3 : #Natural")));
+    assert_eq!(msgs[3].2.get("execution_count"), Some(&Json::from(2u64)));
+    let (msgs, _) = kernel.handle("execute_request", &execute("nope"));
+    assert_eq!(types(&msgs)[2], "error");
+    assert_eq!(msgs[3].2.get("status"), Some(&Json::from("error")));
+    let at = |code: &str, cursor: u64| {
+      Json::obj(vec![("code", code.into()), ("cursor_pos", cursor.into())])
+    };
+    let (msgs, _) = kernel.handle("complete_request", &at("#add tw", 7));
+    let reply = &msgs[1].2;
+    assert_eq!(reply.get("matches"), Some(&Json::Arr(vec!["two".into()])));
+    assert_eq!(reply.get("cursor_start"), Some(&Json::from(5u64)));
+    let (msgs, _) = kernel.handle("inspect_request", &at("two", 1));
+    let data = msgs[1].2.get("data").and_then(|d| d.get("text/plain"));
+    assert_eq!(data, Some(&Json::from("two : #Natural")));
+    let (msgs, shutdown) =
+      kernel.handle("shutdown_request", &Json::obj(vec![]));
+    assert_eq!(types(&msgs), vec!["status", "shutdown_reply", "status"]);
+    assert!(shutdown);
+  }
+
+  #[test]
+  fn dates_messages() {
+    assert_eq!(iso_date(Duration::from_secs(0)), "1970-01-01T00:00:00.000000Z");
+    assert_eq!(
+      iso_date(Duration::from_secs(951_782_400)),
+      "2000-02-29T00:00:00.000000Z"
+    );
+    assert_eq!(
+      iso_date(Duration::from_millis(1_234_567_890_500)),
+      "2009-02-13T23:31:30.500000Z"
+    );
+  }
+
+  #[test]
+  fn signs_messages() {
+    let msg = Message {
+      ids: vec![b"client".to_vec()],
+      parts: [
+        b"{\"msg_type\":\"kernel_info_request\"}".to_vec(),
+        b"{}".to_vec(),
+        b"{}".to_vec(),
+        b"{}".to_vec(),
+      ],
+    };
+    let frames = msg.to_frames(b"secret");
+    assert_eq!(Message::from_frames(frames.clone(), b"secret"), Ok(msg));
+    assert!(Message::from_frames(frames, b"other").is_err());
+    let conn = Connection::parse(
+      "{\"ip\": \"127.0.0.1\", \"transport\": \"tcp\", \"shell_port\": 1, \
+       \"iopub_port\": 2, \"stdin_port\": 3, \"control_port\": 4, \
+       \"hb_port\": 5, \"key\": \"k\", \"signature_scheme\": \
+       \"hmac-sha256\", \"kernel_name\": \"yatima\"}",
+    )
+    .unwrap();
+    assert_eq!(conn.hb_port, 5);
+    assert_eq!(conn.key, "k");
+  }
+}
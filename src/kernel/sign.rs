@@ -0,0 +1,83 @@
+// HMAC-SHA256, which Jupyter signs its messages with, by the key of the
+// connection file
+
+use hmac::{
+  Hmac,
+  Mac,
+};
+use sha2::Sha256;
+
+// The HMAC of the concatenation of `parts` by `key`
+pub fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+  let mut mac =
+    Hmac::<Sha256>::new_varkey(key).expect("HMAC takes keys of any length");
+  for part in parts {
+    mac.input(part);
+  }
+  let mut code = [0u8; 32];
+  code.copy_from_slice(&mac.result().code());
+  code
+}
+
+// Whether the signatures `a` and `b` are equal, in a time which depends only
+// on their lengths, so a forger can't learn how much of a guess is right
+pub fn verify(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len()
+    && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  // The test cases of RFC 4231
+  #[test]
+  fn signs() {
+    assert_eq!(
+      hex(&hmac(&[0x0b; 20], &[b"Hi There"])),
+      "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+    );
+    assert_eq!(
+      hex(&hmac(b"Jefe", &[b"what do ya ", b"want for nothing?"])),
+      "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+      hex(&hmac(&[0xaa; 20], &[&[0xdd; 50]])),
+      "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"
+    );
+    let key: Vec<u8> = (1..=25).collect();
+    assert_eq!(
+      hex(&hmac(&key, &[&[0xcd; 50]])),
+      "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"
+    );
+    assert_eq!(
+      hex(&hmac(&[0x0c; 20], &[b"Test With Truncation"])[..16]),
+      "a3b6167473100ee06e0c796c2955552b"
+    );
+    let key = [0xaa; 131];
+    let data = b"Test Using Larger Than Block-Size Key - Hash Key First";
+    assert_eq!(
+      hex(&hmac(&key, &[data])),
+      "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+    let data: &[u8] = b"This is a test using a larger than block-size key \
+      and a larger than block-size data. The key needs to be hashed before \
+      being used by the HMAC algorithm.";
+    assert_eq!(
+      hex(&hmac(&key, &[data])),
+      "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2"
+    );
+  }
+
+  #[test]
+  fn verifies() {
+    assert!(verify(b"5bdc", b"5bdc"));
+    assert!(!verify(b"5bdc", b"5bdd"));
+    assert!(!verify(b"5bdc", b"5bd"));
+    assert!(verify(b"", b""));
+  }
+}
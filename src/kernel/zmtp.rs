@@ -0,0 +1,143 @@
+use std::{
+  io::{
+    self,
+    Read,
+    Write,
+  },
+  net::TcpStream,
+};
+
+// ZMTP 3, the protocol ZeroMQ sockets speak over TCP, with the NULL
+// mechanism, which is all a kernel needs, as Jupyter only listens on the
+// loopback by default and signs its messages itself. A connection starts
+// with a greeting from each side, then a READY command from each, naming
+// the type of its socket, after which messages of one or more frames are
+// sent either way. Commands sent once the connection is ready, such as the
+// subscriptions of newer versions of the protocol, are read and ignored.
+
+// The flags of a frame
+const MORE: u8 = 1;
+const LONG: u8 = 2;
+const COMMAND: u8 = 4;
+
+// The longest frame read, in bytes, so a peer can't make the kernel allocate
+// whatever length it declares. Jupyter's messages are far shorter.
+const MAX_FRAME: u64 = 64 << 20;
+
+fn invalid(e: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, String::from(e))
+}
+
+// Greet the peer on `stream` and tell it this socket is of type `socket`,
+// such as `ROUTER`, reading its greeting and READY command in turn
+pub fn handshake(stream: &mut TcpStream, socket: &str) -> io::Result<()> {
+  let mut greeting = vec![0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0x7f, 3, 0];
+  let mut mechanism = [0u8; 20];
+  mechanism[..4].copy_from_slice(b"NULL");
+  greeting.extend_from_slice(&mechanism);
+  greeting.extend_from_slice(&[0; 32]);
+  stream.write_all(&greeting)?;
+  let mut theirs = [0u8; 64];
+  stream.read_exact(&mut theirs)?;
+  if theirs[0] != 0xff || theirs[9] != 0x7f || theirs[10] < 3 {
+    return Err(invalid("not a ZMTP 3 peer"));
+  }
+  if &theirs[12..16] != b"NULL" {
+    return Err(invalid("only the NULL mechanism is supported"));
+  }
+  let mut ready = Vec::new();
+  ready.push(5);
+  ready.extend_from_slice(b"READY");
+  ready.push(11);
+  ready.extend_from_slice(b"Socket-Type");
+  ready.extend_from_slice(&(socket.len() as u32).to_be_bytes());
+  ready.extend_from_slice(socket.as_bytes());
+  write_frame(stream, COMMAND, &ready)?;
+  let (flags, body) = read_frame(stream)?;
+  if flags & COMMAND == 0 || !body.starts_with(b"\x05READY") {
+    return Err(invalid("expected a READY command"));
+  }
+  Ok(())
+}
+
+fn write_frame(
+  stream: &mut impl Write,
+  flags: u8,
+  body: &[u8],
+) -> io::Result<()> {
+  if body.len() > 255 {
+    stream.write_all(&[flags | LONG])?;
+    stream.write_all(&(body.len() as u64).to_be_bytes())?;
+  }
+  else {
+    stream.write_all(&[flags, body.len() as u8])?;
+  }
+  stream.write_all(body)
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+  let mut flags = [0u8; 1];
+  stream.read_exact(&mut flags)?;
+  let len = if flags[0] & LONG != 0 {
+    let mut len = [0u8; 8];
+    stream.read_exact(&mut len)?;
+    let len = u64::from_be_bytes(len);
+    if len > MAX_FRAME {
+      return Err(invalid("frame too long"));
+    }
+    len as usize
+  }
+  else {
+    let mut len = [0u8; 1];
+    stream.read_exact(&mut len)?;
+    usize::from(len[0])
+  };
+  let mut body = vec![0; len];
+  stream.read_exact(&mut body)?;
+  Ok((flags[0], body))
+}
+
+// Send a message of the frames `frames`
+pub fn send(stream: &mut impl Write, frames: &[Vec<u8>]) -> io::Result<()> {
+  let mut out = Vec::new();
+  for (i, frame) in frames.iter().enumerate() {
+    let flags = if i + 1 < frames.len() { MORE } else { 0 };
+    write_frame(&mut out, flags, frame)?;
+  }
+  stream.write_all(&out)?;
+  stream.flush()
+}
+
+// Receive the frames of the next message
+pub fn recv(stream: &mut impl Read) -> io::Result<Vec<Vec<u8>>> {
+  let mut frames = Vec::new();
+  loop {
+    let (flags, body) = read_frame(stream)?;
+    if flags & COMMAND != 0 {
+      continue;
+    }
+    frames.push(body);
+    if flags & MORE == 0 {
+      return Ok(frames);
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn frames_messages() {
+    let long = vec![7; 300];
+    let frames = vec![b"<IDS|MSG>".to_vec(), Vec::new(), long];
+    let mut wire = Vec::new();
+    send(&mut wire, &frames).unwrap();
+    assert_eq!(&wire[..11], b"\x01\x09<IDS|MSG>");
+    assert_eq!(recv(&mut wire.as_slice()).unwrap(), frames);
+    let mut huge = vec![LONG];
+    huge.extend_from_slice(&u64::MAX.to_be_bytes());
+    let e = recv(&mut huge.as_slice()).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+  }
+}
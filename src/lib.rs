@@ -8,23 +8,63 @@ extern crate quickcheck_macros;
 #[cfg(test)]
 extern crate rand;
 
+#[cfg(feature = "native")]
 #[macro_use]
 extern crate rocket;
 
 #[macro_use]
-extern crate log;
+extern crate tracing;
 
 #[macro_use]
 extern crate hashexpr;
 
 pub mod anon_term;
+pub mod config;
 pub mod core;
 pub mod decode_error;
 pub mod definition;
+#[cfg(feature = "native")]
+pub mod diff;
+#[cfg(feature = "native")]
+pub mod doc;
+pub mod error;
+pub mod explain;
+pub mod ffi;
+#[cfg(feature = "native")]
+pub mod format;
+pub mod formats;
+pub mod global;
+#[cfg(feature = "native")]
+pub mod graph;
 pub mod hashspace;
+#[cfg(feature = "native")]
+pub mod init;
+pub mod json;
+#[cfg(feature = "native")]
+pub mod kernel;
+#[cfg(feature = "native")]
+pub mod lsp;
 pub mod meta_term;
+pub mod name;
 pub mod package;
 pub mod parse;
+pub mod positions;
+pub mod print;
+#[cfg(feature = "native")]
+pub mod query;
+#[cfg(feature = "native")]
+pub mod render;
+#[cfg(feature = "native")]
 pub mod repl;
+pub mod sexpr;
 pub mod term;
+#[cfg(feature = "native")]
+pub mod testing;
+#[cfg(feature = "native")]
+pub mod trace;
 pub mod unembed_error;
+pub mod warning;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "native")]
+pub mod watch;
@@ -0,0 +1,555 @@
+use crate::{
+  json::{
+    diagnostic::{
+      range,
+      Diagnostic,
+    },
+    Json,
+  },
+  name::Name,
+  package::{
+    Declaration,
+    Package,
+  },
+  parse::{
+    package::import_path,
+    term::is_valid_symbol_char,
+  },
+  query::{
+    Database,
+    Parsed,
+  },
+  term::{
+    Def,
+    Link,
+  },
+  warning::{
+    Diagnostics,
+    Level,
+  },
+};
+
+use std::{
+  collections::HashMap,
+  io::{
+    self,
+    BufRead,
+    Write,
+  },
+  path::{
+    Path,
+    PathBuf,
+  },
+};
+
+// A language server, speaking the language server protocol over stdin and
+// stdout, so that editors can show the parse and type errors of a package
+// as it is edited, the types of the definitions referred to, where they are
+// defined, and which names are in scope. The editor's copy of each open
+// file is parsed and checked with the query database, as the command line
+// does, so only what an edit affects is computed again. A name is resolved
+// through the references in scope to the link of its definition, and the
+// definition found by link in the package that declares it, whichever file
+// that is in.
+
+// Read a message, framed by its Content-Length header, or nothing at the
+// end of the input
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+  let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() && length.is_some() {
+      break;
+    }
+    if let Some(n) = line.strip_prefix("Content-Length:") {
+      let n = n.trim().parse::<usize>().map_err(|e| invalid(e.to_string()))?;
+      length = Some(n);
+    }
+  }
+  let mut body = vec![0; length.unwrap_or(0)];
+  input.read_exact(&mut body)?;
+  let body = String::from_utf8(body).map_err(|e| invalid(e.to_string()))?;
+  Json::parse(&body).map(Some).map_err(invalid)
+}
+
+pub fn write_message(output: &mut impl Write, msg: &Json) -> io::Result<()> {
+  let body = msg.to_string();
+  write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+  output.flush()
+}
+
+// The path of a `file://` URI
+pub fn uri_path(uri: &str) -> Option<PathBuf> {
+  let path = uri.strip_prefix("file://")?.as_bytes();
+  let mut bytes = Vec::new();
+  let mut i = 0;
+  while i < path.len() {
+    let hex = path.get(i + 1..i + 3).and_then(|hex| {
+      u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+    });
+    match (path[i], hex) {
+      (b'%', Some(byte)) => {
+        bytes.push(byte);
+        i += 3;
+      }
+      (byte, _) => {
+        bytes.push(byte);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+// The `file://` URI of a path
+pub fn path_uri(path: &Path) -> String {
+  let mut uri = String::from("file://");
+  for byte in path.to_string_lossy().bytes() {
+    if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+      uri.push(byte as char);
+    }
+    else {
+      uri.push_str(&format!("%{:02X}", byte));
+    }
+  }
+  uri
+}
+
+// The name in `text` around the zero based `line` and `character`, and the
+// part of it before the character
+fn name_at(text: &str, line: usize, character: usize) -> (String, String) {
+  let line: Vec<char> = text.lines().nth(line).unwrap_or("").chars().collect();
+  let at = character.min(line.len());
+  let mut start = at;
+  while start > 0 && is_valid_symbol_char(line[start - 1]) {
+    start -= 1;
+  }
+  let mut end = at;
+  while end < line.len() && is_valid_symbol_char(line[end]) {
+    end += 1;
+  }
+  (line[start..end].iter().collect(), line[start..at].iter().collect())
+}
+
+// The files of the package parsed from `path` and of the packages it opens,
+// transitively, with their packages
+fn files(path: &Path, package: Package) -> Vec<(PathBuf, Package)> {
+  fn go(
+    path: PathBuf,
+    package: Package,
+    files: &mut Vec<(PathBuf, Package)>,
+  ) {
+    if files.iter().any(|(file, _)| *file == path) {
+      return;
+    }
+    let opens: Vec<(Name, Link)> = package
+      .decls
+      .iter()
+      .filter_map(|decl| match decl {
+        Declaration::Open { name, from, .. } => Some((name.clone(), *from)),
+        _ => None,
+      })
+      .collect();
+    files.push((path.clone(), package));
+    for (name, from) in opens {
+      if let Ok(package) = Package::get_link(from) {
+        go(import_path(&path, &name), package, files);
+      }
+    }
+  }
+  let mut files = Vec::new();
+  go(path.to_path_buf(), package, &mut files);
+  files
+}
+
+// The definition `name` refers to in the scope of a package, with its link
+fn lookup(parsed: &Parsed, name: &str) -> Option<(Link, Def)> {
+  let (link, _) = parsed.refs.get(name)?;
+  Some((*link, parsed.defs.get(link)?.clone()))
+}
+
+#[derive(Debug, Default)]
+pub struct Server {
+  db: Database,
+  // The editor's text of each open file, by its URI
+  docs: HashMap<String, String>,
+  shutdown: bool,
+}
+
+impl Server {
+  pub fn new() -> Self { Self::default() }
+
+  // The package in the open file at `uri`, parsed from the editor's text
+  fn parse(&mut self, uri: &str) -> Option<(PathBuf, Parsed)> {
+    let path = uri_path(uri)?;
+    let text = self.docs.get(uri)?.clone();
+    let parsed = self.db.parse_source(&path, text).ok()?;
+    Some((path, parsed))
+  }
+
+  // The diagnostics of the open file at `uri`, as a notification
+  fn diagnostics(&mut self, uri: &str) -> Vec<Json> {
+    let (path, text) = match (uri_path(uri), self.docs.get(uri)) {
+      (Some(path), Some(text)) => (path, text.clone()),
+      _ => return Vec::new(),
+    };
+    let diagnostics = match self.db.parse_source(&path, text.clone()) {
+      Err(e) => vec![Diagnostic::of_query(&e)],
+      Ok(parsed) => {
+        let mut diagnostics = Vec::new();
+        for decl in &parsed.package.decls {
+          if let Declaration::Defn { defn, .. } = decl {
+            if let Err(e) = self.db.check(&parsed.defs, *defn) {
+              diagnostics.push(Diagnostic::of_check(&e));
+            }
+          }
+        }
+        let (p, defs, refs) = (&parsed.package, &parsed.defs, &parsed.refs);
+        let mut lints = Diagnostics::configured();
+        lints.lint(&text, p, defs, refs);
+        let warnings = lints.warnings.iter().map(|w| (Level::Warn, w));
+        let errors = lints.errors.iter().map(|w| (Level::Deny, w));
+        for (level, w) in warnings.chain(errors) {
+          diagnostics.push(Diagnostic::of_warning(w, level));
+        }
+        diagnostics
+      }
+    };
+    let diagnostics =
+      diagnostics.iter().map(|d| d.to_json(Some(uri))).collect();
+    vec![notification(
+      "textDocument/publishDiagnostics",
+      Json::obj(vec![
+        ("uri", uri.into()),
+        ("diagnostics", Json::Arr(diagnostics)),
+      ]),
+    )]
+  }
+
+  // The name at the position of a request, the part of it before the
+  // position, and the package of the file it is in, with the file's path
+  fn at(
+    &mut self,
+    params: &Json,
+  ) -> Option<(String, String, PathBuf, Parsed)> {
+    let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+    let pos = params.get("position")?;
+    let line = pos.get("line")?.as_i64()? as usize;
+    let character = pos.get("character")?.as_i64()? as usize;
+    let (name, prefix) = name_at(self.docs.get(uri)?, line, character);
+    let (path, parsed) = self.parse(uri)?;
+    Some((name, prefix, path, parsed))
+  }
+
+  fn hover(&mut self, params: &Json) -> Json {
+    let at = self.at(params);
+    match at.and_then(|(name, _, _, parsed)| lookup(&parsed, &name)) {
+      Some((_, def)) => {
+        let mut value = format!("```\n{} : {}\n```", def.name, def.typ_);
+        if !def.docs.is_empty() {
+          value.push_str(&format!("\n\n{}", def.docs));
+        }
+        let contents =
+          Json::obj(vec![("kind", "markdown".into()), ("value", value.into())]);
+        Json::obj(vec![("contents", contents)])
+      }
+      None => Json::Null,
+    }
+  }
+
+  fn definition(&mut self, params: &Json) -> Json {
+    let (name, _, path, parsed) = match self.at(params) {
+      Some(at) => at,
+      None => return Json::Null,
+    };
+    let (link, def) = match lookup(&parsed, &name) {
+      Some(found) => found,
+      None => return Json::Null,
+    };
+    let declares = |package: &Package| {
+      package.decls.iter().any(|decl| match decl {
+        Declaration::Defn { defn, .. } => *defn == link,
+        _ => false,
+      })
+    };
+    match files(&path, parsed.package).into_iter().find(|(_, p)| declares(p)) {
+      Some((file, _)) => Json::obj(vec![
+        ("uri", path_uri(&file).into()),
+        ("range", range(def.pos)),
+      ]),
+      None => Json::Null,
+    }
+  }
+
+  fn completion(&mut self, params: &Json) -> Json {
+    let (prefix, parsed) = match self.at(params) {
+      Some((_, prefix, _, parsed)) => (prefix, parsed),
+      None => return Json::Arr(Vec::new()),
+    };
+    let mut names: Vec<&Name> =
+      parsed.refs.keys().filter(|name| name.starts_with(&prefix)).collect();
+    names.sort();
+    let items = names
+      .into_iter()
+      .map(|name| {
+        let detail = lookup(&parsed, name).map(|(_, def)| Json::str(def.typ_));
+        Json::obj(vec![
+          ("label", name.as_str().into()),
+          ("kind", 3u64.into()),
+          ("detail", detail.into()),
+        ])
+      })
+      .collect();
+    Json::Arr(items)
+  }
+
+  // The messages to send in reply to `msg`, and whether to exit
+  pub fn handle(&mut self, msg: &Json) -> (Vec<Json>, bool) {
+    let method = match msg.get("method").and_then(Json::as_str) {
+      Some(method) => method,
+      // A response to a request of ours, of which there are none
+      None => return (Vec::new(), false),
+    };
+    let id = msg.get("id").cloned();
+    let params = msg.get("params").cloned().unwrap_or(Json::Null);
+    let uri = params
+      .get("textDocument")
+      .and_then(|doc| doc.get("uri"))
+      .and_then(Json::as_str)
+      .map(String::from)
+      .unwrap_or_default();
+    let text = |params: &Json| {
+      params.get("textDocument").and_then(|doc| doc.get("text")).cloned()
+    };
+    let replies = match method {
+      "initialize" => {
+        let capabilities = Json::obj(vec![
+          ("textDocumentSync", 1u64.into()),
+          ("hoverProvider", true.into()),
+          ("definitionProvider", true.into()),
+          ("completionProvider", Json::obj(vec![])),
+        ]);
+        let info = Json::obj(vec![("name", "yatima".into())]);
+        vec![response(
+          id,
+          Json::obj(vec![("capabilities", capabilities), ("serverInfo", info)]),
+        )]
+      }
+      "shutdown" => {
+        self.shutdown = true;
+        vec![response(id, Json::Null)]
+      }
+      "exit" => return (Vec::new(), true),
+      "textDocument/didOpen" => {
+        if let Some(Json::Str(text)) = text(&params) {
+          self.docs.insert(uri.clone(), text);
+        }
+        self.diagnostics(&uri)
+      }
+      "textDocument/didChange" => {
+        let changes = params.get("contentChanges");
+        if let Some(Json::Arr(changes)) = changes {
+          let text = changes.last().and_then(|change| change.get("text"));
+          if let Some(Json::Str(text)) = text {
+            self.docs.insert(uri.clone(), text.clone());
+          }
+        }
+        self.diagnostics(&uri)
+      }
+      "textDocument/didSave" => self.diagnostics(&uri),
+      "textDocument/didClose" => {
+        self.docs.remove(&uri);
+        vec![notification(
+          "textDocument/publishDiagnostics",
+          Json::obj(vec![
+            ("uri", uri.into()),
+            ("diagnostics", Json::Arr(Vec::new())),
+          ]),
+        )]
+      }
+      "textDocument/hover" => vec![response(id, self.hover(&params))],
+      "textDocument/definition" => {
+        vec![response(id, self.definition(&params))]
+      }
+      "textDocument/completion" => {
+        vec![response(id, self.completion(&params))]
+      }
+      _ => match id {
+        Some(id) => vec![Json::obj(vec![
+          ("jsonrpc", "2.0".into()),
+          ("id", id),
+          (
+            "error",
+            Json::obj(vec![
+              ("code", Json::Num(-32601)),
+              ("message", format!("Unknown method {}", method).into()),
+            ]),
+          ),
+        ])],
+        // An unknown notification, which can be ignored
+        None => Vec::new(),
+      },
+    };
+    (replies, false)
+  }
+}
+
+fn response(id: Option<Json>, result: Json) -> Json {
+  Json::obj(vec![
+    ("jsonrpc", "2.0".into()),
+    ("id", id.unwrap_or(Json::Null)),
+    ("result", result),
+  ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+  Json::obj(vec![
+    ("jsonrpc", "2.0".into()),
+    ("method", method.into()),
+    ("params", params),
+  ])
+}
+
+// Serve on stdin and stdout until told to exit, returning whether the
+// client asked to shut down first, as it should
+pub fn serve() -> io::Result<bool> {
+  let stdin = io::stdin();
+  let mut input = stdin.lock();
+  let stdout = io::stdout();
+  let mut output = stdout.lock();
+  let mut server = Server::new();
+  while let Some(msg) = read_message(&mut input)? {
+    let (replies, exit) = server.handle(&msg);
+    for reply in replies {
+      write_message(&mut output, &reply)?;
+    }
+    if exit {
+      break;
+    }
+  }
+  Ok(server.shutdown)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use std::{
+    env,
+    fs,
+  };
+
+  #[test]
+  fn frames_messages() {
+    let msg = Json::obj(vec![("jsonrpc", "2.0".into()), ("id", 1u64.into())]);
+    let mut out = Vec::new();
+    write_message(&mut out, &msg).unwrap();
+    write_message(&mut out, &Json::Null).unwrap();
+    let mut input = &out[..];
+    assert_eq!(read_message(&mut input).unwrap(), Some(msg));
+    assert_eq!(read_message(&mut input).unwrap(), Some(Json::Null));
+    assert_eq!(read_message(&mut input).unwrap(), None);
+    let path = PathBuf::from("/a dir/Ünï.ya");
+    assert_eq!(path_uri(&path), "file:///a%20dir/%C3%9Cn%C3%AF.ya");
+    assert_eq!(uri_path(&path_uri(&path)), Some(path));
+    assert_eq!(uri_path("http://a"), None);
+  }
+
+  #[test]
+  fn serves() {
+    let dir = env::temp_dir().join("yatima-lsp");
+    fs::create_dir_all(&dir).unwrap();
+    let lib = dir.join("LspLib.ya");
+    fs::write(&lib, "package LspLib where\n// One\ndef one : #Natural = 1\n")
+      .unwrap();
+    let path = dir.join("Lsp.ya");
+    let uri = path_uri(&path);
+    let text = "package Lsp where\nopen LspLib\n\
+                def two : #Natural = #add one one\n";
+    let mut server = Server::new();
+    let mut send = |method: &str, params: Json| {
+      let msg = Json::obj(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", 1u64.into()),
+        ("method", method.into()),
+        ("params", params),
+      ]);
+      server.handle(&msg)
+    };
+    let doc = |text: Option<&str>| {
+      let mut fields: Vec<(&str, Json)> = vec![("uri", uri.as_str().into())];
+      fields.extend(text.map(|text| ("text", text.into())));
+      Json::obj(fields)
+    };
+    let at = |line: u64, character: u64| {
+      Json::obj(vec![
+        ("textDocument", doc(None)),
+        (
+          "position",
+          Json::obj(vec![
+            ("line", line.into()),
+            ("character", character.into()),
+          ]),
+        ),
+      ])
+    };
+    let result = |(replies, _): (Vec<Json>, bool)| {
+      replies[0].get("result").cloned().unwrap()
+    };
+    let diagnostics = |(replies, _): (Vec<Json>, bool)| {
+      match replies[0].get("params").and_then(|p| p.get("diagnostics")) {
+        Some(Json::Arr(diagnostics)) => diagnostics.clone(),
+        _ => panic!("expected diagnostics"),
+      }
+    };
+    let init = result(send("initialize", Json::obj(vec![])));
+    assert!(init.get("capabilities").is_some());
+    let opened = send(
+      "textDocument/didOpen",
+      Json::obj(vec![("textDocument", doc(Some(text)))]),
+    );
+    assert_eq!(diagnostics(opened), vec![]);
+    let hover = result(send("textDocument/hover", at(2, 27)));
+    let value = hover.get("contents").and_then(|c| c.get("value")).unwrap();
+    assert_eq!(value.as_str(), Some("```\none : #Natural\n```\n\nOne"));
+    assert_eq!(result(send("textDocument/hover", at(2, 0))), Json::Null);
+    let found = result(send("textDocument/definition", at(2, 27)));
+    assert_eq!(found.get("uri"), Some(&Json::str(path_uri(&lib))));
+    let items = match result(send("textDocument/completion", at(2, 28))) {
+      Json::Arr(items) => items,
+      _ => panic!("expected completions"),
+    };
+    let labels: Vec<&str> =
+      items.iter().filter_map(|i| i.get("label")?.as_str()).collect();
+    assert_eq!(labels, vec!["one"]);
+    let change = |text: &str| {
+      Json::obj(vec![
+        ("textDocument", doc(None)),
+        (
+          "contentChanges",
+          Json::Arr(vec![Json::obj(vec![("text", text.into())])]),
+        ),
+      ])
+    };
+    let ill_typed = "package Lsp where\ndef two : #Natural = \"two\"\n";
+    let typ = diagnostics(send("textDocument/didChange", change(ill_typed)));
+    assert_eq!(typ.len(), 1);
+    assert_eq!(typ[0].get("code"), Some(&Json::str("Y0104")));
+    let unused = "package Lsp where\nopen LspLib\ndef two : #Natural = 2\n";
+    let lint = diagnostics(send("textDocument/didChange", change(unused)));
+    assert_eq!(lint.len(), 1);
+    assert_eq!(lint[0].get("severity"), Some(&Json::Num(2)));
+    assert_eq!(lint[0].get("code"), Some(&Json::str("unused-import")));
+    let unparsed = "package Lsp where\ndef two : #Natural = (\n";
+    let parse = diagnostics(send("textDocument/didChange", change(unparsed)));
+    assert_eq!(parse.len(), 1);
+    let unknown = send("textDocument/rename", Json::Null).0;
+    assert!(unknown[0].get("error").is_some());
+    assert_eq!(result(send("shutdown", Json::Null)), Json::Null);
+    assert_eq!(send("exit", Json::Null), (vec![], true));
+  }
+}
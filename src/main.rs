@@ -1,69 +1,1526 @@
 use std::{
+  collections::HashMap,
+  env,
+  fmt,
   fs,
-  path::PathBuf,
+  path::{
+    Path,
+    PathBuf,
+  },
+  process,
+  time::{
+    Duration,
+    Instant,
+  },
 };
 
-use structopt::StructOpt;
+use tracing::info;
+use structopt::{
+  clap::ErrorKind,
+  StructOpt,
+};
 use yatima::{
-  core,
-  hashspace,
-  parse,
+  config::{
+    self,
+    Color,
+    Config,
+    EditMode,
+    Verbosity,
+  },
+  core::{
+    self,
+    check::CheckError,
+    compile::{
+      self,
+      CompileError,
+    },
+    parallel::{
+      Checker,
+      Outcome,
+    },
+    run::{
+      Stats,
+      Strategy,
+    },
+  },
+  doc,
+  definition::Definition,
+  diff::{
+    self,
+    DiffError,
+  },
+  explain,
+  format::{
+    self,
+    FormatError,
+  },
+  formats::lean,
+  graph,
+  hashspace::{
+    self,
+    ipld,
+    remote::{
+      self,
+      Remote,
+      RemoteError,
+    },
+  },
+  init::{
+    self,
+    InitError,
+  },
+  json::{
+    diagnostic::Diagnostic,
+    Json,
+  },
+  kernel,
+  lsp,
+  name::Name,
+  package::{
+    Declaration,
+    Package,
+  },
+  parse::{
+    self,
+    prelude::prelude,
+  },
+  print::{
+    self,
+    Options,
+  },
+  query::{
+    Database,
+    Parsed,
+    QueryError,
+  },
+  render::{
+    self,
+    Stream,
+    Style,
+  },
   repl,
+  term::{
+    Link,
+    Term,
+  },
+  testing,
+  trace::{
+    self,
+    Target,
+  },
+  warning::{
+    Diagnostics,
+    Level,
+  },
+  watch::{
+    self,
+    Outcomes,
+    Watcher,
+  },
 };
 
+// Allocations are counted, for `bench` to report, only in a build with the
+// `count-allocations` feature
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOC: core::bench::Counting = core::bench::Counting;
+
+// The exit codes of the command line. Results are printed to stdout and
+// diagnostics to stderr, so that what a command prints can be piped on, and
+// its exit code tells what, if anything, went wrong.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exit {
+  // Anything else: a file which is not formatted, or a remote without what
+  // it was asked for
+  Failure = 1,
+  // Invalid arguments, or a definition or link which does not exist
+  Usage = 2,
+  // A package which does not parse
+  Parse = 3,
+  // A definition or test which does not typecheck
+  Type = 4,
+  // A term whose evaluation fails
+  Eval = 5,
+  // A file, the hashspace or a remote which cannot be read or written
+  Io = 6,
+}
+
+impl Exit {
+  fn of_query(e: &QueryError) -> Self {
+    match e {
+      QueryError::Io(_) => Self::Io,
+      QueryError::Parse(..) => Self::Parse,
+      QueryError::UndefinedReference(_) => Self::Usage,
+      QueryError::Check(_) => Self::Type,
+    }
+  }
+
+  fn of_remote(e: &RemoteError) -> Self {
+    match e {
+      RemoteError::Io(_) => Self::Io,
+      RemoteError::Missing(_) | RemoteError::Mismatch(_) => Self::Failure,
+    }
+  }
+
+  fn exit(self) -> ! { process::exit(self as i32) }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(about = "A programming language for the decentralized web")]
-enum Cli {
+struct Cli {
+  /// A project configuration file, read instead of the nearest yatima.toml
+  #[structopt(long, global = true, parse(from_os_str))]
+  config: Option<PathBuf>,
+  /// The hashspace directory, overriding the configured one
+  #[structopt(long, global = true, parse(from_os_str))]
+  hashspace: Option<PathBuf>,
+  /// Whether to color output: auto, always or never, where auto colors it
+  /// on a terminal unless NO_COLOR is set
+  #[structopt(long, global = true)]
+  color: Option<Color>,
+  /// Report what is done, such as each package parsed and how long it took,
+  /// and, given twice, the detail of it
+  #[structopt(short, long, global = true, parse(from_occurrences))]
+  verbose: u8,
+  /// Report nothing but results and errors
+  #[structopt(short, long, global = true, conflicts_with = "verbose")]
+  quiet: bool,
+  /// Report everything done by these, of `parse`, `check`, `eval` and
+  /// `hashspace`, separated by commas
+  #[structopt(long, global = true, use_delimiter = true)]
+  trace_target: Vec<Target>,
+  #[structopt(subcommand)]
+  command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
   Save {
     #[structopt(parse(from_os_str))]
     input: PathBuf,
   },
+  /// Print what is stored at a link, decoded as what it is of
   Show {
     input: String,
+    /// Print the stored expression as it is, without decoding it
+    #[structopt(long)]
+    raw: bool,
+    /// Print the result as JSON
+    #[structopt(long)]
+    json: bool,
+  },
+  /// Store a package and print the links of it and each of its declarations
+  Hash {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Print the result as JSON
+    #[structopt(long)]
+    json: bool,
   },
   Parse {
     #[structopt(parse(from_os_str))]
     input: PathBuf,
+    /// Also print the type and term of each definition, and their anonymous
+    /// forms as stored in the hashspace
+    #[structopt(long)]
+    terms: bool,
+    /// Print the result as JSON
+    #[structopt(long)]
+    json: bool,
   },
   Run {
     #[structopt(parse(from_os_str))]
     input: PathBuf,
+    /// The definition to normalize
+    #[structopt(default_value = "main")]
+    def: String,
+    /// How to reduce: `lazy`, by graph reduction, or `step`, by the small-step
+    /// semantics
+    #[structopt(long, default_value = "lazy")]
+    strategy: Strategy,
+    /// The number of reductions to stop after
+    #[structopt(long)]
+    fuel: Option<u64>,
+    /// Print the number of reductions of each kind
+    #[structopt(long)]
+    stats: bool,
+    /// Run again each time the package changes
+    #[structopt(long)]
+    watch: bool,
+    #[structopt(long, conflicts_with = "watch")]
+    json: bool,
+  },
+  /// Compile definitions to a WebAssembly module exporting each of them
+  Compile {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// The definitions to export
+    #[structopt(default_value = "main")]
+    defs: Vec<String>,
+    /// Where to write the module, by default beside the package, with the
+    /// extension `wasm`
+    #[structopt(short, long, parse(from_os_str))]
+    out: Option<PathBuf>,
+  },
+  /// Translate a package, once its definitions check, and the packages it
+  /// opens to Lean 4, as far as they have counterparts in Lean
+  Lean {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Where to write the source, by default beside the package, with the
+    /// extension `lean`
+    #[structopt(short, long, parse(from_os_str))]
+    out: Option<PathBuf>,
+  },
+  /// Normalize definitions repeatedly, timing each run and counting its
+  /// reductions, and its allocations in a build with the `count-allocations`
+  /// feature
+  Bench {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// The definitions to normalize
+    #[structopt(default_value = "main")]
+    defs: Vec<String>,
+    /// `lazy` or `step`, by default both
+    #[structopt(long)]
+    strategy: Vec<Strategy>,
+    /// The number of runs to record
+    #[structopt(long, default_value = "10")]
+    runs: usize,
+    /// The number of runs to make, and not record, before them
+    #[structopt(long, default_value = "1")]
+    warmup: usize,
+    /// The number of reductions to stop each run after
+    #[structopt(long)]
+    fuel: Option<u64>,
+    #[structopt(long)]
+    json: bool,
+  },
+  /// Normalize an expression and print its normal form
+  Eval {
+    #[structopt(short, long = "expr")]
+    expr: String,
+    /// Packages to bring into scope, each by its file or its link, the later
+    /// shadowing the earlier
+    #[structopt(long)]
+    open: Vec<String>,
+    #[structopt(long, default_value = "lazy")]
+    strategy: Strategy,
+    /// The number of reductions to stop after
+    #[structopt(long)]
+    fuel: Option<u64>,
+    #[structopt(long)]
+    json: bool,
+  },
+  Check {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Check every definition, even those with a stored certificate
+    #[structopt(long)]
+    recheck: bool,
+    /// The number of threads to check on, by default one per core
+    #[structopt(long)]
+    jobs: Option<usize>,
+    /// Store the typing derivation of each definition which checks
+    #[structopt(long)]
+    derivations: bool,
+    /// Check again each time the package changes, reporting what changed
+    #[structopt(long)]
+    watch: bool,
+    #[structopt(long, conflicts_with = "watch")]
+    json: bool,
+  },
+  /// Start a read-eval-print loop
+  Repl {
+    /// Packages to load on starting, in place of the configured ones
+    #[structopt(long, parse(from_os_str))]
+    load: Vec<PathBuf>,
+    /// Neither read nor write the history of what is entered
+    #[structopt(long)]
+    no_history: bool,
+    /// `emacs` or `vi`
+    #[structopt(long)]
+    edit_mode: Option<EditMode>,
+    /// The hashspace directory to store to and read from
+    #[structopt(long, parse(from_os_str))]
+    store: Option<PathBuf>,
+    /// The project directory, which packages given to `:load` are relative to
+    /// and opened packages are searched for in
+    #[structopt(long, parse(from_os_str))]
+    dir: Option<PathBuf>,
+  },
+  /// Generate the documentation of a package
+  Doc {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Also document the packages it opens
+    #[structopt(long)]
+    imports: bool,
+    /// `markdown` or `html`
+    #[structopt(long, default_value = "markdown")]
+    format: doc::Format,
+    /// The directory to write the pages to
+    #[structopt(long, parse(from_os_str), default_value = "doc")]
+    out: PathBuf,
+  },
+  /// Print how the packages, or definitions, at two links differ
+  Diff {
+    old: String,
+    new: String,
+    /// Also print how changed definitions differ, term by term
+    #[structopt(long)]
+    terms: bool,
+    /// Print the result as JSON
+    #[structopt(long)]
+    json: bool,
+  },
+  /// Print the explanation of an error code, such as Y0007, with examples
+  Explain {
+    code: String,
+  },
+  /// Start a language server, on stdin and stdout, for editors
+  Lsp,
+  /// Run a Jupyter kernel on the sockets of a connection file
+  Kernel {
+    #[structopt(parse(from_os_str))]
+    connection: PathBuf,
+    /// The directory which packages given to `:load` are relative to
+    #[structopt(long, parse(from_os_str))]
+    dir: Option<PathBuf>,
+  },
+  /// Serve the local hashspace over HTTP, for `publish` and `get`
+  Serve {
+    #[structopt(long, default_value = "localhost")]
+    address: String,
+    #[structopt(long, default_value = "8000")]
+    port: u16,
+    /// The hashspace directory to serve, in place of the configured one
+    #[structopt(long, parse(from_os_str))]
+    store: Option<PathBuf>,
+    /// Refuse to store anything put
+    #[structopt(long)]
+    read_only: bool,
+    /// Store only what is put with this token, as a bearer token
+    #[structopt(long, conflicts_with = "read-only")]
+    token: Option<String>,
+  },
+  /// Print the graph of the packages a package opens, transitively
+  Graph {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Also graph their definitions and what each refers to
+    #[structopt(long)]
+    defs: bool,
+    /// Instead draw the binders and applications of this definition's term
+    #[structopt(long, conflicts_with = "defs")]
+    term: Option<String>,
+    /// `dot` or `json`
+    #[structopt(long, default_value = "dot")]
+    format: graph::Format,
+  },
+  /// Format a package's source in place
+  Fmt {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Only check that the source is formatted, failing if it is not
+    #[structopt(long)]
+    check: bool,
+  },
+  /// Create a package in the current directory
+  Init {
+    name: String,
+  },
+  /// Put a package and everything it refers to in a remote hashspace
+  Publish {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// A directory or an `http://` hashspace server, by default the one in
+    /// the YATIMA_REMOTE environment variable, or else the configured one
+    #[structopt(long)]
+    remote: Option<Remote>,
+  },
+  /// Get a package and everything it refers to from a remote hashspace
+  Get {
+    link: String,
+    #[structopt(long)]
+    remote: Option<Remote>,
+    /// Also write the sources of the package and those it opens to this
+    /// directory
+    #[structopt(long, parse(from_os_str))]
+    sources: Option<PathBuf>,
+  },
+  /// Write a package and everything it refers to as IPLD blocks to a CAR
+  /// file, by default one beside it, and print the CID of its root
+  Export {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    #[structopt(long, parse(from_os_str))]
+    out: Option<PathBuf>,
   },
-  Repl,
+  /// Put the blocks of a CAR file in the hashspace and print the link of its
+  /// root
+  Import {
+    #[structopt(parse(from_os_str))]
+    car: PathBuf,
+  },
+  /// Run the tests of a package, or of each package in a directory
+  Test {
+    #[structopt(parse(from_os_str))]
+    input: PathBuf,
+    /// Only run the tests whose names, qualified by their packages, contain
+    /// this
+    #[structopt(long)]
+    filter: Option<String>,
+    /// The number of threads to run tests on, by default one per core
+    #[structopt(long)]
+    jobs: Option<usize>,
+    /// Also run the tests of the packages opened
+    #[structopt(long)]
+    imports: bool,
+    /// Run again each time a package changes, reporting what changed
+    #[structopt(long)]
+    watch: bool,
+    #[structopt(long, conflicts_with = "watch")]
+    json: bool,
+  },
+}
+
+// The files the definitions of the package parsed from `path`, and of the
+// packages it opens, are declared in
+fn def_files(
+  path: &Path,
+  package: &Package,
+  files: &mut HashMap<Link, PathBuf>,
+) {
+  for decl in &package.decls {
+    match decl {
+      Declaration::Defn { defn, .. } | Declaration::Test { test: defn, .. } => {
+        files.entry(*defn).or_insert_with(|| path.to_path_buf());
+      }
+      Declaration::Open { name, from, .. } => {
+        if let Ok(pack) = Package::get_link(*from) {
+          let path = parse::package::import_path(path, name);
+          def_files(&path, &pack, files);
+        }
+      }
+    }
+  }
+}
+
+// Print a type error in the definition `name`, located in the file the
+// definition is declared in, along with the line it starts on
+fn report(name: &str, file: Option<&PathBuf>, e: &CheckError) {
+  let style = Style::of(Stream::Stderr);
+  let file = file.map(PathBuf::as_path);
+  eprintln!("{}", render::check_error(style, name, file, e));
+}
+
+// The package files to test: `path`, or the files of packages in it if it is
+// a directory
+fn package_files(path: &Path) -> Vec<PathBuf> {
+  if !path.is_dir() {
+    return vec![path.to_path_buf()];
+  }
+  let mut files: Vec<PathBuf> = fs::read_dir(path)
+    .map(|entries| {
+      entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.extension().map_or(false, |ext| ext == "ya"))
+        .collect()
+    })
+    .unwrap_or_default();
+  files.sort();
+  files
+}
+
+// The remote given, or else the one in the environment, or else the
+// configured one, exiting if there is none
+fn remote_or_exit(remote: Option<Remote>) -> Remote {
+  let remote = remote.map(Ok).or_else(|| {
+    env::var("YATIMA_REMOTE")
+      .ok()
+      .or_else(|| config::get().remote.clone())
+      .map(|remote| remote.parse::<Remote>())
+  });
+  match remote {
+    Some(Ok(remote)) => remote,
+    Some(Err(e)) => fail(false, Exit::Usage, e),
+    None => fail(
+      false,
+      Exit::Usage,
+      "No remote: pass --remote, set YATIMA_REMOTE or configure one",
+    ),
+  }
+}
+
+// Compute the outcomes of the packages in the files `inputs` each time one
+// of them, or a package they open, changes, printing those which changed.
+// The same database is used throughout, so what did not change is not
+// computed again.
+fn rerun_on_change(
+  inputs: Vec<PathBuf>,
+  mut outcomes: impl FnMut(&mut Database, &Parsed) -> Outcomes,
+) -> ! {
+  let color = Style::of(Stream::Stdout).color;
+  let mut db = Database::new();
+  let mut watcher = Watcher::new();
+  watcher.watch(inputs.clone());
+  let mut before = Outcomes::new();
+  loop {
+    let mut after = Outcomes::new();
+    let mut files = inputs.clone();
+    for input in &inputs {
+      match db.parse(input) {
+        Ok(parsed) => {
+          files.extend(watch::package_files(input, &parsed.package));
+          after.extend(outcomes(&mut db, &parsed));
+        }
+        Err(e) => {
+          after.insert(format!("{}", input.display()), Err(format!("{}", e)));
+        }
+      }
+    }
+    files.sort();
+    files.dedup();
+    watcher.watch(files);
+    for delta in watch::deltas(&before, &after) {
+      println!("{}", delta.paint(color));
+    }
+    let failed = after.values().filter(|res| res.is_err()).count();
+    let ok = after.len() - failed;
+    println!("{} ok, {} failed, watching for changes", ok, failed);
+    before = after;
+    watcher.wait(Duration::from_millis(200));
+  }
+}
+
+// Exit with `code` after an error, which is printed as JSON on stdout if
+// `json`
+fn fail(json: bool, code: Exit, e: impl fmt::Display) -> ! {
+  if json {
+    let code = Json::Num(code as i64);
+    println!("{}", Json::obj(vec![("error", Json::str(e)), ("code", code)]));
+  }
+  else {
+    let style = Style::of(Stream::Stderr);
+    eprintln!("{}", render::error(style, &e.to_string()));
+  }
+  code.exit()
+}
+
+// A type error in JSON, as the language server publishes it, with the
+// places related to it in `file`, if it is known
+fn diagnostic(e: &CheckError, file: Option<&Path>) -> Json {
+  let uri = file.map(lsp::path_uri);
+  Diagnostic::of_check(e).to_json(uri.as_deref())
+}
+
+// Parse the package in the file at `path`, exiting if it cannot be
+fn parse_or_exit(path: &Path, json: bool) -> Parsed {
+  let start = Instant::now();
+  let parsed = Database::new().parse(path).unwrap_or_else(|e| match e {
+    QueryError::Parse(Some(at), code, message) if !json => {
+      let style = Style::of(Stream::Stderr);
+      let e = render::parse_error(style, path, at, code, &message);
+      eprintln!("{}", e);
+      Exit::Parse.exit()
+    }
+    e if json => {
+      let code = Exit::of_query(&e);
+      println!(
+        "{}",
+        Json::obj(vec![
+          ("error", Json::str(&e)),
+          ("code", Json::Num(code as i64)),
+          ("diagnostic", Diagnostic::of_query(&e).to_json(None)),
+        ])
+      );
+      code.exit()
+    }
+    e => fail(json, Exit::of_query(&e), e),
+  });
+  let time = start.elapsed();
+  let path = path.display();
+  info!(target: "parse", file = %path, link = %parsed.link, ?time, "Parsed");
+  parsed
+}
+
+// The declarations of a parsed package and the references in scope at its
+// end, as `dump` prints them, in JSON
+fn dump_json(parsed: &Parsed, terms: bool) -> Json {
+  let p = &parsed.package;
+  let decls = p
+    .decls
+    .iter()
+    .map(|decl| match decl {
+      Declaration::Defn { name, defn, term, .. } => {
+        let mut fields = vec![
+          ("kind", "def".into()),
+          ("name", name.as_str().into()),
+          ("link", Json::str(defn)),
+          ("term_link", Json::str(term)),
+        ];
+        if terms {
+          let def = &parsed.defs[defn];
+          let (_, typ, term) = def.clone().embed();
+          fields.extend(vec![
+            ("def", Json::str(def)),
+            ("type_anon", Json::str(typ.encode())),
+            ("term_anon", Json::str(term.encode())),
+          ]);
+        }
+        Json::obj(fields)
+      }
+      Declaration::Open { name, alias, from, .. } => Json::obj(vec![
+        ("kind", "open".into()),
+        ("name", name.as_str().into()),
+        ("alias", alias.as_str().into()),
+        ("link", Json::str(from)),
+      ]),
+      Declaration::Test { name, test, .. } => Json::obj(vec![
+        ("kind", "test".into()),
+        ("name", name.as_str().into()),
+        ("link", Json::str(test)),
+      ]),
+    })
+    .collect();
+  let mut names: Vec<&Name> = parsed.refs.keys().collect();
+  names.sort();
+  let refs = names
+    .into_iter()
+    .map(|name| {
+      let (def_link, ast_link) = parsed.refs[name];
+      Json::obj(vec![
+        ("name", name.as_str().into()),
+        ("link", Json::str(def_link)),
+        ("term_link", Json::str(ast_link)),
+      ])
+    })
+    .collect();
+  Json::obj(vec![
+    ("package", p.name.as_str().into()),
+    ("link", Json::str(parsed.link)),
+    ("declarations", Json::Arr(decls)),
+    ("refs", Json::Arr(refs)),
+  ])
+}
+
+// The kind and name of something `hash` prints the links of, its link, and
+// the links of its type and term if it is a definition or test
+type Hash<'a> = (&'static str, &'a str, Link, Option<Link>, Option<Link>);
+
+// The links of a parsed package, its source and each of its declarations
+fn hashes(parsed: &Parsed) -> Vec<Hash> {
+  let p = &parsed.package;
+  let anons = |link: Link| match hashspace::get(link).map(Definition::decode) {
+    Some(Ok(def)) => (Some(def.type_anon), Some(def.term_anon)),
+    _ => (None, None),
+  };
+  let mut rows = vec![
+    ("package", p.name.as_str(), parsed.link, None, None),
+    ("source", p.name.as_str(), p.source, None, None),
+  ];
+  for decl in &p.decls {
+    let (kind, name, link) = match decl {
+      Declaration::Defn { name, defn, .. } => ("def", name, *defn),
+      Declaration::Open { name, from, .. } => ("open", name, *from),
+      Declaration::Test { name, test, .. } => ("test", name, *test),
+    };
+    let (typ, term) = match decl {
+      Declaration::Open { .. } => (None, None),
+      _ => anons(link),
+    };
+    rows.push((kind, name.as_str(), link, typ, term));
+  }
+  rows
+}
+
+// Print the declarations of a parsed package and the references in scope at
+// its end, with their links, and optionally the definitions themselves
+fn dump(parsed: &Parsed, terms: bool) {
+  let p = &parsed.package;
+  println!("package {} {}", p.name, parsed.link);
+  for decl in &p.decls {
+    match decl {
+      Declaration::Defn { name, defn, term, .. } => {
+        println!("  def {} {} {}", name, defn, term);
+      }
+      Declaration::Open { name, alias, from, .. } => {
+        println!("  open {} as {} {}", name, alias, from);
+      }
+      Declaration::Test { name, test, .. } => {
+        println!("  test {} {}", name, test);
+      }
+    }
+  }
+  let mut names: Vec<&Name> = parsed.refs.keys().collect();
+  names.sort();
+  println!("refs");
+  for name in names {
+    let (def_link, ast_link) = parsed.refs[name];
+    println!("  {} {} {}", name, def_link, ast_link);
+  }
+  if terms {
+    for decl in &p.decls {
+      if let Declaration::Defn { defn, .. } = decl {
+        let def = &parsed.defs[defn];
+        let (_, typ, term) = def.clone().embed();
+        println!("{}", def);
+        println!("  type {}", typ.encode());
+        println!("  term {}", term.encode());
+      }
+    }
+  }
 }
-//
+
 fn main() {
-  let command = Cli::from_args();
+  let cli = match Cli::from_iter_safe(env::args_os()) {
+    Ok(cli) => cli,
+    Err(e) => match e.kind {
+      ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+      _ => fail(false, Exit::Usage, e.message),
+    },
+  };
+  let mut config = Config::load(cli.config)
+    .unwrap_or_else(|e| fail(false, Exit::Usage, e))
+    .merge(Config {
+      hashspace: cli.hashspace,
+      color: cli.color,
+      verbosity: match (cli.verbose, cli.quiet) {
+        (0, false) => None,
+        (0, true) => Some(Verbosity::Quiet),
+        (1, _) => Some(Verbosity::Verbose),
+        _ => Some(Verbosity::Debug),
+      },
+      ..Config::default()
+    });
+  if let Command::Repl { load, edit_mode, store, dir, .. } = &cli.command {
+    config = config.merge(Config {
+      hashspace: store.clone(),
+      edit_mode: *edit_mode,
+      load: load.clone(),
+      ..Config::default()
+    });
+    if let Some(dir) = dir {
+      config.search.insert(0, dir.clone());
+    }
+  }
+  if let Command::Serve { store: Some(store), .. } = &cli.command {
+    config.hashspace = Some(store.clone());
+  }
+  config::set(config);
+  trace::init(config::verbosity(), cli.trace_target);
+  let command = cli.command;
   match command {
-    Cli::Repl => repl::main().unwrap(),
-    Cli::Parse { input } => {
-      let env = parse::package::PackageEnv::new(input);
-      let (_, p, ..) = parse::package::parse_file(env);
-      println!("Package parsed:\n{}", p);
-    }
-    Cli::Run { input } => {
-      let env = parse::package::PackageEnv::new(input.clone());
-      let (_, p, defs, refs) = parse::package::parse_file(env);
-      let (def_link, _) = refs.get("main").expect(&format!(
-        "No `main` expression in package {} from file {:?}",
-        p.name, input
-      ));
-      let def = defs.get(def_link).expect("Unknown link for `main` expression");
-      let dag = core::dag::DAG::from_term(def.to_owned().term);
-      let red = core::eval::norm(&defs, dag);
-      println!("{}", red);
-    }
-    Cli::Save { input } => {
-      let string = fs::read_to_string(input).unwrap();
-      let expr = hashexpr::parse(&string).unwrap().1;
+    Command::Repl { no_history, dir, .. } => {
+      if let Err(e) = repl::main(dir, !no_history) {
+        fail(false, Exit::Io, e)
+      }
+    }
+    Command::Doc { input, imports, format, out } => {
+      let parsed = parse_or_exit(&input, false);
+      let pages = doc::pages(parsed.link, imports).unwrap_or_else(|e| {
+        let e = format!("Cannot read package {}: {:?}", parsed.link, e);
+        fail(false, Exit::Io, e)
+      });
+      let written = fs::create_dir_all(&out).and_then(|()| {
+        for (file, contents) in doc::render(&pages, format) {
+          let path = out.join(file);
+          fs::write(&path, contents)?;
+          println!("{}", path.display());
+        }
+        Ok(())
+      });
+      if let Err(e) = written {
+        fail(false, Exit::Io, format!("{}: {}", out.display(), e));
+      }
+    }
+    Command::Diff { old, new, terms, json } => {
+      let link = |input: &str| match Link::parse(input) {
+        Ok((_, link)) => link,
+        Err(_) => fail(json, Exit::Usage, format!("Invalid link {}", input)),
+      };
+      match diff::diff(link(&old), link(&new), terms) {
+        Ok(diff) if json => println!("{}", diff::json(&diff)),
+        Ok(diff) => println!("{}", diff),
+        Err(e @ DiffError::Incomparable(..)) => fail(json, Exit::Usage, e),
+        Err(e) => fail(json, Exit::Failure, e),
+      }
+    }
+    Command::Lsp => match lsp::serve() {
+      Ok(true) => (),
+      // The client exited without asking to shut down first
+      Ok(false) => Exit::Failure.exit(),
+      Err(e) => fail(false, Exit::Io, e),
+    },
+    Command::Explain { code } => {
+      match code.parse().ok().and_then(explain::explain) {
+        Some(explanation) => println!("{}", explanation),
+        None => {
+          fail(false, Exit::Usage, format!("Unknown error code {}", code))
+        }
+      }
+    }
+    Command::Kernel { connection, dir } => {
+      if let Err(e) = kernel::serve(&connection, dir) {
+        fail(false, Exit::Io, e)
+      }
+    }
+    Command::Serve { address, port, read_only, token, .. } => {
+      let e = hashspace::server::serve(&address, port, read_only, token);
+      fail(false, Exit::Io, e)
+    }
+    Command::Graph { input, defs, term, format } => {
+      let json = format == graph::Format::Json;
+      let parsed = parse_or_exit(&input, json);
+      if let Some(name) = term {
+        if json {
+          fail(true, Exit::Usage, "A term can only be drawn in dot format");
+        }
+        match parsed.refs.get(name.as_str()) {
+          Some((def_link, _)) => {
+            print!("{}", parsed.defs[def_link].term.to_dot())
+          }
+          None => fail(
+            false,
+            Exit::Usage,
+            format!(
+              "No `{}` definition in package {} from file {:?}",
+              name, parsed.package.name, input
+            ),
+          ),
+        }
+        return;
+      }
+      let res = match format {
+        graph::Format::Dot => parsed.package.to_dot(defs),
+        graph::Format::Json => {
+          graph::graph(parsed.link, defs).map(|g| graph::render(&g, format))
+        }
+      };
+      match res {
+        Ok(out) => print!("{}", out),
+        Err(e) => {
+          let e = format!("Cannot read package {}: {:?}", parsed.link, e);
+          fail(json, Exit::Io, e)
+        }
+      }
+    }
+    Command::Fmt { input, check } => {
+      let formatted = match format::format_file(&input) {
+        Ok(formatted) => formatted,
+        Err(FormatError::Query(e)) => fail(false, Exit::of_query(&e), e),
+        Err(e) => fail(false, Exit::Failure, e),
+      };
+      let source = fs::read_to_string(&input).unwrap_or_default();
+      if formatted == source {
+        return;
+      }
+      if check {
+        let e = format!("{} is not formatted", input.display());
+        fail(false, Exit::Failure, e);
+      }
+      if let Err(e) = fs::write(&input, formatted) {
+        fail(false, Exit::Io, format!("{}: {}", input.display(), e));
+      }
+    }
+    Command::Init { name } => match init::init(Path::new("."), &name) {
+      Ok(path) => println!("Created {}", path.display()),
+      Err(e) => {
+        let code = match e {
+          InitError::InvalidName(_) => Exit::Usage,
+          InitError::Exists(_) => Exit::Failure,
+          InitError::Io(_) => Exit::Io,
+        };
+        fail(false, code, e)
+      }
+    },
+    Command::Publish { input, remote } => {
+      let remote = remote_or_exit(remote);
+      let parsed = parse_or_exit(&input, false);
+      match remote::publish(&remote, parsed.link) {
+        Ok(count) => {
+          eprintln!("Published {} expressions to {}", count, remote);
+          println!("{}", parsed.link);
+        }
+        Err(e) => fail(false, Exit::of_remote(&e), e),
+      }
+    }
+    Command::Get { link, remote, sources } => {
+      let remote = remote_or_exit(remote);
+      let link = match Link::parse(&link) {
+        Ok((_, link)) => link,
+        Err(_) => fail(false, Exit::Usage, format!("Invalid link {}", link)),
+      };
+      let got = remote::fetch(&remote, link).and_then(|count| {
+        eprintln!("Got {} expressions from {}", count, remote);
+        match sources {
+          Some(dir) => remote::materialize(&dir, link),
+          None => Ok(Vec::new()),
+        }
+      });
+      match got {
+        Ok(paths) => {
+          for path in paths {
+            println!("{}", path.display());
+          }
+        }
+        Err(e) => fail(false, Exit::of_remote(&e), e),
+      }
+    }
+    Command::Export { input, out } => {
+      let parsed = parse_or_exit(&input, false);
+      let blocks = ipld::export(parsed.link)
+        .unwrap_or_else(|e| fail(false, Exit::Failure, e));
+      // The block of the package is last, after those it links to
+      let root = blocks[blocks.len() - 1].cid;
+      let out = out.unwrap_or_else(|| input.with_extension("car"));
+      if let Err(e) = fs::write(&out, ipld::write_car(root, &blocks)) {
+        fail(false, Exit::Io, format!("{}: {}", out.display(), e));
+      }
+      eprintln!("Exported {} blocks to {}", blocks.len(), out.display());
+      println!("{}", root);
+    }
+    Command::Import { car } => {
+      let bytes = fs::read(&car).unwrap_or_else(|e| {
+        fail(false, Exit::Io, format!("{}: {}", car.display(), e))
+      });
+      let link = ipld::read_car(&bytes)
+        .and_then(|(root, blocks)| ipld::import(root, &blocks))
+        .unwrap_or_else(|e| fail(false, Exit::Failure, e));
+      println!("{}", link);
+    }
+    Command::Test { input, filter, jobs, imports, watch, json } => {
+      let jobs = jobs.unwrap_or_else(num_cpus::get);
+      if watch {
+        // A test refers to what it tests by link, so a test with the same
+        // link has the same result
+        let mut results: HashMap<Link, Result<String, String>> = HashMap::new();
+        rerun_on_change(package_files(&input), |_, parsed| {
+          let tests: Vec<_> = testing::tests(parsed.link, imports)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|test| test.matches(&filter))
+            .collect();
+          let new: Vec<_> = tests
+            .iter()
+            .filter(|test| !results.contains_key(&test.link))
+            .cloned()
+            .collect();
+          let ran = testing::run_tests(&parsed.defs, &new, jobs);
+          for (test, res) in new.iter().zip(ran) {
+            let res = res.map(|()| String::new()).map_err(|e| format!("{}", e));
+            results.insert(test.link, res);
+          }
+          tests
+            .iter()
+            .map(|test| (test.name(), results[&test.link].clone()))
+            .collect()
+        })
+      }
+      let mut seen = Vec::new();
+      let (mut passed, mut failed) = (0u64, 0u64);
+      let mut reports = Vec::new();
+      for path in package_files(&input) {
+        let parsed = parse_or_exit(&path, json);
+        let mut files = HashMap::new();
+        def_files(&path, &parsed.package, &mut files);
+        let tests = testing::tests(parsed.link, imports).unwrap_or_else(|e| {
+          let e = format!("Cannot read package {}: {:?}", parsed.link, e);
+          fail(json, Exit::Io, e)
+        });
+        let tests: Vec<_> = tests
+          .into_iter()
+          .filter(|test| test.matches(&filter) && !seen.contains(&test.link))
+          .collect();
+        seen.extend(tests.iter().map(|test| test.link));
+        let results = testing::run_tests(&parsed.defs, &tests, jobs);
+        for (test, res) in tests.iter().zip(results.iter()) {
+          match res {
+            Ok(()) => passed += 1,
+            Err(_) => failed += 1,
+          }
+          if json {
+            let file = files.get(&test.link).map(|file| file.as_path());
+            reports.push(Json::obj(vec![
+              ("name", test.name().into()),
+              ("link", Json::str(test.link)),
+              ("passed", res.is_ok().into()),
+              ("error", res.as_ref().err().map(|e| diagnostic(e, file)).into()),
+            ]));
+            continue;
+          }
+          match res {
+            Ok(()) => println!("✓ {}", test.name()),
+            Err(e) => report(&test.name(), files.get(&test.link), e),
+          }
+        }
+      }
+      if json {
+        println!(
+          "{}",
+          Json::obj(vec![
+            ("tests", Json::Arr(reports)),
+            ("passed", passed.into()),
+            ("failed", failed.into()),
+          ])
+        );
+      }
+      else {
+        println!("{} passed, {} failed", passed, failed);
+      }
+      if failed > 0 {
+        Exit::Type.exit();
+      }
+    }
+    Command::Hash { input, json } => {
+      let parsed = parse_or_exit(&input, json);
+      let rows = hashes(&parsed);
+      if json {
+        let rows = rows
+          .into_iter()
+          .map(|(kind, name, link, typ, term)| {
+            Json::obj(vec![
+              ("kind", kind.into()),
+              ("name", name.into()),
+              ("link", Json::str(link)),
+              ("type", typ.map(Json::str).into()),
+              ("term", term.map(Json::str).into()),
+            ])
+          })
+          .collect();
+        println!("{}", Json::Arr(rows));
+      }
+      else {
+        let width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+        let or_none = |link: Option<Link>| {
+          link.map_or_else(|| String::from("-"), |link| link.to_string())
+        };
+        for (kind, name, link, typ, term) in rows {
+          println!(
+            "{:<7} {:<width$} {} {} {}",
+            kind,
+            name,
+            link,
+            or_none(typ),
+            or_none(term),
+            width = width
+          );
+        }
+      }
+    }
+    Command::Parse { input, terms, json } => {
+      let parsed = parse_or_exit(&input, json);
+      if json {
+        println!("{}", dump_json(&parsed, terms));
+      }
+      else {
+        dump(&parsed, terms);
+      }
+    }
+    Command::Run { input, def, strategy, fuel, stats, watch: true, .. } => {
+      rerun_on_change(vec![input], |_, parsed| {
+        let res = match parsed.refs.get(def.as_str()) {
+          Some((def_link, ast_link)) => {
+            let term = Term::Ref(None, Name::from(&def), *def_link, *ast_link);
+            let mut counts = Stats::new(fuel);
+            core::run::run(&parsed.defs, &term, strategy, &mut counts)
+              .map(|term| {
+                if stats {
+                  format!("{}\n{}", term, counts)
+                }
+                else {
+                  format!("{}", term)
+                }
+              })
+              .map_err(|e| format!("{}", e))
+          }
+          None => Err(format!("No `{}` definition", def)),
+        };
+        vec![(def.clone(), res)].into_iter().collect()
+      })
+    }
+    Command::Run { input, def, strategy, fuel, stats, json, .. } => {
+      let parsed = parse_or_exit(&input, json);
+      let (def_link, ast_link) = match parsed.refs.get(def.as_str()) {
+        Some(links) => *links,
+        None => fail(
+          json,
+          Exit::Usage,
+          format!(
+            "No `{}` definition in package {} from file {:?}",
+            def, parsed.package.name, input
+          ),
+        ),
+      };
+      let term = Term::Ref(None, Name::from(def.as_str()), def_link, ast_link);
+      let mut counts = Stats::new(fuel);
+      let res = core::run::run(&parsed.defs, &term, strategy, &mut counts);
+      if json {
+        let counts = counts
+          .counts
+          .iter()
+          .map(|(rule, count)| (String::from(*rule), (*count).into()))
+          .collect();
+        println!(
+          "{}",
+          Json::obj(vec![
+            ("def", def.as_str().into()),
+            ("link", Json::str(def_link)),
+            ("result", res.as_ref().ok().map(Json::str).into()),
+            ("error", res.as_ref().err().map(Json::str).into()),
+            ("reductions", Json::Obj(counts)),
+          ])
+        );
+      }
+      else {
+        match &res {
+          Ok(term) => {
+            println!("{}", print::pretty(term, &Options::terminal()))
+          }
+          Err(e) => eprintln!("{}", e),
+        }
+        if stats {
+          eprintln!("{}", counts);
+        }
+      }
+      if res.is_err() {
+        Exit::Eval.exit();
+      }
+    }
+    Command::Compile { input, defs, out } => {
+      let parsed = parse_or_exit(&input, false);
+      let mut exports = Vec::new();
+      for def in defs {
+        match parsed.refs.get(def.as_str()) {
+          Some((def_link, _)) => exports.push((def, *def_link)),
+          None => fail(
+            false,
+            Exit::Usage,
+            format!(
+              "No `{}` definition in package {} from file {:?}",
+              def, parsed.package.name, input
+            ),
+          ),
+        }
+      }
+      let module =
+        compile::compile(&parsed.defs, &exports).unwrap_or_else(|e| {
+          let code = match e {
+            CompileError::Check(..) => Exit::Type,
+            CompileError::UndefinedReference(..) => Exit::Usage,
+            _ => Exit::Failure,
+          };
+          fail(false, code, e)
+        });
+      let out = out.unwrap_or_else(|| input.with_extension("wasm"));
+      if let Err(e) = fs::write(&out, module) {
+        fail(false, Exit::Io, format!("{}: {}", out.display(), e));
+      }
+      println!("{}", out.display());
+    }
+    Command::Lean { input, out } => {
+      let parsed = parse_or_exit(&input, false);
+      for decl in &parsed.package.decls {
+        if let Declaration::Defn { name, defn, .. } = decl {
+          let def = &parsed.defs[defn];
+          if let Err(e) = hashspace::certificate::check_def(&parsed.defs, def) {
+            fail(false, Exit::Type, format!("{}: {}", name, e));
+          }
+        }
+      }
+      let source = lean::package(parsed.link).unwrap_or_else(|e| {
+        let e = format!("Cannot read package {}: {:?}", parsed.link, e);
+        fail(false, Exit::Io, e)
+      });
+      let out = out.unwrap_or_else(|| input.with_extension("lean"));
+      if let Err(e) = fs::write(&out, source) {
+        fail(false, Exit::Io, format!("{}: {}", out.display(), e));
+      }
+      println!("{}", out.display());
+    }
+    Command::Bench { input, defs, strategy, runs, warmup, fuel, json } => {
+      let parsed = parse_or_exit(&input, json);
+      let strategies = if strategy.is_empty() {
+        vec![Strategy::Lazy, Strategy::Step]
+      }
+      else {
+        strategy
+      };
+      let mut benches = Vec::new();
+      for def in &defs {
+        let (def_link, ast_link) = match parsed.refs.get(def.as_str()) {
+          Some(links) => *links,
+          None => fail(
+            json,
+            Exit::Usage,
+            format!(
+              "No `{}` definition in package {} from file {:?}",
+              def, parsed.package.name, input
+            ),
+          ),
+        };
+        let nam = Name::from(def.as_str());
+        let term = Term::Ref(None, nam, def_link, ast_link);
+        for &strategy in &strategies {
+          let bench = core::bench::bench(
+            &parsed.defs,
+            def,
+            &term,
+            strategy,
+            warmup,
+            runs,
+            fuel,
+          );
+          if !json {
+            println!("{}", bench);
+          }
+          benches.push(bench);
+        }
+      }
+      if json {
+        let benches = benches
+          .iter()
+          .map(|bench| {
+            let nanos = |time: Duration| Json::Num(time.as_nanos() as i64);
+            Json::obj(vec![
+              ("def", bench.name.as_str().into()),
+              ("strategy", Json::str(bench.strategy)),
+              ("runs", bench.samples.len().into()),
+              ("mean_ns", nanos(bench.mean())),
+              ("min_ns", nanos(bench.min())),
+              ("max_ns", nanos(bench.max())),
+              ("reductions", bench.per_run(|s| s.reductions).into()),
+              ("allocations", bench.per_run(|s| s.allocations).into()),
+              ("bytes", bench.per_run(|s| s.bytes).into()),
+              ("error", bench.error.clone().into()),
+            ])
+          })
+          .collect();
+        println!("{}", Json::Arr(benches));
+      }
+      if benches.iter().any(|bench| bench.error.is_some()) {
+        Exit::Eval.exit();
+      }
+    }
+    Command::Eval { expr, open, strategy, fuel, json } => {
+      let (mut defs, mut refs) = prelude();
+      for open in &open {
+        let (open_defs, open_refs) = if Path::new(open).is_file() {
+          let parsed = parse_or_exit(Path::new(open), json);
+          (parsed.defs, parsed.refs)
+        }
+        else {
+          let link = match Link::parse(open) {
+            Ok((_, link)) => link,
+            Err(_) => fail(
+              json,
+              Exit::Usage,
+              format!("{} is neither a file nor a link", open),
+            ),
+          };
+          match Package::get_link(link).and_then(Package::refs_defs) {
+            Ok((refs, defs)) => (defs, refs),
+            Err(e) => fail(
+              json,
+              Exit::Io,
+              format!("Cannot read package {}: {:?}", link, e),
+            ),
+          }
+        };
+        defs = open_defs.union(defs);
+        refs = open_refs.union(refs);
+      }
+      let term = parse::package::parse_input(&defs, &refs, &expr)
+        .unwrap_or_else(|e| fail(json, Exit::Parse, e));
+      let mut counts = Stats::new(fuel);
+      let res = core::run::run(&defs, &term, strategy, &mut counts);
+      if json {
+        println!(
+          "{}",
+          Json::obj(vec![
+            ("expr", expr.as_str().into()),
+            ("result", res.as_ref().ok().map(Json::str).into()),
+            ("error", res.as_ref().err().map(Json::str).into()),
+            ("reductions", counts.total().into()),
+          ])
+        );
+      }
+      else {
+        match &res {
+          Ok(term) => {
+            println!("{}", print::pretty(term, &Options::terminal()))
+          }
+          Err(e) => eprintln!("{}", e),
+        }
+      }
+      if res.is_err() {
+        Exit::Eval.exit();
+      }
+    }
+    Command::Check { input, watch: true, .. } => {
+      rerun_on_change(vec![input], |db, parsed| {
+        parsed
+          .refs
+          .iter()
+          .map(|(name, (link, _))| {
+            let res = db.check(&parsed.defs, *link);
+            let res = res.map(|()| String::new()).map_err(|e| e.to_string());
+            (name.to_string(), res)
+          })
+          .collect()
+      })
+    }
+    Command::Check { input, recheck, jobs, derivations, json, .. } => {
+      let parsed = parse_or_exit(&input, json);
+      let (p, defs, refs) = (parsed.package, parsed.defs, parsed.refs);
+      let source = fs::read_to_string(&input).unwrap_or_default();
+      let mut lints = Diagnostics::configured();
+      lints.lint(&source, &p, &defs, &refs);
+      if !json {
+        let style = Style::of(Stream::Stderr);
+        for w in &lints.warnings {
+          eprintln!("{}", render::warning(style, &input, &source, w, false));
+        }
+        for w in &lints.errors {
+          eprintln!("{}", render::warning(style, &input, &source, w, true));
+        }
+      }
+      let mut files = HashMap::new();
+      def_files(&input, &p, &mut files);
+      let mut names: Vec<&Name> = refs.keys().collect();
+      names.sort();
+      let links: Vec<_> = names.iter().map(|name| refs[*name].0).collect();
+      let jobs = jobs.unwrap_or_else(num_cpus::get);
+      let check: Checker = if recheck {
+        |defs, def| core::check::check_def(defs, def).map(|()| true)
+      }
+      else {
+        hashspace::certificate::check_def
+      };
+      let outcomes = core::parallel::check_defs(&defs, &links, jobs, check);
+      let mut failed = false;
+      let mut reports = Vec::new();
+      for (name, link) in names.iter().zip(links.iter()) {
+        let deriv = match outcomes[link] {
+          Outcome::Failed(_) => None,
+          _ if derivations => {
+            core::derivation::derive_def(&defs, &defs[link])
+              .ok()
+              .map(|deriv| deriv.store())
+          }
+          _ => None,
+        };
+        failed |= matches!(outcomes[link], Outcome::Failed(_));
+        if json {
+          let (status, error) = match &outcomes[link] {
+            Outcome::Checked => ("checked", Json::Null),
+            Outcome::Certified => ("certified", Json::Null),
+            Outcome::Failed(e) => {
+              let file = files.get(link).map(|file| file.as_path());
+              ("failed", diagnostic(e, file))
+            }
+          };
+          reports.push(Json::obj(vec![
+            ("name", name.as_str().into()),
+            ("link", Json::str(link)),
+            ("status", status.into()),
+            ("error", error),
+            ("derivation", deriv.map(Json::str).into()),
+          ]));
+          continue;
+        }
+        match &outcomes[link] {
+          Outcome::Checked => println!("✓ {}", name),
+          Outcome::Certified => println!("✓ {} (certified)", name),
+          Outcome::Failed(e) => report(name, files.get(link), e),
+        }
+        if let Some(deriv) = deriv {
+          println!("  derivation: {}", deriv);
+        }
+      }
+      if json {
+        let warnings = lints.warnings.iter().map(|w| (Level::Warn, w));
+        let errors = lints.errors.iter().map(|w| (Level::Deny, w));
+        let uri = lsp::path_uri(&input);
+        let warnings = warnings
+          .chain(errors)
+          .map(|(level, w)| Diagnostic::of_warning(w, level))
+          .map(|diagnostic| diagnostic.to_json(Some(&uri)))
+          .collect();
+        println!(
+          "{}",
+          Json::obj(vec![
+            ("package", p.name.as_str().into()),
+            ("link", Json::str(parsed.link)),
+            ("definitions", Json::Arr(reports)),
+            ("warnings", Json::Arr(warnings)),
+            ("ok", (!failed && !lints.failed()).into()),
+          ])
+        );
+        if failed || lints.failed() {
+          Exit::Type.exit();
+        }
+      }
+      if failed {
+        let e = format!("Package {} failed to typecheck", p.name);
+        fail(false, Exit::Type, e);
+      }
+      if lints.failed() {
+        let e = format!("Package {} has denied warnings", p.name);
+        fail(false, Exit::Type, e);
+      }
+    }
+    Command::Save { input } => {
+      let string = fs::read_to_string(&input).unwrap_or_else(|e| {
+        fail(false, Exit::Io, format!("{}: {}", input.display(), e))
+      });
+      let expr = match hashexpr::parse(&string) {
+        Ok((_, expr)) => expr,
+        Err(e) => fail(false, Exit::Parse, format!("{}", e)),
+      };
       let link = hashspace::put(expr);
       println!("Saved as {}", link)
     }
-    Cli::Show { input } => {
-      let link = hashexpr::link::Link::parse(&input).expect("valid link").1;
-      println!("link {:?} {}", link, link);
-      let expr = hashspace::get(link).expect("unknown link");
-      println!("{}", expr)
+    Command::Show { input, raw, json } => {
+      let link = match Link::parse(&input) {
+        Ok((_, link)) => link,
+        Err(_) => fail(json, Exit::Usage, format!("Invalid link {}", input)),
+      };
+      let (kind, content) = if raw {
+        match hashspace::get(link) {
+          Some(expr) => ("expression", format!("{}", expr)),
+          None => {
+            fail(json, Exit::Usage, format!("Nothing is stored at {}", link))
+          }
+        }
+      }
+      else {
+        match hashspace::inspect::inspect(link) {
+          Ok(content) => (content.kind(), format!("{}", content)),
+          Err(e) => {
+            let e = format!("Cannot read {}: {:?}", link, e);
+            fail(json, Exit::Failure, e)
+          }
+        }
+      };
+      if json {
+        println!(
+          "{}",
+          Json::obj(vec![
+            ("link", Json::str(link)),
+            ("kind", kind.into()),
+            ("content", content.into()),
+          ])
+        );
+      }
+      else if raw {
+        println!("{}", content);
+      }
+      else {
+        println!("{} {}", kind, link);
+        println!("{}", content);
+      }
     }
   }
 }
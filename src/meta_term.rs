@@ -128,8 +128,8 @@ pub mod tests {
         vec![
           // arbitrary_meta_ctor() causes stack overflow unless Leaf is set to at least 3
           (1, arbitrary_meta_ctor()),
-          (1, Box::new(|g| Bind(arbitrary_name(g), Arbitrary::arbitrary(g)))),
-          (1, Box::new(|g| Link(arbitrary_name(g), arbitrary_link(g)))),
+          (1, Box::new(|g| Bind(arbitrary_name(g).to_string(), Arbitrary::arbitrary(g)))),
+          (1, Box::new(|g| Link(arbitrary_name(g).to_string(), arbitrary_link(g)))),
           (3, Box::new(|_| Leaf))
         ];
       frequency(g, input)
@@ -0,0 +1,179 @@
+use crate::global::Global;
+
+use std::{
+  borrow::Borrow,
+  cmp::Ordering,
+  collections::HashSet,
+  fmt,
+  hash::{
+    Hash,
+    Hasher,
+  },
+  ops::Deref,
+  sync::{
+    Arc,
+    Mutex,
+  },
+};
+
+// Interned names, equal exactly when they share their string, and hashed and
+// ordered as it is, so that maps keyed by names are looked up with a `&str`.
+#[derive(Clone)]
+pub struct Name(Arc<str>);
+
+// The names in use, with the size past which those no longer in use are
+// pruned from them
+#[derive(Default)]
+struct Names {
+  table: HashSet<Arc<str>>,
+  limit: usize,
+}
+
+static NAMES: Global<Mutex<Names>> = Global::new();
+
+impl Name {
+  pub fn new(name: &str) -> Self {
+    let mut names = NAMES.get_or_init(Default::default).lock().unwrap();
+    if let Some(name) = names.table.get(name) {
+      return Name(name.clone());
+    }
+    if names.table.len() >= names.limit {
+      names.table.retain(|name| Arc::strong_count(name) > 1);
+      names.limit = 2 * names.table.len().max(512);
+    }
+    let name: Arc<str> = Arc::from(name);
+    names.table.insert(name.clone());
+    Name(name)
+  }
+
+  pub fn as_str(&self) -> &str { &self.0 }
+}
+
+impl Default for Name {
+  fn default() -> Self { Name::new("") }
+}
+
+impl Deref for Name {
+  type Target = str;
+
+  fn deref(&self) -> &str { &self.0 }
+}
+
+impl AsRef<str> for Name {
+  fn as_ref(&self) -> &str { &self.0 }
+}
+
+impl Borrow<str> for Name {
+  fn borrow(&self) -> &str { &self.0 }
+}
+
+impl PartialEq for Name {
+  fn eq(&self, other: &Name) -> bool { Arc::ptr_eq(&self.0, &other.0) }
+}
+
+impl Eq for Name {}
+
+impl PartialEq<str> for Name {
+  fn eq(&self, other: &str) -> bool { &*self.0 == other }
+}
+
+impl PartialEq<&str> for Name {
+  fn eq(&self, other: &&str) -> bool { &*self.0 == *other }
+}
+
+impl PartialEq<String> for Name {
+  fn eq(&self, other: &String) -> bool { *self.0 == **other }
+}
+
+impl PartialEq<Name> for str {
+  fn eq(&self, other: &Name) -> bool { self == &*other.0 }
+}
+
+impl PartialEq<Name> for &str {
+  fn eq(&self, other: &Name) -> bool { *self == &*other.0 }
+}
+
+impl PartialEq<Name> for String {
+  fn eq(&self, other: &Name) -> bool { **self == *other.0 }
+}
+
+impl Hash for Name {
+  fn hash<H: Hasher>(&self, state: &mut H) { self.0.hash(state) }
+}
+
+impl PartialOrd for Name {
+  fn partial_cmp(&self, other: &Name) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Name {
+  fn cmp(&self, other: &Name) -> Ordering { self.0.cmp(&other.0) }
+}
+
+impl From<&str> for Name {
+  fn from(name: &str) -> Self { Name::new(name) }
+}
+
+impl From<&String> for Name {
+  fn from(name: &String) -> Self { Name::new(name) }
+}
+
+impl From<String> for Name {
+  fn from(name: String) -> Self { Name::new(&name) }
+}
+
+impl From<Name> for String {
+  fn from(name: Name) -> Self { String::from(&*name.0) }
+}
+
+impl fmt::Display for Name {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl fmt::Debug for Name {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self.0)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Name {
+  fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&self.0)
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Name {
+  fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    String::deserialize(d).map(Name::from)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn interns() {
+    let x = Name::from("x");
+    let y = Name::from(String::from("x"));
+    assert_eq!(x, y);
+    assert!(std::ptr::eq(x.as_str(), y.as_str()));
+    assert_ne!(x, Name::from("y"));
+    assert_eq!(x, "x");
+    let (a, b) = (Name::from("a"), Name::from("b"));
+    assert!(a < b);
+    let mut names = im::HashMap::new();
+    names.insert(x.clone(), 1);
+    assert_eq!(names.get("x"), Some(&1));
+    // Names no longer in use are pruned, and those in use kept
+    for k in 0..4096 {
+      drop(Name::from(format!("unused{}", k)));
+    }
+    assert!(std::ptr::eq(x.as_str(), Name::from("x").as_str()));
+  }
+}
@@ -12,10 +12,12 @@ use crate::{
     Expected,
   },
   hashspace,
+  name::Name,
   term::{
     Def,
     Defs,
     Refs,
+    Term,
   },
   unembed_error::UnembedError,
 };
@@ -26,6 +28,7 @@ use im::{
 use std::fmt;
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Package {
   pub name: String,
   pub docs: String,
@@ -34,37 +37,66 @@ pub struct Package {
 }
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Declaration {
-  Defn { name: String, defn: Link, term: Link },
-  Open { name: String, alias: String, with: Option<Vec<String>>, from: Link },
+  // A definition, with the link to its source positions in the package, if
+  // they are stored, which the definition's own link leaves out. A private
+  // definition is in scope in its package, but not in those which open it.
+  Defn {
+    name: Name,
+    defn: Link,
+    term: Link,
+    positions: Option<Link>,
+    private: bool,
+  },
+  Open { name: Name, alias: Name, with: Option<Vec<Name>>, from: Link },
+  // A test, which passes when the definition at `test` checks, or, when its
+  // type is a hole, when its term synthesizes a type
+  Test { name: Name, test: Link, positions: Option<Link> },
   // Data { name: String, typ_: Term, ctors: HashMap<String, Term> },
 }
 
 impl Declaration {
   pub fn encode(self) -> Expr {
     match self {
-      Self::Defn { name, defn, term } => {
-        cons!(None, text!("defn"), text!(name), link!(defn), link!(term))
+      Self::Defn { name, defn, term, positions, private } => {
+        let head = String::from(if private { "private" } else { "defn" });
+        let name = String::from(name);
+        let mut xs = vec![text!(head), text!(name), link!(defn), link!(term)];
+        xs.extend(positions.map(|positions| link!(positions)));
+        Expr::Cons(None, xs)
       }
       Self::Open { name, alias, with, from } => match with {
         Some(ns) => {
           let mut xs = Vec::new();
           for n in ns {
-            xs.push(text!(n))
+            xs.push(text!(String::from(n)))
           }
           cons!(
             None,
             text!("open"),
-            text!(name),
-            text!(alias),
+            text!(String::from(name)),
+            text!(String::from(alias)),
             Expr::Cons(None, xs),
             link!(from)
           )
         }
         None => {
-          cons!(None, text!("open"), text!(name), text!(alias), link!(from))
+          cons!(
+            None,
+            text!("open"),
+            text!(String::from(name)),
+            text!(String::from(alias)),
+            link!(from)
+          )
         }
       },
+      Self::Test { name, test, positions } => {
+        let name = String::from(name);
+        let mut xs = vec![text!("test"), text!(name), link!(test)];
+        xs.extend(positions.map(|positions| link!(positions)));
+        Expr::Cons(None, xs)
+      }
     }
   }
 
@@ -72,9 +104,26 @@ impl Declaration {
     match expr {
       Cons(pos, xs) => match xs.as_slice() {
         [Atom(_, Text(c)), Atom(_, Text(n)), Atom(_, Link(d)), Atom(_, Link(a))]
-          if *c == String::from("defn") =>
+          if *c == String::from("defn") || *c == String::from("private") =>
         {
-          Ok(Self::Defn { name: n.to_owned(), defn: *d, term: *a })
+          Ok(Self::Defn {
+            name: Name::from(n),
+            defn: *d,
+            term: *a,
+            positions: None,
+            private: *c == String::from("private"),
+          })
+        }
+        [Atom(_, Text(c)), Atom(_, Text(n)), Atom(_, Link(d)), Atom(_, Link(a)), Atom(_, Link(p))]
+          if *c == String::from("defn") || *c == String::from("private") =>
+        {
+          Ok(Self::Defn {
+            name: Name::from(n),
+            defn: *d,
+            term: *a,
+            positions: Some(*p),
+            private: *c == String::from("private"),
+          })
         }
         [Atom(_, Text(c)), Atom(_, Text(n)), Atom(_, Text(a)), Cons(_, xs), Atom(_, Link(f))]
           if *c == String::from("open") =>
@@ -83,7 +132,7 @@ impl Declaration {
           for x in xs {
             match x {
               Atom(_, Text(n)) => {
-                ns.push(n.to_owned());
+                ns.push(Name::from(n));
               }
               _ => {
                 return Err(DecodeError::new(pos, vec![
@@ -94,8 +143,8 @@ impl Declaration {
           }
           let with = Some(ns.to_owned());
           Ok(Self::Open {
-            name: n.to_owned(),
-            alias: a.to_owned(),
+            name: Name::from(n),
+            alias: Name::from(a),
             with,
             from: *f,
           })
@@ -104,12 +153,23 @@ impl Declaration {
           if *c == String::from("open") =>
         {
           Ok(Self::Open {
-            name: n.to_owned(),
-            alias: a.to_owned(),
+            name: Name::from(n),
+            alias: Name::from(a),
             with: None,
             from: *f,
           })
         }
+        [Atom(_, Text(c)), Atom(_, Text(n)), Atom(_, Link(t))]
+          if *c == String::from("test") =>
+        {
+          Ok(Self::Test { name: Name::from(n), test: *t, positions: None })
+        }
+        [Atom(_, Text(c)), Atom(_, Text(n)), Atom(_, Link(t)), Atom(_, Link(p))]
+          if *c == String::from("test") =>
+        {
+          let positions = Some(*p);
+          Ok(Self::Test { name: Name::from(n), test: *t, positions })
+        }
         _ => Err(DecodeError::new(pos, vec![Expected::PackageDefinition])),
       },
       x => {
@@ -122,9 +182,12 @@ impl Declaration {
 impl fmt::Display for Declaration {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      Self::Defn { defn, .. } => {
-        let def = Def::get_link(*defn).expect("unembed error");
-        write!(f, "{}", def)
+      Self::Defn { name, defn, .. } => {
+        match Def::get_link(*defn) {
+          Ok(def) => write!(f, "{}", def),
+          // The definition cannot be shown without the hashspace entry
+          Err(_) => write!(f, "// {}: definition {} not found", name, defn),
+        }
       }
       Self::Open { name, alias, with, from } => {
         let with = match with {
@@ -145,13 +208,25 @@ impl fmt::Display for Declaration {
             s
           }
         };
-        if *alias == String::from("") {
+        if alias.is_empty() {
           write!(f, "open {} {}from {}", name, with, from)
         }
         else {
           write!(f, "open {} as {} {}from {}", name, alias, with, from)
         }
       }
+      Self::Test { name, test, .. } => {
+        let def = match Def::get_link(*test) {
+          Ok(def) => def,
+          Err(_) => {
+            return write!(f, "// {}: test {} not found", name, test);
+          }
+        };
+        match def.typ_ {
+          Term::Hol(_, None) => write!(f, "#check {}", def.term),
+          typ => write!(f, "test {} : {} = {}", def.name, typ, def.term),
+        }
+      }
     }
   }
 }
@@ -216,9 +291,12 @@ impl Package {
     let mut defs: Defs = HashMap::new();
     for d in self.decls {
       match d {
-        Declaration::Defn { name, defn, term } => {
-          refs.insert(name, (defn, term));
-          let def = Def::get_link(defn)?;
+        Declaration::Defn { name, defn, term, positions, private } => {
+          // Private definitions are only in scope of their own package
+          if !private {
+            refs.insert(name, (defn, term));
+          }
+          let def = Def::get_positioned(defn, positions)?;
           defs.insert(defn, def);
         }
         Declaration::Open { alias, from, with, .. } => {
@@ -230,6 +308,7 @@ impl Package {
           refs = merge_refs(refs, import_refs, alias, with);
           defs = merge_defs(defs, import_defs);
         }
+        Declaration::Test { .. } => (),
       }
     }
     Ok((refs, defs))
@@ -239,24 +318,28 @@ impl Package {
 pub fn merge_refs(
   left: Refs,
   right: Refs,
-  alias: String,
-  with: Option<Vec<String>>,
+  alias: Name,
+  with: Option<Vec<Name>>,
 ) -> Refs {
   let mut refs = right;
   match with {
     Some(ns) => {
-      let set: HashSet<String> = ns.iter().collect();
+      let set: HashSet<Name> = ns.into_iter().collect();
       refs.retain(|k, _| set.contains(k));
-      if alias != String::from("") {
-        refs =
-          refs.iter().map(|(k, v)| (format!("{}.{}", alias, k), *v)).collect();
+      if !alias.is_empty() {
+        refs = refs
+          .iter()
+          .map(|(k, v)| (Name::from(format!("{}.{}", alias, k)), *v))
+          .collect();
       }
       left.union_with(refs, |_, right| right)
     }
     None => {
-      if alias != String::from("") {
-        refs =
-          refs.iter().map(|(k, v)| (format!("{}.{}", alias, k), *v)).collect();
+      if !alias.is_empty() {
+        refs = refs
+          .iter()
+          .map(|(k, v)| (Name::from(format!("{}.{}", alias, k)), *v))
+          .collect();
       }
       left.union_with(refs, |_, right| right)
     }
@@ -302,7 +385,7 @@ pub mod tests {
   use std::path::PathBuf;
 
   pub fn test_package() -> Package {
-    let source = "package Test where\n def id (A: Type) (x: A): A := x";
+    let source = "package Test where\n def id (A: Type) (x: A): A = x";
     let source_link = text!(String::from(source)).link();
     let (_, (_, p, ..)) = parse_package(
       PackageEnv::new(PathBuf::from("Test.ya")),
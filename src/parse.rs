@@ -2,6 +2,7 @@ pub mod base;
 pub mod error;
 pub mod literal;
 pub mod package;
+pub mod prelude;
 pub mod span;
 pub mod string;
 pub mod term;
@@ -1,4 +1,12 @@
 use crate::{
+  core::{
+    check::CheckError,
+    datatype::DataError,
+    matching::MatchError,
+    termination::TerminationError,
+  },
+  explain::Code,
+  name::Name,
   parse::span::Span,
   term::{
     Link,
@@ -34,7 +42,7 @@ use std::{
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum ParseErrorKind {
-  UndefinedReference(String, Vector<String>),
+  UndefinedReference(String, Vector<Name>),
   TopLevelRedefinition(String),
   UnknownLiteralType(String),
   UnexpectedLiteral(hashexpr::Expr),
@@ -57,8 +65,77 @@ pub enum ParseErrorKind {
   MisnamedImport(String, Link, String),
   MalformedPath,
   ImportCycle(PathBuf),
+  // A package opened by its file which cannot be read or parsed
+  OpenError(PathBuf, String),
+  // An `open` of a package by its file, without a filesystem to read it from
+  UnlinkedImport(String),
   EmbeddingError(UnembedError),
+  MalformedData(DataError),
+  ElabError(CheckError),
+  NonTerminating(TerminationError),
   Nom(ErrorKind),
+  MalformedMatch(MatchError),
+  // A float literal too large to be represented, as it would be infinite
+  FloatOutOfRange(String),
+  // A non-associative operator applied to the application of another of the
+  // same precedence, as in `a == b == c`
+  NonAssociative(String),
+  // An implicit binder in a local definition, whose uses aren't elaborated
+  LocalImplicit(String),
+  // A definition without a type, and without a signature giving one
+  MissingSignature(String),
+  // A signature with no definition after it in its package
+  UnmatchedSignature(String),
+  // A definition whose type or parameters disagree with its signature
+  SignatureMismatch(String),
+  // A mutually recursive definition with an implicit binder
+  MutualImplicit(String),
+}
+
+impl ParseErrorKind {
+  // The code of the error, or, for an elaboration error, of the type error
+  pub fn code(&self) -> Code {
+    match self {
+      Self::UndefinedReference(..) => Code(1),
+      Self::TopLevelRedefinition(..) => Code(2),
+      Self::UnknownLiteralType(..) => Code(3),
+      Self::UnexpectedLiteral(..) => Code(4),
+      Self::InvalidBaseEncoding(..) => Code(5),
+      Self::UnknownBaseCode => Code(6),
+      Self::ExpectedSingleChar(..) => Code(7),
+      Self::InvalidBase16EscapeSequence(..) => Code(8),
+      Self::DeserialErr(..) => Code(9),
+      Self::ParseIntErr(..) => Code(10),
+      Self::ReservedKeyword(..) => Code(11),
+      Self::HashExprSyntax(..) => Code(12),
+      Self::NumericSyntax(..) => Code(13),
+      Self::LiteralLacksWhitespaceTermination(..) => Code(14),
+      Self::LitTypeLacksWhitespaceTermination(..) => Code(15),
+      Self::PrimOpLacksWhitespaceTermination(..) => Code(16),
+      Self::InvalidSymbol(..) => Code(17),
+      Self::ExpectedImportLink(..) => Code(18),
+      Self::UnknownImportLink(..) => Code(19),
+      Self::MisnamedPackage(..) => Code(20),
+      Self::MisnamedImport(..) => Code(21),
+      Self::MalformedPath => Code(22),
+      Self::ImportCycle(..) => Code(23),
+      Self::OpenError(..) => Code(24),
+      Self::UnlinkedImport(..) => Code(25),
+      Self::EmbeddingError(..) => Code(26),
+      Self::MalformedData(..) => Code(27),
+      Self::ElabError(e) => e.kind.code(),
+      Self::NonTerminating(..) => Code(28),
+      Self::Nom(..) => Code(29),
+      Self::MalformedMatch(..) => Code(30),
+      Self::FloatOutOfRange(..) => Code(31),
+      Self::NonAssociative(..) => Code(32),
+      Self::LocalImplicit(..) => Code(33),
+      Self::MissingSignature(..) => Code(34),
+      Self::UnmatchedSignature(..) => Code(35),
+      Self::SignatureMismatch(..) => Code(36),
+      Self::MutualImplicit(..) => Code(37),
+    }
+  }
 }
 
 impl<'a> fmt::Display for ParseErrorKind {
@@ -165,14 +242,83 @@ impl<'a> fmt::Display for ParseErrorKind {
           path
         )
       }
+      Self::OpenError(path, e) => {
+        write!(f, "Cannot open the package in {}: {}", path.display(), e)
+      }
+      Self::UnlinkedImport(name) => {
+        write!(
+          f,
+          "Cannot open the package {} by its file without a filesystem, open \
+           it by its link",
+          name
+        )
+      }
       Self::EmbeddingError(e) => {
-        write!(f, "Error reading package from hashspace: {:?}", e)
+        write!(f, "Error reading package from hashspace: {}", e)
+      }
+      Self::MalformedData(e) => write!(f, "{}", e),
+      Self::ElabError(e) => write!(f, "{}", e),
+      Self::NonTerminating(e) => write!(f, "{}", e),
+      Self::MalformedMatch(e) => write!(f, "{}", e),
+      Self::FloatOutOfRange(x) => {
+        write!(f, "The float {} is too large to be represented", x)
+      }
+      Self::NonAssociative(op) => {
+        write!(
+          f,
+          "The operator {} is not associative, so cannot follow one of the \
+           same precedence without parentheses",
+          op
+        )
+      }
+      Self::LocalImplicit(nam) => {
+        write!(
+          f,
+          "The local definition {} has an implicit binder, which only global \
+           definitions can have",
+          nam
+        )
+      }
+      Self::MissingSignature(nam) => {
+        write!(
+          f,
+          "The definition {} has no type, nor a signature giving one",
+          nam
+        )
+      }
+      Self::UnmatchedSignature(nam) => {
+        write!(f, "The signature {} is not followed by a definition", nam)
+      }
+      Self::SignatureMismatch(nam) => {
+        write!(f, "The definition {} does not match its signature", nam)
+      }
+      Self::MutualImplicit(nam) => {
+        write!(
+          f,
+          "The mutually recursive definition {} has an implicit binder, \
+           which only definitions on their own can have",
+          nam
+        )
       }
       _ => write!(f, "internal parser error"),
     }
   }
 }
 
+impl std::error::Error for ParseErrorKind {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::ParseIntErr(e) => Some(e),
+      Self::EmbeddingError(e) => Some(e),
+      Self::MalformedData(e) => Some(e),
+      Self::ElabError(e) => Some(e),
+      Self::NonTerminating(e) => Some(e),
+      Self::MalformedMatch(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
 impl ParseErrorKind {
   pub fn from_hashexpr_error(x: hashexpr::error::ParseErrorKind) -> Self {
     use hashexpr::error::ParseErrorKind::*;
@@ -196,16 +342,39 @@ impl ParseErrorKind {
   }
 }
 
+// An error at the furthest input any alternative parsed to, with what each
+// alternative which failed there expected, as the contexts they were parsed
+// in, and why they failed
 #[derive(PartialEq, Debug, Clone)]
 pub struct ParseError<I: AsBytes> {
   pub input: I,
-  pub expected: Option<&'static str>,
+  pub expected: Vec<&'static str>,
   pub errors: Vec<ParseErrorKind>,
 }
 
+// What was expected, as `Expected a` or `Expected one of: a, b, c`
+pub fn expected(expected: &[&str]) -> Option<String> {
+  match expected {
+    [] => None,
+    [exp] => Some(format!("Expected {}", exp)),
+    exps => Some(format!("Expected one of: {}", exps.join(", "))),
+  }
+}
+
 impl<I: AsBytes> ParseError<I> {
   pub fn new(input: I, error: ParseErrorKind) -> Self {
-    ParseError { input, expected: None, errors: vec![error] }
+    ParseError { input, expected: Vec::new(), errors: vec![error] }
+  }
+
+  // The error, with `exp` also expected if it is at `input`, for what can
+  // end the alternatives which failed, such as the end of the input
+  pub fn or_expected(mut self, input: &I, exp: &'static str) -> Self
+  where I: InputLength {
+    let here = self.input.input_len() == input.input_len();
+    if here && !self.expected.contains(&exp) {
+      self.expected.push(exp);
+    }
+    self
   }
 
   pub fn from_hashexpr_error(
@@ -214,12 +383,33 @@ impl<I: AsBytes> ParseError<I> {
   ) -> Self {
     ParseError {
       input: from,
-      expected: None,
+      expected: Vec::new(),
       errors: vec![ParseErrorKind::from_hashexpr_error(x.error)],
     }
   }
 }
 
+impl<I: AsBytes> ParseError<I> {
+  // Why the input does not parse, without where, for when that is shown
+  // otherwise
+  pub fn reasons(&self) -> String {
+    let mut reasons = Vec::new();
+    reasons.extend(expected(&self.expected));
+    reasons.extend(
+      self.errors.iter().filter(|x| !x.is_nom_err()).map(|x| x.to_string()),
+    );
+    if reasons.is_empty() {
+      reasons.push(String::from("Internal parser error"));
+    }
+    reasons.join("\n")
+  }
+
+  // The code of the first reason the input does not parse
+  pub fn code(&self) -> Option<Code> {
+    self.errors.iter().find(|x| !x.is_nom_err()).map(|x| x.code())
+  }
+}
+
 impl<'a> fmt::Display for ParseError<Span<'a>> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     let mut res = String::new();
@@ -241,8 +431,8 @@ impl<'a> fmt::Display for ParseError<Span<'a>> {
     }
     write!(&mut res, "^\n")?;
 
-    if let Some(exp) = self.expected {
-      write!(&mut res, "Expected {}\n", exp)?;
+    if let Some(exp) = expected(&self.expected) {
+      write!(&mut res, "{}\n", exp)?;
     }
 
     let mut errs = self.errors.iter().filter(|x| !x.is_nom_err()).peekable();
@@ -284,7 +474,16 @@ where
   fn or(self, mut other: Self) -> Self {
     match self.input.input_len().cmp(&other.input.input_len()) {
       Ordering::Less => self,
+      // Both failed as far, so either's expectations would have gone on,
+      // in the order the alternatives were tried in
       Ordering::Equal => {
+        let mut expected = self.expected;
+        for exp in other.expected {
+          if !expected.contains(&exp) {
+            expected.push(exp);
+          }
+        }
+        other.expected = expected;
         for x in self.errors {
           other.errors.push(x);
         }
@@ -303,13 +502,14 @@ where
   fn add_context(input: I, ctx: &'static str, other: Self) -> Self {
     match input.input_len().cmp(&other.input.input_len()) {
       Ordering::Less => {
-        ParseError { input, expected: Some(ctx), errors: vec![] }
+        ParseError { input, expected: vec![ctx], errors: vec![] }
       }
-      Ordering::Equal => match other.expected {
-        None => ParseError { input, expected: Some(ctx), errors: other.errors },
-        _ => other,
-      },
-      Ordering::Greater => other,
+      // What failed where the context starts is labeled by it, unless it
+      // already says what it expected
+      Ordering::Equal if other.expected.is_empty() => {
+        ParseError { input, expected: vec![ctx], errors: other.errors }
+      }
+      _ => other,
     }
   }
 }
@@ -24,28 +24,72 @@ use hashexpr::base::Base;
 
 use nom::{
   branch::alt,
-  bytes::complete::tag,
-  character::complete::digit1,
+  bytes::complete::{
+    tag,
+    take_until,
+    take_while,
+  },
+  character::complete::{
+    digit1,
+    one_of,
+    satisfy,
+  },
   combinator::{
     opt,
     peek,
+    recognize,
     value,
   },
   error::context,
   sequence::{
     delimited,
+    pair,
     preceded,
     terminated,
+    tuple,
   },
   Err,
   IResult,
 };
 
-pub fn parse_nat(from: Span) -> IResult<Span, Literal, ParseError<Span>> {
+// The digits of a numeral in a radix up to 16, of either case, which may be
+// separated by underscores, as in `1_000_000`
+pub fn parse_radix_digits(
+  radix: u32,
+) -> impl Fn(Span) -> IResult<Span, BigUint, ParseError<Span>> {
+  move |from: Span| {
+    let (upto, digits) = recognize(pair(
+      satisfy(|c| c.is_digit(radix)),
+      take_while(|c: char| c.is_digit(radix) || c == '_'),
+    ))(from)?;
+    let digits: String =
+      digits.fragment().chars().filter(|c| *c != '_').collect();
+    // The digits are all valid in the radix
+    let x = BigUint::parse_bytes(digits.as_bytes(), radix).unwrap_or_default();
+    Ok((upto, x))
+  }
+}
+
+// A numeral, in decimal or in the base of its prefix, such as `0xff`, `0o755`
+// or `0b1010`. Numerals in the bases of hashexpr's byte encodings, `0v`, `0I`
+// and `0~`, are read as big-endian bytes.
+pub fn parse_numeral(from: Span) -> IResult<Span, BigUint, ParseError<Span>> {
   let (i, base) = opt(preceded(tag("0"), parse_base_code()))(from)?;
-  let base = base.unwrap_or(Base::_10);
-  let (upto, bytes) = parse_base_bytes(base)(i)?;
-  Ok((upto, Literal::Natural(BigUint::from_bytes_be(&bytes))))
+  match base.unwrap_or(Base::_10) {
+    Base::_2 => parse_radix_digits(2)(i),
+    Base::_8 => parse_radix_digits(8)(i),
+    Base::_10 => parse_radix_digits(10)(i),
+    Base::_16 => parse_radix_digits(16)(i),
+    base => {
+      let (upto, bytes) = parse_base_bytes(base)(i)?;
+      Ok((upto, BigUint::from_bytes_be(&bytes)))
+    }
+  }
+}
+
+pub fn parse_nat(from: Span) -> IResult<Span, Literal, ParseError<Span>> {
+  let (upto, x) = parse_numeral(from)?;
+  Ok((upto, Literal::Natural(x)))
 }
 
 pub fn parse_int_sign(from: Span) -> IResult<Span, Sign, ParseError<Span>> {
@@ -58,10 +102,42 @@ pub fn parse_int_sign(from: Span) -> IResult<Span, Sign, ParseError<Span>> {
 
 pub fn parse_int(from: Span) -> IResult<Span, Literal, ParseError<Span>> {
   let (i, s) = parse_int_sign(from)?;
-  let (i, base) = opt(preceded(tag("0"), parse_base_code()))(i)?;
-  let base = base.unwrap_or(Base::_10);
-  let (upto, bytes) = parse_base_bytes(base)(i)?;
-  Ok((upto, Literal::Integer(BigInt::from_bytes_be(s, &bytes))))
+  let (upto, x) = parse_numeral(i)?;
+  Ok((upto, Literal::Integer(BigInt::from_biguint(s, x))))
+}
+
+// A decimal float, such as `3.14`, `-0.5` or `6.02e23`, which has a point or
+// an exponent
+pub fn parse_float(from: Span) -> IResult<Span, Literal, ParseError<Span>> {
+  let exponent = || tuple((one_of("eE"), opt(one_of("+-")), digit1));
+  let (upto, x) = recognize(tuple((
+    opt(one_of("+-")),
+    digit1,
+    alt((
+      recognize(pair(preceded(tag("."), digit1), opt(exponent()))),
+      recognize(exponent()),
+    )),
+  )))(from)?;
+  match x.fragment().parse::<f64>() {
+    Ok(x) if x.is_finite() => Ok((upto, Literal::Float(x.to_bits()))),
+    _ => Err(Err::Failure(ParseError::new(
+      from,
+      ParseErrorKind::FloatOutOfRange(x.fragment().to_string()),
+    ))),
+  }
+}
+
+// A raw text, between triple quotes, whose contents are read as they are,
+// without escapes. A newline just after the opening quotes is not part of it,
+// so that a block of text may start on its own line.
+pub fn parse_raw_text(
+  from: Span,
+) -> IResult<Span, Literal, ParseError<Span>> {
+  let (i, _) = tag("\"\"\"")(from)?;
+  let (i, _) = opt(alt((tag("\r\n"), tag("\n"))))(i)?;
+  let (i, s) = context("close triple quotes", take_until("\"\"\""))(i)?;
+  let (upto, _) = tag("\"\"\"")(i)?;
+  Ok((upto, Literal::Text(s.fragment().to_string())))
 }
 
 pub fn parse_text(from: Span) -> IResult<Span, Literal, ParseError<Span>> {
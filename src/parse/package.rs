@@ -1,5 +1,27 @@
+#[cfg(feature = "native")]
+use crate::config;
 use crate::{
+  core::{
+    codatatype::Codatatype,
+    datatype::{
+      Datatype,
+      Telescope,
+    },
+    elab::{
+      elab_def,
+      elab_term,
+      insert_implicits,
+    },
+    record::Record,
+    termination::{
+      check_mutual_termination,
+      check_termination,
+    },
+  },
+  definition::Transparency,
+  formats::occurs,
   hashspace,
+  name::Name,
   package::{
     merge_defs,
     merge_refs,
@@ -12,24 +34,38 @@ use crate::{
       ParseError,
       ParseErrorKind,
     },
+    prelude::prelude,
     term::*,
   },
+  positions::Positions,
   term::{
     Def,
     Defs,
     Link,
     Refs,
+    Term,
   },
 };
 
 use std::{
+  collections::HashMap,
   ffi::OsString,
-  fs,
   path::PathBuf,
+  sync::{
+    Arc,
+    Mutex,
+  },
+};
+
+#[cfg(feature = "native")]
+use std::{
+  fs,
+  path::Path,
+  thread,
+  time::Instant,
 };
 
 use hashexpr::{
-  atom,
   atom::Atom::*,
   position::Pos,
   span::Span,
@@ -37,18 +73,24 @@ use hashexpr::{
 };
 
 use im::{
-  HashMap,
   HashSet,
   Vector,
 };
 use nom::{
   branch::alt,
   bytes::complete::tag,
-  character::complete::multispace1,
+  character::complete::{
+    multispace1,
+    one_of,
+  },
   combinator::{
     eof,
+    map,
     opt,
+    peek,
+    value,
   },
+  error::context,
   multi::separated_list0,
   sequence::{
     preceded,
@@ -56,27 +98,42 @@ use nom::{
   },
   Err,
   IResult,
+  Slice,
 };
 
 #[derive(Debug, Clone)]
 pub struct PackageEnv {
   path: PathBuf,
   open: HashSet<PathBuf>,
-  /* TODO: Cache of completed files so we don't reparse packages we've
-   * already parsed
-   * done: Rc<HashMap<PathBuf, Link>>, */
+  // The links of the packages in the files parsed so far, shared by every
+  // package opened from the same one, so that no file is parsed twice
+  done: Arc<Mutex<HashMap<PathBuf, Link>>>,
 }
 
 impl PackageEnv {
   pub fn new(path: PathBuf) -> Self {
-    PackageEnv { path, open: HashSet::new() }
+    PackageEnv { path, open: HashSet::new(), done: Arc::default() }
   }
 
   pub fn set_path(self, path: PathBuf) -> Self {
-    PackageEnv { path, open: self.open }
+    PackageEnv { path, ..self }
+  }
+
+  // The environment of the package in the file at `path`, opened from this
+  // one
+  #[cfg(feature = "native")]
+  fn opening(&self, path: PathBuf) -> Self {
+    let mut open = self.open.clone();
+    open.insert(path.clone());
+    PackageEnv { path, open, done: self.done.clone() }
   }
 }
 
+// The size of the stacks of the threads which parse opened packages, as
+// parsing nested terms recurses deeply
+#[cfg(feature = "native")]
+const STACK: usize = 8 * 1024 * 1024;
+
 pub fn parse_link(from: Span) -> IResult<Span, Link, ParseError<Span>> {
   let (upto, link) =
     hashexpr::parse_raw(from).map_err(|e| error::convert(from, e))?;
@@ -89,14 +146,13 @@ pub fn parse_link(from: Span) -> IResult<Span, Link, ParseError<Span>> {
   }
 }
 
-fn parse_alias(i: Span) -> IResult<Span, String, ParseError<Span>> {
+pub fn parse_alias(i: Span) -> IResult<Span, Name, ParseError<Span>> {
   let (i, _) = tag("as")(i)?;
   let (i, _) = parse_space(i)?;
-  let (i, a) = parse_name(i)?;
-  Ok((i, a))
+  parse_name(i)
 }
 
-fn parse_with(i: Span) -> IResult<Span, Vec<String>, ParseError<Span>> {
+pub fn parse_with(i: Span) -> IResult<Span, Vec<Name>, ParseError<Span>> {
   let (i, _) = tag("(")(i)?;
   let (i, ns) = separated_list0(
     terminated(tag(","), parse_space),
@@ -106,71 +162,1061 @@ fn parse_with(i: Span) -> IResult<Span, Vec<String>, ParseError<Span>> {
   Ok((i, ns))
 }
 
+// The path of the file of the package `name`, opened from the file `from`:
+// the one beside it, or else the first in the configured search paths
+#[cfg(feature = "native")]
+pub fn import_path(from: &Path, name: &str) -> PathBuf {
+  let relative = |dir: &Path| {
+    let mut path = dir.to_path_buf();
+    for n in name.split(".") {
+      path.push(n);
+    }
+    path.set_extension("ya");
+    path
+  };
+  let path = relative(from.parent().unwrap_or_else(|| Path::new("")));
+  if path.exists() {
+    return path;
+  }
+  config::get()
+    .search
+    .iter()
+    .map(|dir| relative(dir))
+    .find(|path| path.exists())
+    .unwrap_or(path)
+}
+
+// An `open` declaration, as the name of the package opened, its alias, the
+// names imported from it and its link, if it is given
+fn parse_open_head(
+  i: Span,
+) -> IResult<
+  Span,
+  (Name, Name, Option<Vec<Name>>, Option<Link>),
+  ParseError<Span>,
+> {
+  let (i, _) = tag("open")(i)?;
+  let (i, _) = parse_space(i)?;
+  let (i, name) = parse_name(i)?;
+  let (i, _) = parse_space(i)?;
+  let (i, alias) = opt(terminated(parse_alias, parse_space))(i)?;
+  let alias = alias.unwrap_or_default();
+  let (i, with) = opt(terminated(parse_with, parse_space))(i)?;
+  let (i, from) = opt(terminated(parse_link, parse_space))(i)?;
+  Ok((i, (name, alias, with, from)))
+}
+
+// The link of the package in the file of `env`, parsing it unless it already
+// has been
+#[cfg(feature = "native")]
+fn open_file(env: PackageEnv) -> Result<Link, ParseErrorKind> {
+  if let Some(link) = env.done.lock().unwrap().get(&env.path) {
+    return Ok(*link);
+  }
+  let (path, done) = (env.path.clone(), env.done.clone());
+  let (link, ..) = parse_file(env)?;
+  done.lock().unwrap().insert(path, link);
+  Ok(link)
+}
+
+// The files of the packages opened without links by the declarations in
+// `source`, other than those already open, in the order they are opened.
+// Declarations start at the beginning of a line.
+#[cfg(feature = "native")]
+fn opened_files(env: &PackageEnv, source: &str) -> Vec<PathBuf> {
+  let mut paths = Vec::new();
+  for line in source.lines().filter(|line| line.starts_with("open")) {
+    if let Ok((_, (name, _, _, None))) = parse_open_head(Span::new(line)) {
+      let path = import_path(&env.path, &name);
+      if !env.open.contains(&path) && !paths.contains(&path) {
+        paths.push(path);
+      }
+    }
+  }
+  paths
+}
+
+// Parse the files of the packages opened in `source` on a pool of threads,
+// so that each is already parsed when its `open` is. The packages are still
+// opened one after the other, in the order of their declarations, and so
+// are merged in the same order as if they were parsed sequentially. A
+// package which fails to parse is not cached, and its error is reported
+// when it is opened.
+#[cfg(feature = "native")]
+fn parse_opened(env: &PackageEnv, source: &str) {
+  let paths: Vec<PathBuf> = {
+    let done = env.done.lock().unwrap();
+    let paths = opened_files(env, source).into_iter();
+    paths.filter(|path| !done.contains_key(path)).collect()
+  };
+  if paths.len() < 2 {
+    return;
+  }
+  let jobs = num_cpus::get().min(paths.len());
+  let queue = Arc::new(Mutex::new(paths.into_iter()));
+  let workers: Vec<thread::JoinHandle<()>> = (0..jobs)
+    .map(|_| {
+      let (env, queue) = (env.clone(), queue.clone());
+      let work = move || loop {
+        let path = match queue.lock().unwrap().next() {
+          Some(path) => path,
+          None => return,
+        };
+        let _ = open_file(env.opening(path));
+      };
+      thread::Builder::new()
+        .stack_size(STACK)
+        .spawn(work)
+        .expect("cannot spawn a thread to parse an opened package")
+    })
+    .collect();
+  for worker in workers {
+    worker.join().unwrap();
+  }
+}
+
 pub fn parse_open(
   env: PackageEnv,
 ) -> impl Fn(Span) -> IResult<Span, Declaration, ParseError<Span>> {
   move |i: Span| {
-    let (i, _) = tag("open")(i)?;
-    let (i, _) = parse_space(i)?;
-    let (i, name) = parse_name(i)?;
-    let (i, _) = parse_space(i)?;
-    let (i, alias) = opt(terminated(parse_alias, parse_space))(i)?;
-    let alias = alias.unwrap_or(String::from(""));
-    let (i, with) = opt(terminated(parse_with, parse_space))(i)?;
-    let (i, from) = opt(terminated(parse_link, parse_space))(i)?;
+    let (i, (name, alias, with, from)) = parse_open_head(i)?;
     match from {
       Some(from) => Ok((i, Declaration::Open { name, alias, with, from })),
+      #[cfg(not(feature = "native"))]
       None => {
-        let mut path = env.path.parent().unwrap().to_path_buf();
-        for n in name.split(".") {
-          path.push(n);
-        }
-        path.set_extension("ya");
-        let mut open = env.open.clone();
-        let has_path = open.insert(path.clone());
-        if has_path.is_some() {
+        let kind = ParseErrorKind::UnlinkedImport(String::from(name));
+        Err(Err::Error(ParseError::new(i, kind)))
+      }
+      #[cfg(feature = "native")]
+      None => {
+        let path = import_path(&env.path, &name);
+        if env.open.contains(&path) {
           Err(Err::Error(ParseError::new(i, ParseErrorKind::ImportCycle(path))))
         }
         else {
-          let env = PackageEnv { path, open };
-          let (link, ..) = parse_file(env);
-          Ok((i, Declaration::Open { name, alias, with, from: link }))
+          match open_file(env.opening(path)) {
+            Ok(link) => {
+              Ok((i, Declaration::Open { name, alias, with, from: link }))
+            }
+            Err(kind) => Err(Err::Failure(ParseError::new(i, kind))),
+          }
         }
       }
     }
   }
 }
 
-pub fn parse_defn(
-  refs: Refs,
-) -> impl Fn(Span) -> IResult<Span, Declaration, ParseError<Span>> {
+// A definition's transparency, when it is declared otherwise than
+// transparent
+pub fn parse_transparency(
+  i: Span,
+) -> IResult<Span, Transparency, ParseError<Span>> {
+  alt((
+    value(Transparency::Opaque, tag("opaque")),
+    value(Transparency::Reducible, tag("reducible")),
+  ))(i)
+}
+
+// The documentation of a declaration, from the line comments just before it.
+// Where some of them are doc comments, starting with `///`, only those are
+// read, so that other comments may be left before a declaration without
+// documenting it.
+pub fn docs(comments: &[&str]) -> String {
+  let marked = comments.iter().any(|com| com.starts_with('/'));
+  let lines: Vec<&str> = comments
+    .iter()
+    .filter_map(|com| if marked { com.strip_prefix('/') } else { Some(com) })
+    .map(|com| {
+      let com = com.trim_end();
+      com.strip_prefix(' ').unwrap_or(com)
+    })
+    .collect();
+  lines.join("\n")
+}
+
+// The line comments at the end of the source of a declaration, which are
+// read as part of it when it ends in a term, though they come before the
+// next declaration, and the length of the source without them
+pub fn trailing_comments(source: &str) -> (usize, Vec<&str>) {
+  let mut source = source.trim_end();
+  let mut comments = Vec::new();
+  loop {
+    let line = source.rfind('\n').map_or(0, |n| n + 1);
+    match source[line..].trim_start().strip_prefix("//") {
+      Some(com) if line > 0 => {
+        comments.push(com);
+        source = source[..line].trim_end();
+      }
+      _ => {
+        comments.reverse();
+        return (source.len(), comments);
+      }
+    }
+  }
+}
+
+// A type signature, which declares the type of a definition later in the
+// package, so that the definition can be given without one:
+//
+//   sig double : ∀ (n: #Natural) -> #Natural
+//   def double n = #add n n
+//
+// The doc comments before a signature document its definition, if it has
+// none of its own.
+#[derive(Clone, Debug)]
+pub struct Signature {
+  pub pos: Pos,
+  pub docs: String,
+  pub typ_: Term,
+}
+
+// The signatures whose definitions are yet to be parsed, by name
+pub type Sigs = HashMap<Name, Signature>;
+
+pub fn parse_signature<'a>(
+  refs: &'a Refs,
+  ops: &'a Ops,
+  sigs: &'a Sigs,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, (Name, Signature), ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (i, _) = terminated(tag("sig"), parse_space1)(from)?;
+    let (i, name) = context("name", parse_name)(i)?;
+    if refs.get(&name).is_some() || sigs.contains_key(&name) {
+      let kind = ParseErrorKind::TopLevelRedefinition(name.to_string());
+      return Err(Err::Failure(ParseError::new(from, kind)));
+    }
+    let (i, _) = parse_space(i)?;
+    let (i, _) = context("`:`", tag(":"))(i)?;
+    let (i, _) = parse_space(i)?;
+    let (upto, typ_) =
+      parse_expression(refs.to_owned(), ops.to_owned(), Vector::new())(i)?;
+    let pos = Pos::from_upto(from, upto);
+    Ok((upto, (name, Signature { pos, docs: docs.clone(), typ_ })))
+  }
+}
+
+// The number of binders a type starts with
+fn binders(typ: &Term) -> usize {
+  match typ {
+    Term::All(_, _, _, terms) => 1 + binders(&terms.1),
+    _ => 0,
+  }
+}
+
+pub fn parse_defn<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+  sigs: &'a Sigs,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Declaration, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (i, partial) = opt(terminated(tag("partial"), parse_space1))(from)?;
+    let (i, transparency) =
+      opt(terminated(parse_transparency, parse_space1))(i)?;
+    let (i, _) = context("`def`", tag("def"))(i)?;
+    let (i, _) = parse_space(i)?;
+    let (_, name) = peek(parse_name)(i)?;
+    let sig = sigs.get(&name);
+    let mismatch = || {
+      let kind = ParseErrorKind::SignatureMismatch(name.to_string());
+      Err::Failure(ParseError::new(from, kind))
+    };
+    // A type given with a signature is its type, up to the names of binders
+    let signed = |typ_: &Term| match sig {
+      Some(sig) => sig.typ_.clone().embed().0 == typ_.clone().embed().0,
+      None => true,
+    };
+    let typed = parse_typed_definition(
+      refs.to_owned(),
+      ops.to_owned(),
+      Vector::new(),
+      true,
+      false,
+    )(i);
+    let (upto, (name, term, typ_, implicits)) = match typed {
+      Ok((_, (_, _, typ_, _))) if !signed(&typ_) => return Err(mismatch()),
+      Ok(res) => res,
+      // A definition without a type has the one of its signature
+      Err(Err::Error(e)) => {
+        let untyped = parse_untyped_definition(
+          refs.to_owned(),
+          ops.to_owned(),
+          Vector::new(),
+          true,
+          false,
+        );
+        let (upto, (name, params, term)) = match untyped(i) {
+          Ok(res) => res,
+          Err(Err::Error(_)) => return Err(Err::Error(e)),
+          Err(e) => return Err(e),
+        };
+        let typ_ = match sig {
+          Some(sig) if params.len() <= binders(&sig.typ_) => sig.typ_.clone(),
+          Some(_) => return Err(mismatch()),
+          None => {
+            let kind = ParseErrorKind::MissingSignature(name.to_string());
+            return Err(Err::Failure(ParseError::new(from, kind)));
+          }
+        };
+        (upto, (name, term, typ_, Vec::new()))
+      }
+      Err(e) => return Err(e),
+    };
+    let pos = Some(Pos::from_upto(from, upto));
+    let transparency = transparency.unwrap_or_default();
+    let docs = match sig {
+      Some(sig) if docs.is_empty() => sig.docs.clone(),
+      _ => docs.clone(),
+    };
+    let def = Def { pos, name, docs, transparency, implicits, typ_, term };
+    let def = elaborate(defs, def, from, upto)?;
+    // Only definitions declared `partial` may recurse without terminating
+    if partial.is_none() {
+      check_termination(&def).map_err(|e| {
+        let at = locate(from, upto, e.pos());
+        Err::Failure(ParseError::new(at, ParseErrorKind::NonTerminating(e)))
+      })?;
+    }
+    Ok((upto, store_def(def)))
+  }
+}
+
+// A definition, or a block of mutually recursive definitions, which are
+// private to their package when declared `private`. The first definition of
+// a block is parsed as a definition on its own until it is found to refer to
+// the others, or to be followed by them.
+pub fn parse_defns<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+  sigs: &'a Sigs,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Vec<Declaration>, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (i, private) = opt(terminated(tag("private"), parse_space1))(from)?;
+    let defn = parse_defn(defs, refs, ops, sigs, docs.clone());
+    let mutual = parse_mutual(defs, refs, ops, sigs, docs.clone());
+    let (upto, decls) = match defn(i) {
+      Ok((upto, decl)) => {
+        let (i2, _) = parse_space(upto)?;
+        let and: IResult<Span, Span, ParseError<Span>> =
+          terminated(tag("and"), multispace1)(i2);
+        if and.is_ok() { mutual(i)? } else { (upto, vec![decl]) }
+      }
+      Err(Err::Error(e)) => match mutual(i) {
+        Err(Err::Error(_)) => return Err(Err::Error(e)),
+        res => res?,
+      },
+      Err(e) => return Err(e),
+    };
+    let decls = decls
+      .into_iter()
+      .map(|decl| match decl {
+        Declaration::Defn { name, defn, term, positions, .. } => {
+          let private = private.is_some();
+          Declaration::Defn { name, defn, term, positions, private }
+        }
+        decl => decl,
+      })
+      .collect();
+    Ok((upto, decls))
+  }
+}
+
+// A definition of a block of mutually recursive definitions, whose term is in
+// the scope of the names of the block, as its outermost variables
+struct Member {
+  pos: Pos,
+  docs: String,
+  name: Name,
+  implicits: Vec<bool>,
+  typ_: Term,
+  term: Term,
+}
+
+// The definitions of a block, joined by `and`, of which there are at least
+// two, in the scope of `names`. The comments before each `and` document the
+// definition after it, and a definition without a type has the one of its
+// signature.
+fn parse_members<'a>(
+  refs: &'a Refs,
+  ops: &'a Ops,
+  sigs: &'a Sigs,
+  names: Vector<Name>,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Vec<Member>, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let typed = parse_typed_definition(
+      refs.to_owned(),
+      ops.to_owned(),
+      names.clone(),
+      false,
+      false,
+    );
+    let untyped = parse_untyped_definition(
+      refs.to_owned(),
+      ops.to_owned(),
+      names.clone(),
+      false,
+      false,
+    );
+    let member = |i| match typed(i) {
+      Err(Err::Error(e)) => {
+        let (upto, (name, params, term)) = match untyped(i) {
+          Ok(res) => res,
+          Err(Err::Error(_)) => return Err(Err::Error(e)),
+          Err(e) => return Err(e),
+        };
+        let fail = |kind| Err(Err::Failure(ParseError::new(i, kind)));
+        match sigs.get(&name) {
+          Some(sig) if params.len() <= binders(&sig.typ_) => {
+            Ok((upto, (name, term, sig.typ_.clone(), Vec::new())))
+          }
+          Some(_) => fail(ParseErrorKind::SignatureMismatch(name.to_string())),
+          None => fail(ParseErrorKind::MissingSignature(name.to_string())),
+        }
+      }
+      res => res,
+    };
+    let mut members = Vec::new();
+    let mut docs = docs.clone();
+    let mut i = from;
+    loop {
+      let (upto, (name, term, typ_, implicits)) = member(i)?;
+      let pos = Pos::from_upto(i, upto);
+      members.push(Member { pos, docs, name, implicits, typ_, term });
+      let len = upto.location_offset() - i.location_offset();
+      let (_, comments) = trailing_comments(&i.fragment()[..len]);
+      let (i2, _) = parse_space(upto)?;
+      match terminated(tag("and"), parse_space1)(i2) {
+        Ok((i2, _)) => {
+          docs = self::docs(&comments);
+          i = i2;
+        }
+        Err(_) if members.len() > 1 => return Ok((upto, members)),
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
+
+// The names of the definitions of the block at the start of `source`, read
+// from the heads of its definitions ahead of parsing them: the first token,
+// and the one after each `and`, up to the start of the next declaration.
+// Comments and texts are skipped, as `and` could be in them. The names may
+// run past the end of the block, but never fall short of it.
+fn block_names(source: &str) -> Vector<Name> {
+  let delimiter = |c: char| c.is_whitespace() || ":;)(,[]{}".contains(c);
+  let ends = [
+    "def", "private", "partial", "opaque", "reducible", "open", "test",
+    "#check", "infix", "infixl", "infixr", "sig",
+  ];
+  let mut names = Vector::new();
+  let mut head = true;
+  let mut rest = source.trim_start_matches(delimiter);
+  while !rest.is_empty() {
+    if rest.starts_with("//") {
+      rest = rest.find('\n').map_or("", |end| &rest[end..]);
+    }
+    else if let Some(text) = rest.strip_prefix("\"\"\"") {
+      rest = text.find("\"\"\"").map_or("", |end| &text[end + 3..]);
+    }
+    else if let Some(text) = rest.strip_prefix('"') {
+      let mut chars = text.char_indices();
+      rest = "";
+      while let Some((idx, c)) = chars.next() {
+        match c {
+          '\\' => {
+            chars.next();
+          }
+          '"' => {
+            rest = &text[idx + 1..];
+            break;
+          }
+          _ => (),
+        }
+      }
+    }
+    else {
+      let end = rest.find(delimiter).unwrap_or(rest.len());
+      let token = &rest[..end];
+      if head {
+        names.push_back(Name::from(token));
+        head = false;
+      }
+      else if token == "and" {
+        head = true;
+      }
+      else if ends.contains(&token) {
+        break;
+      }
+      rest = &rest[end..];
+    }
+    rest = rest.trim_start_matches(delimiter);
+  }
+  names
+}
+
+// The `idx`th of the `len` components of a tuple nested to the right, as
+// `(a, (b, c))`
+fn project(tuple: &Term, idx: usize, len: usize) -> Term {
+  let pos = tuple.pos();
+  let mut term = tuple.clone();
+  for _ in 0..idx {
+    term = Term::Snd(pos, Box::new(term));
+  }
+  if idx + 1 < len {
+    Term::Fst(pos, Box::new(term))
+  }
+  else {
+    term
+  }
+}
+
+// A block of mutually recursive definitions, joined by `and`, each of which
+// may refer to all of them:
+//
+//   def even (n: Nat) : #Natural =
+//     (case n) (λ x => #Natural) 1 (λ m => odd m)
+//   and odd (n: Nat) : #Natural =
+//     (case n) (λ x => #Natural) 0 (λ m => even m)
+//
+// As on its own, a definition of a block may leave its type to a signature.
+// A definition can only refer to those stored before it, so the block is
+// stored as a single recursive definition of the tuple of their terms, named
+// after them joined by `&`, and each of them as a projection of it. Their
+// calls to each other are checked to terminate together, and the prefixes of
+// the block apply to all of them.
+pub fn parse_mutual<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+  sigs: &'a Sigs,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Vec<Declaration>, ParseError<Span>> + 'a {
   move |from: Span| {
-    let (i, _) = tag("def")(from)?;
+    let (i, partial) = opt(terminated(tag("partial"), parse_space1))(from)?;
+    let (i, transparency) =
+      opt(terminated(parse_transparency, parse_space1))(i)?;
+    let (i, _) = context("`def`", tag("def"))(i)?;
     let (i, _) = parse_space(i)?;
-    let (upto, (name, term, typ_)) =
-      parse_typed_definition(refs.to_owned(), Vector::new(), true, false)(i)?;
+    // The block is parsed in the scope of the names of its heads, and parsed
+    // again in the scope of those it defines if they run past its end
+    let names = block_names(i.fragment());
+    let parse = |names| parse_members(refs, ops, sigs, names, docs.clone());
+    let (upto, members) = parse(names.clone())(i)?;
+    let defined: Vector<Name> =
+      members.iter().map(|m| m.name.clone()).collect();
+    let (names, (upto, members)) = if defined == names {
+      (names, (upto, members))
+    }
+    else {
+      (defined.clone(), parse(defined)(i)?)
+    };
+    let len = members.len();
+    for (idx, member) in members.iter().enumerate() {
+      let nam = member.name.to_string();
+      let at = locate(from, upto, Some(member.pos));
+      let fail = |kind| Err(Err::Failure(ParseError::new(at, kind)));
+      if names.iter().take(idx).any(|n| *n == member.name) {
+        return fail(ParseErrorKind::TopLevelRedefinition(nam));
+      }
+      if !member.implicits.is_empty() {
+        return fail(ParseErrorKind::MutualImplicit(nam));
+      }
+      // The types are not in the scope of the block
+      let var = (0..len).find(|var| occurs(&member.typ_, *var as u64));
+      if let Some(var) = var {
+        let nam = names[var].to_string();
+        return fail(ParseErrorKind::UndefinedReference(nam, Vector::new()));
+      }
+      if let Some(sig) = sigs.get(&member.name) {
+        if sig.typ_.clone().embed().0 != member.typ_.clone().embed().0 {
+          return fail(ParseErrorKind::SignatureMismatch(nam));
+        }
+      }
+    }
+    let joined: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+    let name = Name::from(joined.join("&"));
+    if refs.get(&name).is_some() {
+      let kind = ParseErrorKind::TopLevelRedefinition(name.to_string());
+      return Err(Err::Failure(ParseError::new(from, kind)));
+    }
+    // Only blocks declared `partial` may recurse without terminating
+    if partial.is_none() {
+      let terms: Vec<(Name, &Term)> =
+        members.iter().map(|m| (m.name.clone(), &m.term)).collect();
+      check_mutual_termination(&terms).map_err(|e| {
+        let at = locate(from, upto, e.pos());
+        Err::Failure(ParseError::new(at, ParseErrorKind::NonTerminating(e)))
+      })?;
+    }
+    // Each reference to a definition of the block is a projection of the
+    // tuple, which is the outermost variable of its own term
     let pos = Some(Pos::from_upto(from, upto));
-    let def = Def { pos, name, docs: String::new(), typ_, term };
-    let def_name = def.name.clone();
-    let (defn, typ_, term) = def.embed();
-    let typ_enc = typ_.encode();
-    // println!("type {}", typ_enc.clone());
-    let _type_link = hashspace::put(typ_enc);
-    // println!("type link {:?} {}", _type_link, _type_link);
-    let trm_enc = term.encode();
-    // println!("term {}", trm_enc.clone());
-    let term_link = hashspace::put(trm_enc);
-    // println!("term link {:?} {}", term_link, term_link);
-    let def_enc = defn.encode();
-    // println!("def {}", def_enc.clone());
-    let def_link = hashspace::put(def_enc);
-    // println!("def link {:?} {}", def_link, def_link);
-    let def = Declaration::Defn {
-      name: def_name.clone(),
-      defn: def_link,
-      term: term_link,
+    let tuple = Term::Var(pos, name.clone(), len as u64);
+    let mut terms: Vec<Term> = members
+      .iter()
+      .map(|m| {
+        let term = (0..len).fold(m.term.clone(), |term, idx| {
+          term.subst(idx as u64, &project(&tuple, idx, len))
+        });
+        term.shift(-(len as i64), 0)
+      })
+      .collect();
+    let mut term = terms.pop().expect("a block of definitions");
+    for member in terms.into_iter().rev() {
+      term = Term::Par(pos, Box::new((member, term)));
+    }
+    let mut typ_ = members[len - 1].typ_.clone();
+    for member in members[..len - 1].iter().rev() {
+      let typs = (member.typ_.clone(), typ_);
+      typ_ = Term::Sig(pos, member.name.clone(), Box::new(typs));
+    }
+    let transparency = transparency.unwrap_or_default();
+    let docs = String::new();
+    let implicits = Vec::new();
+    let tuple = Def {
+      pos,
+      name: name.clone(),
+      docs,
+      transparency,
+      implicits,
+      typ_,
+      term,
     };
-    Ok((upto, def))
+    let tuple = elaborate(defs, tuple, from, upto)?;
+    let (defn, _, anon) = tuple.clone().embed();
+    let (defn, anon) = (defn.encode().link(), anon.encode().link());
+    let mut scope = defs.clone();
+    scope.insert(defn, tuple.clone());
+    let reference = Term::Ref(pos, name, defn, anon);
+    let new = members
+      .into_iter()
+      .enumerate()
+      .map(|(idx, m)| {
+        let docs = match sigs.get(&m.name) {
+          Some(sig) if m.docs.is_empty() => sig.docs.clone(),
+          _ => m.docs,
+        };
+        Def {
+          pos: Some(m.pos),
+          name: m.name,
+          docs,
+          transparency,
+          implicits: Vec::new(),
+          typ_: m.typ_,
+          term: project(&reference, idx, len),
+        }
+      })
+      .collect();
+    let mut decls = vec![store_def(tuple)];
+    decls.extend(store_defs(&scope, new, from, upto)?);
+    Ok((upto, decls))
+  }
+}
+
+// The input at `pos`, if it lies in the declaration parsed from `from` to
+// `upto`, and otherwise the end of the declaration. Errors found once a
+// declaration is parsed are reported where the offending term is.
+pub fn locate<'a>(
+  from: Span<'a>,
+  upto: Span<'a>,
+  pos: Option<Pos>,
+) -> Span<'a> {
+  let (start, end) = (from.location_offset(), upto.location_offset());
+  match pos.map(|pos| pos.from_offset as usize) {
+    Some(offset) if start <= offset && offset <= end => {
+      from.slice(offset - start..)
+    }
+    _ => upto,
+  }
+}
+
+// Elaborate a parsed definition into a core definition, ready to be stored.
+// The declaration has been parsed, so an elaboration error is a failure.
+fn elaborate<'a>(
+  defs: &Defs,
+  def: Def,
+  from: Span<'a>,
+  upto: Span<'a>,
+) -> Result<Def, Err<ParseError<Span<'a>>>> {
+  elab_def(defs, def).map_err(|e| {
+    let at = locate(from, upto, e.pos);
+    Err::Failure(ParseError::new(at, ParseErrorKind::ElabError(e)))
+  })
+}
+
+// Parse an expression in the scope of `refs`, which must be all of it, and
+// elaborate it, or else say why it cannot be
+pub fn parse_input(
+  defs: &Defs,
+  refs: &Refs,
+  line: &str,
+) -> Result<Term, String> {
+  let from = Span::new(line);
+  match parse_expression(refs.clone(), Ops::new(), Vector::new())(from) {
+    Ok((upto, _)) if !upto.fragment().trim().is_empty() => {
+      Err(format!("Unexpected input {}", upto.fragment().trim()))
+    }
+    Ok((upto, term)) => elab_term(defs, &term).map_err(|e| {
+      let at = locate(from, upto, e.pos);
+      ParseError::new(at, ParseErrorKind::ElabError(e)).to_string()
+    }),
+    Err(Err::Incomplete(_)) => Err(String::from("Incomplete input")),
+    Err(Err::Failure(e)) | Err(Err::Error(e)) => Err(e.to_string()),
+  }
+}
+
+// Put a definition, its type and its term in the hashspace, and its source
+// positions apart
+pub fn store_def(def: Def) -> Declaration {
+  let def_name = def.name.clone();
+  let (defn, typ_, term) = def.embed();
+  let positions = Some(Positions::of(&defn).store());
+  let typ_enc = typ_.encode();
+  // println!("type {}", typ_enc.clone());
+  let _type_link = hashspace::put(typ_enc);
+  // println!("type link {:?} {}", _type_link, _type_link);
+  let trm_enc = term.encode();
+  // println!("term {}", trm_enc.clone());
+  let term_link = hashspace::put(trm_enc);
+  // println!("term link {:?} {}", term_link, term_link);
+  let def_enc = defn.encode();
+  // println!("def {}", def_enc.clone());
+  let def_link = hashspace::put(def_enc);
+  // println!("def link {:?} {}", def_link, def_link);
+  Declaration::Defn {
+    name: def_name,
+    defn: def_link,
+    term: term_link,
+    positions,
+    private: false,
+  }
+}
+
+// Put a test in the hashspace. A test is checked when the package's tests
+// are run, not as it is parsed, so that a failing test does not keep the
+// rest of the package from being checked.
+pub fn store_test(def: Def) -> Declaration {
+  let name = def.name.clone();
+  let (defn, typ_, term) = def.embed();
+  let positions = Some(Positions::of(&defn).store());
+  hashspace::put(typ_.encode());
+  hashspace::put(term.encode());
+  let test = hashspace::put(defn.encode());
+  Declaration::Test { name, test, positions }
+}
+
+pub fn parse_test<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Declaration, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (i, _) = terminated(tag("test"), parse_space1)(from)?;
+    let (upto, (name, term, typ_, implicits)) = parse_typed_definition(
+      refs.to_owned(),
+      ops.to_owned(),
+      Vector::new(),
+      false,
+      true,
+    )(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let docs = docs.clone();
+    let transparency = Transparency::default();
+    // A test is elaborated when it is run, but the implicit arguments of the
+    // definitions it uses are inserted now, while they are in scope
+    let typ_ = insert_implicits(defs, &typ_);
+    let term = insert_implicits(defs, &term);
+    let def = Def { pos, name, docs, transparency, implicits, typ_, term };
+    Ok((upto, store_test(def)))
+  }
+}
+
+// A `#check` pragma, which passes when its term, if annotated, has the type
+// it is annotated with, and otherwise when a type can be synthesized for it.
+// The test is named after the line it is on.
+pub fn parse_check<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+) -> impl Fn(Span) -> IResult<Span, Declaration, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (i, _) = tag("#check")(from)?;
+    let (i, _) = parse_space1(i)?;
+    let (upto, term) =
+      parse_expression(refs.to_owned(), ops.to_owned(), Vector::new())(i)?;
+    let pos = Pos::from_upto(from, upto);
+    let name = Name::from(format!("#check:{}", pos.from_line));
+    let (typ_, term) = match term {
+      Term::Ann(_, ann) => *ann,
+      term => (Term::Hol(None, None), term),
+    };
+    let typ_ = insert_implicits(defs, &typ_);
+    let term = insert_implicits(defs, &term);
+    let docs = String::new();
+    let transparency = Transparency::default();
+    let implicits = Vec::new();
+    let def =
+      Def { pos: Some(pos), name, docs, transparency, implicits, typ_, term };
+    Ok((upto, store_test(def)))
+  }
+}
+
+// An infix operator declaration, which makes a symbol an operator applying
+// the definition it names, of an associativity and of a precedence from 0 to
+// 9. An operator is in scope in the rest of the package it is declared in,
+// but not in the packages which open it.
+//
+//   infixl 6 + = Nat.add
+pub fn parse_fixity(
+  refs: &Refs,
+) -> impl Fn(Span) -> IResult<Span, (Name, Operator), ParseError<Span>> + '_ {
+  move |from: Span| {
+    let (i, assoc) = alt((
+      value(Assoc::Left, tag("infixl")),
+      value(Assoc::Right, tag("infixr")),
+      value(Assoc::Non, tag("infix")),
+    ))(from)?;
+    let (i, _) = parse_space1(i)?;
+    let (i, prec) = context("precedence", one_of("0123456789"))(i)?;
+    let (i, _) = parse_space1(i)?;
+    let (i, nam) = context("operator", parse_name)(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = context("`=`", tag("="))(i)?;
+    let (i, _) = parse_space(i)?;
+    let (upto, name) = context("global reference", parse_name)(i)?;
+    if refs.get(&name).is_none() {
+      let kind =
+        ParseErrorKind::UndefinedReference(name.to_string(), Vector::new());
+      return Err(Err::Error(ParseError::new(upto, kind)));
+    }
+    let prec = prec as u8 - b'0';
+    Ok((upto, (nam, Operator { assoc, prec, name })))
+  }
+}
+
+pub fn parse_ctor(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, (Option<Pos>, Name, Term), ParseError<Span>>
+{
+  move |from: Span| {
+    let (i, nam) = parse_name(from)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = tag(":")(i)?;
+    let (i, _) = parse_space(i)?;
+    let (upto, typ) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx.to_owned())(i)?;
+    Ok((upto, (Some(Pos::from_upto(from, upto)), nam, typ)))
+  }
+}
+
+// A datatype declaration, elaborated into the definitions of the datatype,
+// of its constructors and of its recursor:
+//
+//   data List (A: Type): Type@1 {
+//     nil: List A,
+//     cons: ∀ (x: A) (xs: List A) -> List A,
+//   }
+//
+// The binders of its sort, after the colon, are the indices of a family:
+//
+//   data Eq (A: Type) (a: A): ∀ (b: A) -> Type@1 {
+//     refl: Eq A a a,
+//   }
+pub fn parse_data<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Vec<Declaration>, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (i, _) = tag("data")(from)?;
+    let (i, _) = parse_space(i)?;
+    let (i, nam) = parse_name(i)?;
+    if refs.get(&nam).is_some() {
+      return Err(Err::Error(ParseError::new(
+        i,
+        ParseErrorKind::TopLevelRedefinition(nam.to_string()),
+      )));
+    }
+    let (i, _) = parse_space(i)?;
+    let (i, params) = opt(terminated(
+      parse_binders(refs.to_owned(), ops.to_owned(), Vector::new(), false),
+      parse_space,
+    ))(i)?;
+    let params = params.unwrap_or_default();
+    let mut ctx = Vector::new();
+    for (_, n, _) in params.iter() {
+      ctx.push_front(n.clone());
+    }
+    let (i, _) = tag(":")(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, sort) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx.clone())(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = tag("{")(i)?;
+    let (i, _) = parse_space(i)?;
+    // Constructor types are in the scope of the datatype itself, outermost
+    ctx.push_back(nam.clone());
+    let (i, ctors) = separated_list0(
+      terminated(tag(","), parse_space),
+      terminated(
+        parse_ctor(refs.to_owned(), ops.to_owned(), ctx),
+        parse_space,
+      ),
+    )(i)?;
+    let (i, _) = opt(terminated(tag(","), parse_space))(i)?;
+    let (upto, _) = tag("}")(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let data = Datatype::new(pos, nam, params, sort, ctors).map_err(|e| {
+      Err::Error(ParseError::new(upto, ParseErrorKind::MalformedData(e)))
+    })?;
+    let data = Datatype { docs: docs.clone(), ..data };
+    Ok((upto, store_defs(defs, data.defs(), from, upto)?))
+  }
+}
+
+// The name, parameters, sort and fields of a declaration
+type Fields = (Name, Telescope, Term, Vec<(Option<Pos>, Name, Term)>);
+
+// Parse a declaration of fields introduced by `keyword`, each field in the
+// scope of those before it:
+//
+//   codata Stream (A: Type): Type {
+//     head: A,
+//     tail: Stream A,
+//   }
+fn parse_fields<'a>(
+  keyword: &'static str,
+  refs: &'a Refs,
+  ops: &'a Ops,
+) -> impl Fn(Span) -> IResult<Span, Fields, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (i, _) = tag(keyword)(from)?;
+    let (i, _) = parse_space(i)?;
+    let (i, nam) = parse_name(i)?;
+    if refs.get(&nam).is_some() {
+      return Err(Err::Error(ParseError::new(
+        i,
+        ParseErrorKind::TopLevelRedefinition(nam.to_string()),
+      )));
+    }
+    let (i, _) = parse_space(i)?;
+    let (i, params) = opt(terminated(
+      parse_binders(refs.to_owned(), ops.to_owned(), Vector::new(), false),
+      parse_space,
+    ))(i)?;
+    let params = params.unwrap_or_default();
+    let mut ctx = Vector::new();
+    for (_, n, _) in params.iter() {
+      ctx.push_front(n.clone());
+    }
+    let (i, _) = tag(":")(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, sort) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx.clone())(i)?;
+    let (i, _) = parse_space(i)?;
+    let (mut i, _) = tag("{")(i)?;
+    ctx.push_back(nam.clone());
+    let mut fields = Vec::new();
+    loop {
+      let (i2, _) = parse_space(i)?;
+      let ctor = parse_ctor(refs.to_owned(), ops.to_owned(), ctx.clone());
+      let (i2, field) = match ctor(i2) {
+        Ok(res) => res,
+        Err(Err::Error(_)) => break,
+        Err(e) => return Err(e),
+      };
+      ctx.push_front(field.1.clone());
+      fields.push(field);
+      let (i2, _) = parse_space(i2)?;
+      let (i2, comma) = opt(tag(","))(i2)?;
+      i = i2;
+      if comma.is_none() {
+        break;
+      }
+    }
+    let (i, _) = parse_space(i)?;
+    let (upto, _) = tag("}")(i)?;
+    Ok((upto, (nam, params, sort, fields)))
+  }
+}
+
+// Elaborate and store the definitions of a declaration parsed from `from` to
+// `upto`, each in the scope of those before it
+fn store_defs<'a>(
+  defs: &Defs,
+  new: Vec<Def>,
+  from: Span<'a>,
+  upto: Span<'a>,
+) -> Result<Vec<Declaration>, Err<ParseError<Span<'a>>>> {
+  let mut defs = defs.clone();
+  let mut decls = Vec::new();
+  for def in new {
+    let def = elaborate(&defs, def, from, upto)?;
+    let (d, ..) = def.clone().embed();
+    defs.insert(d.encode().link(), def.clone());
+    decls.push(store_def(def));
+  }
+  Ok(decls)
+}
+
+// A codatatype declaration, elaborated into the definitions of the
+// codatatype and of its destructors. Each field is in the scope of the
+// fields before it:
+//
+//   codata Stream (A: Type): Type {
+//     head: A,
+//     tail: Stream A,
+//   }
+pub fn parse_codata<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Vec<Declaration>, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (upto, (nam, params, sort, fields)) =
+      parse_fields("codata", refs, ops)(from)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let data = Codatatype::new(pos, nam, params, sort, fields).map_err(|e| {
+      Err::Error(ParseError::new(upto, ParseErrorKind::MalformedData(e)))
+    })?;
+    let data = Codatatype { docs: docs.clone(), ..data };
+    Ok((upto, store_defs(defs, data.defs(), from, upto)?))
+  }
+}
+
+// A record declaration, elaborated into the definitions of the record, of
+// the projections of its fields and of its constructor. Each field is in the
+// scope of the fields before it, but not of the record:
+//
+//   record Pair (A: Type) (B: Type): Type {
+//     fst: A,
+//     snd: B,
+//   }
+pub fn parse_record<'a>(
+  defs: &'a Defs,
+  refs: &'a Refs,
+  ops: &'a Ops,
+  docs: String,
+) -> impl Fn(Span) -> IResult<Span, Vec<Declaration>, ParseError<Span>> + 'a {
+  move |from: Span| {
+    let (upto, (nam, params, sort, fields)) =
+      parse_fields("record", refs, ops)(from)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let mut data = Record::new(pos, nam, params, sort, fields).map_err(|e| {
+      Err::Error(ParseError::new(upto, ParseErrorKind::MalformedData(e)))
+    })?;
+    data.codata.docs = docs.clone();
+    Ok((upto, store_defs(defs, data.defs(), from, upto)?))
   }
 }
 
@@ -179,13 +1225,14 @@ pub fn parse_package(
   source_link: Link,
 ) -> impl Fn(Span) -> IResult<Span, (Link, Package, Defs, Refs), ParseError<Span>>
 {
-  move |i: Span| {
-    let (i, _) = parse_space(i)?;
-    // let (i, docs) = parse_doc(
-    let docs = String::from("");
+  move |start: Span| {
+    let (i, comments) = parse_space(start)?;
+    let comments: Vec<&str> = comments.iter().map(|c| *c.fragment()).collect();
+    let package_docs = self::docs(&comments);
     let (i, _) = tag("package")(i)?;
     let (i, _) = multispace1(i)?;
     let (i, name) = parse_name(i)?;
+    let name = name.to_string();
     let file_name = env
       .path
       .file_name()
@@ -199,94 +1246,176 @@ pub fn parse_package(
     }
     let (i, _) = multispace1(i)?;
     let (i, _) = tag("where")(i)?;
+    #[cfg(feature = "native")]
+    parse_opened(&env, i.fragment());
     let mut decls: Vec<Declaration> = Vec::new();
-    let mut refs: Refs = HashMap::new();
-    let mut defs: Defs = HashMap::new();
+    let (mut defs, mut refs) = prelude();
+    let mut ops = Ops::new();
+    let mut sigs = Sigs::new();
     let mut i = i;
+    // The comments read as part of the last declaration
+    let mut trailing: Vec<&str> = Vec::new();
     loop {
-      let (i2, _) = parse_space(i)?;
+      let (i2, comments) = parse_space(i)?;
       i = i2;
+      trailing.extend(comments.iter().map(|c| *c.fragment()));
+      let docs = self::docs(&trailing);
       let end: IResult<Span, Span, ParseError<Span>> = eof(i);
+      let infix: IResult<Span, Span, ParseError<Span>> = tag("infix")(i);
+      let sig: IResult<Span, Span, ParseError<Span>> =
+        terminated(tag("sig"), multispace1)(i);
       if end.is_ok() {
-        let pack = Package { name, docs, source: source_link, decls };
+        // Each signature is followed by its definition
+        let unmatched = sigs.iter().min_by_key(|(_, sig)| sig.pos.from_offset);
+        if let Some((name, sig)) = unmatched {
+          let at = locate(start, i, Some(sig.pos));
+          let kind = ParseErrorKind::UnmatchedSignature(name.to_string());
+          return Err(Err::Failure(ParseError::new(at, kind)));
+        }
+        let pack =
+          Package { name, docs: package_docs, source: source_link, decls };
         let pack_link = hashspace::put(pack.clone().encode());
         return Ok((i, (pack_link, pack, defs, refs)));
       }
+      // An operator declaration declares no definition, and is not kept in
+      // the package, but puts the operator in scope of what follows it
+      else if infix.is_ok() {
+        let (i2, (nam, op)) = parse_fixity(&refs)(i)?;
+        ops.insert(nam, op);
+        trailing = Vec::new();
+        i = i2;
+      }
+      // A signature declares no definition, but the type of one which
+      // follows it
+      else if sig.is_ok() {
+        let (i2, (name, sig)) = parse_signature(&refs, &ops, &sigs, docs)(i)?;
+        sigs.insert(name, sig);
+        trailing = Vec::new();
+        i = i2;
+      }
       else {
-        let (i2, decl) =
-          alt((parse_defn(refs.to_owned()), parse_open(env.to_owned())))(i)?;
-        decls.push(decl.clone());
-        match decl {
-          Declaration::Defn { name, defn, term } => {
-            let def = Def::get_link(defn).map_err(|e| {
-              Err::Error(ParseError::new(i2, ParseErrorKind::EmbeddingError(e)))
-            })?;
-            refs.insert(name, (defn, term));
-            defs.insert(defn, def);
-          }
-          Declaration::Open { name, alias, with, from } => {
-            let pack = Package::get_link(from).map_err(|e| {
-              Err::Error(ParseError::new(i2, ParseErrorKind::EmbeddingError(e)))
-            })?;
-            if name != pack.name {
-              return Err(Err::Error(ParseError::new(
-                i2,
-                ParseErrorKind::MisnamedImport(name, from, pack.name),
-              )));
-            };
-            let (import_refs, import_defs): (Refs, Defs) =
-              pack.refs_defs().map_err(|e| {
+        // Each declaration is expected as the keyword it starts with, so
+        // that where none is found the error lists them all, with the end of
+        // the package
+        let (i2, new_decls) = alt((
+          context(
+            "`def`",
+            parse_defns(&defs, &refs, &ops, &sigs, docs.clone()),
+          ),
+          context(
+            "`data`",
+            parse_data(&defs, &refs, &ops, docs.clone()),
+          ),
+          context(
+            "`codata`",
+            parse_codata(&defs, &refs, &ops, docs.clone()),
+          ),
+          context(
+            "`record`",
+            parse_record(&defs, &refs, &ops, docs.clone()),
+          ),
+          context("`open`", map(parse_open(env.to_owned()), |decl| vec![decl])),
+          context(
+            "`test`",
+            map(parse_test(&defs, &refs, &ops, docs), |decl| vec![decl]),
+          ),
+          context(
+            "`#check`",
+            map(parse_check(&defs, &refs, &ops), |decl| vec![decl]),
+          ),
+        ))(i)
+        .map_err(|e| e.map(|e| e.or_expected(&i, "end of file")))?;
+        for decl in new_decls {
+          decls.push(decl.clone());
+          match decl {
+            Declaration::Defn { name, defn, term, positions, .. } => {
+              let def = Def::get_positioned(defn, positions).map_err(|e| {
                 Err::Error(ParseError::new(
                   i2,
                   ParseErrorKind::EmbeddingError(e),
                 ))
               })?;
-            defs = merge_defs(defs, import_defs);
-            refs = merge_refs(refs, import_refs, alias, with);
+              sigs.remove(name.as_str());
+              refs.insert(name, (defn, term));
+              defs.insert(defn, def);
+            }
+            Declaration::Open { name, alias, with, from } => {
+              let pack = Package::get_link(from).map_err(|e| {
+                Err::Error(ParseError::new(
+                  i2,
+                  ParseErrorKind::EmbeddingError(e),
+                ))
+              })?;
+              if name != pack.name {
+                return Err(Err::Error(ParseError::new(
+                  i2,
+                  ParseErrorKind::MisnamedImport(
+                    String::from(name),
+                    from,
+                    pack.name,
+                  ),
+                )));
+              };
+              let (import_refs, import_defs): (Refs, Defs) =
+                pack.refs_defs().map_err(|e| {
+                  Err::Error(ParseError::new(
+                    i2,
+                    ParseErrorKind::EmbeddingError(e),
+                  ))
+                })?;
+              defs = merge_defs(defs, import_defs);
+              refs = merge_refs(refs, import_refs, alias, with);
+            }
+            Declaration::Test { .. } => (),
           }
         }
+        let source = *i.fragment();
+        let len = i2.location_offset() - i.location_offset();
+        trailing = trailing_comments(&source[..len]).1;
         i = i2;
       }
     }
   }
 }
 
-pub fn parse_file<'a>(env: PackageEnv) -> (Link, Package, Defs, Refs) {
+// Parse the package in the file of `env`. An error is in a source other than
+// the one which opens the package, so it is returned as a kind alone, which
+// says where in the file it is.
+#[cfg(feature = "native")]
+pub fn parse_file(
+  env: PackageEnv,
+) -> Result<(Link, Package, Defs, Refs), ParseErrorKind> {
   let path = env.path.clone();
-  let txt = fs::read_to_string(&path).expect("file not found");
-  let source_link = hashspace::put(text!(txt.clone()));
+  let open_error = |e: String| ParseErrorKind::OpenError(path.clone(), e);
+  let file = path.display();
+  let _span = info_span!(target: "parse", "parse_file", %file).entered();
+  let start = Instant::now();
+  let txt = fs::read_to_string(&path).map_err(|e| open_error(e.to_string()))?;
+  info!(target: "parse", %file, "Parsing");
+  let source_link = hashspace::put_text(&txt);
   let span = Span::new(&txt);
   match parse_package(env, source_link)(span) {
-    Ok((_, p)) => p,
-    Err(e) => match e {
-      Err::Incomplete(_) => panic!("Incomplete"),
-      Err::Failure(e) => {
-        panic!("Parse Failure:\n{}", e);
-      }
-      Err::Error(e) => {
-        panic!("Parse Error:\n{}", e);
-      }
-    },
+    Ok((_, p)) => {
+      let time = start.elapsed();
+      debug!(target: "parse", %file, link = %p.0, ?time, "Parsed");
+      Ok(p)
+    }
+    Err(Err::Incomplete(_)) => Err(open_error(String::from("Incomplete"))),
+    Err(Err::Failure(e)) | Err(Err::Error(e)) => {
+      let at = (e.input.location_line(), e.input.get_utf8_column());
+      Err(open_error(format!("{}:{}: {}", at.0, at.1, e.reasons())))
+    }
   }
 }
 
-// pub fn parse_data_decl(
-//  refs: Refs,
-//  ctx: Vector<String>,
-//) -> impl Fn(Span) -> IResult<Span, Vec<(String, Term)>, ParseError<Span>> {
-//  move |i: Span| {
-//    let (i, _) = tag("data")?;
-//    let (i, _) = multispace1(i)?;
-//    let (i, nam) = parse_name(i)?;
-//    let (i, _) = multispace0(i)?;
-//    let (i, bs) = parse_binders(refs, Vector::new(), false)
-//
-//  }
-//}
-//
 #[cfg(test)]
 pub mod tests {
   use super::*;
+  use crate::core::{
+    dag::DAG,
+    eval::norm,
+    nbe,
+  };
 
   #[test]
   fn test_cases() {
@@ -303,4 +1432,387 @@ pub mod tests {
     println!("res: {:?}", res);
     assert!(res.is_ok());
   }
+
+  #[test]
+  fn test_transparency() {
+    let (defs, refs) = prelude();
+    let ops = Ops::new();
+    let sigs = Sigs::new();
+    let cases = [
+      ("def one : #Natural = 1", Transparency::Transparent),
+      ("opaque def one : #Natural = 1", Transparency::Opaque),
+      ("partial reducible def one : #Natural = 1", Transparency::Reducible),
+    ];
+    for (input, transparency) in &cases {
+      let parse = parse_defn(&defs, &refs, &ops, &sigs, String::new());
+      match parse(Span::new(input)) {
+        Ok((_, Declaration::Defn { defn, .. })) => {
+          let def = Def::get_link(defn).unwrap();
+          assert_eq!(def.transparency, *transparency);
+        }
+        res => panic!("unexpected result {:?}", res),
+      }
+    }
+  }
+
+  #[test]
+  fn parses_indexed_data() {
+    let (defs, refs) = prelude();
+    let ops = Ops::new();
+    // The prelude's own `Eq` is in scope
+    let source = "data Path (A: Type) (a: A): ∀ (b: A) -> Type@1 {\n  refl: \
+                  Path A a a,\n}";
+    let parse = parse_data(&defs, &refs, &ops, String::new());
+    let (_, decls) = parse(Span::new(source))
+      .unwrap_or_else(|e| panic!("unexpected error {:?}", e));
+    let names: Vec<Name> = decls
+      .iter()
+      .filter_map(|decl| match decl {
+        Declaration::Defn { name, .. } => Some(name.clone()),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(names, vec!["Path", "Path.refl", "Path.rec"]);
+  }
+
+  #[test]
+  fn test_docs() {
+    let source = "def a : #Natural = 1 // one\n// The next\n  // definition\n";
+    let (len, comments) = trailing_comments(source);
+    assert_eq!(&source[..len], "def a : #Natural = 1 // one");
+    assert_eq!(comments, vec![" The next", " definition"]);
+    assert_eq!(docs(&comments), "The next\ndefinition");
+    assert_eq!(trailing_comments("def a : #Natural = 1\n").1.len(), 0);
+    let comments = vec![" TODO", "/ The next", "/ definition"];
+    assert_eq!(docs(&comments), "The next\ndefinition");
+  }
+
+  #[test]
+  fn stores_doc_comments() {
+    let source = "/// Numbers\npackage Documented where\n// A note\n/// The \
+                  first\n/// number\ndef one : #Natural = 1\n";
+    let env = PackageEnv::new(PathBuf::from("Documented.ya"));
+    let link = Link::make(&Expr::serialize_text(source));
+    let (_, (_, pack, ..)) = parse_package(env, link)(Span::new(source))
+      .unwrap_or_else(|e| panic!("unexpected error {:?}", e));
+    assert_eq!(pack.docs, "Numbers");
+    match &pack.decls[..] {
+      [Declaration::Defn { defn, .. }] => {
+        let def = Def::get_link(*defn).unwrap();
+        assert_eq!(def.docs, "The first\nnumber");
+      }
+      decls => panic!("unexpected declarations {:?}", decls),
+    }
+  }
+
+  #[test]
+  fn parses_input() {
+    let (defs, refs) = prelude();
+    let term = parse_input(&defs, &refs, " (λ x => x) Type ").unwrap();
+    let dag = DAG::from_term(term).unwrap();
+    assert_eq!(format!("{}", norm(&defs, dag)), "Type");
+    assert!(parse_input(&defs, &refs, "Type )").is_err());
+    assert!(parse_input(&defs, &refs, "undefined_name").is_err());
+  }
+
+  #[test]
+  fn reports_expected_declarations() {
+    let source = "package Expects where\ndef one : #Natural = 1\n}\n";
+    let env = PackageEnv::new(PathBuf::from("Expects.ya"));
+    let link = Link::make(&Expr::serialize_text(source));
+    match parse_package(env, link)(Span::new(source)) {
+      Err(Err::Error(e)) => {
+        assert_eq!(e.input.location_line(), 3);
+        assert_eq!(
+          e.reasons(),
+          "Expected one of: `def`, `data`, `codata`, `record`, `open`, \
+           `test`, `#check`, end of file"
+        );
+      }
+      res => panic!("unexpected result {:?}", res.map(|(link, ..)| link)),
+    }
+    let (_, refs) = prelude();
+    let expr = parse_expression(refs, Ops::new(), Vector::new());
+    let res = expr(Span::new("λ x )"));
+    match res {
+      Err(Err::Error(e)) => {
+        assert_eq!(e.expected, vec!["`=>`", "a name"]);
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[test]
+  fn declares_operators() {
+    let head = "package Arith where\ndef add : ∀ (a: #Natural) (b: #Natural) \
+                -> #Natural = λ a b => #add a b\n";
+    let parse = |decls: &str| {
+      let source = format!("{}{}", head, decls);
+      let env = PackageEnv::new(PathBuf::from("Arith.ya"));
+      let link = Link::make(&Expr::serialize_text(&source));
+      match parse_package(env, link)(Span::new(&source)) {
+        Ok((_, parsed)) => Ok(parsed),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => Err(e.errors),
+        Err(Err::Incomplete(_)) => Err(Vec::new()),
+      }
+    };
+    let (_, pack, defs, refs) = parse(
+      "infixl 6 + = add\ndef six : #Natural = 1 + 2 + 3\ndef plus : ∀ (a: \
+       #Natural) (b: #Natural) -> #Natural = (+)\n",
+    )
+    .unwrap_or_else(|e| panic!("unexpected error {:?}", e));
+    // The operator declaration is not kept in the package
+    assert_eq!(pack.decls.len(), 3);
+    let (six, _) = refs.get("six").unwrap();
+    let six = norm(&defs, DAG::from_term(defs[six].term.clone()).unwrap());
+    assert_eq!(format!("{}", six), "6");
+    assert!(refs.contains_key("plus"));
+    match parse("infix 4 == = add\ndef b : #Natural = 1 == 2 == 3\n") {
+      Err(errors) => assert!(errors.iter().any(|e| matches!(
+        e,
+        ParseErrorKind::NonAssociative(op) if op == "=="
+      ))),
+      res => panic!("unexpected result {:?}", res.map(|(link, ..)| link)),
+    }
+    assert!(parse("infixl 6 + = undefined_name\n").is_err());
+    assert!(parse("infixl 10 + = add\n").is_err());
+  }
+
+  #[test]
+  fn elaborates_implicit_arguments() {
+    let source = "package Id where\ndef id {A: Type} (x: A) : A = x\ndef one : \
+                  #Natural = id 1\ndef const {A B: Type} (x: A) (y: B) : A = \
+                  x\ndef two : #Natural = const 2 3\npartial def spin {A: \
+                  Type} (x: A) : A = spin x\n";
+    let env = PackageEnv::new(PathBuf::from("Id.ya"));
+    let link = Link::make(&Expr::serialize_text(source));
+    let (_, (_, _, defs, refs)) =
+      parse_package(env, link)(Span::new(source)).unwrap();
+    let def = |nam: &str| &defs[&refs.get(nam).unwrap().0];
+    assert_eq!(def("id").implicits, vec![true]);
+    assert_eq!(def("const").implicits, vec![true, true]);
+    assert_eq!(format!("{}", def("one").term), "id #Natural 1");
+    assert_eq!(format!("{}", def("two").term), "const #Natural #Natural 2 3");
+    assert_eq!(format!("{}", def("spin").term), "λ A x => spin A x");
+  }
+
+  #[test]
+  fn declares_signatures() {
+    let parse = |decls: &str| {
+      let source = format!("package Sigs where\n{}", decls);
+      let env = PackageEnv::new(PathBuf::from("Sigs.ya"));
+      let link = Link::make(&Expr::serialize_text(&source));
+      match parse_package(env, link)(Span::new(&source)) {
+        Ok((_, (_, _, defs, refs))) => Ok((defs, refs)),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => Err(e.errors),
+        Err(Err::Incomplete(_)) => Err(Vec::new()),
+      }
+    };
+    let sig = "sig double : ∀ (n: #Natural) -> #Natural\n";
+    let (defs, refs) = parse(&format!(
+      "{}def double n = #add n n\ndef four : #Natural = double 2\n",
+      sig
+    ))
+    .unwrap_or_else(|e| panic!("unexpected error {:?}", e));
+    let (double, _) = refs.get("double").unwrap();
+    let typ_ = format!("{}", defs[double].typ_);
+    assert_eq!(typ_, "∀ (n: #Natural) -> #Natural");
+    let (four, _) = refs.get("four").unwrap();
+    let four = norm(&defs, DAG::from_term(defs[four].term.clone()).unwrap());
+    assert_eq!(format!("{}", four), "4");
+    // A definition may also give the type of its signature, up to the names
+    // of its binders
+    let typed = "def double (m: #Natural) : #Natural = #add m m\n";
+    assert!(parse(&format!("{}{}", sig, typed)).is_ok());
+    let fails = |decls: &str, kind: ParseErrorKind| match parse(decls) {
+      Err(errors) => assert!(errors.contains(&kind), "{:?}", errors),
+      Ok(_) => panic!("{} parsed", decls),
+    };
+    let double = String::from("double");
+    fails(
+      "def double n = #add n n\n",
+      ParseErrorKind::MissingSignature(double.clone()),
+    );
+    fails(sig, ParseErrorKind::UnmatchedSignature(double.clone()));
+    fails(
+      &format!("{}def double m n = #add m n\n", sig),
+      ParseErrorKind::SignatureMismatch(double.clone()),
+    );
+    fails(
+      &format!("{}def double (n: #Natural) : Type = #Natural\n", sig),
+      ParseErrorKind::SignatureMismatch(double.clone()),
+    );
+    fails(
+      &format!("{}{}", sig, sig),
+      ParseErrorKind::TopLevelRedefinition(double),
+    );
+  }
+
+  #[test]
+  fn declares_mutual_definitions() {
+    let parse = |decls: &str| {
+      let source = format!(
+        "package Mutual where\ndata Nat : Type@1 {{ zero: Nat, succ: ∀ (n: \
+         Nat) -> Nat }}\n{}",
+        decls
+      );
+      let env = PackageEnv::new(PathBuf::from("Mutual.ya"));
+      let link = Link::make(&Expr::serialize_text(&source));
+      match parse_package(env, link)(Span::new(&source)) {
+        Ok((_, (_, _, defs, refs))) => Ok((defs, refs)),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => Err(e.errors),
+        Err(Err::Incomplete(_)) => Err(Vec::new()),
+      }
+    };
+    let (defs, refs) = parse(
+      "def even (n: Nat) : #Natural =\n  (case n) (λ x => #Natural) 1 (λ m \
+       => odd m)\n/// Whether a number is odd\nand odd (n: Nat) : #Natural \
+       =\n  (case n) (λ x => #Natural) 0 (λ m => even m)\ndef three : \
+       #Natural = odd (Nat.succ (Nat.succ (Nat.succ Nat.zero)))\n",
+    )
+    .unwrap_or_else(|e| panic!("unexpected error {:?}", e));
+    assert!(refs.contains_key("even&odd"));
+    let def = |nam: &str| &defs[&refs.get(nam).unwrap().0];
+    assert_eq!(format!("{}", def("even").term), "fst even&odd");
+    assert_eq!(format!("{}", def("odd").term), "snd even&odd");
+    assert_eq!(def("odd").docs, "Whether a number is odd");
+    let three = nbe::norm(&defs, &def("three").term);
+    assert_eq!(format!("{}", three), "1");
+    let fails = |decls: &str| match parse(decls) {
+      Err(errors) => errors,
+      Ok(_) => panic!("{} parsed", decls),
+    };
+    let errors = fails(
+      "def f (n: Nat) : Nat = g n\nand g (n: Nat) : Nat = f n\n",
+    );
+    let non_terminating =
+      |e: &ParseErrorKind| matches!(e, ParseErrorKind::NonTerminating(_));
+    assert!(errors.iter().any(non_terminating));
+    let errors = fails(
+      "partial def f (n: Nat) : Nat = h n\nand g (n: Nat) : Nat = f n\n",
+    );
+    assert!(errors.iter().any(|e| matches!(
+      e,
+      ParseErrorKind::UndefinedReference(nam, _) if nam == "h"
+    )));
+    let errors = fails(
+      "partial def f {A: Type} (n: Nat) : Nat = g n\nand g (n: Nat) : Nat \
+       = f Nat n\n",
+    );
+    let f = String::from("f");
+    assert!(errors.contains(&ParseErrorKind::MutualImplicit(f)));
+    // Blocks whose definitions do not refer to each other parse as well
+    assert!(parse("def f : Nat = Nat.zero\nand g : Nat = f\n").is_ok());
+    // A definition of a block may have the type of its signature
+    let (defs, refs) = parse(
+      "sig g : ∀ (n: Nat) -> Nat\npartial def f (n: Nat) : Nat = g n\n// \
+       and h\nand g n = f n\ndef k : Nat = Nat.zero\n",
+    )
+    .unwrap_or_else(|e| panic!("unexpected error {:?}", e));
+    assert!(refs.contains_key("f&g") && refs.contains_key("k"));
+    let g = &defs[&refs.get("g").unwrap().0];
+    assert_eq!(format!("{}", g.typ_), "∀ (n: Nat) -> Nat");
+    let errors = fails("def f (n: Nat) : Nat = g n\nand g n = f n\n");
+    let g = String::from("g");
+    assert!(errors.contains(&ParseErrorKind::MissingSignature(g)));
+  }
+
+  #[test]
+  fn keeps_private_definitions() {
+    let source = "package Private where\nprivate def helper : #Natural = \
+                  1\ndef main : #Natural = helper\n";
+    let env = PackageEnv::new(PathBuf::from("Private.ya"));
+    let link = Link::make(&Expr::serialize_text(&source));
+    let (_, (_, pack, defs, refs)) =
+      parse_package(env, link)(Span::new(&source)).unwrap();
+    // In scope of its own package, a private definition is like any other
+    assert!(refs.contains_key("helper") && refs.contains_key("main"));
+    let helper = refs.get("helper").unwrap().0;
+    assert!(defs.contains_key(&helper));
+    assert!(matches!(
+      &pack.decls[0],
+      Declaration::Defn { private: true, .. }
+    ));
+    assert_eq!(Package::decode(pack.clone().encode()), Ok(pack.clone()));
+    // but a package opening it only sees the public names
+    let (open_refs, open_defs) = pack.refs_defs().unwrap();
+    assert!(!open_refs.contains_key("helper"));
+    assert!(open_refs.contains_key("main"));
+    assert!(open_defs.contains_key(&helper));
+  }
+
+  // A package of a thousand definitions, each referring to the one before
+  // it. How the bytes allocated grow with the definitions is measured by the
+  // `parse` bench.
+  #[test]
+  fn parses_a_thousand_definitions() {
+    let mut source = String::from("package Thousand where\n");
+    source.push_str("def d0 : #Natural = 0\n");
+    for n in 1..1000 {
+      source.push_str(&format!("def d{} : #Natural = d{}\n", n, n - 1));
+    }
+    let env = PackageEnv::new(PathBuf::from("Thousand.ya"));
+    let link = Link::make(&Expr::serialize_text(&source));
+    let (_, (_, pack, _, refs)) =
+      parse_package(env, link)(Span::new(&source)).unwrap();
+    assert_eq!(pack.decls.len(), 1000);
+    assert!(refs.contains_key("d0") && refs.contains_key("d999"));
+  }
+
+  #[cfg(feature = "native")]
+  #[test]
+  fn reports_unopenable_packages() {
+    let dir = std::env::temp_dir().join("yatima-open");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("Opens.ya");
+    let parse = || parse_file(PackageEnv::new(path.clone()));
+    let _ = fs::remove_file(&path);
+    match parse() {
+      Err(ParseErrorKind::OpenError(p, _)) => assert_eq!(p, path),
+      res => panic!("unexpected result {:?}", res.map(|(link, ..)| link)),
+    }
+    // A package which opens one that does not parse does not parse either
+    fs::write(&path, "package Opens where\nopen Broken\n").unwrap();
+    fs::write(dir.join("Broken.ya"), "package Broken where\ndef\n").unwrap();
+    match parse() {
+      Err(ParseErrorKind::OpenError(p, _)) => assert_eq!(p, path),
+      res => panic!("unexpected result {:?}", res.map(|(link, ..)| link)),
+    }
+  }
+
+  #[cfg(feature = "native")]
+  #[test]
+  fn parses_opened_packages() {
+    let dir = std::env::temp_dir().join("yatima-opened");
+    fs::create_dir_all(&dir).unwrap();
+    let files = [
+      ("Main", "open Left\nopen Right"),
+      ("Left", "open Base\ndef left : #Natural = base"),
+      ("Right", "open Base\ndef right : #Natural = base"),
+      ("Base", "def base : #Natural = 1"),
+    ];
+    for (name, decls) in &files {
+      let source = format!("package {} where\n{}\n", name, decls);
+      fs::write(dir.join(format!("{}.ya", name)), source).unwrap();
+    }
+    let env = PackageEnv::new(dir.join("Main.ya"));
+    let done = env.done.clone();
+    let (_, pack, _, refs) = parse_file(env).unwrap();
+    assert!(refs.contains_key("left") && refs.contains_key("right"));
+    // The packages are opened in the order of their declarations
+    let names: Vec<Name> = pack
+      .decls
+      .iter()
+      .filter_map(|decl| match decl {
+        Declaration::Open { name, .. } => Some(name.clone()),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(names, vec!["Left", "Right"]);
+    // Each opened file is parsed once, however many packages open it
+    let done = done.lock().unwrap();
+    for name in &["Left.ya", "Right.ya", "Base.ya"] {
+      assert!(done.contains_key(&dir.join(name)));
+    }
+  }
 }
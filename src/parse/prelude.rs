@@ -0,0 +1,146 @@
+use crate::{
+  package::Declaration,
+  parse::{
+    package::{
+      docs,
+      parse_defn,
+      Sigs,
+    },
+    span::Span,
+    term::{
+      parse_space,
+      Ops,
+    },
+  },
+  term::{
+    Def,
+    Defs,
+    Refs,
+  },
+};
+
+use im::HashMap;
+
+// The definitions in scope in every package and in the REPL.
+//
+// Propositional equality is a self type whose only constructor, `refl`, is
+// inlined in it, as with the datatypes of `data` declarations. Eliminating an
+// equality with `case` gives the J rule, which computes on `refl`: the
+// definitional equality `Eq.J A a P r a (Eq.refl A a) = r` holds by
+// conversion. The motive of J quantifies over `Type`, so `Eq` lives in
+// `Type@1`, and J eliminates into `Type`: enough to rewrite the type of a
+// term along an equality with `Eq.rewrite`.
+pub const PRELUDE: &str = "
+def Eq (A: Type) (a: A) (b: A): Type@1 =
+  @self ∀
+    (P: ∀ (b: A) (e: Eq A a b) -> Type)
+    (refl: P a (data λ P refl => refl))
+    -> P b self
+
+def Eq.refl (A: Type) (a: A): Eq A a a = data λ P refl => refl
+
+def Eq.J
+  (A: Type)
+  (a: A)
+  (P: ∀ (b: A) (e: Eq A a b) -> Type)
+  (r: P a (Eq.refl A a))
+  (b: A)
+  (e: Eq A a b)
+  : P b e
+  = (case e) P r
+
+def Eq.rewrite
+  (A: Type)
+  (a: A)
+  (b: A)
+  (e: Eq A a b)
+  (P: ∀ (x: A) -> Type)
+  (x: P a)
+  : P b
+  = Eq.J A a (λ b e => P b) x b e
+";
+
+// Parse and store the prelude's definitions
+pub fn prelude() -> (Defs, Refs) {
+  let mut defs: Defs = HashMap::new();
+  let mut refs: Refs = HashMap::new();
+  let ops = Ops::new();
+  let sigs = Sigs::new();
+  let mut i = Span::new(PRELUDE);
+  loop {
+    let (i2, comments) = parse_space(i).expect("prelude");
+    if i2.fragment().is_empty() {
+      return (defs, refs);
+    }
+    let comments: Vec<&str> = comments.iter().map(|c| *c.fragment()).collect();
+    let defn = parse_defn(&defs, &refs, &ops, &sigs, docs(&comments))(i2);
+    let (i2, decl) = match defn {
+      Ok(res) => res,
+      Err(e) => panic!("Malformed prelude: {:?}", e),
+    };
+    if let Declaration::Defn { name, defn, term, .. } = decl {
+      let def = Def::get_link(defn).expect("prelude definition");
+      refs.insert(name, (defn, term));
+      defs.insert(defn, def);
+    }
+    i = i2;
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    core::{
+      check::{
+        check_def,
+        infer_term,
+      },
+      nbe::norm,
+    },
+    parse::term::parse_expression,
+    term::Term,
+  };
+  use im::Vector;
+
+  fn term(refs: &Refs, input: &str) -> Term {
+    let expr = parse_expression(refs.clone(), HashMap::new(), Vector::new());
+    expr(Span::new(input)).unwrap().1
+  }
+
+  #[test]
+  fn typechecks() {
+    let (defs, refs) = prelude();
+    for nam in &["Eq", "Eq.refl", "Eq.J", "Eq.rewrite"] {
+      let (def_link, _) = refs.get(*nam).unwrap();
+      let def = defs.get(def_link).unwrap();
+      if let Err(e) = check_def(&defs, def) {
+        panic!("{}: {}", nam, e);
+      }
+    }
+  }
+
+  #[test]
+  fn computes() {
+    let (defs, refs) = prelude();
+    let j = term(
+      &refs,
+      "Eq.J #Natural 1 (λ b e => #Natural) 2 1 (Eq.refl #Natural 1)",
+    );
+    assert_eq!(format!("{}", infer_term(&defs, &j).unwrap()), "#Natural");
+    assert_eq!(format!("{}", norm(&defs, &j)), "2");
+    // Rewriting along a variable equality
+    let rewrite = term(
+      &refs,
+      "(λ a b e x => Eq.rewrite #Natural a b e (λ n => #Natural) x) \
+       :: ∀ (a: #Natural) (b: #Natural) (e: Eq #Natural a b) (x: #Natural) \
+       -> #Natural",
+    );
+    assert!(infer_term(&defs, &rewrite).is_ok());
+    // A proof by reflexivity needs the sides to be convertible
+    let refl = term(&refs, "Eq.refl #Natural 2 :: Eq #Natural (#add 1 1) 2");
+    assert!(infer_term(&defs, &refl).is_ok());
+    let refl = term(&refs, "Eq.refl #Natural 2 :: Eq #Natural 1 2");
+    assert!(infer_term(&defs, &refl).is_err());
+  }
+}
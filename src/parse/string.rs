@@ -75,6 +75,7 @@ pub fn parse_escape(i: Span) -> IResult<Span, char, ParseError<Span>> {
     value('\t', char('t')),
     value('\u{08}', char('b')),
     value('\u{0C}', char('f')),
+    value('\0', char('0')),
     value('\\', char('\\')),
     value('"', char('"')),
     value('\'', char('\'')),
@@ -1,4 +1,12 @@
 use crate::{
+  core::matching::{
+    compile,
+    Clause,
+    Family,
+    MatchError,
+    Pattern,
+  },
+  name::Name,
   parse::{
     error::{
       throw_err,
@@ -6,6 +14,7 @@ use crate::{
       ParseErrorKind,
     },
     literal::*,
+    package::locate,
   },
   term::{
     LitType,
@@ -32,6 +41,7 @@ use nom::{
     take_till1,
   },
   character::complete::{
+    digit1,
     multispace0,
     multispace1,
   },
@@ -43,22 +53,60 @@ use nom::{
     success,
     value,
   },
-  error::context,
+  error::{
+    context,
+    ErrorKind,
+  },
   multi::{
     many0,
     many1,
+    separated_list0,
     separated_list1,
   },
   sequence::{
-    delimited,
     preceded,
     terminated,
+    tuple,
   },
   Err,
   IResult,
   Slice,
 };
 
+use std::fmt;
+
+// How applications of operators of the same precedence group: `a - b - c` is
+// `(a - b) - c` for a left associative `-`, `a - (b - c)` for a right
+// associative one, and is ambiguous for one associative neither way
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Assoc {
+  Left,
+  Right,
+  Non,
+}
+
+impl fmt::Display for Assoc {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Left => write!(f, "infixl"),
+      Self::Right => write!(f, "infixr"),
+      Self::Non => write!(f, "infix"),
+    }
+  }
+}
+
+// An infix operator, as declared by `infixl 6 + = Nat.add`, which is read
+// between two terms as the application of the definition it names to both
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Operator {
+  pub assoc: Assoc,
+  pub prec: u8,
+  pub name: Name,
+}
+
+// The infix operators in scope, by their symbols
+pub type Ops = HashMap<Name, Operator>;
+
 pub fn reserved_symbols() -> Vector<String> {
   Vector::from(vec![
     String::from("//"),
@@ -70,6 +118,10 @@ pub fn reserved_symbols() -> Vector<String> {
     String::from("∀"),
     String::from("forall"),
     String::from("->"),
+    String::from("Σ"),
+    String::from("sigma"),
+    String::from("fst"),
+    String::from("snd"),
     String::from("@"),
     String::from("="),
     String::from(";"),
@@ -77,9 +129,26 @@ pub fn reserved_symbols() -> Vector<String> {
     String::from("type"),
     String::from("data"),
     String::from("def"),
+    String::from("private"),
+    String::from("partial"),
+    String::from("opaque"),
+    String::from("reducible"),
     String::from("open"),
+    String::from("test"),
     String::from("case"),
+    String::from("match"),
+    String::from("return"),
+    String::from("if"),
+    String::from("then"),
+    String::from("else"),
+    String::from("infix"),
+    String::from("infixl"),
+    String::from("infixr"),
+    String::from("sig"),
+    String::from("and"),
+    String::from("do"),
     String::from("Type"),
+    String::from("Prop"),
   ])
 }
 
@@ -100,7 +169,7 @@ pub fn parse_space1(i: Span) -> IResult<Span, Vec<Span>, ParseError<Span>> {
   Ok((i, com))
 }
 
-pub fn parse_name(from: Span) -> IResult<Span, String, ParseError<Span>> {
+pub fn parse_name(from: Span) -> IResult<Span, Name, ParseError<Span>> {
   let (i, s) = take_till1(|x| {
     char::is_whitespace(x)
       | (x == ':')
@@ -108,6 +177,10 @@ pub fn parse_name(from: Span) -> IResult<Span, String, ParseError<Span>> {
       | (x == ')')
       | (x == '(')
       | (x == ',')
+      | (x == '[')
+      | (x == ']')
+      | (x == '{')
+      | (x == '}')
   })(from)?;
   let s: String = String::from(s.fragment().to_owned());
   if reserved_symbols().contains(&s) {
@@ -126,7 +199,7 @@ pub fn parse_name(from: Span) -> IResult<Span, String, ParseError<Span>> {
     Err(Err::Error(ParseError::new(from, ParseErrorKind::InvalidSymbol(s))))
   }
   else {
-    Ok((i, s))
+    Ok((i, Name::from(s)))
   }
 }
 
@@ -171,6 +244,10 @@ pub fn is_valid_symbol_char(c: char) -> bool {
     && c != '('
     && c != ')'
     && c != ','
+    && c != '['
+    && c != ']'
+    && c != '{'
+    && c != '}'
     && !char::is_whitespace(c)
     && !char::is_control(c)
 }
@@ -184,42 +261,97 @@ pub fn is_valid_symbol_string(s: &String) -> bool {
   !zero_length && !invalid_chars
 }
 
+// The variable or reference `nam`, or the definition an operator `nam` names,
+// so that `(+)` is the function `+` applies, or, when it is none of these and
+// ends with `.fst` or `.snd`, the projection of what the rest of it names, so
+// that `p.fst.snd` is `snd (fst p)`
+fn resolve(
+  refs: &Refs,
+  ops: &Ops,
+  ctx: &Vector<Name>,
+  nam: &str,
+  pos: Option<Pos>,
+) -> Option<Term> {
+  if let Some(idx) = ctx.iter().position(|x| *x == nam) {
+    Some(Term::Var(pos, Name::from(nam), idx as u64))
+  }
+  else if let Some((d, a)) = refs.get(nam) {
+    Some(Term::Ref(pos, Name::from(nam), *d, *a))
+  }
+  else if let Some(op) = ops.get(nam) {
+    let (d, a) = refs.get(&op.name)?;
+    Some(Term::Ref(pos, op.name.clone(), *d, *a))
+  }
+  else if let Some(pair) = nam.strip_suffix(".fst") {
+    let pair = resolve(refs, ops, ctx, pair, pos)?;
+    Some(Term::Fst(pos, Box::new(pair)))
+  }
+  else if let Some(pair) = nam.strip_suffix(".snd") {
+    let pair = resolve(refs, ops, ctx, pair, pos)?;
+    Some(Term::Snd(pos, Box::new(pair)))
+  }
+  else {
+    None
+  }
+}
+
 pub fn parse_var(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (upto, nam) = context("local or global reference", parse_name)(from)?;
     let pos = Some(Pos::from_upto(from, upto));
-    match ctx.iter().enumerate().find(|(_, x)| **x == nam) {
-      Some((idx, _)) => Ok((upto, Term::Var(pos, nam.clone(), idx as u64))),
-      None => match refs.get(&nam) {
-        Some((d, a)) => Ok((upto, Term::Ref(pos, nam.clone(), *d, *a))),
-        None => Err(Err::Error(ParseError::new(
-          upto,
-          ParseErrorKind::UndefinedReference(nam.clone(), ctx.to_owned()),
-        ))),
-      },
+    match resolve(&refs, &ops, &ctx, &nam, pos) {
+      Some(term) => Ok((upto, term)),
+      None => Err(Err::Error(ParseError::new(
+        upto,
+        ParseErrorKind::UndefinedReference(nam.to_string(), ctx.to_owned()),
+      ))),
     }
   }
 }
 
+// The projections `.fst` and `.snd` after a parenthesized term, as in
+// `(f x).fst`, as whether each is the first
+pub fn parse_postfix_projs(
+  from: Span,
+) -> IResult<Span, Vec<bool>, ParseError<Span>> {
+  many0(alt((value(true, tag(".fst")), value(false, tag(".snd")))))(from)
+}
+
+// The token `tok`, expected as `label`, after something which can go on
+// instead, as `more`, such as the names of a lambda before its `=>`, so that
+// if neither does, the error says both were expected
+pub fn parse_token<'a>(
+  tok: &'static str,
+  label: &'static str,
+  more: &'static str,
+) -> impl Fn(Span<'a>) -> IResult<Span<'a>, Span<'a>, ParseError<Span<'a>>> {
+  move |i: Span<'a>| {
+    let expected = |e: ParseError<Span<'a>>| e.or_expected(&i, more);
+    context(label, tag(tok))(i).map_err(|e| e.map(expected))
+  }
+}
+
 pub fn parse_lam(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (i, _) = alt((tag("λ"), tag("lambda")))(from)?;
     let (i, _) = parse_space(i)?;
     let (i, ns) = separated_list1(multispace1, parse_name)(i)?;
     let (i, _) = parse_space(i)?;
-    let (i, _) = tag("=>")(i)?;
+    let (i, _) = parse_token("=>", "`=>`", "a name")(i)?;
     let (i, _) = parse_space(i)?;
     let mut ctx2 = ctx.clone();
     for n in ns.clone().into_iter() {
       ctx2.push_front(n);
     }
-    let (upto, bod) = parse_expression(refs.clone(), ctx2)(i)?;
+    let (upto, bod) = parse_expression(refs.clone(), ops.clone(), ctx2)(i)?;
     let pos = Some(Pos::from_upto(from, upto));
     let trm = ns
       .iter()
@@ -240,64 +372,94 @@ pub fn parse_uses(i: Span) -> IResult<Span, Uses, ParseError<Span>> {
 
 pub fn parse_binder_full(
   refs: Refs,
-  ctx: Vector<String>,
-) -> impl Fn(Span) -> IResult<Span, (Uses, Vec<String>, Term), ParseError<Span>>
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, (Uses, Vec<Name>, Term), ParseError<Span>>
 {
   move |i: Span| {
     let (i, _) = tag("(")(i)?;
     let (i, _) = parse_space(i)?;
     let (i, u) = parse_uses(i)?;
     let (i, ns) = many1(terminated(parse_name, parse_space))(i)?;
-    let (i, _) = tag(":")(i)?;
+    let (i, _) = parse_token(":", "`:`", "a name")(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, typ) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx.to_owned())(i)?;
+    let (i, _) = context("`)`", tag(")"))(i)?;
+    Ok((i, (u, ns, typ)))
+  }
+}
+
+// An implicit binder `{x y: A}` of a definition, whose arguments are left
+// out wherever the definition is used, for elaboration to fill in
+pub fn parse_binder_implicit(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, (Uses, Vec<Name>, Term), ParseError<Span>>
+{
+  move |i: Span| {
+    let (i, _) = tag("{")(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, u) = parse_uses(i)?;
+    let (i, ns) = many1(terminated(parse_name, parse_space))(i)?;
+    let (i, _) = parse_token(":", "`:`", "a name")(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, typ) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx.to_owned())(i)?;
     let (i, _) = parse_space(i)?;
-    let (i, typ) = parse_expression(refs.to_owned(), ctx.to_owned())(i)?;
-    let (i, _) = tag(")")(i)?;
+    let (i, _) = context("`}`", tag("}"))(i)?;
     Ok((i, (u, ns, typ)))
   }
 }
 
 pub fn parse_binder_short(
   refs: Refs,
-  ctx: Vector<String>,
-) -> impl Fn(Span) -> IResult<Span, (Uses, Vec<String>, Term), ParseError<Span>>
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, (Uses, Vec<Name>, Term), ParseError<Span>>
 {
   move |i: Span| {
-    map(parse_expression(refs.to_owned(), ctx.to_owned()), |t| {
-      (Uses::Many, vec![String::from("")], t)
+    map(parse_expression(refs.to_owned(), ops.to_owned(), ctx.to_owned()), |t| {
+      (Uses::Many, vec![Name::default()], t)
     })(i)
   }
 }
 
 pub fn parse_binder(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
   nam_opt: bool,
-) -> impl Fn(Span) -> IResult<Span, (Uses, Vec<String>, Term), ParseError<Span>>
+) -> impl Fn(Span) -> IResult<Span, (Uses, Vec<Name>, Term), ParseError<Span>>
 {
   move |i: Span| {
     if nam_opt {
       alt((
-        parse_binder_full(refs.clone(), ctx.clone()),
-        parse_binder_short(refs.to_owned(), ctx.to_owned()),
+        parse_binder_full(refs.clone(), ops.clone(), ctx.clone()),
+        parse_binder_short(refs.to_owned(), ops.to_owned(), ctx.to_owned()),
       ))(i)
     }
     else {
-      parse_binder_full(refs.to_owned(), ctx.to_owned())(i)
+      parse_binder_full(refs.to_owned(), ops.to_owned(), ctx.to_owned())(i)
     }
   }
 }
 
 pub fn parse_binders(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
   nam_opt: bool,
-) -> impl FnMut(Span) -> IResult<Span, Vec<(Uses, String, Term)>, ParseError<Span>>
+) -> impl FnMut(Span) -> IResult<Span, Vec<(Uses, Name, Term)>, ParseError<Span>>
 {
   move |mut i: Span| {
     let mut ctx = ctx.to_owned();
     let mut res = Vec::new();
 
-    match parse_binder(refs.to_owned(), ctx.to_owned(), nam_opt)(i.to_owned()) {
+    let binder =
+      parse_binder(refs.to_owned(), ops.to_owned(), ctx.to_owned(), nam_opt);
+    match binder(i.to_owned()) {
       Err(e) => return Err(e),
       Ok((i1, (u, ns, t))) => {
         for n in ns {
@@ -311,7 +473,7 @@ pub fn parse_binders(
     loop {
       match preceded(
         parse_space,
-        parse_binder(refs.to_owned(), ctx.to_owned(), nam_opt),
+        parse_binder(refs.to_owned(), ops.to_owned(), ctx.to_owned(), nam_opt),
       )(i)
       {
         Err(Err::Error(_)) => return Ok((i, res)),
@@ -328,22 +490,60 @@ pub fn parse_binders(
   }
 }
 
+// The binders of a definition, each with whether it is implicit, and each
+// followed by any space
+pub fn parse_def_binders(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(
+  Span,
+) -> IResult<Span, Vec<(bool, Uses, Name, Term)>, ParseError<Span>> {
+  move |mut i: Span| {
+    let mut ctx = ctx.to_owned();
+    let mut res = Vec::new();
+    loop {
+      let implicit =
+        parse_binder_implicit(refs.clone(), ops.clone(), ctx.clone());
+      let explicit = parse_binder_full(refs.clone(), ops.clone(), ctx.clone());
+      match terminated(
+        alt((map(implicit, |b| (true, b)), map(explicit, |b| (false, b)))),
+        parse_space,
+      )(i)
+      {
+        Err(Err::Error(_)) => return Ok((i, res)),
+        Err(e) => return Err(e),
+        Ok((i2, (imp, (u, ns, t)))) => {
+          for n in ns {
+            ctx.push_front(n.to_owned());
+            res.push((imp, u, n, t.clone()));
+          }
+          i = i2;
+        }
+      }
+    }
+  }
+}
+
 pub fn parse_all(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (i, _) = alt((tag("∀"), tag("forall")))(from)?;
     let (i, _) = parse_space(i)?;
-    let (i, bs) = parse_binders(refs.clone(), ctx.clone(), true)(i)?;
+    let (i, bs) =
+      parse_binders(refs.clone(), ops.clone(), ctx.clone(), true)(i)?;
     let (i, _) = parse_space(i)?;
-    let (i, _) = tag("->")(i)?;
+    let (i, _) = parse_token("->", "`->`", "a binder")(i)?;
     let (i, _) = parse_space(i)?;
     let mut ctx2 = ctx.clone();
     for (_, n, _) in bs.clone().iter() {
       ctx2.push_front(n.clone());
     }
-    let (upto, bod) = parse_expression(refs.to_owned(), ctx2)(i)?;
+    let (upto, bod) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx2)(i)?;
     let pos = Some(Pos::from_upto(from, upto));
     let trm = bs
       .into_iter()
@@ -353,17 +553,167 @@ pub fn parse_all(
   }
 }
 
+// A dependent pair type, binding each name in the types after it
+pub fn parse_sig(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, _) = alt((tag("Σ"), tag("sigma")))(from)?;
+    let (i, _) = parse_space(i)?;
+    let (i, bs) =
+      parse_binders(refs.clone(), ops.clone(), ctx.clone(), true)(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = parse_token("->", "`->`", "a binder")(i)?;
+    let (i, _) = parse_space(i)?;
+    let mut ctx2 = ctx.clone();
+    for (_, n, _) in bs.clone().iter() {
+      ctx2.push_front(n.clone());
+    }
+    let (upto, bod) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx2)(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let trm = bs
+      .into_iter()
+      .rev()
+      .fold(bod, |acc, (_, n, t)| Term::Sig(pos, n, Box::new((t, acc))));
+    Ok((upto, trm))
+  }
+}
+
+// A parenthesized expression, or a pair `(a, b)`. Pairs of more than two
+// terms nest to the right: `(a, b, c)` is `(a, (b, c))`. The first term may
+// be ascribed a type, as in `(e : T)`, which annotates it as `e :: T` does.
+pub fn parse_parens(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, _) = preceded(tag("("), parse_space)(from)?;
+    let (i, trm) = context(
+      "expression",
+      parse_expression(refs.clone(), ops.clone(), ctx.clone()),
+    )(i)?;
+    let (i, typ) = opt(preceded(
+      tuple((parse_space, tag(":"), parse_space)),
+      context(
+        "type ascription",
+        parse_expression(refs.clone(), ops.clone(), ctx.clone()),
+      ),
+    ))(i)?;
+    let trm = match typ {
+      Some(typ) => {
+        let pos = Some(Pos::from_upto(from, i));
+        Term::Ann(pos, Box::new((typ, trm)))
+      }
+      None => trm,
+    };
+    let (i, mut trms) = many0(preceded(
+      preceded(parse_space, tag(",")),
+      parse_expression(refs.clone(), ops.clone(), ctx.clone()),
+    ))(i)?;
+    let (i, _) = context(
+      "close parenthesis ')' of an expression",
+      preceded(parse_space, tag(")")),
+    )(i)?;
+    let pos = Some(Pos::from_upto(from, i));
+    let trm = match trms.pop() {
+      Some(last) => {
+        let snd = trms
+          .into_iter()
+          .rev()
+          .fold(last, |acc, trm| Term::Par(pos, Box::new((trm, acc))));
+        Term::Par(pos, Box::new((trm, snd)))
+      }
+      None => trm,
+    };
+    let (upto, projs) = parse_postfix_projs(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let trm = projs.into_iter().fold(trm, |trm, fst| {
+      if fst {
+        Term::Fst(pos, Box::new(trm))
+      }
+      else {
+        Term::Snd(pos, Box::new(trm))
+      }
+    });
+    Ok((upto, trm))
+  }
+}
+
+// The first or second projection of a pair
+pub fn parse_proj(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, fst) =
+      alt((value(true, tag("fst")), value(false, tag("snd"))))(from)?;
+    let (i, _) = parse_space1(i)?;
+    let (upto, bod) =
+      parse_term(refs.to_owned(), ops.to_owned(), ctx.clone())(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    if fst {
+      Ok((upto, Term::Fst(pos, Box::new(bod))))
+    }
+    else {
+      Ok((upto, Term::Snd(pos, Box::new(bod))))
+    }
+  }
+}
+
+// A universe, `Type` being the lowest and `Type@n` the one at level `n`. The
+// level is joined to `Type`, so that `f Type 1` applies `f` to two arguments.
 pub fn parse_type() -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
-    let (upto, _) = tag("Type")(from)?;
+    let (i, _) = tag("Type")(from)?;
+    let (upto, lvl) = opt(preceded(tag("@"), digit1))(i)?;
+    let lvl = match lvl {
+      Some(lvl) => lvl.fragment().parse::<u64>().map_err(|e| {
+        Err::Error(ParseError::new(lvl, ParseErrorKind::ParseIntErr(e)))
+      })?,
+      None => 0,
+    };
+    let pos = Some(Pos::from_upto(from, upto));
+    Ok((upto, Term::Typ(pos, lvl)))
+  }
+}
+
+// The universe of propositions, unless `Prop` only begins a longer name
+pub fn parse_prop() -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, _) = tag("Prop")(from)?;
+    let (upto, _) = parse_builtin_symbol_end()(i)?;
     let pos = Some(Pos::from_upto(from, upto));
-    Ok((upto, Term::Typ(pos)))
+    Ok((upto, Term::Prp(pos)))
+  }
+}
+
+// A placeholder `_` for a term to be inferred, or a named hole `?name`
+pub fn parse_hole() -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (upto, nam) = parse_name(from)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    if nam == "_" {
+      Ok((upto, Term::Hol(pos, None)))
+    }
+    else if nam.len() > 1 && nam.starts_with('?') {
+      Ok((upto, Term::Hol(pos, Some(String::from(&nam[1..])))))
+    }
+    else {
+      let kind = ParseErrorKind::Nom(ErrorKind::Tag);
+      Err(Err::Error(ParseError::new(from, kind)))
+    }
   }
 }
 
 pub fn parse_self(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (i, _) = nom::character::complete::char('@')(from)?;
@@ -371,7 +721,8 @@ pub fn parse_self(
     let (i, _) = parse_space(i)?;
     let mut ctx2 = ctx.clone();
     ctx2.push_front(n.clone());
-    let (upto, bod) = parse_expression(refs.to_owned(), ctx2)(i)?;
+    let (upto, bod) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx2)(i)?;
     let pos = Some(Pos::from_upto(from, upto));
     Ok((upto, Term::Slf(pos, n, Box::new(bod))))
   }
@@ -379,106 +730,442 @@ pub fn parse_self(
 
 pub fn parse_case(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (i, _) = tag("case")(from)?;
     let (i, _) = parse_space(i)?;
-    let (upto, bod) = parse_expression(refs.to_owned(), ctx.clone())(i)?;
+    let (upto, bod) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx.clone())(i)?;
     let pos = Some(Pos::from_upto(from, upto));
     Ok((upto, Term::Cse(pos, Box::new(bod))))
   }
 }
 
+// A pattern: a constructor applied to patterns of its fields, or a variable
+// or `_`, which matches any value. A name is a constructor when it names one
+// of a datatype in scope.
+pub fn parse_pattern(
+  refs: Refs,
+) -> impl Fn(Span) -> IResult<Span, Pattern, ParseError<Span>> {
+  move |from: Span| {
+    let (i, nam) = parse_name(from)?;
+    match Family::of_ctor(&refs, &nam) {
+      Some((family, idx)) => {
+        let (upto, args) =
+          many0(preceded(parse_space, parse_pattern_atom(refs.clone())))(i)?;
+        ctor_pattern(from, upto, family, idx, args)
+      }
+      None => {
+        let pos = Some(Pos::from_upto(from, i));
+        Ok((i, Pattern::Var(pos, nam)))
+      }
+    }
+  }
+}
+
+// A pattern of a field: a parenthesized pattern, or a name alone
+pub fn parse_pattern_atom(
+  refs: Refs,
+) -> impl Fn(Span) -> IResult<Span, Pattern, ParseError<Span>> {
+  move |from: Span| {
+    let open: IResult<Span, Span, ParseError<Span>> = tag("(")(from);
+    if let Ok((i, _)) = open {
+      let (i, _) = parse_space(i)?;
+      let (i, pat) = parse_pattern(refs.clone())(i)?;
+      let (i, _) = parse_space(i)?;
+      let (upto, _) =
+        context("close parenthesis ')' of a pattern", tag(")"))(i)?;
+      return Ok((upto, pat));
+    }
+    let (upto, nam) = parse_name(from)?;
+    match Family::of_ctor(&refs, &nam) {
+      Some((family, idx)) => ctor_pattern(from, upto, family, idx, vec![]),
+      None => Ok((upto, Pattern::Var(Some(Pos::from_upto(from, upto)), nam))),
+    }
+  }
+}
+
+// The constructor with index `idx` of `family`, applied to `args`, which
+// must be as many as its fields
+fn ctor_pattern<'a>(
+  from: Span<'a>,
+  upto: Span<'a>,
+  family: Family,
+  idx: usize,
+  args: Vec<Pattern>,
+) -> IResult<Span<'a>, Pattern, ParseError<Span<'a>>> {
+  let pos = Some(Pos::from_upto(from, upto));
+  let (nam, fields) = &family.ctors[idx];
+  if fields.len() != args.len() {
+    let (nam, arity) = (nam.to_string(), fields.len());
+    let e = MatchError::CtorArity(pos, nam, arity, args.len());
+    let kind = ParseErrorKind::MalformedMatch(e);
+    return Err(Err::Failure(ParseError::new(from, kind)));
+  }
+  Ok((upto, Pattern::Ctor(pos, family, idx, args)))
+}
+
+// A clause of a match, whose body is in the scope of its pattern's variables
+pub fn parse_clause(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Clause, ParseError<Span>> {
+  move |from: Span| {
+    let (i, pattern) = parse_pattern(refs.clone())(from)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = parse_token("=>", "`=>`", "a pattern")(i)?;
+    let (i, _) = parse_space(i)?;
+    let mut ctx2 = ctx.clone();
+    for (_, nam) in pattern.vars() {
+      ctx2.push_front(nam);
+    }
+    let (upto, body) = parse_expression(refs.clone(), ops.clone(), ctx2)(i)?;
+    Ok((upto, Clause { pattern, body }))
+  }
+}
+
+// A pattern match on values of datatypes, compiled into their eliminations.
+// The return type, which does not depend on the value matched, is given:
+//
+//   match xs return #Natural {
+//     List.nil => 0,
+//     List.cons x List.nil => 1,
+//     _ => 2,
+//   }
+pub fn parse_match(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, _) = terminated(tag("match"), parse_space1)(from)?;
+    let (i, scrut) =
+      parse_expression(refs.clone(), ops.clone(), ctx.clone())(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = context("`return`", tag("return"))(i)?;
+    let (i, _) = parse_space1(i)?;
+    let (i, typ) = parse_expression(refs.clone(), ops.clone(), ctx.clone())(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = context("`{`", tag("{"))(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, clauses) = separated_list0(
+      terminated(tag(","), parse_space),
+      terminated(
+        parse_clause(refs.clone(), ops.clone(), ctx.clone()),
+        parse_space,
+      ),
+    )(i)?;
+    let (i, _) = opt(terminated(tag(","), parse_space))(i)?;
+    let (upto, _) = context("`}`", tag("}"))(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    match compile(pos, scrut, typ, &clauses) {
+      Ok(term) => Ok((upto, term)),
+      Err(e) => {
+        let at = locate(from, upto, e.pos());
+        let kind = ParseErrorKind::MalformedMatch(e);
+        Err(Err::Failure(ParseError::new(at, kind)))
+      }
+    }
+  }
+}
+
+// A list, `[a, b, c]`, which is read as the application of the constructors
+// `List.cons` and `List.nil` in scope, `List.cons _ a (List.cons _ b (List.cons
+// _ c (List.nil _)))`, whose element type is left to elaboration
+pub fn parse_list(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, _) = terminated(tag("["), parse_space)(from)?;
+    let (i, elems) = separated_list0(
+      terminated(tag(","), parse_space),
+      terminated(
+        parse_expression(refs.clone(), ops.clone(), ctx.clone()),
+        parse_space,
+      ),
+    )(i)?;
+    let (i, _) = opt(terminated(tag(","), parse_space))(i)?;
+    let (upto, _) = context("`]`", tag("]"))(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let ctor = |nam: &str| match refs.get(nam) {
+      Some((d, a)) => Ok(Term::Ref(pos, Name::from(nam), *d, *a)),
+      None => {
+        let nam = nam.to_owned();
+        let kind = ParseErrorKind::UndefinedReference(nam, ctx.clone());
+        Err(Err::Failure(ParseError::new(from, kind)))
+      }
+    };
+    let app = |fun, arg| Term::App(pos, Box::new((fun, arg)));
+    let (nil, cons) = (ctor("List.nil")?, ctor("List.cons")?);
+    let list = elems.into_iter().rev().fold(
+      app(nil, Term::Hol(pos, None)),
+      |list, elem| {
+        app(app(app(cons.clone(), Term::Hol(pos, None)), elem), list)
+      },
+    );
+    Ok((upto, list))
+  }
+}
+
+// A conditional, `if c then x else y`, which is a match on `c` with the
+// constructors `Bool.true` and `Bool.false` in scope. The type of its
+// branches is left to elaboration, bound as a type before the match:
+//
+//   let A: Type = _; match c return A { Bool.true => x, Bool.false => y }
+//
+// As there are no metavariables for levels, the branches must be of a type
+// in the lowest universe.
+pub fn parse_if(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, _) = terminated(tag("if"), parse_space1)(from)?;
+    let (i, cond) =
+      parse_expression(refs.clone(), ops.clone(), ctx.clone())(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = context("`then`", terminated(tag("then"), parse_space1))(i)?;
+    let (i, yes) = parse_expression(refs.clone(), ops.clone(), ctx.clone())(i)?;
+    let (i, _) = parse_space(i)?;
+    let (i, _) = context("`else`", terminated(tag("else"), parse_space1))(i)?;
+    let (upto, no) =
+      parse_expression(refs.clone(), ops.clone(), ctx.clone())(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let clause = |ctor: &str, body: Term| match Family::of_ctor(&refs, ctor) {
+      Some((family, idx)) if family.ctors[idx].1.is_empty() => {
+        let pattern = Pattern::Ctor(pos, family, idx, Vec::new());
+        Ok(Clause { pattern, body: body.shift(1, 0) })
+      }
+      _ => {
+        let ctor = ctor.to_owned();
+        let kind = ParseErrorKind::UndefinedReference(ctor, ctx.clone());
+        Err(Err::Failure(ParseError::new(from, kind)))
+      }
+    };
+    let clauses = [clause("Bool.true", yes)?, clause("Bool.false", no)?];
+    let typ = Term::Var(pos, Name::from("A"), 0);
+    match compile(pos, cond.shift(1, 0), typ, &clauses) {
+      Ok(term) => {
+        let terms = Box::new((Term::Typ(pos, 0), Term::Hol(pos, None), term));
+        Ok((upto, Term::Let(pos, false, Uses::Many, Name::from("A"), terms)))
+      }
+      Err(e) => {
+        let kind = ParseErrorKind::MalformedMatch(e);
+        Err(Err::Failure(ParseError::new(from, kind)))
+      }
+    }
+  }
+}
+
+// A `do` block, whose statements, separated by `;`, are sequenced by the
+// `bind` in scope. A statement `x <- m` binds the result of `m` in those
+// after it, and `return x` is `pure x`, with the `pure` in scope:
+//
+//   do { x <- get; put (f x); return x }
+//
+// is `bind get (λ x => bind (put (f x)) (λ _ => pure x))`. The last statement
+// is the result of the block, and can't bind a name.
+pub fn parse_do(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (i, _) = terminated(tag("do"), parse_space)(from)?;
+    let (mut i, _) = tag("{")(i)?;
+    let mut ctx = ctx.clone();
+    let mut stmts = Vec::new();
+    // The `bind` or `pure` in scope of a statement
+    let monad = |ctx: &Vector<Name>, nam: &str, pos| {
+      resolve(&refs, &ops, ctx, nam, pos).ok_or_else(|| {
+        let nam = nam.to_owned();
+        let kind = ParseErrorKind::UndefinedReference(nam, ctx.clone());
+        Err::Failure(ParseError::new(from, kind))
+      })
+    };
+    let (upto, last) = loop {
+      let (i2, _) = parse_space(i)?;
+      let arrow = tuple((parse_space, tag("<-"), parse_space));
+      let (i2, bound) = opt(terminated(parse_name, arrow))(i2)?;
+      let (i2, ret) = opt(terminated(tag("return"), parse_space1))(i2)?;
+      let (upto, term) =
+        parse_expression(refs.clone(), ops.clone(), ctx.clone())(i2)?;
+      let pos = Some(Pos::from_upto(i2, upto));
+      let term = match ret {
+        Some(_) => Term::App(pos, Box::new((monad(&ctx, "pure", pos)?, term))),
+        None => term,
+      };
+      let (upto, _) = parse_space(upto)?;
+      if bound.is_none() {
+        let end: IResult<Span, Span, ParseError<Span>> = tag("}")(upto);
+        if let Ok((upto, _)) = end {
+          break (upto, term);
+        }
+      }
+      let (i2, _) = context("`;`", tag(";"))(upto)?;
+      let bound = bound.unwrap_or_else(|| Name::from("_"));
+      stmts.push((pos, monad(&ctx, "bind", pos)?, bound.clone(), term));
+      ctx.push_front(bound);
+      i = i2;
+    };
+    let term = stmts.into_iter().rev().fold(last, |rest, stmt| {
+      let (pos, bind, bound, term) = stmt;
+      let rest = Term::Lam(pos, bound, Box::new(rest));
+      let bind = Term::App(pos, Box::new((bind, term)));
+      Term::App(pos, Box::new((bind, rest)))
+    });
+    Ok((upto, term))
+  }
+}
+
 pub fn parse_data(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (i, _) = tag("data")(from)?;
     let (i, _) = parse_space(i)?;
-    let (upto, bod) = parse_expression(refs.to_owned(), ctx.clone())(i)?;
+    let (upto, bod) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx.clone())(i)?;
     let pos = Some(Pos::from_upto(from, upto));
     Ok((upto, Term::Dat(pos, Box::new(bod))))
   }
 }
 
+// A definition's name, binders, type and term, with whether each of its
+// leading binders is implicit, up to the last implicit one
 pub fn parse_typed_definition(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
   rec: bool,
   shadow: bool,
-) -> impl Fn(Span) -> IResult<Span, (String, Term, Term), ParseError<Span>> {
+) -> impl Fn(
+  Span,
+) -> IResult<Span, (Name, Term, Term, Vec<bool>), ParseError<Span>> {
   move |from: Span| {
     let (i, nam) = parse_name(from)?;
     if refs.get(&nam).is_some() && !shadow {
       Err(Err::Error(ParseError::new(
         from,
-        ParseErrorKind::TopLevelRedefinition(nam.clone()),
+        ParseErrorKind::TopLevelRedefinition(nam.to_string()),
       )))
     }
     else {
       let (i, _) = parse_space(i)?;
-      let (i, bs) = alt((
-        terminated(
-          parse_binders(refs.clone(), ctx.clone(), false),
-          parse_space,
-        ),
-        success(Vec::new()),
-      ))(i)?;
-      let (i, _) = tag(":")(i)?;
+      let (i, bs) =
+        parse_def_binders(refs.clone(), ops.clone(), ctx.clone())(i)?;
+      let (i, _) = parse_token(":", "`:`", "a binder")(i)?;
       let (i, _) = parse_space(i)?;
       let mut type_ctx = ctx.clone();
-      for (_, n, _) in bs.clone().iter() {
+      for (_, _, n, _) in bs.clone().iter() {
         type_ctx.push_front(n.clone());
       }
-      let (i, typ) = parse_expression(refs.clone(), type_ctx)(i)?;
+      let (i, typ) = parse_expression(refs.clone(), ops.clone(), type_ctx)(i)?;
       let mut term_ctx = ctx.to_owned();
       if rec {
         term_ctx.push_front(nam.clone());
       };
-      for (_, n, _) in bs.clone().iter() {
+      for (_, _, n, _) in bs.clone().iter() {
         term_ctx.push_front(n.clone());
       }
       let (i, _) = parse_space(i)?;
-      let (i, _) = tag("=")(i)?;
+      let (i, _) = context("`=`", tag("="))(i)?;
       let (i, _) = parse_space(i)?;
-      let (upto, trm) = parse_expression(refs.clone(), term_ctx)(i)?;
+      let (upto, trm) =
+        parse_expression(refs.clone(), ops.clone(), term_ctx)(i)?;
       let pos = Some(Pos::from_upto(from, upto));
-      let trm = bs
-        .iter()
-        .rev()
-        .fold(trm, |acc, (_, n, _)| Term::Lam(pos, n.clone(), Box::new(acc)));
-      let typ = bs
-        .into_iter()
-        .rev()
-        .fold(typ, |acc, (u, n, t)| Term::All(pos, u, n, Box::new((t, acc))));
-      Ok((upto, (nam, trm, typ)))
+      let mut implicits: Vec<bool> = bs.iter().map(|b| b.0).collect();
+      while implicits.last() == Some(&false) {
+        implicits.pop();
+      }
+      let trm = bs.iter().rev().fold(trm, |acc, (_, _, n, _)| {
+        Term::Lam(pos, n.clone(), Box::new(acc))
+      });
+      let typ = bs.into_iter().rev().fold(typ, |acc, (_, u, n, t)| {
+        Term::All(pos, u, n, Box::new((t, acc)))
+      });
+      Ok((upto, (nam, trm, typ, implicits)))
     }
   }
 }
 
+// A definition whose type is declared apart, by a signature, as its name,
+// the names of its parameters, and its term, in which it is in scope when
+// `rec`
+pub fn parse_untyped_definition(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+  rec: bool,
+  shadow: bool,
+) -> impl Fn(Span) -> IResult<Span, (Name, Vec<Name>, Term), ParseError<Span>>
+{
+  move |from: Span| {
+    let (i, nam) = parse_name(from)?;
+    if refs.get(&nam).is_some() && !shadow {
+      let kind = ParseErrorKind::TopLevelRedefinition(nam.to_string());
+      return Err(Err::Error(ParseError::new(from, kind)));
+    }
+    let (i, _) = parse_space(i)?;
+    let (i, params) = many0(terminated(parse_name, parse_space))(i)?;
+    let (i, _) = parse_token("=", "`=`", "a parameter")(i)?;
+    let (i, _) = parse_space(i)?;
+    let mut ctx = ctx.clone();
+    if rec {
+      ctx.push_front(nam.clone());
+    }
+    for param in params.iter() {
+      ctx.push_front(param.clone());
+    }
+    let (upto, bod) = parse_expression(refs.clone(), ops.clone(), ctx)(i)?;
+    let pos = Some(Pos::from_upto(from, upto));
+    let term = params
+      .iter()
+      .rev()
+      .fold(bod, |acc, param| Term::Lam(pos, param.clone(), Box::new(acc)));
+    Ok((upto, (nam, params, term)))
+  }
+}
+
+// A local definition, whose binders can't be implicit, as elaboration only
+// fills in the arguments of global definitions
 pub fn parse_let(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (i, rec) =
       alt((value(true, tag("letrec")), value(false, tag("let"))))(from)?;
     let (i, _) = parse_space(i)?;
     let (i, uses) = parse_uses(i)?;
-    let (i, (nam, exp, typ)) =
-      parse_typed_definition(refs.clone(), ctx.clone(), rec, true)(i)?;
-    let (i, _) = tag(";")(i)?;
+    let (i, (nam, exp, typ, implicits)) = parse_typed_definition(
+      refs.clone(),
+      ops.clone(),
+      ctx.clone(),
+      rec,
+      true,
+    )(i)?;
+    if !implicits.is_empty() {
+      let kind = ParseErrorKind::LocalImplicit(nam.to_string());
+      return Err(Err::Failure(ParseError::new(from, kind)));
+    }
+    let (i, _) = context("`;`", tag(";"))(i)?;
     let (i, _) = parse_space(i)?;
     let mut ctx2 = ctx.clone();
     ctx2.push_front(nam.clone());
-    let (upto, bod) = parse_expression(refs.to_owned(), ctx2)(i)?;
+    let (upto, bod) =
+      parse_expression(refs.to_owned(), ops.to_owned(), ctx2)(i)?;
     let pos = Some(Pos::from_upto(from, upto));
-    Ok((
-      upto,
-      Term::Let(pos, rec, uses, nam.clone(), Box::new((typ, exp, bod))),
-    ))
+    Ok((upto, Term::Let(pos, rec, uses, nam, Box::new((typ, exp, bod)))))
   }
 }
 
@@ -493,6 +1180,8 @@ pub fn parse_builtin_symbol_end()
       peek(value((), tag(";"))),
       peek(value((), tag(":"))),
       peek(value((), tag(","))),
+      peek(value((), tag("]"))),
+      peek(value((), tag("}"))),
     ))(from)
   }
 }
@@ -505,6 +1194,7 @@ pub fn parse_lty() -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
       value(LitType::BitString, tag("#BitString")),
       value(LitType::Text, tag("#Text")),
       value(LitType::Char, tag("#Char")),
+      value(LitType::Float, tag("#Float")),
     ))(from)?;
     let (upto, _) = throw_err(parse_builtin_symbol_end()(i), |_| {
       ParseError::new(
@@ -530,8 +1220,15 @@ pub fn parse_lty() -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
 
 pub fn parse_lit() -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
-    let (i, lit) =
-      alt((parse_bits, parse_text, parse_char, parse_nat, parse_int))(from)?;
+    let (i, lit) = alt((
+      parse_bits,
+      parse_raw_text,
+      parse_text,
+      parse_char,
+      parse_float,
+      parse_nat,
+      parse_int,
+    ))(from)?;
     let (upto, _) = throw_err(parse_builtin_symbol_end()(i), |_| {
       ParseError::new(
         i,
@@ -584,14 +1281,18 @@ pub fn parse_opr() -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
 
 pub fn parse_expression(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
-    let (i, trm) = parse_apps(refs.clone(), ctx.clone())(from)?;
+    let (i, trm) =
+      parse_infix(refs.clone(), ops.clone(), ctx.clone(), 0)(from)?;
     let (i, has_ann) = opt(tag("::"))(i)?;
     if let Some(_) = has_ann {
-      let (i, typ) =
-        context("type annotation", parse_apps(refs.clone(), ctx.clone()))(i)?;
+      let (i, typ) = context(
+        "type annotation",
+        parse_infix(refs.clone(), ops.clone(), ctx.clone(), 0),
+      )(i)?;
       let pos = Some(Pos::from_upto(from, i));
       Ok((i, Term::Ann(pos, Box::new((typ, trm)))))
     }
@@ -601,15 +1302,108 @@ pub fn parse_expression(
   }
 }
 
+// An operator in scope, with its symbol
+pub fn parse_operator(
+  ops: &Ops,
+) -> impl Fn(Span) -> IResult<Span, (Name, Operator), ParseError<Span>> + '_ {
+  move |from: Span| {
+    let (upto, nam) = parse_name(from)?;
+    match ops.get(&nam) {
+      Some(op) => Ok((upto, (nam, op.clone()))),
+      None => {
+        let kind = ParseErrorKind::Nom(ErrorKind::Tag);
+        Err(Err::Error(ParseError::new(from, kind)))
+      }
+    }
+  }
+}
+
+// Applications joined by infix operators of precedence at least `min`, read
+// by precedence climbing: the right operand of an operator takes in those of
+// higher precedence, and, if it is right associative, those of the same, so
+// that with `+` at 6 and `*` at 7, `a + b * c + d` is `(a + (b * c)) + d`
+pub fn parse_infix(
+  refs: Refs,
+  ops: Ops,
+  ctx: Vector<Name>,
+  min: u8,
+) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
+  move |from: Span| {
+    let (mut i, mut lhs) =
+      parse_apps(refs.clone(), ops.clone(), ctx.clone())(from)?;
+    // The last operator applied, which, if either is non-associative, cannot
+    // be followed by another of the same precedence
+    let mut last: Option<(Name, Operator)> = None;
+    loop {
+      let (nam, op, i2) = match parse_operator(&ops)(i) {
+        Ok((i2, (nam, op))) if op.prec >= min => (nam, op, i2),
+        _ => return Ok((i, lhs)),
+      };
+      if let Some((last_nam, last_op)) = last {
+        if last_op.prec == op.prec
+          && (last_op.assoc == Assoc::Non || op.assoc == Assoc::Non)
+        {
+          let non = if op.assoc == Assoc::Non { nam } else { last_nam };
+          let kind = ParseErrorKind::NonAssociative(non.to_string());
+          return Err(Err::Failure(ParseError::new(i, kind)));
+        }
+      }
+      let fun = match refs.get(&op.name) {
+        Some((d, a)) => {
+          Term::Ref(Some(Pos::from_upto(i, i2)), op.name.clone(), *d, *a)
+        }
+        None => {
+          let kind = ParseErrorKind::UndefinedReference(
+            op.name.to_string(),
+            ctx.to_owned(),
+          );
+          return Err(Err::Failure(ParseError::new(i, kind)));
+        }
+      };
+      let next = if op.assoc == Assoc::Right { op.prec } else { op.prec + 1 };
+      let (i2, rhs) =
+        parse_infix(refs.clone(), ops.clone(), ctx.clone(), next)(i2)?;
+      let pos = Some(Pos::from_upto(from, i2));
+      let app = Term::App(pos, Box::new((fun, lhs)));
+      lhs = Term::App(pos, Box::new((app, rhs)));
+      last = Some((nam, op));
+      i = i2;
+    }
+  }
+}
+
 pub fn parse_app_end(i: Span) -> IResult<Span, (), ParseError<Span>> {
   let (i, _) = alt((
-    peek(tag("def")),
-    peek(tag("open")),
-    peek(tag("::")),
+    // The start of the next declaration
+    alt((
+      peek(tag("def")),
+      peek(terminated(tag("private"), multispace1)),
+      peek(terminated(tag("partial"), multispace1)),
+      peek(terminated(tag("opaque"), multispace1)),
+      peek(terminated(tag("reducible"), multispace1)),
+      peek(terminated(tag("data"), multispace1)),
+      peek(tag("open")),
+      peek(terminated(tag("test"), multispace1)),
+      peek(tag("#check")),
+      peek(terminated(
+        alt((tag("infixl"), tag("infixr"), tag("infix"))),
+        multispace1,
+      )),
+      peek(terminated(tag("sig"), multispace1)),
+      peek(terminated(tag("and"), multispace1)),
+    )),
+    peek(terminated(tag("return"), multispace1)),
+    peek(terminated(tag("then"), multispace1)),
+    peek(terminated(tag("else"), multispace1)),
+    peek(tag(":")),
     peek(tag("=")),
     peek(tag("->")),
     peek(tag(";")),
     peek(tag(")")),
+    peek(tag(",")),
+    peek(tag("]")),
+    peek(tag("{")),
+    peek(tag("}")),
     peek(eof),
   ))(i)?;
   Ok((i, ()))
@@ -617,28 +1411,29 @@ pub fn parse_app_end(i: Span) -> IResult<Span, (), ParseError<Span>> {
 
 pub fn parse_apps(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |from: Span| {
     let (i2, _) = parse_space(from)?;
-    let (i2, fun) = parse_term(refs.clone(), ctx.clone())(i2)?;
+    let (i2, fun) = parse_term(refs.clone(), ops.clone(), ctx.clone())(i2)?;
     let mut i = i2;
     let mut args = Vec::new();
     loop {
       let (i2, _) = parse_space(i)?;
-      match parse_app_end(i2) {
-        Ok((..)) => {
-          let pos = Some(Pos::from_upto(from, i2));
-          let trm = args
-            .into_iter()
-            .fold(fun, |acc, arg| Term::App(pos, Box::new((acc, arg))));
-          return Ok((i2, trm));
-        }
-        _ => {
-          let (i2, arg) = parse_term(refs.clone(), ctx.clone())(i2)?;
-          args.push(arg);
-          i = i2
-        }
+      // An operator ends the application, which is its left operand
+      if parse_app_end(i2).is_ok() || parse_operator(&ops)(i2).is_ok() {
+        let pos = Some(Pos::from_upto(from, i2));
+        let trm = args
+          .into_iter()
+          .fold(fun, |acc, arg| Term::App(pos, Box::new((acc, arg))));
+        return Ok((i2, trm));
+      }
+      else {
+        let (i2, arg) =
+          parse_term(refs.clone(), ops.clone(), ctx.clone())(i2)?;
+        args.push(arg);
+        i = i2
       }
     }
   }
@@ -646,105 +1441,472 @@ pub fn parse_apps(
 
 pub fn parse_term(
   refs: Refs,
-  ctx: Vector<String>,
+  ops: Ops,
+  ctx: Vector<Name>,
 ) -> impl Fn(Span) -> IResult<Span, Term, ParseError<Span>> {
   move |i: Span| {
     context(
       "term",
       alt((
-        delimited(
-          preceded(tag("("), parse_space),
-          context("expression", parse_expression(refs.clone(), ctx.clone())),
-          context(
-            "close parenthesis ')' of an expression",
-            preceded(parse_space, tag(")")),
-          ),
-        ),
-        parse_self(refs.clone(), ctx.clone()),
-        parse_data(refs.clone(), ctx.clone()),
-        parse_case(refs.clone(), ctx.clone()),
-        parse_all(refs.clone(), ctx.clone()),
-        parse_lam(refs.clone(), ctx.clone()),
-        parse_let(refs.clone(), ctx.clone()),
+        parse_parens(refs.clone(), ops.clone(), ctx.clone()),
+        parse_self(refs.clone(), ops.clone(), ctx.clone()),
+        parse_data(refs.clone(), ops.clone(), ctx.clone()),
+        parse_case(refs.clone(), ops.clone(), ctx.clone()),
+        parse_match(refs.clone(), ops.clone(), ctx.clone()),
+        parse_list(refs.clone(), ops.clone(), ctx.clone()),
+        parse_if(refs.clone(), ops.clone(), ctx.clone()),
+        parse_all(refs.clone(), ops.clone(), ctx.clone()),
+        parse_sig(refs.clone(), ops.clone(), ctx.clone()),
+        parse_proj(refs.clone(), ops.clone(), ctx.clone()),
+        parse_lam(refs.clone(), ops.clone(), ctx.clone()),
+        parse_let(refs.clone(), ops.clone(), ctx.clone()),
+        parse_do(refs.clone(), ops.clone(), ctx.clone()),
         parse_type(),
+        parse_prop(),
         parse_lty(),
         parse_opr(),
         parse_lit(),
-        parse_var(refs.to_owned(), ctx.to_owned()),
+        parse_hole(),
+        parse_var(refs.to_owned(), ops.to_owned(), ctx.to_owned()),
       )),
     )(i)
   }
 }
 pub fn parse(i: &str) -> IResult<Span, Term, ParseError<Span>> {
-  parse_expression(HashMap::new(), Vector::new())(Span::new(i))
+  parse_expression(HashMap::new(), Ops::new(), Vector::new())(Span::new(i))
 }
 
 #[cfg(test)]
 pub mod tests {
   use super::*;
-  use crate::term::tests::test_refs;
+  use crate::{
+    core::{
+      check::infer_term,
+      datatype::{
+        tests::datatype,
+        Datatype,
+      },
+      elab::elab_term,
+      nbe,
+    },
+    package::Declaration,
+    parse::package::store_def,
+    term::{
+      tests::test_refs,
+      Def,
+      Defs,
+      Literal,
+    },
+  };
+  use num_bigint::{
+    BigInt,
+    BigUint,
+  };
 
   #[test]
   fn test_apps() {
-    let res = parse_apps(HashMap::new(), Vector::new())(Span::new("0d1"));
+    let apps = parse_apps(HashMap::new(), Ops::new(), Vector::new());
+    let res = apps(Span::new("0d1"));
     println!("res: {:?}", res);
     assert!(res.is_ok());
-    let res = parse_apps(HashMap::new(), Vector::new())(Span::new("0d1 0d1"));
+    let res = apps(Span::new("0d1 0d1"));
     println!("res: {:?}", res);
     assert!(res.is_ok());
-    let res =
-      parse_apps(HashMap::new(), Vector::new())(Span::new("0d1 0d1 def"));
+    let res = apps(Span::new("0d1 0d1 def"));
     println!("res: {:?}", res);
     assert!(res.is_ok());
+    // A name which only begins with a keyword is an argument
+    let names = vec!["infixes", "reducibleX", "opaqueX", "partially"];
+    let ctx: Vector<Name> =
+      names.into_iter().chain(vec!["dataset", "f"]).map(Name::from).collect();
+    let apps = parse_apps(HashMap::new(), Ops::new(), ctx);
+    let args = "dataset partially opaqueX reducibleX infixes";
+    let (_, res) = apps(Span::new(&format!("f {}", args))).unwrap();
+    assert_eq!(format!("{}", res), format!("f {}", args));
+  }
+
+  #[test]
+  fn test_universes() {
+    let parse_typ = |i: &'static str| {
+      parse_expression(HashMap::new(), Ops::new(), Vector::new())(Span::new(i))
+        .map(|(_, term)| term)
+    };
+    assert_eq!(parse_typ("Type").unwrap(), Term::Typ(None, 0));
+    assert_eq!(parse_typ("Type@2").unwrap(), Term::Typ(None, 2));
+    let res = parse_typ("Type :: Type@1").unwrap();
+    let (lo, hi) = (Term::Typ(None, 0), Term::Typ(None, 1));
+    assert_eq!(res, Term::Ann(None, Box::new((hi, lo))));
+    assert_eq!(format!("{}", res), "Type :: Type@1");
+    // A level is only read joined to `Type`
+    let res = parse_typ("λ f => f Type 1").unwrap();
+    assert_eq!(format!("{}", res), "λ f => f Type 1");
+    let res = parse_typ("λ f => f Type@1").unwrap();
+    assert_eq!(format!("{}", res), "λ f => f Type@1");
+    assert_eq!(parse_typ("Prop").unwrap(), Term::Prp(None));
+    let res = parse_typ("∀ (P: Prop) (p: P) -> P").unwrap();
+    assert_eq!(format!("{}", res), "∀ (P: Prop) (p: P) -> P");
+  }
+
+  #[test]
+  fn test_holes() {
+    let (_, res) = parse("λ _ x => _ x").unwrap();
+    let hole = Term::App(
+      None,
+      Box::new((Term::Hol(None, None), Term::Var(None, Name::from("x"), 0))),
+    );
+    let lam =
+      |nam: &str, bod| Term::Lam(None, Name::from(nam), Box::new(bod));
+    assert_eq!(res, lam("_", lam("x", hole)));
+    assert_eq!(format!("{}", res), "λ _ x => _ x");
+    assert!(parse("_x").is_err());
+    let (_, res) = parse("λ x => ?goal").unwrap();
+    let goal = Term::Hol(None, Some(String::from("goal")));
+    assert_eq!(res, lam("x", goal));
+    assert_eq!(format!("{}", res), "λ x => ?goal");
+    assert!(parse("?").is_err());
+    // A hole is a term wherever one is expected, such as a binder's type
+    let (_, res) = parse("∀ (x: ?A) -> ?B x").unwrap();
+    assert_eq!(format!("{}", res), "∀ (x: ?A) -> ?B x");
+    let def = parse_typed_definition(
+      HashMap::new(),
+      Ops::new(),
+      Vector::new(),
+      true,
+      false,
+    );
+    let (_, (_, term, typ, _)) =
+      def(Span::new("f {A: ?s} (x: A) : ?t = ?body")).unwrap();
+    assert_eq!(format!("{}", typ), "∀ (A: ?s) (x: A) -> ?t");
+    assert_eq!(format!("{}", term), "λ A x => ?body");
+  }
+
+  #[test]
+  fn test_numbers() {
+    let lit = |i: &str| match parse(i) {
+      Ok((_, Term::Lit(_, lit))) => lit,
+      res => panic!("{}: unexpected result {:?}", i, res),
+    };
+    assert_eq!(lit("42"), Literal::Natural(BigUint::from(42u64)));
+    assert_eq!(lit("0"), Literal::Natural(BigUint::from(0u64)));
+    assert_eq!(lit("-7"), Literal::Integer(BigInt::from(-7)));
+    assert_eq!(lit("+7"), Literal::Integer(BigInt::from(7)));
+    assert_eq!(format!("{}", lit("-7")), "-7");
+    assert_eq!(format!("{}", lit("+7")), "+7");
+    let nat = |x: u64| Literal::Natural(BigUint::from(x));
+    assert_eq!(lit("0xFF"), nat(255));
+    assert_eq!(lit("0xff"), nat(255));
+    assert_eq!(lit("0o755"), nat(493));
+    assert_eq!(lit("0b1010"), nat(10));
+    assert_eq!(lit("1_000_000"), nat(1_000_000));
+    assert_eq!(lit("0b1111_0000"), nat(240));
+    assert_eq!(lit("-0x10"), Literal::Integer(BigInt::from(-16)));
+    assert!(parse("0b102").is_err());
+    assert!(parse("_1").is_err());
+    assert!(parse("42x").is_err());
+    assert!(parse("- 7").is_err());
+  }
+
+  #[test]
+  fn test_escapes() {
+    let lit = |i: &str| match parse(i) {
+      Ok((_, Term::Lit(_, lit))) => lit,
+      res => panic!("{}: unexpected result {:?}", i, res),
+    };
+    let text = lit(r#""a\tb\n\\ \"q\" \u{3bb}\u{1F600}\0""#);
+    let expected = "a\tb\n\\ \"q\" λ😀\0";
+    assert_eq!(text, Literal::Text(String::from(expected)));
+    assert_eq!(format!("{}", text), r#""a\tb\n\\ \"q\" λ😀\0""#);
+    assert_eq!(lit(r"'\''"), Literal::Char('\''));
+    assert_eq!(lit(r"'\u{7}'"), Literal::Char('\u{7}'));
+    assert_eq!(format!("{}", lit(r"'\u{7}'")), r"'\u{7}'");
+    assert!(parse(r#""\q""#).is_err());
+    assert!(parse(r#""\u{D800}""#).is_err());
+  }
+
+  #[test]
+  fn test_raw_text() {
+    let text = |i: &str| match parse(i) {
+      Ok((_, Term::Lit(_, Literal::Text(x)))) => x,
+      res => panic!("{}: unexpected result {:?}", i, res),
+    };
+    assert_eq!(text(r#""""a "quoted" \n""""#), r#"a "quoted" \n"#);
+    let block = "\"\"\"\nfirst\n  second\n\"\"\"";
+    assert_eq!(text(block), "first\n  second\n");
+    assert_eq!(text(r#""""""""#), "");
+    let (_, term) = parse(r#""""say "hi" now""""#).unwrap();
+    assert_eq!(format!("{}", term), r#""say \"hi\" now""#);
+    assert!(parse(r#""""unterminated"#).is_err());
+  }
+
+  // The definitions of a datatype, stored in the hashspace, and their scope
+  fn stored(data: Datatype) -> (Defs, Refs) {
+    let mut defs = HashMap::new();
+    let mut refs = HashMap::new();
+    for def in data.defs() {
+      if let Declaration::Defn { name, defn, term, .. } = store_def(def) {
+        refs.insert(name, (defn, term));
+        defs.insert(defn, Def::get_link(defn).unwrap());
+      }
+    }
+    (defs, refs)
+  }
+
+  #[test]
+  fn test_lists() {
+    let (defs, refs) = stored(
+      datatype("List", &[("A", "Type")], "Type@1", &[
+        ("nil", "List A"),
+        ("cons", "∀ (x: A) (xs: List A) -> List A"),
+      ])
+      .unwrap(),
+    );
+    let parse = |i: &'static str| {
+      parse_expression(refs.clone(), Ops::new(), Vector::new())(Span::new(i))
+        .map(|(_, term)| term)
+    };
+    let list = parse("[1, #add 1 1, 3,]").unwrap();
+    let expected = parse(
+      "List.cons _ 1 (List.cons _ (#add 1 1) (List.cons _ 3 (List.nil _)))",
+    );
+    assert_eq!(Ok(list.clone()), expected);
+    let typ = parse("List #Natural").unwrap();
+    let ann = Term::Ann(None, Box::new((typ, list)));
+    assert!(infer_term(&defs, &ann).is_ok());
+    assert_eq!(parse("[ ]"), parse("List.nil _"));
+    assert!(parse("[1, 2").is_err());
+    // The constructors are those in scope
+    assert!(super::parse("[1]").is_err());
+  }
+
+  #[test]
+  fn test_conditionals() {
+    let (defs, refs) = stored(
+      datatype("Bool", &[], "Type@1", &[("true", "Bool"), ("false", "Bool")])
+        .unwrap(),
+    );
+    let parse = |i: &'static str| {
+      parse_expression(refs.clone(), Ops::new(), Vector::new())(Span::new(i))
+        .map(|(_, term)| term)
+    };
+    let not = parse("λ b => if b then Bool.false else Bool.true").unwrap();
+    let typ = parse("∀ (b: Bool) -> Bool").unwrap();
+    let ann = Term::Ann(None, Box::new((typ, not)));
+    let not = elab_term(&defs, &ann).unwrap();
+    let app = Term::App(None, Box::new((not, parse("Bool.true").unwrap())));
+    let no = parse("Bool.false").unwrap();
+    assert_eq!(nbe::norm(&defs, &app), nbe::norm(&defs, &no));
+    // The branches extend as far as they can, so that they nest
+    let nested = parse("λ b c => if b then if c then 1 else 2 else 3");
+    assert!(nested.is_ok());
+    assert!(parse("λ b => if b then 1").is_err());
+    assert!(super::parse("λ b => if b then 1 else 2").is_err());
+  }
+
+  #[test]
+  fn test_operators() {
+    let bin = "∀ (x: E) (y: E) -> E";
+    let ctors = [("add", bin), ("mul", bin), ("pow", bin), ("eql", bin)];
+    let (_, refs) = stored(datatype("E", &[], "Type@1", &ctors).unwrap());
+    let op = |sym: &str, assoc, prec, name: &str| {
+      (Name::from(sym), Operator { assoc, prec, name: Name::from(name) })
+    };
+    let ops: Ops = vec![
+      op("+", Assoc::Left, 6, "E.add"),
+      op("*", Assoc::Left, 7, "E.mul"),
+      op("^", Assoc::Right, 8, "E.pow"),
+      op("==", Assoc::Non, 4, "E.eql"),
+    ]
+    .into_iter()
+    .collect();
+    let ctx: Vector<Name> =
+      vec!["c", "b", "a"].into_iter().map(Name::from).collect();
+    let parse = |i: &'static str| {
+      parse_expression(refs.clone(), ops.clone(), ctx.clone())(Span::new(i))
+        .map(|(_, term)| term)
+    };
+    let same = |infix: &'static str, prefix: &'static str| {
+      assert_eq!(parse(infix).unwrap(), parse(prefix).unwrap(), "{}", infix)
+    };
+    same("a + b * c + a", "E.add (E.add a (E.mul b c)) a");
+    same("a ^ b ^ c", "E.pow a (E.pow b c)");
+    same("(a + b) * c", "E.mul (E.add a b) c");
+    same("E.pow a b + c", "E.add (E.pow a b) c");
+    same("a == b * c", "E.eql a (E.mul b c)");
+    same("(+) a b", "E.add a b");
+    same("a + b :: E", "E.add a b :: E");
+    match parse("a == b == c") {
+      Err(Err::Failure(e)) => assert!(matches!(
+        e.errors.last(),
+        Some(ParseErrorKind::NonAssociative(op)) if op == "=="
+      )),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[test]
+  fn test_implicit_binders() {
+    let def = |i: &'static str| {
+      let def = parse_typed_definition(
+        HashMap::new(),
+        Ops::new(),
+        Vector::new(),
+        true,
+        false,
+      );
+      def(Span::new(i)).map(|(_, def)| def)
+    };
+    let (nam, term, typ, implicits) =
+      def("id {A: Type} (x: A) : A = x").unwrap();
+    assert_eq!(nam, Name::from("id"));
+    assert_eq!(implicits, vec![true]);
+    assert_eq!(format!("{}", typ), "∀ (A: Type) (x: A) -> A");
+    assert_eq!(format!("{}", term), "λ A x => x");
+    let (.., implicits) =
+      def("k {A B: Type} (x: A) {y: B} (z: #Natural) : A = x").unwrap();
+    assert_eq!(implicits, vec![true, true, false, true]);
+    let (.., implicits) = def("f (n: #Natural) : #Natural = n").unwrap();
+    assert!(implicits.is_empty());
+    match parse("let f {A: Type} (x: A) : A = x; f") {
+      Err(Err::Failure(e)) => assert!(matches!(
+        e.errors.last(),
+        Some(ParseErrorKind::LocalImplicit(nam)) if nam == "f"
+      )),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[test]
+  fn test_ascriptions() {
+    let same = |ascribed: &str, annotated: &str| {
+      assert_eq!(parse(ascribed).unwrap().1, parse(annotated).unwrap().1)
+    };
+    same("(Type : Type@1)", "Type :: Type@1");
+    same("λ x => (x : #Natural)", "λ x => (x :: #Natural)");
+    let id = "∀ (x: #Natural) -> #Natural";
+    let ascribed = format!("(λ x => x : {}) 1", id);
+    same(&ascribed, &format!("((λ x => x) :: {}) 1", id));
+    same("((1 : #Natural), 2)", "(1 :: #Natural, 2)");
+    // The ascribed type is checked
+    let defs = Defs::new();
+    let (_, term) = parse("(1 : #Natural)").unwrap();
+    assert_eq!(format!("{}", infer_term(&defs, &term).unwrap()), "#Natural");
+    let (_, term) = parse("(1 : #Text)").unwrap();
+    assert!(infer_term(&defs, &term).is_err());
+    assert!(parse("(1 :)").is_err());
+  }
+
+  #[test]
+  fn test_do_notation() {
+    let names = vec!["bind", "pure", "get", "put", "f"];
+    let ctx: Vector<Name> = names.into_iter().map(Name::from).collect();
+    let parse = |i: &'static str| {
+      parse_expression(HashMap::new(), Ops::new(), ctx.clone())(Span::new(i))
+        .map(|(_, term)| term)
+    };
+    let same = |sugared: &'static str, desugared: &'static str| {
+      assert_eq!(parse(sugared).unwrap(), parse(desugared).unwrap())
+    };
+    same(
+      "do { x <- get; put (f x); return x }",
+      "bind get (λ x => bind (put (f x)) (λ _ => pure x))",
+    );
+    same("do { get }", "get");
+    same("do {return f}", "pure f");
+    same(
+      "λ g => do { x <- g; y <- f x; return (x, y) }",
+      "λ g => bind g (λ x => bind (f x) (λ y => pure (x, y)))",
+    );
+    // The last statement can't bind a name
+    assert!(parse("do { x <- get }").is_err());
+    assert!(parse("do { }").is_err());
+    // `bind` and `pure` are those in scope
+    assert!(super::parse("do { x <- 1; 2 }").is_err());
+    assert!(super::parse("do { return 1 }").is_err());
+  }
+
+  #[test]
+  fn test_floats() {
+    let flt = |i: &str| match parse(i) {
+      Ok((_, Term::Lit(_, Literal::Float(x)))) => f64::from_bits(x),
+      res => panic!("{}: unexpected result {:?}", i, res),
+    };
+    assert_eq!(flt("1.25"), 1.25);
+    assert_eq!(flt("-0.5"), -0.5);
+    assert_eq!(flt("6.02e23"), 6.02e23);
+    assert_eq!(flt("1E-3"), 0.001);
+    assert_eq!(flt("2.5e+2"), 250.0);
+    for i in &["1.25", "-0.5", "6.02e23", "1e-7", "100.0"] {
+      let (_, term) = parse(i).unwrap();
+      assert_eq!(format!("{}", term), *i);
+    }
+    assert!(matches!(parse("1"), Ok((_, Term::Lit(_, Literal::Natural(_))))));
+    assert!(parse("1.").is_err());
+    match parse("1.0e400") {
+      Err(Err::Failure(e)) => assert!(matches!(
+        e.errors.last(),
+        Some(ParseErrorKind::FloatOutOfRange(_))
+      )),
+      res => panic!("unexpected result {:?}", res),
+    }
+  }
+
+  #[test]
+  fn test_pairs() {
+    let (_, res) = parse("λ x => (x, Type, fst x)").unwrap();
+    assert_eq!(format!("{}", res), "λ x => (x, (Type, fst x))");
+    let (_, res) = parse("Σ (A: Type) (x: A) -> A").unwrap();
+    assert_eq!(format!("{}", res), "Σ (A: Type) (x: A) -> A");
+    let (_, res) = parse("λ p => snd p Type").unwrap();
+    assert_eq!(format!("{}", res), "λ p => (snd p) Type");
+    assert!(parse("fst").is_err());
+    let (_, res) = parse("λ p => p.fst.snd").unwrap();
+    assert_eq!(res, parse("λ p => snd (fst p)").unwrap().1);
+    let (_, res) = parse("λ p => (p, Type).snd.fst").unwrap();
+    assert_eq!(res, parse("λ p => fst (snd (p, Type))").unwrap().1);
+    // A variable named as a projection is the variable
+    let (_, res) = parse("λ p.fst => p.fst").unwrap();
+    let var = Term::Var(None, Name::from("p.fst"), 0);
+    assert_eq!(res, Term::Lam(None, Name::from("p.fst"), Box::new(var)));
+    assert!(parse("λ p => q.fst").is_err());
   }
 
   #[test]
   fn test_cases() {
-    let res = parse_expression(HashMap::new(), Vector::new())(Span::new(
-      "(Type :: Type)",
-    ));
+    let expr = parse_expression(HashMap::new(), Ops::new(), Vector::new());
+    let res = expr(Span::new("(Type :: Type)"));
     println!("res: {:?}", res);
     assert!(res.is_ok());
-    let res = parse_expression(HashMap::new(), Vector::new())(Span::new(
-      "(Type (Type Type)  )",
-    ));
+    let res = expr(Span::new("(Type (Type Type)  )"));
     println!("res: {:?}", res);
     assert!(res.is_ok());
-    let res = parse_expression(HashMap::new(), Vector::new())(Span::new(
+    let res = expr(Span::new(
       "λ x c n => (c x (c x (c x (c x (c x (c x (c x (c x (c x (c x (c x x (c \
        x (c x (c x (c x n)))))))))))))))",
     ));
     println!("res: {:?}", res);
     assert!(res.is_ok());
-    let res = parse_expression(HashMap::new(), Vector::new())(Span::new(
+    let res = expr(Span::new(
       "λ x c n => (c x (c x (c x (c x (c x (c x (c x (c x (c x (c x (c x x (c \
        x (c x (c x (c x n)))))))))))))))",
     ));
     println!("res2: {:?}", res);
     assert!(res.is_ok());
-    let res = parse_binder_full(HashMap::new(), Vector::new())(Span::new(
-      "(a b c: Type)",
-    ));
+    let binder = parse_binder_full(HashMap::new(), Ops::new(), Vector::new());
+    let res = binder(Span::new("(a b c: Type)"));
     println!("res2: {:?}", res);
     assert!(res.is_ok());
-    let res = parse_expression(HashMap::new(), Vector::new())(Span::new(
-      "∀ Type -> Type",
-    ));
+    let res = expr(Span::new("∀ Type -> Type"));
     println!("res: {:?}", res);
     assert!(res.is_ok());
-    let res = parse_expression(HashMap::new(), Vector::new())(Span::new(
-      "∀ (_ :Type) -> Type",
-    ));
+    let res = expr(Span::new("∀ (_ :Type) -> Type"));
     println!("res: {:?}", res);
     assert!(res.is_ok());
   }
 
   #[quickcheck]
   fn term_parse_print(x: Term) -> bool {
-    match parse_expression(test_refs(), Vector::new())(Span::new(&format!(
-      "{}",
-      x
-    ))) {
+    let expr = parse_expression(test_refs(), Ops::new(), Vector::new());
+    match expr(Span::new(&format!("{}", x))) {
       Ok((_, y)) => x == y,
       e => {
         println!("{}", x);
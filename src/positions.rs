@@ -0,0 +1,231 @@
+use hashexpr::{
+  atom,
+  atom::Atom::*,
+  position::Pos,
+  Expr,
+  Expr::{
+    Atom,
+    Cons,
+  },
+  Link,
+};
+
+use crate::{
+  decode_error::{
+    DecodeError,
+    Expected,
+  },
+  definition::Definition,
+  hashspace,
+  meta_term::MetaTerm,
+  unembed_error::UnembedError,
+};
+
+// The source positions of a definition and of the terms of its type and
+// term, which are left out of its encoding, so that its link is the same
+// wherever in a source it is written. They are stored apart, in an entry of
+// their own, which the declaration of the definition in a package links to,
+// and are positions in the source of that package, so that errors in
+// definitions read back from the hashspace, as those of packages opened by
+// their link, still point at where they are. The positions of a term are
+// those of the constructors of its metadata, in the order they are visited
+// in, first the constructor and then what is under it.
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Positions {
+  pub def: Option<Pos>,
+  pub typ: Vec<Option<Pos>>,
+  pub term: Vec<Option<Pos>>,
+}
+
+fn collect(meta: &MetaTerm, posns: &mut Vec<Option<Pos>>) {
+  match meta {
+    MetaTerm::Ctor(pos, xs) => {
+      posns.push(*pos);
+      for x in xs {
+        collect(x, posns);
+      }
+    }
+    MetaTerm::Bind(_, x) => collect(x, posns),
+    MetaTerm::Link(..) | MetaTerm::Leaf => (),
+  }
+}
+
+// Give the constructors of `meta` the positions of `posns`, in order, or
+// none, once they run out
+fn assign<'a>(
+  meta: &mut MetaTerm,
+  posns: &mut impl Iterator<Item = &'a Option<Pos>>,
+) {
+  match meta {
+    MetaTerm::Ctor(pos, xs) => {
+      *pos = posns.next().copied().flatten();
+      for x in xs {
+        assign(x, posns);
+      }
+    }
+    MetaTerm::Bind(_, x) => assign(x, posns),
+    MetaTerm::Link(..) | MetaTerm::Leaf => (),
+  }
+}
+
+// A position as the bits of its offsets, lines and columns, each of eight
+// bytes, or as no bits if there is none
+fn encode_pos(pos: &Option<Pos>) -> Expr {
+  let mut bytes = Vec::new();
+  if let Some(pos) = pos {
+    for x in &[
+      pos.from_offset,
+      pos.from_line,
+      pos.from_column,
+      pos.upto_offset,
+      pos.upto_line,
+      pos.upto_column,
+    ] {
+      bytes.extend(x.to_be_bytes());
+    }
+  }
+  bits!(bytes)
+}
+
+fn decode_pos(expr: &Expr) -> Result<Option<Pos>, DecodeError> {
+  match expr {
+    Atom(_, Bits(bytes)) if bytes.is_empty() => Ok(None),
+    Atom(_, Bits(bytes)) if bytes.len() == 48 => {
+      let mut xs = bytes.chunks(8).map(|chunk| {
+        let mut x = [0; 8];
+        x.copy_from_slice(chunk);
+        u64::from_be_bytes(x)
+      });
+      let mut next = || xs.next().unwrap_or(0);
+      Ok(Some(Pos {
+        from_offset: next(),
+        from_line: next(),
+        from_column: next(),
+        upto_offset: next(),
+        upto_line: next(),
+        upto_column: next(),
+      }))
+    }
+    _ => Err(DecodeError::new(expr.position(), vec![Expected::Positions])),
+  }
+}
+
+fn decode_posns(expr: &Expr) -> Result<Vec<Option<Pos>>, DecodeError> {
+  match expr {
+    Cons(_, xs) => xs.iter().map(decode_pos).collect(),
+    _ => Err(DecodeError::new(expr.position(), vec![Expected::Positions])),
+  }
+}
+
+impl Positions {
+  pub fn of(def: &Definition) -> Self {
+    let mut typ = Vec::new();
+    let mut term = Vec::new();
+    collect(&def.type_meta, &mut typ);
+    collect(&def.term_meta, &mut term);
+    Positions { def: def.pos, typ, term }
+  }
+
+  // Give a definition read back from the hashspace its positions
+  pub fn apply(&self, def: &mut Definition) {
+    def.pos = self.def;
+    assign(&mut def.type_meta, &mut self.typ.iter());
+    assign(&mut def.term_meta, &mut self.term.iter());
+  }
+
+  pub fn encode(&self) -> Expr {
+    cons!(
+      None,
+      text!("positions"),
+      encode_pos(&self.def),
+      Expr::Cons(None, self.typ.iter().map(encode_pos).collect()),
+      Expr::Cons(None, self.term.iter().map(encode_pos).collect())
+    )
+  }
+
+  pub fn decode(expr: Expr) -> Result<Self, DecodeError> {
+    match &expr {
+      Cons(pos, xs) => match xs.as_slice() {
+        [Atom(_, Text(c)), def, typ, term] if c == "positions" => {
+          Ok(Positions {
+            def: decode_pos(def)?,
+            typ: decode_posns(typ)?,
+            term: decode_posns(term)?,
+          })
+        }
+        _ => Err(DecodeError::new(*pos, vec![Expected::Positions])),
+      },
+      _ => Err(DecodeError::new(expr.position(), vec![Expected::Positions])),
+    }
+  }
+
+  pub fn store(&self) -> Link { hashspace::put(self.encode()) }
+
+  pub fn get_link(link: Link) -> Result<Self, UnembedError> {
+    let expr = hashspace::get(link).ok_or(UnembedError::UnknownLink(link))?;
+    Positions::decode(expr).map_err(UnembedError::DecodeError)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::package::store_def,
+    package::Declaration,
+    term::{
+      Def,
+      Term,
+    },
+  };
+
+  fn pos(line: u64, from: u64, upto: u64) -> Pos {
+    Pos {
+      from_offset: from,
+      from_line: line,
+      from_column: from,
+      upto_offset: upto,
+      upto_line: line,
+      upto_column: upto,
+    }
+  }
+
+  #[test]
+  fn round_trips() {
+    let typ_ = Term::Typ(Some(pos(1, 11, 15)), 0);
+    let term = Term::Lam(
+      Some(pos(1, 18, 29)),
+      Name::from("x"),
+      Box::new(Term::Var(Some(pos(1, 28, 29)), Name::from("x"), 0)),
+    );
+    let name = Name::from("positioned");
+    let def = Def::new(Some(pos(1, 1, 29)), name, String::new(), typ_, term);
+    let (definition, ..) = def.clone().embed();
+    let positions = Positions::of(&definition);
+    assert_eq!(positions.def, def.pos);
+    assert_eq!(positions.term, vec![def.term.pos(), Some(pos(1, 28, 29))]);
+    let decoded = Positions::decode(positions.encode());
+    assert_eq!(decoded, Ok(positions.clone()));
+    // The positions are left out of the definition's link
+    let mut moved = definition.clone();
+    moved.pos = Some(pos(7, 1, 29));
+    assert_eq!(moved.encode().link(), definition.clone().encode().link());
+    match store_def(def.clone()) {
+      Declaration::Defn { defn, positions: Some(posns), .. } => {
+        let found = Def::get_positioned(defn, Some(posns)).unwrap();
+        assert_eq!(found.pos, def.pos);
+        assert_eq!(found.typ_.pos(), def.typ_.pos());
+        assert_eq!(found.term.pos(), def.term.pos());
+        match found.term {
+          Term::Lam(_, _, body) => {
+            assert_eq!(body.pos(), Some(pos(1, 28, 29)))
+          }
+          term => panic!("{}", term),
+        }
+      }
+      decl => panic!("{:?}", decl),
+    }
+  }
+}
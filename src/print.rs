@@ -0,0 +1,538 @@
+use crate::term::{
+  Term,
+  Uses,
+};
+
+use std::collections::HashSet;
+
+// Pretty-printing of terms. A term is printed as a document, which is laid
+// out either flat, on one line, or broken over several where it does not fit
+// in the width it is printed to, as in Wadler's "A prettier printer". How a
+// term is printed is set by its `Options`: the width and indentation of its
+// layout, and whether it is re-sugared. Re-sugaring prints the arrow form
+// `∀ A -> B` of a binder which is not named, and names each variable by the
+// binder its de Bruijn index points to, rather than the name it was parsed
+// with, priming the names of binders which shadow others, so that the terms
+// evaluation and checking make, in which names may be captured, read as
+// they mean. Either way, what is printed parses back to the same term.
+
+// A document, which is laid out either flat, on one line, or broken over
+// several
+#[derive(Clone, Debug, PartialEq)]
+pub enum Doc {
+  Text(String),
+  // A space, when the group it is in is flat, or else a line break
+  Line,
+  // A document whose line breaks are indented one level further
+  Nest(Box<Doc>),
+  // A document laid out flat if it fits on the rest of the line
+  Group(Box<Doc>),
+  Cat(Vec<Doc>),
+}
+
+pub fn text(s: &str) -> Doc { Doc::Text(String::from(s)) }
+
+pub fn cat(docs: Vec<Doc>) -> Doc { Doc::Cat(docs) }
+
+pub fn nest(doc: Doc) -> Doc { Doc::Nest(Box::new(doc)) }
+
+pub fn group(doc: Doc) -> Doc { Doc::Group(Box::new(doc)) }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Options {
+  // The number of columns laid out to
+  pub width: usize,
+  // The number of columns each level of nesting is indented by
+  pub indent: usize,
+  // Whether binders which are not named are printed as arrows
+  pub arrows: bool,
+  // Whether variables are named by their binders
+  pub names: bool,
+}
+
+impl Options {
+  // As source is formatted, with the names it was written with
+  pub fn source() -> Self {
+    Options { width: 80, indent: 2, arrows: false, names: false }
+  }
+
+  // As terms are shown in the terminal, to its width
+  #[cfg(feature = "native")]
+  pub fn terminal() -> Self {
+    let width = crate::render::width();
+    Options { width, indent: 2, arrows: true, names: true }
+  }
+
+  // On one line, unsugared, as terms are displayed
+  pub fn line() -> Self {
+    Options { width: isize::MAX as usize, ..Self::source() }
+  }
+
+  // On one line, re-sugared, as terms are shown in messages, which are
+  // wrapped as a whole
+  pub fn message() -> Self {
+    Options { arrows: true, names: true, ..Self::line() }
+  }
+}
+
+// Whether the documents to be laid out, the next one last, fit in `width`
+// columns up to their first line break
+fn fits(mut width: isize, mut docs: Vec<(usize, bool, &Doc)>) -> bool {
+  while let Some((ind, flat, doc)) = docs.pop() {
+    if width < 0 {
+      return false;
+    }
+    match doc {
+      Doc::Text(s) => width -= s.chars().count() as isize,
+      Doc::Line if flat => width -= 1,
+      Doc::Line => return true,
+      Doc::Nest(doc) => docs.push((ind + 1, flat, doc)),
+      Doc::Group(doc) => docs.push((ind, flat, doc)),
+      Doc::Cat(cat) => {
+        docs.extend(cat.iter().rev().map(|doc| (ind, flat, doc)))
+      }
+    }
+  }
+  width >= 0
+}
+
+// Lay out a document, laying out each group flat when it fits
+pub fn render(doc: &Doc, opts: &Options) -> String {
+  let mut out = String::new();
+  let mut col = 0;
+  let mut docs = vec![(0, false, doc)];
+  while let Some((ind, flat, doc)) = docs.pop() {
+    match doc {
+      Doc::Text(s) => {
+        out.push_str(s);
+        col += s.chars().count();
+      }
+      Doc::Line if flat => {
+        out.push(' ');
+        col += 1;
+      }
+      Doc::Line => {
+        out.push('\n');
+        out.push_str(&" ".repeat(ind * opts.indent));
+        col = ind * opts.indent;
+      }
+      Doc::Nest(doc) => docs.push((ind + 1, flat, doc)),
+      Doc::Group(doc) => {
+        let flat = flat || {
+          let mut rest = docs.clone();
+          rest.push((ind, true, doc));
+          fits(opts.width as isize - col as isize, rest)
+        };
+        docs.push((ind, flat, doc));
+      }
+      Doc::Cat(cat) => {
+        docs.extend(cat.iter().rev().map(|doc| (ind, flat, doc)))
+      }
+    }
+  }
+  out
+}
+
+pub fn name(nam: &str) -> &str { if nam.is_empty() { "_" } else { nam } }
+
+pub fn uses(uses: &Uses) -> &str {
+  match uses {
+    Uses::None => "0 ",
+    Uses::Affi => "& ",
+    Uses::Once => "1 ",
+    Uses::Many => "",
+  }
+}
+
+pub fn is_atom(term: &Term) -> bool {
+  matches!(
+    term,
+    Term::Var(..)
+      | Term::Ref(..)
+      | Term::Lit(..)
+      | Term::LTy(..)
+      | Term::Opr(..)
+      | Term::Hol(..)
+      | Term::Met(..)
+      | Term::Par(..)
+      | Term::Typ(..)
+      | Term::Prp(_)
+  )
+}
+
+// The names of the references in a term, which binders are not to shadow
+fn ref_names(term: &Term, names: &mut HashSet<String>) {
+  match term {
+    Term::Ref(_, nam, ..) => {
+      names.insert(nam.to_string());
+    }
+    Term::Lam(_, _, bod)
+    | Term::Slf(_, _, bod)
+    | Term::Dat(_, bod)
+    | Term::Cse(_, bod)
+    | Term::Fst(_, bod)
+    | Term::Snd(_, bod) => ref_names(bod, names),
+    Term::App(_, terms)
+    | Term::Ann(_, terms)
+    | Term::Par(_, terms)
+    | Term::All(_, _, _, terms)
+    | Term::Sig(_, _, terms) => {
+      ref_names(&terms.0, names);
+      ref_names(&terms.1, names);
+    }
+    Term::Let(_, _, _, _, terms) => {
+      ref_names(&terms.0, names);
+      ref_names(&terms.1, names);
+      ref_names(&terms.2, names);
+    }
+    _ => (),
+  }
+}
+
+// Whether the variable with index `idx` occurs in a term
+fn occurs(term: &Term, idx: u64) -> bool {
+  match term {
+    Term::Var(_, _, idx2) => *idx2 == idx,
+    Term::Lam(_, _, bod) | Term::Slf(_, _, bod) => occurs(bod, idx + 1),
+    Term::Dat(_, bod)
+    | Term::Cse(_, bod)
+    | Term::Fst(_, bod)
+    | Term::Snd(_, bod) => occurs(bod, idx),
+    Term::App(_, terms) | Term::Ann(_, terms) | Term::Par(_, terms) => {
+      occurs(&terms.0, idx) || occurs(&terms.1, idx)
+    }
+    Term::All(_, _, _, terms) | Term::Sig(_, _, terms) => {
+      occurs(&terms.0, idx) || occurs(&terms.1, idx + 1)
+    }
+    Term::Let(_, rec, _, _, terms) => {
+      occurs(&terms.0, idx)
+        || occurs(&terms.1, if *rec { idx + 1 } else { idx })
+        || occurs(&terms.2, idx + 1)
+    }
+    _ => false,
+  }
+}
+
+// Prints a term in the context of the names of the binders it is under
+struct Printer<'a> {
+  opts: &'a Options,
+  ctx: Vec<String>,
+  refs: HashSet<String>,
+}
+
+impl<'a> Printer<'a> {
+  fn new(opts: &'a Options, term: &Term) -> Self {
+    let mut refs = HashSet::new();
+    if opts.names {
+      ref_names(term, &mut refs);
+    }
+    Printer { opts, ctx: Vec::new(), refs }
+  }
+
+  // Bind a name, primed until it shadows no other if variables are named by
+  // their binders, returning the name it is printed as
+  fn bind(&mut self, nam: &str) -> String {
+    let mut nam = String::from(nam);
+    if self.opts.names && name(&nam) != "_" {
+      while self.ctx.contains(&nam) || self.refs.contains(&nam) {
+        nam.push('\'');
+      }
+    }
+    self.ctx.push(nam.clone());
+    nam
+  }
+
+  fn unbind(&mut self, len: usize) {
+    self.ctx.truncate(self.ctx.len() - len);
+  }
+
+  fn var(&self, nam: &str, idx: u64) -> String {
+    let len = self.ctx.len() as u64;
+    if self.opts.names && idx < len {
+      let bound = &self.ctx[(len - 1 - idx) as usize];
+      if !bound.is_empty() {
+        return bound.clone();
+      }
+    }
+    String::from(nam)
+  }
+
+  // Whether a binder is printed as the bare type of an arrow, which it is
+  // if it is not named and the variable it binds does not occur in `bod`
+  fn is_arrow(&self, use_: &Uses, nam: &str, bod: &Term) -> bool {
+    self.opts.arrows
+      && name(nam) == "_"
+      && matches!(use_, Uses::Many)
+      && !occurs(bod, 0)
+  }
+
+  fn parens(&mut self, term: &Term) -> Doc {
+    if is_atom(term) {
+      self.term(term)
+    }
+    else {
+      cat(vec![text("("), self.term(term), text(")")])
+    }
+  }
+
+  // The bare type of an arrow, which ends the binders it is in, since the
+  // type of the binder after it would be read as an argument of it
+  fn arrow(&mut self, typ: &Term) -> Doc {
+    if let Term::App(..) = typ { self.term(typ) } else { self.parens(typ) }
+  }
+
+  // A binder, whose type is in the context it is bound in
+  fn binder(&mut self, use_: &Uses, nam: &str, typ: &Term) -> Doc {
+    self.delimited_binder(("(", ")"), use_, nam, typ)
+  }
+
+  // A binder between `delims`, which are braces for the implicit binder of a
+  // definition
+  fn delimited_binder(
+    &mut self,
+    delims: (&str, &str),
+    use_: &Uses,
+    nam: &str,
+    typ: &Term,
+  ) -> Doc {
+    let typ = self.term(typ);
+    let nam = self.bind(nam);
+    let open = format!("{}{}{}: ", delims.0, uses(use_), name(&nam));
+    cat(vec![Doc::Text(open), typ, text(delims.1)])
+  }
+
+  // The binders of a ∀ or Σ and the term they are bound in
+  fn binders<'b>(
+    &mut self,
+    head: &str,
+    use_: &'b Uses,
+    nam: &'b str,
+    terms: &'b (Term, Term),
+  ) -> Doc {
+    let (mut use_, mut nam, mut terms) = (use_, nam, terms);
+    let mut docs = Vec::new();
+    let mut len = 0;
+    loop {
+      len += 1;
+      if self.is_arrow(use_, nam, &terms.1) {
+        docs.extend(vec![Doc::Line, self.arrow(&terms.0)]);
+        self.bind(nam);
+        break;
+      }
+      docs.extend(vec![Doc::Line, self.binder(use_, nam, &terms.0)]);
+      match (head, &terms.1) {
+        ("∀", Term::All(_, use2, nam2, terms2)) => {
+          use_ = use2;
+          nam = nam2;
+          terms = terms2;
+        }
+        ("Σ", Term::Sig(_, nam2, terms2)) => {
+          nam = nam2;
+          terms = terms2;
+        }
+        _ => break,
+      }
+    }
+    docs.extend(vec![Doc::Line, text("-> "), self.term(&terms.1)]);
+    self.unbind(len);
+    group(cat(vec![text(head), nest(cat(docs))]))
+  }
+
+  fn term(&mut self, term: &Term) -> Doc {
+    match term {
+      Term::Var(_, nam, idx) => Doc::Text(self.var(nam, *idx)),
+      Term::Ref(_, nam, ..) => text(nam),
+      Term::Lam(_, nam, bod) => {
+        let mut nams = vec![self.bind(nam)];
+        let mut bod = &**bod;
+        while let Term::Lam(_, nam, bod2) = bod {
+          nams.push(self.bind(nam));
+          bod = &**bod2;
+        }
+        let last = nams.len() - 1;
+        let raw = !self.opts.names;
+        let nams: Vec<&str> = nams
+          .iter()
+          .enumerate()
+          .map(|(idx, nam)| {
+            if raw && idx == last { nam.as_str() } else { name(nam) }
+          })
+          .collect();
+        let head = format!("λ {} =>", nams.join(" "));
+        let bod = nest(cat(vec![Doc::Line, self.term(bod)]));
+        self.unbind(last + 1);
+        group(cat(vec![Doc::Text(head), bod]))
+      }
+      Term::All(_, use_, nam, terms) => self.binders("∀", use_, nam, terms),
+      Term::Sig(_, nam, terms) => self.binders("Σ", &Uses::Many, nam, terms),
+      Term::App(..) => {
+        let mut args = Vec::new();
+        let mut fun = term;
+        while let Term::App(_, terms) = fun {
+          args.push(&terms.1);
+          fun = &terms.0;
+        }
+        let fun = self.parens(fun);
+        let mut docs = Vec::new();
+        for arg in args.into_iter().rev() {
+          docs.extend(vec![Doc::Line, self.parens(arg)]);
+        }
+        group(cat(vec![fun, nest(cat(docs))]))
+      }
+      Term::Let(_, rec, use_, nam, terms) => {
+        let typ = self.term(&terms.0);
+        let (nam, exp) = if *rec {
+          let nam = self.bind(nam);
+          (nam, self.term(&terms.1))
+        }
+        else {
+          let exp = self.term(&terms.1);
+          (self.bind(nam), exp)
+        };
+        let bod = self.term(&terms.2);
+        self.unbind(1);
+        let kw = if *rec { "letrec" } else { "let" };
+        let head = format!("{} {}{}: ", kw, uses(use_), name(&nam));
+        group(cat(vec![
+          Doc::Text(head),
+          typ,
+          text(" :="),
+          nest(cat(vec![Doc::Line, exp])),
+          text(";"),
+          Doc::Line,
+          bod,
+        ]))
+      }
+      Term::Slf(_, nam, bod) => {
+        let nam = self.bind(nam);
+        let bod = self.term(bod);
+        self.unbind(1);
+        cat(vec![Doc::Text(format!("@{} ", name(&nam))), bod])
+      }
+      Term::Ann(_, terms) => {
+        let trm = self.parens(&terms.1);
+        let typ = self.parens(&terms.0);
+        group(cat(vec![trm, text(" ::"), nest(cat(vec![Doc::Line, typ]))]))
+      }
+      Term::Dat(_, bod) => cat(vec![text("data "), self.term(bod)]),
+      Term::Cse(_, bod) => cat(vec![text("case "), self.term(bod)]),
+      Term::Par(_, terms) => {
+        let fst = self.term(&terms.0);
+        let snd = self.term(&terms.1);
+        group(cat(vec![
+          text("("),
+          fst,
+          text(","),
+          nest(cat(vec![Doc::Line, snd])),
+          text(")"),
+        ]))
+      }
+      Term::Fst(_, bod) => cat(vec![text("fst "), self.parens(bod)]),
+      Term::Snd(_, bod) => cat(vec![text("snd "), self.parens(bod)]),
+      Term::Typ(_, 0) => text("Type"),
+      Term::Typ(_, lvl) => Doc::Text(format!("Type@{}", lvl)),
+      Term::Prp(_) => text("Prop"),
+      Term::Lit(_, lit) => Doc::Text(format!("{}", lit)),
+      Term::LTy(_, lty) => Doc::Text(format!("{}", lty)),
+      Term::Opr(_, opr) => Doc::Text(format!("{}", opr)),
+      Term::Hol(_, None) => text("_"),
+      Term::Hol(_, Some(nam)) => Doc::Text(format!("?{}", nam)),
+      Term::Met(_, idx) => Doc::Text(format!("?{}", idx)),
+    }
+  }
+}
+
+// The document of a term
+pub fn term_doc(term: &Term, opts: &Options) -> Doc {
+  Printer::new(opts, term).term(term)
+}
+
+// The document of a binder, of a definition or a datatype
+pub fn binder_doc(use_: &Uses, nam: &str, typ: &Term, opts: &Options) -> Doc {
+  Printer::new(opts, typ).binder(use_, nam, typ)
+}
+
+// An implicit binder `{x: A}` of a definition
+pub fn implicit_binder_doc(
+  use_: &Uses,
+  nam: &str,
+  typ: &Term,
+  opts: &Options,
+) -> Doc {
+  Printer::new(opts, typ).delimited_binder(("{", "}"), use_, nam, typ)
+}
+
+// A term, laid out as set by `opts`
+pub fn pretty(term: &Term, opts: &Options) -> String {
+  render(&term_doc(term, opts), opts)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    parse::{
+      span::Span,
+      term::{
+        parse_expression,
+        Ops,
+      },
+    },
+    term::tests::test_refs,
+  };
+  use im::Vector;
+
+  fn parse(i: &str) -> Term {
+    let expr = parse_expression(test_refs(), Ops::new(), Vector::new());
+    expr(Span::new(i)).unwrap().1
+  }
+
+  #[quickcheck]
+  fn sugared_parses(x: Term) -> bool {
+    let printed = pretty(&x, &Options::message());
+    let expr = parse_expression(test_refs(), Ops::new(), Vector::new());
+    match expr(Span::new(&printed)) {
+      Ok((_, y)) => x.embed().0 == y.embed().0,
+      Err(_) => false,
+    }
+  }
+
+  #[test]
+  fn lays_out() {
+    let term = parse("λ f x => f x x x");
+    let opts = |width| Options { width, ..Options::source() };
+    assert_eq!(pretty(&term, &opts(80)), "λ f x => f x x x");
+    assert_eq!(pretty(&term, &opts(12)), "λ f x =>\n  f x x x");
+    let wide = Options { width: 6, indent: 4, ..Options::source() };
+    let broken = "λ f x =>\n    f\n        x\n        x\n        x";
+    assert_eq!(pretty(&term, &wide), broken);
+  }
+
+  #[test]
+  fn resugars() {
+    let sugar = Options::message();
+    let term = parse("∀ (_: Type) (_: Type) -> Type");
+    assert_eq!(pretty(&term, &Options::line()), format!("{}", term));
+    assert_eq!(pretty(&term, &sugar), "∀ Type -> ∀ Type -> Type");
+    let term = parse("∀ (A: Type) (_: ∀ (x: A) -> A) (_: A) -> A");
+    let arrows = "∀ (A: Type) (∀ (x: A) -> A) -> ∀ A -> A";
+    assert_eq!(pretty(&term, &sugar), arrows);
+    let term = parse("Σ (A: Type) -> λ x => A x");
+    assert_eq!(pretty(&term, &sugar), "Σ (A: Type) -> λ x => A x");
+    let term = parse("λ x => λ x => x");
+    assert_eq!(pretty(&term, &sugar), "λ x x' => x'");
+    // A variable whose name was captured is named by its binder
+    let term = Term::Lam(
+      None,
+      Name::from("x"),
+      Box::new(Term::Lam(
+        None,
+        Name::from("y"),
+        Box::new(Term::Var(None, Name::from("y"), 1)),
+      )),
+    );
+    assert_eq!(pretty(&term, &Options::line()), "λ x y => y");
+    assert_eq!(pretty(&term, &sugar), "λ x y => x");
+  }
+}
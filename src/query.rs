@@ -0,0 +1,285 @@
+use crate::{
+  core::check::{
+    self,
+    CheckError,
+  },
+  explain::Code,
+  hashspace,
+  name::Name,
+  package::{
+    Declaration,
+    Package,
+  },
+  parse::{
+    package::{
+      import_path,
+      parse_package,
+      PackageEnv,
+    },
+    span::Span,
+  },
+  term::{
+    Def,
+    Defs,
+    Link,
+    Refs,
+    Term,
+  },
+};
+
+use hashexpr::Expr;
+
+use im::HashMap;
+
+use nom::Err;
+
+use std::{
+  fmt,
+  fs,
+  path::Path,
+};
+
+// Incremental checking, by memoized queries. Parsing a package, and
+// elaborating and checking each of its definitions, are queries whose
+// answers are remembered, keyed by content hash, so that once a package is
+// edited only what the edit affects is computed again.
+//
+// A package is parsed again when its source changes, or the source of one of
+// the packages it opens does. A definition is known by the link of its
+// contents, which covers the links of the definitions it refers to, so an
+// edited definition and those which depend on it, directly or not, get new
+// links, while the others keep theirs. Elaborating a definition only needs
+// the definitions its link covers, so the answer stored for a link is never
+// out of date, and the dirty part of a package is exactly the definitions
+// with new links.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryError {
+  // A file which could not be read, and why
+  Io(String),
+  // A package which could not be parsed, the line and column it could not
+  // be parsed at, if known, the code of the error, if it has one, and why
+  Parse(Option<(u32, usize)>, Option<Code>, String),
+  UndefinedReference(String),
+  Check(CheckError),
+}
+
+impl fmt::Display for QueryError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Parse(Some((line, column)), _, e) => {
+        write!(f, "{}:{}: {}", line, column, e)
+      }
+      Self::Io(e) | Self::Parse(None, _, e) => write!(f, "{}", e),
+      Self::UndefinedReference(nam) => write!(f, "Undefined reference {}", nam),
+      Self::Check(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for QueryError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Check(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+// A parsed package, its link and the definitions and references in scope at
+// its end
+#[derive(Clone, Debug)]
+pub struct Parsed {
+  pub link: Link,
+  pub package: Package,
+  pub defs: Defs,
+  pub refs: Refs,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+  // The parsed packages, by the link of their source
+  packages: HashMap<Link, Parsed>,
+  // The outcome of elaborating each definition, by its link
+  elaborated: HashMap<Link, Result<Def, CheckError>>,
+  // The number of queries answered by computing rather than from memory
+  pub computed: usize,
+}
+
+// The link a package source is stored at
+fn source_link(source: &str) -> Link {
+  Link::make(&Expr::serialize_text(source))
+}
+
+// Whether the packages a package opens, transitively, are unchanged since it
+// was parsed from the file at `path`. A package opened by its link alone,
+// with no file to read, cannot change.
+fn opens_unchanged(path: &Path, package: &Package) -> bool {
+  package.decls.iter().all(|decl| match decl {
+    Declaration::Open { name, from, .. } => {
+      let path = import_path(path, name);
+      match fs::read_to_string(&path) {
+        Ok(source) => match Package::get_link(*from) {
+          Ok(pack) => {
+            pack.source == source_link(&source) && opens_unchanged(&path, &pack)
+          }
+          Err(_) => false,
+        },
+        Err(_) => true,
+      }
+    }
+    Declaration::Defn { .. } | Declaration::Test { .. } => true,
+  })
+}
+
+impl Database {
+  pub fn new() -> Self { Self::default() }
+
+  // Parse the package in the file at `path`
+  pub fn parse(&mut self, path: &Path) -> Result<Parsed, QueryError> {
+    let source = fs::read_to_string(path)
+      .map_err(|e| QueryError::Io(format!("{}: {}", path.display(), e)))?;
+    self.parse_source(path, source)
+  }
+
+  // Parse the package with source `source`, as if it were in the file at
+  // `path`, such as an editor's unsaved copy of it
+  pub fn parse_source(
+    &mut self,
+    path: &Path,
+    source: String,
+  ) -> Result<Parsed, QueryError> {
+    let link = source_link(&source);
+    let file = path.display();
+    let _span =
+      debug_span!(target: "parse", "parse_source", %file, %link).entered();
+    if let Some(parsed) = self.packages.get(&link) {
+      if opens_unchanged(path, &parsed.package) {
+        debug!(target: "parse", %file, %link, "Reusing");
+        return Ok(parsed.clone());
+      }
+    }
+    self.computed += 1;
+    debug!(target: "parse", %file, %link, "Parsing");
+    // The source is stored with the package, so that it can be read back
+    hashspace::put_text(&source);
+    let env = PackageEnv::new(path.to_path_buf());
+    let parsed = match parse_package(env, link)(Span::new(&source)) {
+      Ok((_, (link, package, defs, refs))) => {
+        Parsed { link, package, defs, refs }
+      }
+      Err(Err::Incomplete(_)) => {
+        let e = String::from("Incomplete");
+        return Err(QueryError::Parse(None, None, e));
+      }
+      Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+        let at = (e.input.location_line(), e.input.get_utf8_column());
+        return Err(QueryError::Parse(Some(at), e.code(), e.reasons()));
+      }
+    };
+    self.packages.insert(link, parsed.clone());
+    Ok(parsed)
+  }
+
+  // Elaborate the definition at `link`, which must be in `defs`, filling in
+  // its placeholders
+  pub fn elaborate(
+    &mut self,
+    defs: &Defs,
+    link: Link,
+  ) -> Result<Def, CheckError> {
+    if let Some(res) = self.elaborated.get(&link) {
+      return res.clone();
+    }
+    self.computed += 1;
+    let res = check::elab_def(defs, &defs[&link]);
+    self.elaborated.insert(link, res.clone());
+    res
+  }
+
+  // Check the definition at `link`, which must be in `defs`
+  pub fn check(&mut self, defs: &Defs, link: Link) -> Result<(), CheckError> {
+    self.elaborate(defs, link).map(|_| ())
+  }
+
+  // The type of the definition `name` in scope in the package at `path`,
+  // provided that it checks
+  pub fn type_of(
+    &mut self,
+    path: &Path,
+    name: &str,
+  ) -> Result<Term, QueryError> {
+    let parsed = self.parse(path)?;
+    let (link, _) = parsed
+      .refs
+      .get(name)
+      .ok_or_else(|| QueryError::UndefinedReference(String::from(name)))?;
+    let def = self.elaborate(&parsed.defs, *link).map_err(QueryError::Check)?;
+    Ok(def.typ_)
+  }
+
+  // Check every definition in scope in the package at `path`, in order of
+  // name
+  pub fn check_package(
+    &mut self,
+    path: &Path,
+  ) -> Result<Vec<(String, Result<(), CheckError>)>, QueryError> {
+    let parsed = self.parse(path)?;
+    let mut names: Vec<&Name> = parsed.refs.keys().collect();
+    names.sort();
+    let mut outcomes = Vec::new();
+    for name in names {
+      let (link, _) = parsed.refs[name];
+      outcomes.push((name.to_string(), self.check(&parsed.defs, link)));
+    }
+    Ok(outcomes)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn recomputes_only_edits() {
+    let dir = env::temp_dir().join("yatima-query");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("Query.ya");
+    let write = |two: &str| {
+      let source = format!(
+        "package Query where\n\
+         def one : #Natural = 1\n\
+         def two : #Natural = {}\n",
+        two
+      );
+      fs::write(&path, source).unwrap();
+    };
+    let mut db = Database::new();
+    write("#add one one");
+    let outcomes = db.check_package(&path).unwrap();
+    assert!(outcomes.iter().all(|(_, res)| res.is_ok()));
+    let computed = db.computed;
+    // A package and its definitions are only processed once
+    assert_eq!(db.check_package(&path).unwrap(), outcomes);
+    assert_eq!(db.computed, computed);
+    let typ = db.type_of(&path, "two").unwrap();
+    assert_eq!(format!("{}", typ), "#Natural");
+    assert_eq!(db.computed, computed);
+    // Editing `two` reparses the package and checks `two` alone again
+    write("\"two\"");
+    let outcomes = db.check_package(&path).unwrap();
+    assert_eq!(db.computed, computed + 2);
+    let failed: Vec<&String> = outcomes
+      .iter()
+      .filter(|(_, res)| res.is_err())
+      .map(|(name, _)| name)
+      .collect();
+    assert_eq!(failed, vec!["two"]);
+    assert!(matches!(db.type_of(&path, "two"), Err(QueryError::Check(_))));
+    assert_eq!(
+      db.type_of(&path, "three"),
+      Err(QueryError::UndefinedReference(String::from("three")))
+    );
+  }
+}
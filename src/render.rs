@@ -0,0 +1,218 @@
+use crate::{
+  config::{
+    self,
+    Color,
+  },
+  core::check::CheckError,
+  explain::Code,
+  warning::Warning,
+};
+
+use std::{
+  env,
+  fs,
+  path::Path,
+};
+
+pub mod diagnostic;
+
+use diagnostic::Diagnostic;
+
+// Rendering diagnostics for people, shared by the command line and the REPL.
+// A diagnostic's message is wrapped to the width of the terminal, as given by
+// `COLUMNS`, and its marks are colored if the configured `Color` says so for
+// the stream it is printed to, which, when it is `auto`, it does not if
+// `NO_COLOR` is set. An error in a file is followed by the lines it is on,
+// and those of what it is because of, with each part underlined and labeled,
+// as laid out in `diagnostic`.
+
+// The width wrapped to when the terminal's is not known
+pub const DEFAULT_WIDTH: usize = 80;
+
+// The narrowest width wrapped to, however narrow the terminal
+const MIN_WIDTH: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+  Stdout,
+  Stderr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+  pub color: bool,
+  pub width: usize,
+}
+
+// The width of the terminal, by `COLUMNS`
+pub fn width() -> usize {
+  env::var("COLUMNS")
+    .ok()
+    .and_then(|cols| cols.parse().ok())
+    .unwrap_or(DEFAULT_WIDTH)
+    .max(MIN_WIDTH)
+}
+
+impl Style {
+  // The style of what is printed to `stream`, as configured
+  pub fn of(stream: Stream) -> Self {
+    let terminal = match stream {
+      Stream::Stdout => atty::is(atty::Stream::Stdout),
+      Stream::Stderr => atty::is(atty::Stream::Stderr),
+    };
+    let color = config::get().color.unwrap_or(Color::Auto).enabled(terminal);
+    Style { color, width: width() }
+  }
+
+  // `s` in the ANSI color `code`, if colors are used
+  pub fn paint(&self, code: &str, s: &str) -> String {
+    if self.color {
+      format!("\x1b[{}m{}\x1b[0m", code, s)
+    }
+    else {
+      String::from(s)
+    }
+  }
+}
+
+// Wrap each line of `text` at spaces to `width` characters, where it can be,
+// indenting the lines it is wrapped onto two spaces past its own indentation
+pub fn wrap(text: &str, width: usize) -> String {
+  let mut out = Vec::new();
+  for line in text.lines() {
+    let body = line.trim_start_matches(' ');
+    let indent = line.len() - body.len();
+    let mut row = String::from(&line[..indent]);
+    let mut len = indent;
+    let mut empty = true;
+    for word in body.split(' ') {
+      let size = word.chars().count();
+      if !empty && len + 1 + size > width {
+        out.push(row);
+        row = " ".repeat(indent + 2);
+        len = indent + 2;
+        empty = true;
+      }
+      if !empty {
+        row.push(' ');
+        len += 1;
+      }
+      row.push_str(word);
+      len += size;
+      empty = false;
+    }
+    out.push(row);
+  }
+  out.join("\n")
+}
+
+// An error, with its message wrapped
+pub fn error(style: Style, message: &str) -> String {
+  let text = wrap(&format!("error: {}", message), style.width);
+  format!("{}{}", style.paint("1;31", "error"), &text["error".len()..])
+}
+
+// A type error in the definition `name`, located in the file the definition
+// is declared in, along with the lines it and what it is because of are on
+pub fn check_error(
+  style: Style,
+  name: &str,
+  file: Option<&Path>,
+  e: &CheckError,
+) -> String {
+  let source = file.and_then(|file| fs::read_to_string(file).ok());
+  let head = format!("✗ {} [{}]: ", name, e.kind.code());
+  let diagnostic = Diagnostic::of_check(e);
+  diagnostic.render(style, (&head, "31"), file, source.as_deref())
+}
+
+// A file which does not parse at the line and column `at`, with the code of
+// the error, if it has one, along with the line it does not parse at
+pub fn parse_error(
+  style: Style,
+  file: &Path,
+  at: (u32, usize),
+  code: Option<Code>,
+  message: &str,
+) -> String {
+  let source = fs::read_to_string(file).ok();
+  let head = match code {
+    Some(code) => format!("error[{}]: ", code),
+    None => String::from("error: "),
+  };
+  let diagnostic = Diagnostic::of_parse(at, message);
+  diagnostic.render(style, (&head, "1;31"), Some(file), source.as_deref())
+}
+
+// A warning in `file`, whose source is `source`, as an error if `denied`,
+// along with the line it is on
+pub fn warning(
+  style: Style,
+  file: &Path,
+  source: &str,
+  warning: &Warning,
+  denied: bool,
+) -> String {
+  let (kind, code) =
+    if denied { ("error", "1;31") } else { ("warning", "1;33") };
+  let head = format!("{}[{}]: ", kind, warning.lint);
+  let mut diagnostic = Diagnostic::new(warning.message.as_str());
+  if let Some(pos) = warning.pos {
+    diagnostic = diagnostic.primary(pos, "");
+  }
+  diagnostic.render(style, (&head, code), Some(file), Some(source))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::core::check::CheckErrorKind;
+  use hashexpr::position::Pos;
+
+  #[test]
+  fn wraps() {
+    assert_eq!(wrap("a bb ccc dddd", 6), "a bb\n  ccc\n  dddd");
+    assert_eq!(wrap("  a bb ccc\nd", 8), "  a bb\n    ccc\nd");
+    assert_eq!(wrap("abcdefghij k", 4), "abcdefghij\n  k");
+    assert_eq!(wrap("λ λλ λλλ", 6), "λ λλ\n  λλλ");
+    let plain = Style { color: false, width: 14 };
+    assert_eq!(error(plain, "No such file"), "error: No such\n  file");
+    let color = Style { color: true, width: 80 };
+    assert_eq!(error(color, "x"), "\x1b[1;31merror\x1b[0m: x");
+  }
+
+  #[test]
+  fn renders_check_errors() {
+    let dir = env::temp_dir().join("yatima-render");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("Render.ya");
+    fs::write(&file, "package Render where\ndef x : Type = yy\n").unwrap();
+    let pos = Pos {
+      from_offset: 36,
+      from_line: 2,
+      from_column: 16,
+      upto_offset: 38,
+      upto_line: 2,
+      upto_column: 18,
+    };
+    let kind = CheckErrorKind::UnboundVariable(String::from("yy"), 0);
+    let e = CheckError::new(Some(pos), kind);
+    let wide = Style { color: false, width: 1000 };
+    let out = check_error(wide, "x", Some(&file), &e);
+    let lines: Vec<&str> = out.lines().collect();
+    let message = CheckError::new(None, e.kind.clone());
+    assert_eq!(lines[0], format!("✗ x [Y0101]: {}", message));
+    assert_eq!(lines[1], format!("  --> {}:2:16", file.display()));
+    assert_eq!(lines[2], "  |");
+    assert_eq!(lines[3], "2 | def x : Type = yy");
+    assert_eq!(lines[4], "  |                ^^");
+    let color = Style { color: true, width: 1000 };
+    let out = check_error(color, "x", Some(&file), &e);
+    assert!(out.starts_with("\x1b[31m✗ x [Y0101]: \x1b[0m"));
+    assert!(out.ends_with("\x1b[31m^^\x1b[0m"));
+    let e = CheckError::new(None, e.kind);
+    let out = check_error(wide, "x", None, &e);
+    assert!(out.starts_with("✗ x [Y0101]: Error"), "{}", out);
+    assert!(!out.contains(" | "));
+  }
+}
@@ -0,0 +1,231 @@
+use crate::{
+  core::check::{
+    CheckError,
+    CheckErrorKind,
+  },
+  print::{
+    self,
+    Options,
+  },
+  render::{
+    wrap,
+    Style,
+  },
+};
+
+use hashexpr::position::Pos;
+
+use std::path::Path;
+
+// Diagnostics rendered as `codespan` renders them: the message, then where
+// it is, then the lines of the source its labels are on, each followed by a
+// row for each label on it, underlining its span and saying what it is. The
+// primary label, underlined with `^`, is where the error is, and secondary
+// ones, underlined with `-`, are the places which it is because of, such as
+// the annotation of a type which is not the one found:
+//
+//   error: Type mismatch
+//     --> Math.ya:2:22
+//     |
+//   2 | def two : #Natural = "two"
+//     |           -------- expected because of the annotation here
+//     |                      ^^^^^ has type #Text
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+  pub pos: Pos,
+  pub message: String,
+  pub primary: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+  pub message: String,
+  pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+  pub fn new(message: impl Into<String>) -> Self {
+    Diagnostic { message: message.into(), labels: Vec::new() }
+  }
+
+  pub fn primary(mut self, pos: Pos, message: impl Into<String>) -> Self {
+    self.labels.push(Label { pos, message: message.into(), primary: true });
+    self
+  }
+
+  pub fn secondary(mut self, pos: Pos, message: impl Into<String>) -> Self {
+    self.labels.push(Label { pos, message: message.into(), primary: false });
+    self
+  }
+
+  // The diagnostic of a type error, without its position in its message, as
+  // its labels show it
+  pub fn of_check(e: &CheckError) -> Self {
+    let message = CheckError { pos: None, ..e.clone() }.to_string();
+    let mut diagnostic = Diagnostic::new(message);
+    if let Some(pos) = e.pos {
+      let label = match &e.kind {
+        CheckErrorKind::TypeMismatch(_, _, found) => {
+          format!("has type {}", print::pretty(found, &Options::message()))
+        }
+        _ => String::new(),
+      };
+      diagnostic = diagnostic.primary(pos, label);
+    }
+    if let CheckErrorKind::UnfilledHoles(goals) = &e.kind {
+      for goal in goals {
+        if let Some(pos) = goal.pos {
+          diagnostic = diagnostic.secondary(pos, format!("?{}", goal.name));
+        }
+      }
+    }
+    for (pos, why) in &e.related {
+      diagnostic = diagnostic.secondary(*pos, why.as_str());
+    }
+    diagnostic
+  }
+
+  // The diagnostic of a source which does not parse at the line and column
+  // `at`, and why
+  pub fn of_parse(at: (u32, usize), message: &str) -> Self {
+    let (line, column) = (u64::from(at.0), at.1 as u64);
+    let pos = Pos {
+      from_offset: 0,
+      from_line: line,
+      from_column: column,
+      upto_offset: 0,
+      upto_line: line,
+      upto_column: column + 1,
+    };
+    Diagnostic::new(message).primary(pos, "")
+  }
+
+  // The primary label, or else the first
+  fn main_label(&self) -> Option<&Label> {
+    self.labels.iter().find(|l| l.primary).or_else(|| self.labels.first())
+  }
+
+  // Render the diagnostic, after `head`, which is painted in `code`, with the
+  // lines of `source`, the contents of `file`, which its labels are on
+  pub fn render(
+    &self,
+    style: Style,
+    (head, code): (&str, &str),
+    file: Option<&Path>,
+    source: Option<&str>,
+  ) -> String {
+    let text = wrap(&format!("{}{}", head, self.message), style.width);
+    let mut out = format!("{}{}", style.paint(code, head), &text[head.len()..]);
+    let main = match (file, self.main_label()) {
+      (Some(file), Some(label)) => {
+        let (line, col) = (label.pos.from_line, label.pos.from_column);
+        Some(format!("{}:{}:{}", file.display(), line, col))
+      }
+      (Some(file), None) => Some(file.display().to_string()),
+      (None, _) => None,
+    };
+    let lines: Vec<&str> = source.map_or(Vec::new(), |s| s.lines().collect());
+    let mut labels: Vec<&Label> = self
+      .labels
+      .iter()
+      .filter(|l| (1..=lines.len() as u64).contains(&l.pos.from_line))
+      .collect();
+    labels.sort_by_key(|l| (l.pos.from_line, l.pos.from_column));
+    let last = labels.last().map_or(0, |l| l.pos.from_line);
+    let gutter = " ".repeat(format!("{}", last).len());
+    if let Some(main) = main {
+      out.push_str(&format!("\n{} --> {}", gutter, main));
+    }
+    if labels.is_empty() {
+      return out;
+    }
+    out.push_str(&format!("\n{} |", gutter));
+    let mut prev: Option<u64> = None;
+    for label in &labels {
+      let num = label.pos.from_line;
+      let line = lines[num as usize - 1];
+      if prev != Some(num) {
+        if prev.map_or(false, |prev| num > prev + 1) {
+          out.push_str("\n...");
+        }
+        let num = format!("{:>width$}", num, width = gutter.len());
+        out.push_str(&format!("\n{} | {}", num, line));
+        prev = Some(label.pos.from_line);
+      }
+      let from = (label.pos.from_column as usize).saturating_sub(1);
+      // The span, to the end of the line if it ends on a later one
+      let len = if label.pos.upto_line == label.pos.from_line {
+        (label.pos.upto_column as usize).saturating_sub(from + 1).max(1)
+      }
+      else {
+        line.chars().count().saturating_sub(from).max(1)
+      };
+      let (mark, code) = if label.primary { ("^", "31") } else { ("-", "34") };
+      let marks = if label.message.is_empty() {
+        mark.repeat(len)
+      }
+      else {
+        format!("{} {}", mark.repeat(len), label.message)
+      };
+      let marks = style.paint(code, &marks);
+      out.push_str(&format!("\n{} | {}{}", gutter, " ".repeat(from), marks));
+    }
+    out
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  fn pos(line: u64, from: u64, upto: u64) -> Pos {
+    Pos {
+      from_offset: 0,
+      from_line: line,
+      from_column: from,
+      upto_offset: 0,
+      upto_line: line,
+      upto_column: upto,
+    }
+  }
+
+  #[test]
+  fn renders_labels() {
+    let source = "package Math where\n\
+                  def two : #Natural = \"two\"\n\
+                  def one : #Natural = 1\n\
+                  def three : #Natural = #add two one\n";
+    let diagnostic = Diagnostic::new("Type mismatch")
+      .primary(pos(2, 22, 27), "has type #Text")
+      .secondary(pos(2, 11, 19), "expected because of the annotation here")
+      .secondary(pos(4, 29, 32), "used here");
+    let plain = Style { color: false, width: 1000 };
+    let file = Path::new("Math.ya");
+    let out =
+      diagnostic.render(plain, ("error: ", "1;31"), Some(file), Some(source));
+    let expected = vec![
+      "error: Type mismatch",
+      "  --> Math.ya:2:22",
+      "  |",
+      "2 | def two : #Natural = \"two\"",
+      "  |           -------- expected because of the annotation here",
+      "  |                      ^^^^^ has type #Text",
+      "...",
+      "4 | def three : #Natural = #add two one",
+      "  |                             --- used here",
+    ];
+    assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+    let color = Style { color: true, width: 1000 };
+    let out =
+      diagnostic.render(color, ("error: ", "1;31"), Some(file), Some(source));
+    assert!(out.contains("\x1b[31m^^^^^ has type #Text\x1b[0m"));
+    assert!(out.contains("\x1b[34m--- used here\x1b[0m"));
+    // Without the source, only where the diagnostic is is shown
+    let out = diagnostic.render(plain, ("error: ", "1;31"), Some(file), None);
+    assert_eq!(out, "error: Type mismatch\n  --> Math.ya:2:22");
+    let out = Diagnostic::of_parse((3, 5), "Expected a term")
+      .render(plain, ("error: ", "1;31"), None, Some(source));
+    assert_eq!(out.lines().nth(3), Some("  |     ^"));
+  }
+}
@@ -8,50 +8,311 @@ use rustyline::{
   KeyEvent,
 };
 
-use im::HashMap;
+use im::Vector;
 
-use nom::Err;
+use std::{
+  fmt,
+  path::{
+    Path,
+    PathBuf,
+  },
+};
 
 use crate::{
+  config,
   core::{
+    auto,
+    check::infer_term,
     dag::DAG,
-    eval::norm,
+    eval::{
+      eval,
+      validate,
+    },
+    gas::{
+      self,
+      Gas,
+    },
+    profile::profile,
+    smallstep::trace,
+    stepper::{
+      Stepper,
+      Stop,
+    },
   },
   package::Declaration,
-  parse::term::parse,
+  parse::{
+    error::{
+      ParseError,
+      ParseErrorKind,
+    },
+    package::locate,
+    span::Span,
+    term::{
+      parse_expression,
+      Ops,
+    },
+  },
+  print::{
+    self,
+    Options,
+  },
+  render::{
+    self,
+    Stream,
+    Style,
+  },
+  term::{
+    Defs,
+    Refs,
+    Term,
+  },
 };
 
-pub fn main() -> rustyline::Result<()> {
-  let config = Config::builder().edit_mode(EditMode::Vi).build();
-  let mut rl = Editor::<()>::with_config(config);
-  let mut defs = HashMap::new();
+pub mod session;
+
+use session::Session;
+
+// The maximum number of steps shown by `:trace`
+const TRACE_LIMIT: usize = 1000;
+
+// Print an error, rendered for the terminal
+fn print_error(e: impl fmt::Display) {
+  println!("{}", render::error(Style::of(Stream::Stdout), &e.to_string()));
+}
+
+// Parse an expression in the scope of the session and elaborate it,
+// printing any errors
+fn parse_line(session: &Session, line: &str) -> Option<Term> {
+  session.parse(line).map_err(print_error).ok()
+}
+
+// A term as it is shown, laid out to the width of the terminal
+fn show(term: &Term) -> String { print::pretty(term, &Options::terminal()) }
+
+// The graph of a term, to be evaluated with `defs`, or none, printing why,
+// if the term cannot be evaluated with them
+fn graph(defs: &Defs, term: Term) -> Option<DAG> {
+  match validate(defs, &term).and_then(|()| DAG::from_term(term)) {
+    Ok(dag) => Some(dag),
+    Err(e) => {
+      print_error(e);
+      None
+    }
+  }
+}
+
+// Search for a term to fill the hole `?name` of the expression `line`
+fn fill_hole(defs: &Defs, refs: &Refs, line: &str, name: &str) {
+  let from = Span::new(line);
+  match parse_expression(refs.clone(), Ops::new(), Vector::new())(from) {
+    Ok((upto, term)) => match auto::fill(defs, &term, name) {
+      Ok(Some(term)) => println!("?{} := {}", name, show(&term)),
+      Ok(None) => println!("No term found for ?{}", name),
+      Err(e) => {
+        let at = locate(from, upto, e.pos);
+        print_error(ParseError::new(at, ParseErrorKind::ElabError(e)));
+      }
+    },
+    Err(_) => print_error(format!("Could not parse {}", line)),
+  }
+}
+
+fn print_stop(stepper: &Stepper, stop: Stop) {
+  match stop {
+    Stop::Normal => println!("{}", show(&stepper.term)),
+    Stop::Breakpoint(nam, link) => {
+      println!("Breakpoint: about to unfold {} ({})", nam, link);
+      println!("{}", show(&stepper.term));
+    }
+    Stop::OutOfFuel(fuel) => {
+      println!("Stopped after {} steps without reaching normal form", fuel);
+      println!("{}", show(&stepper.term));
+    }
+  }
+}
+
+fn debug_command(
+  stepper: &mut Option<Stepper>,
+  cmd: &str,
+  arg: &str,
+) -> bool {
+  let stepper = match stepper {
+    Some(stepper) => stepper,
+    None => {
+      println!("No term is being debugged, start with `:debug <expr>`");
+      return true;
+    }
+  };
+  match cmd {
+    "break" => stepper.add_breakpoint(String::from(arg)),
+    "delete" => {
+      if !stepper.remove_breakpoint(arg) {
+        println!("No breakpoint on {}", arg);
+      }
+    }
+    "step" | "next" => {
+      let rule =
+        if cmd == "step" { Ok(stepper.step()) } else { stepper.step_over() };
+      match rule {
+        Ok(Some(rule)) => println!("{}\n{}", rule, show(&stepper.term)),
+        Ok(None) => println!("Normal form reached"),
+        Err(stop) => print_stop(stepper, stop),
+      }
+    }
+    "continue" => {
+      let stop = stepper.resume();
+      print_stop(stepper, stop);
+    }
+    "term" => println!("{}", show(&stepper.term)),
+    "dag" => {
+      if let Some(dag) = graph(&stepper.defs, stepper.term.clone()) {
+        println!("{:?}", dag);
+      }
+    }
+    _ => return false,
+  }
+  true
+}
+
+// Load the package in the file `path` into the session's scope, printing
+// any error
+fn load(session: &mut Session, path: &Path) {
+  match session.load(path) {
+    Ok(name) => println!("Loaded package {}", name),
+    Err(e) => print_error(e),
+  }
+}
+
+// Start the REPL, loading the packages `:load` is given relative to `dir`,
+// and keeping a history of what is entered if `history`
+pub fn main(dir: Option<PathBuf>, history: bool) -> rustyline::Result<()> {
+  let settings = config::get();
+  let edit_mode = match settings.edit_mode {
+    Some(config::EditMode::Emacs) => EditMode::Emacs,
+    Some(config::EditMode::Vi) | None => EditMode::Vi,
+  };
+  let history = history.then(|| {
+    settings.history.clone().unwrap_or_else(|| PathBuf::from("history.txt"))
+  });
+  let mut rl = Editor::<()>::with_config(
+    Config::builder().edit_mode(edit_mode).build(),
+  );
+  let mut session = Session::new(dir);
+  for path in &settings.load {
+    load(&mut session, path);
+  }
+  let mut stepper: Option<Stepper> = None;
+  // The last expression entered, whose holes `:fill` fills
+  let mut last: Option<String> = None;
   let mut _decls: Vec<Declaration> = Vec::new();
   rl.bind_sequence(KeyEvent::alt('l'), Cmd::Insert(1, String::from("λ ")));
   rl.bind_sequence(KeyEvent::alt('a'), Cmd::Insert(1, String::from("∀ ")));
-  if rl.load_history("history.txt").is_err() {
-    println!("No previous history.");
+  if let Some(history) = &history {
+    if rl.load_history(history).is_err() {
+      println!("No previous history.");
+    }
   }
   loop {
     let readline = rl.readline("⅄ ");
     match readline {
       Ok(line) => {
         rl.add_history_entry(line.as_str());
-        let res = parse(&line);
-        match res {
-          Ok((_, term)) => {
-            println!("{}", norm(&defs, DAG::from_term(term)));
-          }
-          Err(e) => match e {
-            Err::Incomplete(_) => println!("Incomplete"),
-            Err::Failure(e) => {
-              println!("Parse Failure:\n");
-              println!("{}", e);
+        if let Some(command) = line.trim().strip_prefix(":") {
+          let (cmd, arg) = match command.find(char::is_whitespace) {
+            Some(idx) => (&command[..idx], command[idx..].trim()),
+            None => (command, ""),
+          };
+          match cmd {
+            "load" => load(&mut session, Path::new(arg)),
+            "gas" => {
+              let (limit, expr) = match arg.find(char::is_whitespace) {
+                Some(idx) => (&arg[..idx], &arg[idx..]),
+                None => (arg, ""),
+              };
+              match limit.parse::<u64>() {
+                Ok(limit) => {
+                  let term = parse_line(&session, expr);
+                  let dag = term.and_then(|term| graph(&session.defs, term));
+                  if let Some(dag) = dag {
+                    let mut gas = Gas::new(limit);
+                    match gas::norm(&session.defs, dag, &mut gas) {
+                      Ok(dag) => println!("{}", show(&dag.to_term())),
+                      Err(e) => print_error(e),
+                    }
+                    println!("Gas used: {}", gas.used);
+                  }
+                }
+                Err(_) => println!("Usage: :gas <limit> <expr>"),
+              }
+            }
+            "type" => {
+              last = Some(String::from(arg));
+              if let Some(term) = parse_line(&session, arg) {
+                match infer_term(&session.defs, &term) {
+                  Ok(typ) => println!("{} :: {}", show(&term), show(&typ)),
+                  Err(e) => print_error(e),
+                }
+              }
+            }
+            "fill" => match (arg.strip_prefix('?'), &last) {
+              (Some(name), Some(line)) => {
+                fill_hole(&session.defs, &session.refs, line, name)
+              }
+              (Some(_), None) => println!("No expression to fill a hole in"),
+              (None, _) => println!("Usage: :fill ?<hole>"),
+            },
+            "profile" => {
+              let term = parse_line(&session, arg);
+              if let Some(dag) = term.and_then(|t| graph(&session.defs, t)) {
+                match profile(&session.defs, dag) {
+                  Ok((dag, report)) => {
+                    println!("{}\n\n{}", show(&dag.to_term()), report)
+                  }
+                  Err(e) => print_error(e),
+                }
+              }
+            }
+            "trace" => {
+              if let Some(term) = parse_line(&session, arg) {
+                let defs = &session.defs;
+                for (rule, term) in trace(defs, &term, TRACE_LIMIT) {
+                  println!("--> [{}] {}", rule.name(), show(&term));
+                }
+              }
+            }
+            "debug" => {
+              if let Some(term) = parse_line(&session, arg) {
+                let mut new = Stepper::new(session.defs.clone(), term);
+                if let Some(old) = stepper {
+                  new.breakpoints = old.breakpoints;
+                }
+                println!("{}", show(&new.term));
+                stepper = Some(new);
+              }
+            }
+            _ => {
+              if !debug_command(&mut stepper, cmd, arg) {
+                println!("Unknown command :{}", cmd);
+              }
             }
-            Err::Error(e) => {
-              println!("Parse Error:\n");
-              println!("{}", e);
+          }
+        }
+        else if Session::is_declaration(&line) {
+          match session.declare(&line) {
+            Ok(names) => println!("Declared {}", names.join(", ")),
+            Err(e) => print_error(e),
+          }
+        }
+        else {
+          last = Some(line.clone());
+          // An expression which cannot be evaluated is reported like any
+          // other error, and the session goes on
+          if let Some(term) = parse_line(&session, &line) {
+            match eval(&session.defs, &term, &mut ()) {
+              Ok(term) => println!("{}", show(&term)),
+              Err(e) => print_error(e),
             }
-          },
+          }
         }
       }
       Err(ReadlineError::Interrupted) => {
@@ -68,5 +329,9 @@ pub fn main() -> rustyline::Result<()> {
       }
     }
   }
-  rl.save_history("history.txt")
+  match history {
+    Some(history) => rl.save_history(&history),
+    None => Ok(()),
+  }
 }
+
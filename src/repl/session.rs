@@ -0,0 +1,289 @@
+use crate::{
+  core::{
+    check::{
+      check_def,
+      infer_term,
+    },
+    eval::eval,
+  },
+  package::Declaration,
+  parse::{
+    error::ParseErrorKind,
+    package::{
+      docs,
+      parse_codata,
+      parse_data,
+      parse_defns,
+      parse_input,
+      parse_record,
+      parse_signature,
+      Sigs,
+    },
+    prelude::prelude,
+    span::Span,
+    term::{
+      parse_space,
+      Ops,
+    },
+  },
+  query::Database,
+  term::{
+    Def,
+    Defs,
+    Refs,
+    Term,
+  },
+};
+
+use nom::Err;
+
+use std::path::{
+  Path,
+  PathBuf,
+};
+
+// A REPL session, to be embedded in what reads the input and shows the
+// results: the scope, which starts as the prelude and which loaded packages
+// and entered declarations extend, and the evaluation of what is entered in
+// it. What is entered is either declarations, each in scope of those before
+// it, or an expression, which is checked and normalized. A declaration of a
+// name already in scope replaces it, so that what was entered can be entered
+// again once changed. A signature entered on its own gives the type of a
+// definition entered after it, as in a package.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+  // The names declared, in order
+  Declared(Vec<String>),
+  // The normal form of an expression, and its type
+  Value(Term, Term),
+}
+
+#[derive(Clone, Debug)]
+pub struct Session {
+  pub defs: Defs,
+  pub refs: Refs,
+  // The signatures whose definitions are yet to be entered
+  pub sigs: Sigs,
+  // The directory packages are loaded relative to, if not the current one
+  pub dir: Option<PathBuf>,
+}
+
+// The keywords a declaration may start with
+const DECLARATIONS: [&str; 9] = [
+  "def",
+  "sig",
+  "private",
+  "partial",
+  "opaque",
+  "reducible",
+  "data",
+  "codata",
+  "record",
+];
+
+impl Session {
+  pub fn new(dir: Option<PathBuf>) -> Self {
+    let (defs, refs) = prelude();
+    Session { defs, refs, sigs: Sigs::new(), dir }
+  }
+
+  // Load the package in the file `path` into scope, returning its name
+  pub fn load(&mut self, path: &Path) -> Result<String, String> {
+    let path = match &self.dir {
+      Some(dir) => dir.join(path),
+      None => path.to_path_buf(),
+    };
+    let parsed = Database::new().parse(&path).map_err(|e| e.to_string())?;
+    self.defs = self.defs.clone().union(parsed.defs);
+    self.refs = self.refs.clone().union(parsed.refs);
+    Ok(parsed.package.name)
+  }
+
+  // The keyword `input` starts with, after any comments
+  fn keyword(input: &str) -> &str {
+    let input = match parse_space(Span::new(input)) {
+      Ok((i, _)) => *i.fragment(),
+      Err(_) => input,
+    };
+    input.split_whitespace().next().unwrap_or("")
+  }
+
+  // Whether `input` is declarations rather than an expression
+  pub fn is_declaration(input: &str) -> bool {
+    DECLARATIONS.contains(&Session::keyword(input))
+  }
+
+  // Parse an expression in scope and elaborate it
+  pub fn parse(&self, input: &str) -> Result<Term, String> {
+    parse_input(&self.defs, &self.refs, input)
+  }
+
+  pub fn infer(&self, term: &Term) -> Result<Term, String> {
+    infer_term(&self.defs, term).map_err(|e| e.to_string())
+  }
+
+  pub fn normalize(&self, term: Term) -> Result<Term, String> {
+    eval(&self.defs, &term, &mut ()).map_err(|e| e.to_string())
+  }
+
+  // Parse and check the declarations of `input` and bring them into scope,
+  // returning their names. The declarations before one which does not parse
+  // or check stay in scope.
+  pub fn declare(&mut self, input: &str) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    let mut i = Span::new(input);
+    loop {
+      let (i2, comments) = parse_space(i).map_err(|e| e.to_string())?;
+      if i2.fragment().is_empty() {
+        return Ok(names);
+      }
+      let comments: Vec<&str> =
+        comments.iter().map(|c| *c.fragment()).collect();
+      let docs = docs(&comments);
+      let (defs, refs, ops) = (&self.defs, &self.refs, &Ops::new());
+      let sigs = &self.sigs;
+      // A signature declares no definition, but the type of one after it
+      let mut sig = None;
+      let decls = match Session::keyword(i2.fragment()) {
+        "sig" => parse_signature(refs, ops, sigs, docs)(i2).map(|(i, s)| {
+          sig = Some(s);
+          (i, Vec::new())
+        }),
+        "data" => parse_data(defs, refs, ops, docs)(i2),
+        "codata" => parse_codata(defs, refs, ops, docs)(i2),
+        "record" => parse_record(defs, refs, ops, docs)(i2),
+        _ => parse_defns(defs, refs, ops, sigs, docs)(i2),
+      };
+      let (i3, decls) = match decls {
+        Ok(res) => res,
+        Err(Err::Error(e)) | Err(Err::Failure(e))
+          if e.errors.iter().any(|e| self.shadows(e)) =>
+        {
+          // Parse the declaration again, without what it replaces in scope
+          for e in &e.errors {
+            if let ParseErrorKind::TopLevelRedefinition(name) = e {
+              self.refs.remove(name.as_str());
+              self.sigs.remove(name.as_str());
+            }
+          }
+          continue;
+        }
+        Err(Err::Incomplete(_)) => {
+          return Err(String::from("Incomplete input"));
+        }
+        Err(Err::Failure(e)) | Err(Err::Error(e)) => {
+          return Err(e.to_string());
+        }
+      };
+      if let Some((name, sig)) = sig {
+        self.sigs.insert(name, sig);
+      }
+      for decl in decls {
+        if let Declaration::Defn { name, defn, term, positions, .. } = decl {
+          let def = Def::get_positioned(defn, positions)
+            .map_err(|e| format!("{:?}", e))?;
+          self.defs.insert(defn, def.clone());
+          check_def(&self.defs, &def).map_err(|e| e.to_string())?;
+          self.sigs.remove(&name);
+          self.refs.insert(name.clone(), (defn, term));
+          names.push(String::from(name));
+        }
+      }
+      i = i3;
+    }
+  }
+
+  // Whether a parse error is of a declaration of a name in scope
+  fn shadows(&self, e: &ParseErrorKind) -> bool {
+    match e {
+      ParseErrorKind::TopLevelRedefinition(name) => {
+        self.refs.contains_key(name.as_str())
+          || self.sigs.contains_key(name.as_str())
+      }
+      _ => false,
+    }
+  }
+
+  // Evaluate what is entered, declarations or an expression
+  pub fn eval(&mut self, input: &str) -> Result<Output, String> {
+    if Session::is_declaration(input) {
+      self.declare(input).map(Output::Declared)
+    }
+    else {
+      let term = self.parse(input)?;
+      let typ = self.infer(&term)?;
+      Ok(Output::Value(self.normalize(term)?, typ))
+    }
+  }
+
+  // The names in scope which start with `prefix`, in order
+  pub fn complete(&self, prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = self
+      .refs
+      .keys()
+      .filter(|name| name.starts_with(prefix))
+      .map(|name| name.to_string())
+      .collect();
+    names.sort();
+    names
+  }
+
+  // The definition `name` refers to, if it is in scope
+  pub fn lookup(&self, name: &str) -> Option<&Def> {
+    let (defn, _) = self.refs.get(name)?;
+    self.defs.get(defn)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn evaluates_cells() {
+    let mut session = Session::new(None);
+    let cell = "// Two\ndef two : #Natural = 2\n\
+                def four : #Natural = #mul two two";
+    assert_eq!(
+      session.eval(cell),
+      Ok(Output::Declared(vec![String::from("two"), String::from("four")]))
+    );
+    match session.eval("#add four 1") {
+      Ok(Output::Value(val, typ)) => {
+        assert_eq!(format!("{}", val), "5");
+        assert_eq!(format!("{}", typ), "#Natural");
+      }
+      res => panic!("{:?}", res),
+    }
+    let docs = session.lookup("two").map(|def| def.docs.clone());
+    assert_eq!(docs, Some(String::from("Two")));
+    assert_eq!(session.complete("fo"), vec![String::from("four")]);
+    assert!(session.eval("#add four").is_err());
+    assert!(session.eval("def bad : #Natural = \"bad\"").is_err());
+    let again = session.eval("def two : #Natural = 3");
+    assert_eq!(again, Ok(Output::Declared(vec![String::from("two")])));
+    match session.eval("two") {
+      Ok(Output::Value(val, _)) => assert_eq!(format!("{}", val), "3"),
+      res => panic!("{:?}", res),
+    }
+    assert!(session.eval("unknown").is_err());
+  }
+
+  #[test]
+  fn pairs_signatures_with_definitions() {
+    let mut session = Session::new(None);
+    let sig = "// Doubled\nsig double : ∀ (n: #Natural) -> #Natural";
+    assert_eq!(session.eval(sig), Ok(Output::Declared(Vec::new())));
+    assert!(session.eval("def double n = #add n n").is_ok());
+    match session.eval("double 2") {
+      Ok(Output::Value(val, _)) => assert_eq!(format!("{}", val), "4"),
+      res => panic!("{:?}", res),
+    }
+    let docs = session.lookup("double").map(|def| def.docs.clone());
+    assert_eq!(docs, Some(String::from("Doubled")));
+    assert!(session.sigs.is_empty());
+    // A definition without a type needs a signature before it
+    assert!(session.eval("def triple n = #mul n 3").is_err());
+  }
+}
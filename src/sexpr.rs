@@ -0,0 +1,306 @@
+use hashexpr::{
+  atom,
+  atom::Atom::*,
+  Expr,
+  Expr::*,
+  Link,
+};
+
+use crate::{
+  anon_term::AnonTerm,
+  decode_error::{
+    DecodeError,
+    Expected,
+  },
+  definition::Definition,
+  meta_term::MetaTerm,
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Def,
+    Term,
+  },
+  unembed_error::UnembedError,
+};
+
+use im::Vector;
+use std::fmt;
+
+// Everything stored in the hashspace as plain text, the S-expressions of
+// hashexpr, to be inspected, edited by hand and read back. What is read is
+// only accepted if encoding it again gives back the expression it was read
+// from, so that content read from its text is stored under the same link as
+// the content the text was written from. A term is its anonymous term and
+// its names, and a definition its metadata and the anonymous terms of its
+// type and term, so that both can be read without the hashspace.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SexprError {
+  Parse(String),
+  Trailing(String),
+  Decode(DecodeError),
+  Unembed(UnembedError),
+  // A definition whose type or term is not the anonymous term it links to
+  Mismatch(Link, Link),
+  // An expression which decodes, but encodes again differently, as the link
+  // it is read from and the link it would be stored under
+  NotCanonical(Link, Link),
+}
+
+impl fmt::Display for SexprError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Parse(e) => write!(f, "Cannot parse the expression: {}", e),
+      Self::Trailing(rest) => {
+        write!(f, "Unexpected input after the expression: {}", rest)
+      }
+      Self::Decode(e) => write!(f, "Cannot decode the expression: {}", e),
+      Self::Unembed(e) => write!(f, "Cannot read the term: {}", e),
+      Self::Mismatch(expected, found) => write!(
+        f,
+        "The definition links to {}, but its term is {}",
+        expected, found
+      ),
+      Self::NotCanonical(read, stored) => write!(
+        f,
+        "The expression {} would be stored as {}, as it is not in its \
+         canonical form",
+        read, stored
+      ),
+    }
+  }
+}
+
+impl std::error::Error for SexprError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Decode(e) => Some(e),
+      Self::Unembed(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<DecodeError> for SexprError {
+  fn from(e: DecodeError) -> Self { SexprError::Decode(e) }
+}
+
+impl From<UnembedError> for SexprError {
+  fn from(e: UnembedError) -> Self { SexprError::Unembed(e) }
+}
+
+// The one expression of `input`, which may be surrounded by whitespace
+pub fn parse(input: &str) -> Result<Expr, SexprError> {
+  match hashexpr::parse(input.trim()) {
+    Ok((rest, expr)) if rest.fragment().is_empty() => Ok(expr),
+    Ok((rest, _)) => Err(SexprError::Trailing(rest.fragment().to_string())),
+    Err(e) => Err(SexprError::Parse(format!("{}", e))),
+  }
+}
+
+// Read `input` with `decode`, as long as `encode` gives back what was read
+fn read<A>(
+  input: &str,
+  decode: impl Fn(Expr) -> Result<A, SexprError>,
+  encode: impl Fn(&A) -> Expr,
+) -> Result<A, SexprError> {
+  let expr = parse(input)?;
+  let link = expr.link();
+  let val = decode(expr)?;
+  let again = encode(&val).link();
+  if again != link {
+    Err(SexprError::NotCanonical(link, again))
+  }
+  else {
+    Ok(val)
+  }
+}
+
+impl AnonTerm {
+  pub fn to_sexpr_string(&self) -> String {
+    format!("{}", self.clone().encode())
+  }
+
+  pub fn from_sexpr_string(input: &str) -> Result<Self, SexprError> {
+    read(input, |x| Ok(AnonTerm::decode(x)?), |x| x.clone().encode())
+  }
+}
+
+impl MetaTerm {
+  pub fn to_sexpr_string(&self) -> String { format!("{}", self.encode()) }
+
+  pub fn from_sexpr_string(input: &str) -> Result<Self, SexprError> {
+    read(input, |x| Ok(MetaTerm::decode(x)?), |x| x.encode())
+  }
+}
+
+impl Definition {
+  pub fn to_sexpr_string(&self) -> String {
+    format!("{}", self.clone().encode())
+  }
+
+  pub fn from_sexpr_string(input: &str) -> Result<Self, SexprError> {
+    read(input, |x| Ok(Definition::decode(x)?), |x| x.clone().encode())
+  }
+}
+
+impl Declaration {
+  pub fn to_sexpr_string(&self) -> String {
+    format!("{}", self.clone().encode())
+  }
+
+  pub fn from_sexpr_string(input: &str) -> Result<Self, SexprError> {
+    read(input, |x| Ok(Declaration::decode(x)?), |x| x.clone().encode())
+  }
+}
+
+impl Package {
+  pub fn to_sexpr_string(&self) -> String {
+    format!("{}", self.clone().encode())
+  }
+
+  pub fn from_sexpr_string(input: &str) -> Result<Self, SexprError> {
+    read(input, |x| Ok(Package::decode(x)?), |x| x.clone().encode())
+  }
+}
+
+impl Term {
+  pub fn to_sexpr(&self) -> Expr {
+    let (anon, meta) = self.clone().embed();
+    cons!(None, text!("term"), anon.encode(), meta.encode())
+  }
+
+  pub fn to_sexpr_string(&self) -> String { format!("{}", self.to_sexpr()) }
+
+  pub fn from_sexpr_string(input: &str) -> Result<Self, SexprError> {
+    read(input, decode_term, |x| x.to_sexpr())
+  }
+}
+
+fn decode_term(expr: Expr) -> Result<Term, SexprError> {
+  match expr {
+    Cons(pos, xs) => match xs.as_slice() {
+      [Atom(_, Text(c)), anon, meta] if *c == String::from("term") => {
+        let anon = AnonTerm::decode(anon.to_owned())?;
+        let meta = MetaTerm::decode(meta.to_owned())?;
+        Ok(Term::unembed(Vector::new(), &anon, &meta)?)
+      }
+      _ => Err(DecodeError::new(pos, vec![Expected::Term]).into()),
+    },
+    _ => Err(DecodeError::new(expr.position(), vec![Expected::Term]).into()),
+  }
+}
+
+impl Def {
+  pub fn to_sexpr(&self) -> Expr {
+    let (def, type_anon, term_anon) = self.clone().embed();
+    cons!(
+      None,
+      text!("definition"),
+      def.encode(),
+      type_anon.encode(),
+      term_anon.encode()
+    )
+  }
+
+  pub fn to_sexpr_string(&self) -> String { format!("{}", self.to_sexpr()) }
+
+  pub fn from_sexpr_string(input: &str) -> Result<Self, SexprError> {
+    read(input, decode_def, |x| x.to_sexpr())
+  }
+}
+
+fn decode_def(expr: Expr) -> Result<Def, SexprError> {
+  match expr {
+    Cons(pos, xs) => match xs.as_slice() {
+      [Atom(_, Text(c)), def, type_anon, term_anon]
+        if *c == String::from("definition") =>
+      {
+        let def = Definition::decode(def.to_owned())?;
+        if type_anon.link() != def.type_anon {
+          return Err(SexprError::Mismatch(def.type_anon, type_anon.link()));
+        }
+        if term_anon.link() != def.term_anon {
+          return Err(SexprError::Mismatch(def.term_anon, term_anon.link()));
+        }
+        let type_anon = AnonTerm::decode(type_anon.to_owned())?;
+        let term_anon = AnonTerm::decode(term_anon.to_owned())?;
+        Ok(Def::unembed(def, type_anon, term_anon)?)
+      }
+      _ => Err(DecodeError::new(pos, vec![Expected::TermDef]).into()),
+    },
+    _ => Err(DecodeError::new(expr.position(), vec![Expected::TermDef]).into()),
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    name::Name,
+    package::tests::test_package,
+    parse::term::parse,
+  };
+
+  #[quickcheck]
+  fn term_sexpr_round_trip(x: Term) -> bool {
+    match Term::from_sexpr_string(&x.to_sexpr_string()) {
+      Ok(y) => x == y && x.to_sexpr().link() == y.to_sexpr().link(),
+      Err(_) => false,
+    }
+  }
+
+  #[quickcheck]
+  fn def_sexpr_round_trip(x: Def) -> bool {
+    match Def::from_sexpr_string(&x.to_sexpr_string()) {
+      Ok(y) => x == y && x.to_sexpr().link() == y.to_sexpr().link(),
+      Err(_) => false,
+    }
+  }
+
+  #[test]
+  fn package_sexpr_round_trip() {
+    let pack = test_package();
+    let text = pack.to_sexpr_string();
+    let read = Package::from_sexpr_string(&format!("\n{}\n", text)).unwrap();
+    assert_eq!(read.clone().encode().link(), pack.clone().encode().link());
+    assert_eq!(read, pack);
+    for decl in &pack.decls {
+      let text = decl.to_sexpr_string();
+      assert_eq!(Declaration::from_sexpr_string(&text).as_ref(), Ok(decl));
+    }
+  }
+
+  #[test]
+  fn rejects_what_would_not_round_trip() {
+    let (_, term) = parse("λ x => x").unwrap();
+    let text = term.to_sexpr_string();
+    assert!(matches!(
+      Term::from_sexpr_string(&format!("{} 1", text)),
+      Err(SexprError::Trailing(_))
+    ));
+    // A leaf of naming metadata is empty, but any bits decode as one
+    let leaf = MetaTerm::Leaf.to_sexpr_string();
+    assert_eq!(MetaTerm::from_sexpr_string(&leaf), Ok(MetaTerm::Leaf));
+    let bits = format!("{}", bits!(vec![1]));
+    assert!(matches!(
+      MetaTerm::from_sexpr_string(&bits),
+      Err(SexprError::NotCanonical(..))
+    ));
+    let (_, typ) = parse("Type").unwrap();
+    let def = Def::new(None, Name::from("id"), String::new(), typ, term);
+    let (d, type_anon, _) = def.embed();
+    let text = format!(
+      "(\"definition\" {} {} {})",
+      d.encode(),
+      type_anon.clone().encode(),
+      type_anon.encode()
+    );
+    assert!(matches!(
+      Def::from_sexpr_string(&text),
+      Err(SexprError::Mismatch(..))
+    ));
+  }
+}
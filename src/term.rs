@@ -4,8 +4,14 @@ use crate::{
     DecodeError,
     Expected,
   },
-  definition::Definition,
+  definition::{
+    Definition,
+    Transparency,
+  },
   hashspace,
+  name::Name,
+  positions::Positions,
+  print,
   unembed_error::UnembedError,
 };
 
@@ -37,31 +43,55 @@ use im::{
   Vector,
 };
 
-use std::fmt;
+use std::{
+  convert::TryInto,
+  fmt,
+};
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Term {
-  Var(Option<Pos>, String, u64),
-  Lam(Option<Pos>, String, Box<Term>),
+  Var(Option<Pos>, Name, u64),
+  Lam(Option<Pos>, Name, Box<Term>),
   App(Option<Pos>, Box<(Term, Term)>),
-  All(Option<Pos>, Uses, String, Box<(Term, Term)>),
-  Slf(Option<Pos>, String, Box<Term>),
+  All(Option<Pos>, Uses, Name, Box<(Term, Term)>),
+  Slf(Option<Pos>, Name, Box<Term>),
   Dat(Option<Pos>, Box<Term>),
   Cse(Option<Pos>, Box<Term>),
-  Ref(Option<Pos>, String, Link, Link),
-  Let(Option<Pos>, bool, Uses, String, Box<(Term, Term, Term)>),
-  Typ(Option<Pos>),
+  // A dependent pair type `Σ (x: A) -> B`, its pairs `(a, b)`, and their
+  // first and second projections
+  Sig(Option<Pos>, Name, Box<(Term, Term)>),
+  Par(Option<Pos>, Box<(Term, Term)>),
+  Fst(Option<Pos>, Box<Term>),
+  Snd(Option<Pos>, Box<Term>),
+  Ref(Option<Pos>, Name, Link, Link),
+  Let(Option<Pos>, bool, Uses, Name, Box<(Term, Term, Term)>),
+  // A universe, at the given level
+  Typ(Option<Pos>, u64),
+  // The universe of propositions, whose proofs are irrelevant
+  Prp(Option<Pos>),
   Ann(Option<Pos>, Box<(Term, Term)>),
   Lit(Option<Pos>, Literal),
   LTy(Option<Pos>, LitType),
   Opr(Option<Pos>, PrimOp),
+  // A placeholder `_` for a term to be inferred by unification, or a named
+  // hole `?name` whose goal is reported by the type checker
+  Hol(Option<Pos>, Option<String>),
+  // A metavariable, created by the type checker for a placeholder
+  Met(Option<Pos>, u64),
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Def {
   pub pos: Option<Pos>,
-  pub name: String,
+  pub name: Name,
   pub docs: String,
+  pub transparency: Transparency,
+  // Whether each of the leading binders of the definition is implicit, so
+  // that its argument is left for elaboration to fill in at each use. Only
+  // the binders up to the last implicit one are listed.
+  pub implicits: Vec<bool>,
   pub typ_: Term,
   pub term: Term,
 }
@@ -69,6 +99,8 @@ pub struct Def {
 impl PartialEq for Def {
   fn eq(&self, other: &Def) -> bool {
     self.name == other.name
+      && self.transparency == other.transparency
+      && self.implicits == other.implicits
       && self.typ_ == other.typ_
       && self.term == other.term
   }
@@ -76,7 +108,10 @@ impl PartialEq for Def {
 
 /// A map of names to pairs of links. The first link points to the
 /// Definition, the second to the AnonTerm
-pub type Refs = HashMap<String, (Link, Link)>;
+///
+/// Both maps are persistent, so that a clone shares its structure with the
+/// original and each scope a declaration is parsed in costs no copy.
+pub type Refs = HashMap<Name, (Link, Link)>;
 pub type Defs = HashMap<Link, Def>;
 
 impl PartialEq for Term {
@@ -91,6 +126,12 @@ impl PartialEq for Term {
       (Self::Slf(_, na, ba), Self::Slf(_, nb, bb)) => na == nb && ba == bb,
       (Self::Dat(_, ba), Self::Dat(_, bb)) => ba == bb,
       (Self::Cse(_, ba), Self::Cse(_, bb)) => ba == bb,
+      (Self::Sig(_, na, ta), Self::Sig(_, nb, tb)) => {
+        na == nb && ta.0 == tb.0 && ta.1 == tb.1
+      }
+      (Self::Par(_, ta), Self::Par(_, tb)) => ta.0 == tb.0 && ta.1 == tb.1,
+      (Self::Fst(_, ba), Self::Fst(_, bb)) => ba == bb,
+      (Self::Snd(_, ba), Self::Snd(_, bb)) => ba == bb,
       (Self::Ref(_, na, da, aa), Self::Ref(_, nb, db, ab)) => {
         na == nb && da == db && aa == ab
       }
@@ -98,11 +139,14 @@ impl PartialEq for Term {
         Self::Let(_, ra, ua, na, ta),
         Self::Let(_, rb, ub, nb, tb),
       ) => ra == rb && ua == ub && na == nb && ta.0 == tb.0 && ta.1 == tb.1 && ta.2 == tb.2,
-      (Self::Typ(_), Self::Typ(_)) => true,
+      (Self::Typ(_, la), Self::Typ(_, lb)) => la == lb,
+      (Self::Prp(_), Self::Prp(_)) => true,
       (Self::Ann(_, ta), Self::Ann(_, tb)) => ta.0 == tb.0 && ta.1 == tb.1,
       (Self::Lit(_, a), Self::Lit(_, b)) => a == b,
       (Self::LTy(_, a), Self::LTy(_, b)) => a == b,
       (Self::Opr(_, a), Self::Opr(_, b)) => a == b,
+      (Self::Hol(_, a), Self::Hol(_, b)) => a == b,
+      (Self::Met(_, a), Self::Met(_, b)) => a == b,
       _ => false,
     }
   }
@@ -110,100 +154,167 @@ impl PartialEq for Term {
 
 impl fmt::Display for Term {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    use Term::*;
-    const WILDCARD: &str = "_";
-
-    fn name(nam: &str) -> &str { if nam.is_empty() { WILDCARD } else { nam } }
+    write!(f, "{}", print::pretty(self, &print::Options::line()))
+  }
+}
 
-    fn uses(uses: &Uses) -> &str {
-      match uses {
-        Uses::None => "0 ",
-        Uses::Affi => "& ",
-        Uses::Once => "1 ",
-        Uses::Many => "",
-      }
+impl Term {
+  pub fn pos(&self) -> Option<Pos> {
+    match self {
+      Self::Var(pos, ..) => *pos,
+      Self::Lam(pos, ..) => *pos,
+      Self::App(pos, ..) => *pos,
+      Self::All(pos, ..) => *pos,
+      Self::Slf(pos, ..) => *pos,
+      Self::Dat(pos, ..) => *pos,
+      Self::Cse(pos, ..) => *pos,
+      Self::Sig(pos, ..) => *pos,
+      Self::Par(pos, ..) => *pos,
+      Self::Fst(pos, ..) => *pos,
+      Self::Snd(pos, ..) => *pos,
+      Self::Ref(pos, ..) => *pos,
+      Self::Let(pos, ..) => *pos,
+      Self::Typ(pos, _) => *pos,
+      Self::Prp(pos) => *pos,
+      Self::Ann(pos, ..) => *pos,
+      Self::Lit(pos, ..) => *pos,
+      Self::LTy(pos, ..) => *pos,
+      Self::Opr(pos, ..) => *pos,
+      Self::Hol(pos, _) => *pos,
+      Self::Met(pos, ..) => *pos,
     }
+  }
 
-    fn is_atom(term: &Term) -> bool {
-      match term {
-        Var(..) => true,
-        Ref(..) => true,
-        Lit(..) => true,
-        LTy(..) => true,
-        Opr(..) => true,
-        Typ(..) => true,
-        _ => false,
+  // Shift the indices of all variables at or above `cutoff` by `inc`
+  pub fn shift(&self, inc: i64, cutoff: u64) -> Term {
+    match self {
+      Self::Var(pos, nam, idx) if *idx >= cutoff => {
+        Self::Var(*pos, nam.clone(), (*idx as i64 + inc) as u64)
       }
-    }
-
-    fn lams(nam: &str, bod: &Term) -> String {
-      match bod {
-        Lam(_, nam2, bod2) => format!("{} {}", name(nam), lams(nam2, bod2)),
-        _ => format!("{} => {}", nam, bod),
+      Self::Lam(pos, nam, bod) => {
+        Self::Lam(*pos, nam.clone(), Box::new(bod.shift(inc, cutoff + 1)))
       }
-    }
-
-    fn alls(use_: &Uses, nam: &str, typ: &Term, bod: &Term) -> String {
-      match bod {
-        All(_, bod_use, bod_nam, bod) => {
-          format!(
-            " ({}{}: {}){}",
-            uses(use_),
-            name(nam),
-            typ,
-            alls(bod_use, bod_nam, &bod.0, &bod.1)
-          )
-        }
-        _ => format!(" ({}{}: {}) -> {}", uses(use_), name(nam), typ, bod),
+      Self::Slf(pos, nam, bod) => {
+        Self::Slf(*pos, nam.clone(), Box::new(bod.shift(inc, cutoff + 1)))
       }
+      Self::Dat(pos, bod) => Self::Dat(*pos, Box::new(bod.shift(inc, cutoff))),
+      Self::Cse(pos, bod) => Self::Cse(*pos, Box::new(bod.shift(inc, cutoff))),
+      Self::Fst(pos, bod) => Self::Fst(*pos, Box::new(bod.shift(inc, cutoff))),
+      Self::Snd(pos, bod) => Self::Snd(*pos, Box::new(bod.shift(inc, cutoff))),
+      Self::Par(pos, terms) => Self::Par(
+        *pos,
+        Box::new((terms.0.shift(inc, cutoff), terms.1.shift(inc, cutoff))),
+      ),
+      Self::Sig(pos, nam, terms) => Self::Sig(
+        *pos,
+        nam.clone(),
+        Box::new((terms.0.shift(inc, cutoff), terms.1.shift(inc, cutoff + 1))),
+      ),
+      Self::App(pos, terms) => Self::App(
+        *pos,
+        Box::new((terms.0.shift(inc, cutoff), terms.1.shift(inc, cutoff))),
+      ),
+      Self::Ann(pos, terms) => Self::Ann(
+        *pos,
+        Box::new((terms.0.shift(inc, cutoff), terms.1.shift(inc, cutoff))),
+      ),
+      Self::All(pos, uses, nam, terms) => Self::All(
+        *pos,
+        *uses,
+        nam.clone(),
+        Box::new((terms.0.shift(inc, cutoff), terms.1.shift(inc, cutoff + 1))),
+      ),
+      Self::Let(pos, rec, uses, nam, terms) => {
+        let exp_cutoff = if *rec { cutoff + 1 } else { cutoff };
+        Self::Let(
+          *pos,
+          *rec,
+          *uses,
+          nam.clone(),
+          Box::new((
+            terms.0.shift(inc, cutoff),
+            terms.1.shift(inc, exp_cutoff),
+            terms.2.shift(inc, cutoff + 1),
+          )),
+        )
+      }
+      _ => self.clone(),
     }
+  }
 
-    fn parens(term: &Term) -> String {
-      if is_atom(term) { format!("{}", term) } else { format!("({})", term) }
-    }
-
-    fn apps(fun: &Term, arg: &Term) -> String {
-      match (fun, arg) {
-        (App(_, f), App(_, a)) => {
-          format!("{} ({})", apps(&f.0, &f.1), apps(&a.0, &a.1))
-        }
-        (App(_, f), arg) => {
-          format!("{} {}", apps(&f.0, &f.1), parens(arg))
-        }
-        (fun, App(_, a)) => {
-          format!("{} ({})", parens(fun), apps(&a.0, &a.1))
-        }
-        (fun, arg) => {
-          format!("{} {}", parens(fun), parens(arg))
+  // Substitute `val` for the variable with index `idx`
+  pub fn subst(&self, idx: u64, val: &Term) -> Term {
+    match self {
+      Self::Var(_, _, i) if *i == idx => val.clone(),
+      Self::Lam(pos, nam, bod) => {
+        let bod = bod.subst(idx + 1, &val.shift(1, 0));
+        Self::Lam(*pos, nam.clone(), Box::new(bod))
+      }
+      Self::Slf(pos, nam, bod) => {
+        let bod = bod.subst(idx + 1, &val.shift(1, 0));
+        Self::Slf(*pos, nam.clone(), Box::new(bod))
+      }
+      Self::Dat(pos, bod) => Self::Dat(*pos, Box::new(bod.subst(idx, val))),
+      Self::Cse(pos, bod) => Self::Cse(*pos, Box::new(bod.subst(idx, val))),
+      Self::Fst(pos, bod) => Self::Fst(*pos, Box::new(bod.subst(idx, val))),
+      Self::Snd(pos, bod) => Self::Snd(*pos, Box::new(bod.subst(idx, val))),
+      Self::Par(pos, terms) => Self::Par(
+        *pos,
+        Box::new((terms.0.subst(idx, val), terms.1.subst(idx, val))),
+      ),
+      Self::Sig(pos, nam, terms) => Self::Sig(
+        *pos,
+        nam.clone(),
+        Box::new((
+          terms.0.subst(idx, val),
+          terms.1.subst(idx + 1, &val.shift(1, 0)),
+        )),
+      ),
+      Self::App(pos, terms) => Self::App(
+        *pos,
+        Box::new((terms.0.subst(idx, val), terms.1.subst(idx, val))),
+      ),
+      Self::Ann(pos, terms) => Self::Ann(
+        *pos,
+        Box::new((terms.0.subst(idx, val), terms.1.subst(idx, val))),
+      ),
+      Self::All(pos, uses, nam, terms) => Self::All(
+        *pos,
+        *uses,
+        nam.clone(),
+        Box::new((
+          terms.0.subst(idx, val),
+          terms.1.subst(idx + 1, &val.shift(1, 0)),
+        )),
+      ),
+      Self::Let(pos, rec, uses, nam, terms) => {
+        let exp = if *rec {
+          terms.1.subst(idx + 1, &val.shift(1, 0))
         }
+        else {
+          terms.1.subst(idx, val)
+        };
+        Self::Let(
+          *pos,
+          *rec,
+          *uses,
+          nam.clone(),
+          Box::new((
+            terms.0.subst(idx, val),
+            exp,
+            terms.2.subst(idx + 1, &val.shift(1, 0)),
+          )),
+        )
       }
+      _ => self.clone(),
     }
+  }
 
-    match self {
-      Var(_, nam, ..) => write!(f, "{}", nam),
-      Ref(_, nam, ..) => write!(f, "{}", nam),
-      Lam(_, nam, term) => write!(f, "λ {}", lams(nam, term)),
-      App(_, terms) => write!(f, "{}", apps(&terms.0, &terms.1)),
-      Let(_, true, u, n, terms) => {
-        write!(f, "letrec {}{}: {} := {}; {}", uses(u), name(n), terms.0, terms.1, terms.2)
-      }
-      Let(_, false, u, n, terms) => {
-        write!(f, "let {}{}: {} := {}; {}", uses(u), name(n), terms.0, terms.1, terms.2)
-      }
-      Slf(_, nam, bod) => write!(f, "@{} {}", name(nam), bod),
-      All(_, us_, nam, terms) => write!(f, "∀{}", alls(us_, nam, &terms.0, &terms.1)),
-      Ann(_, terms) => write!(f, "{} :: {}", parens(&terms.1), parens(&terms.0)),
-      Dat(_, bod) => write!(f, "data {}", bod),
-      Cse(_, bod) => write!(f, "case {}", bod),
-      Typ(_) => write!(f, "Type"),
-      Lit(_, lit) => write!(f, "{}", lit),
-      LTy(_, lty) => write!(f, "{}", lty),
-      Opr(_, opr) => write!(f, "{}", opr),
-    }
+  // Replace the outermost bound variable of a binder's body with `arg`
+  pub fn instantiate(&self, arg: &Term) -> Term {
+    self.subst(0, &arg.shift(1, 0)).shift(-1, 0)
   }
-}
 
-impl Term {
   pub fn embed(self) -> (AnonTerm, MetaTerm) {
     match self {
       Self::Var(pos, _, idx) => (
@@ -212,7 +323,7 @@ impl Term {
       ),
       Self::Ref(pos, name, def, ast) => (
         AnonTerm::Ctor(String::from("ref"), vec![AnonTerm::Link(ast)]),
-        MetaTerm::Ctor(pos, vec![MetaTerm::Link(name, def)]),
+        MetaTerm::Ctor(pos, vec![MetaTerm::Link(name.to_string(), def)]),
       ),
       Self::Lit(pos, lit) => (
         AnonTerm::Ctor(String::from("lit"), vec![AnonTerm::Data(
@@ -232,10 +343,38 @@ impl Term {
         )]),
         MetaTerm::Ctor(pos, vec![MetaTerm::Leaf]),
       ),
-      Self::Typ(pos) => (
+      // The lowest universe keeps the form it had before there were levels
+      Self::Typ(pos, 0) => (
         AnonTerm::Ctor(String::from("typ"), vec![]),
         MetaTerm::Ctor(pos, vec![]),
       ),
+      Self::Typ(pos, lvl) => (
+        AnonTerm::Ctor(String::from("typ"), vec![AnonTerm::Data(
+          Literal::Natural(lvl.into()).encode().serialize(),
+        )]),
+        MetaTerm::Ctor(pos, vec![MetaTerm::Leaf]),
+      ),
+      Self::Prp(pos) => (
+        AnonTerm::Ctor(String::from("prp"), vec![]),
+        MetaTerm::Ctor(pos, vec![]),
+      ),
+      Self::Hol(pos, None) => (
+        AnonTerm::Ctor(String::from("hol"), vec![]),
+        MetaTerm::Ctor(pos, vec![]),
+      ),
+      Self::Hol(pos, Some(name)) => (
+        AnonTerm::Ctor(String::from("hol"), vec![]),
+        MetaTerm::Ctor(pos, vec![MetaTerm::Bind(
+          name,
+          Box::new(MetaTerm::Leaf),
+        )]),
+      ),
+      Self::Met(pos, idx) => (
+        AnonTerm::Ctor(String::from("met"), vec![AnonTerm::Data(
+          Literal::Natural(idx.into()).encode().serialize(),
+        )]),
+        MetaTerm::Ctor(pos, vec![MetaTerm::Leaf]),
+      ),
       Self::Lam(pos, name, body) => {
         let (anon, meta) = (*body).clone().embed();
         (
@@ -243,7 +382,7 @@ impl Term {
             anon,
           ))]),
           MetaTerm::Ctor(pos, vec![MetaTerm::Bind(
-            name.to_owned(),
+            name.to_string(),
             Box::new(meta),
           )]),
         )
@@ -255,7 +394,7 @@ impl Term {
             anon,
           ))]),
           MetaTerm::Ctor(pos, vec![MetaTerm::Bind(
-            name.to_owned(),
+            name.to_string(),
             Box::new(meta),
           )]),
         )
@@ -290,6 +429,42 @@ impl Term {
           MetaTerm::Ctor(pos, vec![meta]),
         )
       }
+      Self::Fst(pos, body) => {
+        let (anon, meta) = (*body).clone().embed();
+        (
+          AnonTerm::Ctor(String::from("fst"), vec![anon]),
+          MetaTerm::Ctor(pos, vec![meta]),
+        )
+      }
+      Self::Snd(pos, body) => {
+        let (anon, meta) = (*body).clone().embed();
+        (
+          AnonTerm::Ctor(String::from("snd"), vec![anon]),
+          MetaTerm::Ctor(pos, vec![meta]),
+        )
+      }
+      Self::Par(pos, terms) => {
+        let (fst_anon, fst_meta) = terms.0.clone().embed();
+        let (snd_anon, snd_meta) = terms.1.clone().embed();
+        (
+          AnonTerm::Ctor(String::from("par"), vec![fst_anon, snd_anon]),
+          MetaTerm::Ctor(pos, vec![fst_meta, snd_meta]),
+        )
+      }
+      Self::Sig(pos, name, terms) => {
+        let (typ_anon, typ_meta) = terms.0.clone().embed();
+        let (bod_anon, bod_meta) = terms.1.clone().embed();
+        (
+          AnonTerm::Ctor(String::from("sig"), vec![
+            typ_anon,
+            AnonTerm::Bind(Box::new(bod_anon)),
+          ]),
+          MetaTerm::Ctor(pos, vec![
+            typ_meta,
+            MetaTerm::Bind(name.to_string(), Box::new(bod_meta)),
+          ]),
+        )
+      }
       Self::All(pos, uses, name, terms) => {
         let (typ_anon, typ_meta) = terms.0.clone().embed();
         let (bod_anon, bod_meta) = terms.1.clone().embed();
@@ -302,7 +477,7 @@ impl Term {
           MetaTerm::Ctor(pos, vec![
             MetaTerm::Leaf,
             typ_meta,
-            MetaTerm::Bind(name, Box::new(bod_meta)),
+            MetaTerm::Bind(name.to_string(), Box::new(bod_meta)),
           ]),
         )
       }
@@ -320,8 +495,8 @@ impl Term {
           MetaTerm::Ctor(pos, vec![
             MetaTerm::Leaf,
             typ_meta,
-            MetaTerm::Bind(name.clone(), Box::new(exp_meta)),
-            MetaTerm::Bind(name, Box::new(bod_meta)),
+            MetaTerm::Bind(name.to_string(), Box::new(exp_meta)),
+            MetaTerm::Bind(name.to_string(), Box::new(bod_meta)),
           ]),
         )
       }
@@ -340,7 +515,7 @@ impl Term {
             MetaTerm::Leaf,
             typ_meta,
             exp_meta,
-            MetaTerm::Bind(name, Box::new(bod_meta)),
+            MetaTerm::Bind(name.to_string(), Box::new(bod_meta)),
           ]),
         )
       }
@@ -348,7 +523,7 @@ impl Term {
   }
 
   pub fn unembed(
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
     anon_term: &AnonTerm,
     name_meta: &MetaTerm,
   ) -> Result<Term, UnembedError> {
@@ -357,12 +532,12 @@ impl Term {
         match (&n[..], xs.as_slice(), ys.as_slice()) {
           ("var", [AnonTerm::Vari(idx)], [MetaTerm::Leaf]) => {
             match ctx.iter().enumerate().find(|(i, _)| (*i as u64) == *idx) {
-              Some((_, n)) => Ok(Term::Var(*pos, n.to_owned(), *idx)),
+              Some((_, n)) => Ok(Term::Var(*pos, n.clone(), *idx)),
               None => Err(UnembedError::FreeVariable),
             }
           }
           ("ref", [AnonTerm::Link(ast)], [MetaTerm::Link(name, def)]) => {
-            Ok(Term::Ref(*pos, name.clone(), *def, *ast))
+            Ok(Term::Ref(*pos, Name::from(name), *def, *ast))
           }
           ("lit", [AnonTerm::Data(data)], [MetaTerm::Leaf]) => {
             let (_, lit) = hashexpr::Expr::deserialize(&data)
@@ -385,7 +560,38 @@ impl Term {
               PrimOp::decode(opr).map_err(|e| UnembedError::DecodeError(e))?;
             Ok(Term::Opr(*pos, opr))
           }
-          ("typ", [], []) => Ok(Term::Typ(*pos)),
+          ("typ", [], []) => Ok(Term::Typ(*pos, 0)),
+          ("typ", [AnonTerm::Data(data)], [MetaTerm::Leaf]) => {
+            let (_, lvl) = hashexpr::Expr::deserialize(&data)
+              .map_err(|_| UnembedError::DeserialError)?;
+            let lvl =
+              Literal::decode(lvl).map_err(|e| UnembedError::DecodeError(e))?;
+            match lvl {
+              Literal::Natural(lvl) => match lvl.try_into() {
+                Ok(lvl) => Ok(Term::Typ(*pos, lvl)),
+                Err(_) => Err(UnembedError::BadLevel),
+              },
+              _ => Err(UnembedError::BadLevel),
+            }
+          }
+          ("prp", [], []) => Ok(Term::Prp(*pos)),
+          ("hol", [], []) => Ok(Term::Hol(*pos, None)),
+          ("hol", [], [MetaTerm::Bind(n, _)]) => {
+            Ok(Term::Hol(*pos, Some(n.clone())))
+          }
+          ("met", [AnonTerm::Data(data)], [MetaTerm::Leaf]) => {
+            let (_, idx) = hashexpr::Expr::deserialize(&data)
+              .map_err(|_| UnembedError::DeserialError)?;
+            let idx =
+              Literal::decode(idx).map_err(|e| UnembedError::DecodeError(e))?;
+            match idx {
+              Literal::Natural(idx) => match idx.try_into() {
+                Ok(idx) => Ok(Term::Met(*pos, idx)),
+                Err(_) => Err(UnembedError::BadMeta),
+              },
+              _ => Err(UnembedError::BadMeta),
+            }
+          }
           ("dat", [anon], [meta]) => {
             let body = Term::unembed(ctx, anon, meta)?;
             Ok(Term::Dat(*pos, Box::new(body)))
@@ -394,17 +600,41 @@ impl Term {
             let body = Term::unembed(ctx, anon, meta)?;
             Ok(Term::Cse(*pos, Box::new(body)))
           }
+          ("fst", [anon], [meta]) => {
+            let body = Term::unembed(ctx, anon, meta)?;
+            Ok(Term::Fst(*pos, Box::new(body)))
+          }
+          ("snd", [anon], [meta]) => {
+            let body = Term::unembed(ctx, anon, meta)?;
+            Ok(Term::Snd(*pos, Box::new(body)))
+          }
+          ("par", [xanon, yanon], [xmeta, ymeta]) => {
+            let fst = Term::unembed(ctx.clone(), xanon, xmeta)?;
+            let snd = Term::unembed(ctx.clone(), yanon, ymeta)?;
+            Ok(Term::Par(*pos, Box::new((fst, snd))))
+          }
+          (
+            "sig",
+            [tanon, AnonTerm::Bind(banon)],
+            [tmeta, MetaTerm::Bind(n, bmeta)],
+          ) => {
+            let typ_ = Term::unembed(ctx.clone(), tanon, tmeta)?;
+            let mut new_ctx = ctx.clone();
+            new_ctx.push_front(Name::from(n));
+            let body = Term::unembed(new_ctx, banon, bmeta)?;
+            Ok(Term::Sig(*pos, Name::from(n), Box::new((typ_, body))))
+          }
           ("lam", [AnonTerm::Bind(anon)], [MetaTerm::Bind(n, meta)]) => {
             let mut new_ctx = ctx.clone();
-            new_ctx.push_front(n.clone());
+            new_ctx.push_front(Name::from(n));
             let body = Term::unembed(new_ctx, &anon, meta)?;
-            Ok(Term::Lam(*pos, n.clone(), Box::new(body)))
+            Ok(Term::Lam(*pos, Name::from(n), Box::new(body)))
           }
           ("slf", [AnonTerm::Bind(anon)], [MetaTerm::Bind(n, meta)]) => {
             let mut new_ctx = ctx.clone();
-            new_ctx.push_front(n.clone());
+            new_ctx.push_front(Name::from(n));
             let body = Term::unembed(new_ctx, &anon, meta)?;
-            Ok(Term::Slf(*pos, n.clone(), Box::new(body)))
+            Ok(Term::Slf(*pos, Name::from(n), Box::new(body)))
           }
           ("app", [fanon, aanon], [fmeta, ameta]) => {
             let fun = Term::unembed(ctx.clone(), fanon, fmeta)?;
@@ -427,9 +657,9 @@ impl Term {
               Uses::decode(uses).map_err(|e| UnembedError::DecodeError(e))?;
             let typ_ = Term::unembed(ctx.clone(), tanon, tmeta)?;
             let mut new_ctx = ctx.clone();
-            new_ctx.push_front(n.clone());
+            new_ctx.push_front(Name::from(n));
             let body = Term::unembed(new_ctx, banon, bmeta)?;
-            Ok(Term::All(*pos, uses, n.clone(), Box::new((typ_, body))))
+            Ok(Term::All(*pos, uses, Name::from(n), Box::new((typ_, body))))
           }
           (
             "rec",
@@ -444,14 +674,14 @@ impl Term {
               Uses::decode(uses).map_err(|e| UnembedError::DecodeError(e))?;
             let typ_ = Term::unembed(ctx.clone(), tanon, tmeta)?;
             let mut new_ctx = ctx.clone();
-            new_ctx.push_front(name.clone());
+            new_ctx.push_front(Name::from(name));
             let exp = Term::unembed(new_ctx.clone(), &xanon, xmeta)?;
             let body = Term::unembed(new_ctx, &banon, bmeta)?;
             Ok(Term::Let(
               *pos,
               true,
               uses,
-              name.clone(),
+              Name::from(name),
               Box::new((typ_, exp, body))
             ))
           }
@@ -467,13 +697,13 @@ impl Term {
             let typ_ = Term::unembed(ctx.clone(), tanon, tmeta)?;
             let exp = Term::unembed(ctx.clone(), xanon, xmeta)?;
             let mut new_ctx = ctx;
-            new_ctx.push_front(name.clone());
+            new_ctx.push_front(Name::from(name));
             let body = Term::unembed(new_ctx, &banon, bmeta)?;
             Ok(Term::Let(
               *pos,
               false,
               uses,
-              name.clone(),
+              Name::from(name),
               Box::new((typ_, exp, body))
             ))
           }
@@ -493,12 +723,13 @@ impl Term {
 impl Def {
   pub fn new(
     pos: Option<Pos>,
-    name: String,
+    name: Name,
     docs: String,
     typ_: Term,
     term: Term,
   ) -> Self {
-    Def { pos, name, docs, typ_, term }
+    let transparency = Transparency::default();
+    Def { pos, name, docs, transparency, implicits: Vec::new(), typ_, term }
   }
 
   pub fn embed(self) -> (Definition, AnonTerm, AnonTerm) {
@@ -506,8 +737,10 @@ impl Def {
     let (term_anon, term_meta) = self.term.embed();
     let d = Definition {
       pos: self.pos,
-      name: self.name,
+      name: self.name.to_string(),
       docs: self.docs,
+      transparency: self.transparency,
+      implicits: self.implicits,
       term_anon: term_anon.clone().encode().link(),
       type_anon: type_anon.clone().encode().link(),
       term_meta,
@@ -523,17 +756,40 @@ impl Def {
   ) -> Result<Self, UnembedError> {
     let typ_ = Term::unembed(Vector::new(), &type_anon, &def.type_meta)?;
     let term = Term::unembed(
-      Vector::from(vec![def.name.clone()]),
+      Vector::from(vec![Name::from(&def.name)]),
       &term_anon,
       &def.term_meta,
     )?;
-    Ok(Def::new(def.pos, def.name, def.docs, typ_, term))
+    Ok(Def {
+      pos: def.pos,
+      name: Name::from(def.name),
+      docs: def.docs,
+      transparency: def.transparency,
+      implicits: def.implicits,
+      typ_,
+      term,
+    })
   }
 
   pub fn get_link(defn: Link) -> Result<Self, UnembedError> {
+    Def::get_positioned(defn, None)
+  }
+
+  // The definition at `defn`, with the source positions stored at
+  // `positions`, as its encoding leaves them out. Positions which are not
+  // stored, or not readable, are only metadata, so the definition is read
+  // without them.
+  pub fn get_positioned(
+    defn: Link,
+    positions: Option<Link>,
+  ) -> Result<Self, UnembedError> {
     let def = hashspace::get(defn).ok_or(UnembedError::UnknownLink(defn))?;
-    let def =
+    let mut def =
       Definition::decode(def).map_err(|e| UnembedError::DecodeError(e))?;
+    let positions = positions.and_then(|l| Positions::get_link(l).ok());
+    if let Some(positions) = positions {
+      positions.apply(&mut def);
+    }
     let type_anon =
       hashspace::get(def.type_anon).ok_or(UnembedError::UnknownLink(defn))?;
     let type_anon =
@@ -557,16 +813,13 @@ pub fn refs_to_defs(refs: Refs) -> Result<Defs, UnembedError> {
 
 impl fmt::Display for Def {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    if self.docs.is_empty() {
-      write!(f, "def {} : {} = {}", self.name, self.typ_, self.term)
+    if !self.docs.is_empty() {
+      write!(f, "//{}\n ", self.docs)?;
     }
-    else {
-      write!(
-        f,
-        "//{}\n def {} : {} = {}",
-        self.docs, self.name, self.typ_, self.term
-      )
+    if self.transparency != Transparency::Transparent {
+      write!(f, "{} ", self.transparency)?;
     }
+    write!(f, "def {} : {} = {}", self.name, self.typ_, self.term)
   }
 }
 
@@ -596,7 +849,7 @@ pub mod tests {
     //})
   }
 
-  pub fn arbitrary_name(g: &mut Gen) -> String {
+  pub fn arbitrary_name(g: &mut Gen) -> Name {
     let s: String = Arbitrary::arbitrary(g);
     let mut s: String = s
       .chars()
@@ -606,12 +859,16 @@ pub mod tests {
       })
       .collect();
     s.truncate(1);
-    format!("_{}", s)
+    // A lone `_` is a placeholder, not a variable
+    if s.is_empty() {
+      s.push('x');
+    }
+    Name::from(format!("_{}", s))
   }
 
   fn arbitrary_lam(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       let n = arbitrary_name(g);
@@ -623,7 +880,7 @@ pub mod tests {
 
   fn arbitrary_slf(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       let n = arbitrary_name(g);
@@ -635,7 +892,7 @@ pub mod tests {
 
   fn arbitrary_let(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       let rec: bool = Arbitrary::arbitrary(g);
@@ -661,7 +918,7 @@ pub mod tests {
 
   fn arbitrary_all(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       let n = arbitrary_name(g);
@@ -689,10 +946,10 @@ pub mod tests {
     HashMap::new()
   }
 
-  fn arbitrary_var(ctx: Vector<String>) -> Box<dyn Fn(&mut Gen) -> Term> {
+  fn arbitrary_var(ctx: Vector<Name>) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |_g: &mut Gen| {
       if ctx.len() == 0 {
-        return Term::Typ(None);
+        return Term::Typ(None, 0);
       }
       let mut rng = rand::thread_rng();
       let gen = rng.gen_range(0..ctx.len());
@@ -704,26 +961,26 @@ pub mod tests {
 
   fn arbitrary_ref(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |_g: &mut Gen| {
       let mut rng = rand::thread_rng();
       let mut ref_iter = refs.iter().filter(|(n, _)| !ctx.contains(n));
       let len = ref_iter.by_ref().count();
       if len == 0 {
-        return Term::Typ(None);
+        return Term::Typ(None, 0);
       }
       let gen = rng.gen_range(0..len);
       match ref_iter.nth(gen) {
         Some((n, (d, a))) => Ref(None, n.clone(), *d, *a),
-        None => Term::Typ(None),
+        None => Term::Typ(None, 0),
       }
     })
   }
 
   fn arbitrary_app(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       Term::App(
@@ -735,7 +992,7 @@ pub mod tests {
 
   fn arbitrary_ann(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       Term::Ann(
@@ -745,9 +1002,61 @@ pub mod tests {
     })
   }
 
+  fn arbitrary_sig(
+    refs: Refs,
+    ctx: Vector<Name>,
+  ) -> Box<dyn Fn(&mut Gen) -> Term> {
+    Box::new(move |g: &mut Gen| {
+      let n = arbitrary_name(g);
+      let mut ctx2 = ctx.clone();
+      ctx2.push_front(n.clone());
+      Sig(
+        None,
+        n,
+        Box::new((
+          arbitrary_term(g, refs.clone(), ctx.clone()),
+          arbitrary_term(g, refs.clone(), ctx2),
+        )),
+      )
+    })
+  }
+
+  fn arbitrary_par(
+    refs: Refs,
+    ctx: Vector<Name>,
+  ) -> Box<dyn Fn(&mut Gen) -> Term> {
+    Box::new(move |g: &mut Gen| {
+      Term::Par(
+        None,
+        Box::new((
+          arbitrary_term(g, refs.clone(), ctx.clone()),
+          arbitrary_term(g, refs.clone(), ctx.clone()),
+        )),
+      )
+    })
+  }
+
+  fn arbitrary_fst(
+    refs: Refs,
+    ctx: Vector<Name>,
+  ) -> Box<dyn Fn(&mut Gen) -> Term> {
+    Box::new(move |g: &mut Gen| {
+      Term::Fst(None, Box::new(arbitrary_term(g, refs.clone(), ctx.clone())))
+    })
+  }
+
+  fn arbitrary_snd(
+    refs: Refs,
+    ctx: Vector<Name>,
+  ) -> Box<dyn Fn(&mut Gen) -> Term> {
+    Box::new(move |g: &mut Gen| {
+      Term::Snd(None, Box::new(arbitrary_term(g, refs.clone(), ctx.clone())))
+    })
+  }
+
   fn arbitrary_dat(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       Term::Dat(None, Box::new(arbitrary_term(g, refs.clone(), ctx.clone())))
@@ -755,7 +1064,7 @@ pub mod tests {
   }
   fn arbitrary_cse(
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Box<dyn Fn(&mut Gen) -> Term> {
     Box::new(move |g: &mut Gen| {
       Term::Cse(None, Box::new(arbitrary_term(g, refs.clone(), ctx.clone())))
@@ -787,7 +1096,7 @@ pub mod tests {
   pub fn arbitrary_term(
     g: &mut Gen,
     refs: Refs,
-    ctx: Vector<String>,
+    ctx: Vector<Name>,
   ) -> Term {
     let len = ctx.len();
     if len == 0 {
@@ -797,7 +1106,8 @@ pub mod tests {
       frequency(g, vec![
         (100, arbitrary_var(ctx.clone())),
         (100, arbitrary_ref(refs.clone(), ctx.clone())),
-        (100, Box::new(|_| Term::Typ(None))),
+        (100, Box::new(|g| Term::Typ(None, u64::arbitrary(g) % 3))),
+        (100, Box::new(|_| Term::Prp(None))),
         (100, Box::new(|g| Term::Lit(None, Arbitrary::arbitrary(g)))),
         (100, Box::new(|g| Term::LTy(None, Arbitrary::arbitrary(g)))),
         (100, Box::new(|g| Term::Opr(None, Arbitrary::arbitrary(g)))),
@@ -805,6 +1115,10 @@ pub mod tests {
         (100, arbitrary_dat(refs.clone(), ctx.clone())),
         (100, arbitrary_cse(refs.clone(), ctx.clone())),
         (100, arbitrary_slf(refs.clone(), ctx.clone())),
+        (90, arbitrary_sig(refs.clone(), ctx.clone())),
+        (90, arbitrary_par(refs.clone(), ctx.clone())),
+        (90, arbitrary_fst(refs.clone(), ctx.clone())),
+        (90, arbitrary_snd(refs.clone(), ctx.clone())),
         (90, arbitrary_all(refs.clone(), ctx.clone())),
         (90, arbitrary_app(refs.clone(), ctx.clone())),
         (90, arbitrary_ann(refs.clone(), ctx.clone())),
@@ -818,13 +1132,15 @@ pub mod tests {
     }
   }
 
-  pub fn arbitrary_def(g: &mut Gen, refs: Refs, name: String) -> Def {
+  pub fn arbitrary_def(g: &mut Gen, refs: Refs, name: Name) -> Def {
     let mut ctx = Vector::new();
     ctx.push_front(name.clone());
     Def {
       pos: None,
       name,
       docs: String::from(""),
+      transparency: Transparency::default(),
+      implicits: Vec::new(),
       typ_: arbitrary_term(g, refs.clone(), Vector::new()),
       term: arbitrary_term(g, refs, ctx),
     }
@@ -0,0 +1,191 @@
+use crate::{
+  core::check::{
+    check_def,
+    infer_term,
+    CheckError,
+  },
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Def,
+    Defs,
+    Link,
+    Term,
+  },
+  unembed_error::UnembedError,
+};
+
+use std::{
+  sync::{
+    atomic::{
+      AtomicUsize,
+      Ordering,
+    },
+    Arc,
+    Mutex,
+  },
+  thread,
+};
+
+// Running the tests of a package. A `test` declaration passes when its
+// term checks against its type, and a `#check` pragma when its term, if it
+// is not annotated, synthesizes a type. Tests are stored with the package,
+// but are not in scope in it and are not checked as it is parsed, so that a
+// package with failing tests can still be used and its tests run on their
+// own, on as many threads as are asked for.
+
+// A test, and the package declaring it
+#[derive(Clone, Debug)]
+pub struct Test {
+  pub package: String,
+  pub link: Link,
+  pub def: Def,
+}
+
+impl Test {
+  // The name of the test, qualified by its package
+  pub fn name(&self) -> String {
+    format!("{}.{}", self.package, self.def.name)
+  }
+
+  // Whether the test is chosen by `filter`, which chooses the tests whose
+  // qualified names contain it
+  pub fn matches(&self, filter: &Option<String>) -> bool {
+    let name = self.name();
+    filter.as_ref().map_or(true, |filter| name.contains(filter.as_str()))
+  }
+
+  pub fn run(&self, defs: &Defs) -> Result<(), CheckError> {
+    match self.def.typ_ {
+      Term::Hol(_, None) => infer_term(defs, &self.def.term).map(|_| ()),
+      _ => check_def(defs, &self.def),
+    }
+  }
+}
+
+// The tests of the package at `link` and, if `imports`, of the packages it
+// opens, transitively, each once, in the order they are declared
+pub fn tests(link: Link, imports: bool) -> Result<Vec<Test>, UnembedError> {
+  fn go(
+    link: Link,
+    imports: bool,
+    seen: &mut Vec<Link>,
+    tests: &mut Vec<Test>,
+  ) -> Result<(), UnembedError> {
+    if seen.contains(&link) {
+      return Ok(());
+    }
+    seen.push(link);
+    let package = Package::get_link(link)?;
+    for decl in &package.decls {
+      match decl {
+        Declaration::Test { test, positions, .. } => {
+          let def = Def::get_positioned(*test, *positions)?;
+          let package = package.name.clone();
+          tests.push(Test { package, link: *test, def });
+        }
+        Declaration::Open { from, .. } if imports => {
+          go(*from, imports, seen, tests)?;
+        }
+        _ => (),
+      }
+    }
+    Ok(())
+  }
+  let mut tests = Vec::new();
+  go(link, imports, &mut Vec::new(), &mut tests)?;
+  Ok(tests)
+}
+
+// Run the tests, which must only refer to definitions in `defs`, on `jobs`
+// threads, returning the result of each in order
+pub fn run_tests(
+  defs: &Defs,
+  tests: &[Test],
+  jobs: usize,
+) -> Vec<Result<(), CheckError>> {
+  // The tests, the index of the next one to run and the results so far,
+  // shared between the threads
+  let shared = Arc::new((
+    defs.clone(),
+    tests.to_vec(),
+    AtomicUsize::new(0),
+    Mutex::new(vec![Ok(()); tests.len()]),
+  ));
+  let workers: Vec<thread::JoinHandle<()>> = (0..jobs.max(1).min(tests.len()))
+    .map(|_| {
+      let shared = shared.clone();
+      thread::spawn(move || {
+        let (defs, tests, next, results) = &*shared;
+        loop {
+          let idx = next.fetch_add(1, Ordering::SeqCst);
+          if idx >= tests.len() {
+            break;
+          }
+          let res = tests[idx].run(defs);
+          results.lock().unwrap()[idx] = res;
+        }
+      })
+    })
+    .collect();
+  for worker in workers {
+    worker.join().unwrap();
+  }
+  let mut results = shared.3.lock().unwrap();
+  std::mem::take(&mut *results)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::query::Database;
+  use std::{
+    env,
+    fs,
+  };
+
+  #[test]
+  fn runs_tests() {
+    let dir = env::temp_dir().join("yatima-testing");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+      dir.join("TestLib.ya"),
+      "package TestLib where\n\
+       def one : #Natural = 1\n\
+       test one_is_one : Eq #Natural one 1 = Eq.refl #Natural 1\n",
+    )
+    .unwrap();
+    let path = dir.join("Tests.ya");
+    fs::write(
+      &path,
+      "package Tests where\nopen TestLib\n\
+       def two : #Natural = #add one one\n\
+       test two_is_two : Eq #Natural two 2 = Eq.refl #Natural 2\n\
+       test two_is_three : Eq #Natural two 3 = Eq.refl #Natural 3\n\
+       #check two :: #Natural\n\
+       #check #add two one\n\
+       #check two :: #Text\n",
+    )
+    .unwrap();
+    let parsed = Database::new().parse(&path).unwrap();
+    assert!(parsed.refs.get("two_is_two").is_none());
+    assert_eq!(tests(parsed.link, false).unwrap().len(), 5);
+    let tests = tests(parsed.link, true).unwrap();
+    let names: Vec<String> = tests.iter().map(|test| test.name()).collect();
+    assert_eq!(names, vec![
+      "TestLib.one_is_one",
+      "Tests.two_is_two",
+      "Tests.two_is_three",
+      "Tests.#check:6",
+      "Tests.#check:7",
+      "Tests.#check:8",
+    ]);
+    let results = run_tests(&parsed.defs, &tests, 2);
+    let passed: Vec<bool> = results.iter().map(|res| res.is_ok()).collect();
+    assert_eq!(passed, vec![true, true, false, true, true, false]);
+    let filter = Some(String::from("two_is"));
+    assert_eq!(tests.iter().filter(|test| test.matches(&filter)).count(), 2);
+  }
+}
@@ -0,0 +1,172 @@
+use crate::{
+  config::Verbosity,
+  global::Global,
+};
+
+use log::{
+  Level,
+  LevelFilter,
+  Log,
+  Metadata,
+  Record,
+};
+
+use std::{
+  fmt,
+  str::FromStr,
+  time::Instant,
+};
+
+// Tracing, of what is done as it is done, to stderr: which files are parsed,
+// which links are read, stored, fetched and published, which definitions are
+// checked, and how long parsing, checking and evaluation take. The crate is
+// instrumented with `tracing`: each part of it enters a span for the work it
+// does, such as parsing a file, with the file, link or definition as fields,
+// and emits events, with what they are about and how long it took as fields,
+// to the target of that part. An application embedding the crate can set its
+// own `tracing` subscriber to collect them. When none is set, as by the
+// command line, the events are logged, with `log`, and this logger prints
+// each with the time since it was started, its level and its target. How
+// much is printed is set by the verbosity, and tracing a target prints
+// everything logged to it, whatever the verbosity.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+  Parse,
+  Check,
+  Eval,
+  Hashspace,
+}
+
+impl Target {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Parse => "parse",
+      Self::Check => "check",
+      Self::Eval => "eval",
+      Self::Hashspace => "hashspace",
+    }
+  }
+}
+
+impl FromStr for Target {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "parse" => Ok(Self::Parse),
+      "check" => Ok(Self::Check),
+      "eval" => Ok(Self::Eval),
+      "hashspace" => Ok(Self::Hashspace),
+      _ => Err(format!("Unknown trace target {}", s)),
+    }
+  }
+}
+
+impl fmt::Display for Target {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+// The most detailed level printed at a verbosity
+pub fn level(verbosity: Verbosity) -> LevelFilter {
+  match verbosity {
+    Verbosity::Quiet => LevelFilter::Error,
+    Verbosity::Normal => LevelFilter::Warn,
+    Verbosity::Verbose => LevelFilter::Info,
+    Verbosity::Debug => LevelFilter::Debug,
+  }
+}
+
+pub struct Logger {
+  pub level: LevelFilter,
+  pub traced: Vec<Target>,
+  pub start: Instant,
+}
+
+impl Logger {
+  pub fn new(verbosity: Verbosity, traced: Vec<Target>) -> Self {
+    Logger { level: level(verbosity), traced, start: Instant::now() }
+  }
+
+  // The message of a record, as it is printed
+  pub fn format(&self, record: &Record) -> String {
+    let time = self.start.elapsed();
+    format!(
+      "[{:>4}.{:03}s {:<5} {}] {}",
+      time.as_secs(),
+      time.subsec_millis(),
+      record.level(),
+      record.target(),
+      record.args()
+    )
+  }
+}
+
+impl Log for Logger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    let target = metadata.target();
+    if self.traced.iter().any(|t| t.name() == target) {
+      return true;
+    }
+    // Others' messages, such as the server's, are printed only if they are
+    // warnings or errors
+    let level = metadata.level();
+    let ours = target.parse::<Target>().is_ok();
+    (ours || level <= Level::Warn) && level <= self.level
+  }
+
+  fn log(&self, record: &Record) {
+    if self.enabled(record.metadata()) {
+      eprintln!("{}", self.format(record));
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+static LOGGER: Global<Logger> = Global::new();
+
+// Start the logger of the process, once, printing what is logged at
+// `verbosity` or to the `traced` targets
+pub fn init(verbosity: Verbosity, traced: Vec<Target>) {
+  let logger = LOGGER.get_or_init(|| Logger::new(verbosity, traced));
+  if log::set_logger(logger).is_ok() {
+    let traced = !logger.traced.is_empty();
+    log::set_max_level(if traced { LevelFilter::Trace } else { logger.level });
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn filters() {
+    assert_eq!("hashspace".parse(), Ok(Target::Hashspace));
+    assert_eq!("check".parse(), Ok(Target::Check));
+    assert!("type".parse::<Target>().is_err());
+    let meta = |level, target| {
+      Metadata::builder().level(level).target(target).build()
+    };
+    let logger = Logger::new(Verbosity::Verbose, vec![Target::Eval]);
+    assert!(logger.enabled(&meta(Level::Info, "parse")));
+    assert!(!logger.enabled(&meta(Level::Debug, "parse")));
+    assert!(logger.enabled(&meta(Level::Trace, "eval")));
+    assert!(!logger.enabled(&meta(Level::Info, "rocket")));
+    assert!(logger.enabled(&meta(Level::Warn, "rocket")));
+    let quiet = Logger::new(Verbosity::Quiet, vec![]);
+    assert!(!quiet.enabled(&meta(Level::Info, "hashspace")));
+    assert!(quiet.enabled(&meta(Level::Error, "hashspace")));
+    let line = logger.format(
+      &Record::builder()
+        .args(format_args!("Parsed {}", "A.ya"))
+        .level(Level::Info)
+        .target("parse")
+        .build(),
+    );
+    assert!(line.starts_with("[   0."), "{}", line);
+    assert!(line.ends_with("s INFO  parse] Parsed A.ya"), "{}", line);
+  }
+}
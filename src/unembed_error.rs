@@ -15,8 +15,34 @@ pub enum UnembedError {
   UnexpectedCtor(AnonTerm, MetaTerm),
   UnknownLink(Link),
   BadLet,
+  BadLevel,
+  BadMeta,
 }
 
-// impl fmt::Display for UnembedError {
-//  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {}
-//}
+impl fmt::Display for UnembedError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::FreeVariable => write!(f, "Free variable in an embedded term"),
+      Self::DecodeError(e) => write!(f, "{}", e),
+      Self::DeserialError => write!(f, "Cannot deserialize an embedded atom"),
+      Self::UnexpectedCtor(anon, meta) => write!(
+        f,
+        "Term {} does not have the shape of its metadata {}",
+        anon, meta
+      ),
+      Self::UnknownLink(link) => write!(f, "Nothing is stored at {}", link),
+      Self::BadLet => write!(f, "Malformed embedded let"),
+      Self::BadLevel => write!(f, "Malformed embedded universe level"),
+      Self::BadMeta => write!(f, "Malformed embedded metavariable"),
+    }
+  }
+}
+
+impl std::error::Error for UnembedError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::DecodeError(e) => Some(e),
+      _ => None,
+    }
+  }
+}
@@ -0,0 +1,530 @@
+use crate::{
+  config,
+  package::{
+    Declaration,
+    Package,
+  },
+  term::{
+    Def,
+    Defs,
+    Refs,
+    Term,
+  },
+};
+
+use hashexpr::{
+  link::Link,
+  position::Pos,
+};
+
+use std::{
+  collections::HashSet,
+  fmt,
+  str::FromStr,
+};
+
+// Warnings about packages which parse and check, but likely not as meant.
+// Each lint has a level, by default `warn`, which the `[warnings]` table of
+// the configuration sets, by the lint's name:
+//
+//   [warnings]
+//   unused-import = "allow"     # not reported
+//   confusable = "deny"         # reported as an error
+//
+// The warnings found in a package are collected in `Diagnostics`, which
+// keeps those denied apart, as errors, for the command line and the language
+// server to report alongside the package's type errors.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+  // An opened package none of whose definitions are used
+  UnusedImport,
+  // A bound variable with the name of a definition in scope, which it hides
+  ShadowedName,
+  // A `partial` definition whose type is left to be inferred
+  UnannotatedPartial,
+  // A character which looks like another, such as a Cyrillic `а`, or which
+  // cannot be seen, such as a zero width space
+  Confusable,
+}
+
+impl Lint {
+  pub const ALL: [Lint; 4] = [
+    Self::UnusedImport,
+    Self::ShadowedName,
+    Self::UnannotatedPartial,
+    Self::Confusable,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::UnusedImport => "unused-import",
+      Self::ShadowedName => "shadowed-name",
+      Self::UnannotatedPartial => "unannotated-partial",
+      Self::Confusable => "confusable",
+    }
+  }
+}
+
+impl fmt::Display for Lint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+impl FromStr for Lint {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::ALL
+      .iter()
+      .find(|lint| lint.name() == s)
+      .copied()
+      .ok_or_else(|| format!("Unknown warning {}", s))
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Allow,
+  Warn,
+  Deny,
+}
+
+impl FromStr for Level {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "allow" => Ok(Self::Allow),
+      "warn" => Ok(Self::Warn),
+      "deny" => Ok(Self::Deny),
+      _ => Err(format!("Unknown warning level {}", s)),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+  pub lint: Lint,
+  pub pos: Option<Pos>,
+  pub message: String,
+}
+
+impl fmt::Display for Warning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(pos) = self.pos {
+      write!(f, "{}:{}: ", pos.from_line, pos.from_column)?;
+    }
+    write!(f, "{} [{}]", self.message, self.lint)
+  }
+}
+
+// The warnings reported, by their level
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+  // The levels of lints, later ones overriding earlier ones
+  levels: Vec<(Lint, Level)>,
+  pub warnings: Vec<Warning>,
+  // The warnings of lints which are denied
+  pub errors: Vec<Warning>,
+}
+
+impl Diagnostics {
+  pub fn new(levels: Vec<(Lint, Level)>) -> Self {
+    Diagnostics { levels, ..Self::default() }
+  }
+
+  // Diagnostics with the levels of the configuration of the process
+  pub fn configured() -> Self { Self::new(config::get().warnings.clone()) }
+
+  pub fn level(&self, lint: Lint) -> Level {
+    self
+      .levels
+      .iter()
+      .rev()
+      .find(|(l, _)| *l == lint)
+      .map_or(Level::Warn, |(_, level)| *level)
+  }
+
+  pub fn report(&mut self, warning: Warning) {
+    match self.level(warning.lint) {
+      Level::Allow => (),
+      Level::Warn => self.warnings.push(warning),
+      Level::Deny => self.errors.push(warning),
+    }
+  }
+
+  pub fn failed(&self) -> bool { !self.errors.is_empty() }
+
+  // Report the warnings of `package`, parsed from `source`, with its
+  // definitions `defs` and the references `refs` in scope at its end
+  pub fn lint(
+    &mut self,
+    source: &str,
+    package: &Package,
+    defs: &Defs,
+    refs: &Refs,
+  ) {
+    let lints = vec![
+      unused_imports(source, package, defs),
+      shadowed_names(package, defs, refs),
+      unannotated_partials(source, package, defs),
+      confusables(source),
+    ];
+    for warning in lints.into_iter().flatten() {
+      self.report(warning);
+    }
+  }
+}
+
+// Each term in `term`, itself first
+fn walk<'a>(term: &'a Term, f: &mut impl FnMut(&'a Term)) {
+  f(term);
+  match term {
+    Term::Lam(_, _, bod)
+    | Term::Slf(_, _, bod)
+    | Term::Dat(_, bod)
+    | Term::Cse(_, bod)
+    | Term::Fst(_, bod)
+    | Term::Snd(_, bod) => walk(bod, f),
+    Term::App(_, xs)
+    | Term::All(_, _, _, xs)
+    | Term::Sig(_, _, xs)
+    | Term::Par(_, xs)
+    | Term::Ann(_, xs) => {
+      walk(&xs.0, f);
+      walk(&xs.1, f);
+    }
+    Term::Let(_, _, _, _, xs) => {
+      walk(&xs.0, f);
+      walk(&xs.1, f);
+      walk(&xs.2, f);
+    }
+    _ => (),
+  }
+}
+
+// The definitions the package declares, by name, tests included
+fn declared<'a>(
+  package: &Package,
+  defs: &'a Defs,
+) -> Vec<(&'a str, &'a Def)> {
+  package
+    .decls
+    .iter()
+    .filter_map(|decl| match decl {
+      Declaration::Defn { defn, .. } | Declaration::Test { test: defn, .. } => {
+        defs.get(defn).map(|def| (def.name.as_str(), def))
+      }
+      Declaration::Open { .. } => None,
+    })
+    .collect()
+}
+
+// The position of the characters of `source` from the byte offset `from` up
+// to `upto`
+fn span(source: &str, from: usize, upto: usize) -> Pos {
+  let at = |offset: usize| {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let start = before.rfind('\n').map_or(0, |n| n + 1);
+    (line as u64, before[start..].chars().count() as u64 + 1)
+  };
+  let (from_line, from_column) = at(from);
+  let (upto_line, upto_column) = at(upto);
+  Pos {
+    from_offset: from as u64,
+    from_line,
+    from_column,
+    upto_offset: upto as u64,
+    upto_line,
+    upto_column,
+  }
+}
+
+// The position of the `open` of the package `name` in `source`, up to the
+// end of its line
+fn open_pos(source: &str, name: &str) -> Option<Pos> {
+  let mut offset = 0;
+  for line in source.split('\n') {
+    let trimmed = line.trim_start();
+    let mut words = trimmed.split_whitespace();
+    if words.next() == Some("open") && words.next() == Some(name) {
+      let from = offset + line.len() - trimmed.len();
+      return Some(span(source, from, offset + line.trim_end().len()));
+    }
+    offset += line.len() + 1;
+  }
+  None
+}
+
+fn unused_imports(
+  source: &str,
+  package: &Package,
+  defs: &Defs,
+) -> Vec<Warning> {
+  let mut used = HashSet::new();
+  for (_, def) in declared(package, defs) {
+    for term in vec![&def.typ_, &def.term] {
+      walk(term, &mut |term| {
+        if let Term::Ref(_, _, link, _) = term {
+          used.insert(*link);
+        }
+      });
+    }
+  }
+  let mut warnings = Vec::new();
+  for decl in &package.decls {
+    if let Declaration::Open { name, with, from, .. } = decl {
+      let refs = match Package::get_link(*from).and_then(|p| p.refs_defs()) {
+        Ok((refs, _)) => refs,
+        Err(_) => continue,
+      };
+      let imported: Vec<Link> = refs
+        .iter()
+        .filter(|(n, _)| {
+          with.as_ref().map_or(true, |ns| ns.contains(*n))
+        })
+        .map(|(_, (link, _))| *link)
+        .collect();
+      if !imported.iter().any(|link| used.contains(link)) {
+        warnings.push(Warning {
+          lint: Lint::UnusedImport,
+          pos: open_pos(source, name),
+          message: format!(
+            "None of the definitions opened from {} are used",
+            name
+          ),
+        });
+      }
+    }
+  }
+  warnings
+}
+
+fn shadowed_names(
+  package: &Package,
+  defs: &Defs,
+  refs: &Refs,
+) -> Vec<Warning> {
+  let mut warnings = Vec::new();
+  for (def_name, def) in declared(package, defs) {
+    // Each name is reported once in a definition, where it is first bound
+    let mut seen = HashSet::new();
+    for term in vec![&def.typ_, &def.term] {
+      walk(term, &mut |term| {
+        let (pos, name) = match term {
+          Term::Lam(pos, name, _)
+          | Term::All(pos, _, name, _)
+          | Term::Slf(pos, name, _)
+          | Term::Sig(pos, name, _)
+          | Term::Let(pos, _, _, name, _) => (pos, name),
+          _ => return,
+        };
+        if name != "_" && refs.contains_key(name) && seen.insert(name) {
+          warnings.push(Warning {
+            lint: Lint::ShadowedName,
+            pos: *pos,
+            message: format!(
+              "The variable {} in {} shadows the definition {}",
+              name, def_name, name
+            ),
+          });
+        }
+      });
+    }
+  }
+  warnings
+}
+
+// The type written in the source of a definition, `name (binders) : type =
+// term`, being what follows the first colon outside of the binders up to the
+// equals sign
+fn written_type(decl: &str) -> Option<&str> {
+  let mut depth = 0i32;
+  let mut colon = None;
+  for (i, c) in decl.char_indices() {
+    match c {
+      '(' | '{' | '[' => depth += 1,
+      ')' | '}' | ']' => depth -= 1,
+      ':' if depth == 0 && colon.is_none() => colon = Some(i + 1),
+      '=' if depth == 0 && colon.is_some() => {
+        return colon.map(|from| decl[from..i].trim());
+      }
+      _ => (),
+    }
+  }
+  None
+}
+
+fn unannotated_partials(
+  source: &str,
+  package: &Package,
+  defs: &Defs,
+) -> Vec<Warning> {
+  let mut warnings = Vec::new();
+  for (name, def) in declared(package, defs) {
+    let pos = match def.pos {
+      Some(pos) => pos,
+      None => continue,
+    };
+    let (from, upto) = (pos.from_offset as usize, pos.upto_offset as usize);
+    let decl = match source.get(from..upto) {
+      Some(decl) => decl,
+      None => continue,
+    };
+    if !decl.starts_with("partial") {
+      continue;
+    }
+    if written_type(decl) == Some("_") {
+      warnings.push(Warning {
+        lint: Lint::UnannotatedPartial,
+        pos: Some(pos),
+        message: format!(
+          "The partial definition {} has no type annotation, so its type is \
+           inferred from a term which may not terminate",
+          name
+        ),
+      });
+    }
+  }
+  warnings
+}
+
+// The characters which look like ASCII letters or spaces, or like nothing
+// at all, and what they look like
+const CONFUSABLES: &[(char, &str)] = &[
+  ('\u{a0}', "a space"),
+  ('\u{200b}', "nothing"),
+  ('\u{200c}', "nothing"),
+  ('\u{200d}', "nothing"),
+  ('\u{2060}', "nothing"),
+  ('\u{feff}', "nothing"),
+  ('\u{391}', "A"),
+  ('\u{392}', "B"),
+  ('\u{395}', "E"),
+  ('\u{396}', "Z"),
+  ('\u{397}', "H"),
+  ('\u{399}', "I"),
+  ('\u{39a}', "K"),
+  ('\u{39c}', "M"),
+  ('\u{39d}', "N"),
+  ('\u{39f}', "O"),
+  ('\u{3a1}', "P"),
+  ('\u{3a4}', "T"),
+  ('\u{3a7}', "X"),
+  ('\u{3bf}', "o"),
+  ('\u{410}', "A"),
+  ('\u{412}', "B"),
+  ('\u{415}', "E"),
+  ('\u{41a}', "K"),
+  ('\u{41c}', "M"),
+  ('\u{41d}', "H"),
+  ('\u{41e}', "O"),
+  ('\u{420}', "P"),
+  ('\u{421}', "C"),
+  ('\u{422}', "T"),
+  ('\u{425}', "X"),
+  ('\u{430}', "a"),
+  ('\u{435}', "e"),
+  ('\u{43e}', "o"),
+  ('\u{440}', "p"),
+  ('\u{441}', "c"),
+  ('\u{443}', "y"),
+  ('\u{445}', "x"),
+  ('\u{456}', "i"),
+];
+
+// The confusable characters in the code of `source`, but not in its
+// comments or text literals, where they may well be meant
+fn confusables(source: &str) -> Vec<Warning> {
+  let mut warnings = Vec::new();
+  let mut offset = 0;
+  for line in source.split('\n') {
+    let mut text = false;
+    let mut prev = None;
+    for (i, c) in line.char_indices() {
+      if c == '"' && prev != Some('\\') {
+        text = !text;
+      }
+      if !text && c == '/' && prev == Some('/') {
+        break;
+      }
+      prev = Some(c);
+      if text {
+        continue;
+      }
+      if let Some((_, like)) = CONFUSABLES.iter().find(|(x, _)| *x == c) {
+        let from = offset + i;
+        warnings.push(Warning {
+          lint: Lint::Confusable,
+          pos: Some(span(source, from, from + c.len_utf8())),
+          message: format!(
+            "The character U+{:04X} looks like {}",
+            u32::from(c),
+            like
+          ),
+        });
+      }
+    }
+    offset += line.len() + 1;
+  }
+  warnings
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::parse::package::{
+    parse_file,
+    PackageEnv,
+  };
+  use std::fs;
+
+  #[test]
+  fn lints() {
+    let dir = std::env::temp_dir().join("yatima-warnings");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+      dir.join("Lib.ya"),
+      "package Lib where\ndef one : #Natural = 1\n",
+    )
+    .unwrap();
+    let source = "package Main where\n\
+                  open Lib\n\
+                  def two : #Natural = 2\n\
+                  def id : ∀ (A: Type) (x: A) -> A = λ A two => two\n\
+                  partial def loop : _ = \"lоop\" // lооp\n";
+    let path = dir.join("Main.ya");
+    fs::write(&path, source).unwrap();
+    let (_, package, defs, refs) =
+      parse_file(PackageEnv::new(path)).unwrap();
+    let mut diagnostics =
+      Diagnostics::new(vec![(Lint::Confusable, Level::Deny)]);
+    diagnostics.lint(source, &package, &defs, &refs);
+    let lints: Vec<Lint> =
+      diagnostics.warnings.iter().map(|w| w.lint).collect();
+    assert_eq!(lints, vec![
+      Lint::UnusedImport,
+      Lint::ShadowedName,
+      Lint::UnannotatedPartial,
+    ]);
+    assert_eq!(
+      diagnostics.warnings[0].to_string(),
+      "2:1: None of the definitions opened from Lib are used [unused-import]"
+    );
+    // The lookalike `о`s are in a text literal and a comment
+    assert!(!diagnostics.failed());
+    let mut diagnostics = Diagnostics::new(vec![
+      (Lint::Confusable, Level::Allow),
+      (Lint::Confusable, Level::Deny),
+    ]);
+    diagnostics.lint("def tw\u{43e} : #Natural = 2", &package, &defs, &refs);
+    assert!(diagnostics.failed());
+    assert_eq!(
+      diagnostics.errors[0].to_string(),
+      "1:7: The character U+043E looks like o [confusable]"
+    );
+  }
+}
@@ -0,0 +1,74 @@
+use crate::{
+  core::{
+    check::infer_term,
+    run::{
+      run,
+      Stats,
+      Strategy,
+    },
+  },
+  parse::{
+    package::parse_input,
+    prelude::prelude,
+  },
+  print::{
+    pretty,
+    Options,
+  },
+  term::{
+    Defs,
+    Term,
+  },
+};
+
+use wasm_bindgen::prelude::*;
+
+// Exports for JavaScript, for a playground in the browser, with the crate
+// built for `wasm32-unknown-unknown` without the `native` feature. An
+// expression is parsed, run or checked in the scope of the prelude, and is
+// printed back laid out to `width` columns, or else the error it has is
+// thrown as a string.
+
+// The expression, elaborated, and the definitions it is in the scope of
+fn parsed(expr: &str) -> Result<(Defs, Term), JsValue> {
+  let (defs, refs) = prelude();
+  let term =
+    parse_input(&defs, &refs, expr).map_err(|e| JsValue::from_str(&e))?;
+  Ok((defs, term))
+}
+
+fn print(term: &Term, width: usize) -> String {
+  pretty(term, &Options { width, ..Options::message() })
+}
+
+// The expression, as it is parsed and elaborated
+#[wasm_bindgen]
+pub fn parse(expr: &str, width: usize) -> Result<String, JsValue> {
+  let (_, term) = parsed(expr)?;
+  Ok(print(&term, width))
+}
+
+// The normal form of the expression, reduced lazily with at most `fuel`
+// reductions, if it is given, so that a term which does not terminate does
+// not hang the page
+#[wasm_bindgen]
+pub fn eval(
+  expr: &str,
+  width: usize,
+  fuel: Option<u32>,
+) -> Result<String, JsValue> {
+  let (defs, term) = parsed(expr)?;
+  let mut stats = Stats::new(fuel.map(u64::from));
+  run(&defs, &term, Strategy::Lazy, &mut stats)
+    .map(|norm| print(&norm, width))
+    .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+// The type of the expression
+#[wasm_bindgen]
+pub fn check(expr: &str, width: usize) -> Result<String, JsValue> {
+  let (defs, term) = parsed(expr)?;
+  infer_term(&defs, &term)
+    .map(|typ| print(&typ, width))
+    .map_err(|e| JsValue::from_str(&e.to_string()))
+}
@@ -0,0 +1,219 @@
+use crate::{
+  package::{
+    Declaration,
+    Package,
+  },
+  parse::package::import_path,
+};
+
+use std::{
+  collections::{
+    BTreeMap,
+    HashMap,
+  },
+  fs,
+  path::{
+    Path,
+    PathBuf,
+  },
+  thread,
+  time::{
+    Duration,
+    SystemTime,
+  },
+};
+
+// Watching a package for changes, so that what depends on it can be rerun
+// each time it is saved. The files of a package and of the packages it
+// opens are polled for changes to their modification times or sizes, which
+// needs nothing from the platform. What is rerun is answered from the query
+// database where it can be, so only what changed is recomputed, and only the
+// outcomes which differ from the previous run are reported.
+
+// The modification time and size of a file, if it can be read
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+  let meta = fs::metadata(path).ok()?;
+  Some((meta.modified().ok()?, meta.len()))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Watcher {
+  stamps: HashMap<PathBuf, Stamp>,
+}
+
+impl Watcher {
+  pub fn new() -> Self { Self::default() }
+
+  // Watch `paths` from now on, rather than the files watched so far
+  pub fn watch(&mut self, paths: Vec<PathBuf>) {
+    let mut stamps = HashMap::new();
+    for path in paths {
+      let stamp =
+        self.stamps.get(&path).cloned().unwrap_or_else(|| stamp(&path));
+      stamps.insert(path, stamp);
+    }
+    self.stamps = stamps;
+  }
+
+  // The watched files which have changed since they were last looked at
+  pub fn changed(&mut self) -> Vec<PathBuf> {
+    let mut changed = Vec::new();
+    for (path, old) in self.stamps.iter_mut() {
+      let new = stamp(path);
+      if new != *old {
+        *old = new;
+        changed.push(path.clone());
+      }
+    }
+    changed.sort();
+    changed
+  }
+
+  // Wait until a watched file changes, looking every `interval`
+  pub fn wait(&mut self, interval: Duration) -> Vec<PathBuf> {
+    loop {
+      let changed = self.changed();
+      if !changed.is_empty() {
+        return changed;
+      }
+      thread::sleep(interval);
+    }
+  }
+}
+
+// The files of the package parsed from `path` and of the packages it opens,
+// transitively, which are read from files
+pub fn package_files(path: &Path, package: &Package) -> Vec<PathBuf> {
+  fn go(path: &Path, package: &Package, files: &mut Vec<PathBuf>) {
+    if files.iter().any(|file| file == path) {
+      return;
+    }
+    files.push(path.to_path_buf());
+    for decl in &package.decls {
+      if let Declaration::Open { name, from, .. } = decl {
+        let path = import_path(path, name);
+        if let (true, Ok(pack)) = (path.exists(), Package::get_link(*from)) {
+          go(&path, &pack, files);
+        }
+      }
+    }
+  }
+  let mut files = Vec::new();
+  go(path, package, &mut files);
+  files
+}
+
+// The outcome of each thing rerun, by name: what it gave, or why it failed
+pub type Outcomes = BTreeMap<String, Result<String, String>>;
+
+// How the outcome of something changed between two runs
+#[derive(Clone, Debug, PartialEq)]
+pub enum Delta {
+  Added(String, Result<String, String>),
+  Changed(String, Result<String, String>),
+  Removed(String),
+}
+
+// The outcomes of `after` which differ from those of `before`, in order of
+// name
+pub fn deltas(before: &Outcomes, after: &Outcomes) -> Vec<Delta> {
+  let mut deltas = Vec::new();
+  for (name, res) in after {
+    match before.get(name) {
+      None => deltas.push(Delta::Added(name.clone(), res.clone())),
+      Some(old) if old != res => {
+        deltas.push(Delta::Changed(name.clone(), res.clone()))
+      }
+      Some(_) => (),
+    }
+  }
+  for name in before.keys() {
+    if !after.contains_key(name) {
+      deltas.push(Delta::Removed(name.clone()));
+    }
+  }
+  deltas.sort_by(|a, b| a.name().cmp(b.name()));
+  deltas
+}
+
+impl Delta {
+  pub fn name(&self) -> &str {
+    match self {
+      Self::Added(name, _) | Self::Changed(name, _) | Self::Removed(name) => {
+        name
+      }
+    }
+  }
+
+  // The delta as a line of a report, in ANSI colors if `color`
+  pub fn paint(&self, color: bool) -> String {
+    let (mark, code, name, detail) = match self {
+      Self::Added(name, Ok(out)) | Self::Changed(name, Ok(out)) => {
+        ("✓", "32", name, out.as_str())
+      }
+      Self::Added(name, Err(e)) | Self::Changed(name, Err(e)) => {
+        ("✗", "31", name, e.as_str())
+      }
+      Self::Removed(name) => ("-", "33", name, ""),
+    };
+    let mark = if color {
+      format!("\x1b[{}m{}\x1b[0m", code, mark)
+    }
+    else {
+      String::from(mark)
+    };
+    if detail.is_empty() {
+      format!("{} {}", mark, name)
+    }
+    else {
+      format!("{} {}: {}", mark, name, detail)
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use std::env;
+
+  #[test]
+  fn watches() {
+    let dir = env::temp_dir().join("yatima-watch");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("Watched.ya");
+    fs::write(&path, "package Watched where\n").unwrap();
+    let mut watcher = Watcher::new();
+    watcher.watch(vec![path.clone()]);
+    assert!(watcher.changed().is_empty());
+    fs::write(&path, "package Watched where\ndef one : #Natural = 1\n")
+      .unwrap();
+    assert_eq!(watcher.changed(), vec![path.clone()]);
+    assert!(watcher.changed().is_empty());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(watcher.wait(Duration::from_millis(1)), vec![path]);
+  }
+
+  #[test]
+  fn reports_deltas() {
+    let outcomes = |xs: &[(&str, Result<&str, &str>)]| -> Outcomes {
+      xs.iter()
+        .map(|(name, res)| {
+          (String::from(*name), res.map(String::from).map_err(String::from))
+        })
+        .collect()
+    };
+    let before = outcomes(&[("a", Ok("")), ("b", Ok("")), ("c", Ok(""))]);
+    let after = outcomes(&[("a", Ok("")), ("b", Err("no")), ("d", Ok("1"))]);
+    let deltas = deltas(&before, &after);
+    assert_eq!(deltas, vec![
+      Delta::Changed(String::from("b"), Err(String::from("no"))),
+      Delta::Removed(String::from("c")),
+      Delta::Added(String::from("d"), Ok(String::from("1"))),
+    ]);
+    let lines: Vec<String> = deltas.iter().map(|d| d.paint(false)).collect();
+    assert_eq!(lines, vec!["✗ b: no", "- c", "✓ d: 1"]);
+    assert_eq!(deltas[2].paint(true), "\x1b[32m✓\x1b[0m d: 1");
+  }
+}